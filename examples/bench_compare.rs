@@ -0,0 +1,165 @@
+// Identical workloads through this crate and other OSM tools, as a table
+//
+// Usage: cargo run --release --features bench-compare --example bench-compare -- \
+//            [--runs <n>] [--external <name>=<command>]... [file.osm.pbf]
+//
+// Without a file, a synthetic street grid is generated into a temporary file.
+// Each workload runs `--runs` times (default 3) per tool and the fastest run
+// is reported, with the time relative to this crate and whether the result
+// agrees with it.
+//
+// osmium is run when it is on the PATH. Running osmpbfreader-rs in-process, as
+// first asked for, is declined: the crate isn't available to this build, so it
+// can't be a dependency, not even behind the `bench-compare` feature. Wrap it
+// (or anything else) in a small binary printing the workload's count and pass
+// it with `--external` instead. In the command, `{file}` is
+// replaced by the input path and `{workload}` by the workload name ("count"
+// or "highways"), e.g. `--external 'osmpbfreader=./count-osmpbfreader {workload} {file}'`.
+//
+// Timings include process start-up for external tools and, for osmium's
+// tags-filter, writing OPL to a pipe, so they favour this crate slightly on
+// tiny inputs; use a regional extract for meaningful numbers.
+
+use osm_pbf::synthetic::PlanetBuilder;
+use osm_pbf::{ElementFilter, Reader};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Workloads as (name, description)
+const WORKLOADS: &[(&str, &str)] = &[
+    ("count", "nodes + ways + relations"),
+    ("highways", "ways tagged highway"),
+];
+
+/// One tool's answer to a workload
+struct Measurement {
+    tool: String,
+    best: Duration,
+    result: Result<u64>,
+}
+
+fn crate_workload(path: &Path, workload: &str) -> Result<u64> {
+    let mut reader = Reader::new(File::open(path)?)?;
+    let stats = match workload {
+        "count" => reader.count_filtered(&ElementFilter::all())?,
+        _ => reader.count_filtered(&ElementFilter::ways_only(false).with_tag_key("highway".to_string()))?,
+    };
+    Ok(stats.nodes_processed + stats.ways_processed + stats.relations_processed)
+}
+
+fn osmium_workload(path: &Path, workload: &str) -> Result<u64> {
+    let output = match workload {
+        "count" => Command::new("osmium").args(["fileinfo", "-e", "-j"]).arg(path).output()?,
+        _ => Command::new("osmium").args(["tags-filter", "-R", "-f", "opl", "-o", "-"]).arg(path).arg("w/highway").output()?,
+    };
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    match workload {
+        "count" => {
+            let info: serde_json::Value = serde_json::from_str(&stdout)?;
+            let count = |kind: &str| info["data"]["count"][kind].as_u64().unwrap_or(0);
+            Ok(count("nodes") + count("ways") + count("relations"))
+        }
+        _ => Ok(stdout.lines().filter(|line| !line.is_empty()).count() as u64),
+    }
+}
+
+fn external_workload(command: &str, path: &Path, workload: &str) -> Result<u64> {
+    let command = command.replace("{file}", &path.to_string_lossy()).replace("{workload}", workload);
+    let output = Command::new("sh").arg("-c").arg(&command).output()?;
+    if !output.status.success() {
+        return Err(format!("`{command}` failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    let first = stdout.split_whitespace().next().ok_or("no output")?;
+    Ok(first.parse()?)
+}
+
+/// Fastest of `runs` runs, and the result of the last one
+fn measure(tool: &str, runs: usize, mut run: impl FnMut() -> Result<u64>) -> Measurement {
+    let mut best = Duration::MAX;
+    let mut result = Err("not run".into());
+    for _ in 0..runs {
+        let start = Instant::now();
+        result = run();
+        best = best.min(start.elapsed());
+        if result.is_err() {
+            break;
+        }
+    }
+    Measurement { tool: tool.to_string(), best, result }
+}
+
+fn osmium_available() -> bool {
+    Command::new("osmium").arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+fn print_table(workload: &str, description: &str, measurements: &[Measurement]) {
+    println!("\n{workload}: {description}");
+    println!("  {:<16} {:>12} {:>10} {:>14}", "tool", "best", "relative", "result");
+    let baseline = &measurements[0];
+    for m in measurements {
+        let relative = m.best.as_secs_f64() / baseline.best.as_secs_f64().max(f64::EPSILON);
+        let result = match (&m.result, &baseline.result) {
+            (Ok(value), Ok(expected)) if value == expected => value.to_string(),
+            (Ok(value), _) => format!("{value} (differs)"),
+            (Err(e), _) => format!("error: {e}"),
+        };
+        println!("  {:<16} {:>12.2?} {:>9.2}x {:>14}", m.tool, m.best, relative, result);
+    }
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (mut runs, mut externals, mut input) = (3usize, Vec::new(), None::<PathBuf>);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" => runs = args.next().ok_or("--runs needs a value")?.parse()?,
+            "--external" => {
+                let spec = args.next().ok_or("--external needs a value")?;
+                let (name, command) = spec.split_once('=').ok_or("--external needs <name>=<command>")?;
+                externals.push((name.to_string(), command.to_string()));
+            }
+            _ if input.is_none() && !arg.starts_with("--") => input = Some(arg.into()),
+            _ => {
+                eprintln!("usage: bench-compare [--runs <n>] [--external <name>=<command>]... [file.osm.pbf]");
+                std::process::exit(2);
+            }
+        }
+    }
+    let runs = runs.max(1);
+
+    let generated = match &input {
+        Some(_) => None,
+        None => {
+            let file = tempfile::Builder::new().suffix(".osm.pbf").tempfile()?;
+            let stats = PlanetBuilder::new(7).grid_size(200).relation_count(50).write_to(file.reopen()?)?;
+            eprintln!("generated {} nodes, {} ways, {} relations", stats.nodes, stats.ways, stats.relations);
+            Some(file)
+        }
+    };
+    let path = input.as_deref().or_else(|| generated.as_ref().map(|file| file.path())).expect("an input file");
+
+    let osmium = osmium_available();
+    if !osmium {
+        eprintln!("osmium not found on PATH, skipping it");
+    }
+
+    for (workload, description) in WORKLOADS {
+        let mut measurements = vec![measure("osm-pbf", runs, || crate_workload(path, workload))];
+        if osmium {
+            measurements.push(measure("osmium", runs, || osmium_workload(path, workload)));
+        }
+        for (name, command) in &externals {
+            measurements.push(measure(name, runs, || external_workload(command, path, workload)));
+        }
+        print_table(workload, description, &measurements);
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+// Print the execution plan of a filter against a PBF file
+//
+// Usage: cargo run --example explain -- <file.osm.pbf> [nodes|ways|relations] [min_id max_id]
+
+use osm_pbf::{ElementFilter, NodeId, Reader, RelationId, WayId};
+use std::fs::File;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: explain <file.osm.pbf> [nodes|ways|relations] [min_id max_id]");
+        std::process::exit(2);
+    };
+
+    let mut filter = match args.get(1).map(String::as_str) {
+        Some("nodes") => ElementFilter::nodes_only(),
+        Some("ways") => ElementFilter::ways_only(false),
+        Some("relations") => ElementFilter {
+            include_nodes: false,
+            include_ways: false,
+            ..ElementFilter::all()
+        },
+        _ => ElementFilter::all(),
+    };
+    // Ids are ranges of the chosen element type, node ids without one
+    if let (Some(min), Some(max)) = (args.get(2), args.get(3)) {
+        filter = match args.get(1).map(String::as_str) {
+            Some("ways") => filter.with_id_range(min.parse::<WayId>()?, max.parse::<WayId>()?),
+            Some("relations") => filter.with_id_range(min.parse::<RelationId>()?, max.parse::<RelationId>()?),
+            _ => filter.with_id_range(min.parse::<NodeId>()?, max.parse::<NodeId>()?),
+        };
+    }
+
+    let reader = Reader::new(File::open(path)?)?;
+    print!("{}", reader.explain(&filter));
+    Ok(())
+}
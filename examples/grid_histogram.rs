@@ -0,0 +1,31 @@
+// Count the elements carrying a tag key per web mercator grid cell
+//
+// Usage: cargo run --example grid_histogram -- <file.osm.pbf> <key> [zoom] [limit]
+
+use osm_pbf::Reader;
+use osm_pbf::analysis::grid_histogram;
+use std::fs::File;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (Some(path), Some(key)) = (args.first(), args.get(1)) else {
+        eprintln!("usage: grid_histogram <file.osm.pbf> <key> [zoom] [limit]");
+        std::process::exit(2);
+    };
+    let zoom = args.get(2).map(|z| z.parse()).transpose()?.unwrap_or(10);
+    let limit = args.get(3).map(|l| l.parse()).transpose()?.unwrap_or(20);
+
+    let mut reader = Reader::new(File::open(path)?)?;
+    let histogram = grid_histogram(&mut reader, key, zoom)?;
+
+    for (cell, count) in histogram.sorted().into_iter().take(limit) {
+        println!("{zoom}/{}/{}\t{count}", cell.x, cell.y);
+    }
+    eprintln!(
+        "{} elements with {key} in {} cells ({} ways/relations without a location)",
+        histogram.total(),
+        histogram.cells.len(),
+        histogram.unlocated
+    );
+    Ok(())
+}
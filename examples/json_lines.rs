@@ -0,0 +1,25 @@
+// Print the elements of a file as JSON lines (schema v1), or the schema itself
+//
+// Usage: cargo run --features json --example json_lines -- <file.osm.pbf>
+//        cargo run --features json --example json_lines -- --schema
+
+use osm_pbf::{write_json_lines, ElementFilter, Reader, JSON_SCHEMA_V1};
+use std::fs::File;
+use std::io::BufWriter;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["--schema"] => print!("{JSON_SCHEMA_V1}"),
+        [path] => {
+            let mut reader = Reader::new(File::open(path)?)?;
+            let stats = write_json_lines(&mut reader, &ElementFilter::all(), BufWriter::new(std::io::stdout().lock()))?;
+            eprintln!("{} elements", stats.elements_processed);
+        }
+        _ => {
+            eprintln!("usage: json_lines <file.osm.pbf> | json_lines --schema");
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
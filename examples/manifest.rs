@@ -0,0 +1,39 @@
+// Write a per-blob SHA-256 manifest of a file, or verify a download against one
+//
+// Usage: cargo run --example manifest -- <file.osm.pbf>                 (manifest to stdout)
+//        cargo run --example manifest -- verify <file.osm.pbf> <manifest>
+
+use osm_pbf::{IndexedReader, Manifest};
+use std::fs::File;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [path] => {
+            let mut reader = IndexedReader::new(File::open(path)?)?;
+            print!("{}", Manifest::from_reader(&mut reader)?);
+        }
+        ["verify", path, manifest] => {
+            let manifest: Manifest = std::fs::read_to_string(manifest)?.parse()?;
+            let verification = manifest.verify(&mut File::open(path)?)?;
+            eprintln!(
+                "{} valid, {} corrupt, {} missing",
+                verification.valid,
+                verification.corrupt.len(),
+                verification.missing.len()
+            );
+            for range in verification.refetch_ranges(&manifest) {
+                // Inclusive end, as in an HTTP Range header
+                println!("bytes={}-{}", range.start, range.end - 1);
+            }
+            if !verification.is_valid() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("usage: manifest <file.osm.pbf> | manifest verify <file.osm.pbf> <manifest>");
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
@@ -4,7 +4,7 @@
 
 #[cfg(feature = "mmap")]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    use osm_pbf::{MmapBlobReader, ParallelMmapBlobReader, ElementFilter};
+    use osm_pbf::{MmapBlobReader, ParallelMmapBlobReader, ElementFilter, NodeId};
     use std::io::Write;
     use tempfile::NamedTempFile;
     use rayon::prelude::*;
@@ -89,13 +89,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n6. Advanced Raw Access:");
     if reader.file_size() > 0 {
         // Get first 10 bytes as raw slice (zero-copy)
-        let slice = reader.get_raw_slice(0, 10.min(reader.file_size() as usize))?;
+        let slice = reader.get_raw_slice(0, 10.min(reader.file_size()))?;
         println!("   - First 10 bytes: {:?}", slice);
     }
     
     // Example 7: ID range lookup (for indexed access)
     println!("\n7. ID Range Lookup:");
-    let blob_indices = reader.find_blobs_for_id_range(1000, 2000);
+    let blob_indices = reader.find_blobs_for_id_range(NodeId(1000), NodeId(2000));
     println!("   - Blobs potentially containing IDs 1000-2000: {:?}", blob_indices);
     
     println!("\n=== Performance Benefits ===");
@@ -0,0 +1,40 @@
+// Way node-count and length distributions per highway class, as CSV
+//
+// Usage: cargo run --example way_stats -- <file.osm.pbf> [class key]
+//
+// Node locations are kept in memory, so this suits extracts rather than the planet.
+
+use osm_pbf::{NodeId, Reader};
+use osm_pbf::analysis::way_distributions;
+use std::collections::HashMap;
+use std::fs::File;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: way_stats <file.osm.pbf> [class key]");
+        std::process::exit(2);
+    };
+    let class_key = args.get(1).map_or("highway", String::as_str);
+
+    let mut reader = Reader::new(File::open(path)?)?;
+    let mut locations = HashMap::new();
+    reader.nodes(|node| {
+        locations.insert(node.id, node.location);
+        Ok(())
+    })?;
+
+    let lookup = |id: i64| locations.get(&NodeId(id)).copied();
+    let distributions = way_distributions(&mut reader, class_key, Some(&lookup))?;
+    distributions.write_csv(std::io::stdout().lock())?;
+
+    for (class, stats) in &distributions.classes {
+        eprintln!(
+            "{class}: {} ways, median {} nodes, median {:.0} m",
+            stats.node_counts.count,
+            stats.node_counts.quantile(0.5).unwrap_or(0.0),
+            stats.lengths.quantile(0.5).unwrap_or(0.0)
+        );
+    }
+    Ok(())
+}
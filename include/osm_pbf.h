@@ -0,0 +1,91 @@
+/* C ABI of osm-pbf, built with the `ffi` feature (see src/ffi.rs) */
+#ifndef OSM_PBF_H
+#define OSM_PBF_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define OSM_PBF_ABI_VERSION 1
+
+#define OSM_PBF_NODES 1
+#define OSM_PBF_WAYS 2
+#define OSM_PBF_RELATIONS 4
+#define OSM_PBF_CHANGESETS 8
+
+#define OSM_PBF_OK 0
+#define OSM_PBF_STOPPED 1
+#define OSM_PBF_ERROR (-1)
+
+typedef struct osm_pbf_reader osm_pbf_reader;
+
+/*
+ * Elements of one kind from one block, as parallel arrays.
+ *
+ * Element i is ids[i], with:
+ * - lats[i], lons[i]: location in nanodegrees (nodes; NULL otherwise);
+ * - tags tag_keys[j] = tag_vals[j] for tag_offsets[i] <= j < tag_offsets[i + 1],
+ *   both string indices;
+ * - refs[j] for ref_offsets[i] <= j < ref_offsets[i + 1]: absolute node ids of
+ *   a way, or member ids of a relation with member_types[j] (0 node, 1 way,
+ *   2 relation) and role string member_roles[j]. ref_offsets and refs are
+ *   NULL for nodes and changesets, member_types and member_roles for all but
+ *   relations.
+ *
+ * String k is the string_offsets[k + 1] - string_offsets[k] bytes of UTF-8 at
+ * string_arena + string_offsets[k], not NUL-terminated. The table is the
+ * block's, shared by all batches of the block; string 0 is empty.
+ *
+ * Lifetime: the batch and every array it points to belong to the reader and
+ * are valid only until the callback returns. Copy what you keep; don't write
+ * through the pointers.
+ */
+typedef struct osm_pbf_batch {
+    size_t struct_size;
+    uint32_t kind;
+    uint64_t blob_index;
+    size_t len;
+    const int64_t *ids;
+    const int64_t *lats;
+    const int64_t *lons;
+    const uint32_t *tag_offsets;
+    const uint32_t *tag_keys;
+    const uint32_t *tag_vals;
+    const uint32_t *ref_offsets;
+    const int64_t *refs;
+    const uint8_t *member_types;
+    const uint32_t *member_roles;
+    size_t string_count;
+    const uint32_t *string_offsets;
+    const uint8_t *string_arena;
+} osm_pbf_batch;
+
+/* Return 0 to continue, anything else to stop; must not unwind (C++ exceptions) */
+typedef int32_t (*osm_pbf_batch_callback)(const osm_pbf_batch *batch, void *user_data);
+
+uint32_t osm_pbf_abi_version(void);
+
+/* NULL on failure, see osm_pbf_last_error */
+osm_pbf_reader *osm_pbf_open(const char *path);
+
+/*
+ * Call callback with each batch of the elements of kinds (an or of
+ * OSM_PBF_NODES, ...), in file order, on the calling thread. Each call reads
+ * the file from the start; a reader must not be used by two threads at once.
+ */
+int32_t osm_pbf_for_each_batch(osm_pbf_reader *reader, uint32_t kinds, osm_pbf_batch_callback callback, void *user_data);
+
+/* NULL is ignored */
+void osm_pbf_close(osm_pbf_reader *reader);
+
+/* Message of the last failed call on this thread, or NULL; valid until the next failing call */
+const char *osm_pbf_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
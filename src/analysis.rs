@@ -0,0 +1,431 @@
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::PI;
+use std::io::{Read, Seek, Write};
+use std::ops::ControlFlow;
+
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::lat_lon::LatLon;
+use crate::io::blob::{BlobError, Result};
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, ParallelConfig, Reader};
+
+/// Highest zoom level accepted by `grid_histogram`
+pub const MAX_GRID_ZOOM: u8 = 30;
+
+/// Latitude limit of the web mercator projection, in degrees
+const MAX_MERCATOR_LAT: f64 = 85.051_128_779_806_59;
+
+/// Web mercator (slippy map) grid cell at some zoom level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GridCell {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl GridCell {
+    /// The cell containing a location at `zoom`
+    ///
+    /// Latitudes beyond the projection limit fall into the top or bottom row.
+    pub fn containing(location: LatLon, zoom: u8) -> Self {
+        let (lat, lon) = location.to_degrees();
+        let n = (1u64 << zoom) as f64;
+        let lat = lat.clamp(-MAX_MERCATOR_LAT, MAX_MERCATOR_LAT).to_radians();
+
+        let x = (lon + 180.0) / 360.0 * n;
+        let y = (1.0 - lat.tan().asinh() / PI) / 2.0 * n;
+        let clamp = |v: f64| v.clamp(0.0, n - 1.0) as u32;
+        Self { x: clamp(x), y: clamp(y) }
+    }
+
+    /// Extent of the cell at `zoom`
+    pub fn bounds(self, zoom: u8) -> BoundingBox {
+        let n = (1u64 << zoom) as f64;
+        let lon = |x: f64| x / n * 360.0 - 180.0;
+        let lat = |y: f64| (PI * (1.0 - 2.0 * y / n)).sinh().atan().to_degrees();
+
+        let mut bbox = BoundingBox::from_point(corner(lat(self.y as f64), lon(self.x as f64)));
+        bbox.extend(corner(lat(self.y as f64 + 1.0), lon(self.x as f64 + 1.0)));
+        bbox
+    }
+}
+
+fn corner(lat: f64, lon: f64) -> LatLon {
+    LatLon::try_from_degrees(lat, lon).expect("grid cell corners lie within the projection")
+}
+
+/// Counts of elements carrying a tag key, per web mercator grid cell
+///
+/// Only cells with at least one element are stored, so memory grows with the
+/// covered area rather than with the file size or the zoom level.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GridHistogram {
+    pub zoom: u8,
+    pub cells: HashMap<GridCell, u64>,
+    /// Ways and relations carrying the key; they have no location of their own
+    pub unlocated: u64,
+}
+
+impl GridHistogram {
+    /// Create an empty histogram for `zoom`
+    pub fn new(zoom: u8) -> Self {
+        Self { zoom, ..Default::default() }
+    }
+
+    /// Count one element with the key at `location`
+    pub fn add(&mut self, location: LatLon) {
+        *self.cells.entry(GridCell::containing(location, self.zoom)).or_insert(0) += 1;
+    }
+
+    /// Total number of elements counted, located or not
+    pub fn total(&self) -> u64 {
+        self.cells.values().sum::<u64>() + self.unlocated
+    }
+
+    /// Cells ordered by descending count, ties by cell
+    pub fn sorted(&self) -> Vec<(GridCell, u64)> {
+        let mut cells: Vec<_> = self.cells.iter().map(|(cell, count)| (*cell, *count)).collect();
+        cells.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        cells
+    }
+
+    /// Fold another histogram of the same zoom into this one
+    pub fn merge(&mut self, other: &GridHistogram) {
+        debug_assert_eq!(self.zoom, other.zoom);
+        for (cell, count) in &other.cells {
+            *self.cells.entry(*cell).or_insert(0) += count;
+        }
+        self.unlocated += other.unlocated;
+    }
+}
+
+/// Count the elements tagged with `key` per web mercator grid cell at `zoom`
+///
+/// Blobs are decoded in parallel and counted as they arrive, so only one chunk
+/// of blobs and the histogram itself are held in memory. Nodes are placed by
+/// their location; ways and relations carrying the key are counted in
+/// `unlocated`, since placing them would require every node location.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::Reader;
+/// use osm_pbf::analysis::grid_histogram;
+/// use std::fs::File;
+///
+/// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+/// let histogram = grid_histogram(&mut reader, "amenity", 12)?;
+/// for (cell, count) in histogram.sorted().iter().take(10) {
+///     println!("12/{}/{}: {count}", cell.x, cell.y);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn grid_histogram<R: Read + Seek>(reader: &mut Reader<R>, key: &str, zoom: u8) -> Result<GridHistogram> {
+    if zoom > MAX_GRID_ZOOM {
+        return Err(BlobError::InvalidFormat(format!("Zoom {zoom} exceeds the maximum of {MAX_GRID_ZOOM}")));
+    }
+
+    let mut histogram = GridHistogram::new(zoom);
+    reader.par_decode(&ParallelConfig::default(), |_stats, _blob_index, _size, decoded| {
+        let (strings, elements) = decoded?;
+        // Tags refer to the blob's own string table; skip blobs without the key
+        let Some(key_index) = strings.s.iter().skip(1).position(|s| s == key).map(|i| i as u32 + 1) else {
+            return Ok(ControlFlow::Continue(()));
+        };
+
+        for element in &elements {
+            match element {
+                OsmElement::Node(node) if node.keys.contains(&key_index) => histogram.add(node.location),
+                OsmElement::Way(way) if way.keys.contains(&key_index) => histogram.unlocated += 1,
+                OsmElement::Relation(relation) if relation.keys.contains(&key_index) => histogram.unlocated += 1,
+                _ => {}
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    Ok(histogram)
+}
+
+/// Distribution of non-negative values in power-of-two buckets
+///
+/// Bucket 0 holds values below 2, bucket `k` values in `[2^k, 2^(k+1))`, so a
+/// distribution takes a few hundred bytes however many values it counts.
+/// Quantiles are answered at bucket resolution.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct Distribution {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub buckets: Vec<u64>,
+}
+
+impl Distribution {
+    /// Count one value; negative values count as 0
+    pub fn add(&mut self, value: f64) {
+        let value = value.max(0.0);
+        if self.count == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.sum += value;
+
+        let bucket = if value < 2.0 { 0 } else { value.log2().floor() as usize };
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// Value range `[start, end)` of a bucket
+    pub fn bucket_range(bucket: usize) -> (f64, f64) {
+        let end = 2f64.powi(bucket as i32 + 1);
+        (if bucket == 0 { 0.0 } else { end / 2.0 }, end)
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile (0 to 1), capped at `max`
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::bucket_range(bucket).1.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Fold another distribution into this one
+    pub fn merge(&mut self, other: &Distribution) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 { other.min } else { self.min.min(other.min) };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (bucket, count) in other.buckets.iter().enumerate() {
+            self.buckets[bucket] += count;
+        }
+    }
+}
+
+/// Node counts and lengths of the ways of one class
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct WayClassStats {
+    pub node_counts: Distribution,
+    /// Lengths in meters of the ways whose nodes all have a location
+    pub lengths: Distribution,
+    /// Ways left out of `lengths` for a missing node location
+    pub missing_locations: u64,
+}
+
+/// Way distributions per value of a class tag, from `way_distributions`
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct WayDistributions {
+    /// Tag key the ways are classified by, e.g. `highway`
+    pub class_key: String,
+    pub classes: BTreeMap<String, WayClassStats>,
+}
+
+impl WayDistributions {
+    /// Write the buckets as CSV: `class,metric,bucket_start,bucket_end,count`
+    ///
+    /// `metric` is `nodes` or `length_m`; empty buckets are left out.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "class,metric,bucket_start,bucket_end,count")?;
+        for (class, stats) in &self.classes {
+            for (metric, distribution) in [("nodes", &stats.node_counts), ("length_m", &stats.lengths)] {
+                for (bucket, count) in distribution.buckets.iter().enumerate().filter(|(_, count)| **count > 0) {
+                    let (start, end) = Distribution::bucket_range(bucket);
+                    writeln!(writer, "{},{metric},{start},{end},{count}", csv_field(class))?;
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the distributions as one JSON document
+    #[cfg(feature = "json")]
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self).map_err(|e| BlobError::Io(e.into()))
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Distributions of way node counts and lengths per value of `class_key`
+///
+/// Only ways carrying `class_key` are counted. Lengths need the location of
+/// every node of a way: pass a lookup such as a `HashMap` built in an earlier
+/// pass or a `SparseLocationStore`, or `None` to collect node counts only.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::Reader;
+/// use osm_pbf::analysis::way_distributions;
+/// use std::fs::File;
+///
+/// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+/// let distributions = way_distributions(&mut reader, "highway", None)?;
+/// distributions.write_csv(std::io::stdout().lock())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn way_distributions<R: Read + Seek>(
+    reader: &mut Reader<R>,
+    class_key: &str,
+    node_locations: Option<&dyn Fn(i64) -> Option<LatLon>>,
+) -> Result<WayDistributions> {
+    let mut distributions = WayDistributions { class_key: class_key.to_string(), classes: BTreeMap::new() };
+    let filter = ElementFilter::ways_only(false).with_tag_key(class_key.to_string());
+
+    reader.for_each_filtered_with_strings(&filter, |element, strings| {
+        let OsmElement::Way(way) = element else {
+            return Ok(());
+        };
+        let Some(class) = way.tags(strings).get(class_key) else {
+            return Ok(());
+        };
+        let stats = distributions.classes.entry(class.to_string()).or_default();
+        stats.node_counts.add(way.refs.len() as f64);
+
+        let Some(locate) = node_locations else {
+            return Ok(());
+        };
+        let mut id = 0i64;
+        let mut previous: Option<LatLon> = None;
+        let mut length = 0.0;
+        for delta in &way.refs {
+            id = id.wrapping_add(*delta);
+            let Some(location) = locate(id) else {
+                stats.missing_locations += 1;
+                return Ok(());
+            };
+            length += previous.map_or(0.0, |previous| previous.distance_to(location));
+            previous = Some(location);
+        }
+        stats.lengths.add(length);
+        Ok(())
+    })?;
+
+    Ok(distributions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::NodeId;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn location(lat: f64, lon: f64) -> LatLon {
+        LatLon::try_from_degrees(lat, lon).unwrap()
+    }
+
+    #[test]
+    fn test_cell_math() {
+        assert_eq!(GridCell::containing(location(0.0, 0.0), 0), GridCell { x: 0, y: 0 });
+        assert_eq!(GridCell::containing(location(10.0, 10.0), 1), GridCell { x: 1, y: 0 });
+        assert_eq!(GridCell::containing(location(-10.0, -10.0), 1), GridCell { x: 0, y: 1 });
+        assert_eq!(GridCell::containing(location(90.0, 180.0), 2), GridCell { x: 3, y: 0 });
+
+        let cell = GridCell::containing(location(48.8566, 2.3522), 10);
+        assert_eq!(cell, GridCell { x: 518, y: 352 });
+        assert!(cell.bounds(10).contains(location(48.8566, 2.3522)));
+    }
+
+    #[test]
+    fn test_histogram_counts_and_merge() {
+        let mut histogram = GridHistogram::new(1);
+        histogram.add(location(10.0, 10.0));
+        histogram.add(location(20.0, 20.0));
+        histogram.add(location(-10.0, -10.0));
+
+        let mut other = GridHistogram::new(1);
+        other.add(location(-5.0, -5.0));
+        other.unlocated = 2;
+        histogram.merge(&other);
+
+        assert_eq!(histogram.total(), 6);
+        assert_eq!(histogram.sorted(), vec![(GridCell { x: 0, y: 1 }, 2), (GridCell { x: 1, y: 0 }, 2)]);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let mut distribution = Distribution::default();
+        for value in [0.0, 1.5, 2.0, 3.0, 9.0, 100.0] {
+            distribution.add(value);
+        }
+        assert_eq!(distribution.buckets, vec![2, 2, 0, 1, 0, 0, 1]);
+        assert_eq!((distribution.min, distribution.max, distribution.count), (0.0, 100.0, 6));
+        assert_eq!(distribution.quantile(0.5), Some(4.0));
+        assert_eq!(distribution.quantile(1.0), Some(100.0));
+        assert_eq!(Distribution::bucket_range(3), (8.0, 16.0));
+
+        let mut merged = Distribution::default();
+        merged.merge(&distribution);
+        merged.merge(&Distribution::default());
+        assert_eq!(merged, distribution);
+        assert_eq!(Distribution::default().mean(), None);
+    }
+
+    #[test]
+    fn test_way_distributions() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(6).grid_size(10).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+
+        let mut locations = HashMap::new();
+        reader.nodes(|node| {
+            locations.insert(node.id, node.location);
+            Ok(())
+        }).unwrap();
+        let ways = reader.count_filtered(&ElementFilter::ways_only(false)).unwrap().ways_processed;
+
+        let lookup = |id: i64| locations.get(&NodeId(id)).copied();
+        let distributions = way_distributions(&mut reader, "highway", Some(&lookup)).unwrap();
+        let counted: u64 = distributions.classes.values().map(|stats| stats.node_counts.count).sum();
+        assert_eq!(counted, ways);
+        for stats in distributions.classes.values() {
+            assert_eq!((stats.lengths.count, stats.missing_locations), (stats.node_counts.count, 0));
+            assert!(stats.lengths.min > 0.0 && stats.node_counts.min >= 2.0);
+        }
+
+        let without_locations = way_distributions(&mut reader, "highway", None).unwrap();
+        assert!(without_locations.classes.values().all(|stats| stats.lengths.count == 0));
+
+        let mut csv = Vec::new();
+        distributions.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("class,metric,bucket_start,bucket_end,count\n"));
+        assert!(csv.lines().skip(1).all(|line| line.split(',').count() == 5));
+    }
+
+    #[test]
+    fn test_rejects_excessive_zoom() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(1).grid_size(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+
+        assert!(grid_histogram(&mut reader, "amenity", MAX_GRID_ZOOM + 1).is_err());
+        assert!(grid_histogram(&mut reader, "amenity", 12).is_ok());
+    }
+}
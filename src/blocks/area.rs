@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// How the values of one tag key decide whether a closed way is an area.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AreaRule {
+    /// Any value except "no" makes an area.
+    Any,
+    /// Any value except "no" and the listed ones makes an area.
+    AllExcept(Vec<String>),
+    /// Only the listed values make an area.
+    Only(Vec<String>),
+}
+
+impl AreaRule {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            AreaRule::Any => value != "no",
+            AreaRule::AllExcept(lines) => value != "no" && !lines.iter().any(|v| v == value),
+            AreaRule::Only(areas) => areas.iter().any(|v| v == value),
+        }
+    }
+}
+
+/// Rule table deciding whether a closed way is a polygon or a closed linestring.
+///
+/// `area=yes` and `area=no` always win. Otherwise the way is an area if any of its
+/// tags matches a rule; keys without a rule, such as `highway` and `barrier`, keep
+/// a closed way a line. The default table follows common renderer conventions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AreaRules {
+    rules: HashMap<String, AreaRule>,
+}
+
+impl AreaRules {
+    /// Creates an empty rule table, where only `area=yes` makes an area.
+    pub fn empty() -> Self {
+        Self { rules: HashMap::new() }
+    }
+
+    /// Adds or replaces the rule for a key.
+    pub fn with_rule(mut self, key: &str, rule: AreaRule) -> Self {
+        self.rules.insert(key.to_string(), rule);
+        self
+    }
+
+    /// Removes the rule for a key, so it no longer implies an area.
+    pub fn without_rule(mut self, key: &str) -> Self {
+        self.rules.remove(key);
+        self
+    }
+
+    /// Returns the shared default rule table.
+    pub fn standard() -> &'static AreaRules {
+        static STANDARD: OnceLock<AreaRules> = OnceLock::new();
+        STANDARD.get_or_init(AreaRules::default)
+    }
+
+    /// Returns true if the tags of a closed way make it an area.
+    pub fn is_area_tags<'a>(&self, tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> bool {
+        let mut implied = false;
+        for (key, value) in tags {
+            if key == "area" {
+                match value {
+                    "yes" => return true,
+                    "no" => return false,
+                    _ => {}
+                }
+            }
+            if let Some(rule) = self.rules.get(key) {
+                implied |= rule.matches(value);
+            }
+        }
+        implied
+    }
+}
+
+impl Default for AreaRules {
+    fn default() -> Self {
+        let except = |values: &[&str]| AreaRule::AllExcept(values.iter().map(|v| v.to_string()).collect());
+        let only = |values: &[&str]| AreaRule::Only(values.iter().map(|v| v.to_string()).collect());
+
+        let mut rules = Self::empty();
+        for key in [
+            "building", "building:part", "landuse", "amenity", "leisure", "shop", "tourism",
+            "office", "craft", "historic", "military", "place", "healthcare", "area:highway",
+        ] {
+            rules = rules.with_rule(key, AreaRule::Any);
+        }
+
+        rules
+            .with_rule("natural", except(&["coastline", "cliff", "ridge", "arete", "tree_row"]))
+            .with_rule("man_made", except(&["cutline", "embankment", "pipeline", "groyne", "breakwater", "dyke"]))
+            .with_rule("aeroway", except(&["taxiway", "runway"]))
+            .with_rule("power", except(&["line", "minor_line", "cable"]))
+            .with_rule("waterway", only(&["riverbank", "dock", "boatyard", "dam"]))
+            .with_rule("railway", only(&["platform", "station", "turntable", "roundhouse"]))
+            .with_rule("public_transport", only(&["platform", "station"]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_rules() {
+        let rules = AreaRules::standard();
+
+        assert!(rules.is_area_tags([("building", "yes")]));
+        assert!(rules.is_area_tags([("natural", "water")]));
+        assert!(!rules.is_area_tags([("natural", "coastline")]));
+        assert!(rules.is_area_tags([("waterway", "riverbank")]));
+        assert!(!rules.is_area_tags([("waterway", "river")]));
+        assert!(!rules.is_area_tags([("building", "no")]));
+    }
+
+    #[test]
+    fn test_area_tag_overrides() {
+        let rules = AreaRules::standard();
+
+        assert!(!rules.is_area_tags([("highway", "pedestrian")]));
+        assert!(rules.is_area_tags([("highway", "pedestrian"), ("area", "yes")]));
+        assert!(!rules.is_area_tags([("barrier", "fence")]));
+        assert!(!rules.is_area_tags([("landuse", "grass"), ("area", "no")]));
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = AreaRules::empty()
+            .with_rule("barrier", AreaRule::Only(vec!["city_wall".to_string()]));
+
+        assert!(rules.is_area_tags([("barrier", "city_wall")]));
+        assert!(!rules.is_area_tags([("building", "yes")]));
+        assert!(!AreaRules::default().without_rule("building").is_area_tags([("building", "yes")]));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::nano_degree::NanoDegree;
+
+/// Axis-aligned bounding box of OSM data, in nanodegrees.
+/// Used for element and blob extents; unlike `HeaderBBox` it can be grown point by point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct BoundingBox {
+    pub min_lon: NanoDegree,
+    pub max_lon: NanoDegree,
+    pub min_lat: NanoDegree,
+    pub max_lat: NanoDegree,
+}
+
+impl BoundingBox {
+    /// Creates a bounding box from its edges; swapped minimum and maximum are reordered.
+    pub fn new(min_lon: NanoDegree, min_lat: NanoDegree, max_lon: NanoDegree, max_lat: NanoDegree) -> Self {
+        Self {
+            min_lon: NanoDegree(min_lon.0.min(max_lon.0)),
+            max_lon: NanoDegree(min_lon.0.max(max_lon.0)),
+            min_lat: NanoDegree(min_lat.0.min(max_lat.0)),
+            max_lat: NanoDegree(min_lat.0.max(max_lat.0)),
+        }
+    }
+
+    /// Creates a degenerate bounding box containing a single point.
+    pub fn from_point(point: LatLon) -> Self {
+        Self {
+            min_lon: point.lon,
+            max_lon: point.lon,
+            min_lat: point.lat,
+            max_lat: point.lat,
+        }
+    }
+
+    /// Returns the south-west corner.
+    pub fn min(&self) -> LatLon {
+        LatLon::new(self.min_lat, self.min_lon)
+    }
+
+    /// Returns the north-east corner.
+    pub fn max(&self) -> LatLon {
+        LatLon::new(self.max_lat, self.max_lon)
+    }
+
+    /// Grows the bounding box to include the given point.
+    pub fn extend(&mut self, point: LatLon) {
+        self.min_lat = NanoDegree(self.min_lat.0.min(point.lat.0));
+        self.max_lat = NanoDegree(self.max_lat.0.max(point.lat.0));
+        self.min_lon = NanoDegree(self.min_lon.0.min(point.lon.0));
+        self.max_lon = NanoDegree(self.max_lon.0.max(point.lon.0));
+    }
+
+    /// Grows the bounding box to include another bounding box.
+    pub fn merge(&mut self, other: &BoundingBox) {
+        self.extend(other.min());
+        self.extend(other.max());
+    }
+
+    /// Grows an optional bounding box by a point, creating it if needed.
+    pub fn extend_option(bbox: &mut Option<BoundingBox>, point: LatLon) {
+        match bbox {
+            Some(bbox) => bbox.extend(point),
+            None => *bbox = Some(BoundingBox::from_point(point)),
+        }
+    }
+
+    /// Grows an optional bounding box by another bounding box, creating it if needed.
+    pub fn merge_option(bbox: &mut Option<BoundingBox>, other: &BoundingBox) {
+        match bbox {
+            Some(bbox) => bbox.merge(other),
+            None => *bbox = Some(*other),
+        }
+    }
+
+    /// Returns true if the point lies inside the bounding box (edges included).
+    pub fn contains(&self, point: LatLon) -> bool {
+        (self.min_lat.0..=self.max_lat.0).contains(&point.lat.0) && (self.min_lon.0..=self.max_lon.0).contains(&point.lon.0)
+    }
+
+    /// Returns true if the two bounding boxes overlap (touching edges count).
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min_lat.0 <= other.max_lat.0
+            && self.max_lat.0 >= other.min_lat.0
+            && self.min_lon.0 <= other.max_lon.0
+            && self.max_lon.0 >= other.min_lon.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_extend_and_contains() {
+        let mut bbox = BoundingBox::from_point(LatLon::from_raw(10, 20));
+        bbox.extend(LatLon::from_raw(-5, 40));
+
+        assert_eq!(bbox.min_lat, NanoDegree(-5));
+        assert_eq!(bbox.max_lat, NanoDegree(10));
+        assert_eq!(bbox.min_lon, NanoDegree(20));
+        assert_eq!(bbox.max_lon, NanoDegree(40));
+        assert!(bbox.contains(LatLon::from_raw(0, 30)));
+        assert!(!bbox.contains(LatLon::from_raw(11, 30)));
+        assert_eq!(bbox.min(), LatLon::from_raw(-5, 20));
+        assert_eq!(bbox.max(), LatLon::from_raw(10, 40));
+        assert_eq!(BoundingBox::new(NanoDegree(40), NanoDegree(10), NanoDegree(20), NanoDegree(-5)), bbox);
+    }
+
+    #[test]
+    fn test_intersects() {
+        let a = BoundingBox { min_lon: NanoDegree(0), max_lon: NanoDegree(10), min_lat: NanoDegree(0), max_lat: NanoDegree(10) };
+        let b = BoundingBox { min_lon: NanoDegree(10), max_lon: NanoDegree(20), min_lat: NanoDegree(5), max_lat: NanoDegree(6) };
+        let c = BoundingBox { min_lon: NanoDegree(11), max_lon: NanoDegree(20), min_lat: NanoDegree(5), max_lat: NanoDegree(6) };
+
+        assert!(a.intersects(&b));
+        assert!(b.intersects(&a));
+        assert!(!a.intersects(&c));
+    }
+
+    #[test]
+    fn test_option_helpers() {
+        let mut bbox = None;
+        BoundingBox::extend_option(&mut bbox, LatLon::from_raw(1, 2));
+        BoundingBox::merge_option(&mut bbox, &BoundingBox::from_point(LatLon::from_raw(3, 4)));
+
+        assert_eq!(bbox, Some(BoundingBox { min_lon: NanoDegree(2), max_lon: NanoDegree(4), min_lat: NanoDegree(1), max_lat: NanoDegree(3) }));
+    }
+}
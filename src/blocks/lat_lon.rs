@@ -0,0 +1,111 @@
+use crate::blocks::nano_degree::NanoDegree;
+
+/// Mean Earth radius in meters, as used for great-circle distances.
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// A coordinate pair, latitude first.
+/// Named fields keep latitude and longitude from being swapped the way bare `(i64, i64)` pairs can be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
+pub struct LatLon {
+    pub lat: NanoDegree,
+    pub lon: NanoDegree,
+}
+
+impl LatLon {
+    /// Creates a coordinate pair.
+    pub const fn new(lat: NanoDegree, lon: NanoDegree) -> Self {
+        Self { lat, lon }
+    }
+
+    /// Creates a coordinate pair from raw values, as stored in the file.
+    pub const fn from_raw(lat: i64, lon: i64) -> Self {
+        Self::new(NanoDegree(lat), NanoDegree(lon))
+    }
+
+    /// Creates a coordinate pair from degrees.
+    /// Rejects non-finite and out-of-range values.
+    pub fn try_from_degrees(lat: f64, lon: f64) -> Result<Self, &'static str> {
+        if !lat.is_finite() || !lon.is_finite() {
+            return Err("LatLon cannot be created from non-finite values");
+        }
+        Ok(Self::new(NanoDegree::from_latitude(lat)?, NanoDegree::from_longitude(lon)?))
+    }
+
+    /// Returns `(lat, lon)` in degrees.
+    pub fn to_degrees(self) -> (f64, f64) {
+        (self.lat.to_degrees(), self.lon.to_degrees())
+    }
+
+    /// Returns `(lat, lon)` in radians.
+    pub fn to_radians(self) -> (f64, f64) {
+        let (lat, lon) = self.to_degrees();
+        (lat.to_radians(), lon.to_radians())
+    }
+
+    /// Returns the great-circle (haversine) distance to `other` in meters.
+    pub fn distance_to(self, other: LatLon) -> f64 {
+        let (lat1, lon1) = self.to_radians();
+        let (lat2, lon2) = other.to_radians();
+        let a = ((lat2 - lat1) / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+    }
+
+    /// Returns true if both the latitude and the longitude are in range.
+    pub fn is_valid(self) -> bool {
+        self.lat.is_valid_latitude() && self.lon.is_valid_longitude()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_from_degrees_keeps_order() {
+        let location = LatLon::try_from_degrees(45.0, 9.0).unwrap();
+        let (lat, lon) = location.to_degrees();
+
+        assert!((lat - 45.0).abs() < 1e-9);
+        assert!((lon - 9.0).abs() < 1e-9);
+        assert!(LatLon::try_from_degrees(120.0, 9.0).is_err());
+        assert!(LatLon::try_from_degrees(45.0, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_radians() {
+        let (lat, lon) = LatLon::try_from_degrees(90.0, -180.0).unwrap().to_radians();
+
+        assert!((lat - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+        assert!((lon + std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_distance() {
+        let paris = LatLon::try_from_degrees(48.8566, 2.3522).unwrap();
+        let london = LatLon::try_from_degrees(51.5074, -0.1278).unwrap();
+
+        assert!((paris.distance_to(london) - 343_560.0).abs() < 500.0);
+        assert_eq!(paris.distance_to(paris), 0.0);
+        let equator = LatLon::try_from_degrees(0.0, 0.0).unwrap().distance_to(LatLon::try_from_degrees(0.0, 1.0).unwrap());
+        assert!((equator - 111_195.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_degrees_round_trip_is_valid() {
+        // Both directions use the NanoDegree scale, so anything built from degrees is valid
+        for (lat, lon) in [(45.0, 9.0), (90.0, 180.0), (-90.0, -180.0), (0.0, 0.0), (-33.8688, 151.2093)] {
+            let location = LatLon::try_from_degrees(lat, lon).unwrap();
+            assert!(location.is_valid(), "{lat}, {lon}");
+            assert_eq!(LatLon::try_from_degrees(location.to_degrees().0, location.to_degrees().1).unwrap(), location);
+        }
+        assert_eq!(LatLon::try_from_degrees(45.0, 9.0).unwrap(), LatLon::from_raw(45_000_000_000, 9_000_000_000));
+    }
+
+    #[test]
+    fn test_validity() {
+        assert!(LatLon::from_raw(90_000_000_000, -180_000_000_000).is_valid());
+        assert!(!LatLon::from_raw(180_000_000_000, 0).is_valid());
+        assert_eq!(LatLon::default(), LatLon::from_raw(0, 0));
+    }
+}
@@ -1,6 +1,12 @@
+pub mod area;
+pub mod bbox;
 pub mod header_block;
+pub mod lat_lon;
 pub mod nano_degree;
+pub mod osm_id;
 pub mod prelude;
 pub mod primitives;
 pub mod string_table;
+pub mod tags;
+pub mod timestamp;
 
@@ -1,17 +1,36 @@
 
 /// Represents a value in nanodegrees (1e-9 degrees).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+///
+/// Valid values run from -180e9 to 180e9, i.e. -180° to 180°.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
 pub struct NanoDegree(pub i64);
 
 impl NanoDegree {
+    /// 90 degrees, the largest valid latitude.
+    pub const MAX_LATITUDE: i64 = 90_000_000_000;
+
+    /// 180 degrees, the largest valid longitude.
+    pub const MAX_LONGITUDE: i64 = 180_000_000_000;
+
     /// Creates a new NanoDegree from a value in nanodegrees.
+    ///
+    /// # Panics
+    /// Panics if the value is outside the longitude/latitude bounds.
+    #[deprecated(note = "panics on out-of-range input; use `NanoDegree::try_new` instead")]
     pub fn new(nd: i64) -> Self {
-        assert!(
-            (-1_800_000_000..=1_800_000_000).contains(&nd),
-            "NanoDegree must be in the range [-180e7, 180e7] (longitude/latitude bounds)"
-        );
+        match Self::try_new(nd) {
+            Ok(value) => value,
+            Err(msg) => panic!("{msg}"),
+        }
+    }
 
-        NanoDegree(nd)
+    /// Creates a new NanoDegree from a value in nanodegrees.
+    /// Returns an error instead of panicking when the value is out of range.
+    pub fn try_new(nd: i64) -> Result<Self, &'static str> {
+        if !(-Self::MAX_LONGITUDE..=Self::MAX_LONGITUDE).contains(&nd) {
+            return Err("NanoDegree must be in the range [-180e9, 180e9] (longitude/latitude bounds)");
+        }
+        Ok(NanoDegree(nd))
     }
 
     /// Converts the NanoDegree to degrees.
@@ -20,9 +39,24 @@ impl NanoDegree {
     }
 
     /// Creates a NanoDegree from a value in degrees.
+    ///
+    /// # Panics
+    /// Panics if the value is outside the longitude/latitude bounds.
+    #[deprecated(note = "panics on out-of-range input; use `NanoDegree::try_from_degrees` instead")]
     pub fn from_degrees(deg: f64) -> Self {
-        let nd = (deg * 1e9) as i64;
-        NanoDegree::new(nd)
+        match Self::try_from_degrees(deg) {
+            Ok(value) => value,
+            Err(msg) => panic!("{msg}"),
+        }
+    }
+
+    /// Creates a NanoDegree from a value in degrees.
+    /// Non-finite and out-of-range inputs are rejected instead of panicking.
+    pub fn try_from_degrees(deg: f64) -> Result<Self, &'static str> {
+        if !deg.is_finite() {
+            return Err("NanoDegree cannot be created from a non-finite value");
+        }
+        Self::try_new((deg * 1e9) as i64)
     }
 
     /// Creates a NanoDegree from latitude in degrees.
@@ -31,7 +65,7 @@ impl NanoDegree {
         if !(-90.0..=90.0).contains(&lat) {
             return Err("Latitude must be in range [-90, 90]");
         }
-        Ok(NanoDegree((lat * 1e9) as i64))
+        Self::try_from_degrees(lat)
     }
 
     /// Creates a NanoDegree from longitude in degrees.
@@ -40,7 +74,7 @@ impl NanoDegree {
         if !(-180.0..=180.0).contains(&lon) {
             return Err("Longitude must be in range [-180, 180]");
         }
-        Ok(NanoDegree((lon * 1e9) as i64))
+        Self::try_from_degrees(lon)
     }
 
     /// Returns the raw nanodegree value.
@@ -50,19 +84,21 @@ impl NanoDegree {
 
     /// Returns true if this represents a valid latitude.
     pub fn is_valid_latitude(self) -> bool {
-        (-900_000_000..=900_000_000).contains(&self.0)
+        (-Self::MAX_LATITUDE..=Self::MAX_LATITUDE).contains(&self.0)
     }
 
     /// Returns true if this represents a valid longitude.
     pub fn is_valid_longitude(self) -> bool {
-        (-1_800_000_000..=1_800_000_000).contains(&self.0)
+        (-Self::MAX_LONGITUDE..=Self::MAX_LONGITUDE).contains(&self.0)
     }
 }
 
-// Implement From<f64> for NanoDegree
-impl From<f64> for NanoDegree {
-    fn from(deg: f64) -> Self {
-        NanoDegree::from_degrees(deg)
+// Implement TryFrom<f64> for NanoDegree, failing like `try_from_degrees`
+impl TryFrom<f64> for NanoDegree {
+    type Error = &'static str;
+
+    fn try_from(deg: f64) -> Result<Self, Self::Error> {
+        NanoDegree::try_from_degrees(deg)
     }
 }
 
@@ -74,23 +110,24 @@ impl From<NanoDegree> for f64 {
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
     #[test]
     fn test_nano_degree_creation() {
-        let nd = NanoDegree::new(900_000_000); // 90 degrees
-        assert_eq!(nd.0, 900_000_000);
-        assert_eq!(nd.raw(), 900_000_000);
+        let nd = NanoDegree::new(90_000_000_000); // 90 degrees
+        assert_eq!(nd.0, 90_000_000_000);
+        assert_eq!(nd.raw(), 90_000_000_000);
     }
 
     #[test]
     fn test_nano_degree_to_degrees() {
-        let nd = NanoDegree::new(900_000_000); // 90 degrees
+        let nd = NanoDegree::new(90_000_000_000); // 90 degrees
         assert!((nd.to_degrees() - 90.0).abs() < 1e-10);
         
-        let nd = NanoDegree::new(-1_800_000_000); // -180 degrees
+        let nd = NanoDegree::new(-180_000_000_000); // -180 degrees
         assert!((nd.to_degrees() - (-180.0)).abs() < 1e-10);
         
         let nd = NanoDegree::new(0);
@@ -100,10 +137,10 @@ mod tests {
     #[test]
     fn test_nano_degree_from_degrees() {
         let nd = NanoDegree::from_degrees(90.0);
-        assert_eq!(nd.0, 900_000_000);
+        assert_eq!(nd.0, 90_000_000_000);
         
         let nd = NanoDegree::from_degrees(-180.0);
-        assert_eq!(nd.0, -1_800_000_000);
+        assert_eq!(nd.0, -180_000_000_000);
         
         let nd = NanoDegree::from_degrees(0.0);
         assert_eq!(nd.0, 0);
@@ -139,22 +176,22 @@ mod tests {
 
     #[test]
     fn test_is_valid_latitude() {
-        let valid_lat = NanoDegree::new(900_000_000); // 90 degrees
+        let valid_lat = NanoDegree::new(90_000_000_000); // 90 degrees
         assert!(valid_lat.is_valid_latitude());
         
-        let valid_lat = NanoDegree::new(-900_000_000); // -90 degrees
+        let valid_lat = NanoDegree::new(-90_000_000_000); // -90 degrees
         assert!(valid_lat.is_valid_latitude());
         
-        let invalid_lat = NanoDegree::new(1_000_000_000); // 100 degrees
+        let invalid_lat = NanoDegree::new(100_000_000_000); // 100 degrees
         assert!(!invalid_lat.is_valid_latitude());
     }
 
     #[test]
     fn test_is_valid_longitude() {
-        let valid_lon = NanoDegree::new(1_800_000_000); // 180 degrees
+        let valid_lon = NanoDegree::new(180_000_000_000); // 180 degrees
         assert!(valid_lon.is_valid_longitude());
         
-        let valid_lon = NanoDegree::new(-1_800_000_000); // -180 degrees
+        let valid_lon = NanoDegree::new(-180_000_000_000); // -180 degrees
         assert!(valid_lon.is_valid_longitude());
         
         let valid_lon = NanoDegree::new(0); // 0 degrees
@@ -163,11 +200,14 @@ mod tests {
 
     #[test]
     fn test_from_trait_implementation() {
-        let nd: NanoDegree = 90.0.into();
-        assert_eq!(nd.0, 900_000_000);
+        let nd: NanoDegree = 90.0.try_into().unwrap();
+        assert_eq!(nd.0, 90_000_000_000);
         
         let deg: f64 = nd.into();
         assert!((deg - 90.0).abs() < 1e-10);
+        
+        assert_eq!(NanoDegree::try_from(180.5), NanoDegree::try_from_degrees(180.5));
+        assert!(NanoDegree::try_from(f64::NAN).is_err());
     }
 
     #[test]
@@ -192,7 +232,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "NanoDegree must be in the range")]
     fn test_panic_on_invalid_range() {
-        NanoDegree::new(2_000_000_000); // Beyond valid range
+        NanoDegree::new(200_000_000_000); // Beyond valid range
     }
 
     #[test]
@@ -279,4 +319,28 @@ mod tests {
         
         assert_eq!(set.len(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(NanoDegree::try_new(90_000_000_000), Ok(NanoDegree(90_000_000_000)));
+        assert_eq!(NanoDegree::try_new(-180_000_000_000), Ok(NanoDegree(-180_000_000_000)));
+        assert!(NanoDegree::try_new(180_000_000_001).is_err());
+        assert!(NanoDegree::try_new(i64::MIN).is_err());
+    }
+
+    #[test]
+    fn test_try_from_degrees() {
+        assert_eq!(NanoDegree::try_from_degrees(0.0), Ok(NanoDegree(0)));
+        assert_eq!(NanoDegree::try_from_degrees(1.5), Ok(NanoDegree(1_500_000_000)));
+        assert_eq!(NanoDegree::try_from_degrees(90.0), Ok(NanoDegree(90_000_000_000)));
+        assert_eq!(NanoDegree::try_from_degrees(-180.0), Ok(NanoDegree(-180_000_000_000)));
+        assert!(NanoDegree::try_from_degrees(180.000001).is_err());
+        assert!(NanoDegree::try_from_degrees(f64::NAN).is_err());
+        assert!(NanoDegree::try_from_degrees(f64::INFINITY).is_err());
+
+        // Latitudes stop at 90 degrees
+        assert!(NanoDegree::try_from_degrees(90.0).unwrap().is_valid_latitude());
+        assert!(!NanoDegree::try_from_degrees(90.000001).unwrap().is_valid_latitude());
+        assert!(NanoDegree::from_latitude(90.000001).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// Defines a typed OSM id wrapping the i64 stored in the file.
+macro_rules! osm_id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, serde::Serialize, serde::Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub i64);
+
+        impl $name {
+            /// Wraps a raw id.
+            pub const fn new(id: i64) -> Self {
+                Self(id)
+            }
+
+            /// Returns the raw id.
+            pub const fn get(self) -> i64 {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(id: i64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for i64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        impl PartialEq<i64> for $name {
+            fn eq(&self, other: &i64) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+osm_id! {
+    /// Id of an OSM node.
+    ///
+    /// Ids of different element types are distinct types, so a node id can't
+    /// be compared with or looked up as a way id by mistake. They display as
+    /// the bare number and serialize as one.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use osm_pbf::NodeId;
+    ///
+    /// let id = NodeId::from(240_109_189);
+    /// assert_eq!(id.to_string(), "240109189");
+    /// assert_eq!(i64::from(id), 240_109_189);
+    /// assert!(NodeId(1) < NodeId(2));
+    /// ```
+    NodeId
+}
+
+osm_id! {
+    /// Id of an OSM way; see `NodeId`.
+    WayId
+}
+
+osm_id! {
+    /// Id of an OSM relation; see `NodeId`.
+    RelationId
+}
+
+osm_id! {
+    /// Id of an OSM changeset; see `NodeId`.
+    ChangesetId
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_conversions_and_formatting() {
+        let way = WayId::new(-42);
+        assert_eq!((way.get(), i64::from(way), WayId::from(-42)), (-42, -42, way));
+        assert_eq!(format!("{way} {:>5}", RelationId(7)), "-42     7");
+        assert_eq!(serde_json::to_string(&ChangesetId(9)).unwrap(), "9");
+        assert_eq!(serde_json::from_str::<NodeId>("12").unwrap(), NodeId(12));
+        assert!(NodeId(3) == 3);
+
+        let mut ids = vec![NodeId(5), NodeId(-1), NodeId(3)];
+        ids.sort();
+        assert_eq!(ids, [NodeId(-1), NodeId(3), NodeId(5)]);
+    }
+}
@@ -1,4 +1,10 @@
+pub use crate::blocks::area::{AreaRule, AreaRules};
+pub use crate::blocks::bbox::BoundingBox;
 pub use crate::blocks::header_block::HeaderBlock;
+pub use crate::blocks::lat_lon::LatLon;
 pub use crate::blocks::nano_degree::NanoDegree;
+pub use crate::blocks::osm_id::{ChangesetId, NodeId, RelationId, WayId};
 pub use crate::blocks::primitives::prelude::*;
-pub use crate::blocks::string_table::StringTable;
+pub use crate::blocks::string_table::{StringTable, StringTableBuilder};
+pub use crate::blocks::tags::{TagIter, Tags};
+pub use crate::blocks::timestamp::TimestampMillis;
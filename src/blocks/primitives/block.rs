@@ -1,5 +1,12 @@
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::nano_degree::NanoDegree;
 use crate::blocks::string_table::StringTable;
+use crate::blocks::primitives::dense_info::DenseInfo;
 use crate::blocks::primitives::group::PrimitiveGroup;
+use crate::blocks::primitives::info::Info;
+use crate::blocks::primitives::node::Node;
+use crate::blocks::primitives::way::Way;
+use crate::blocks::timestamp::TimestampMillis;
 
 /// Represents a block of OSM primitives, including nodes, ways, and relations.
 /// Stores coordinate and date granularity, offsets, and references to string and primitive tables.
@@ -38,6 +45,113 @@ impl PrimitiveBlock {
     pub fn default_date_granularity() -> i32 {
         Self::DEFAULT_DATE_GRANULARITY
     }
+
+    /// Converts a latitude stored in this block, in granularity units, to nanodegrees.
+    pub fn lat_of(&self, raw: i64) -> NanoDegree {
+        NanoDegree(self.lat_offset.wrapping_add((self.granularity as i64).wrapping_mul(raw)))
+    }
+
+    /// Converts a longitude stored in this block, in granularity units, to nanodegrees.
+    pub fn lon_of(&self, raw: i64) -> NanoDegree {
+        NanoDegree(self.lon_offset.wrapping_add((self.granularity as i64).wrapping_mul(raw)))
+    }
+
+    /// Converts a location stored in this block to nanodegrees.
+    pub fn location_of(&self, raw_lat: i64, raw_lon: i64) -> LatLon {
+        LatLon::from_raw(self.lat_of(raw_lat).0, self.lon_of(raw_lon).0)
+    }
+
+    /// Converts a location in nanodegrees to this block's granularity units,
+    /// rounding to the nearest grid point. The inverse of `location_of`.
+    pub fn raw_location(&self, location: LatLon) -> (i64, i64) {
+        let granularity = (self.granularity as i64).max(1);
+        let raw = |nd: i64, offset: i64| {
+            let units = nd.wrapping_sub(offset);
+            units.div_euclid(granularity) + (units.rem_euclid(granularity) * 2 >= granularity) as i64
+        };
+        (raw(location.lat.0, self.lat_offset), raw(location.lon.0, self.lon_offset))
+    }
+
+    /// Returns the location of a node as stored in this block (not of a decoded node,
+    /// which is already in nanodegrees).
+    pub fn node_location(&self, node: &Node) -> LatLon {
+        self.location_of(node.location.lat.0, node.location.lon.0)
+    }
+
+    /// Returns the node locations of a way as stored in this block, from the
+    /// delta-encoded coordinates of files with `LocationsOnWays`, or None if the
+    /// way carries none for some of its nodes.
+    pub fn way_locations(&self, way: &Way) -> Option<Vec<LatLon>> {
+        let raw = way.locations()?;
+        Some(raw.into_iter().map(|location| self.location_of(location.lat.0, location.lon.0)).collect())
+    }
+
+    /// Converts a timestamp stored in this block to milliseconds since the epoch.
+    pub fn timestamp(&self, raw: i64) -> TimestampMillis {
+        TimestampMillis::from_raw(raw, self.date_granularity)
+    }
+
+    /// Returns the timestamp of an element's metadata stored in this block.
+    pub fn info_timestamp(&self, info: &Info) -> TimestampMillis {
+        self.timestamp(info.timestamp)
+    }
+
+    /// Delta-decodes the timestamps of a dense info column of this block.
+    pub fn dense_timestamps(&self, info: &DenseInfo) -> Vec<TimestampMillis> {
+        let mut raw = 0i64;
+        info.timestamp.iter()
+            .map(|delta| {
+                raw = raw.wrapping_add(*delta);
+                self.timestamp(raw)
+            })
+            .collect()
+    }
+
+    /// Changes the date granularity, re-quantizing every timestamp in the block
+    /// to the nearest multiple of the new granularity.
+    /// Returns an error if the new granularity is not positive.
+    pub fn requantize_dates(&mut self, date_granularity: i32) -> Result<(), &'static str> {
+        if date_granularity <= 0 {
+            return Err("Date granularity must be positive");
+        }
+        let old = self.date_granularity;
+        if old == date_granularity {
+            return Ok(());
+        }
+        let convert = |raw: i64| TimestampMillis::from_raw(raw, old).to_raw(date_granularity);
+        let convert_info = |info: &mut Option<Info>| -> Result<(), &'static str> {
+            if let Some(info) = info {
+                info.timestamp = convert(info.timestamp)?;
+            }
+            Ok(())
+        };
+
+        for group in &mut self.primitivegroup {
+            for node in &mut group.nodes {
+                convert_info(&mut node.info)?;
+            }
+            if let Some(info) = group.dense.as_mut().and_then(|dense| dense.denseinfo.as_mut()) {
+                let (mut old_raw, mut new_raw) = (0i64, 0i64);
+                for delta in &mut info.timestamp {
+                    old_raw = old_raw.wrapping_add(*delta);
+                    let raw = convert(old_raw)?;
+                    *delta = raw.wrapping_sub(new_raw);
+                    new_raw = raw;
+                }
+            }
+            for way in &mut group.ways {
+                convert_info(&mut way.info)?;
+            }
+            for relation in &mut group.relations {
+                convert_info(&mut relation.info)?;
+            }
+            for changeset in &mut group.changesets {
+                convert_info(&mut changeset.info)?;
+            }
+        }
+        self.date_granularity = date_granularity;
+        Ok(())
+    }
 }
 
 impl Default for PrimitiveBlock {
@@ -56,6 +170,7 @@ impl Default for PrimitiveBlock {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blocks::osm_id::WayId;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -146,17 +261,28 @@ mod tests {
             date_granularity: 1000,
         };
         
-        // Test coordinate calculation: raw_coord * granularity + offset
-        let raw_lat = 450_000_000; // 45 degrees in nanodegrees
-        let raw_lon = 900_000_000; // 90 degrees in nanodegrees
-        
-        let actual_lat = (raw_lat as i64) * (block.granularity as i64) + block.lat_offset;
-        let actual_lon = (raw_lon as i64) * (block.granularity as i64) + block.lon_offset;
-        
-        // Expected: 45 * 100 + 500_000_000 = 4_500_000_000 + 500_000_000 = 5_000_000_000 (50 degrees)
-        assert_eq!(actual_lat, 4_500_000_000 + 500_000_000);
-        // Expected: 90 * 100 + (-1_000_000_000) = 9_000_000_000 - 1_000_000_000 = 8_000_000_000 (80 degrees)
-        assert_eq!(actual_lon, 9_000_000_000 - 1_000_000_000);
+        // Stored coordinates are in units of 100 nanodegrees: raw * granularity + offset
+        let raw_lat = 450_000_000; // 45 degrees
+        let raw_lon = 900_000_000; // 90 degrees
+
+        assert_eq!(block.lat_of(raw_lat), NanoDegree(45_500_000_000)); // 45.5 degrees
+        assert_eq!(block.lon_of(raw_lon), NanoDegree(89_000_000_000)); // 89 degrees
+        let location = block.location_of(raw_lat, raw_lon);
+        assert_eq!(location, LatLon::from_raw(45_500_000_000, 89_000_000_000));
+        assert_eq!(block.raw_location(location), (raw_lat, raw_lon));
+        // Off-grid locations round to the nearest grid point
+        assert_eq!(block.raw_location(LatLon::from_raw(45_500_000_049, 88_999_999_950)), (raw_lat, raw_lon));
+        assert_eq!(block.raw_location(LatLon::from_raw(45_500_000_051, 88_999_999_949)), (raw_lat + 1, raw_lon - 1));
+
+        let mut node = Node::new(1, LatLon::from_raw(raw_lat, raw_lon));
+        assert_eq!(block.node_location(&node), location);
+        node.info = Some(Info { timestamp: 1_700_000_000, ..Default::default() });
+        assert_eq!(block.info_timestamp(node.info.as_ref().unwrap()), TimestampMillis::from_secs(1_700_000_000));
+
+        let way = Way { id: WayId(2), keys: vec![], vals: vec![], info: None, refs: vec![1, 1], lat: vec![raw_lat, -1], lon: vec![raw_lon, 2] };
+        let next = LatLon::from_raw(45_500_000_000 - 100, 89_000_000_000 + 200);
+        assert_eq!(block.way_locations(&way), Some(vec![location, next]));
+        assert_eq!(block.way_locations(&Way { lat: vec![], ..way }), None);
     }
 
     #[test]
@@ -250,4 +376,32 @@ mod tests {
         let size = std::mem::size_of::<PrimitiveBlock>();
         assert!(size > std::mem::size_of::<StringTable>());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_requantize_dates() {
+        use crate::blocks::primitives::prelude::*;
+
+        let mut block = PrimitiveBlock { date_granularity: 1, ..Default::default() };
+        block.primitivegroup.push(PrimitiveGroup {
+            dense: Some(DenseNodes {
+                id: vec![1, 1],
+                denseinfo: Some(DenseInfo { timestamp: vec![1_000_400, 1_100], ..Default::default() }),
+                lat: vec![0, 0],
+                lon: vec![0, 0],
+                keys_vals: vec![],
+            }),
+            ways: vec![Way { id: WayId(1), keys: vec![], vals: vec![], info: Some(Info { timestamp: 59_999, ..Default::default() }), refs: vec![], lat: vec![], lon: vec![] }],
+            ..Default::default()
+        });
+        let dense = |block: &PrimitiveBlock| block.dense_timestamps(block.primitivegroup[0].dense.as_ref().unwrap().denseinfo.as_ref().unwrap());
+        assert_eq!(dense(&block), vec![TimestampMillis(1_000_400), TimestampMillis(1_001_500)]);
+
+        block.requantize_dates(1000).unwrap();
+        assert_eq!(block.date_granularity, 1000);
+        assert_eq!(dense(&block), vec![TimestampMillis(1_000_000), TimestampMillis(1_002_000)]);
+        let way_info = block.primitivegroup[0].ways[0].info.as_ref().unwrap();
+        assert_eq!(block.timestamp(way_info.timestamp), TimestampMillis(60_000));
+
+        assert!(block.requantize_dates(0).is_err());
+    }
+}
@@ -1,10 +1,13 @@
+use crate::blocks::osm_id::ChangesetId;
 use crate::blocks::primitives::info::Info;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::tags::Tags;
 
 /// Represents an OSM changeset.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct ChangeSet {
     /// Changeset ID
-    pub id: i64,
+    pub id: ChangesetId,
 
     /// Array of key indices into the string table
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -18,3 +21,10 @@ pub struct ChangeSet {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub info: Option<Info>,
 }
+
+impl ChangeSet {
+    /// Returns the changeset's tags resolved against the string table of its block.
+    pub fn tags<'a>(&'a self, strings: &'a StringTable) -> Tags<'a> {
+        Tags::new(&self.keys, &self.vals, strings)
+    }
+}
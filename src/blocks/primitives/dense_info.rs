@@ -6,7 +6,7 @@ pub struct DenseInfo {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub version: Vec<i32>,
 
-    /// Delta-encoded timestamps
+    /// Delta-encoded timestamps in units of the block's `date_granularity`
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub timestamp: Vec<i64>,
 
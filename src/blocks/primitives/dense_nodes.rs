@@ -1,4 +1,9 @@
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::osm_id::NodeId;
+use crate::blocks::primitives::block::PrimitiveBlock;
 use crate::blocks::primitives::dense_info::DenseInfo;
+use crate::blocks::primitives::info::Info;
+use crate::blocks::primitives::node::Node;
 
 /// Represents dense node storage format for efficient bulk node storage.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -36,3 +41,191 @@ impl Default for DenseNodes {
     }
 }
 
+
+impl DenseNodes {
+    /// Returns an iterator over the decoded nodes, in order.
+    ///
+    /// Ids, coordinates and metadata are delta-decoded as the iterator
+    /// advances, and coordinates are converted to nanodegrees with the
+    /// block's granularity and offsets. Tags are not copied; use
+    /// `DenseNode::tags` or `DenseNode::to_node` for the nodes you keep.
+    pub fn iter<'a>(&'a self, block: &PrimitiveBlock) -> DenseNodeIter<'a> {
+        DenseNodeIter::new(self, block.granularity, block.lat_offset, block.lon_offset)
+    }
+}
+
+/// A node decoded from `DenseNodes`, borrowing its tags from the group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseNode<'a> {
+    /// Node ID
+    pub id: NodeId,
+    /// Coordinates in nanodegrees
+    pub location: LatLon,
+    /// Metadata, if the group has a `DenseInfo`
+    pub info: Option<Info>,
+    /// Interleaved key and value indices, without the terminating 0
+    keys_vals: &'a [i32],
+}
+
+impl<'a> DenseNode<'a> {
+    /// Returns the node's tags as `(key_index, value_index)` pairs.
+    pub fn tags(&self) -> impl Iterator<Item = (u32, u32)> + 'a {
+        self.keys_vals.chunks_exact(2).map(|pair| (pair[0] as u32, pair[1] as u32))
+    }
+
+    /// Returns true if the node has at least one tag.
+    pub fn is_tagged(&self) -> bool {
+        !self.keys_vals.is_empty()
+    }
+
+    /// Converts the node into an owned `Node`.
+    pub fn to_node(&self) -> Node {
+        let mut node = Node::new(self.id, self.location);
+        for (key, value) in self.tags() {
+            node.add_tag(key, value);
+        }
+        node.info = self.info.clone();
+        node
+    }
+}
+
+/// Iterator over the nodes of a `DenseNodes` group, created by `DenseNodes::iter`.
+#[derive(Debug, Clone)]
+pub struct DenseNodeIter<'a> {
+    dense: &'a DenseNodes,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+    index: usize,
+    cursor: usize,
+    id: i64,
+    lat: i64,
+    lon: i64,
+    timestamp: i64,
+    changeset: i64,
+    uid: i32,
+    user_sid: i32,
+}
+
+impl<'a> DenseNodeIter<'a> {
+    pub(crate) fn new(dense: &'a DenseNodes, granularity: i32, lat_offset: i64, lon_offset: i64) -> Self {
+        Self {
+            dense,
+            granularity: granularity as i64,
+            lat_offset,
+            lon_offset,
+            index: 0,
+            cursor: 0,
+            id: 0,
+            lat: 0,
+            lon: 0,
+            timestamp: 0,
+            changeset: 0,
+            uid: 0,
+            user_sid: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for DenseNodeIter<'a> {
+    type Item = DenseNode<'a>;
+
+    fn next(&mut self) -> Option<DenseNode<'a>> {
+        let dense = self.dense;
+        let i = self.index;
+        let column = |values: &[i64]| values.get(i).copied().unwrap_or(0);
+
+        self.id = self.id.wrapping_add(*dense.id.get(i)?);
+        self.lat = self.lat.wrapping_add(column(&dense.lat));
+        self.lon = self.lon.wrapping_add(column(&dense.lon));
+        self.index += 1;
+
+        let info = dense.denseinfo.as_ref().map(|info| {
+            self.timestamp = self.timestamp.wrapping_add(column(&info.timestamp));
+            self.changeset = self.changeset.wrapping_add(column(&info.changeset));
+            self.uid = self.uid.wrapping_add(info.uid.get(i).copied().unwrap_or(0));
+            self.user_sid = self.user_sid.wrapping_add(info.user_sid.get(i).copied().unwrap_or(0));
+            Info {
+                version: info.version.get(i).copied().unwrap_or(0),
+                timestamp: self.timestamp,
+                changeset: self.changeset,
+                uid: self.uid,
+                user_sid: self.user_sid as u32,
+                visible: info.visible.get(i).copied().unwrap_or(true),
+            }
+        });
+
+        // Tags run up to the next 0 key; files without any tags omit keys_vals
+        let start = self.cursor.min(dense.keys_vals.len());
+        while let Some(&key) = dense.keys_vals.get(self.cursor) {
+            if key == 0 {
+                break;
+            }
+            self.cursor += 2;
+        }
+        let keys_vals = &dense.keys_vals[start..self.cursor.min(dense.keys_vals.len())];
+        self.cursor += 1;
+
+        let location = LatLon::from_raw(
+            self.lat_offset.wrapping_add(self.granularity.wrapping_mul(self.lat)),
+            self.lon_offset.wrapping_add(self.granularity.wrapping_mul(self.lon)),
+        );
+        Some(DenseNode { id: NodeId(self.id), location, info, keys_vals })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.dense.id.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for DenseNodeIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_iter_decodes_deltas() {
+        let block = PrimitiveBlock { granularity: 100, lat_offset: 5, lon_offset: -5, ..Default::default() };
+        let dense = DenseNodes {
+            id: vec![10, 1, 5],
+            denseinfo: Some(DenseInfo {
+                version: vec![1, 2, 3],
+                timestamp: vec![1000, 10, -5],
+                changeset: vec![7, 0, 1],
+                uid: vec![3, 0, -1],
+                user_sid: vec![1, 0, 1],
+                visible: vec![],
+            }),
+            lat: vec![500, 10, -20],
+            lon: vec![-100, 0, 30],
+            keys_vals: vec![1, 2, 3, 4, 0, 0, 5, 6, 0],
+        };
+
+        let nodes: Vec<_> = dense.iter(&block).collect();
+        assert_eq!(dense.iter(&block).len(), 3);
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![10, 11, 16]);
+        assert_eq!(nodes[1].location, LatLon::from_raw(51_005, -10_005));
+        assert_eq!(nodes[2].location, LatLon::from_raw(49_005, -7_005));
+        assert_eq!(nodes[0].tags().collect::<Vec<_>>(), vec![(1, 2), (3, 4)]);
+        assert!(!nodes[1].is_tagged());
+        assert_eq!(nodes[2].tags().collect::<Vec<_>>(), vec![(5, 6)]);
+
+        let info = nodes[2].info.clone().unwrap();
+        assert_eq!((info.version, info.timestamp, info.changeset, info.uid, info.user_sid), (3, 1005, 8, 2, 2));
+        assert!(info.visible);
+        assert_eq!(nodes[0].to_node().keys, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_iter_without_tags_or_info() {
+        let dense = DenseNodes { id: vec![1, 1], lat: vec![1, 1], lon: vec![2, 2], ..Default::default() };
+        let nodes: Vec<_> = dense.iter(&PrimitiveBlock::default()).collect();
+
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes.iter().all(|n| !n.is_tagged() && n.info.is_none()));
+        assert_eq!(nodes[1].location, LatLon::from_raw(200, 400));
+    }
+}
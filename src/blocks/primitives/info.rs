@@ -5,7 +5,7 @@ pub struct Info {
     #[serde(default)]
     pub version: i32,
 
-    /// Timestamp in milliseconds since epoch
+    /// Timestamp in units of the block's `date_granularity` (see `PrimitiveBlock::timestamp`)
     #[serde(default)]
     pub timestamp: i64,
 
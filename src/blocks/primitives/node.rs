@@ -1,10 +1,14 @@
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::osm_id::NodeId;
 use crate::blocks::primitives::info::Info;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::tags::Tags;
 
 /// Represents an OSM node in sparse format.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Node {
     /// Node ID
-    pub id: i64,
+    pub id: NodeId,
 
     /// Array of key indices into the string table
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -18,23 +22,20 @@ pub struct Node {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub info: Option<Info>,
 
-    /// Latitude in nanodegrees
-    pub lat: i64,
-
-    /// Longitude in nanodegrees
-    pub lon: i64,
+    /// Coordinates in nanodegrees
+    #[serde(flatten)]
+    pub location: LatLon,
 }
 
 impl Node {
     /// Creates a new Node with the given ID and coordinates.
-    pub fn new(id: i64, lat: i64, lon: i64) -> Self {
+    pub fn new(id: impl Into<NodeId>, location: LatLon) -> Self {
         Self {
-            id,
+            id: id.into(),
             keys: Vec::new(),
             vals: Vec::new(),
             info: None,
-            lat,
-            lon,
+            location,
         }
     }
 
@@ -61,12 +62,17 @@ impl Node {
 
     /// Converts latitude from internal representation to degrees.
     pub fn lat_degrees(&self) -> f64 {
-        self.lat as f64 * 1e-9
+        self.location.lat.to_degrees()
     }
 
     /// Converts longitude from internal representation to degrees.
     pub fn lon_degrees(&self) -> f64 {
-        self.lon as f64 * 1e-9
+        self.location.lon.to_degrees()
+    }
+
+    /// Returns the node's tags resolved against the string table of its block.
+    pub fn tags<'a>(&'a self, strings: &'a StringTable) -> Tags<'a> {
+        Tags::new(&self.keys, &self.vals, strings)
     }
 
     /// Returns true if this node has any tags.
@@ -88,11 +94,10 @@ mod tests {
 
     #[test]
     fn test_node_creation() {
-        let node = Node::new(123, 450_000_000, 90_000_000); // 45°N, 9°E
+        let node = Node::new(123, LatLon::from_raw(45_000_000_000, 9_000_000_000)); // 45°N, 9°E
         
         assert_eq!(node.id, 123);
-        assert_eq!(node.lat, 450_000_000);
-        assert_eq!(node.lon, 90_000_000);
+        assert_eq!(node.location, LatLon::from_raw(45_000_000_000, 9_000_000_000));
         assert!(node.keys.is_empty());
         assert!(node.vals.is_empty());
         assert!(node.info.is_none());
@@ -101,7 +106,7 @@ mod tests {
 
     #[test]
     fn test_coordinate_conversion() {
-        let node = Node::new(1, 450_000_000, -90_000_000); // 45°N, 9°W
+        let node = Node::new(1, LatLon::from_raw(45_000_000_000, -9_000_000_000)); // 45°N, 9°W
         
         assert!((node.lat_degrees() - 45.0).abs() < 1e-10);
         assert!((node.lon_degrees() - (-9.0)).abs() < 1e-10);
@@ -109,7 +114,7 @@ mod tests {
 
     #[test]
     fn test_add_tags() {
-        let mut node = Node::new(1, 0, 0);
+        let mut node = Node::new(1, LatLon::from_raw(0, 0));
         
         node.add_tag(1, 2); // highway -> primary
         node.add_tag(3, 4); // name -> "Main Street"
@@ -123,7 +128,7 @@ mod tests {
 
     #[test]
     fn test_clear_tags() {
-        let mut node = Node::new(1, 0, 0);
+        let mut node = Node::new(1, LatLon::from_raw(0, 0));
         node.add_tag(1, 2);
         node.add_tag(3, 4);
         
@@ -135,36 +140,36 @@ mod tests {
 
     #[test]
     fn test_node_with_info() {
-        let mut node = Node::new(1, 0, 0);
+        let mut node = Node::new(1, LatLon::from_raw(0, 0));
         node.info = Some(Info {
-            version: Some(1),
-            timestamp: Some(1609459200),
-            changeset: Some(12345),
-            uid: Some(678),
-            user_sid: Some(5),
-            visible: None,
+            version: 1,
+            timestamp: 1609459200,
+            changeset: 12345,
+            uid: 678,
+            user_sid: 5,
+            visible: true,
         });
         
         assert!(node.info.is_some());
         let info = node.info.as_ref().unwrap();
-        assert_eq!(info.version, Some(1));
-        assert_eq!(info.changeset, Some(12345));
+        assert_eq!(info.version, 1);
+        assert_eq!(info.changeset, 12345);
     }
 
     #[test]
     fn test_extreme_coordinates() {
         // Test maximum valid coordinates
-        let max_lat = 900_000_000; // 90°N
-        let max_lon = 1_800_000_000; // 180°E
-        let node_max = Node::new(1, max_lat, max_lon);
+        let max_lat = 90_000_000_000; // 90°N
+        let max_lon = 180_000_000_000; // 180°E
+        let node_max = Node::new(1, LatLon::from_raw(max_lat, max_lon));
         
         assert!((node_max.lat_degrees() - 90.0).abs() < 1e-10);
         assert!((node_max.lon_degrees() - 180.0).abs() < 1e-10);
         
         // Test minimum valid coordinates
-        let min_lat = -900_000_000; // 90°S
-        let min_lon = -1_800_000_000; // 180°W
-        let node_min = Node::new(2, min_lat, min_lon);
+        let min_lat = -90_000_000_000; // 90°S
+        let min_lon = -180_000_000_000; // 180°W
+        let node_min = Node::new(2, LatLon::from_raw(min_lat, min_lon));
         
         assert!((node_min.lat_degrees() - (-90.0)).abs() < 1e-10);
         assert!((node_min.lon_degrees() - (-180.0)).abs() < 1e-10);
@@ -172,7 +177,7 @@ mod tests {
 
     #[test]
     fn test_serialization() {
-        let mut node = Node::new(123, 450_000_000, 90_000_000);
+        let mut node = Node::new(123, LatLon::from_raw(45_000_000_000, 9_000_000_000));
         node.add_tag(1, 2);
         
         let serialized = serde_json::to_string(&node).unwrap();
@@ -183,13 +188,13 @@ mod tests {
 
     #[test]
     fn test_clone_and_equality() {
-        let mut node1 = Node::new(1, 100, 200);
+        let mut node1 = Node::new(1, LatLon::from_raw(100, 200));
         node1.add_tag(1, 2);
         
         let node2 = node1.clone();
         assert_eq!(node1, node2);
         
-        let node3 = Node::new(2, 100, 200);
+        let node3 = Node::new(2, LatLon::from_raw(100, 200));
         assert_ne!(node1, node3);
     }
 
@@ -198,7 +203,7 @@ mod tests {
         use std::time::Instant;
         
         let start = Instant::now();
-        let mut node = Node::new(1, 0, 0);
+        let mut node = Node::new(1, LatLon::from_raw(0, 0));
         
         // Add 1000 tags
         for i in 0..1000 {
@@ -225,7 +230,7 @@ mod tests {
         use std::time::Instant;
         
         let nodes: Vec<Node> = (0..10_000)
-            .map(|i| Node::new(i, (i * 100) as i64, (i * 200) as i64))
+            .map(|i| Node::new(i, LatLon::from_raw((i * 100) as i64, (i * 200) as i64)))
             .collect();
         
         let start = Instant::now();
@@ -242,10 +247,10 @@ mod tests {
     #[test]
     fn test_high_precision_coordinates() {
         // Test nanodegree precision
-        let precise_lat = 450_123_456; // ~45.0123456°
-        let precise_lon = 90_987_654;  // ~9.0987654°
+        let precise_lat = 45_012_345_600; // 45.0123456°
+        let precise_lon = 9_098_765_400;  // 9.0987654°
         
-        let node = Node::new(1, precise_lat, precise_lon);
+        let node = Node::new(1, LatLon::from_raw(precise_lat, precise_lon));
         let lat_deg = node.lat_degrees();
         let lon_deg = node.lon_degrees();
         
@@ -256,7 +261,7 @@ mod tests {
 
     #[test]
     fn test_empty_tag_arrays() {
-        let node = Node::new(1, 0, 0);
+        let node = Node::new(1, LatLon::from_raw(0, 0));
         
         assert_eq!(node.keys.len(), 0);
         assert_eq!(node.vals.len(), 0);
@@ -267,26 +272,26 @@ mod tests {
     #[test]
     fn test_large_node_ids() {
         let large_id = i64::MAX;
-        let node = Node::new(large_id, 0, 0);
+        let node = Node::new(large_id, LatLon::from_raw(0, 0));
         assert_eq!(node.id, large_id);
         
         let negative_id = i64::MIN;
-        let node_neg = Node::new(negative_id, 0, 0);
+        let node_neg = Node::new(negative_id, LatLon::from_raw(0, 0));
         assert_eq!(node_neg.id, negative_id);
     }
 
     #[test]
     fn test_tag_consistency() {
-        let mut node = Node::new(1, 0, 0);
+        let mut node = Node::new(1, LatLon::from_raw(0, 0));
         
         // Add multiple tags and verify consistency
-        for i in 0..100 {
+        for i in 0..100u32 {
             node.add_tag(i, i + 100);
         }
         
         // Verify all tags are correctly stored
-        for i in 0..100 {
-            let tag = node.get_tag(i).unwrap();
+        for i in 0..100u32 {
+            let tag = node.get_tag(i as usize).unwrap();
             assert_eq!(tag.0, i);
             assert_eq!(tag.1, i + 100);
         }
@@ -297,7 +302,7 @@ mod tests {
 
     #[test]
     fn test_memory_efficiency() {
-        let node = Node::new(1, 0, 0);
+        let node = Node::new(1, LatLon::from_raw(0, 0));
         
         // Check that empty vectors don't waste too much space
         assert_eq!(node.keys.len(), 0);
@@ -1,11 +1,15 @@
+use crate::blocks::osm_id::{NodeId, RelationId, WayId};
 use crate::blocks::primitives::info::Info;
 use crate::blocks::primitives::member_type::MemberType;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::tags::Tags;
+use crate::io::delta::{delta_decoded, DeltaDecoded};
 
 /// Represents an OSM relation.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Relation {
     /// Relation ID
-    pub id: i64,
+    pub id: RelationId,
 
     /// Array of key indices into the string table
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -31,3 +35,138 @@ pub struct Relation {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub types: Vec<MemberType>,
 }
+
+impl Relation {
+    /// Returns the relation's tags resolved against the string table of its block.
+    pub fn tags<'a>(&'a self, strings: &'a StringTable) -> Tags<'a> {
+        Tags::new(&self.keys, &self.vals, strings)
+    }
+
+    /// Returns the ids of the relation's members, decoded from the delta-encoded memids.
+    pub fn member_ids(&self) -> DeltaDecoded<'_, i64> {
+        delta_decoded(&self.memids)
+    }
+
+    /// Returns the relation's members, combining the parallel `memids`, `types` and
+    /// `roles_sid` arrays. If the arrays differ in length, only the members present
+    /// in all three are returned.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use osm_pbf::{MemberRef, MemberType, Relation, RelationId, StringTable};
+    ///
+    /// let mut strings = StringTable::new();
+    /// let outer = strings.add_string("outer".into()) as i32;
+    /// let relation = Relation {
+    ///     id: RelationId(1),
+    ///     keys: vec![],
+    ///     vals: vec![],
+    ///     info: None,
+    ///     roles_sid: vec![outer, 0],
+    ///     memids: vec![20, -10],
+    ///     types: vec![MemberType::Way, MemberType::Node],
+    /// };
+    ///
+    /// let first = relation.members().next().unwrap();
+    /// assert_eq!(first, MemberRef { member_type: MemberType::Way, id: 20, role_index: outer as u32 });
+    /// let roles: Vec<_> = relation.members_with_roles(&strings).map(|(member, role)| (member.id, role)).collect();
+    /// assert_eq!(roles, [(20, "outer"), (10, "")]);
+    /// ```
+    pub fn members(&self) -> Members<'_> {
+        Members { ids: self.member_ids(), types: self.types.iter(), roles: self.roles_sid.iter() }
+    }
+
+    /// Returns the relation's members together with their roles resolved against
+    /// the string table of its block.
+    pub fn members_with_roles<'a>(&'a self, strings: &'a StringTable) -> impl Iterator<Item = (MemberRef, &'a str)> + 'a {
+        self.members().map(move |member| (member, member.role(strings)))
+    }
+}
+
+/// A relation member, decoded from the parallel member arrays by `Relation::members`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemberRef {
+    /// Type of the member
+    pub member_type: MemberType,
+    /// Id of the member, of the element type given by `member_type`
+    pub id: i64,
+    /// Role (index into the string table)
+    pub role_index: u32,
+}
+
+impl MemberRef {
+    /// Returns the member's role resolved against the string table of its block.
+    pub fn role<'a>(&self, strings: &'a StringTable) -> &'a str {
+        strings.get_string_or_empty(self.role_index as usize)
+    }
+
+    /// Returns the member's id if it is a node.
+    pub fn node_id(&self) -> Option<NodeId> {
+        (self.member_type == MemberType::Node).then_some(NodeId(self.id))
+    }
+
+    /// Returns the member's id if it is a way.
+    pub fn way_id(&self) -> Option<WayId> {
+        (self.member_type == MemberType::Way).then_some(WayId(self.id))
+    }
+
+    /// Returns the member's id if it is a relation.
+    pub fn relation_id(&self) -> Option<RelationId> {
+        (self.member_type == MemberType::Relation).then_some(RelationId(self.id))
+    }
+}
+
+/// Iterator over the members of a relation, created by `Relation::members`.
+#[derive(Debug, Clone)]
+pub struct Members<'a> {
+    ids: DeltaDecoded<'a, i64>,
+    types: std::slice::Iter<'a, MemberType>,
+    roles: std::slice::Iter<'a, i32>,
+}
+
+impl Iterator for Members<'_> {
+    type Item = MemberRef;
+
+    fn next(&mut self) -> Option<MemberRef> {
+        let (id, member_type, role) = (self.ids.next()?, self.types.next()?, self.roles.next()?);
+        Some(MemberRef { member_type: *member_type, id, role_index: *role as u32 })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.ids.len().min(self.types.len()).min(self.roles.len());
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Members<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_members() {
+        let mut strings = StringTable::new();
+        let (outer, inner) = (strings.add_string("outer".into()) as i32, strings.add_string("inner".into()) as i32);
+        let mut relation = Relation {
+            id: RelationId(1),
+            keys: vec![],
+            vals: vec![],
+            info: None,
+            roles_sid: vec![outer, inner, 0],
+            memids: vec![7, 3, -9],
+            types: vec![MemberType::Way, MemberType::Way, MemberType::Relation],
+        };
+
+        let members: Vec<_> = relation.members_with_roles(&strings).map(|(member, role)| (member.member_type, member.id, role)).collect();
+        assert_eq!(members, [(MemberType::Way, 7, "outer"), (MemberType::Way, 10, "inner"), (MemberType::Relation, 1, "")]);
+        let ways: Vec<_> = relation.members().filter_map(|member| member.way_id()).collect();
+        assert_eq!(ways, [WayId(7), WayId(10)]);
+        assert_eq!(relation.members().filter_map(|member| member.relation_id()).collect::<Vec<_>>(), [RelationId(1)]);
+
+        // Members missing from one of the parallel arrays are left out
+        relation.types.pop();
+        assert_eq!(relation.members().len(), 2);
+        assert_eq!(relation.members().last().map(|member| member.id), Some(10));
+    }
+}
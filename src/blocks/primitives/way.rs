@@ -1,10 +1,16 @@
+use crate::blocks::area::AreaRules;
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::osm_id::WayId;
 use crate::blocks::primitives::info::Info;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::tags::Tags;
+use crate::io::delta::{delta_decoded, DeltaDecoded};
 
 /// Represents an OSM way.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Way {
     /// Way ID
-    pub id: i64,
+    pub id: WayId,
 
     /// Array of key indices into the string table
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -21,4 +27,88 @@ pub struct Way {
     /// Delta-encoded node references
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub refs: Vec<i64>,
+
+    /// Delta-encoded latitudes of the referenced nodes, in files with the
+    /// `LocationsOnWays` feature (parallel to refs)
+    ///
+    /// In granularity units in a block, in nanodegrees once decoded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lat: Vec<i64>,
+
+    /// Delta-encoded longitudes of the referenced nodes (parallel to refs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lon: Vec<i64>,
+}
+
+impl Way {
+    /// Returns true if the way starts and ends at the same node (at least 4 refs).
+    pub fn is_closed(&self) -> bool {
+        // With delta-encoded refs the ring is closed when the deltas after the first sum to zero
+        self.refs.len() >= 4 && self.refs[1..].iter().fold(0i64, |sum, delta| sum.wrapping_add(*delta)) == 0
+    }
+
+    /// Returns the node locations stored on a decoded way, or None if the way
+    /// carries none for some of its nodes.
+    pub fn locations(&self) -> Option<Vec<LatLon>> {
+        if self.lat.len() != self.refs.len() || self.lon.len() != self.refs.len() || self.refs.is_empty() {
+            return None;
+        }
+        let locations = delta_decoded(&self.lat).zip(delta_decoded(&self.lon));
+        Some(locations.map(|(lat, lon)| LatLon::from_raw(lat, lon)).collect())
+    }
+
+    /// Returns the ids of the way's nodes, decoded from the delta-encoded refs.
+    pub fn node_ids(&self) -> DeltaDecoded<'_, i64> {
+        delta_decoded(&self.refs)
+    }
+
+    /// Returns the way's tags resolved against the string table of its block.
+    pub fn tags<'a>(&'a self, strings: &'a StringTable) -> Tags<'a> {
+        Tags::new(&self.keys, &self.vals, strings)
+    }
+
+    /// Returns true if the way should be treated as a polygon rather than a linestring,
+    /// using the standard `AreaRules`.
+    pub fn is_area(&self, strings: &StringTable) -> bool {
+        self.is_area_with(strings, AreaRules::standard())
+    }
+
+    /// Returns true if the way is closed and its tags make it an area under `rules`.
+    pub fn is_area_with(&self, strings: &StringTable, rules: &AreaRules) -> bool {
+        self.is_closed() && rules.is_area_tags(self.tags(strings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn way_with_tags(refs: Vec<i64>, tags: &[(&str, &str)], strings: &mut StringTable) -> Way {
+        let mut way = Way { id: WayId(1), keys: vec![], vals: vec![], info: None, refs, lat: vec![], lon: vec![] };
+        for (key, value) in tags {
+            way.keys.push(strings.add_string(key.to_string()) as u32);
+            way.vals.push(strings.add_string(value.to_string()) as u32);
+        }
+        way
+    }
+
+    #[test]
+    fn test_is_closed() {
+        let mut strings = StringTable::new();
+        // Refs 10, 11, 12, 10
+        assert!(way_with_tags(vec![10, 1, 1, -2], &[], &mut strings).is_closed());
+        assert!(!way_with_tags(vec![10, 1, 1, -1], &[], &mut strings).is_closed());
+        assert!(!way_with_tags(vec![10, 0], &[], &mut strings).is_closed());
+    }
+
+    #[test]
+    fn test_is_area() {
+        let mut strings = StringTable::new();
+        let ring = vec![10, 1, 1, -2];
+
+        assert!(way_with_tags(ring.clone(), &[("building", "yes")], &mut strings).is_area(&strings));
+        assert!(!way_with_tags(ring.clone(), &[("highway", "residential")], &mut strings).is_area(&strings));
+        assert!(way_with_tags(ring.clone(), &[("highway", "pedestrian"), ("area", "yes")], &mut strings).is_area(&strings));
+        assert!(!way_with_tags(vec![10, 1, 1], &[("building", "yes")], &mut strings).is_area(&strings));
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 /// Represents a string table used in OSM PBF format.
 /// String tables contain an array of UTF-8 strings which are referenced by index
 /// from other parts of the PBF data structure to reduce redundancy.
@@ -52,11 +54,111 @@ impl Default for StringTable {
     }
 }
 
+/// Builds a StringTable holding each string once.
+///
+/// `get_or_insert` hands out the index of a string, adding it on first use
+/// and counting every use. `build` keeps the strings in insertion order;
+/// `build_by_frequency` puts the most used strings first, so they get the
+/// small indices that take a single varint byte, and returns the mapping from
+/// the indices handed out to the final ones.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::StringTableBuilder;
+///
+/// let mut builder = StringTableBuilder::new();
+/// let name = builder.get_or_insert("name");
+/// let highway = builder.get_or_insert("highway");
+/// assert_eq!(builder.get_or_insert("highway"), highway);
+///
+/// let (table, remap) = builder.build_by_frequency();
+/// assert_eq!(table.get_string(remap[highway as usize] as usize), Some("highway"));
+/// assert_eq!((remap[highway as usize], remap[name as usize]), (1, 2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StringTableBuilder {
+    strings: Vec<String>,
+    index: HashMap<String, u32>,
+    uses: Vec<u64>,
+}
+
+impl StringTableBuilder {
+    /// Creates a builder holding only the empty string at index 0.
+    pub fn new() -> Self {
+        Self { strings: vec![String::new()], index: HashMap::new(), uses: vec![0] }
+    }
+
+    /// Returns the index of a string, adding it if new. The empty string is always 0.
+    pub fn get_or_insert(&mut self, string: &str) -> u32 {
+        if string.is_empty() {
+            return 0;
+        }
+        let index = match self.index.get(string) {
+            Some(&index) => index,
+            None => {
+                let index = self.strings.len() as u32;
+                self.strings.push(string.to_string());
+                self.uses.push(0);
+                self.index.insert(string.to_string(), index);
+                index
+            }
+        };
+        self.uses[index as usize] += 1;
+        index
+    }
+
+    /// Returns the index of a string if it was added.
+    pub fn get(&self, string: &str) -> Option<u32> {
+        if string.is_empty() {
+            return Some(0);
+        }
+        self.index.get(string).copied()
+    }
+
+    /// Returns the number of strings, the empty string at index 0 included.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns true if only the empty string was added.
+    pub fn is_empty(&self) -> bool {
+        self.strings.len() <= 1
+    }
+
+    /// Builds the table with the strings in insertion order.
+    pub fn build(self) -> StringTable {
+        StringTable { s: self.strings }
+    }
+
+    /// Builds the table with the strings by descending number of uses, ties in
+    /// insertion order, and returns the new index of each index handed out.
+    pub fn build_by_frequency(self) -> (StringTable, Vec<u32>) {
+        let mut order: Vec<usize> = (1..self.strings.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.uses[index]));
+
+        let mut remap = vec![0u32; self.strings.len()];
+        let mut strings = vec![None; self.strings.len()];
+        for (new, &old) in order.iter().enumerate() {
+            remap[old] = new as u32 + 1;
+        }
+        for (old, string) in self.strings.into_iter().enumerate().skip(1) {
+            strings[remap[old] as usize] = Some(string);
+        }
+        let s = strings.into_iter().map(Option::unwrap_or_default).collect();
+        (StringTable { s }, remap)
+    }
+}
+
+impl Default for StringTableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
 
     #[test]
     fn test_new_string_table() {
@@ -303,6 +405,22 @@ mod tests {
         assert_eq!(st.get_string(0), Some(""));
     }
 
+    #[test]
+    fn test_builder_deduplicates_and_orders_by_frequency() {
+        let mut builder = StringTableBuilder::new();
+        let indices: Vec<u32> = ["name", "highway", "", "primary", "highway", "primary", "highway"]
+            .iter()
+            .map(|s| builder.get_or_insert(s))
+            .collect();
+        assert_eq!(indices, vec![1, 2, 0, 3, 2, 3, 2]);
+        assert_eq!((builder.len(), builder.get("primary"), builder.get("oneway")), (4, Some(3), None));
+        assert_eq!(builder.clone().build().s, vec!["", "name", "highway", "primary"]);
+
+        let (table, remap) = builder.build_by_frequency();
+        assert_eq!(table.s, vec!["", "highway", "primary", "name"]);
+        assert_eq!(remap, vec![0, 3, 1, 2]);
+    }
+
     #[test]
     fn test_string_table_capacity_growth() {
         let mut st = StringTable::new();
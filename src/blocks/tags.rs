@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use crate::blocks::string_table::StringTable;
+
+/// Tags of an element resolved against the string table of its block.
+///
+/// A borrowed view over the parallel `keys`/`vals` index arrays; nothing is
+/// copied until `to_map` is called. Lookups scan the tags, which is faster
+/// than hashing for the handful of tags elements usually carry.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::{StringTable, Way, WayId};
+///
+/// let mut strings = StringTable::new();
+/// let (key, value) = (strings.add_string("highway".into()), strings.add_string("primary".into()));
+/// let way = Way { id: WayId(1), keys: vec![key as u32], vals: vec![value as u32], info: None, refs: vec![], lat: vec![], lon: vec![] };
+///
+/// let tags = way.tags(&strings);
+/// assert_eq!(tags.get("highway"), Some("primary"));
+/// assert!(!tags.contains_key("name"));
+/// assert_eq!(tags.iter().collect::<Vec<_>>(), [("highway", "primary")]);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Tags<'a> {
+    keys: &'a [u32],
+    vals: &'a [u32],
+    strings: &'a StringTable,
+}
+
+impl<'a> Tags<'a> {
+    /// Creates a view over parallel key and value indices into `strings`.
+    pub fn new(keys: &'a [u32], vals: &'a [u32], strings: &'a StringTable) -> Self {
+        Self { keys, vals, strings }
+    }
+
+    /// Iterates over the tags as `(key, value)` strings, in stored order.
+    ///
+    /// Indices outside the string table resolve to the empty string.
+    pub fn iter(&self) -> TagIter<'a> {
+        TagIter { pairs: self.keys.iter().zip(self.vals), strings: self.strings }
+    }
+
+    /// Returns the value of `key`, the first one if the key is repeated.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Returns true if a tag has the key `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of tags.
+    pub fn len(&self) -> usize {
+        self.keys.len().min(self.vals.len())
+    }
+
+    /// Returns true if there are no tags.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the tags into an owned, key-sorted map; for repeated keys the last value wins.
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        self.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+}
+
+impl<'a> IntoIterator for Tags<'a> {
+    type Item = (&'a str, &'a str);
+    type IntoIter = TagIter<'a>;
+
+    fn into_iter(self) -> TagIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over resolved tags, from `Tags::iter`.
+#[derive(Debug, Clone)]
+pub struct TagIter<'a> {
+    pairs: std::iter::Zip<std::slice::Iter<'a, u32>, std::slice::Iter<'a, u32>>,
+    strings: &'a StringTable,
+}
+
+impl<'a> Iterator for TagIter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.pairs.next()?;
+        Some((self.strings.get_string_or_empty(*k as usize), self.strings.get_string_or_empty(*v as usize)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pairs.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_lookup_and_iteration() {
+        let strings = StringTable { s: ["", "amenity", "cafe", "name", "Corner", "bar"].iter().map(|s| s.to_string()).collect() };
+        let (keys, vals) = ([1, 3, 1, 9], [2, 4, 5, 1]);
+        let tags = Tags::new(&keys, &vals, &strings);
+
+        assert_eq!(tags.len(), 4);
+        assert_eq!(tags.get("amenity"), Some("cafe"));
+        assert_eq!(tags.get("name"), Some("Corner"));
+        assert!(tags.contains_key("name") && !tags.contains_key("cafe"));
+        // Out-of-range indices read as empty strings
+        assert_eq!(tags.get(""), Some("amenity"));
+        assert_eq!(tags.to_map().get("amenity").map(String::as_str), Some("bar"));
+        assert_eq!(tags.into_iter().count(), 4);
+        assert!(Tags::new(&[], &[], &strings).is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+/// Point in time in milliseconds since the 1970 epoch.
+///
+/// Blocks store timestamps in units of their `date_granularity`; use
+/// `from_raw` and `to_raw` to convert between the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, serde::Serialize, serde::Deserialize)]
+pub struct TimestampMillis(pub i64);
+
+impl TimestampMillis {
+    /// Creates a timestamp from a whole number of seconds.
+    pub fn from_secs(secs: i64) -> Self {
+        TimestampMillis(secs.saturating_mul(1000))
+    }
+
+    /// Creates a timestamp from a value stored in units of `date_granularity` milliseconds.
+    pub fn from_raw(raw: i64, date_granularity: i32) -> Self {
+        TimestampMillis(raw.saturating_mul(date_granularity as i64))
+    }
+
+    /// Converts the timestamp to units of `date_granularity` milliseconds, rounding to the nearest unit.
+    /// Returns an error if the granularity is not positive.
+    pub fn to_raw(self, date_granularity: i32) -> Result<i64, &'static str> {
+        if date_granularity <= 0 {
+            return Err("Date granularity must be positive");
+        }
+        let granularity = date_granularity as i64;
+        Ok(self.0.saturating_add(granularity / 2).div_euclid(granularity))
+    }
+
+    /// Rounds the timestamp to the nearest multiple of `date_granularity` milliseconds.
+    pub fn quantize(self, date_granularity: i32) -> Result<Self, &'static str> {
+        Ok(Self::from_raw(self.to_raw(date_granularity)?, date_granularity))
+    }
+
+    /// Whole seconds since the epoch, rounded down.
+    pub fn as_secs(self) -> i64 {
+        self.0.div_euclid(1000)
+    }
+
+    /// Milliseconds past the last whole second.
+    pub fn subsec_millis(self) -> u32 {
+        self.0.rem_euclid(1000) as u32
+    }
+
+    /// Parses a UTC timestamp in the `YYYY-MM-DDTHH:MM:SSZ` form used by OSM XML and replication state files.
+    /// Returns `None` for any other form.
+    pub fn parse_iso8601(value: &str) -> Option<Self> {
+        let value = value.strip_suffix('Z')?;
+        let (date, time) = value.split_once('T')?;
+        let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+        let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+        let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+        let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        // Days from 1970-01-01 in the proleptic Gregorian calendar
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        Some(Self::from_secs(days * 86_400 + hour * 3600 + minute * 60 + second))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_raw_conversion() {
+        assert_eq!(TimestampMillis::from_raw(1_609_459_200, 1000), TimestampMillis(1_609_459_200_000));
+        assert_eq!(TimestampMillis::from_raw(1_609_459_200_123, 1), TimestampMillis(1_609_459_200_123));
+        assert_eq!(TimestampMillis(1_609_459_200_123).to_raw(1), Ok(1_609_459_200_123));
+        assert_eq!(TimestampMillis(1_609_459_200_123).to_raw(1000), Ok(1_609_459_200));
+        assert_eq!(TimestampMillis(1_609_459_200_500).to_raw(1000), Ok(1_609_459_201));
+        assert_eq!(TimestampMillis(-1_499).to_raw(1000), Ok(-1));
+        assert!(TimestampMillis(0).to_raw(0).is_err());
+    }
+
+    #[test]
+    fn test_seconds() {
+        let t = TimestampMillis(1_609_459_200_042);
+        assert_eq!((t.as_secs(), t.subsec_millis()), (1_609_459_200, 42));
+        assert_eq!(TimestampMillis(-1).as_secs(), -1);
+        assert_eq!(TimestampMillis(-1).subsec_millis(), 999);
+        assert_eq!(TimestampMillis::from_secs(2), TimestampMillis(2000));
+        assert_eq!(TimestampMillis(59_999).quantize(60_000), Ok(TimestampMillis(60_000)));
+    }
+
+    #[test]
+    fn test_parse_iso8601() {
+        assert_eq!(TimestampMillis::parse_iso8601("1970-01-01T00:00:00Z"), Some(TimestampMillis(0)));
+        assert_eq!(TimestampMillis::parse_iso8601("2000-02-29T23:59:59Z"), Some(TimestampMillis::from_secs(951_868_799)));
+        assert_eq!(TimestampMillis::parse_iso8601("2000-02-29 23:59:59"), None);
+    }
+}
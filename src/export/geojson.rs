@@ -0,0 +1,425 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, Write};
+use crate::blocks::area::{AreaRule, AreaRules};
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::tags::Tags;
+use crate::formats::poly::PolygonRings;
+use crate::io::blob::{BlobError, Result};
+use crate::io::geometry::{NodeLocationCache, NodeLocationStore};
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, Reader};
+
+/// Geometry of a GeoJSON feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Geometry {
+    Point(LatLon),
+    LineString(Vec<LatLon>),
+    /// Outer ring first, then holes; each ring repeats its first point at the end
+    Polygon(Vec<Vec<LatLon>>),
+    MultiPolygon(Vec<Vec<Vec<LatLon>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+impl From<&PolygonRings> for Geometry {
+    fn from(rings: &PolygonRings) -> Self {
+        let close = |ring: &Vec<LatLon>| {
+            let mut ring = ring.clone();
+            if ring.len() > 1 && ring.first() != ring.last() {
+                ring.push(ring[0]);
+            }
+            ring
+        };
+        Geometry::Polygon(std::iter::once(&rings.outer).chain(&rings.inners).map(close).collect())
+    }
+}
+
+/// How elements become GeoJSON features
+///
+/// By default all tags become properties under their own key, coordinates
+/// are written with 7 decimals (the precision of OSM data, about 1 cm) and
+/// closed ways are Polygons where `AreaRules::standard` makes them areas,
+/// LineStrings otherwise.
+#[derive(Debug, Clone)]
+pub struct GeoJsonOptions {
+    precision: usize,
+    keys: Option<HashSet<String>>,
+    property_names: HashMap<String, String>,
+    /// `None` for `AreaRules::standard`
+    area_rules: Option<AreaRules>,
+}
+
+impl Default for GeoJsonOptions {
+    fn default() -> Self {
+        Self { precision: 7, keys: None, property_names: HashMap::new(), area_rules: None }
+    }
+}
+
+impl GeoJsonOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decimals of written coordinates, at most 9; 6 is about 10 cm and keeps files smaller
+    pub fn with_precision(mut self, decimals: u8) -> Self {
+        self.precision = decimals.min(9) as usize;
+        self
+    }
+
+    /// Keep only these tag keys as properties
+    pub fn with_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.keys.get_or_insert_with(HashSet::new).extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Write the tag `key` as the property `name`
+    pub fn with_property_name(mut self, key: impl Into<String>, name: impl Into<String>) -> Self {
+        self.property_names.insert(key.into(), name.into());
+        self
+    }
+
+    /// Decide which closed ways are Polygons with `rules` instead of
+    /// `AreaRules::standard`
+    pub fn with_area_rules(mut self, rules: AreaRules) -> Self {
+        self.area_rules = Some(rules);
+        self
+    }
+
+    /// Also write closed ways carrying one of these keys as Polygons, e.g.
+    /// `highway` for pedestrian squares; `area=yes` and `area=no` always decide
+    pub fn with_area_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut rules = self.area_rules.take().unwrap_or_else(|| AreaRules::standard().clone());
+        for key in keys {
+            rules = rules.with_rule(&key.into(), AreaRule::Any);
+        }
+        self.area_rules = Some(rules);
+        self
+    }
+
+    /// Whether a way with these tags and coordinates is an area
+    fn is_area(&self, tags: Tags, coords: &[LatLon]) -> bool {
+        let closed = coords.len() >= 4 && coords.first() == coords.last();
+        closed && self.area_rules.as_ref().unwrap_or_else(|| AreaRules::standard()).is_area_tags(tags)
+    }
+}
+
+/// Streams GeoJSON features into a FeatureCollection
+///
+/// Each feature is written as soon as it is passed in, so memory use doesn't
+/// grow with the output. Feature ids follow the `node/1`, `way/2`,
+/// `relation/3` convention of web map tooling. The collection is only
+/// complete once `finish` has written its closing brackets.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::export::geojson::{GeoJsonOptions, GeoJsonWriter};
+/// use osm_pbf::{LatLon, Node, StringTable};
+///
+/// let mut strings = StringTable::new();
+/// let mut node = Node::new(1, LatLon::from_raw(52_516_275_000, 13_377_704_000));
+/// node.add_tag(strings.add_string("name".into()) as u32, strings.add_string("Brandenburger Tor".into()) as u32);
+///
+/// let mut writer = GeoJsonWriter::new(Vec::new(), GeoJsonOptions::new().with_precision(5))?;
+/// writer.write_node(&node, &strings)?;
+/// let json = String::from_utf8(writer.finish()?).unwrap();
+/// assert!(json.contains(r#""coordinates":[13.3777,52.51628]"#));
+/// assert!(json.contains(r#""properties":{"name":"Brandenburger Tor"}"#));
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub struct GeoJsonWriter<W: Write> {
+    writer: W,
+    options: GeoJsonOptions,
+    features: u64,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> GeoJsonWriter<W> {
+    /// Start a FeatureCollection on `writer`
+    pub fn new(mut writer: W, options: GeoJsonOptions) -> Result<Self> {
+        writer.write_all(br#"{"type":"FeatureCollection","features":["#)?;
+        Ok(Self { writer, options, features: 0, buffer: Vec::new() })
+    }
+
+    /// Write a node as a Point
+    pub fn write_node(&mut self, node: &Node, strings: &StringTable) -> Result<()> {
+        self.write_feature(&format!("node/{}", node.id), &Geometry::Point(node.location), node.tags(strings))
+    }
+
+    /// Write a way with the locations of its nodes, as a LineString or, for
+    /// areas (see `GeoJsonOptions::with_area_rules`), a Polygon
+    pub fn write_way(&mut self, way: &Way, coords: &[LatLon], strings: &StringTable) -> Result<()> {
+        let tags = way.tags(strings);
+        let geometry = if self.options.is_area(tags, coords) {
+            Geometry::Polygon(vec![coords.to_vec()])
+        } else {
+            Geometry::LineString(coords.to_vec())
+        };
+        self.write_feature(&format!("way/{}", way.id), &geometry, tags)
+    }
+
+    /// Write a relation with a geometry assembled from its members
+    pub fn write_relation(&mut self, relation: &Relation, geometry: &Geometry, strings: &StringTable) -> Result<()> {
+        self.write_feature(&format!("relation/{}", relation.id), geometry, relation.tags(strings))
+    }
+
+    /// Write a feature with tags mapped to properties as configured
+    pub fn write_feature(&mut self, id: &str, geometry: &Geometry, tags: Tags) -> Result<()> {
+        let mut out = std::mem::take(&mut self.buffer);
+        out.clear();
+        if self.features > 0 {
+            out.push(b',');
+        }
+        out.extend_from_slice(br#"{"type":"Feature","id":"#);
+        json_string(&mut out, id)?;
+        out.extend_from_slice(br#","geometry":"#);
+        self.write_geometry(&mut out, geometry);
+        out.extend_from_slice(br#","properties":{"#);
+        let mut first = true;
+        for (key, value) in tags {
+            if self.options.keys.as_ref().is_some_and(|keys| !keys.contains(key)) {
+                continue;
+            }
+            if !std::mem::take(&mut first) {
+                out.push(b',');
+            }
+            json_string(&mut out, self.options.property_names.get(key).map_or(key, String::as_str))?;
+            out.push(b':');
+            json_string(&mut out, value)?;
+        }
+        out.extend_from_slice(b"}}");
+
+        self.writer.write_all(&out)?;
+        self.buffer = out;
+        self.features += 1;
+        Ok(())
+    }
+
+    /// Number of features written so far
+    pub fn features_written(&self) -> u64 {
+        self.features
+    }
+
+    /// Close the FeatureCollection and return the sink
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.write_all(b"]}\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_geometry(&self, out: &mut Vec<u8>, geometry: &Geometry) {
+        let kind = match geometry {
+            Geometry::Point(_) => "Point",
+            Geometry::LineString(_) => "LineString",
+            Geometry::Polygon(_) => "Polygon",
+            Geometry::MultiPolygon(_) => "MultiPolygon",
+            Geometry::GeometryCollection(_) => "GeometryCollection",
+        };
+        out.extend_from_slice(br#"{"type":""#);
+        out.extend_from_slice(kind.as_bytes());
+        if let Geometry::GeometryCollection(geometries) = geometry {
+            out.extend_from_slice(br#"","geometries":"#);
+            list(out, geometries, |out, geometry| self.write_geometry(out, geometry));
+        } else {
+            out.extend_from_slice(br#"","coordinates":"#);
+            match geometry {
+                Geometry::Point(point) => self.position(out, *point),
+                Geometry::LineString(line) => self.positions(out, line),
+                Geometry::Polygon(rings) => self.rings(out, rings),
+                Geometry::MultiPolygon(polygons) => list(out, polygons, |out, rings| self.rings(out, rings)),
+                Geometry::GeometryCollection(_) => unreachable!(),
+            }
+        }
+        out.push(b'}');
+    }
+
+    fn rings(&self, out: &mut Vec<u8>, rings: &[Vec<LatLon>]) {
+        list(out, rings, |out, ring| self.positions(out, ring));
+    }
+
+    fn positions(&self, out: &mut Vec<u8>, points: &[LatLon]) {
+        list(out, points, |out, point| self.position(out, *point));
+    }
+
+    /// `[lon,lat]` in degrees, without trailing zeros
+    fn position(&self, out: &mut Vec<u8>, point: LatLon) {
+        let (lat, lon) = point.to_degrees();
+        out.push(b'[');
+        self.number(out, lon);
+        out.push(b',');
+        self.number(out, lat);
+        out.push(b']');
+    }
+
+    fn number(&self, out: &mut Vec<u8>, value: f64) {
+        let text = format!("{value:.precision$}", precision = self.options.precision);
+        let text = if text.contains('.') { text.trim_end_matches('0').trim_end_matches('.') } else { &text };
+        out.extend_from_slice(if text == "-0" { "0" } else { text }.as_bytes());
+    }
+}
+
+fn list<T>(out: &mut Vec<u8>, items: &[T], mut item: impl FnMut(&mut Vec<u8>, &T)) {
+    out.push(b'[');
+    for (index, value) in items.iter().enumerate() {
+        if index > 0 {
+            out.push(b',');
+        }
+        item(out, value);
+    }
+    out.push(b']');
+}
+
+fn json_string(out: &mut Vec<u8>, value: &str) -> Result<()> {
+    serde_json::to_writer(out, value).map_err(|e| BlobError::Io(e.into()))
+}
+
+/// What `write_geojson` wrote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GeoJsonStats {
+    pub nodes: u64,
+    pub ways: u64,
+    /// Ways left out because a node location was missing
+    pub ways_incomplete: u64,
+    /// Relations left out; assemble their geometry and use `GeoJsonWriter::write_relation`
+    pub relations_skipped: u64,
+}
+
+/// Write the tagged nodes and the ways matching `filter` as a GeoJSON
+/// FeatureCollection
+///
+/// Untagged nodes are left out, as they only place way vertices. Unless the
+/// file carries locations on ways, node locations are first read into a
+/// `NodeLocationCache::Dense`, which suits extracts rather than the planet.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::export::geojson::{write_geojson, GeoJsonOptions};
+/// use osm_pbf::{ElementFilter, Reader};
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+/// let filter = ElementFilter::all().with_tag_key("building".to_string());
+/// let options = GeoJsonOptions::new().with_keys(["building", "name"]).with_precision(6);
+/// let stats = write_geojson(&mut reader, &filter, BufWriter::new(File::create("buildings.geojson")?), &options)?;
+/// println!("{} buildings", stats.ways);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn write_geojson<R: Read + Seek, W: Write>(
+    reader: &mut Reader<R>,
+    filter: &ElementFilter,
+    writer: W,
+    options: &GeoJsonOptions,
+) -> Result<GeoJsonStats> {
+    let locations = if filter.include_ways && !reader.has_locations_on_ways() {
+        Some(NodeLocationCache::build_dense(reader)?)
+    } else {
+        None
+    };
+    let mut output = GeoJsonWriter::new(writer, options.clone())?;
+    let mut stats = GeoJsonStats::default();
+    reader.for_each_filtered_with_strings(filter, |element, strings| {
+        match element {
+            OsmElement::Node(node) if node.has_tags() => {
+                output.write_node(&node, strings)?;
+                stats.nodes += 1;
+            }
+            OsmElement::Way(way) => {
+                let coords = way.locations().or_else(|| locations.as_ref()?.way_locations(&way));
+                match coords {
+                    Some(coords) => {
+                        output.write_way(&way, &coords, strings)?;
+                        stats.ways += 1;
+                    }
+                    None => stats.ways_incomplete += 1,
+                }
+            }
+            OsmElement::Relation(_) => stats.relations_skipped += 1,
+            _ => {}
+        }
+        Ok(())
+    })?;
+    output.finish()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::WayId;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use std::io::Cursor;
+
+    fn point(lat: f64, lon: f64) -> LatLon {
+        LatLon::try_from_degrees(lat, lon).unwrap()
+    }
+
+    #[test]
+    fn test_features_and_options() {
+        let strings = StringTable { s: ["", "building", "yes", "name", "Hall \"A\"", "height"].iter().map(|s| s.to_string()).collect() };
+        let square = vec![point(1.0, 2.0), point(1.0, 2.5), point(1.5, 2.5), point(1.0, 2.0)];
+        let way = Way { id: WayId(4), keys: vec![1, 3, 5], vals: vec![2, 4, 2], info: None, refs: vec![], lat: vec![], lon: vec![] };
+        let options = GeoJsonOptions::new().with_precision(2).with_keys(["building", "name"]).with_property_name("name", "label").with_area_keys(["building"]);
+
+        let mut writer = GeoJsonWriter::new(Vec::new(), options.clone()).unwrap();
+        writer.write_way(&way, &square, &strings).unwrap();
+        writer.write_way(&way, &square[..3], &strings).unwrap();
+        let rings = PolygonRings { outer: square[..3].to_vec(), inners: vec![] };
+        let collection = Geometry::GeometryCollection(vec![Geometry::Point(point(-0.001, 0.123456)), (&rings).into()]);
+        writer.write_feature("relation/9", &collection, Tags::new(&[], &[], &strings)).unwrap();
+        assert_eq!(writer.features_written(), 3);
+        let output: Value = serde_json::from_slice(&writer.finish().unwrap()).unwrap();
+
+        let features = output["features"].as_array().unwrap();
+        assert_eq!(features[0], json!({
+            "type": "Feature",
+            "id": "way/4",
+            "geometry": { "type": "Polygon", "coordinates": [[[2, 1], [2.5, 1], [2.5, 1.5], [2, 1]]] },
+            "properties": { "building": "yes", "label": "Hall \"A\"" },
+        }));
+        assert_eq!(features[1]["geometry"]["type"], "LineString");
+        assert_eq!(features[2]["geometry"]["geometries"][0]["coordinates"], json!([0.12, 0]));
+        assert_eq!(features[2]["geometry"]["geometries"][1]["coordinates"][0].as_array().unwrap().len(), 4);
+        assert_eq!(features[2]["properties"], json!({}));
+
+        // Area keys extend the standard rules rather than replacing them
+        let strings = StringTable { s: ["", "building", "yes", "highway", "pedestrian"].iter().map(|s| s.to_string()).collect() };
+        let building = Way { id: WayId(5), keys: vec![1], vals: vec![2], info: None, refs: vec![], lat: vec![], lon: vec![] };
+        let square_way = Way { id: WayId(6), keys: vec![3], vals: vec![4], info: None, refs: vec![], lat: vec![], lon: vec![] };
+        let geometries = |options: GeoJsonOptions| {
+            let mut writer = GeoJsonWriter::new(Vec::new(), options).unwrap();
+            writer.write_way(&building, &square, &strings).unwrap();
+            writer.write_way(&square_way, &square, &strings).unwrap();
+            let output: Value = serde_json::from_slice(&writer.finish().unwrap()).unwrap();
+            output["features"].as_array().unwrap().iter().map(|f| f["geometry"]["type"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(geometries(GeoJsonOptions::new()), ["Polygon", "LineString"]);
+        assert_eq!(geometries(GeoJsonOptions::new().with_area_keys(["highway"])), ["Polygon", "Polygon"]);
+        assert_eq!(geometries(GeoJsonOptions::new().with_area_rules(AreaRules::empty())), ["LineString", "LineString"]);
+    }
+
+    #[test]
+    fn test_write_geojson() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(5).grid_size(8).block_size(30).relation_count(2).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let filter = ElementFilter::all().with_tag_key("highway".to_string());
+        let mut out = Vec::new();
+        let stats = write_geojson(&mut reader, &filter, &mut out, &GeoJsonOptions::new()).unwrap();
+        assert_eq!(stats, GeoJsonStats { nodes: 0, ways: planet.ways, ways_incomplete: 0, relations_skipped: 0 });
+
+        let output: Value = serde_json::from_slice(&out).unwrap();
+        let features = output["features"].as_array().unwrap();
+        assert_eq!(features.len() as u64, planet.ways);
+        assert!(features.iter().all(|f| f["geometry"]["type"] == "LineString" && f["properties"]["highway"].is_string()));
+    }
+}
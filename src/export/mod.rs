@@ -0,0 +1,2 @@
+#[cfg(feature = "json")]
+pub mod geojson;
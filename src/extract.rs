@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+use std::io::{Read, Seek, Write};
+
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::Result;
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::polygon::Polygon;
+use crate::io::reader::{OsmElement, Reader};
+use crate::io::writer::{BlockBuffer, PbfWriter};
+
+/// Maximum number of elements per block written by `extract`
+pub const BLOCK_SIZE: usize = 8000;
+
+/// Area cut out by `extract`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Region {
+    BBox(BoundingBox),
+    /// Polygon with holes, e.g. from a .poly file
+    Polygon(Polygon),
+}
+
+impl Region {
+    /// Whether `point` lies inside the region; bbox edges are included
+    pub fn contains(&self, point: LatLon) -> bool {
+        match self {
+            Region::BBox(bbox) => bbox.contains(point),
+            Region::Polygon(polygon) => polygon.contains(point),
+        }
+    }
+
+    /// Extent of the region
+    pub fn bbox(&self) -> BoundingBox {
+        match self {
+            Region::BBox(bbox) => *bbox,
+            Region::Polygon(polygon) => polygon.bbox(),
+        }
+    }
+}
+
+/// Which elements beyond the nodes in the region `extract` keeps, after the
+/// strategies of `osmium extract`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtractStrategy {
+    /// Nodes in the region, ways with one of those nodes and relations with
+    /// one of those nodes or ways as member, in a single pass. Ways miss
+    /// their nodes outside the region.
+    Simple,
+    /// Like `Simple`, but ways keep all their nodes, so every way in the
+    /// output is complete
+    #[default]
+    CompleteWays,
+    /// Like `CompleteWays`, and multipolygon relations in the output keep
+    /// all their member ways with their nodes, so their areas can be built
+    Smart,
+}
+
+/// What `extract` wrote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtractStats {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    /// Passes over the input
+    pub passes: u32,
+}
+
+/// Cut a region out of a file, writing a PBF with `header`
+///
+/// The input must be sorted by type, nodes before ways before relations, as
+/// files declaring `Sort.Type_then_ID` are; the output keeps its order.
+/// Relations are kept when they have a kept node or way as member, or a
+/// relation kept before them. Kept ids are held in memory, about 16 bytes
+/// per element in the output.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::extract::{extract, ExtractStrategy, Region};
+/// use osm_pbf::formats::poly::PolyFile;
+/// use osm_pbf::{HeaderBlock, Polygon, Reader};
+/// use std::fs::File;
+///
+/// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+/// let region = Region::Polygon(Polygon::from_poly(&PolyFile::read(File::open("berlin.poly")?)?)?);
+/// let output = File::create("berlin.osm.pbf")?;
+/// let stats = extract(&mut reader, &region, ExtractStrategy::Smart, &HeaderBlock::default(), output)?;
+/// println!("{} nodes, {} ways, {} relations", stats.nodes, stats.ways, stats.relations);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn extract<R: Read + Seek, W: Write>(
+    reader: &mut Reader<R>,
+    region: &Region,
+    strategy: ExtractStrategy,
+    header: &HeaderBlock,
+    output: W,
+) -> Result<ExtractStats> {
+    let mut output = Output::new(output, header)?;
+    let mut kept = Kept::default();
+
+    reader.for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| {
+        if kept.select(&element, strings, region, strategy) && strategy == ExtractStrategy::Simple {
+            output.write(&element, strings)?;
+        }
+        Ok(())
+    })?;
+    output.stats.passes = 1;
+    if strategy == ExtractStrategy::Simple {
+        return output.finish();
+    }
+
+    // Member ways of multipolygons come before the relations naming them
+    if !kept.member_ways.is_empty() {
+        reader.for_each_filtered(&ElementFilter::ways_only(false), |element| {
+            if let OsmElement::Way(way) = element
+                && kept.member_ways.contains(&way.id.0)
+            {
+                kept.way_nodes.extend(way.node_ids());
+            }
+            Ok(())
+        })?;
+        output.stats.passes += 1;
+    }
+
+    reader.for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| {
+        if kept.contains(&element) {
+            output.write(&element, strings)?;
+        }
+        Ok(())
+    })?;
+    output.stats.passes += 1;
+    output.finish()
+}
+
+/// Ids of the elements selected for the output
+#[derive(Default)]
+struct Kept {
+    /// Nodes in the region
+    nodes: HashSet<i64>,
+    /// Nodes of kept ways outside the region
+    way_nodes: HashSet<i64>,
+    ways: HashSet<i64>,
+    /// Ways of kept multipolygons that aren't in `ways`
+    member_ways: HashSet<i64>,
+    relations: HashSet<i64>,
+}
+
+impl Kept {
+    /// Decide whether the element is kept in the first pass, recording what
+    /// it pulls in under `strategy`
+    fn select(&mut self, element: &OsmElement, strings: &StringTable, region: &Region, strategy: ExtractStrategy) -> bool {
+        match element {
+            OsmElement::Node(node) => region.contains(node.location) && self.nodes.insert(node.id.0),
+            OsmElement::Way(way) => {
+                let refs: Vec<i64> = way.node_ids().collect();
+                if !refs.iter().any(|id| self.nodes.contains(id)) {
+                    return false;
+                }
+                if strategy != ExtractStrategy::Simple {
+                    self.way_nodes.extend(refs.into_iter().filter(|id| !self.nodes.contains(id)));
+                }
+                self.ways.insert(way.id.0)
+            }
+            OsmElement::Relation(relation) => {
+                let members: Vec<_> = relation.members().collect();
+                let keep = members.iter().any(|member| match member.member_type {
+                    MemberType::Node => self.nodes.contains(&member.id),
+                    MemberType::Way => self.ways.contains(&member.id),
+                    MemberType::Relation => self.relations.contains(&member.id),
+                });
+                if !keep {
+                    return false;
+                }
+                if strategy == ExtractStrategy::Smart && relation.tags(strings).get("type") == Some("multipolygon") {
+                    let ways = members.iter().filter_map(|member| member.way_id()).map(|id| id.0);
+                    self.member_ways.extend(ways.filter(|id| !self.ways.contains(id)));
+                }
+                self.relations.insert(relation.id.0)
+            }
+            OsmElement::ChangeSet(_) => false,
+        }
+    }
+
+    /// Whether the element is in the output once all passes are done
+    fn contains(&self, element: &OsmElement) -> bool {
+        match element {
+            OsmElement::Node(node) => self.nodes.contains(&node.id.0) || self.way_nodes.contains(&node.id.0),
+            OsmElement::Way(way) => self.ways.contains(&way.id.0) || self.member_ways.contains(&way.id.0),
+            OsmElement::Relation(relation) => self.relations.contains(&relation.id.0),
+            OsmElement::ChangeSet(_) => false,
+        }
+    }
+}
+
+struct Output<W: Write> {
+    writer: PbfWriter<W>,
+    block: BlockBuffer,
+    stats: ExtractStats,
+}
+
+impl<W: Write> Output<W> {
+    fn new(output: W, header: &HeaderBlock) -> Result<Self> {
+        let mut writer = PbfWriter::new(output);
+        writer.write_header(header)?;
+        Ok(Self { writer, block: BlockBuffer::default(), stats: ExtractStats::default() })
+    }
+
+    fn write(&mut self, element: &OsmElement, strings: &StringTable) -> Result<()> {
+        match element {
+            OsmElement::Node(_) => self.stats.nodes += 1,
+            OsmElement::Way(_) => self.stats.ways += 1,
+            _ => self.stats.relations += 1,
+        }
+        self.block.push(element, strings);
+        if self.block.len() >= BLOCK_SIZE {
+            self.writer.write_primitive_block(&std::mem::take(&mut self.block).finish())?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ExtractStats> {
+        if self.block.len() > 0 {
+            self.writer.write_primitive_block(&self.block.finish())?;
+        }
+        self.writer.flush()?;
+        Ok(self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::{RelationId, WayId};
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeSet;
+    use std::io::Cursor;
+
+    /// Node ids, ways with their refs and relation ids of a file
+    fn contents(data: Vec<u8>) -> (BTreeSet<i64>, Vec<(i64, Vec<i64>)>, Vec<i64>) {
+        let (mut nodes, mut ways, mut relations) = (BTreeSet::new(), Vec::new(), Vec::new());
+        Reader::new(Cursor::new(data)).unwrap().for_each_filtered(&ElementFilter::all(), |element| {
+            match element {
+                OsmElement::Node(node) => {
+                    nodes.insert(node.id.0);
+                }
+                OsmElement::Way(way) => ways.push((way.id.0, way.node_ids().collect())),
+                OsmElement::Relation(relation) => relations.push(relation.id.0),
+                OsmElement::ChangeSet(_) => {}
+            }
+            Ok(())
+        }).unwrap();
+        (nodes, ways, relations)
+    }
+
+    fn run(data: &[u8], region: &Region, strategy: ExtractStrategy) -> (ExtractStats, Vec<u8>) {
+        let mut reader = Reader::new(Cursor::new(data.to_vec())).unwrap();
+        let mut output = Vec::new();
+        let stats = extract(&mut reader, region, strategy, &HeaderBlock::default(), &mut output).unwrap();
+        (stats, output)
+    }
+
+    #[test]
+    fn test_extract_strategies() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(5).grid_size(12).block_size(40).relation_count(6).write_to(&mut data).unwrap();
+        // The lower left quarter of the grid, which starts at 47.5°N 8.5°E with about 0.001° spacing
+        let corner = |lat, lon| LatLon::try_from_degrees(lat, lon).unwrap();
+        let mut bbox = BoundingBox::from_point(corner(47.4, 8.4));
+        bbox.extend(corner(47.5055, 8.5055));
+
+        let (simple, output) = run(&data, &Region::BBox(bbox), ExtractStrategy::Simple);
+        let (nodes, ways, _) = contents(output);
+        assert_eq!(simple.passes, 1);
+        assert!(simple.nodes > 0 && simple.nodes < 144 && simple.ways > 0 && simple.ways < 24);
+        assert_eq!(nodes.len() as u64, simple.nodes);
+        assert!(ways.iter().any(|(_, refs)| refs.iter().any(|id| !nodes.contains(id))));
+
+        let (complete, output) = run(&data, &Region::BBox(bbox), ExtractStrategy::CompleteWays);
+        let (nodes, ways, relations) = contents(output);
+        assert_eq!((complete.ways, complete.relations, complete.passes), (simple.ways, simple.relations, 2));
+        assert!(complete.nodes > simple.nodes);
+        assert!(ways.iter().all(|(_, refs)| refs.iter().all(|id| nodes.contains(id))));
+        assert_eq!(relations.len() as u64, complete.relations);
+
+        // The same quarter as a polygon, cutting a triangle out of it
+        let ring = |points: &[(f64, f64)]| points.iter().map(|&(lat, lon)| corner(lat, lon)).collect::<Vec<_>>();
+        let square = ring(&[(47.4, 8.4), (47.4, 8.5055), (47.5055, 8.5055), (47.5055, 8.4)]);
+        let polygon = Polygon::new(std::slice::from_ref(&square)).unwrap();
+        assert_eq!(run(&data, &Region::Polygon(polygon), ExtractStrategy::Simple).0, simple);
+        let hole = ring(&[(47.49, 8.49), (47.49, 8.5155), (47.5155, 8.49)]);
+        let (holed, _) = run(&data, &Region::Polygon(Polygon::new(&[square, hole]).unwrap()), ExtractStrategy::Simple);
+        assert!(holed.nodes < simple.nodes);
+    }
+
+    #[test]
+    fn test_smart_completes_multipolygons() {
+        let strings = StringTable { s: vec![String::new(), "type".into(), "multipolygon".into(), "route".into()] };
+        let node = |id, lat| OsmElement::Node(Node::new(id, LatLon::from_raw(lat, 0)));
+        let deltas = |ids: &[i64]| ids.iter().scan(0, |previous, id| Some(id - std::mem::replace(previous, *id))).collect();
+        let way = |id, refs: &[i64]| OsmElement::Way(Way { id: WayId(id), keys: vec![], vals: vec![], info: None, refs: deltas(refs), lat: vec![], lon: vec![] });
+        let relation = |id, value, ways: &[i64]| OsmElement::Relation(Relation {
+            id: RelationId(id),
+            keys: vec![1],
+            vals: vec![value],
+            info: None,
+            roles_sid: vec![0; ways.len()],
+            memids: deltas(ways),
+            types: vec![MemberType::Way; ways.len()],
+        });
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        let elements = [
+            node(1, 0), node(2, 100), node(3, 1_000), node(4, 2_000), node(5, 3_000),
+            way(10, &[1, 3]), way(11, &[3, 4]), way(12, &[4, 5]),
+            relation(20, 2, &[10, 11]), relation(21, 3, &[10, 12]),
+        ];
+        let mut block = BlockBuffer::default();
+        for element in &elements {
+            block.push(element, &strings);
+        }
+        writer.write_primitive_block(&block.finish()).unwrap();
+        let data = writer.into_inner();
+
+        let mut bbox = BoundingBox::from_point(LatLon::from_raw(0, 0));
+        bbox.extend(LatLon::from_raw(100, 0));
+        let region = Region::BBox(bbox);
+        let (_, output) = run(&data, &region, ExtractStrategy::CompleteWays);
+        let (nodes, ways, relations) = contents(output);
+        assert_eq!((nodes, ways.len(), relations), (BTreeSet::from([1, 2, 3]), 1, vec![20, 21]));
+
+        let (stats, output) = run(&data, &region, ExtractStrategy::Smart);
+        let (nodes, ways, relations) = contents(output);
+        assert_eq!(stats.passes, 3);
+        assert_eq!(nodes, BTreeSet::from([1, 2, 3, 4]));
+        assert_eq!(ways.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![10, 11]);
+        assert_eq!(relations, vec![20, 21]);
+    }
+}
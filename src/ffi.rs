@@ -0,0 +1,404 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::fs::File;
+use std::ptr;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobError, Result};
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, Reader};
+
+/// Version of the batch ABI; bumped on any change to `OsmPbfBatch` or the
+/// signatures below
+pub const OSM_PBF_ABI_VERSION: u32 = 1;
+
+/// `kinds` bit and `OsmPbfBatch::kind` for nodes
+pub const OSM_PBF_NODES: u32 = 1;
+/// `kinds` bit and `OsmPbfBatch::kind` for ways
+pub const OSM_PBF_WAYS: u32 = 2;
+/// `kinds` bit and `OsmPbfBatch::kind` for relations
+pub const OSM_PBF_RELATIONS: u32 = 4;
+/// `kinds` bit and `OsmPbfBatch::kind` for changesets
+pub const OSM_PBF_CHANGESETS: u32 = 8;
+
+/// `osm_pbf_for_each_batch` read every batch
+pub const OSM_PBF_OK: i32 = 0;
+/// `osm_pbf_for_each_batch` stopped because the callback returned non-zero
+pub const OSM_PBF_STOPPED: i32 = 1;
+/// The call failed; `osm_pbf_last_error` says why
+pub const OSM_PBF_ERROR: i32 = -1;
+
+/// Elements of one kind from one block, as parallel arrays
+///
+/// Element `i` of the batch is `ids[i]`, with:
+///
+/// - `lats[i]`, `lons[i]`: location in nanodegrees (nodes; null otherwise);
+/// - tags `tag_keys[j]` = `tag_vals[j]` for `j` in `tag_offsets[i]..tag_offsets[i + 1]`,
+///   both string indices;
+/// - refs `refs[j]` for `j` in `ref_offsets[i]..ref_offsets[i + 1]`: absolute
+///   node ids of a way, or member ids of a relation with `member_types[j]`
+///   (0 node, 1 way, 2 relation) and role string `member_roles[j]`.
+///   `ref_offsets` is null for nodes and changesets, `member_types` and
+///   `member_roles` for all but relations.
+///
+/// String `k` is the `string_offsets[k + 1] - string_offsets[k]` bytes of UTF-8
+/// at `string_arena + string_offsets[k]`, not NUL-terminated; the table is the
+/// block's, shared by all batches of the block, and string 0 is empty.
+///
+/// # Lifetime
+/// The batch and every array it points to belong to the reader and are valid
+/// only until the callback returns; copy what you keep. Don't write through
+/// the pointers.
+#[repr(C)]
+#[derive(Debug)]
+pub struct OsmPbfBatch {
+    /// `size_of::<OsmPbfBatch>()`, for consumers built against a later ABI
+    pub struct_size: usize,
+    /// One of `OSM_PBF_NODES`, `OSM_PBF_WAYS`, `OSM_PBF_RELATIONS`, `OSM_PBF_CHANGESETS`
+    pub kind: u32,
+    /// Index of the blob in the file's blob index
+    pub blob_index: u64,
+    /// Number of elements
+    pub len: usize,
+    pub ids: *const i64,
+    pub lats: *const i64,
+    pub lons: *const i64,
+    /// `len + 1` offsets into `tag_keys` and `tag_vals`
+    pub tag_offsets: *const u32,
+    pub tag_keys: *const u32,
+    pub tag_vals: *const u32,
+    /// `len + 1` offsets into `refs`, `member_types` and `member_roles`
+    pub ref_offsets: *const u32,
+    pub refs: *const i64,
+    pub member_types: *const u8,
+    pub member_roles: *const u32,
+    /// Number of strings in the block's table
+    pub string_count: usize,
+    /// `string_count + 1` offsets into `string_arena`
+    pub string_offsets: *const u32,
+    pub string_arena: *const u8,
+}
+
+/// Called with each batch and the `user_data` given to `osm_pbf_for_each_batch`;
+/// return 0 to continue, anything else to stop
+pub type OsmPbfBatchCallback = unsafe extern "C" fn(batch: *const OsmPbfBatch, user_data: *mut c_void) -> i32;
+
+/// Reader behind the handle returned by `osm_pbf_open`
+pub struct OsmPbfReader {
+    reader: Reader<File>,
+}
+
+thread_local! {
+    /// Message of the last failed call on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Block strings packed into one arena
+#[derive(Default)]
+struct Arena {
+    offsets: Vec<u32>,
+    bytes: Vec<u8>,
+}
+
+impl Arena {
+    fn fill(&mut self, strings: &StringTable) {
+        self.offsets.clear();
+        self.bytes.clear();
+        self.offsets.push(0);
+        for s in &strings.s {
+            self.bytes.extend_from_slice(s.as_bytes());
+            self.offsets.push(self.bytes.len() as u32);
+        }
+    }
+}
+
+/// Arrays of the batch being filled
+#[derive(Default)]
+struct BatchBuffer {
+    kind: u32,
+    ids: Vec<i64>,
+    lats: Vec<i64>,
+    lons: Vec<i64>,
+    tag_offsets: Vec<u32>,
+    tag_keys: Vec<u32>,
+    tag_vals: Vec<u32>,
+    ref_offsets: Vec<u32>,
+    refs: Vec<i64>,
+    member_types: Vec<u8>,
+    member_roles: Vec<u32>,
+}
+
+impl BatchBuffer {
+    fn clear(&mut self, kind: u32) {
+        self.kind = kind;
+        for v in [&mut self.ids, &mut self.lats, &mut self.lons, &mut self.refs] {
+            v.clear();
+        }
+        for v in [&mut self.tag_offsets, &mut self.tag_keys, &mut self.tag_vals, &mut self.ref_offsets, &mut self.member_roles] {
+            v.clear();
+        }
+        self.member_types.clear();
+        self.tag_offsets.push(0);
+        self.ref_offsets.push(0);
+    }
+
+    fn push(&mut self, element: &OsmElement) {
+        let (id, keys, vals) = match element {
+            OsmElement::Node(node) => {
+                self.lats.push(node.location.lat.0);
+                self.lons.push(node.location.lon.0);
+                (node.id.0, &node.keys, &node.vals)
+            }
+            OsmElement::Way(way) => {
+                self.refs.extend(way.node_ids());
+                (way.id.0, &way.keys, &way.vals)
+            }
+            OsmElement::Relation(relation) => {
+                self.refs.extend(relation.member_ids());
+                self.member_types.extend(relation.types.iter().map(|t| *t as u8));
+                self.member_roles.extend(relation.roles_sid.iter().map(|role| *role as u32));
+                (relation.id.0, &relation.keys, &relation.vals)
+            }
+            OsmElement::ChangeSet(changeset) => (changeset.id.0, &changeset.keys, &changeset.vals),
+        };
+        self.ids.push(id);
+        self.tag_keys.extend_from_slice(keys);
+        self.tag_vals.extend_from_slice(vals);
+        self.tag_offsets.push(self.tag_keys.len() as u32);
+        self.ref_offsets.push(self.refs.len() as u32);
+    }
+
+    fn batch(&self, blob_index: usize, arena: &Arena) -> OsmPbfBatch {
+        fn ptr_or_null<T>(v: &[T], present: bool) -> *const T {
+            if present { v.as_ptr() } else { ptr::null() }
+        }
+        let nodes = self.kind == OSM_PBF_NODES;
+        let with_refs = matches!(self.kind, OSM_PBF_WAYS | OSM_PBF_RELATIONS);
+        let relations = self.kind == OSM_PBF_RELATIONS;
+        OsmPbfBatch {
+            struct_size: size_of::<OsmPbfBatch>(),
+            kind: self.kind,
+            blob_index: blob_index as u64,
+            len: self.ids.len(),
+            ids: self.ids.as_ptr(),
+            lats: ptr_or_null(&self.lats, nodes),
+            lons: ptr_or_null(&self.lons, nodes),
+            tag_offsets: self.tag_offsets.as_ptr(),
+            tag_keys: self.tag_keys.as_ptr(),
+            tag_vals: self.tag_vals.as_ptr(),
+            ref_offsets: ptr_or_null(&self.ref_offsets, with_refs),
+            refs: ptr_or_null(&self.refs, with_refs),
+            member_types: ptr_or_null(&self.member_types, relations),
+            member_roles: ptr_or_null(&self.member_roles, relations),
+            string_count: arena.offsets.len() - 1,
+            string_offsets: arena.offsets.as_ptr(),
+            string_arena: arena.bytes.as_ptr(),
+        }
+    }
+}
+
+fn kind_of(element: &OsmElement) -> u32 {
+    match element {
+        OsmElement::Node(_) => OSM_PBF_NODES,
+        OsmElement::Way(_) => OSM_PBF_WAYS,
+        OsmElement::Relation(_) => OSM_PBF_RELATIONS,
+        OsmElement::ChangeSet(_) => OSM_PBF_CHANGESETS,
+    }
+}
+
+/// Stream the elements of `kinds` as batches; `Ok(false)` if the callback stopped
+fn for_each_batch(reader: &mut Reader<File>, kinds: u32, mut callback: impl FnMut(&OsmPbfBatch) -> bool) -> Result<bool> {
+    let filter = ElementFilter {
+        include_nodes: kinds & OSM_PBF_NODES != 0,
+        include_ways: kinds & OSM_PBF_WAYS != 0,
+        include_relations: kinds & OSM_PBF_RELATIONS != 0,
+        include_changesets: kinds & OSM_PBF_CHANGESETS != 0,
+        ..ElementFilter::default()
+    };
+    let mut arena = Arena::default();
+    let mut buffer = BatchBuffer::default();
+    let mut current: Option<usize> = None;
+    let mut stopped = false;
+    reader.for_each_filtered_traced(&filter, |element, strings, provenance| {
+        let kind = kind_of(&element);
+        if current != Some(provenance.blob_index) || kind != buffer.kind {
+            if let Some(blob_index) = current.filter(|_| !buffer.ids.is_empty())
+                && !callback(&buffer.batch(blob_index, &arena))
+            {
+                // Unwinds the read; told apart from real errors by `stopped`
+                stopped = true;
+                return Err(BlobError::InvalidFormat("stopped by the batch callback".to_string()));
+            }
+            if current != Some(provenance.blob_index) {
+                arena.fill(strings);
+                current = Some(provenance.blob_index);
+            }
+            buffer.clear(kind);
+        }
+        buffer.push(&element);
+        Ok(())
+    }).or_else(|e| if stopped { Ok(Default::default()) } else { Err(e) })?;
+    if stopped {
+        return Ok(false);
+    }
+    match current.filter(|_| !buffer.ids.is_empty()) {
+        Some(blob_index) => Ok(callback(&buffer.batch(blob_index, &arena))),
+        None => Ok(true),
+    }
+}
+
+/// Version of the batch ABI the library was built with, `OSM_PBF_ABI_VERSION`
+#[unsafe(no_mangle)]
+pub extern "C" fn osm_pbf_abi_version() -> u32 {
+    OSM_PBF_ABI_VERSION
+}
+
+/// Open a PBF file; null on failure, see `osm_pbf_last_error`
+///
+/// # Safety
+/// `path` must be a NUL-terminated string, valid for the call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn osm_pbf_open(path: *const c_char) -> *mut OsmPbfReader {
+    if path.is_null() {
+        set_last_error("null path".to_string());
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller passes a NUL-terminated string
+    let path = unsafe { CStr::from_ptr(path) };
+    let opened = path.to_str().map_err(|e| BlobError::InvalidFormat(format!("path is not UTF-8: {e}")))
+        .and_then(|path| Ok(File::open(path)?))
+        .and_then(Reader::new);
+    match opened {
+        Ok(reader) => Box::into_raw(Box::new(OsmPbfReader { reader })),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Call `callback` with each batch of the elements of `kinds` (an or of
+/// `OSM_PBF_NODES`, `OSM_PBF_WAYS`, ...), in file order, on the calling thread
+///
+/// Returns `OSM_PBF_OK`, `OSM_PBF_STOPPED` or `OSM_PBF_ERROR`. Each call
+/// reads the file from the start.
+///
+/// # Safety
+/// `reader` must come from `osm_pbf_open` and not be closed or used by
+/// another thread during the call. `callback` must not unwind.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn osm_pbf_for_each_batch(reader: *mut OsmPbfReader, kinds: u32, callback: OsmPbfBatchCallback, user_data: *mut c_void) -> i32 {
+    // SAFETY: the caller passes an open handle no one else uses
+    let Some(reader) = (unsafe { reader.as_mut() }) else {
+        set_last_error("null reader".to_string());
+        return OSM_PBF_ERROR;
+    };
+    // SAFETY: the batch outlives the call, as the callback's contract requires
+    match for_each_batch(&mut reader.reader, kinds, |batch| unsafe { callback(batch, user_data) } == 0) {
+        Ok(true) => OSM_PBF_OK,
+        Ok(false) => OSM_PBF_STOPPED,
+        Err(e) => {
+            set_last_error(e.to_string());
+            OSM_PBF_ERROR
+        }
+    }
+}
+
+/// Close a reader from `osm_pbf_open`; null is ignored
+///
+/// # Safety
+/// `reader` must be null or an open handle, not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn osm_pbf_close(reader: *mut OsmPbfReader) {
+    if !reader.is_null() {
+        // SAFETY: the handle came from `Box::into_raw` in `osm_pbf_open`
+        drop(unsafe { Box::from_raw(reader) });
+    }
+}
+
+/// Message of the last failed call on this thread, or null
+///
+/// The string is valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn osm_pbf_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[derive(Default)]
+    struct Seen {
+        nodes: u64,
+        ways: u64,
+        relations: u64,
+        tagged_highways: u64,
+        batches: u64,
+    }
+
+    unsafe extern "C" fn count(batch: *const OsmPbfBatch, user_data: *mut c_void) -> i32 {
+        let (batch, seen) = unsafe { (&*batch, &mut *(user_data as *mut Seen)) };
+        assert_eq!(batch.struct_size, size_of::<OsmPbfBatch>());
+        let string = |k: u32| unsafe {
+            let start = *batch.string_offsets.add(k as usize) as usize;
+            let end = *batch.string_offsets.add(k as usize + 1) as usize;
+            std::slice::from_raw_parts(batch.string_arena.add(start), end - start)
+        };
+        let tags = unsafe { *batch.tag_offsets.add(batch.len) } as usize;
+        for j in (batch.kind == OSM_PBF_WAYS).then_some(0..tags).into_iter().flatten() {
+            if string(unsafe { *batch.tag_keys.add(j) }) == b"highway" {
+                seen.tagged_highways += 1;
+            }
+        }
+        match batch.kind {
+            OSM_PBF_NODES => {
+                assert!(!batch.lats.is_null() && batch.refs.is_null());
+                seen.nodes += batch.len as u64;
+            }
+            OSM_PBF_WAYS => {
+                let first = unsafe { *batch.refs };
+                assert!(first > 0, "refs are absolute node ids");
+                seen.ways += batch.len as u64;
+            }
+            _ => seen.relations += batch.len as u64,
+        }
+        seen.batches += 1;
+        0
+    }
+
+    unsafe extern "C" fn stop(_: *const OsmPbfBatch, _: *mut c_void) -> i32 {
+        1
+    }
+
+    #[test]
+    fn test_batches_match_reader() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let planet = crate::synthetic::PlanetBuilder::new(4).grid_size(30).block_size(100).relation_count(5).write_to(file.reopen().unwrap()).unwrap();
+        let highways = Reader::new(File::open(file.path()).unwrap()).unwrap()
+            .count_filtered(&ElementFilter::ways_only(false).with_tag_key("highway".to_string())).unwrap();
+
+        let path = CString::new(file.path().to_str().unwrap()).unwrap();
+        unsafe {
+            let reader = osm_pbf_open(path.as_ptr());
+            assert!(!reader.is_null());
+            let mut seen = Seen::default();
+            let kinds = OSM_PBF_NODES | OSM_PBF_WAYS | OSM_PBF_RELATIONS;
+            assert_eq!(osm_pbf_for_each_batch(reader, kinds, count, &mut seen as *mut Seen as *mut c_void), OSM_PBF_OK);
+            assert_eq!((seen.nodes, seen.ways, seen.relations), (planet.nodes, planet.ways, planet.relations));
+            assert_eq!(seen.tagged_highways, highways.ways_processed);
+            assert!(seen.batches < seen.nodes + seen.ways, "elements come in batches");
+            assert_eq!(osm_pbf_for_each_batch(reader, kinds, stop, ptr::null_mut()), OSM_PBF_STOPPED);
+            osm_pbf_close(reader);
+
+            let missing = CString::new("/nonexistent.osm.pbf").unwrap();
+            assert!(osm_pbf_open(missing.as_ptr()).is_null());
+            assert!(!osm_pbf_last_error().is_null());
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod poly;
+
+#[cfg(feature = "xml")]
+pub mod xml;
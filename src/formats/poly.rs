@@ -0,0 +1,213 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::lat_lon::LatLon;
+use crate::io::blob::{BlobError, Result};
+
+/// One section of a .poly file: a closed ring of points
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolyRing {
+    /// Section name, without the `!` marking holes
+    pub name: String,
+    /// Whether the ring cuts a hole into the rings before it
+    pub hole: bool,
+    /// Points in file order; the closing point may or may not repeat the first
+    pub points: Vec<LatLon>,
+}
+
+/// Outer ring of a polygon with its holes, as assembled from a multipolygon relation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolygonRings {
+    pub outer: Vec<LatLon>,
+    pub inners: Vec<Vec<LatLon>>,
+}
+
+/// Polygon file in the Osmosis .poly format
+///
+/// The format is line based: the polygon name, then sections of a name
+/// line (prefixed with `!` for holes), one `lon lat` pair per line in
+/// degrees, and `END`; a final `END` closes the file. Parsing accepts the
+/// exponent notation Osmosis writes, and writing uses plain decimals with
+/// seven digits, the precision of OSM coordinates.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::formats::poly::PolyFile;
+///
+/// let poly: PolyFile = std::fs::read_to_string("berlin.poly")?.parse()?;
+/// println!("{}: {} rings, {:?}", poly.name, poly.rings.len(), poly.bbox());
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolyFile {
+    pub name: String,
+    pub rings: Vec<PolyRing>,
+}
+
+impl PolyFile {
+    /// Polygon file of an assembled multipolygon, e.g. an admin boundary
+    ///
+    /// Sections are numbered from 1 in order, each outer ring followed by
+    /// its holes. Rings with fewer than three distinct points are dropped.
+    pub fn from_multipolygon(name: impl Into<String>, polygons: &[PolygonRings]) -> Self {
+        let mut rings = Vec::new();
+        let mut push = |points: &[LatLon], hole: bool| {
+            let distinct = points.len() - usize::from(points.len() > 1 && points.first() == points.last());
+            if distinct >= 3 {
+                rings.push(PolyRing { name: (rings.len() + 1).to_string(), hole, points: points.to_vec() });
+            }
+        };
+        for polygon in polygons {
+            push(&polygon.outer, false);
+            for inner in &polygon.inners {
+                push(inner, true);
+            }
+        }
+        Self { name: name.into(), rings }
+    }
+
+    /// Read a .poly file
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        text.parse()
+    }
+
+    /// Write the .poly file
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(writer, "{self}")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Extent of the outer rings, `None` without any
+    pub fn bbox(&self) -> Option<BoundingBox> {
+        let mut bbox = None;
+        for ring in self.rings.iter().filter(|ring| !ring.hole) {
+            for point in &ring.points {
+                BoundingBox::extend_option(&mut bbox, *point);
+            }
+        }
+        bbox
+    }
+}
+
+impl fmt::Display for PolyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for ring in &self.rings {
+            writeln!(f, "{}{}", if ring.hole { "!" } else { "" }, ring.name)?;
+            for point in &ring.points {
+                let (lat, lon) = point.to_degrees();
+                writeln!(f, "\t{lon:.7}\t{lat:.7}")?;
+            }
+            writeln!(f, "END")?;
+        }
+        writeln!(f, "END")
+    }
+}
+
+impl FromStr for PolyFile {
+    type Err = BlobError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let error = |line: usize, reason: &str| BlobError::InvalidFormat(format!("Poly file line {}: {reason}", line + 1));
+        let mut lines = s.lines().enumerate().map(|(number, line)| (number, line.trim())).filter(|(_, line)| !line.is_empty());
+
+        let Some((_, name)) = lines.next() else {
+            return Err(error(0, "empty file"));
+        };
+        let mut poly = PolyFile { name: name.to_string(), rings: Vec::new() };
+        let mut ring: Option<PolyRing> = None;
+        for (number, line) in lines.by_ref() {
+            match (&mut ring, line) {
+                (None, "END") => return Ok(poly),
+                (None, section) => {
+                    let (hole, name) = match section.strip_prefix('!') {
+                        Some(name) => (true, name),
+                        None => (false, section),
+                    };
+                    ring = Some(PolyRing { name: name.to_string(), hole, points: Vec::new() });
+                }
+                (Some(_), "END") => poly.rings.extend(ring.take()),
+                (Some(ring), point) => {
+                    let mut fields = point.split_whitespace().map(str::parse::<f64>);
+                    let (Some(Ok(lon)), Some(Ok(lat)), None) = (fields.next(), fields.next(), fields.next()) else {
+                        return Err(error(number, &format!("expected `lon lat`, found {point:?}")));
+                    };
+                    let point = LatLon::try_from_degrees(lat, lon).map_err(|e| error(number, e))?;
+                    ring.points.push(point);
+                }
+            }
+        }
+        Err(error(s.lines().count(), "missing final END"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SAMPLE: &str = "australia_v
+first_area
+     0.1446763E+03    -0.3825659E+02
+     0.1446693E+03    -0.3826255E+02
+     0.1446627E+03    -0.3825661E+02
+     0.1446763E+03    -0.3825659E+02
+END
+!second_area
+     0.1446700E+03    -0.3825800E+02
+     0.1446710E+03    -0.3825900E+02
+     0.1446690E+03    -0.3825900E+02
+END
+END
+";
+
+    #[test]
+    fn test_parse_and_round_trip() {
+        let poly: PolyFile = SAMPLE.parse().unwrap();
+        assert_eq!(poly.name, "australia_v");
+        assert_eq!(poly.rings.iter().map(|r| (r.name.as_str(), r.hole, r.points.len())).collect::<Vec<_>>(),
+            vec![("first_area", false, 4), ("second_area", true, 3)]);
+        assert_eq!(poly.rings[0].points[0], LatLon::try_from_degrees(-38.25659, 144.6763).unwrap());
+
+        let bbox = poly.bbox().unwrap();
+        assert_eq!((bbox.min(), bbox.max()), (
+            LatLon::try_from_degrees(-38.26255, 144.6627).unwrap(),
+            LatLon::try_from_degrees(-38.25659, 144.6763).unwrap(),
+        ));
+
+        let mut written = Vec::new();
+        poly.write(&mut written).unwrap();
+        assert!(String::from_utf8(written.clone()).unwrap().starts_with("australia_v\nfirst_area\n\t144.6763000\t-38.2565900\n"));
+        assert_eq!(PolyFile::read(written.as_slice()).unwrap(), poly);
+    }
+
+    #[test]
+    fn test_malformed_files() {
+        for text in ["", "name\n1\n 1.0 2.0\nEND\n", "name\n1\n 1.0\nEND\nEND\n", "name\n1\n 1.0 95.0\nEND\nEND\n"] {
+            assert!(text.parse::<PolyFile>().is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_from_multipolygon() {
+        let point = |lat, lon| LatLon::try_from_degrees(lat, lon).unwrap();
+        let square = |size: f64| vec![point(0.0, 0.0), point(0.0, size), point(size, size), point(size, 0.0), point(0.0, 0.0)];
+        let polygons = [
+            PolygonRings { outer: square(2.0), inners: vec![square(1.0)] },
+            PolygonRings { outer: vec![point(5.0, 5.0), point(6.0, 6.0), point(5.0, 5.0)], inners: vec![] },
+        ];
+        let poly = PolyFile::from_multipolygon("boundary", &polygons);
+        assert_eq!(poly.rings.iter().map(|r| (r.name.as_str(), r.hole)).collect::<Vec<_>>(), vec![("1", false), ("2", true)]);
+        assert_eq!(poly.to_string().parse::<PolyFile>().unwrap(), poly);
+
+        let inside = [(1.5, 1.5), (0.2, 1.8), (1.99, 0.01)];
+        let outside = [(0.5, 0.5), (2.5, 1.0), (-0.1, 1.0), (5.5, 5.5)];
+        let polygon = crate::io::polygon::Polygon::from_poly(&poly).unwrap();
+        assert!(inside.iter().all(|&(lat, lon)| polygon.contains(point(lat, lon))));
+        assert!(!outside.iter().any(|&(lat, lon)| polygon.contains(point(lat, lon))));
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use bzip2::read::MultiBzDecoder;
+use quick_xml::events::{BytesStart, Event};
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::osm_id::{ChangesetId, NodeId, RelationId, WayId};
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::timestamp::TimestampMillis;
+use crate::io::blob::{BlobError, Result};
+use crate::io::change::ChangeAction;
+use crate::io::delta::delta_encode;
+use crate::io::reader::{OsmElement, ProcessingStats};
+
+/// Elements sharing a string table, as many as osmium puts in a PBF block
+const BLOCK_ELEMENTS: usize = 8000;
+
+/// Reader for OSM XML (.osm) files, plain or bzip2-compressed
+///
+/// Produces the same `OsmElement` stream as `Reader::for_each`: node
+/// locations in nanodegrees, way refs and relation member ids
+/// delta-encoded, and tags, roles and user names as indices into a string
+/// table shared by up to 8000 consecutive elements. Timestamps are in
+/// seconds, the default `date_granularity` of PBF blocks. Elements get an
+/// `Info` when they carry any metadata attribute.
+///
+/// Only `<node>`, `<way>`, `<relation>` and `<changeset>` elements and their
+/// children are read; `<bounds>` and unknown elements are skipped. OsmChange
+/// (.osc) files read the same way, with `for_each_change` telling which
+/// `<create>`, `<modify>` or `<delete>` section each element is in.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::formats::xml::XmlReader;
+/// use osm_pbf::OsmElement;
+///
+/// let mut reader = XmlReader::open("liechtenstein.osm.bz2")?;
+/// reader.for_each_with_strings(|element, strings| {
+///     if let OsmElement::Way(way) = &element {
+///         println!("{} {:?}", way.id, way.tags(strings).get("highway"));
+///     }
+///     Ok(())
+/// })?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub struct XmlReader<R: BufRead> {
+    reader: quick_xml::Reader<R>,
+}
+
+impl XmlReader<Box<dyn BufRead>> {
+    /// Open a .osm or .osm.bz2 file, telling them apart by content
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::detect(File::open(path)?)
+    }
+
+    /// Read plain or bzip2-compressed XML, telling them apart by the bzip2
+    /// magic bytes; concatenated bzip2 streams (as in planet dumps) are read
+    /// to the end
+    pub fn detect(reader: impl Read + 'static) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let reader: Box<dyn BufRead> = if reader.fill_buf()?.starts_with(b"BZh") {
+            Box::new(BufReader::new(MultiBzDecoder::new(reader)))
+        } else {
+            Box::new(reader)
+        };
+        Ok(XmlReader::new(reader))
+    }
+}
+
+impl<R: BufRead> XmlReader<R> {
+    /// Read uncompressed XML
+    pub fn new(reader: R) -> Self {
+        Self { reader: quick_xml::Reader::from_reader(reader) }
+    }
+
+    /// Stream all elements in file order
+    pub fn for_each<F>(&mut self, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(OsmElement) -> Result<()>,
+    {
+        self.for_each_with_strings(|element, _| processor(element))
+    }
+
+    /// Like `for_each`, also passing the string table the element's tags refer to
+    pub fn for_each_with_strings<F>(&mut self, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(OsmElement, &StringTable) -> Result<()>,
+    {
+        self.for_each_change(|_, element, strings| processor(element, strings))
+    }
+
+    /// Stream the elements of an OsmChange file with the action applied to them
+    ///
+    /// Elements outside `<create>`, `<modify>` and `<delete>` sections, as in
+    /// plain .osm files, are reported as `ChangeAction::Create`.
+    pub fn for_each_change<F>(&mut self, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(ChangeAction, OsmElement, &StringTable) -> Result<()>,
+    {
+        let mut stats = ProcessingStats::default();
+        let mut block = XmlBlock::default();
+        let mut current: Option<XmlElement> = None;
+        let mut action = ChangeAction::Create;
+        let mut buf = Vec::new();
+
+        loop {
+            let event = match self.reader.read_event_into(&mut buf) {
+                Ok(event) => event,
+                Err(e) => {
+                    return Err(BlobError::InvalidFormat(format!("XML error at byte {}: {e}", self.reader.error_position())));
+                }
+            };
+            let empty = matches!(event, Event::Empty(_));
+            match event {
+                Event::Start(start) | Event::Empty(start) => {
+                    match start.name().as_ref() {
+                        b"node" | b"way" | b"relation" | b"changeset" => {
+                            let element = XmlElement::start(&start, &mut block)?;
+                            if empty {
+                                block.push(action, element.finish()?);
+                            } else {
+                                current = Some(element);
+                            }
+                        }
+                        b"create" if !empty => action = ChangeAction::Create,
+                        b"modify" if !empty => action = ChangeAction::Modify,
+                        b"delete" if !empty => action = ChangeAction::Delete,
+                        b"tag" => {
+                            if let Some(element) = &mut current {
+                                let attrs = attributes(&start)?;
+                                let key = block.string(required(&attrs, "k", "tag")?);
+                                let value = block.string(required(&attrs, "v", "tag")?);
+                                element.add_tag(key, value);
+                            }
+                        }
+                        b"nd" => {
+                            if let Some(XmlElement::Way(_, refs)) = &mut current {
+                                let attrs = attributes(&start)?;
+                                refs.push(parse(required(&attrs, "ref", "nd")?, "ref", "nd")?);
+                            }
+                        }
+                        b"member" => {
+                            if let Some(XmlElement::Relation(relation, memids)) = &mut current {
+                                let attrs = attributes(&start)?;
+                                let member_type = match required(&attrs, "type", "member")? {
+                                    "node" => MemberType::Node,
+                                    "way" => MemberType::Way,
+                                    "relation" => MemberType::Relation,
+                                    other => return Err(invalid("type", "member", other)),
+                                };
+                                memids.push(parse(required(&attrs, "ref", "member")?, "ref", "member")?);
+                                relation.types.push(member_type);
+                                relation.roles_sid.push(block.string(find(&attrs, "role").unwrap_or("")) as i32);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::End(end) => match end.name().as_ref() {
+                    b"node" | b"way" | b"relation" | b"changeset" => {
+                        if let Some(element) = current.take() {
+                            block.push(action, element.finish()?);
+                        }
+                    }
+                    b"create" | b"modify" | b"delete" => action = ChangeAction::Create,
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+
+            // Strings of an unfinished element are already in the current table
+            if current.is_none() && block.elements.len() >= BLOCK_ELEMENTS {
+                std::mem::take(&mut block).emit(&mut stats, &mut processor)?;
+            }
+        }
+
+        block.emit(&mut stats, &mut processor)?;
+        Ok(stats)
+    }
+}
+
+/// Elements read so far with the strings they refer to
+#[derive(Default)]
+struct XmlBlock {
+    strings: StringTable,
+    index: HashMap<String, u32>,
+    elements: Vec<(ChangeAction, OsmElement)>,
+}
+
+impl XmlBlock {
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(&id) = self.index.get(s) {
+            return id;
+        }
+        let id = self.strings.add_string(s.to_string()) as u32;
+        self.index.insert(s.to_string(), id);
+        id
+    }
+
+    fn push(&mut self, action: ChangeAction, element: OsmElement) {
+        self.elements.push((action, element));
+    }
+
+    fn emit<F>(self, stats: &mut ProcessingStats, processor: &mut F) -> Result<()>
+    where
+        F: FnMut(ChangeAction, OsmElement, &StringTable) -> Result<()>,
+    {
+        for (action, element) in self.elements {
+            match &element {
+                OsmElement::Node(_) => stats.nodes_processed += 1,
+                OsmElement::Way(_) => stats.ways_processed += 1,
+                OsmElement::Relation(_) => stats.relations_processed += 1,
+                OsmElement::ChangeSet(_) => stats.changesets_processed += 1,
+            }
+            stats.elements_processed += 1;
+            processor(action, element, &self.strings)?;
+        }
+        Ok(())
+    }
+}
+
+/// An element between its start and end tags; ways and relations keep
+/// their absolute member ids until the end
+enum XmlElement {
+    Node(Node),
+    Way(Way, Vec<i64>),
+    Relation(Relation, Vec<i64>),
+    ChangeSet(ChangeSet),
+}
+
+impl XmlElement {
+    fn start(start: &BytesStart<'_>, block: &mut XmlBlock) -> Result<Self> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+        let attrs = attributes(start)?;
+        let id = parse(required(&attrs, "id", &name)?, "id", &name)?;
+        let info = info(&attrs, &name, block)?;
+
+        Ok(match name.as_str() {
+            "node" => {
+                let coordinate = |key| find(&attrs, key).map(|value| parse_nanodegrees(value).ok_or_else(|| invalid(key, "node", value))).transpose();
+                // Deleted nodes in history files have no location
+                let (lat, lon) = (coordinate("lat")?.unwrap_or(0), coordinate("lon")?.unwrap_or(0));
+                XmlElement::Node(Node { id: NodeId(id), keys: vec![], vals: vec![], info, location: LatLon::from_raw(lat, lon) })
+            }
+            "way" => XmlElement::Way(Way { id: WayId(id), keys: vec![], vals: vec![], info, refs: vec![], lat: vec![], lon: vec![] }, vec![]),
+            "relation" => XmlElement::Relation(
+                Relation { id: RelationId(id), keys: vec![], vals: vec![], info, roles_sid: vec![], memids: vec![], types: vec![] },
+                vec![],
+            ),
+            _ => XmlElement::ChangeSet(ChangeSet { id: ChangesetId(id), keys: vec![], vals: vec![], info }),
+        })
+    }
+
+    fn add_tag(&mut self, key: u32, value: u32) {
+        let (keys, vals) = match self {
+            XmlElement::Node(node) => (&mut node.keys, &mut node.vals),
+            XmlElement::Way(way, _) => (&mut way.keys, &mut way.vals),
+            XmlElement::Relation(relation, _) => (&mut relation.keys, &mut relation.vals),
+            XmlElement::ChangeSet(changeset) => (&mut changeset.keys, &mut changeset.vals),
+        };
+        keys.push(key);
+        vals.push(value);
+    }
+
+    fn finish(self) -> Result<OsmElement> {
+        Ok(match self {
+            XmlElement::Node(node) => OsmElement::Node(node),
+            XmlElement::Way(mut way, refs) => {
+                way.refs = delta_encode(&refs)?;
+                OsmElement::Way(way)
+            }
+            XmlElement::Relation(mut relation, memids) => {
+                relation.memids = delta_encode(&memids)?;
+                OsmElement::Relation(relation)
+            }
+            XmlElement::ChangeSet(changeset) => OsmElement::ChangeSet(changeset),
+        })
+    }
+}
+
+fn attributes(start: &BytesStart<'_>) -> Result<Vec<(String, String)>> {
+    start.attributes()
+        .map(|attr| {
+            let attr = attr.map_err(|e| BlobError::InvalidFormat(format!("XML attribute: {e}")))?;
+            let value = attr.unescape_value().map_err(|e| BlobError::InvalidFormat(format!("XML attribute: {e}")))?;
+            Ok((String::from_utf8_lossy(attr.key.as_ref()).into_owned(), value.into_owned()))
+        })
+        .collect()
+}
+
+fn find<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn required<'a>(attrs: &'a [(String, String)], key: &str, element: &str) -> Result<&'a str> {
+    find(attrs, key).ok_or_else(|| BlobError::InvalidFormat(format!("<{element}> without {key}")))
+}
+
+fn parse<T: std::str::FromStr>(value: &str, key: &str, element: &str) -> Result<T> {
+    value.parse().map_err(|_| invalid(key, element, value))
+}
+
+fn invalid(key: &str, element: &str, value: &str) -> BlobError {
+    BlobError::InvalidFormat(format!("Invalid {key} in <{element}>: {value:?}"))
+}
+
+/// Metadata from the attributes, if there is any
+fn info(attrs: &[(String, String)], element: &str, block: &mut XmlBlock) -> Result<Option<Info>> {
+    const KEYS: [&str; 6] = ["version", "timestamp", "changeset", "uid", "user", "visible"];
+    if !attrs.iter().any(|(key, _)| KEYS.contains(&key.as_str())) {
+        return Ok(None);
+    }
+    let number = |key| find(attrs, key).map(|value| parse(value, key, element)).transpose();
+    let timestamp = match find(attrs, "timestamp") {
+        Some(value) => TimestampMillis::parse_iso8601(value).map(TimestampMillis::as_secs).ok_or_else(|| invalid("timestamp", element, value))?,
+        None => 0,
+    };
+    Ok(Some(Info {
+        version: number("version")?.unwrap_or(0) as i32,
+        timestamp,
+        changeset: number("changeset")?.unwrap_or(0),
+        uid: number("uid")?.unwrap_or(0) as i32,
+        user_sid: find(attrs, "user").map_or(0, |user| block.string(user)),
+        visible: find(attrs, "visible") != Some("false"),
+    }))
+}
+
+/// Parse a decimal number of degrees exactly into nanodegrees
+fn parse_nanodegrees(value: &str) -> Option<i64> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty() || fraction.len() > 9 {
+        return None;
+    }
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let fraction: i64 = format!("{fraction:0<9}").parse().ok()?;
+    let nanodegrees = whole.checked_mul(1_000_000_000)?.checked_add(fraction)?;
+    if nanodegrees > 180_000_000_000 {
+        return None;
+    }
+    Some(if negative { -nanodegrees } else { nanodegrees })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bzip2::write::BzEncoder;
+    use pretty_assertions::assert_eq;
+    use std::io::{Cursor, Write};
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <bounds minlat="47.0" minlon="8.0" maxlat="48.0" maxlon="9.0"/>
+  <node id="1" lat="47.1234567" lon="-8.5" version="2" timestamp="2012-03-04T05:06:07Z" changeset="9" uid="5" user="anna"/>
+  <node id="2" lat="47.2" lon="8.6">
+    <tag k="name" v="Caf&#233; &amp; Bar"/>
+    <tag k="amenity" v="cafe"/>
+  </node>
+  <way id="10">
+    <nd ref="2"/>
+    <nd ref="1"/>
+    <nd ref="5"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <relation id="20" visible="false">
+    <member type="way" ref="10" role="outer"/>
+    <member type="node" ref="2" role=""/>
+    <tag k="type" v="multipolygon"/>
+  </relation>
+</osm>
+"#;
+
+    fn read_all(reader: &mut XmlReader<impl BufRead>) -> Vec<(OsmElement, Vec<(String, String)>)> {
+        let mut elements = Vec::new();
+        reader.for_each_with_strings(|element, strings| {
+            let tags = element.tags(strings).iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            elements.push((element, tags));
+            Ok(())
+        }).unwrap();
+        elements
+    }
+
+    #[test]
+    fn test_reads_plain_and_bzip2_xml() {
+        let mut plain = XmlReader::new(SAMPLE.as_bytes());
+        let elements = read_all(&mut plain);
+        assert_eq!(elements.len(), 4);
+
+        let OsmElement::Node(node) = &elements[0].0 else { panic!("expected a node") };
+        assert_eq!(node.location, LatLon::from_raw(47_123_456_700, -8_500_000_000));
+        let info = node.info.as_ref().unwrap();
+        assert_eq!((info.version, info.timestamp, info.changeset, info.uid), (2, 1_330_837_567, 9, 5));
+        assert_eq!(elements[1].1, vec![("name".to_string(), "Café & Bar".to_string()), ("amenity".to_string(), "cafe".to_string())]);
+        let OsmElement::Way(way) = &elements[2].0 else { panic!("expected a way") };
+        assert_eq!(way.refs, vec![2, -1, 4]);
+        let OsmElement::Relation(relation) = &elements[3].0 else { panic!("expected a relation") };
+        assert_eq!(relation.memids, vec![10, -8]);
+        assert_eq!(relation.types, vec![MemberType::Way, MemberType::Node]);
+        assert!(!relation.info.as_ref().unwrap().visible);
+        assert_eq!(elements[3].1, vec![("type".to_string(), "multipolygon".to_string())]);
+
+        let mut compressed = BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        compressed.write_all(SAMPLE.as_bytes()).unwrap();
+        let mut detected = XmlReader::detect(Cursor::new(compressed.finish().unwrap())).unwrap();
+        let decompressed = read_all(&mut detected);
+        let json = |elements: &[(OsmElement, Vec<(String, String)>)]| serde_json::to_string(&elements.iter().map(|(e, _)| e).collect::<Vec<_>>()).unwrap();
+        assert_eq!(json(&decompressed), json(&elements));
+        assert!(XmlReader::new("<osm><node id=\"x\"/></osm>".as_bytes()).for_each(|_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_change_actions() {
+        let osc = r#"<osmChange version="0.6">
+  <create><node id="-1" lat="1" lon="2"/></create>
+  <modify><way id="7"><nd ref="1"/></way><node id="3" lat="0" lon="0"/></modify>
+  <delete><relation id="9" version="4"/></delete>
+</osmChange>"#;
+        let mut actions = Vec::new();
+        XmlReader::new(osc.as_bytes()).for_each_change(|action, element, _| {
+            let id = match element {
+                OsmElement::Node(node) => node.id.0,
+                OsmElement::Way(way) => way.id.0,
+                OsmElement::Relation(relation) => relation.id.0,
+                OsmElement::ChangeSet(changeset) => changeset.id.0,
+            };
+            actions.push((action, id));
+            Ok(())
+        }).unwrap();
+        assert_eq!(actions, vec![(ChangeAction::Create, -1), (ChangeAction::Modify, 7), (ChangeAction::Modify, 3), (ChangeAction::Delete, 9)]);
+    }
+
+    #[test]
+    fn test_coordinate_parsing() {
+        assert_eq!(parse_nanodegrees("-0.0000001"), Some(-100));
+        assert_eq!(parse_nanodegrees("180"), Some(180_000_000_000));
+        assert_eq!(parse_nanodegrees(".5"), Some(500_000_000));
+        assert_eq!(parse_nanodegrees("181"), None);
+        assert_eq!(parse_nanodegrees("1e5"), None);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::osm_id::RelationId;
+use crate::blocks::primitives::prelude::*;
+use crate::formats::poly::PolygonRings;
+use crate::io::blob::Result;
+use crate::io::fingerprint::Fnv128;
+use crate::io::index_file::Input;
+
+/// First bytes of a geometry cache file
+const MAGIC: &[u8; 8] = b"OPBF-GEO";
+
+/// Version of the cache file layout, bumped on any change
+const VERSION: u32 = 1;
+
+/// Hash of the versions a relation's geometry was assembled from
+///
+/// Covers the relation's id and version and, for each member in order, its
+/// type, id and the stamp `stamp_of` returns for it (`None` for members
+/// missing from the data). The stamp must change whenever the member's
+/// geometry does: a way's version doesn't change when its nodes move, so for
+/// ways return something that also covers the nodes, e.g. the way version
+/// mixed with the newest node version or timestamp.
+///
+/// The hash is stable across platforms and compiler versions, so it can be
+/// persisted.
+pub fn member_version_hash<F>(relation: &Relation, mut stamp_of: F) -> u128
+where
+    F: FnMut(MemberType, i64) -> Option<i64>,
+{
+    let mut hasher = Fnv128::new();
+    hasher.write_i64(relation.id.0);
+    hasher.write_i64(relation.info.as_ref().map_or(0, |info| info.version as i64));
+    hasher.write_u64(relation.memids.len() as u64);
+    for (id, member_type) in relation.member_ids().zip(&relation.types) {
+        let stamp = stamp_of(*member_type, id);
+        hasher.write_u8(*member_type as u8);
+        hasher.write_i64(id);
+        hasher.write_u8(stamp.is_some() as u8);
+        hasher.write_i64(stamp.unwrap_or(0));
+    }
+    hasher.finish()
+}
+
+/// Assembled relation geometries keyed by relation id and member versions
+///
+/// An entry is returned only for the `member_version_hash` it was stored
+/// with, so geometries go stale by themselves when a member changes: the
+/// next lookup misses, and storing the reassembled geometry replaces the old
+/// one. Caches opened from a file are written back by `save`.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::geometry_cache::{member_version_hash, GeometryCache};
+/// # use osm_pbf::{formats::poly::PolygonRings, Relation};
+/// # fn assemble(relation: &Relation) -> osm_pbf::Result<Vec<PolygonRings>> { Ok(Vec::new()) }
+/// # fn boundaries() -> Vec<Relation> { Vec::new() }
+/// # fn version_of(member_type: osm_pbf::MemberType, id: i64) -> Option<i64> { None }
+///
+/// let mut cache = GeometryCache::open("boundaries.geocache")?;
+/// for relation in boundaries() {
+///     let versions = member_version_hash(&relation, version_of);
+///     let polygons = cache.get_or_assemble(relation.id, versions, || assemble(&relation))?;
+///     println!("{}: {} polygons", relation.id, polygons.len());
+/// }
+/// cache.save()?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct GeometryCache {
+    path: Option<PathBuf>,
+    entries: HashMap<RelationId, (u128, Vec<PolygonRings>)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl GeometryCache {
+    /// Empty cache kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache persisted at `path`, starting from its contents if the file exists
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => from_bytes(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path: Some(path), entries, ..Self::default() })
+    }
+
+    /// Geometry of the relation, if stored for the same member versions
+    pub fn get(&self, relation_id: RelationId, versions: u128) -> Option<&[PolygonRings]> {
+        self.entries.get(&relation_id)
+            .filter(|(stored, _)| *stored == versions)
+            .map(|(_, polygons)| polygons.as_slice())
+    }
+
+    /// Store the relation's geometry, replacing any for other member versions
+    pub fn insert(&mut self, relation_id: RelationId, versions: u128, polygons: Vec<PolygonRings>) {
+        self.entries.insert(relation_id, (versions, polygons));
+    }
+
+    /// Cached geometry of the relation, or the one `assemble` returns, stored
+    pub fn get_or_assemble<F>(&mut self, relation_id: RelationId, versions: u128, assemble: F) -> Result<&[PolygonRings]>
+    where
+        F: FnOnce() -> Result<Vec<PolygonRings>>,
+    {
+        if self.get(relation_id, versions).is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            let polygons = assemble()?;
+            self.insert(relation_id, versions, polygons);
+        }
+        Ok(&self.entries[&relation_id].1)
+    }
+
+    /// Drop the entries of relations `keep` rejects, e.g. deleted ones
+    pub fn retain(&mut self, mut keep: impl FnMut(RelationId) -> bool) {
+        self.entries.retain(|id, _| keep(*id));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lookups by `get_or_assemble` answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups by `get_or_assemble` that had to assemble
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Write the cache back to the file it was opened from, replacing it
+    /// atomically; does nothing for a cache made with `new`
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, to_bytes(&self.entries))?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Serialize entries to the little-endian cache layout, sorted by relation id
+fn to_bytes(entries: &HashMap<RelationId, (u128, Vec<PolygonRings>)>) -> Vec<u8> {
+    let mut ids: Vec<_> = entries.keys().copied().collect();
+    ids.sort_unstable();
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(ids.len() as u64).to_le_bytes());
+    let ring = |out: &mut Vec<u8>, points: &[LatLon]| {
+        out.extend_from_slice(&(points.len() as u32).to_le_bytes());
+        for point in points {
+            out.extend_from_slice(&point.lat.0.to_le_bytes());
+            out.extend_from_slice(&point.lon.0.to_le_bytes());
+        }
+    };
+    for id in ids {
+        let (versions, polygons) = &entries[&id];
+        out.extend_from_slice(&id.0.to_le_bytes());
+        out.extend_from_slice(&versions.to_le_bytes());
+        out.extend_from_slice(&(polygons.len() as u32).to_le_bytes());
+        for polygon in polygons {
+            ring(&mut out, &polygon.outer);
+            out.extend_from_slice(&(polygon.inners.len() as u32).to_le_bytes());
+            for inner in &polygon.inners {
+                ring(&mut out, inner);
+            }
+        }
+    }
+    out
+}
+
+fn from_bytes(bytes: &[u8]) -> Result<HashMap<RelationId, (u128, Vec<PolygonRings>)>> {
+    let mut input = Input::new("Geometry cache", bytes);
+    if input.take(MAGIC.len())? != MAGIC {
+        return Err(input.error("not a geometry cache"));
+    }
+    let version = input.u32()?;
+    if version != VERSION {
+        return Err(input.error(&format!("unsupported version {version}")));
+    }
+    // Counts are checked against the bytes left by `take`, so a bogus count
+    // fails instead of allocating
+    fn ring(input: &mut Input) -> Result<Vec<LatLon>> {
+        let len = input.u32()? as usize;
+        let bytes = input.take(len.saturating_mul(16))?;
+        Ok(bytes.chunks_exact(16).map(|point| {
+            let (lat, lon) = point.split_at(8);
+            LatLon::from_raw(i64::from_le_bytes(lat.try_into().expect("8 bytes")), i64::from_le_bytes(lon.try_into().expect("8 bytes")))
+        }).collect())
+    }
+    let count = input.u64()?;
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let id = RelationId(input.i64()?);
+        let versions = u128::from_le_bytes(input.take(16)?.try_into().expect("16 bytes"));
+        let polygon_count = input.u32()?;
+        let mut polygons = Vec::new();
+        for _ in 0..polygon_count {
+            let outer = ring(&mut input)?;
+            let inners = (0..input.u32()?).map(|_| ring(&mut input)).collect::<Result<_>>()?;
+            polygons.push(PolygonRings { outer, inners });
+        }
+        entries.insert(id, (versions, polygons));
+    }
+    if !input.is_at_end() {
+        return Err(input.error("trailing bytes"));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn relation(members: &[i64]) -> Relation {
+        let mut relation = Relation { id: RelationId(7), keys: Vec::new(), vals: Vec::new(), info: None, roles_sid: Vec::new(), memids: Vec::new(), types: Vec::new() };
+        let mut previous = 0;
+        for id in members {
+            relation.memids.push(id - previous);
+            relation.types.push(MemberType::Way);
+            relation.roles_sid.push(0);
+            previous = *id;
+        }
+        relation
+    }
+
+    fn square(size: i64) -> Vec<PolygonRings> {
+        let points = [(0, 0), (0, size), (size, size), (size, 0), (0, 0)];
+        vec![PolygonRings {
+            outer: points.iter().map(|(lat, lon)| LatLon::from_raw(*lat, *lon)).collect(),
+            inners: vec![vec![LatLon::from_raw(1, 1), LatLon::from_raw(1, 2), LatLon::from_raw(2, 1)]],
+        }]
+    }
+
+    #[test]
+    fn test_invalidated_by_member_versions_and_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("relations.geocache");
+        let relation = relation(&[10, 11, 12]);
+        let mut member_versions = HashMap::from([(10, 1), (11, 1), (12, 3)]);
+        let versions = |member_versions: &HashMap<i64, i64>| member_version_hash(&relation, |_, id| member_versions.get(&id).copied());
+
+        let mut cache = GeometryCache::open(&path).unwrap();
+        let v1 = versions(&member_versions);
+        assert_eq!(cache.get_or_assemble(relation.id, v1, || Ok(square(10))).unwrap(), square(10));
+        assert_eq!(cache.get_or_assemble(relation.id, v1, || panic!("cached")).unwrap(), square(10));
+        cache.save().unwrap();
+
+        let mut cache = GeometryCache::open(&path).unwrap();
+        assert_eq!((cache.len(), cache.get(relation.id, v1)), (1, Some(&square(10)[..])));
+        member_versions.insert(11, 2);
+        let v2 = versions(&member_versions);
+        assert_ne!(v1, v2);
+        assert_eq!(cache.get(relation.id, v2), None);
+        assert_eq!(cache.get_or_assemble(relation.id, v2, || Ok(square(20))).unwrap(), square(20));
+        assert_eq!(cache.get(relation.id, v1), None);
+        assert_eq!((cache.hits(), cache.misses()), (0, 1));
+
+        member_versions.remove(&12);
+        assert_ne!(versions(&member_versions), v2);
+        std::fs::write(&path, b"OPBF-GEO\x01\0\0\0\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+        assert!(GeometryCache::open(&path).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use std::future::Future;
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use crate::io::blob::{Blob, BlobError, Result, MAX_BLOB_HEADER_SIZE, MAX_BLOB_MESSAGE_SIZE};
+use crate::io::codec::{BlockDecoder, PbfBlockCodec};
+use crate::io::decode::{decode_blob, decode_blob_header, decode_matching_elements, DecodePredicate};
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::OsmElement;
+use crate::io::validate::GroupPolicy;
+use crate::io::zstd_dictionary::ZstdDictionaries;
+
+/// Sequential blob reader over a Tokio `AsyncRead + AsyncSeek` source
+///
+/// Reads never block the runtime, so sources backed by network storage
+/// (S3, HTTP range requests) can be read from async tasks directly. Decoding
+/// is CPU work done on the polling task; for large blobs on a busy runtime,
+/// decode in `spawn_blocking` from `next_blob` instead of using `into_elements`.
+pub struct AsyncReader<R> {
+    inner: R,
+    offset: u64,
+    group_policy: GroupPolicy,
+    block_decoder: Arc<dyn BlockDecoder>,
+    zstd_dictionaries: ZstdDictionaries,
+}
+
+impl<R> std::fmt::Debug for AsyncReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncReader").field("offset", &self.offset).field("group_policy", &self.group_policy).finish_non_exhaustive()
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncReader<R> {
+    /// Read from the current position of `inner`, which must be a blob boundary
+    ///
+    /// Offsets of returned blobs are counted from that position; call `seek`
+    /// first to start elsewhere.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            group_policy: GroupPolicy::default(),
+            block_decoder: Arc::new(PbfBlockCodec),
+            zstd_dictionaries: ZstdDictionaries::default(),
+        }
+    }
+
+    /// Continue at a blob boundary, e.g. a `BlobIndex::offset` or `Provenance::byte_offset`
+    pub async fn seek(&mut self, offset: u64) -> Result<()> {
+        self.offset = self.inner.seek(SeekFrom::Start(offset)).await?;
+        Ok(())
+    }
+
+    /// Offset of the next blob
+    pub fn position(&self) -> u64 {
+        self.offset
+    }
+
+    /// Set how groups holding more than one element kind are handled
+    pub fn set_group_policy(&mut self, group_policy: GroupPolicy) {
+        self.group_policy = group_policy;
+    }
+
+    /// Decode data blocks with `decoder` instead of as PBF PrimitiveBlock
+    /// messages, as with `Reader::set_block_decoder`
+    pub fn set_block_decoder(&mut self, decoder: impl BlockDecoder + 'static) {
+        self.block_decoder = Arc::new(decoder);
+    }
+
+    /// Read the next blob, header blobs included; `None` at the end of the file
+    ///
+    /// zstd dictionary blobs are returned as well, and loaded for the zstd
+    /// blobs after them; a reader that seeks past them can't decompress those.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::AsyncReader;
+    /// use tokio::io::{AsyncRead, AsyncSeek};
+    ///
+    /// # async fn run(source: impl AsyncRead + AsyncSeek + Unpin) -> osm_pbf::Result<()> {
+    /// let mut reader = AsyncReader::new(source);
+    /// while let Some(blob) = reader.next_blob().await? {
+    ///     println!("{:?} blob at {}", blob.header.blob_type, blob.offset);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn next_blob(&mut self) -> Result<Option<Blob>> {
+        let offset = self.offset;
+        let mut size_bytes = [0u8; 4];
+        match self.inner.read_exact(&mut size_bytes).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(BlobError::Io(e)),
+        }
+
+        let header_size = u32::from_be_bytes(size_bytes) as usize;
+        if header_size > MAX_BLOB_HEADER_SIZE {
+            return Err(BlobError::HeaderTooLarge { size: header_size, max: MAX_BLOB_HEADER_SIZE });
+        }
+        let mut header_buf = vec![0u8; header_size];
+        self.inner.read_exact(&mut header_buf).await?;
+        let header = decode_blob_header(&header_buf)?;
+
+        let datasize = usize::try_from(header.datasize).unwrap_or(usize::MAX);
+        if datasize > MAX_BLOB_MESSAGE_SIZE {
+            return Err(BlobError::MessageTooLarge { size: datasize, max: MAX_BLOB_MESSAGE_SIZE });
+        }
+        let mut data = vec![0u8; datasize];
+        self.inner.read_exact(&mut data).await?;
+
+        self.offset += (4 + header_size + datasize) as u64;
+        let mut blob = decode_blob(header, &data, offset)?;
+        self.zstd_dictionaries.observe(&mut blob)?;
+        Ok(Some(blob))
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send + 'static> AsyncReader<R> {
+    /// Stream of the elements matching `filter`, in file order
+    ///
+    /// Node locations are in nanodegrees; way refs and relation memids stay
+    /// delta-encoded, as with `Reader::for_each_filtered`. The stream ends
+    /// after the first error.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{AsyncReader, ElementFilter};
+    /// use tokio::io::{AsyncRead, AsyncSeek};
+    ///
+    /// # async fn run(source: impl AsyncRead + AsyncSeek + Unpin + Send + 'static) -> osm_pbf::Result<()> {
+    /// let ways = AsyncReader::new(source).into_elements(ElementFilter::ways_only(false));
+    /// // e.g. `while let Some(way) = ways.next().await` with `futures::StreamExt`
+    /// # drop(ways);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_elements(self, filter: ElementFilter) -> AsyncElementStream<R> {
+        AsyncElementStream {
+            predicate: DecodePredicate::from(&filter),
+            pending: Vec::new().into_iter(),
+            state: State::Idle(self),
+        }
+    }
+}
+
+type NextBlob<R> = Pin<Box<dyn Future<Output = (AsyncReader<R>, Result<Option<Blob>>)> + Send>>;
+
+enum State<R> {
+    Idle(AsyncReader<R>),
+    Reading(NextBlob<R>),
+    Done,
+}
+
+/// Element stream from `AsyncReader::into_elements`
+pub struct AsyncElementStream<R> {
+    predicate: DecodePredicate,
+    pending: std::vec::IntoIter<OsmElement>,
+    state: State<R>,
+}
+
+impl<R> AsyncElementStream<R> {
+    fn decode(&self, blob: &Blob, reader: &AsyncReader<R>) -> Result<Vec<OsmElement>> {
+        Ok(decode_matching_elements(blob, &self.predicate, reader.group_policy, reader.block_decoder.as_ref())?.elements)
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send + 'static> Stream for AsyncElementStream<R> {
+    type Item = Result<OsmElement>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(element) = this.pending.next() {
+                return Poll::Ready(Some(Ok(element)));
+            }
+            match std::mem::replace(&mut this.state, State::Done) {
+                State::Idle(mut reader) => {
+                    this.state = State::Reading(Box::pin(async move {
+                        let blob = reader.next_blob().await;
+                        (reader, blob)
+                    }));
+                }
+                State::Reading(mut next) => match next.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = State::Reading(next);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((reader, Ok(Some(blob)))) => match this.decode(&blob, &reader) {
+                        Ok(elements) => {
+                            this.pending = elements.into_iter();
+                            this.state = State::Idle(reader);
+                        }
+                        Err(e) => return Poll::Ready(Some(Err(e))),
+                    },
+                    Poll::Ready((_, Ok(None))) => return Poll::Ready(None),
+                    Poll::Ready((_, Err(e))) => return Poll::Ready(Some(Err(e))),
+                },
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::reader::Reader;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Waker;
+
+    /// Drive a future over an in-memory source, which is always ready
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn planet() -> Vec<u8> {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(8).grid_size(8).block_size(30).relation_count(2).write_to(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_next_blob_matches_indexed_offsets() {
+        let data = planet();
+        let indexed = crate::io::indexed_reader::IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        let mut reader = AsyncReader::new(Cursor::new(data.clone()));
+
+        let mut offsets = Vec::new();
+        while let Some(blob) = block_on(reader.next_blob()).unwrap() {
+            offsets.push(blob.offset);
+        }
+        let expected: Vec<u64> = (0..indexed.blob_count()).map(|i| indexed.get_blob_index(i).unwrap().offset).collect();
+        assert_eq!(offsets, expected);
+        assert_eq!(reader.position(), data.len() as u64);
+
+        block_on(reader.seek(expected[2])).unwrap();
+        assert_eq!(block_on(reader.next_blob()).unwrap().unwrap().offset, expected[2]);
+
+        // A frame cut short is an error, not the end of the file
+        let mut truncated = AsyncReader::new(Cursor::new(data[..data.len() - 3].to_vec()));
+        let results: Vec<_> = std::iter::from_fn(|| block_on(truncated.next_blob()).transpose()).collect();
+        assert!(results.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_element_stream_matches_reader() {
+        let data = planet();
+        let filter = ElementFilter::all().with_tag_key("highway".to_string());
+        let mut expected = Vec::new();
+        Reader::new(Cursor::new(data.clone())).unwrap()
+            .for_each_filtered(&filter, |element| {
+                expected.push(format!("{element:?}"));
+                Ok(())
+            })
+            .unwrap();
+        assert!(!expected.is_empty());
+
+        let mut stream = AsyncReader::new(Cursor::new(data)).into_elements(filter);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut elements = Vec::new();
+        while let Poll::Ready(Some(element)) = Pin::new(&mut stream).poll_next(&mut cx) {
+            elements.push(format!("{:?}", element.unwrap()));
+        }
+        assert_eq!(elements, expected);
+    }
+
+    /// Decodes PBF blocks, counting the calls
+    struct CountingDecoder(Arc<AtomicUsize>);
+
+    impl BlockDecoder for CountingDecoder {
+        fn decode_block(&self, message: &[u8]) -> Result<crate::blocks::primitives::block::PrimitiveBlock> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            PbfBlockCodec.decode_block(message)
+        }
+    }
+
+    #[test]
+    fn test_element_stream_uses_block_decoder() {
+        let data = planet();
+        let data_blobs = crate::io::indexed_reader::IndexedReader::new(Cursor::new(data.clone())).unwrap().statistics().data_blobs;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut reader = AsyncReader::new(Cursor::new(data));
+        reader.set_block_decoder(CountingDecoder(calls.clone()));
+
+        let mut stream = reader.into_elements(ElementFilter::all());
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut elements = 0;
+        while let Poll::Ready(Some(element)) = Pin::new(&mut stream).poll_next(&mut cx) {
+            element.unwrap();
+            elements += 1;
+        }
+        assert!(elements > 0);
+        assert_eq!(calls.load(Ordering::Relaxed) as u64, data_blobs);
+    }
+}
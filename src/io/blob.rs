@@ -1,6 +1,12 @@
 use bytes::Bytes;
 use thiserror::Error;
 use std::str::FromStr;
+use crate::io::inflate::zlib_decompress;
+#[cfg(feature = "lz4")]
+use crate::io::inflate::lz4_decompress;
+#[cfg(feature = "zstd")]
+use crate::io::inflate::zstd_decompress;
+use crate::io::zstd_dictionary::ZstdDictionary;
 
 /// Maximum size for a BlobHeader: 64 KiB (65,536 bytes)
 pub const MAX_BLOB_HEADER_SIZE: usize = 65_536;
@@ -28,10 +34,85 @@ pub enum BlobError {
     
     #[error("Unknown blob type: {0}")]
     UnknownType(String),
+    
+    #[error("Unsupported required features: {}", features.join(", "))]
+    UnsupportedFeature { features: Vec<String> },
+    
+    #[error("Invalid string in {element}, key {key:?}: {reason}")]
+    InvalidString { element: String, key: String, reason: String },
+    
+    #[error("Delta overflow at position {index}")]
+    DeltaOverflow { index: usize },
+
+    #[error("Primitive group {group} mixes element kinds: {}", kinds.join(", "))]
+    MixedGroup { group: usize, kinds: Vec<String> },
+
+    #[error("Relation cycle: {}", cycle.iter().map(i64::to_string).collect::<Vec<_>>().join(" -> "))]
+    RelationCycle { cycle: Vec<i64> },
+
+    #[error("{needed} bytes exceed the in-memory limit of {limit} bytes")]
+    MemoryLimit { needed: u64, limit: u64 },
+
+    #[error("Replication error: {0}")]
+    Replication(String),
 }
 
 pub type Result<T> = std::result::Result<T, BlobError>;
 
+/// Variant of a `BlobError` without its details, e.g. to count or match
+/// errors collected in `ProcessingStats::corrupt_blobs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlobErrorKind {
+    Io,
+    HeaderTooLarge,
+    MessageTooLarge,
+    InvalidFormat,
+    Compression,
+    UnknownType,
+    UnsupportedFeature,
+    InvalidString,
+    DeltaOverflow,
+    MixedGroup,
+    RelationCycle,
+    MemoryLimit,
+    Replication,
+}
+
+impl BlobError {
+    pub fn kind(&self) -> BlobErrorKind {
+        match self {
+            BlobError::Io(_) => BlobErrorKind::Io,
+            BlobError::HeaderTooLarge { .. } => BlobErrorKind::HeaderTooLarge,
+            BlobError::MessageTooLarge { .. } => BlobErrorKind::MessageTooLarge,
+            BlobError::InvalidFormat(_) => BlobErrorKind::InvalidFormat,
+            BlobError::Compression(_) => BlobErrorKind::Compression,
+            BlobError::UnknownType(_) => BlobErrorKind::UnknownType,
+            BlobError::UnsupportedFeature { .. } => BlobErrorKind::UnsupportedFeature,
+            BlobError::InvalidString { .. } => BlobErrorKind::InvalidString,
+            BlobError::DeltaOverflow { .. } => BlobErrorKind::DeltaOverflow,
+            BlobError::MixedGroup { .. } => BlobErrorKind::MixedGroup,
+            BlobError::RelationCycle { .. } => BlobErrorKind::RelationCycle,
+            BlobError::MemoryLimit { .. } => BlobErrorKind::MemoryLimit,
+            BlobError::Replication(_) => BlobErrorKind::Replication,
+        }
+    }
+}
+
+/// Convert a file offset or length to `usize`, failing instead of truncating
+/// on targets where `usize` is narrower than 64 bits
+pub(crate) fn checked_usize(value: u64) -> Result<usize> {
+    usize::try_from(value).map_err(|_| {
+        BlobError::InvalidFormat(format!("Offset {value} exceeds the address space of this platform"))
+    })
+}
+
+/// Advance a file offset, failing on overflow
+pub(crate) fn checked_offset(offset: u64, len: u64) -> Result<u64> {
+    offset.checked_add(len).ok_or_else(|| {
+        BlobError::InvalidFormat(format!("Offset {offset} + length {len} overflows"))
+    })
+}
+
 /// Represents the type of data contained in a Blob
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BlobType {
@@ -39,6 +120,10 @@ pub enum BlobType {
     OSMHeader,
     /// Actual OSM map elements (PrimitiveBlock)
     OSMData,
+    /// Changes to OSM map elements, as in .osc.pbf files (PrimitiveBlock; see `ChangeElement`)
+    OSMChange,
+    /// zstd dictionary shared by the file's zstd blobs (nonstandard; see `ZstdDictionary`)
+    ZstdDictionary,
     /// Non-standard blob with custom identifier
     Unknown(String),
 }
@@ -50,6 +135,8 @@ impl FromStr for BlobType {
         Ok(match s {
             "OSMHeader" => BlobType::OSMHeader,
             "OSMData" => BlobType::OSMData,
+            "OSMChange" => BlobType::OSMChange,
+            "OSMZstdDictionary" => BlobType::ZstdDictionary,
             other => BlobType::Unknown(other.to_string()),
         })
     }
@@ -61,9 +148,16 @@ impl BlobType {
         match self {
             BlobType::OSMHeader => "OSMHeader",
             BlobType::OSMData => "OSMData",
+            BlobType::OSMChange => "OSMChange",
+            BlobType::ZstdDictionary => "OSMZstdDictionary",
             BlobType::Unknown(s) => s,
         }
     }
+
+    /// Whether the blob holds a PrimitiveBlock of elements (`OSMData` or `OSMChange`)
+    pub fn holds_elements(&self) -> bool {
+        matches!(self, BlobType::OSMData | BlobType::OSMChange)
+    }
 }
 
 /// Header for a Blob, containing metadata about the blob's content
@@ -130,6 +224,18 @@ pub enum BlobData {
         compressed: Bytes, 
         raw_size: u32 
     },
+    /// LZ4 block with original size (read with the `lz4` feature)
+    Lz4Data {
+        compressed: Bytes,
+        raw_size: u32
+    },
+    /// Zstandard frame with original size (read with the `zstd` feature), and
+    /// the file's dictionary if the frame was compressed with one
+    ZstdData {
+        compressed: Bytes,
+        raw_size: u32,
+        dictionary: Option<ZstdDictionary>
+    },
 }
 
 impl BlobData {
@@ -140,6 +246,8 @@ impl BlobData {
             BlobData::ZlibData { raw_size, .. } => *raw_size,
             BlobData::LzmaData { raw_size, .. } => *raw_size,
             BlobData::Bzip2Data { raw_size, .. } => *raw_size,
+            BlobData::Lz4Data { raw_size, .. } => *raw_size,
+            BlobData::ZstdData { raw_size, .. } => *raw_size,
         }
     }
     
@@ -148,6 +256,49 @@ impl BlobData {
         !matches!(self, BlobData::Raw(_))
     }
     
+    /// Returns the uncompressed data
+    ///
+    /// Zlib data, and zstd and lz4 data with their features, are inflated and
+    /// must come out at exactly `raw_size` bytes. LZMA and bzip2 data are not
+    /// supported yet.
+    pub fn decompress(&self) -> Result<Bytes> {
+        let (format, data) = match self {
+            BlobData::Raw(data) => return Ok(data.clone()),
+            BlobData::ZlibData { compressed, raw_size } => {
+                self.validate_size()?;
+                ("Zlib", zlib_decompress(compressed, *raw_size as usize)?)
+            }
+            #[cfg(feature = "lz4")]
+            BlobData::Lz4Data { compressed, raw_size } => {
+                self.validate_size()?;
+                ("LZ4", lz4_decompress(compressed, *raw_size as usize)?)
+            }
+            #[cfg(feature = "zstd")]
+            BlobData::ZstdData { compressed, raw_size, dictionary } => {
+                self.validate_size()?;
+                ("Zstd", zstd_decompress(compressed, *raw_size as usize, dictionary.as_ref())?)
+            }
+            #[cfg(not(feature = "lz4"))]
+            BlobData::Lz4Data { .. } => {
+                return Err(BlobError::Compression("lz4 blobs need the `lz4` feature".to_string()));
+            }
+            #[cfg(not(feature = "zstd"))]
+            BlobData::ZstdData { .. } => {
+                return Err(BlobError::Compression("zstd blobs need the `zstd` feature".to_string()));
+            }
+            BlobData::LzmaData { .. } => return Err(BlobError::Compression("LZMA blobs are not supported".to_string())),
+            BlobData::Bzip2Data { .. } => return Err(BlobError::Compression("Bzip2 blobs are not supported".to_string())),
+        };
+        let raw_size = self.raw_size();
+        if data.len() != raw_size as usize {
+            return Err(BlobError::Compression(format!(
+                "{format} data inflated to {} bytes, expected raw_size {raw_size}",
+                data.len()
+            )));
+        }
+        Ok(Bytes::from(data))
+    }
+
     /// Validates that the uncompressed size doesn't exceed limits
     pub fn validate_size(&self) -> Result<()> {
         let size = self.raw_size() as usize;
@@ -216,6 +367,11 @@ impl Blob {
     pub fn is_compressed(&self) -> bool {
         self.data.is_compressed()
     }
+
+    /// Returns the uncompressed data
+    pub fn decompress(&self) -> Result<Bytes> {
+        self.data.decompress()
+    }
 }
 
 #[cfg(test)]
@@ -232,6 +388,7 @@ mod tests {
         assert_eq!(BlobType::OSMHeader.as_str(), "OSMHeader");
         assert_eq!(BlobType::OSMData.as_str(), "OSMData");
         assert_eq!(BlobType::Unknown("Custom".to_string()).as_str(), "Custom");
+        assert_eq!(BlobType::from_str(BlobType::ZstdDictionary.as_str()).unwrap(), BlobType::ZstdDictionary);
     }
     
     #[test]
@@ -274,4 +431,35 @@ mod tests {
         assert_eq!(blob.raw_size(), raw_size);
         assert!(blob.is_compressed());
     }
+
+    #[test]
+    fn test_decompress() {
+        // zlib.compress(b"osm")
+        let compressed = Bytes::from_static(&[0x78, 0x9c, 0xcb, 0x2f, 0xce, 0x05, 0x00, 0x02, 0xa3, 0x01, 0x50]);
+        let blob = Blob::new_zlib(BlobType::OSMData, compressed.clone(), 3, 0).unwrap();
+        assert_eq!(blob.decompress().unwrap(), Bytes::from_static(b"osm"));
+
+        // A raw_size that doesn't match the inflated data is rejected either way
+        assert!(Blob::new_zlib(BlobType::OSMData, compressed.clone(), 4, 0).unwrap().decompress().is_err());
+        assert!(Blob::new_zlib(BlobType::OSMData, compressed, 2, 0).unwrap().decompress().is_err());
+
+        let raw = Blob::new_raw(BlobType::OSMData, Bytes::from_static(b"raw"), 0).unwrap();
+        assert_eq!(raw.decompress().unwrap(), Bytes::from_static(b"raw"));
+        let lzma = BlobData::LzmaData { compressed: Bytes::new(), raw_size: 0 };
+        assert!(matches!(lzma.decompress(), Err(BlobError::Compression(_))));
+
+        // `zstd` of "osm"; a short raw_size is rejected like zlib's
+        let zstd = |raw_size| BlobData::ZstdData { compressed: Bytes::from_static(b"\x28\xb5\x2f\xfd\x00\x58\x19\x00\x00osm"), raw_size, dictionary: None };
+        assert_eq!(zstd(3).decompress().ok(), cfg!(feature = "zstd").then(|| Bytes::from_static(b"osm")));
+        assert!(zstd(4).decompress().is_err());
+        let lz4 = BlobData::Lz4Data { compressed: Bytes::from_static(b"\x30osm"), raw_size: 3 };
+        assert_eq!(lz4.decompress().ok(), cfg!(feature = "lz4").then(|| Bytes::from_static(b"osm")));
+    }
+
+    #[test]
+    fn test_checked_conversions() {
+        assert_eq!(checked_offset(u32::MAX as u64, 4).unwrap(), (1 << 32) + 3);
+        assert!(checked_offset(u64::MAX, 1).is_err());
+        assert_eq!(checked_usize(5 << 30).unwrap(), 5 << 30);
+    }
 }
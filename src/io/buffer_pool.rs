@@ -0,0 +1,180 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Pool of reusable byte buffers for blob reads and decompression scratch space
+///
+/// Blob buffers run up to 32 MiB, so allocating one per blob puts a lot of
+/// pressure on the allocator during long scans. Buffers handed out by `get`
+/// return to the pool when dropped and keep their capacity. Cheap to clone;
+/// clones share the same buffers and counters.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Counters of a `BufferPool`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Requests served by a pooled buffer
+    pub hits: u64,
+    /// Requests that had to allocate
+    pub misses: u64,
+    /// Buffers currently idle in the pool
+    pub pooled_buffers: usize,
+    /// Capacity of the idle buffers in bytes
+    pub pooled_bytes: usize,
+}
+
+impl BufferPool {
+    /// Default number of idle buffers kept
+    pub const DEFAULT_MAX_BUFFERS: usize = 4;
+
+    /// Create a pool keeping at most `max_buffers` idle buffers
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                buffers: Mutex::new(Vec::new()),
+                max_buffers,
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Get a zero-filled buffer of exactly `len` bytes
+    ///
+    /// Prefers the idle buffer with the largest capacity, so a pool warmed up by
+    /// large blobs serves later requests without reallocating.
+    pub fn get(&self, len: usize) -> PooledBuffer {
+        let pooled = {
+            let mut buffers = self.lock();
+            let largest = (0..buffers.len()).max_by_key(|&i| buffers[i].capacity());
+            largest.map(|i| buffers.swap_remove(i))
+        };
+
+        let mut buf = match pooled {
+            Some(buf) if buf.capacity() >= len => {
+                self.inner.hits.fetch_add(1, Ordering::Relaxed);
+                buf
+            }
+            other => {
+                self.inner.misses.fetch_add(1, Ordering::Relaxed);
+                other.unwrap_or_default()
+            }
+        };
+
+        buf.clear();
+        buf.resize(len, 0);
+        PooledBuffer { buf, pool: self.clone() }
+    }
+
+    /// Snapshot of the pool counters
+    pub fn stats(&self) -> BufferPoolStats {
+        let buffers = self.lock();
+        BufferPoolStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            pooled_buffers: buffers.len(),
+            pooled_bytes: buffers.iter().map(Vec::capacity).sum(),
+        }
+    }
+
+    fn put(&self, buf: Vec<u8>) {
+        let mut buffers = self.lock();
+        if buffers.len() < self.inner.max_buffers {
+            buffers.push(buf);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        self.inner.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_BUFFERS)
+    }
+}
+
+/// Buffer borrowed from a `BufferPool`, returned to it on drop
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buf: Vec<u8>,
+    pool: BufferPool,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = BufferPool::new(2);
+
+        let mut first = pool.get(1024);
+        first[0] = 7;
+        drop(first);
+
+        let second = pool.get(512);
+        assert_eq!(second.len(), 512);
+        assert!(second.iter().all(|b| *b == 0));
+        assert!(second.capacity() >= 1024);
+        drop(second);
+
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.pooled_buffers, 1);
+    }
+
+    #[test]
+    fn test_idle_buffers_are_capped() {
+        let pool = BufferPool::new(1);
+        let a = pool.get(16);
+        let b = pool.get(16);
+        drop(a);
+        drop(b);
+
+        assert_eq!(pool.stats().pooled_buffers, 1);
+    }
+
+    #[test]
+    fn test_small_pooled_buffer_grows() {
+        let pool = BufferPool::default();
+        drop(pool.get(8));
+
+        let buf = pool.get(4096);
+        assert_eq!(buf.len(), 4096);
+        assert_eq!(pool.stats().misses, 2);
+    }
+}
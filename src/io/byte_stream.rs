@@ -0,0 +1,110 @@
+use std::io::{Read, Seek};
+use std::ops::Range;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use bytes::Bytes;
+use futures_core::Stream;
+use crate::io::blob::Result;
+use crate::io::indexed_reader::IndexedReader;
+
+/// Stream of raw frames from `IndexedReader::blob_byte_stream`
+///
+/// Each item is one complete frame as stored in the file, ready to be sent as
+/// an HTTP body chunk. Reads go to the underlying `Read + Seek` source when the
+/// stream is polled and block for their duration, so wrap slow sources in
+/// `spawn_blocking` or use a local file.
+pub struct BlobByteStream<'a, R: Read + Seek> {
+    reader: &'a mut IndexedReader<R>,
+    blobs: Range<usize>,
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// Stream the frames of the blobs in `range`, undecoded
+    ///
+    /// The range is clamped to the indexed blobs. Start at blob 0 to include
+    /// the OSMHeader blob, so the streamed bytes form a standalone file.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::IndexedReader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = IndexedReader::new(File::open("map.osm.pbf")?)?;
+    /// let stream = reader.blob_byte_stream(0..10);
+    /// // e.g. hand to `http_body_util::StreamBody` after mapping items to frames
+    /// # drop(stream);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn blob_byte_stream(&mut self, range: Range<usize>) -> BlobByteStream<'_, R> {
+        let end = range.end.min(self.blob_count());
+        BlobByteStream { reader: self, blobs: range.start.min(end)..end }
+    }
+}
+
+impl<R: Read + Seek> BlobByteStream<'_, R> {
+    /// Number of frames not yet yielded
+    pub fn remaining(&self) -> usize {
+        self.blobs.len()
+    }
+}
+
+impl<R: Read + Seek> Stream for BlobByteStream<'_, R> {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        Poll::Ready(this.blobs.next().map(|index| this.reader.read_frame_bytes(index)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.blobs.len(), Some(self.blobs.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use std::task::Waker;
+
+    fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut items = Vec::new();
+        while let Poll::Ready(Some(item)) = Pin::new(&mut stream).poll_next(&mut cx) {
+            items.push(item);
+        }
+        items
+    }
+
+    #[test]
+    fn test_full_stream_reproduces_file() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(2).grid_size(10).block_size(20).write_to(&mut data).unwrap();
+        let mut reader = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        let blobs = reader.blob_count();
+
+        let stream = reader.blob_byte_stream(0..usize::MAX);
+        assert_eq!(stream.remaining(), blobs);
+        let frames: Vec<Bytes> = collect(stream).into_iter().map(|frame| frame.unwrap()).collect();
+
+        assert_eq!(frames.len(), blobs);
+        assert_eq!(frames.concat(), data);
+    }
+
+    #[test]
+    fn test_partial_range_is_readable() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(2).grid_size(10).block_size(20).write_to(&mut data).unwrap();
+        let mut reader = IndexedReader::new(Cursor::new(data)).unwrap();
+
+        let mut extract = collect(reader.blob_byte_stream(0..1)).pop().unwrap().unwrap().to_vec();
+        for frame in collect(reader.blob_byte_stream(2..4)) {
+            extract.extend_from_slice(&frame.unwrap());
+        }
+
+        let extract = IndexedReader::new(Cursor::new(extract)).unwrap();
+        assert_eq!(extract.blob_count(), 3);
+        assert!(extract.header_blob().is_some());
+    }
+}
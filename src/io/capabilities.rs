@@ -0,0 +1,127 @@
+use serde::Serialize;
+use crate::io::blob::{MAX_BLOB_HEADER_SIZE, MAX_BLOB_MESSAGE_SIZE};
+use crate::io::features::SUPPORTED_FEATURES;
+use crate::io::memory::MemoryMode;
+
+/// Cargo features of this crate and whether this build has them
+const CARGO_FEATURES: &[(&str, bool)] = &[
+    ("mmap", cfg!(feature = "mmap")),
+    ("async", cfg!(feature = "async")),
+    ("json", cfg!(feature = "json")),
+    ("xml", cfg!(feature = "xml")),
+    ("http", cfg!(feature = "http")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("ffi", cfg!(feature = "ffi")),
+    ("synthetic", cfg!(feature = "synthetic")),
+    ("pure-safe", cfg!(feature = "pure-safe")),
+    ("low-memory", cfg!(feature = "low-memory")),
+    ("zstd", cfg!(feature = "zstd")),
+    ("zstd-write", cfg!(feature = "zstd-write")),
+    ("lz4", cfg!(feature = "lz4")),
+];
+
+/// Blob compressions and whether they can be read
+const COMPRESSIONS: &[(&str, bool)] = &[
+    ("raw", true),
+    ("zlib", true),
+    ("zstd", cfg!(feature = "zstd")),
+    ("lz4", cfg!(feature = "lz4")),
+];
+
+/// What this build of the crate supports, from `capabilities()`
+///
+/// Serializes to JSON (with the `json` feature, `to_json`) for orchestration
+/// systems to check a deployed binary before handing it a job; `missing`
+/// does the check in-process. Fields are only added, never renamed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Crate version
+    pub version: &'static str,
+    /// Cargo features compiled in
+    pub features: Vec<&'static str>,
+    /// Blob compressions that can be read
+    pub compression: Vec<&'static str>,
+    /// Blob types whose contents are read
+    pub blob_types: Vec<&'static str>,
+    /// Header `required_features` files may declare (`SUPPORTED_FEATURES`)
+    pub header_features: Vec<&'static str>,
+    /// Largest serialized `BlobHeader` accepted, in bytes
+    pub max_blob_header_size: usize,
+    /// Largest blob, compressed or inflated, accepted, in bytes
+    pub max_blob_message_size: usize,
+    /// Whether `MmapBlobReader` maps files instead of reading them into memory
+    pub memory_mapped: bool,
+    /// Whether `Reader::new` defaults to `MemoryMode::LowMemory`
+    pub low_memory_default: bool,
+    /// Pointer width of the target in bits
+    pub pointer_width: u32,
+}
+
+/// Capabilities of this build of the crate
+///
+/// # Examples
+/// ```rust
+/// let capabilities = osm_pbf::capabilities();
+/// let missing = capabilities.missing(&["zlib", "DenseNodes"]);
+/// assert!(missing.is_empty(), "unsupported: {missing:?}");
+/// ```
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features: CARGO_FEATURES.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect(),
+        compression: COMPRESSIONS.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect(),
+        blob_types: vec!["OSMHeader", "OSMData", "OSMChange", "OSMZstdDictionary"],
+        header_features: SUPPORTED_FEATURES.to_vec(),
+        max_blob_header_size: MAX_BLOB_HEADER_SIZE,
+        max_blob_message_size: MAX_BLOB_MESSAGE_SIZE,
+        memory_mapped: cfg!(all(feature = "mmap", any(unix, windows), not(miri), not(feature = "pure-safe"))),
+        low_memory_default: MemoryMode::default().is_low_memory(),
+        pointer_width: usize::BITS,
+    }
+}
+
+impl Capabilities {
+    /// Whether `name` is a compiled feature, compression, blob type or
+    /// header feature
+    pub fn supports(&self, name: &str) -> bool {
+        [&self.features, &self.compression, &self.blob_types, &self.header_features]
+            .iter()
+            .any(|names| names.contains(&name))
+    }
+
+    /// Requirements this build doesn't `support`
+    pub fn missing<'a>(&self, requirements: &[&'a str]) -> Vec<&'a str> {
+        requirements.iter().copied().filter(|name| !self.supports(name)).collect()
+    }
+
+    /// Capabilities as a JSON object
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("capabilities serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(capabilities.supports("mmap"), cfg!(feature = "mmap"));
+        assert_eq!(capabilities.supports("xml"), cfg!(feature = "xml"));
+        assert_eq!(capabilities.missing(&["zlib", "OSMChange", "LocationsOnWays", "lzma"]), vec!["lzma"]);
+        assert_eq!(capabilities.compression.contains(&"zstd"), cfg!(feature = "zstd"));
+        assert_eq!(capabilities.supports("lz4"), cfg!(feature = "lz4"));
+        assert_eq!(capabilities.pointer_width as usize, size_of::<usize>() * 8);
+
+        #[cfg(feature = "json")]
+        {
+            let json: serde_json::Value = serde_json::from_str(&capabilities.to_json()).unwrap();
+            assert_eq!(json["max_blob_message_size"], MAX_BLOB_MESSAGE_SIZE);
+            assert_eq!(json["features"].as_array().unwrap().len(), capabilities.features.len());
+        }
+    }
+}
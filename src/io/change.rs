@@ -0,0 +1,120 @@
+use crate::io::reader::OsmElement;
+
+/// What an OsmChange (.osc) file does with the elements it lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum ChangeAction {
+    /// The element is new, at version 1
+    Create,
+    /// The element replaces its previous version
+    Modify,
+    /// The element was deleted; it carries its last version and no tags
+    Delete,
+}
+
+impl ChangeAction {
+    /// The action a change block records for `element` through its metadata:
+    /// `Delete` when it isn't visible, `Create` at version 1, `Modify` otherwise
+    /// (also without metadata)
+    pub fn of(element: &OsmElement) -> Self {
+        let info = match element {
+            OsmElement::Node(node) => &node.info,
+            OsmElement::Way(way) => &way.info,
+            OsmElement::Relation(relation) => &relation.info,
+            OsmElement::ChangeSet(changeset) => &changeset.info,
+        };
+        match info {
+            Some(info) if !info.visible => ChangeAction::Delete,
+            Some(info) if info.version == 1 => ChangeAction::Create,
+            _ => ChangeAction::Modify,
+        }
+    }
+}
+
+/// An element of an `OSMChange` blob with what the change does with it
+///
+/// Change blocks are PrimitiveBlocks like those of `OSMData` blobs; the
+/// action of each element follows from its metadata (see `ChangeAction::of`),
+/// as when osmium converts an .osc file to PBF.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum ChangeElement {
+    Create(OsmElement),
+    Modify(OsmElement),
+    Delete(OsmElement),
+}
+
+impl ChangeElement {
+    pub fn new(action: ChangeAction, element: OsmElement) -> Self {
+        match action {
+            ChangeAction::Create => ChangeElement::Create(element),
+            ChangeAction::Modify => ChangeElement::Modify(element),
+            ChangeAction::Delete => ChangeElement::Delete(element),
+        }
+    }
+
+    /// Wrap an element with the action its metadata records
+    pub fn from_element(element: OsmElement) -> Self {
+        Self::new(ChangeAction::of(&element), element)
+    }
+
+    pub fn action(&self) -> ChangeAction {
+        match self {
+            ChangeElement::Create(_) => ChangeAction::Create,
+            ChangeElement::Modify(_) => ChangeAction::Modify,
+            ChangeElement::Delete(_) => ChangeAction::Delete,
+        }
+    }
+
+    pub fn element(&self) -> &OsmElement {
+        match self {
+            ChangeElement::Create(element) | ChangeElement::Modify(element) | ChangeElement::Delete(element) => element,
+        }
+    }
+
+    pub fn into_element(self) -> OsmElement {
+        match self {
+            ChangeElement::Create(element) | ChangeElement::Modify(element) | ChangeElement::Delete(element) => element,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::header_block::HeaderBlock;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::NodeId;
+    use crate::blocks::primitives::prelude::*;
+    use crate::blocks::string_table::StringTable;
+    use crate::io::indexed_reader::ElementFilter;
+    use crate::io::reader::Reader;
+    use crate::io::writer::{BlockBuffer, PbfWriter};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn node(id: i64, version: i32, visible: bool) -> OsmElement {
+        let info = Info { version, timestamp: 0, changeset: 7, uid: 1, user_sid: 0, visible };
+        OsmElement::Node(Node { id: NodeId(id), keys: vec![], vals: vec![], info: Some(info), location: LatLon::from_raw(0, 0) })
+    }
+
+    #[test]
+    fn test_change_blobs_round_trip() {
+        let mut block = BlockBuffer::default();
+        for element in [node(1, 1, true), node(2, 5, true), node(3, 4, false)] {
+            block.push(&element, &StringTable::default());
+        }
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        writer.write_change_block(&block.finish()).unwrap();
+
+        let mut reader = Reader::new(Cursor::new(writer.into_inner())).unwrap();
+        let mut changes = Vec::new();
+        let stats = reader.for_each_change(&ElementFilter::all(), |change, _| {
+            let OsmElement::Node(node) = change.element() else { panic!("expected a node") };
+            changes.push((change.action(), node.id));
+            Ok(())
+        }).unwrap();
+        assert_eq!(changes, vec![(ChangeAction::Create, NodeId(1)), (ChangeAction::Modify, NodeId(2)), (ChangeAction::Delete, NodeId(3))]);
+        assert_eq!(stats.nodes_processed, 3);
+        assert_eq!(reader.statistics().change_blobs, 1);
+    }
+}
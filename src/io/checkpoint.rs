@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+use crate::io::blob::Result;
+use crate::io::pagination::PageCursor;
+use crate::io::reader::ProcessingStats;
+
+/// Position of a resumable run, persisted in a small text file
+///
+/// The file holds a `PageCursor` token and is replaced atomically on save,
+/// so a crash mid-write leaves the previous checkpoint intact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    /// Checkpoint stored at `path`; the file is created on first save
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saved position, or `None` if nothing was saved yet
+    pub fn load(&self) -> Result<Option<PageCursor>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(token) => Ok(Some(PageCursor::from_token(token.trim())?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Persist `position`, replacing any previous checkpoint
+    pub fn save(&self, position: &PageCursor) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, format!("{}\n", position.to_token()))?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint so the next run starts from the beginning
+    pub fn clear(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Outcome of one `Reader::process_for` run
+#[derive(Debug, Clone)]
+pub struct TimedRun {
+    /// Work done in this run
+    pub stats: ProcessingStats,
+    /// Where the next run resumes, as saved in the checkpoint
+    pub position: PageCursor,
+    /// Number of blobs in the file
+    pub blob_count: usize,
+}
+
+impl TimedRun {
+    /// Whether the whole file has been processed
+    pub fn finished(&self) -> bool {
+        self.position.blob_index() >= self.blob_count
+    }
+
+    /// Fraction of blobs completed over all runs so far, from 0.0 to 1.0
+    pub fn progress(&self) -> f64 {
+        if self.blob_count == 0 {
+            return 1.0;
+        }
+        self.position.blob_index().min(self.blob_count) as f64 / self.blob_count as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_save_load_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path().join("run.checkpoint"));
+        assert_eq!(checkpoint.load().unwrap(), None);
+
+        checkpoint.save(&PageCursor::new(3, 17)).unwrap();
+        checkpoint.save(&PageCursor::new(4, 2)).unwrap();
+        assert_eq!(checkpoint.load().unwrap(), Some(PageCursor::new(4, 2)));
+
+        checkpoint.clear().unwrap();
+        checkpoint.clear().unwrap();
+        assert_eq!(checkpoint.load().unwrap(), None);
+    }
+
+    #[test]
+    fn test_corrupt_checkpoint_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path().join("run.checkpoint"));
+        std::fs::write(checkpoint.path(), "garbage").unwrap();
+        assert!(checkpoint.load().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::blocks::primitives::block::PrimitiveBlock;
+use crate::io::blob::Result;
+use crate::io::decode::{decode_primitive_block, decode_primitive_block_without_metadata};
+use crate::io::writer::encode_primitive_block;
+
+/// Decodes the message of an OSMData blob into a `PrimitiveBlock`
+///
+/// Swapping the decoder lets the readers, filters and pipelines work on
+/// blocks stored in another wire format, e.g. a flat layout for transport
+/// between cluster nodes. Blob framing, compression and the index stay as
+/// in PBF; only the uncompressed block message changes.
+///
+/// Blocks must come out as PBF stores them: coordinates in granularity units
+/// relative to the block offsets, and dense ids and coordinates, way refs and
+/// relation memids delta-encoded.
+pub trait BlockDecoder: Send + Sync {
+    fn decode_block(&self, message: &[u8]) -> Result<PrimitiveBlock>;
+}
+
+/// Encodes a `PrimitiveBlock` into the message of an OSMData blob
+///
+/// The counterpart of `BlockDecoder`, taking blocks in the same stored form.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::{BlockDecoder, BlockEncoder, PrimitiveBlock, Result};
+///
+/// /// Blocks as JSON, for debugging
+/// struct JsonBlocks;
+///
+/// impl BlockEncoder for JsonBlocks {
+///     fn encode_block(&self, block: &PrimitiveBlock) -> Result<Vec<u8>> {
+///         serde_json::to_vec(block).map_err(|e| osm_pbf::BlobError::InvalidFormat(e.to_string()))
+///     }
+/// }
+///
+/// impl BlockDecoder for JsonBlocks {
+///     fn decode_block(&self, message: &[u8]) -> Result<PrimitiveBlock> {
+///         serde_json::from_slice(message).map_err(|e| osm_pbf::BlobError::InvalidFormat(e.to_string()))
+///     }
+/// }
+///
+/// let block = PrimitiveBlock::default();
+/// assert_eq!(JsonBlocks.decode_block(&JsonBlocks.encode_block(&block)?)?, block);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub trait BlockEncoder: Send + Sync {
+    fn encode_block(&self, block: &PrimitiveBlock) -> Result<Vec<u8>>;
+}
+
+/// The standard PBF PrimitiveBlock message, used unless another codec is set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PbfBlockCodec;
+
+impl BlockDecoder for PbfBlockCodec {
+    fn decode_block(&self, message: &[u8]) -> Result<PrimitiveBlock> {
+        decode_primitive_block(message)
+    }
+}
+
+impl BlockEncoder for PbfBlockCodec {
+    fn encode_block(&self, block: &PrimitiveBlock) -> Result<Vec<u8>> {
+        Ok(encode_primitive_block(block))
+    }
+}
+
+/// PBF PrimitiveBlock messages decoded without element metadata
+///
+/// `Info` and `DenseInfo` are skipped on the wire instead of parsed, so
+/// elements come out with `info: None`; set with `Reader::set_skip_metadata`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkipMetadataCodec;
+
+impl BlockDecoder for SkipMetadataCodec {
+    fn decode_block(&self, message: &[u8]) -> Result<PrimitiveBlock> {
+        decode_primitive_block_without_metadata(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::bbox::BoundingBox;
+    use crate::io::blob::BlobError;
+    use crate::io::indexed_reader::{ElementFilter, IndexedReader};
+    use crate::io::manifest::Manifest;
+    use crate::io::plan::PruneReason;
+    use crate::io::reader::{OsmElement, Reader};
+    use crate::io::transform::map_blocks_with_codecs;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    struct JsonBlocks;
+
+    impl BlockEncoder for JsonBlocks {
+        fn encode_block(&self, block: &PrimitiveBlock) -> Result<Vec<u8>> {
+            serde_json::to_vec(block).map_err(|e| BlobError::InvalidFormat(e.to_string()))
+        }
+    }
+
+    impl BlockDecoder for JsonBlocks {
+        fn decode_block(&self, message: &[u8]) -> Result<PrimitiveBlock> {
+            serde_json::from_slice(message).map_err(|e| BlobError::InvalidFormat(e.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_reader_and_writer_with_other_codec() {
+        let mut pbf = Vec::new();
+        crate::synthetic::PlanetBuilder::new(4).grid_size(10).block_size(40).relation_count(3).write_to(&mut pbf).unwrap();
+        let mut json = Vec::new();
+        map_blocks_with_codecs(pbf.as_slice(), &mut json, &PbfBlockCodec, JsonBlocks, |block| block).unwrap();
+        assert_ne!(json, pbf);
+
+        let filter = ElementFilter::all().with_tag_key("highway".to_string());
+        let expected = Reader::new(Cursor::new(pbf.clone())).unwrap().count_filtered(&filter).unwrap();
+        let mut reader = Reader::new(Cursor::new(json.clone())).unwrap();
+        assert!(reader.count_filtered(&filter).is_err());
+        reader.set_block_decoder(JsonBlocks);
+        let counts = reader.count_filtered(&filter).unwrap();
+        assert_eq!((counts.ways_processed, counts.elements_skipped_early), (expected.ways_processed, expected.elements_skipped_early));
+
+        // Index passes and manifests decode with the reader's decoder too
+        let mut pbf_reader = Reader::new(Cursor::new(pbf.clone())).unwrap();
+        pbf_reader.build_deep_index().unwrap();
+        pbf_reader.build_bbox_index(false).unwrap();
+        reader.build_deep_index().unwrap();
+        reader.build_bbox_index(false).unwrap();
+        let (nodes, _) = pbf_reader.collect_filtered(&ElementFilter::nodes_only()).unwrap();
+        let OsmElement::Node(first) = &nodes[0] else { unreachable!() };
+        let near_first = ElementFilter::nodes_only().with_bbox(BoundingBox::from_point(first.location));
+        let pruned = |reader: &mut Reader<Cursor<Vec<u8>>>| -> Vec<_> {
+            reader.explain(&near_first).blobs.into_iter().map(|blob| blob.pruned_by).collect()
+        };
+        let expected = pruned(&mut pbf_reader);
+        assert!(expected.contains(&Some(PruneReason::ElementCounts)) && expected.contains(&Some(PruneReason::BoundingBox)));
+        assert_eq!(pruned(&mut reader), expected);
+        let counts = |manifest: Manifest| -> Vec<_> { manifest.entries.into_iter().map(|entry| entry.element_counts).collect() };
+        let json_manifest = Manifest::from_reader_with(&mut IndexedReader::new(Cursor::new(json.clone())).unwrap(), &JsonBlocks).unwrap();
+        assert_eq!(counts(json_manifest), counts(Manifest::from_reader(&mut IndexedReader::new(Cursor::new(pbf.clone())).unwrap()).unwrap()));
+
+        let mut back = Vec::new();
+        map_blocks_with_codecs(json.as_slice(), &mut back, &JsonBlocks, PbfBlockCodec, |block| block).unwrap();
+        assert_eq!(back, pbf);
+    }
+}
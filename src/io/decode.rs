@@ -0,0 +1,1351 @@
+use std::io::Read;
+use std::sync::Arc;
+use bytes::Bytes;
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::header_block::{OsmosisReplicationTimestamp, OsmosisSequenceNumber};
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::nano_degree::NanoDegree;
+use crate::blocks::osm_id::{ChangesetId, NodeId, RelationId, WayId};
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::codec::BlockDecoder;
+use crate::io::delta::delta_decoded;
+use crate::io::features::ReplicationInfo;
+use crate::io::hot_keys::HotKeys;
+use crate::io::blob::{Blob, BlobData, BlobError, BlobHeader, BlobType, Result, MAX_BLOB_HEADER_SIZE, MAX_BLOB_MESSAGE_SIZE};
+use crate::io::indexed_reader::{BlobIndex, ElementCounts, ElementFilter};
+use crate::io::id_ranges::{BlobIdRanges, ElementId, IdRanges};
+use crate::io::instrument;
+use crate::io::metadata_filter::MetadataFilter;
+use crate::io::polygon::Polygon;
+use crate::io::reader::OsmElement;
+use crate::io::validate::GroupPolicy;
+use crate::io::wire::{zigzag_decode, WireReader, WireValue};
+
+/// Decode all elements of a data blob together with the string table their
+/// tag and role indices refer to
+///
+/// Shared by the unfiltered paths of the high-level Reader and the index
+/// passes of IndexedReader; non-data blobs decode to no elements.
+pub(crate) fn decode_elements(blob: &Blob, group_policy: GroupPolicy, decoder: &dyn BlockDecoder) -> Result<(StringTable, Vec<OsmElement>)> {
+    let decoded = decode_matching_elements(blob, &DecodePredicate::default(), group_policy, decoder)?;
+    Ok((decoded.strings, decoded.elements))
+}
+
+/// Elements of a blob decoded by `decode_matching_elements`
+#[derive(Debug, Default)]
+pub(crate) struct MatchingElements {
+    /// String table the elements' tag and role indices refer to
+    pub strings: StringTable,
+    pub elements: Vec<OsmElement>,
+    /// Position of each element among all elements of the block, in decoding order
+    pub ordinals: Vec<usize>,
+    /// Ids of all nodes inside the predicate's box, with `record_bbox_nodes`
+    pub nodes_in_bbox: Vec<i64>,
+    /// Elements rejected by kind, id, bounding box, metadata or tag presence, or in
+    /// blocks whose string table lacks a filtered tag
+    pub skipped: u64,
+    /// Elements rejected by tag keys or values
+    pub skipped_by_tags: u64,
+    /// Groups holding more than one element kind, decoded under `GroupPolicy::Lenient`
+    pub mixed_groups: u64,
+}
+
+/// Decode the elements of a data blob that may satisfy `predicate`
+///
+/// Node coordinates are converted to nanodegrees; way refs and relation
+/// memids stay delta-encoded. Excluded element kinds are never materialized.
+/// Groups mixing element kinds fail under `GroupPolicy::Strict`.
+///
+/// Within a group, elements are decoded as nodes, dense nodes, ways,
+/// relations and changesets; ordinals count skipped elements too, so they
+/// don't depend on the predicate.
+pub(crate) fn decode_matching_elements(blob: &Blob, predicate: &DecodePredicate, group_policy: GroupPolicy, decoder: &dyn BlockDecoder) -> Result<MatchingElements> {
+    let Some((mut block, mixed_groups)) = decode_data_block(blob, group_policy, decoder)? else {
+        return Ok(MatchingElements::default());
+    };
+    let grid = CoordinateGrid::of(&block);
+    let tags = TagMatcher::new(&predicate.tag_filters, &block.stringtable);
+    if !tags.can_match() && !predicate.record_bbox_nodes {
+        return Ok(MatchingElements {
+            strings: block.stringtable,
+            skipped: block.primitivegroup.iter().map(group_len).sum::<usize>() as u64,
+            mixed_groups,
+            ..Default::default()
+        });
+    }
+
+    let (date_granularity, strings) = (block.date_granularity, &block.stringtable);
+    let mut out = Collector::default();
+    for group in &mut block.primitivegroup {
+        let dense_count = group.dense.as_ref().map_or(0, |dense| dense.id.len());
+        if predicate.include_nodes || predicate.record_bbox_nodes {
+            for mut node in group.nodes.drain(..) {
+                node.location = grid.location(node.location.lat.0, node.location.lon.0);
+                out.observe_location(predicate, node.id.0, node.location);
+                let matches = predicate.include_nodes && predicate.matches(node.id, !node.keys.is_empty(), Some(node.location))
+                    && predicate.matches_info(node.info.as_ref(), date_granularity, strings)
+                    && out.check_tags(tags.matches(&node.keys, &node.vals));
+                out.keep(matches.then_some(OsmElement::Node(node)));
+            }
+            if let Some(dense) = &group.dense {
+                for node in grid.dense_nodes(dense) {
+                    out.observe_location(predicate, node.id.0, node.location);
+                    let matches = predicate.include_nodes && predicate.matches(node.id, node.is_tagged(), Some(node.location))
+                        && predicate.matches_info(node.info.as_ref(), date_granularity, strings)
+                        && out.check_tags(tags.matches_pairs(|| node.tags()));
+                    out.keep(matches.then(|| OsmElement::Node(node.to_node())));
+                }
+            }
+        } else {
+            out.ordinal += group.nodes.len() + dense_count;
+        }
+        if predicate.include_ways {
+            for mut way in group.ways.drain(..) {
+                let matches = predicate.matches(way.id, !way.keys.is_empty(), None)
+                    && predicate.matches_info(way.info.as_ref(), date_granularity, strings)
+                    && out.check_tags(tags.matches(&way.keys, &way.vals));
+                if matches && !way.lat.is_empty() {
+                    grid.way_locations(&mut way);
+                }
+                out.keep(matches.then_some(OsmElement::Way(way)));
+            }
+        } else {
+            out.ordinal += group.ways.len();
+        }
+        if predicate.include_relations {
+            for relation in group.relations.drain(..) {
+                let matches = predicate.matches(relation.id, !relation.keys.is_empty(), None)
+                    && predicate.matches_info(relation.info.as_ref(), date_granularity, strings)
+                    && out.check_tags(tags.matches(&relation.keys, &relation.vals));
+                out.keep(matches.then_some(OsmElement::Relation(relation)));
+            }
+        } else {
+            out.ordinal += group.relations.len();
+        }
+        if predicate.include_changesets {
+            for changeset in group.changesets.drain(..) {
+                let matches = predicate.matches_info(changeset.info.as_ref(), date_granularity, strings)
+                    && out.check_tags(tags.matches(&changeset.keys, &changeset.vals));
+                out.keep(matches.then_some(OsmElement::ChangeSet(changeset)));
+            }
+        } else {
+            out.ordinal += group.changesets.len();
+        }
+    }
+    let skipped = (out.ordinal - out.elements.len()) as u64 - out.skipped_by_tags;
+    instrument::record_elements(out.elements.len() as u64);
+    Ok(MatchingElements {
+        strings: block.stringtable,
+        elements: out.elements,
+        ordinals: out.ordinals,
+        nodes_in_bbox: out.nodes_in_bbox,
+        skipped,
+        skipped_by_tags: out.skipped_by_tags,
+        mixed_groups,
+    })
+}
+
+/// Elements kept by `decode_matching_elements` with their ordinals
+#[derive(Default)]
+struct Collector {
+    elements: Vec<OsmElement>,
+    ordinals: Vec<usize>,
+    nodes_in_bbox: Vec<i64>,
+    skipped_by_tags: u64,
+    /// Ordinal of the next element
+    ordinal: usize,
+}
+
+impl Collector {
+    /// Advance past an element, keeping it if it matched
+    fn keep(&mut self, element: Option<OsmElement>) {
+        if let Some(element) = element {
+            self.elements.push(element);
+            self.ordinals.push(self.ordinal);
+        }
+        self.ordinal += 1;
+    }
+
+    /// Pass on the result of a tag check, counting rejections
+    fn check_tags(&mut self, matches: bool) -> bool {
+        self.skipped_by_tags += u64::from(!matches);
+        matches
+    }
+
+    fn observe_location(&mut self, predicate: &DecodePredicate, id: i64, location: LatLon) {
+        if predicate.record_bbox_nodes && predicate.bbox.is_some() && predicate.in_area(location) {
+            self.nodes_in_bbox.push(id);
+        }
+    }
+}
+
+/// Per-type counts of the elements of a data blob matching a filter
+#[derive(Debug, Default)]
+pub(crate) struct MatchCounts {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    pub changesets: u64,
+    /// Elements rejected by kind, id, bounding box, metadata or tag presence
+    pub skipped_early: u64,
+    /// Elements rejected by tag keys or values
+    pub skipped_late: u64,
+    /// Groups holding more than one element kind, counted under `GroupPolicy::Lenient`
+    pub mixed_groups: u64,
+}
+
+impl MatchCounts {
+    /// Count an element in `slot` if it passed both checks, as skipped otherwise
+    fn record(&mut self, slot: fn(&mut Self) -> &mut u64, early: bool, late: bool) {
+        let slot = match (early, late) {
+            (false, _) => &mut self.skipped_early,
+            (true, false) => &mut self.skipped_late,
+            (true, true) => slot(self),
+        };
+        *slot += 1;
+    }
+}
+
+/// Count the elements of a data blob matching `filter` without materializing them
+///
+/// Applies the same checks as `decode_matching_elements`.
+pub(crate) fn count_matching_elements(blob: &Blob, filter: &ElementFilter, group_policy: GroupPolicy, decoder: &dyn BlockDecoder) -> Result<MatchCounts> {
+    let Some((block, mixed_groups)) = decode_data_block(blob, group_policy, decoder)? else {
+        return Ok(MatchCounts::default());
+    };
+    let grid = CoordinateGrid::of(&block);
+    let predicate = DecodePredicate::from(filter);
+    let tags = TagMatcher::new(&predicate.tag_filters, &block.stringtable);
+
+    let (date_granularity, strings) = (block.date_granularity, &block.stringtable);
+    let mut counts = MatchCounts { mixed_groups, ..Default::default() };
+    if !tags.can_match() {
+        counts.skipped_early = block.primitivegroup.iter().map(group_len).sum::<usize>() as u64;
+        return Ok(counts);
+    }
+    for group in &block.primitivegroup {
+        for node in &group.nodes {
+            let location = grid.location(node.location.lat.0, node.location.lon.0);
+            let early = predicate.include_nodes && predicate.matches(node.id, !node.keys.is_empty(), Some(location))
+                && predicate.matches_info(node.info.as_ref(), date_granularity, strings);
+            counts.record(|c| &mut c.nodes, early, early && tags.matches(&node.keys, &node.vals));
+        }
+        if let Some(dense) = &group.dense {
+            if predicate.include_nodes {
+                for node in grid.dense_nodes(dense) {
+                    let early = predicate.matches(node.id, node.is_tagged(), Some(node.location))
+                        && predicate.matches_info(node.info.as_ref(), date_granularity, strings);
+                    counts.record(|c| &mut c.nodes, early, early && tags.matches_pairs(|| node.tags()));
+                }
+            } else {
+                counts.skipped_early += dense.id.len() as u64;
+            }
+        }
+        for way in &group.ways {
+            let early = predicate.include_ways && predicate.matches(way.id, !way.keys.is_empty(), None)
+                && predicate.matches_info(way.info.as_ref(), date_granularity, strings);
+            counts.record(|c| &mut c.ways, early, early && tags.matches(&way.keys, &way.vals));
+        }
+        for relation in &group.relations {
+            let early = predicate.include_relations && predicate.matches(relation.id, !relation.keys.is_empty(), None)
+                && predicate.matches_info(relation.info.as_ref(), date_granularity, strings);
+            counts.record(|c| &mut c.relations, early, early && tags.matches(&relation.keys, &relation.vals));
+        }
+        for changeset in &group.changesets {
+            let early = predicate.include_changesets && predicate.matches_info(changeset.info.as_ref(), date_granularity, strings);
+            counts.record(|c| &mut c.changesets, early, early && tags.matches(&changeset.keys, &changeset.vals));
+        }
+    }
+    Ok(counts)
+}
+
+/// Fill in the element counts, id ranges and hot key presence of a data
+/// blob's index entry
+///
+/// Only ids, and tag keys when `hot_keys` isn't empty, are decoded; tag
+/// values, metadata and coordinates are left alone. Entries of other blob
+/// types are left unchanged.
+pub(crate) fn summarize_data_blob(blob: &Blob, entry: &mut BlobIndex, decoder: &dyn BlockDecoder, hot_keys: &HotKeys) -> Result<()> {
+    let Some((block, _)) = decode_data_block(blob, GroupPolicy::Lenient, decoder)? else {
+        return Ok(());
+    };
+    let mut counts = ElementCounts::default();
+    let mut ranges = BlobIdRanges::default();
+    let mut keys = Vec::new();
+    for group in &block.primitivegroup {
+        let dense = group.dense.as_ref().map_or(&[][..], |dense| &dense.id[..]);
+        counts.nodes += (group.nodes.len() + dense.len()) as u32;
+        counts.ways += group.ways.len() as u32;
+        counts.relations += group.relations.len() as u32;
+        counts.changesets += group.changesets.len() as u32;
+
+        group.nodes.iter().for_each(|node| ranges.observe(node.id));
+        delta_decoded(dense).for_each(|id| ranges.observe(NodeId(id)));
+        group.ways.iter().for_each(|way| ranges.observe(way.id));
+        group.relations.iter().for_each(|relation| ranges.observe(relation.id));
+
+        if !hot_keys.is_empty() {
+            keys.extend(group.nodes.iter().flat_map(|node| &node.keys));
+            keys.extend(group.ways.iter().flat_map(|way| &way.keys));
+            keys.extend(group.relations.iter().flat_map(|relation| &relation.keys));
+            // Dense tags are key, value pairs with a 0 after each node's
+            if let Some(dense) = &group.dense {
+                let mut keys_vals = dense.keys_vals.iter();
+                while let Some(&key) = keys_vals.next() {
+                    if key != 0 {
+                        keys.push(key as u32);
+                        keys_vals.next();
+                    }
+                }
+            }
+        }
+    }
+    entry.element_counts = counts;
+    entry.id_ranges = Some(ranges);
+    if !hot_keys.is_empty() {
+        entry.key_presence = Some(hot_keys.presence_of_keys(&block.stringtable, keys));
+    }
+    Ok(())
+}
+
+/// Decode a data blob's block with `decoder`, checking its groups against
+/// `group_policy`
+///
+/// Returns `None` for blobs of other types, otherwise the block and the
+/// number of mixed groups tolerated.
+fn decode_data_block(blob: &Blob, group_policy: GroupPolicy, decoder: &dyn BlockDecoder) -> Result<Option<(PrimitiveBlock, u64)>> {
+    if !blob.blob_type().holds_elements() {
+        return Ok(None);
+    }
+    let _span = instrument::decode_blob(blob.offset, blob.raw_size());
+    let payload = blob_payload(blob)?;
+    if blob.data.is_compressed() {
+        instrument::record_decompressed(payload.len() as u64);
+    }
+    let block = {
+        let _span = instrument::decode_block(payload.len());
+        decoder.decode_block(&payload)?
+    };
+
+    let mut mixed_groups = 0;
+    for (index, group) in block.primitivegroup.iter().enumerate() {
+        let kinds = group_kinds(group);
+        if kinds.len() > 1 {
+            if group_policy == GroupPolicy::Strict {
+                return Err(BlobError::MixedGroup { group: index, kinds: kinds.iter().map(|k| k.to_string()).collect() });
+            }
+            mixed_groups += 1;
+        }
+    }
+    Ok(Some((block, mixed_groups)))
+}
+
+/// Tag filters of an `ElementFilter` resolved to the string indices of one block
+///
+/// A string table may hold the same string more than once, so each key and
+/// value maps to a list of indices.
+struct TagMatcher {
+    /// Per filter: indices of the key, and of the value when one is required
+    filters: Vec<(Vec<u32>, Option<Vec<u32>>)>,
+}
+
+impl TagMatcher {
+    fn new(tag_filters: &[(String, Option<String>)], strings: &StringTable) -> Self {
+        let indices = |wanted: &str| -> Vec<u32> {
+            strings.s.iter().enumerate().filter(|(_, s)| s.as_str() == wanted).map(|(i, _)| i as u32).collect()
+        };
+        let filters = tag_filters.iter()
+            .map(|(key, value)| (indices(key), value.as_deref().map(indices)))
+            .collect();
+        Self { filters }
+    }
+
+    /// False when a filtered key or value is missing from the string table,
+    /// so no element of the block can match
+    fn can_match(&self) -> bool {
+        self.filters.iter().all(|(keys, values)| !keys.is_empty() && values.as_ref().is_none_or(|values| !values.is_empty()))
+    }
+
+    fn matches(&self, keys: &[u32], vals: &[u32]) -> bool {
+        self.matches_pairs(|| keys.iter().copied().zip(vals.iter().copied()))
+    }
+
+    fn matches_pairs<I: Iterator<Item = (u32, u32)>>(&self, pairs: impl Fn() -> I) -> bool {
+        self.filters.iter().all(|(keys, values)| {
+            pairs().any(|(k, v)| keys.contains(&k) && values.as_ref().is_none_or(|values| values.contains(&v)))
+        })
+    }
+}
+
+/// Number of elements in a group, of all kinds
+fn group_len(group: &PrimitiveGroup) -> usize {
+    group.nodes.len()
+        + group.dense.as_ref().map_or(0, |dense| dense.id.len())
+        + group.ways.len()
+        + group.relations.len()
+        + group.changesets.len()
+}
+
+/// Non-empty element collections of a group
+fn group_kinds(group: &PrimitiveGroup) -> Vec<&'static str> {
+    [
+        ("nodes", !group.nodes.is_empty()),
+        ("dense nodes", group.dense.as_ref().is_some_and(|dense| !dense.id.is_empty())),
+        ("ways", !group.ways.is_empty()),
+        ("relations", !group.relations.is_empty()),
+        ("changesets", !group.changesets.is_empty()),
+    ]
+    .into_iter()
+    .filter_map(|(kind, present)| present.then_some(kind))
+    .collect()
+}
+
+/// Read one `[u32 BE header length][BlobHeader][Blob]` frame starting at `offset`
+///
+/// Returns `None` on a clean end of input, otherwise the blob and the total
+/// frame length in bytes.
+pub(crate) fn read_frame<R: Read>(reader: &mut R, offset: u64) -> Result<Option<(Blob, u64)>> {
+    let mut len_bytes = [0u8; 4];
+    match reader.read_exact(&mut len_bytes) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(BlobError::Io(e)),
+    }
+
+    let header_len = u32::from_be_bytes(len_bytes) as usize;
+    if header_len > MAX_BLOB_HEADER_SIZE {
+        return Err(BlobError::HeaderTooLarge { size: header_len, max: MAX_BLOB_HEADER_SIZE });
+    }
+    let mut header_bytes = vec![0u8; header_len];
+    reader.read_exact(&mut header_bytes)?;
+    let header = decode_blob_header(&header_bytes)?;
+
+    let datasize = header.datasize as usize;
+    if datasize > MAX_BLOB_MESSAGE_SIZE {
+        return Err(BlobError::MessageTooLarge { size: datasize, max: MAX_BLOB_MESSAGE_SIZE });
+    }
+    let mut blob_bytes = vec![0u8; datasize];
+    reader.read_exact(&mut blob_bytes)?;
+
+    let blob = decode_blob(header, &blob_bytes, offset)?;
+    Ok(Some((blob, 4 + header_len as u64 + datasize as u64)))
+}
+
+/// Decode the `required_features`, `optional_features` and replication fields of a HeaderBlock message
+pub(crate) fn decode_header_features(buf: &[u8]) -> Result<(Vec<String>, Vec<String>, ReplicationInfo)> {
+    let mut required = Vec::new();
+    let mut optional = Vec::new();
+    let mut replication = ReplicationInfo::default();
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            4 => required.push(value.as_str()?.to_string()),
+            5 => optional.push(value.as_str()?.to_string()),
+            32 => replication.timestamp = OsmosisReplicationTimestamp::new(value.as_i64()?),
+            33 => replication.sequence_number = OsmosisSequenceNumber::new(value.as_i64()?),
+            34 => replication.base_url = Some(value.as_str()?.to_string()),
+            _ => {}
+        }
+    }
+    Ok((required, optional, replication))
+}
+
+/// Decode a BlobHeader message
+pub(crate) fn decode_blob_header(buf: &[u8]) -> Result<BlobHeader> {
+    let mut blob_type = None;
+    let mut datasize = None;
+    let mut indexdata = None;
+
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => blob_type = Some(value.as_str()?.parse::<BlobType>().unwrap()),
+            2 => indexdata = Some(Bytes::copy_from_slice(value.as_bytes()?)),
+            3 => datasize = Some(value.as_i64()?),
+            _ => {}
+        }
+    }
+
+    let blob_type = blob_type.ok_or_else(|| BlobError::InvalidFormat("BlobHeader without type".to_string()))?;
+    let datasize = datasize
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or_else(|| BlobError::InvalidFormat("BlobHeader without valid datasize".to_string()))?;
+
+    Ok(BlobHeader { blob_type, datasize, indexdata })
+}
+
+/// Decode a Blob message belonging to `header`
+pub(crate) fn decode_blob(header: BlobHeader, buf: &[u8], offset: u64) -> Result<Blob> {
+    let mut raw_size = None;
+    let mut data = None;
+
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => data = Some(BlobData::Raw(Bytes::copy_from_slice(value.as_bytes()?))),
+            2 => raw_size = Some(value.as_u64()? as u32),
+            3 => data = Some(BlobData::ZlibData { compressed: Bytes::copy_from_slice(value.as_bytes()?), raw_size: 0 }),
+            4 => data = Some(BlobData::LzmaData { compressed: Bytes::copy_from_slice(value.as_bytes()?), raw_size: 0 }),
+            5 => data = Some(BlobData::Bzip2Data { compressed: Bytes::copy_from_slice(value.as_bytes()?), raw_size: 0 }),
+            6 => data = Some(BlobData::Lz4Data { compressed: Bytes::copy_from_slice(value.as_bytes()?), raw_size: 0 }),
+            7 => data = Some(BlobData::ZstdData { compressed: Bytes::copy_from_slice(value.as_bytes()?), raw_size: 0, dictionary: None }),
+            _ => {}
+        }
+    }
+
+    let mut data = data.ok_or_else(|| BlobError::InvalidFormat("Blob without data".to_string()))?;
+    if let BlobData::ZlibData { raw_size: size, .. }
+        | BlobData::LzmaData { raw_size: size, .. }
+        | BlobData::Bzip2Data { raw_size: size, .. }
+        | BlobData::Lz4Data { raw_size: size, .. }
+        | BlobData::ZstdData { raw_size: size, .. } = &mut data
+    {
+        *size = raw_size.unwrap_or(0);
+    }
+    data.validate_size()?;
+
+    Ok(Blob { header, data, offset })
+}
+
+/// Uncompressed block message of a blob
+pub(crate) fn blob_payload(blob: &Blob) -> Result<Bytes> {
+    blob.decompress()
+}
+
+/// Decode a PrimitiveBlock message
+///
+/// Values are kept as stored in the file: coordinates in granularity units and
+/// delta-encoded fields (dense ids/coordinates, way refs, relation memids) undecoded.
+pub fn decode_primitive_block(buf: &[u8]) -> Result<PrimitiveBlock> {
+    decode_block(buf, false)
+}
+
+/// Decode a PrimitiveBlock message, passing over element metadata
+///
+/// `Info` and `DenseInfo` fields are skipped on the wire rather than parsed,
+/// so every element comes out with `info: None` and dense nodes without
+/// `denseinfo`. Otherwise as `decode_primitive_block`.
+pub(crate) fn decode_primitive_block_without_metadata(buf: &[u8]) -> Result<PrimitiveBlock> {
+    decode_block(buf, true)
+}
+
+fn decode_block(buf: &[u8], skip_metadata: bool) -> Result<PrimitiveBlock> {
+    let mut block = PrimitiveBlock {
+        stringtable: StringTable { s: Vec::new() },
+        ..Default::default()
+    };
+
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => block.stringtable = decode_string_table(value.as_bytes()?)?,
+            2 => block.primitivegroup.push(decode_group(value.as_bytes()?, skip_metadata)?),
+            17 => block.granularity = value.as_i64()? as i32,
+            18 => block.date_granularity = value.as_i64()? as i32,
+            19 => block.lat_offset = value.as_i64()?,
+            20 => block.lon_offset = value.as_i64()?,
+            _ => {}
+        }
+    }
+
+    Ok(block)
+}
+
+/// Decode only the element of type `kind` with id `id` from a PrimitiveBlock message
+///
+/// Other elements are passed over by id without being materialized: dense ids
+/// are summed until the id shows up, and way and relation messages are read
+/// up to their id field. The element comes out as from
+/// `decode_matching_elements`, with a string table holding just the strings
+/// it references.
+pub(crate) fn find_element(buf: &[u8], kind: MemberType, id: i64) -> Result<Option<(OsmElement, StringTable)>> {
+    let mut strings: &[u8] = &[];
+    let mut grid = CoordinateGrid { granularity: PrimitiveBlock::default_granularity() as i64, lat_offset: 0, lon_offset: 0 };
+    let mut found = None;
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => strings = value.as_bytes()?,
+            2 if found.is_none() => found = find_in_group(value.as_bytes()?, kind, id)?,
+            17 => grid.granularity = value.as_i64()?,
+            19 => grid.lat_offset = value.as_i64()?,
+            20 => grid.lon_offset = value.as_i64()?,
+            _ => {}
+        }
+    }
+    let Some(mut element) = found else {
+        return Ok(None);
+    };
+    match &mut element {
+        OsmElement::Node(node) => node.location = grid.location(node.location.lat.0, node.location.lon.0),
+        OsmElement::Way(way) if !way.lat.is_empty() => grid.way_locations(way),
+        _ => {}
+    }
+    let strings = referenced_strings(strings, &mut element)?;
+    Ok(Some((element, strings)))
+}
+
+fn find_in_group(buf: &[u8], kind: MemberType, id: i64) -> Result<Option<OsmElement>> {
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        let found = match (kind, field) {
+            (MemberType::Node, 1) if message_id(value.as_bytes()?, true)? == Some(id) => Some(OsmElement::Node(decode_node(value.as_bytes()?, false)?)),
+            (MemberType::Node, 2) => find_dense_node(value.as_bytes()?, id)?.map(OsmElement::Node),
+            (MemberType::Way, 3) if message_id(value.as_bytes()?, false)? == Some(id) => Some(OsmElement::Way(decode_way(value.as_bytes()?, false)?)),
+            (MemberType::Relation, 4) if message_id(value.as_bytes()?, false)? == Some(id) => {
+                Some(OsmElement::Relation(decode_relation(value.as_bytes()?, false)?))
+            }
+            _ => None,
+        };
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+    Ok(None)
+}
+
+/// The id field of a Node, Way or Relation message, reading no further
+fn message_id(buf: &[u8], zigzag: bool) -> Result<Option<i64>> {
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        if field == 1 {
+            return Ok(Some(if zigzag { value.as_sint64()? } else { value.as_i64()? }));
+        }
+    }
+    Ok(None)
+}
+
+/// The node with id `id` of a DenseNodes message, location in granularity units
+fn find_dense_node(buf: &[u8], id: i64) -> Result<Option<Node>> {
+    let (mut ids, mut lat, mut lon, mut keys_vals): (&[u8], &[u8], &[u8], &[u8]) = (&[], &[], &[], &[]);
+    let mut info = None;
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => ids = value.as_bytes()?,
+            5 => info = Some(value.as_bytes()?),
+            8 => lat = value.as_bytes()?,
+            9 => lon = value.as_bytes()?,
+            10 => keys_vals = value.as_bytes()?,
+            _ => {}
+        }
+    }
+
+    let mut reader = WireReader::new(ids);
+    let (mut current, mut index) = (0i64, 0usize);
+    loop {
+        if reader.is_empty() {
+            return Ok(None);
+        }
+        current = current.wrapping_add(zigzag_decode(reader.read_varint()?));
+        if current == id {
+            break;
+        }
+        index += 1;
+    }
+
+    let column = |buf, zigzag, delta| packed_value(buf, index, zigzag, delta).map(Option::unwrap_or_default);
+    let mut node = Node::new(id, LatLon::from_raw(column(lat, true, true)?, column(lon, true, true)?));
+    let mut reader = WireReader::new(keys_vals);
+    let mut skipped = 0;
+    while skipped < index && !reader.is_empty() {
+        if reader.read_varint()? == 0 {
+            skipped += 1;
+        } else {
+            reader.read_varint()?;
+        }
+    }
+    while !reader.is_empty() {
+        let key = reader.read_varint()? as u32;
+        if key == 0 {
+            break;
+        }
+        node.add_tag(key, reader.read_varint()? as u32);
+    }
+
+    if let Some(info) = info {
+        let mut columns: [&[u8]; 6] = [&[]; 6];
+        let mut reader = WireReader::new(info);
+        while let Some((field, value)) = reader.next_field()? {
+            if let Some(slot) = (field as usize).checked_sub(1).and_then(|i| columns.get_mut(i)) {
+                *slot = value.as_bytes()?;
+            }
+        }
+        node.info = Some(Info {
+            version: column(columns[0], false, false)? as i32,
+            timestamp: column(columns[1], true, true)?,
+            changeset: column(columns[2], true, true)?,
+            uid: column(columns[3], true, true)? as i32,
+            user_sid: column(columns[4], true, true)? as u32,
+            visible: packed_value(columns[5], index, false, false)?.is_none_or(|visible| visible != 0),
+        });
+    }
+    Ok(Some(node))
+}
+
+/// Value at `index` of a packed varint column, summing the values up to it
+/// for delta-coded columns; `None` if the column is shorter
+fn packed_value(buf: &[u8], index: usize, zigzag: bool, delta: bool) -> Result<Option<i64>> {
+    let mut reader = WireReader::new(buf);
+    let mut value = 0i64;
+    for _ in 0..=index {
+        if reader.is_empty() {
+            return Ok(None);
+        }
+        let raw = reader.read_varint()?;
+        let raw = if zigzag { zigzag_decode(raw) } else { raw as i64 };
+        value = if delta { value.wrapping_add(raw) } else { raw };
+    }
+    Ok(Some(value))
+}
+
+/// Copy the strings an element references out of a StringTable message and
+/// point its indices at the copies
+///
+/// Indices outside the table resolve to the empty string, as with
+/// `StringTable::get_string_or_empty`.
+fn referenced_strings(table: &[u8], element: &mut OsmElement) -> Result<StringTable> {
+    let (keys, vals, info, roles) = match element {
+        OsmElement::Node(n) => (&mut n.keys, &mut n.vals, &mut n.info, None),
+        OsmElement::Way(w) => (&mut w.keys, &mut w.vals, &mut w.info, None),
+        OsmElement::Relation(r) => (&mut r.keys, &mut r.vals, &mut r.info, Some(&mut r.roles_sid)),
+        OsmElement::ChangeSet(c) => (&mut c.keys, &mut c.vals, &mut c.info, None),
+    };
+    let mut wanted: Vec<usize> = keys.iter().chain(vals.iter()).map(|i| *i as usize)
+        .chain(info.iter().map(|info| info.user_sid as usize))
+        .chain(roles.iter().flat_map(|roles| roles.iter().map(|i| *i as usize)))
+        .collect();
+    wanted.sort_unstable();
+    wanted.dedup();
+
+    let mut strings = StringTable { s: vec![String::new()] };
+    let mut remap = std::collections::HashMap::new();
+    let mut wanted = wanted.into_iter().filter(|index| *index != 0).peekable();
+    let mut reader = WireReader::new(table);
+    let mut index = 0;
+    while let (Some(&next), Some((field, value))) = (wanted.peek(), reader.next_field()?) {
+        if field != 1 {
+            continue;
+        }
+        if index == next {
+            remap.insert(index, strings.s.len());
+            strings.s.push(value.as_str()?.to_string());
+            wanted.next();
+        }
+        index += 1;
+    }
+
+    let map = |index: usize| remap.get(&index).copied().unwrap_or(0);
+    keys.iter_mut().chain(vals.iter_mut()).for_each(|i| *i = map(*i as usize) as u32);
+    if let Some(info) = info {
+        info.user_sid = map(info.user_sid as usize) as u32;
+    }
+    if let Some(roles) = roles {
+        roles.iter_mut().for_each(|i| *i = map(*i as usize) as i32);
+    }
+    Ok(strings)
+}
+
+/// Element predicate pushed down into the decoder
+///
+/// Evaluated on ids, tag presence, node locations, metadata and tag filters
+/// before an element's tags and metadata are copied out. Tag filters are
+/// resolved against each block's string table first, so blocks lacking a
+/// filtered key or value are skipped whole.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DecodePredicate {
+    pub include_nodes: bool,
+    pub include_ways: bool,
+    pub include_relations: bool,
+    pub include_changesets: bool,
+    /// Skip elements without tags, e.g. because a tag filter can't match them
+    pub tagged_only: bool,
+    /// Skip nodes outside the box
+    pub bbox: Option<BoundingBox>,
+    /// Skip nodes outside the polygon too
+    pub polygon: Option<Arc<Polygon>>,
+    /// Inclusive id ranges per element type; a type without ranges takes any id
+    pub id_ranges: IdRanges,
+    /// Record the ids of all nodes inside `bbox` and `polygon`, excluded ones too
+    pub record_bbox_nodes: bool,
+    /// Tags an element must carry, with `None` for any value
+    pub tag_filters: Vec<(String, Option<String>)>,
+    /// Metadata predicates, `None` if none is set
+    pub metadata: Option<MetadataFilter>,
+}
+
+impl Default for DecodePredicate {
+    fn default() -> Self {
+        Self {
+            include_nodes: true,
+            include_ways: true,
+            include_relations: true,
+            include_changesets: true,
+            tagged_only: false,
+            bbox: None,
+            polygon: None,
+            id_ranges: IdRanges::default(),
+            record_bbox_nodes: false,
+            tag_filters: Vec::new(),
+            metadata: None,
+        }
+    }
+}
+
+impl From<&ElementFilter> for DecodePredicate {
+    fn from(filter: &ElementFilter) -> Self {
+        Self {
+            include_nodes: filter.include_nodes,
+            include_ways: filter.include_ways,
+            include_relations: filter.include_relations,
+            include_changesets: filter.include_changesets,
+            tagged_only: !filter.tag_filters.is_empty(),
+            bbox: filter.bbox,
+            polygon: filter.polygon.clone(),
+            id_ranges: filter.id_ranges.clone(),
+            record_bbox_nodes: filter.bbox_dependencies(),
+            tag_filters: filter.tag_filters.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+            metadata: (!filter.metadata.is_empty()).then(|| filter.metadata.clone()),
+        }
+    }
+}
+
+impl DecodePredicate {
+    fn matches<I: ElementId>(&self, id: I, tagged: bool, location: Option<LatLon>) -> bool {
+        (tagged || !self.tagged_only)
+            && location.is_none_or(|location| self.in_area(location))
+            && self.id_ranges.contains(id)
+    }
+
+    /// Whether a node location is inside the box and polygon, if any
+    fn in_area(&self, location: LatLon) -> bool {
+        self.bbox.is_none_or(|bbox| bbox.contains(location)) && self.polygon.as_ref().is_none_or(|polygon| polygon.contains(location))
+    }
+
+    /// Whether an element's metadata passes, resolved against its block's
+    /// date granularity and strings
+    fn matches_info(&self, info: Option<&Info>, date_granularity: i32, strings: &StringTable) -> bool {
+        self.metadata.as_ref().is_none_or(|metadata| metadata.matches(info, date_granularity, strings))
+    }
+}
+
+/// Coordinate granularity and offsets of a block
+#[derive(Debug, Clone, Copy)]
+struct CoordinateGrid {
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl CoordinateGrid {
+    fn of(block: &PrimitiveBlock) -> Self {
+        Self { granularity: block.granularity as i64, lat_offset: block.lat_offset, lon_offset: block.lon_offset }
+    }
+
+    /// Decoded nodes of a dense group of the block
+    fn dense_nodes(self, dense: &DenseNodes) -> DenseNodeIter<'_> {
+        DenseNodeIter::new(dense, self.granularity as i32, self.lat_offset, self.lon_offset)
+    }
+
+    /// Location in nanodegrees of raw coordinates in granularity units
+    fn location(self, lat: i64, lon: i64) -> LatLon {
+        LatLon::from_raw(
+            self.lat_offset.wrapping_add(self.granularity.wrapping_mul(lat)),
+            self.lon_offset.wrapping_add(self.granularity.wrapping_mul(lon)),
+        )
+    }
+
+    /// Convert a way's stored node locations to nanodegree deltas
+    fn way_locations(self, way: &mut Way) {
+        let (mut lat, mut lon) = (0i64, 0i64);
+        let mut previous = LatLon::from_raw(0, 0);
+        for (dlat, dlon) in way.lat.iter_mut().zip(way.lon.iter_mut()) {
+            lat = lat.wrapping_add(*dlat);
+            lon = lon.wrapping_add(*dlon);
+            let location = self.location(lat, lon);
+            *dlat = location.lat.0.wrapping_sub(previous.lat.0);
+            *dlon = location.lon.0.wrapping_sub(previous.lon.0);
+            previous = location;
+        }
+    }
+}
+
+/// Materialize the dense nodes that satisfy `predicate`
+///
+/// Skipped nodes still advance the delta accumulators and the `keys_vals`
+/// cursor, but their tags are never copied or allocated.
+fn decode_string_table(buf: &[u8]) -> Result<StringTable> {
+    let mut table = StringTable { s: Vec::new() };
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        if field == 1 {
+            table.s.push(value.as_str()?.to_string());
+        }
+    }
+    Ok(table)
+}
+
+fn decode_group(buf: &[u8], skip_metadata: bool) -> Result<PrimitiveGroup> {
+    let mut group = PrimitiveGroup::default();
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => group.nodes.push(decode_node(value.as_bytes()?, skip_metadata)?),
+            2 => group.dense = Some(decode_dense(value.as_bytes()?, skip_metadata)?),
+            3 => group.ways.push(decode_way(value.as_bytes()?, skip_metadata)?),
+            4 => group.relations.push(decode_relation(value.as_bytes()?, skip_metadata)?),
+            5 => group.changesets.push(decode_changeset(value.as_bytes()?)?),
+            _ => {}
+        }
+    }
+    Ok(group)
+}
+
+fn read_u32s(value: &WireValue, out: &mut Vec<u32>) -> Result<()> {
+    let mut raw = Vec::new();
+    value.read_varints(&mut raw)?;
+    out.extend(raw.into_iter().map(|v| v as u32));
+    Ok(())
+}
+
+fn read_i32s(value: &WireValue, out: &mut Vec<i32>, zigzag: bool) -> Result<()> {
+    let mut raw = Vec::new();
+    if zigzag {
+        value.read_sint64s(&mut raw)?;
+    } else {
+        value.read_i64s(&mut raw)?;
+    }
+    out.extend(raw.into_iter().map(|v| v as i32));
+    Ok(())
+}
+
+fn decode_info(buf: &[u8]) -> Result<Info> {
+    let mut info = Info::default();
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => info.version = value.as_i64()? as i32,
+            2 => info.timestamp = value.as_i64()?,
+            3 => info.changeset = value.as_i64()?,
+            4 => info.uid = value.as_i64()? as i32,
+            5 => info.user_sid = value.as_u64()? as u32,
+            6 => info.visible = value.as_u64()? != 0,
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+fn decode_node(buf: &[u8], skip_metadata: bool) -> Result<Node> {
+    let mut node = Node::new(0, LatLon::default());
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => node.id = NodeId(value.as_sint64()?),
+            2 => read_u32s(&value, &mut node.keys)?,
+            3 => read_u32s(&value, &mut node.vals)?,
+            4 if !skip_metadata => node.info = Some(decode_info(value.as_bytes()?)?),
+            8 => node.location.lat = NanoDegree(value.as_sint64()?),
+            9 => node.location.lon = NanoDegree(value.as_sint64()?),
+            _ => {}
+        }
+    }
+    Ok(node)
+}
+
+fn decode_dense(buf: &[u8], skip_metadata: bool) -> Result<DenseNodes> {
+    let mut dense = DenseNodes::default();
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => value.read_sint64s(&mut dense.id)?,
+            5 if !skip_metadata => dense.denseinfo = Some(decode_dense_info(value.as_bytes()?)?),
+            8 => value.read_sint64s(&mut dense.lat)?,
+            9 => value.read_sint64s(&mut dense.lon)?,
+            10 => read_i32s(&value, &mut dense.keys_vals, false)?,
+            _ => {}
+        }
+    }
+    Ok(dense)
+}
+
+fn decode_dense_info(buf: &[u8]) -> Result<DenseInfo> {
+    let mut info = DenseInfo::default();
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => read_i32s(&value, &mut info.version, false)?,
+            2 => value.read_sint64s(&mut info.timestamp)?,
+            3 => value.read_sint64s(&mut info.changeset)?,
+            4 => read_i32s(&value, &mut info.uid, true)?,
+            5 => read_i32s(&value, &mut info.user_sid, true)?,
+            6 => {
+                let mut raw = Vec::new();
+                value.read_varints(&mut raw)?;
+                info.visible.extend(raw.into_iter().map(|v| v != 0));
+            }
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+fn decode_way(buf: &[u8], skip_metadata: bool) -> Result<Way> {
+    let mut way = Way { id: WayId(0), keys: Vec::new(), vals: Vec::new(), info: None, refs: Vec::new(), lat: Vec::new(), lon: Vec::new() };
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => way.id = WayId(value.as_i64()?),
+            2 => read_u32s(&value, &mut way.keys)?,
+            3 => read_u32s(&value, &mut way.vals)?,
+            4 if !skip_metadata => way.info = Some(decode_info(value.as_bytes()?)?),
+            8 => value.read_sint64s(&mut way.refs)?,
+            9 => value.read_sint64s(&mut way.lat)?,
+            10 => value.read_sint64s(&mut way.lon)?,
+            _ => {}
+        }
+    }
+    Ok(way)
+}
+
+fn decode_relation(buf: &[u8], skip_metadata: bool) -> Result<Relation> {
+    let mut relation = Relation {
+        id: RelationId(0),
+        keys: Vec::new(),
+        vals: Vec::new(),
+        info: None,
+        roles_sid: Vec::new(),
+        memids: Vec::new(),
+        types: Vec::new(),
+    };
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => relation.id = RelationId(value.as_i64()?),
+            2 => read_u32s(&value, &mut relation.keys)?,
+            3 => read_u32s(&value, &mut relation.vals)?,
+            4 if !skip_metadata => relation.info = Some(decode_info(value.as_bytes()?)?),
+            8 => read_i32s(&value, &mut relation.roles_sid, false)?,
+            9 => value.read_sint64s(&mut relation.memids)?,
+            10 => {
+                let mut raw = Vec::new();
+                value.read_varints(&mut raw)?;
+                for t in raw {
+                    relation.types.push(match t {
+                        0 => MemberType::Node,
+                        1 => MemberType::Way,
+                        2 => MemberType::Relation,
+                        other => return Err(BlobError::InvalidFormat(format!("Unknown member type {other}"))),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(relation)
+}
+
+fn decode_changeset(buf: &[u8]) -> Result<ChangeSet> {
+    let mut changeset = ChangeSet { id: ChangesetId(0), keys: Vec::new(), vals: Vec::new(), info: None };
+    let mut reader = WireReader::new(buf);
+    while let Some((field, value)) = reader.next_field()? {
+        if field == 1 {
+            changeset.id = ChangesetId(value.as_i64()?);
+        }
+    }
+    Ok(changeset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::codec::PbfBlockCodec;
+    use crate::blocks::timestamp::TimestampMillis;
+    use crate::io::writer::{encode_primitive_block, PbfWriter};
+    use pretty_assertions::assert_eq;
+
+    fn sample_block() -> PrimitiveBlock {
+        let mut block = PrimitiveBlock { granularity: 1000, lat_offset: 7, ..Default::default() };
+        block.stringtable.add_string("highway".to_string());
+        block.stringtable.add_string("route".to_string());
+        block.primitivegroup.push(PrimitiveGroup {
+            dense: Some(DenseNodes {
+                id: vec![10, 1, 1],
+                denseinfo: Some(DenseInfo {
+                    version: vec![1, 2, 3],
+                    timestamp: vec![100, -5, 7],
+                    changeset: vec![9, 0, 1],
+                    uid: vec![4, -4, 2],
+                    user_sid: vec![1, 0, 1],
+                    visible: vec![true, false, true],
+                }),
+                lat: vec![500, -1, 2],
+                lon: vec![-600, 3, -4],
+                keys_vals: vec![1, 2, 0, 0, 0],
+            }),
+            ..Default::default()
+        });
+        block.primitivegroup.push(PrimitiveGroup {
+            nodes: vec![Node::new(-3, LatLon::from_raw(12, -34))],
+            ways: vec![Way { id: WayId(5), keys: vec![1], vals: vec![2], info: Some(Info::default()), refs: vec![10, 1, -1], lat: vec![], lon: vec![] }],
+            relations: vec![Relation {
+                id: RelationId(6),
+                keys: vec![2],
+                vals: vec![2],
+                info: None,
+                roles_sid: vec![0, 1],
+                memids: vec![5, 6],
+                types: vec![MemberType::Way, MemberType::Node],
+            }],
+            ..Default::default()
+        });
+        block
+    }
+
+    #[test]
+    fn test_primitive_block_round_trip() {
+        let block = sample_block();
+        let decoded = decode_primitive_block(&encode_primitive_block(&block)).unwrap();
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn test_skip_metadata() {
+        let mut block = sample_block();
+        let decoded = decode_primitive_block_without_metadata(&encode_primitive_block(&block)).unwrap();
+        block.primitivegroup[0].dense.as_mut().unwrap().denseinfo = None;
+        block.primitivegroup[1].ways[0].info = None;
+        assert_eq!(decoded, block);
+
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&crate::blocks::header_block::HeaderBlock::default()).unwrap();
+        writer.write_primitive_block(&sample_block()).unwrap();
+        let data = writer.into_inner();
+        let collect = |skip_metadata: bool| {
+            let options = crate::io::options::ReaderOptions::default().with_skip_metadata(skip_metadata);
+            let mut reader = crate::io::reader::Reader::with_options(std::io::Cursor::new(data.clone()), options).unwrap();
+            reader.collect_filtered(&crate::io::indexed_reader::ElementFilter::all()).unwrap().0
+        };
+        let (full, bare) = (collect(false), collect(true));
+        assert_eq!(bare.len(), full.len());
+        assert!(full.iter().any(|element| matches!(element, OsmElement::Node(node) if node.info.is_some())));
+        for (full, bare) in full.iter().zip(&bare) {
+            match (full, bare) {
+                (OsmElement::Node(full), OsmElement::Node(bare)) => {
+                    assert_eq!((bare.id, &bare.keys, bare.location, &bare.info), (full.id, &full.keys, full.location, &None));
+                }
+                (OsmElement::Way(full), OsmElement::Way(bare)) => assert_eq!((bare.id, &bare.refs, &bare.info), (full.id, &full.refs, &None)),
+                (OsmElement::Relation(full), OsmElement::Relation(bare)) => assert_eq!((bare.id, &bare.memids), (full.id, &full.memids)),
+                other => panic!("mismatched elements {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_frame() {
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_primitive_block(&sample_block()).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut cursor = std::io::Cursor::new(&bytes);
+        let (blob, frame_len) = read_frame(&mut cursor, 0).unwrap().unwrap();
+        assert_eq!(frame_len, bytes.len() as u64);
+        assert_eq!(blob.blob_type(), &BlobType::OSMData);
+
+        let block = decode_primitive_block(&blob_payload(&blob).unwrap()).unwrap();
+        assert_eq!(block, sample_block());
+        assert!(read_frame(&mut cursor, frame_len).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_frame_truncated() {
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_primitive_block(&sample_block()).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut cursor = std::io::Cursor::new(&bytes[..bytes.len() - 1]);
+        assert!(read_frame(&mut cursor, 0).is_err());
+    }
+
+    fn dated_block(date_granularity: i32, raw: i64) -> PrimitiveBlock {
+        let mut block = sample_block();
+        block.date_granularity = date_granularity;
+        block.primitivegroup[1].ways[0].info = Some(Info { timestamp: raw, ..Default::default() });
+        block
+    }
+
+    #[test]
+    fn test_date_granularity_round_trip() {
+        // 2021-01-01T00:00:00.123Z, in each granularity's units
+        let cases = [
+            (1, 1_609_459_200_123, 1_609_459_200_123),
+            (250, 6_437_836_800, 1_609_459_200_000),
+            (60_000, 26_824_320, 1_609_459_200_000),
+        ];
+        for (date_granularity, raw, millis) in cases {
+            let block = dated_block(date_granularity, raw);
+            let decoded = decode_primitive_block(&encode_primitive_block(&block)).unwrap();
+            assert_eq!(decoded, block);
+
+            let info = decoded.primitivegroup[1].ways[0].info.as_ref().unwrap();
+            assert_eq!(decoded.timestamp(info.timestamp), TimestampMillis(millis));
+        }
+    }
+
+    #[test]
+    fn test_writer_requantizes_dates() {
+        let mut writer = PbfWriter::new(Vec::new()).with_date_granularity(1000);
+        writer.write_primitive_block(&dated_block(1, 1_609_459_200_623)).unwrap();
+        let bytes = writer.into_inner();
+
+        let (blob, _) = read_frame(&mut std::io::Cursor::new(&bytes), 0).unwrap().unwrap();
+        let block = decode_primitive_block(&blob_payload(&blob).unwrap()).unwrap();
+        assert_eq!(block.date_granularity, 1000);
+        let info = block.primitivegroup[1].ways[0].info.as_ref().unwrap();
+        assert_eq!(block.timestamp(info.timestamp), TimestampMillis(1_609_459_201_000));
+        // Dense timestamps are re-quantized too: 100, 95, 102 ms round to 0, 0, 0 s
+        let dense = block.primitivegroup[0].dense.as_ref().unwrap().denseinfo.as_ref().unwrap();
+        assert_eq!(block.dense_timestamps(dense), vec![TimestampMillis(0); 3]);
+
+        let mut writer = PbfWriter::new(Vec::new()).with_date_granularity(0);
+        assert!(writer.write_primitive_block(&dated_block(1, 0)).is_err());
+    }
+
+    fn dense_blob() -> Blob {
+        // A mixed group, as some writers produce: nodes 10, 11, 12, 13 at raw (lat, lon) = (0, 0), (100, 100), (200, 200), (300, 300);
+        // 11 and 13 are tagged
+        let mut block = PrimitiveBlock::default();
+        block.stringtable.add_string("amenity".to_string());
+        block.stringtable.add_string("cafe".to_string());
+        block.primitivegroup.push(PrimitiveGroup {
+            dense: Some(DenseNodes {
+                id: vec![10, 1, 1, 1],
+                denseinfo: Some(DenseInfo {
+                    version: vec![1, 2, 3, 4],
+                    timestamp: vec![100, 1, 1, 1],
+                    changeset: vec![5, 0, 0, 1],
+                    uid: vec![7, 0, 0, 0],
+                    user_sid: vec![0, 0, 0, 0],
+                    visible: vec![],
+                }),
+                lat: vec![0, 100, 100, 100],
+                lon: vec![0, 100, 100, 100],
+                keys_vals: vec![0, 1, 2, 0, 0, 1, 2, 2, 1, 0],
+            }),
+            ways: vec![Way { id: WayId(20), keys: vec![], vals: vec![], info: None, refs: vec![10, 1], lat: vec![], lon: vec![] }],
+            ..Default::default()
+        });
+        block.primitivegroup.insert(0, PrimitiveGroup {
+            relations: vec![Relation { id: RelationId(30), keys: vec![], vals: vec![], info: None, roles_sid: vec![], memids: vec![], types: vec![] }],
+            ..Default::default()
+        });
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_primitive_block(&block).unwrap();
+        read_frame(&mut std::io::Cursor::new(writer.into_inner()), 0).unwrap().unwrap().0
+    }
+
+    fn node_ids(elements: &[OsmElement]) -> Vec<i64> {
+        elements.iter().filter_map(|e| match e {
+            OsmElement::Node(node) => Some(node.id.0),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_decode_dense_nodes() {
+        let MatchingElements { strings, elements, ordinals, skipped, mixed_groups, .. } =
+            decode_matching_elements(&dense_blob(), &DecodePredicate::default(), GroupPolicy::Lenient, &PbfBlockCodec).unwrap();
+        assert_eq!((strings.len(), skipped, mixed_groups), (3, 0, 1));
+        assert_eq!(node_ids(&elements), vec![10, 11, 12, 13]);
+        assert_eq!(elements.len(), 6);
+        assert_eq!(ordinals, (0..6).collect::<Vec<_>>());
+
+        let OsmElement::Node(node) = &elements[4] else { panic!("expected a node") };
+        assert_eq!(node.location, LatLon::from_raw(30_000, 30_000));
+        assert_eq!((node.keys.clone(), node.vals.clone()), (vec![1, 2], vec![2, 1]));
+        let info = node.info.as_ref().unwrap();
+        assert_eq!((info.version, info.timestamp, info.changeset, info.uid), (4, 103, 6, 7));
+    }
+
+    #[test]
+    fn test_strict_group_policy_rejects_mixed_groups() {
+        match decode_matching_elements(&dense_blob(), &DecodePredicate::default(), GroupPolicy::Strict, &PbfBlockCodec) {
+            Err(BlobError::MixedGroup { group, kinds }) => {
+                assert_eq!((group, kinds), (1, vec!["dense nodes".to_string(), "ways".to_string()]));
+            }
+            other => panic!("expected MixedGroup, got {other:?}"),
+        }
+        assert!(group_kinds(&PrimitiveGroup::default()).is_empty());
+    }
+
+    #[test]
+    fn test_predicate_pushdown() {
+        let blob = dense_blob();
+        let decode = |predicate: DecodePredicate| decode_matching_elements(&blob, &predicate, GroupPolicy::Lenient, &PbfBlockCodec).unwrap().elements;
+
+        // Skipped nodes still advance the delta accumulators
+        let tagged = decode(DecodePredicate { tagged_only: true, ..Default::default() });
+        assert_eq!(node_ids(&tagged), vec![11, 13]);
+        assert_eq!(tagged.len(), 2);
+        let OsmElement::Node(node) = &tagged[1] else { panic!("expected a node") };
+        assert_eq!(node.info.as_ref().unwrap().timestamp, 103);
+
+        let mut bbox = BoundingBox::from_point(LatLon::from_raw(5_000, 5_000));
+        bbox.extend(LatLon::from_raw(25_000, 25_000));
+        let in_bbox = decode(DecodePredicate::from(&ElementFilter::nodes_only().with_bbox(bbox)));
+        assert_eq!(node_ids(&in_bbox), vec![11, 12]);
+        assert_eq!(in_bbox.len(), 2);
+        let in_range = decode(DecodePredicate::from(&ElementFilter::nodes_only().with_bbox(bbox).with_id_range(NodeId(0), NodeId(11))));
+        assert_eq!(node_ids(&in_range), vec![11]);
+        // Node 11 sits on a corner of the triangle's box, but outside the triangle
+        let triangle = [LatLon::from_raw(10_000, 35_000), LatLon::from_raw(35_000, 35_000), LatLon::from_raw(35_000, 10_000)];
+        let in_polygon = ElementFilter::nodes_only().with_polygon(Polygon::new(&[triangle.to_vec()]).unwrap());
+        assert_eq!(node_ids(&decode(DecodePredicate::from(&in_polygon))), vec![13]);
+
+        // The way and relation carry no metadata, so metadata predicates drop them
+        let versions = ElementFilter::all().with_version_range(2, 3);
+        assert_eq!(node_ids(&decode(DecodePredicate::from(&versions))), vec![11, 12]);
+        assert_eq!(decode(DecodePredicate::from(&versions)).len(), 2);
+        let changeset = ElementFilter::all().with_changeset_range(6, 6);
+        assert_eq!(node_ids(&decode(DecodePredicate::from(&changeset))), vec![13]);
+        let counts = count_matching_elements(&blob, &changeset, GroupPolicy::Lenient, &PbfBlockCodec).unwrap();
+        assert_eq!((counts.nodes, counts.ways, counts.skipped_early), (1, 0, 5));
+        assert_eq!(decode(DecodePredicate::from(&ElementFilter::all().with_visible_only())).len(), 6);
+
+        let ways = decode_matching_elements(&blob, &DecodePredicate::from(&ElementFilter::ways_only(false)), GroupPolicy::Lenient, &PbfBlockCodec).unwrap();
+        assert!(matches!(ways.elements[..], [OsmElement::Way(_)]));
+        assert_eq!(ways.skipped, 5);
+
+        // Ordinals point at the same element whatever was skipped
+        let all = decode_matching_elements(&blob, &DecodePredicate::default(), GroupPolicy::Lenient, &PbfBlockCodec).unwrap().elements;
+        let tagged = decode_matching_elements(&blob, &DecodePredicate { tagged_only: true, ..Default::default() }, GroupPolicy::Lenient, &PbfBlockCodec).unwrap();
+        for (ordinal, element) in tagged.ordinals.iter().chain(&ways.ordinals).zip(tagged.elements.iter().chain(&ways.elements)) {
+            assert_eq!(format!("{:?}", all[*ordinal]), format!("{element:?}"));
+        }
+        assert_eq!(tagged.ordinals.len(), tagged.elements.len());
+    }
+
+    #[test]
+    fn test_tag_filters_resolved_per_block() {
+        let blob = dense_blob();
+        let decode = |filter: ElementFilter| decode_matching_elements(&blob, &DecodePredicate::from(&filter), GroupPolicy::Lenient, &PbfBlockCodec).unwrap();
+
+        let cafes = decode(ElementFilter::all().with_tag("amenity".to_string(), "cafe".to_string()));
+        assert_eq!(node_ids(&cafes.elements), vec![11, 13]);
+        assert_eq!((cafes.skipped, cafes.skipped_by_tags), (4, 0));
+        let reversed = decode(ElementFilter::all().with_tag("cafe".to_string(), "amenity".to_string()));
+        assert_eq!(node_ids(&reversed.elements), vec![13]);
+        assert_eq!((reversed.skipped, reversed.skipped_by_tags), (4, 1));
+
+        // Keys or values missing from the string table skip the whole block
+        for filter in [
+            ElementFilter::all().with_tag_key("shop".to_string()),
+            ElementFilter::all().with_tag("amenity".to_string(), "bar".to_string()),
+        ] {
+            let decoded = decode(filter);
+            assert!(decoded.elements.is_empty() && decoded.ordinals.is_empty());
+            assert_eq!((decoded.skipped, decoded.skipped_by_tags), (6, 0));
+        }
+    }
+}
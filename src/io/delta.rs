@@ -0,0 +1,236 @@
+use crate::blocks::primitives::prelude::*;
+use crate::io::blob::{BlobError, Result};
+
+/// Delta-encode absolute values (ids, coordinates) as the PBF format stores them
+///
+/// Fails with `BlobError::DeltaOverflow` instead of wrapping when the
+/// difference between neighbours doesn't fit in an i64, e.g. `i64::MIN`
+/// followed by `i64::MAX`. A wrapped delta would decode to a different id
+/// and silently corrupt way refs or relation members.
+pub fn delta_encode(values: &[i64]) -> Result<Vec<i64>> {
+    let mut last = 0i64;
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, &value)| {
+            let delta = value.checked_sub(last).ok_or(BlobError::DeltaOverflow { index })?;
+            last = value;
+            Ok(delta)
+        })
+        .collect()
+}
+
+/// Decode delta-encoded values back to absolute ones, failing on overflow
+pub fn delta_decode(deltas: &[i64]) -> Result<Vec<i64>> {
+    let mut last = 0i64;
+    deltas
+        .iter()
+        .enumerate()
+        .map(|(index, &delta)| {
+            last = last.checked_add(delta).ok_or(BlobError::DeltaOverflow { index })?;
+            Ok(last)
+        })
+        .collect()
+}
+
+/// Integer column type stored delta-encoded: ids, coordinates, timestamps,
+/// changesets, uids and user names of dense nodes, way refs, member ids
+pub trait DeltaValue: Copy + Default {
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+}
+
+impl DeltaValue for i32 {
+    fn wrapping_add(self, other: Self) -> Self {
+        i32::wrapping_add(self, other)
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        i32::wrapping_sub(self, other)
+    }
+}
+
+impl DeltaValue for i64 {
+    fn wrapping_add(self, other: Self) -> Self {
+        i64::wrapping_add(self, other)
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        i64::wrapping_sub(self, other)
+    }
+}
+
+/// Absolute values of a delta-encoded column, decoded as they're iterated
+///
+/// Sums wrap on overflow, as everywhere else in decoding; `delta_decode`
+/// rejects overflow instead.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::delta_decoded;
+///
+/// let refs = [100i64, 1, 1, -2];
+/// assert_eq!(delta_decoded(&refs).collect::<Vec<_>>(), [100, 101, 102, 100]);
+/// ```
+pub fn delta_decoded<T: DeltaValue>(deltas: &[T]) -> DeltaDecoded<'_, T> {
+    DeltaDecoded { deltas: deltas.iter(), last: T::default() }
+}
+
+/// Iterator returned by `delta_decoded`
+#[derive(Debug, Clone)]
+pub struct DeltaDecoded<'a, T> {
+    deltas: std::slice::Iter<'a, T>,
+    last: T,
+}
+
+impl<T: DeltaValue> Iterator for DeltaDecoded<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.last = self.last.wrapping_add(*self.deltas.next()?);
+        Some(self.last)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.deltas.size_hint()
+    }
+}
+
+impl<T: DeltaValue> ExactSizeIterator for DeltaDecoded<'_, T> {}
+
+/// Delta-encodes a column one value at a time, e.g. while filling dense nodes
+///
+/// Differences wrap on overflow and decode back exactly with
+/// `delta_decoded`; `delta_encode` rejects overflow instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeltaEncoder<T> {
+    last: T,
+}
+
+impl<T: DeltaValue> DeltaEncoder<T> {
+    pub fn new() -> Self {
+        Self { last: T::default() }
+    }
+
+    /// Delta to store for the next value
+    pub fn encode(&mut self, value: T) -> T {
+        let delta = value.wrapping_sub(self.last);
+        self.last = value;
+        delta
+    }
+}
+
+/// Check that every delta-encoded column of a block decodes without overflow
+///
+/// Covers dense node ids and coordinates, way refs and relation memids.
+pub(crate) fn check_block_deltas(block: &PrimitiveBlock) -> Result<()> {
+    for group in &block.primitivegroup {
+        if let Some(dense) = &group.dense {
+            check_running_sum(&dense.id)?;
+            check_running_sum(&dense.lat)?;
+            check_running_sum(&dense.lon)?;
+        }
+        for way in &group.ways {
+            check_running_sum(&way.refs)?;
+        }
+        for relation in &group.relations {
+            check_running_sum(&relation.memids)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_running_sum(deltas: &[i64]) -> Result<()> {
+    let mut last = 0i64;
+    for (index, delta) in deltas.iter().enumerate() {
+        last = last.checked_add(*delta).ok_or(BlobError::DeltaOverflow { index })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::RelationId;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_delta_encode() {
+        assert_eq!(delta_encode(&[5, 7, 6]).unwrap(), vec![5, 2, -1]);
+        assert!(delta_encode(&[]).unwrap().is_empty());
+        assert_eq!(delta_decode(&[5, 2, -1]).unwrap(), vec![5, 7, 6]);
+    }
+
+    #[test]
+    fn test_extreme_values_round_trip() {
+        for values in [
+            vec![i64::MAX, i64::MAX, 0, i64::MIN + 1],
+            vec![i64::MIN, -1, i64::MAX - 1],
+            vec![-1, i64::MAX - 1, -1],
+            vec![i64::MIN, i64::MIN],
+        ] {
+            let deltas = delta_encode(&values).unwrap();
+            assert_eq!(delta_decode(&deltas).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn test_overflow_is_rejected() {
+        assert!(matches!(delta_encode(&[i64::MIN, i64::MAX]), Err(BlobError::DeltaOverflow { index: 1 })));
+        assert!(matches!(delta_encode(&[i64::MAX, -2]), Err(BlobError::DeltaOverflow { index: 1 })));
+        assert!(matches!(delta_encode(&[0, 1, i64::MIN]), Err(BlobError::DeltaOverflow { index: 2 })));
+        assert!(matches!(delta_decode(&[i64::MAX, 1]), Err(BlobError::DeltaOverflow { index: 1 })));
+        assert!(matches!(delta_decode(&[i64::MIN, -1]), Err(BlobError::DeltaOverflow { index: 1 })));
+    }
+
+    #[test]
+    fn test_streaming_round_trip() {
+        fn round_trip<T: DeltaValue + PartialEq + std::fmt::Debug>(values: &[T]) {
+            let mut encoder = DeltaEncoder::new();
+            let deltas: Vec<T> = values.iter().map(|value| encoder.encode(*value)).collect();
+            assert_eq!(delta_decoded(&deltas).collect::<Vec<_>>(), values);
+            assert_eq!(delta_decoded(&deltas).len(), values.len());
+        }
+        // Ids, coordinates in nanodegrees, timestamps, changesets, uids and string indices
+        round_trip::<i64>(&[]);
+        round_trip::<i64>(&[1, 2, 3, 10_000_000_000, 9, -5]);
+        round_trip::<i64>(&[900_000_000_000, -900_000_000_000, 1_800_000_000_000, -1_800_000_000_000]);
+        round_trip::<i64>(&[1_700_000_000, 1_200_000_000, 1_700_000_001]);
+        round_trip::<i64>(&[i64::MIN, i64::MAX, 0, i64::MIN, -1, i64::MAX]);
+        round_trip::<i32>(&[0, 17, 3, 3, 0]);
+        round_trip::<i32>(&[i32::MIN, i32::MAX, -1, i32::MIN]);
+
+        // Wrapped deltas decode exactly, where the checked functions refuse them
+        let mut encoder = DeltaEncoder::new();
+        let deltas = [encoder.encode(i64::MIN), encoder.encode(i64::MAX)];
+        assert!(delta_decode(&deltas).is_err());
+        assert_eq!(delta_decoded(&deltas).collect::<Vec<_>>(), [i64::MIN, i64::MAX]);
+        for values in [vec![5, 7, 6], vec![-1, i64::MAX - 1, -1]] {
+            assert_eq!(delta_decoded(&delta_encode(&values).unwrap()).collect::<Vec<_>>(), values);
+        }
+    }
+
+    #[test]
+    fn test_block_check() {
+        let relation = |memids: Vec<i64>| Relation {
+            id: RelationId(1),
+            keys: vec![],
+            vals: vec![],
+            info: None,
+            roles_sid: vec![0; memids.len()],
+            types: vec![MemberType::Way; memids.len()],
+            memids,
+        };
+        let block = |group: PrimitiveGroup| PrimitiveBlock { primitivegroup: vec![group], ..Default::default() };
+
+        let ok = PrimitiveGroup { relations: vec![relation(vec![i64::MAX, -1, i64::MIN + 1])], ..Default::default() };
+        assert!(check_block_deltas(&block(ok)).is_ok());
+
+        let bad = PrimitiveGroup { relations: vec![relation(vec![i64::MAX, 1])], ..Default::default() };
+        assert!(check_block_deltas(&block(bad)).is_err());
+
+        let dense = DenseNodes { id: vec![i64::MIN, -1], lat: vec![0, 0], lon: vec![0, 0], ..Default::default() };
+        let bad = PrimitiveGroup { dense: Some(dense), ..Default::default() };
+        assert!(matches!(check_block_deltas(&block(bad)), Err(BlobError::DeltaOverflow { index: 1 })));
+    }
+}
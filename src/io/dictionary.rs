@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use crate::blocks::string_table::StringTable;
+use crate::io::reader::OsmElement;
+
+/// Strings of a whole file, from `Reader::export_string_dictionary`
+///
+/// Holds every distinct string of the blobs' string tables once, ordered by
+/// how often elements use it (tag keys and values, relation roles, user
+/// names), ties broken alphabetically so the order is reproducible. Index 0
+/// is the empty string, as in a block's string table. Each blob's table maps
+/// onto it through a remapping table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StringDictionary {
+    /// Distinct strings, most used first after the empty string
+    pub strings: Vec<String>,
+    /// Uses of each string by elements
+    pub counts: Vec<u64>,
+    /// Per data blob: its blob index and the global index of each entry of its string table
+    pub remaps: Vec<(usize, Vec<u32>)>,
+}
+
+impl StringDictionary {
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.strings.get(index as usize).map(String::as_str)
+    }
+
+    /// Global index of entry `local` of a blob's string table
+    pub fn remap(&self, blob_index: usize, local: u32) -> Option<u32> {
+        let position = self.remaps.binary_search_by_key(&blob_index, |(index, _)| *index).ok()?;
+        self.remaps[position].1.get(local as usize).copied()
+    }
+}
+
+/// Collects string tables blob by blob for a `StringDictionary`
+#[derive(Debug, Default)]
+pub(crate) struct StringDictionaryBuilder {
+    /// Index into `strings` of each distinct string
+    ids: HashMap<String, u32>,
+    strings: Vec<String>,
+    counts: Vec<u64>,
+    /// Per blob, provisional ids in `strings`
+    tables: Vec<(usize, Vec<u32>)>,
+}
+
+impl StringDictionaryBuilder {
+    pub fn new() -> Self {
+        let mut builder = Self::default();
+        builder.intern("");
+        builder
+    }
+
+    /// Add a blob's string table and count the uses by its elements
+    pub fn add_block(&mut self, blob_index: usize, strings: &StringTable, elements: &[OsmElement]) {
+        let table: Vec<u32> = strings.s.iter().map(|s| self.intern(s)).collect();
+        let mut count = |local: u32| {
+            if let Some(&id) = table.get(local as usize) {
+                self.counts[id as usize] += 1;
+            }
+        };
+        for element in elements {
+            let (keys, vals, info) = match element {
+                OsmElement::Node(n) => (&n.keys, &n.vals, &n.info),
+                OsmElement::Way(w) => (&w.keys, &w.vals, &w.info),
+                OsmElement::Relation(r) => (&r.keys, &r.vals, &r.info),
+                OsmElement::ChangeSet(c) => (&c.keys, &c.vals, &c.info),
+            };
+            keys.iter().chain(vals).for_each(|index| count(*index));
+            if let Some(info) = info {
+                count(info.user_sid);
+            }
+            if let OsmElement::Relation(relation) = element {
+                relation.roles_sid.iter().for_each(|role| count(*role as u32));
+            }
+        }
+        self.tables.push((blob_index, table));
+    }
+
+    fn intern(&mut self, string: &str) -> u32 {
+        if let Some(&id) = self.ids.get(string) {
+            return id;
+        }
+        let id = self.strings.len() as u32;
+        self.ids.insert(string.to_string(), id);
+        self.strings.push(string.to_string());
+        self.counts.push(0);
+        id
+    }
+
+    /// Order the strings by use and rewrite the blob tables to the final indices
+    pub fn finish(mut self) -> StringDictionary {
+        let mut order: Vec<u32> = (0..self.strings.len() as u32).collect();
+        // The empty string was interned first and keeps index 0
+        order[1..].sort_by(|&a, &b| {
+            let (a, b) = (a as usize, b as usize);
+            self.counts[b].cmp(&self.counts[a]).then_with(|| self.strings[a].cmp(&self.strings[b]))
+        });
+
+        let mut new_index = vec![0u32; order.len()];
+        for (position, &id) in order.iter().enumerate() {
+            new_index[id as usize] = position as u32;
+        }
+        let mut strings = std::mem::take(&mut self.strings);
+        StringDictionary {
+            counts: order.iter().map(|&id| self.counts[id as usize]).collect(),
+            strings: order.iter().map(|&id| std::mem::take(&mut strings[id as usize])).collect(),
+            remaps: self.tables.into_iter()
+                .map(|(blob_index, table)| (blob_index, table.iter().map(|id| new_index[*id as usize]).collect()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::reader::Reader;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_frequency_order_and_remaps() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(6).grid_size(8).block_size(20).relation_count(3).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(std::io::Cursor::new(data)).unwrap();
+        let dictionary = reader.export_string_dictionary().unwrap();
+
+        assert_eq!(dictionary.get(0), Some(""));
+        assert!(dictionary.counts[1..].windows(2).all(|pair| pair[0] >= pair[1]));
+        let mut distinct = dictionary.strings.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), dictionary.len());
+
+        // Every element's tags read the same through the global dictionary
+        let mut tags = 0;
+        reader.for_each_filtered_with_provenance(&crate::io::indexed_reader::ElementFilter::all(), |element, provenance| {
+            if let OsmElement::Way(way) = element {
+                for key in way.keys {
+                    let global = dictionary.remap(provenance.blob_index, key).unwrap();
+                    assert!(dictionary.counts[global as usize] > 0);
+                    tags += 1;
+                }
+            }
+            Ok(())
+        }).unwrap();
+        assert!(tags > 0);
+        assert_eq!(dictionary.get(dictionary.remap(1, 0).unwrap()), Some(""));
+        assert_eq!(dictionary.remap(0, 0), None);
+    }
+
+    #[test]
+    fn test_builder_merges_tables() {
+        let table = |strings: &[&str]| StringTable { s: strings.iter().map(|s| s.to_string()).collect() };
+        let node = |keys: Vec<u32>, vals: Vec<u32>| {
+            let mut node = crate::blocks::primitives::node::Node::new(1, Default::default());
+            (node.keys, node.vals) = (keys, vals);
+            OsmElement::Node(node)
+        };
+        let mut builder = StringDictionaryBuilder::new();
+        builder.add_block(1, &table(&["", "name", "amenity", "cafe"]), &[node(vec![2], vec![3])]);
+        builder.add_block(4, &table(&["", "amenity", "bar", "unused"]), &[node(vec![1], vec![2]), node(vec![1], vec![2])]);
+        let dictionary = builder.finish();
+
+        assert_eq!(dictionary.strings, ["", "amenity", "bar", "cafe", "name", "unused"]);
+        assert_eq!(dictionary.counts, [0, 3, 2, 1, 0, 0]);
+        assert_eq!(dictionary.remaps, vec![(1, vec![0, 4, 1, 3]), (4, vec![0, 1, 2, 5])]);
+        assert_eq!(dictionary.remap(4, 2), Some(2));
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{Read, Seek};
+use crate::blocks::osm_id::{NodeId, RelationId, WayId};
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::Result;
+use crate::io::decode::{blob_payload, find_element};
+use crate::io::indexed_reader::{BlobIndex, IndexedReader};
+use crate::io::reader::OsmElement;
+
+/// Single-element lookups by type and id, for latency-sensitive services
+///
+/// Blobs are looked up by the id ranges and element counts of the deep index
+/// (built on creation unless a saved index already holds them). A lookup
+/// reads and decompresses a candidate blob and decodes only the requested
+/// element from it instead of the whole block; on files sorted by id the
+/// first candidate is the right one.
+///
+/// Elements come back as from `Reader::for_each`, node locations in
+/// nanodegrees and way refs delta-encoded, together with a string table
+/// holding just the strings the element references. Blocks are read as PBF;
+/// a `BlockDecoder` set on a `Reader` doesn't apply here.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{ElementIndex, NodeId};
+/// use std::fs::File;
+///
+/// let mut index = ElementIndex::new(File::open("map.osm.pbf")?)?;
+/// if let Some((node, strings)) = index.get_node(NodeId(240_109_189))? {
+///     println!("{:?} {:?}", node.location.to_degrees(), node.tags(&strings).get("name"));
+/// }
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub struct ElementIndex<R: Read + Seek> {
+    reader: IndexedReader<R>,
+    /// Blobs holding nodes, ways and relations, as `(min id, max id, blob)` sorted by min id
+    ranges: [Vec<(i64, i64, usize)>; 3],
+}
+
+impl<R: Read + Seek> ElementIndex<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        Self::from_indexed_reader(IndexedReader::new(reader)?)
+    }
+
+    /// Use an opened reader, e.g. one from `IndexedReader::open_with_index`
+    pub fn from_indexed_reader(mut reader: IndexedReader<R>) -> Result<Self> {
+        reader.finish_index()?;
+        let data = |blob: &&BlobIndex| blob.blob_type.holds_elements();
+        if reader.index().iter().filter(data).any(|blob| blob.id_ranges.is_none() || blob.element_counts.is_unknown()) {
+            reader.build_deep_index()?;
+        }
+
+        let mut ranges: [Vec<(i64, i64, usize)>; 3] = Default::default();
+        for (index, blob) in reader.index().iter().enumerate().filter(|(_, blob)| data(blob)) {
+            let Some(blob_ranges) = blob.id_ranges else {
+                continue;
+            };
+            let node = blob_ranges.node.map(|(min, max)| (min.0, max.0));
+            let way = blob_ranges.way.map(|(min, max)| (min.0, max.0));
+            let relation = blob_ranges.relation.map(|(min, max)| (min.0, max.0));
+            for (slot, range) in ranges.iter_mut().zip([node, way, relation]) {
+                if let Some((min, max)) = range {
+                    slot.push((min, max, index));
+                }
+            }
+        }
+        for slot in &mut ranges {
+            slot.sort_unstable();
+        }
+        Ok(Self { reader, ranges })
+    }
+
+    pub fn get_node(&mut self, id: NodeId) -> Result<Option<(Node, StringTable)>> {
+        Ok(self.get(MemberType::Node, id.0)?.and_then(|(element, strings)| match element {
+            OsmElement::Node(node) => Some((node, strings)),
+            _ => None,
+        }))
+    }
+
+    pub fn get_way(&mut self, id: WayId) -> Result<Option<(Way, StringTable)>> {
+        Ok(self.get(MemberType::Way, id.0)?.and_then(|(element, strings)| match element {
+            OsmElement::Way(way) => Some((way, strings)),
+            _ => None,
+        }))
+    }
+
+    pub fn get_relation(&mut self, id: RelationId) -> Result<Option<(Relation, StringTable)>> {
+        Ok(self.get(MemberType::Relation, id.0)?.and_then(|(element, strings)| match element {
+            OsmElement::Relation(relation) => Some((relation, strings)),
+            _ => None,
+        }))
+    }
+
+    /// Look up an element of any of the three types
+    pub fn get(&mut self, kind: MemberType, id: i64) -> Result<Option<(OsmElement, StringTable)>> {
+        let ranges = &self.ranges[kind as usize];
+        // Blobs starting at or below `id`, the latest start first
+        let end = ranges.partition_point(|(min, _, _)| *min <= id);
+        for &(_, max, blob_index) in ranges[..end].iter().rev() {
+            if max < id {
+                continue;
+            }
+            let Some(blob) = self.reader.read_blob_by_index(blob_index)? else {
+                continue;
+            };
+            if let Some(found) = find_element(&blob_payload(&blob)?, kind, id)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The underlying reader, e.g. to save the index built on creation
+    pub fn indexed_reader(&mut self) -> &mut IndexedReader<R> {
+        &mut self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::indexed_reader::ElementFilter;
+    use crate::io::reader::Reader;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    /// An element without its string indices, which differ between tables
+    fn without_strings(element: &OsmElement) -> serde_json::Value {
+        let mut element = element.clone();
+        let (keys, vals, info) = match &mut element {
+            OsmElement::Node(n) => (&mut n.keys, &mut n.vals, &mut n.info),
+            OsmElement::Way(w) => (&mut w.keys, &mut w.vals, &mut w.info),
+            OsmElement::Relation(r) => {
+                r.roles_sid.clear();
+                (&mut r.keys, &mut r.vals, &mut r.info)
+            }
+            OsmElement::ChangeSet(c) => (&mut c.keys, &mut c.vals, &mut c.info),
+        };
+        keys.clear();
+        vals.clear();
+        if let Some(info) = info {
+            info.user_sid = 0;
+        }
+        serde_json::to_value(&element).unwrap()
+    }
+
+    #[test]
+    fn test_lookups_match_full_decode() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(11).grid_size(12).block_size(40).relation_count(3).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data.clone())).unwrap();
+        let mut expected = Vec::new();
+        reader.for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| {
+            expected.push((element.tags(strings).to_map(), element));
+            Ok(())
+        }).unwrap();
+
+        let mut index = ElementIndex::new(Cursor::new(data)).unwrap();
+        for (tags, element) in &expected {
+            let (kind, id) = match element {
+                OsmElement::Node(node) => (MemberType::Node, node.id.0),
+                OsmElement::Way(way) => (MemberType::Way, way.id.0),
+                OsmElement::Relation(relation) => (MemberType::Relation, relation.id.0),
+                OsmElement::ChangeSet(_) => continue,
+            };
+            let (found, strings) = index.get(kind, id).unwrap().unwrap();
+            assert_eq!(found.tags(&strings).to_map(), *tags);
+            assert_eq!(without_strings(&found), without_strings(element));
+        }
+        let relation = expected.iter().find_map(|(_, element)| match element {
+            OsmElement::Relation(relation) => Some(relation),
+            _ => None,
+        }).unwrap();
+        let (found, strings) = index.get_relation(relation.id).unwrap().unwrap();
+        assert_eq!(strings.get_string_or_empty(found.roles_sid[0] as usize), "platform");
+        assert!(index.get_node(NodeId(-5)).unwrap().is_none());
+        assert!(index.get_way(WayId(relation.id.0 + 1_000_000)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::blocks::header_block::{OsmosisReplicationTimestamp, OsmosisSequenceNumber};
+use crate::io::blob::{BlobError, Result};
+use crate::io::logging::log_unsupported_features;
+
+/// Header `required_features` this crate can read
+pub const SUPPORTED_FEATURES: &[&str] = &[
+    "OsmSchema-V0.6",
+    "DenseNodes",
+    HISTORICAL_INFORMATION,
+    LOCATIONS_ON_WAYS,
+    SORT_TYPE_THEN_ID,
+    SORT_GEOGRAPHIC,
+    SORT_TIMESTAMP,
+];
+
+/// The file holds every version of its elements, deleted ones included
+/// (see `HistoryIter`)
+pub const HISTORICAL_INFORMATION: &str = "HistoricalInformation";
+
+/// Ways carry the locations of their nodes (see `Way::locations`)
+pub const LOCATIONS_ON_WAYS: &str = "LocationsOnWays";
+
+/// Nodes, then ways, then relations, each by ascending id
+pub const SORT_TYPE_THEN_ID: &str = "Sort.Type_then_ID";
+
+/// Blobs hold spatially clustered elements, e.g. one tile each
+pub const SORT_GEOGRAPHIC: &str = "Sort.Geographic";
+
+/// Versions of an element in history files follow their timestamps
+pub const SORT_TIMESTAMP: &str = "Sort.Timestamp";
+
+/// Replication state recorded in a file header by the tool that wrote it
+///
+/// All fields are `None` for files that weren't cut from a replicated
+/// dataset; see `replication::Client` for catching up from them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplicationInfo {
+    /// Time of the last diff applied to the file
+    pub timestamp: Option<OsmosisReplicationTimestamp>,
+    /// Sequence number of the last diff applied to the file
+    pub sequence_number: Option<OsmosisSequenceNumber>,
+    /// Base URL of the replication server the diffs come from
+    pub base_url: Option<String>,
+}
+
+/// Sort order a file declares in its header features
+///
+/// Only the declaration is recorded; the reader doesn't check that the file
+/// keeps it (see `Reader::ordering`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileOrdering {
+    /// `Sort.Type_then_ID`
+    pub type_then_id: bool,
+    /// `Sort.Geographic`
+    pub geographic: bool,
+    /// `Sort.Timestamp`
+    pub timestamp: bool,
+    /// Other `Sort.` features, which this crate doesn't interpret
+    pub unrecognized: Vec<String>,
+}
+
+impl FileOrdering {
+    /// Collect the sort features among a header's required and optional features
+    pub fn from_features<'a>(features: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut ordering = Self::default();
+        for feature in features {
+            match feature {
+                SORT_TYPE_THEN_ID => ordering.type_then_id = true,
+                SORT_GEOGRAPHIC => ordering.geographic = true,
+                SORT_TIMESTAMP => ordering.timestamp = true,
+                other if other.starts_with("Sort.") => ordering.unrecognized.push(other.to_string()),
+                _ => {}
+            }
+        }
+        ordering
+    }
+
+    /// Whether nodes may follow the ways and relations referencing them
+    ///
+    /// True for geographic files, where each blob mixes the element types of
+    /// its area; files without a sort feature are assumed to be in the usual
+    /// type-then-id order.
+    pub fn interleaves_types(&self) -> bool {
+        self.geographic && !self.type_then_id
+    }
+}
+
+/// What to do when a file requires features this crate doesn't support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FeaturePolicy {
+    /// Fail to open with `BlobError::UnsupportedFeature`
+    #[default]
+    Reject,
+    /// Open anyway and log the unsupported features
+    Warn,
+    /// Open anyway without checking
+    Ignore,
+}
+
+impl FeaturePolicy {
+    /// Apply the policy to a file's required features
+    pub fn check<'a>(self, required: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        if self == FeaturePolicy::Ignore {
+            return Ok(());
+        }
+
+        let features = unsupported_features(required);
+        if features.is_empty() {
+            return Ok(());
+        }
+
+        match self {
+            FeaturePolicy::Reject => Err(BlobError::UnsupportedFeature { features }),
+            _ => {
+                log_unsupported_features(&features);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Required features that aren't in `SUPPORTED_FEATURES`
+pub fn unsupported_features<'a>(required: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    required
+        .into_iter()
+        .filter(|feature| !SUPPORTED_FEATURES.contains(feature))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_unsupported_features() {
+        assert!(unsupported_features(["OsmSchema-V0.6", "DenseNodes"]).is_empty());
+        assert_eq!(unsupported_features(["DenseNodes", "OsmSchema-V0.7"]), vec!["OsmSchema-V0.7".to_string()]);
+    }
+
+    #[test]
+    fn test_policies() {
+        let required = ["OsmSchema-V0.6", "OsmSchema-V0.7"];
+
+        match FeaturePolicy::Reject.check(required) {
+            Err(BlobError::UnsupportedFeature { features }) => assert_eq!(features, vec!["OsmSchema-V0.7".to_string()]),
+            other => panic!("expected UnsupportedFeature, got {other:?}"),
+        }
+        assert!(FeaturePolicy::Warn.check(required).is_ok());
+        assert!(FeaturePolicy::Ignore.check(required).is_ok());
+        assert!(FeaturePolicy::Reject.check(["DenseNodes"]).is_ok());
+        assert!(FeaturePolicy::Reject.check(["Sort.Geographic"]).is_ok());
+    }
+
+    #[test]
+    fn test_file_ordering() {
+        let ordering = FileOrdering::from_features(["DenseNodes", "Sort.Geographic", "Sort.Made-Up"]);
+        assert_eq!(ordering, FileOrdering {
+            geographic: true,
+            unrecognized: vec!["Sort.Made-Up".to_string()],
+            ..Default::default()
+        });
+        assert!(ordering.interleaves_types());
+        assert!(!FileOrdering::from_features(["Sort.Type_then_ID", "Sort.Timestamp"]).interleaves_types());
+        assert!(!FileOrdering::default().interleaves_types());
+    }
+}
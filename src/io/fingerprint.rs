@@ -0,0 +1,231 @@
+use std::fmt;
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::reader::OsmElement;
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Stable content hash of a dataset, independent of compression and blob packing
+///
+/// Each element is hashed on its own (type, id, version, tags resolved to strings,
+/// coordinates, delta-decoded refs/members) and the per-element hashes are combined
+/// with wrapping addition, so the result does not depend on the order in which
+/// elements were encountered. Two files with the same logical content produce the
+/// same fingerprint. The hash is not cryptographic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Fingerprint {
+    /// Number of elements that contributed to the fingerprint
+    pub elements: u64,
+    /// Order-independent combination of the per-element hashes
+    pub digest: u128,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}-{}", self.digest, self.elements)
+    }
+}
+
+/// Incremental builder for a [`Fingerprint`]
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintBuilder {
+    fingerprint: Fingerprint,
+}
+
+impl FingerprintBuilder {
+    /// Create an empty builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an element whose tag and role indices refer to `strings`
+    pub fn add(&mut self, element: &OsmElement, strings: &StringTable) {
+        let mut hasher = Fnv128::new();
+        match element {
+            OsmElement::Node(node) => {
+                hasher.write_u8(0);
+                hasher.write_i64(node.id.0);
+                hash_info(&mut hasher, node.info.as_ref());
+                hash_tags(&mut hasher, &node.keys, &node.vals, strings);
+                hasher.write_i64(node.location.lat.0);
+                hasher.write_i64(node.location.lon.0);
+            }
+            OsmElement::Way(way) => {
+                hasher.write_u8(1);
+                hasher.write_i64(way.id.0);
+                hash_info(&mut hasher, way.info.as_ref());
+                hash_tags(&mut hasher, &way.keys, &way.vals, strings);
+                hasher.write_u64(way.refs.len() as u64);
+                for node_ref in way.node_ids() {
+                    hasher.write_i64(node_ref);
+                }
+            }
+            OsmElement::Relation(relation) => {
+                hasher.write_u8(2);
+                hasher.write_i64(relation.id.0);
+                hash_info(&mut hasher, relation.info.as_ref());
+                hash_tags(&mut hasher, &relation.keys, &relation.vals, strings);
+                hasher.write_u64(relation.memids.len() as u64);
+                for (i, member_id) in relation.member_ids().enumerate() {
+                    hasher.write_u8(relation.types.get(i).map_or(u8::MAX, |t| *t as u8));
+                    hasher.write_i64(member_id);
+                    let role = relation.roles_sid.get(i)
+                        .and_then(|sid| usize::try_from(*sid).ok())
+                        .map_or("", |sid| strings.get_string_or_empty(sid));
+                    hasher.write_str(role);
+                }
+            }
+            OsmElement::ChangeSet(changeset) => {
+                hasher.write_u8(3);
+                hasher.write_i64(changeset.id.0);
+                hash_info(&mut hasher, changeset.info.as_ref());
+                hash_tags(&mut hasher, &changeset.keys, &changeset.vals, strings);
+            }
+        }
+
+        self.fingerprint.elements += 1;
+        self.fingerprint.digest = self.fingerprint.digest.wrapping_add(hasher.finish());
+    }
+
+    /// Finish and return the fingerprint
+    pub fn finish(&self) -> Fingerprint {
+        self.fingerprint
+    }
+}
+
+fn hash_info(hasher: &mut Fnv128, info: Option<&Info>) {
+    hasher.write_i64(info.map_or(0, |info| info.version as i64));
+}
+
+/// Hash tags as resolved strings sorted by key, so string table layout doesn't matter
+fn hash_tags(hasher: &mut Fnv128, keys: &[u32], vals: &[u32], strings: &StringTable) {
+    let mut tags: Vec<(&str, &str)> = keys
+        .iter()
+        .zip(vals)
+        .map(|(k, v)| (strings.get_string_or_empty(*k as usize), strings.get_string_or_empty(*v as usize)))
+        .collect();
+    tags.sort_unstable();
+
+    hasher.write_u64(tags.len() as u64);
+    for (key, value) in tags {
+        hasher.write_str(key);
+        hasher.write_str(value);
+    }
+}
+
+/// FNV-1a, 128-bit variant: stable across platforms and compiler versions
+pub(crate) struct Fnv128(u128);
+
+impl Fnv128 {
+    pub(crate) fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u128;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_i64(&mut self, value: i64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_str(&mut self, value: &str) {
+        // Length prefix keeps ("ab", "c") distinct from ("a", "bc")
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u128 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::WayId;
+
+    fn tagged_node(strings: &mut StringTable, id: i64, key: &str, value: &str) -> OsmElement {
+        let mut node = Node::new(id, LatLon::from_raw(100, 200));
+        let k = strings.add_string(key.to_string()) as u32;
+        let v = strings.add_string(value.to_string()) as u32;
+        node.add_tag(k, v);
+        OsmElement::Node(node)
+    }
+
+    #[test]
+    fn test_order_independent() {
+        let mut strings = StringTable::new();
+        let a = tagged_node(&mut strings, 1, "amenity", "cafe");
+        let b = tagged_node(&mut strings, 2, "shop", "bakery");
+
+        let mut forward = FingerprintBuilder::new();
+        forward.add(&a, &strings);
+        forward.add(&b, &strings);
+
+        let mut backward = FingerprintBuilder::new();
+        backward.add(&b, &strings);
+        backward.add(&a, &strings);
+
+        assert_eq!(forward.finish(), backward.finish());
+        assert_eq!(forward.finish().elements, 2);
+    }
+
+    #[test]
+    fn test_independent_of_string_table_layout() {
+        let mut first = StringTable::new();
+        let a = tagged_node(&mut first, 1, "amenity", "cafe");
+
+        let mut second = StringTable::new();
+        second.add_string("padding".to_string());
+        let b = tagged_node(&mut second, 1, "amenity", "cafe");
+
+        let mut fa = FingerprintBuilder::new();
+        fa.add(&a, &first);
+        let mut fb = FingerprintBuilder::new();
+        fb.add(&b, &second);
+
+        assert_eq!(fa.finish(), fb.finish());
+    }
+
+    #[test]
+    fn test_content_changes_fingerprint() {
+        let mut strings = StringTable::new();
+        let a = tagged_node(&mut strings, 1, "amenity", "cafe");
+        let b = tagged_node(&mut strings, 1, "amenity", "pub");
+
+        let mut fa = FingerprintBuilder::new();
+        fa.add(&a, &strings);
+        let mut fb = FingerprintBuilder::new();
+        fb.add(&b, &strings);
+
+        assert_ne!(fa.finish(), fb.finish());
+    }
+
+    #[test]
+    fn test_way_refs_are_delta_decoded() {
+        let strings = StringTable::new();
+        let way = |refs: Vec<i64>| OsmElement::Way(Way { id: WayId(7), keys: vec![], vals: vec![], info: None, refs, lat: vec![], lon: vec![] });
+
+        let mut fa = FingerprintBuilder::new();
+        fa.add(&way(vec![10, 1, 1]), &strings);
+        let mut fb = FingerprintBuilder::new();
+        fb.add(&way(vec![10, 2]), &strings);
+
+        assert_ne!(fa.finish(), fb.finish());
+        assert_eq!(FingerprintBuilder::new().finish(), Fingerprint::default());
+    }
+}
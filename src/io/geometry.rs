@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::primitives::way::Way;
+use crate::io::blob::Result;
+#[cfg(feature = "mmap")]
+use crate::io::blob::BlobError;
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, Reader};
+
+#[cfg(feature = "mmap")]
+use crate::io::locations::SparseLocationStore;
+
+/// Node locations by id, filled from a pass over the nodes and read while
+/// resolving way geometries
+///
+/// Pick the implementation by id space rather than node count:
+/// `DenseLocations` for files whose ids fit in memory at 8 bytes each,
+/// `SparseLocations` for small extracts with scattered ids, and
+/// `MappedLocations` (feature `mmap`) for the planet, whose id space takes
+/// tens of GB.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{NodeLocationStore, Reader, SparseLocations};
+/// use std::fs::File;
+///
+/// let mut reader = Reader::new(File::open("city.osm.pbf")?)?;
+/// let mut locations = SparseLocations::new();
+/// locations.load(&mut reader)?;
+/// let stats = reader.ways_with_geometry_using(&locations, |way, coords| Ok(()))?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub trait NodeLocationStore {
+    /// Store the location of a node, replacing an earlier one
+    fn set(&mut self, id: i64, location: LatLon) -> Result<()>;
+
+    /// Location of a node, `None` if it was never stored
+    fn get(&self, id: i64) -> Option<LatLon>;
+
+    /// Make the stored locations readable; called once after the last `set`
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Store every node of a file, then `finish`
+    fn load<R: Read + Seek>(&mut self, reader: &mut Reader<R>) -> Result<()>
+    where
+        Self: Sized,
+    {
+        reader.for_each_filtered(&ElementFilter::nodes_only(), |element| match element {
+            OsmElement::Node(node) => self.set(node.id.0, node.location),
+            _ => Ok(()),
+        })?;
+        self.finish()
+    }
+
+    /// Locations of a decoded way's nodes, `None` if any of them is missing
+    fn way_locations(&self, way: &Way) -> Option<Vec<LatLon>> {
+        way.node_ids().map(|id| self.get(id)).collect()
+    }
+}
+
+/// Slot value of a node without a location
+const EMPTY_SLOT: (i32, i32) = (i32::MIN, i32::MIN);
+
+/// Node locations in memory, one slot per node id
+///
+/// Slots take 8 bytes each and run up to the highest id seen, so memory
+/// follows the id space rather than the node count: fine for extracts,
+/// several tens of GB for the planet (use `NodeLocationCache::Mapped` there).
+/// Locations are kept to 1e-7 degrees, the precision of the default
+/// granularity. Negative ids, as in unsaved editor data, are kept aside.
+#[derive(Debug, Clone, Default)]
+pub struct DenseLocations {
+    slots: Vec<(i32, i32)>,
+    negative: HashMap<i64, LatLon>,
+    len: u64,
+}
+
+impl DenseLocations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Preallocate slots for ids up to `max_id`
+    pub fn with_max_id(max_id: i64) -> Self {
+        let slots = usize::try_from(max_id.saturating_add(1)).unwrap_or(0);
+        Self { slots: vec![EMPTY_SLOT; slots], ..Self::default() }
+    }
+
+    /// Store the location of a node, replacing an earlier one
+    pub fn set(&mut self, id: i64, location: LatLon) {
+        let Ok(index) = usize::try_from(id) else {
+            if self.negative.insert(id, location).is_none() {
+                self.len += 1;
+            }
+            return;
+        };
+        if index >= self.slots.len() {
+            self.slots.resize(index + 1, EMPTY_SLOT);
+        }
+        let scale = |nd: i64| i32::try_from((nd + 50).div_euclid(100)).unwrap_or(i32::MIN + 1);
+        let slot = (scale(location.lat.raw()), scale(location.lon.raw()));
+        if self.slots[index] == EMPTY_SLOT {
+            self.len += 1;
+        }
+        self.slots[index] = slot;
+    }
+
+    pub fn get(&self, id: i64) -> Option<LatLon> {
+        let Ok(index) = usize::try_from(id) else {
+            return self.negative.get(&id).copied();
+        };
+        let &(lat, lon) = self.slots.get(index).filter(|slot| **slot != EMPTY_SLOT)?;
+        Some(LatLon::from_raw(lat as i64 * 100, lon as i64 * 100))
+    }
+
+    /// Number of nodes stored
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl NodeLocationStore for DenseLocations {
+    fn set(&mut self, id: i64, location: LatLon) -> Result<()> {
+        DenseLocations::set(self, id, location);
+        Ok(())
+    }
+
+    fn get(&self, id: i64) -> Option<LatLon> {
+        DenseLocations::get(self, id)
+    }
+}
+
+/// Node locations in a hash map, exact and of any id
+///
+/// Takes a few dozen bytes per node whatever the ids, so it suits extracts
+/// of a city or region whose ids are spread over the whole planet's range.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SparseLocations {
+    locations: HashMap<i64, LatLon>,
+}
+
+impl SparseLocations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of nodes stored
+    pub fn len(&self) -> u64 {
+        self.locations.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+impl NodeLocationStore for SparseLocations {
+    fn set(&mut self, id: i64, location: LatLon) -> Result<()> {
+        self.locations.insert(id, location);
+        Ok(())
+    }
+
+    fn get(&self, id: i64) -> Option<LatLon> {
+        self.locations.get(&id).copied()
+    }
+}
+
+/// Node locations by id for resolving way geometries, from a first pass
+/// over a file's nodes
+pub enum NodeLocationCache {
+    /// In memory, see `DenseLocations`
+    Dense(DenseLocations),
+    /// Memory-mapped sparse file, for files whose id space doesn't fit in memory
+    #[cfg(feature = "mmap")]
+    Mapped(SparseLocationStore),
+}
+
+impl NodeLocationCache {
+    /// Read every node of the file into memory
+    pub fn build_dense<R: Read + Seek>(reader: &mut Reader<R>) -> Result<Self> {
+        let mut locations = reader.max_ids()?.node.map_or_else(DenseLocations::new, DenseLocations::with_max_id);
+        locations.load(reader)?;
+        Ok(Self::Dense(locations))
+    }
+
+    /// Write every node of the file to a sparse location file at `path` and map it
+    #[cfg(feature = "mmap")]
+    pub fn build_mapped<R: Read + Seek>(path: impl AsRef<std::path::Path>, reader: &mut Reader<R>) -> Result<Self> {
+        Ok(Self::Mapped(SparseLocationStore::build(path, reader)?))
+    }
+
+}
+
+/// Filled by the `build_*` constructors; `set` only reaches the dense cache
+impl NodeLocationStore for NodeLocationCache {
+    fn set(&mut self, id: i64, location: LatLon) -> Result<()> {
+        match self {
+            Self::Dense(locations) => NodeLocationStore::set(locations, id, location),
+            #[cfg(feature = "mmap")]
+            Self::Mapped(_) => Err(BlobError::InvalidFormat("Mapped location cache is read-only".to_string())),
+        }
+    }
+
+    fn get(&self, id: i64) -> Option<LatLon> {
+        match self {
+            Self::Dense(locations) => locations.get(id),
+            #[cfg(feature = "mmap")]
+            Self::Mapped(store) => store.get(id),
+        }
+    }
+}
+
+impl std::fmt::Debug for NodeLocationCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dense(locations) => f.debug_tuple("Dense").field(&locations.len()).finish(),
+            #[cfg(feature = "mmap")]
+            Self::Mapped(store) => f.debug_tuple("Mapped").field(store).finish(),
+        }
+    }
+}
+
+/// Counters reported by `Reader::ways_with_geometry`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GeometryStats {
+    /// Ways handed to the callback with their geometry
+    pub ways_resolved: u64,
+    /// Of those, ways whose locations were stored on the way (`LocationsOnWays`)
+    pub from_locations_on_ways: u64,
+    /// Ways skipped because a node location was missing, e.g. at the edge of an extract
+    pub ways_incomplete: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_dense_locations() {
+        let mut locations = DenseLocations::with_max_id(4);
+        let point = LatLon::from_raw(523_456_789_00, -12_345_678_00);
+        locations.set(3, point);
+        locations.set(10, LatLon::from_raw(100, 200));
+        locations.set(-7, LatLon::from_raw(1, 2));
+        locations.set(3, point);
+
+        assert_eq!(locations.get(3), Some(point));
+        assert_eq!(locations.get(10), Some(LatLon::from_raw(100, 200)));
+        assert_eq!(locations.get(-7), Some(LatLon::from_raw(1, 2)));
+        assert_eq!((locations.get(2), locations.get(11), locations.get(-1)), (None, None, None));
+        assert_eq!(locations.len(), 3);
+    }
+
+    #[test]
+    fn test_location_stores_resolve_ways() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(6).grid_size(12).block_size(50).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(std::io::Cursor::new(data)).unwrap();
+        let expected = reader.ways_with_geometry(|_, _| Ok(())).unwrap();
+        assert!(expected.ways_resolved > 0);
+        assert_eq!(expected.ways_incomplete, 0);
+
+        let mut sparse = SparseLocations::new();
+        sparse.load(&mut reader).unwrap();
+        assert_eq!(reader.ways_with_geometry_using(&sparse, |_, _| Ok(())).unwrap(), expected);
+
+        let mut partial = SparseLocations::new();
+        partial.set(1, LatLon::from_raw(1, 2)).unwrap();
+        let incomplete = reader.ways_with_geometry_using(&partial, |_, _| Ok(())).unwrap();
+        assert_eq!((incomplete.ways_resolved, incomplete.ways_incomplete), (0, expected.ways_resolved));
+
+        #[cfg(feature = "mmap")]
+        {
+            use crate::io::locations::MappedLocations;
+            let dir = tempfile::tempdir().unwrap();
+            let mut mapped = MappedLocations::create(dir.path().join("nodes.bin"), None).unwrap();
+            mapped.set(3, LatLon::from_raw(100, 200)).unwrap();
+            assert_eq!(mapped.get(3), None);
+            mapped.finish().unwrap();
+            assert_eq!(mapped.get(3), Some(LatLon::from_raw(100, 200)));
+            assert!(mapped.set(4, LatLon::from_raw(100, 200)).is_err());
+
+            let mut mapped = MappedLocations::create(dir.path().join("planet.bin"), None).unwrap();
+            mapped.load(&mut reader).unwrap();
+            assert_eq!(reader.ways_with_geometry_using(&mapped, |_, _| Ok(())).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::iter::Peekable;
+use std::sync::Arc;
+use crate::blocks::primitives::info::Info;
+use crate::blocks::string_table::StringTable;
+use crate::io::reader::OsmElement;
+
+/// Items `HistoryIter` groups: elements, alone or with what they came with
+pub trait HistoryItem {
+    fn element(&self) -> &OsmElement;
+}
+
+impl HistoryItem for OsmElement {
+    fn element(&self) -> &OsmElement {
+        self
+    }
+}
+
+impl HistoryItem for (OsmElement, Arc<StringTable>) {
+    fn element(&self) -> &OsmElement {
+        &self.0
+    }
+}
+
+/// Kind and id of an element, the identity its versions share
+pub(crate) fn identity(element: &OsmElement) -> (u8, i64) {
+    match element {
+        OsmElement::Node(node) => (0, node.id.0),
+        OsmElement::Way(way) => (1, way.id.0),
+        OsmElement::Relation(relation) => (2, relation.id.0),
+        OsmElement::ChangeSet(changeset) => (3, changeset.id.0),
+    }
+}
+
+fn info(element: &OsmElement) -> Option<&Info> {
+    match element {
+        OsmElement::Node(node) => node.info.as_ref(),
+        OsmElement::Way(way) => way.info.as_ref(),
+        OsmElement::Relation(relation) => relation.info.as_ref(),
+        OsmElement::ChangeSet(changeset) => changeset.info.as_ref(),
+    }
+}
+
+/// All versions of one element of a full-history file, in file order
+///
+/// History files store the versions of an element oldest first, deleted
+/// versions included with `visible == false`. Never empty.
+#[derive(Debug, Clone)]
+pub struct ElementHistory<T = OsmElement> {
+    pub versions: Vec<T>,
+}
+
+impl<T: HistoryItem> ElementHistory<T> {
+    /// Id of the element
+    pub fn id(&self) -> i64 {
+        identity(self.latest().element()).1
+    }
+
+    /// Last version in the file, the current one
+    pub fn latest(&self) -> &T {
+        self.versions.last().expect("histories have a version")
+    }
+
+    /// Whether the latest version deletes the element
+    pub fn is_deleted(&self) -> bool {
+        info(self.latest().element()).is_some_and(|info| !info.visible)
+    }
+
+    /// The version with number `version`, if the file has it
+    pub fn version(&self, version: i32) -> Option<&T> {
+        self.versions.iter().find(|item| info(item.element()).is_some_and(|info| info.version == version))
+    }
+}
+
+/// Groups consecutive versions of the same element into `ElementHistory`s
+///
+/// Relies on the versions of an element being adjacent, as in history files
+/// sorted `Sort.Type_then_ID`; versions split by other elements come out as
+/// separate histories.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{ElementFilter, HistoryIter, Reader};
+/// use std::fs::File;
+///
+/// let mut reader = Reader::new(File::open("history.osh.pbf")?)?;
+/// let (elements, _) = reader.collect_filtered(&ElementFilter::nodes_only())?;
+/// for history in HistoryIter::new(elements) {
+///     println!("node {}: {} versions", history.id(), history.versions.len());
+/// }
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub struct HistoryIter<I: Iterator> {
+    items: Peekable<I>,
+}
+
+impl<I: Iterator> HistoryIter<I> {
+    pub fn new(items: impl IntoIterator<IntoIter = I>) -> Self {
+        Self { items: items.into_iter().peekable() }
+    }
+}
+
+impl<I> Iterator for HistoryIter<I>
+where
+    I: Iterator,
+    I::Item: HistoryItem,
+{
+    type Item = ElementHistory<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.items.next()?;
+        let identity_of_first = identity(first.element());
+        let mut versions = vec![first];
+        while let Some(item) = self.items.next_if(|item| identity(item.element()) == identity_of_first) {
+            versions.push(item);
+        }
+        Some(ElementHistory { versions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::header_block::HeaderBlock;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::NodeId;
+    use crate::blocks::primitives::prelude::*;
+    use crate::io::features::HISTORICAL_INFORMATION;
+    use crate::io::indexed_reader::ElementFilter;
+    use crate::io::reader::Reader;
+    use crate::io::writer::{BlockBuffer, PbfWriter};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn node(id: i64, version: i32, visible: bool) -> OsmElement {
+        let info = Info { version, timestamp: version as i64, changeset: 7, uid: 1, user_sid: 0, visible };
+        OsmElement::Node(Node { id: NodeId(id), keys: vec![], vals: vec![], info: Some(info), location: LatLon::from_raw(0, 0) })
+    }
+
+    fn versions(history: &ElementHistory<(OsmElement, Arc<StringTable>)>) -> Vec<i32> {
+        history.versions.iter().map(|(element, _)| info(element).unwrap().version).collect()
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock {
+            required_features: vec!["OsmSchema-V0.6".into(), "DenseNodes".into(), HISTORICAL_INFORMATION.into()],
+            ..Default::default()
+        }).unwrap();
+        // Node 2's versions straddle a block boundary
+        let blocks = [
+            vec![node(1, 1, true), node(1, 2, true), node(2, 1, true)],
+            vec![node(2, 2, true), node(2, 3, false), node(3, 1, true)],
+        ];
+        for elements in blocks {
+            let mut block = BlockBuffer::default();
+            for element in &elements {
+                block.push(element, &StringTable::default());
+            }
+            writer.write_primitive_block(&block.finish()).unwrap();
+        }
+
+        let mut reader = Reader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert!(reader.is_history());
+        let mut histories = Vec::new();
+        reader.for_each_history(&ElementFilter::all(), |history| {
+            histories.push(history);
+            Ok(())
+        }).unwrap();
+        let summary: Vec<_> = histories.iter().map(|history| (history.id(), versions(history), history.is_deleted())).collect();
+        assert_eq!(summary, vec![(1, vec![1, 2], false), (2, vec![1, 2, 3], true), (3, vec![1], false)]);
+        assert!(histories[1].version(2).is_some_and(|(element, _)| info(element).unwrap().visible));
+        assert!(histories[1].version(4).is_none());
+
+        let plain: Vec<_> = HistoryIter::new([node(5, 1, true), node(5, 2, true), node(6, 1, true)]).map(|history| history.id()).collect();
+        assert_eq!(plain, vec![5, 6]);
+    }
+}
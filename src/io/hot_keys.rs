@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobError, Result};
+use crate::io::reader::OsmElement;
+
+/// Tag keys whose presence `IndexedReader::build_deep_index` and
+/// `IndexedReader::build_bbox_index` record per blob
+///
+/// Registered with `IndexedReader::set_hot_keys`. Filters on a hot key then
+/// skip blobs where no element carries it, without decoding them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HotKeys {
+    keys: Arc<[String]>,
+}
+
+/// Which hot keys occur in one blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPresence {
+    keys: Arc<[String]>,
+    bits: u64,
+}
+
+impl HotKeys {
+    /// Maximum number of hot keys, one bit each
+    pub const MAX_KEYS: usize = 64;
+
+    /// Register the given keys, failing if there are more than `MAX_KEYS`
+    pub fn new<S: AsRef<str>>(keys: &[S]) -> Result<Self> {
+        if keys.len() > Self::MAX_KEYS {
+            return Err(BlobError::InvalidFormat(format!(
+                "{} hot keys registered (max: {})", keys.len(), Self::MAX_KEYS
+            )));
+        }
+        Ok(Self { keys: keys.iter().map(|k| k.as_ref().to_string()).collect() })
+    }
+
+    /// The registered keys, in bit order
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Record which hot keys the elements of a blob carry
+    pub fn presence(&self, strings: &StringTable, elements: &[OsmElement]) -> KeyPresence {
+        let keys = elements.iter().flat_map(|element| match element {
+            OsmElement::Node(node) => &node.keys[..],
+            OsmElement::Way(way) => &way.keys[..],
+            OsmElement::Relation(relation) => &relation.keys[..],
+            OsmElement::ChangeSet(_) => &[],
+        });
+        self.presence_of_keys(strings, keys.copied())
+    }
+
+    /// Record which hot keys occur among the key indices of a block
+    pub(crate) fn presence_of_keys(&self, strings: &StringTable, keys: impl IntoIterator<Item = u32>) -> KeyPresence {
+        // Map string table indices of hot keys to their bits once per blob
+        let key_bits: Vec<(u32, u64)> = strings.s.iter().enumerate().skip(1)
+            .filter_map(|(index, s)| {
+                let bit = self.keys.iter().position(|k| k == s)?;
+                Some((index as u32, 1u64 << bit))
+            })
+            .collect();
+
+        let mut bits = 0u64;
+        if !key_bits.is_empty() {
+            for key in keys {
+                if let Some((_, bit)) = key_bits.iter().find(|(index, _)| *index == key) {
+                    bits |= bit;
+                }
+            }
+        }
+        KeyPresence { keys: self.keys.clone(), bits }
+    }
+
+    /// Presence recorded earlier as `KeyPresence::bits_for` these keys
+    pub(crate) fn presence_from_bits(&self, bits: u64) -> KeyPresence {
+        KeyPresence { keys: self.keys.clone(), bits }
+    }
+}
+
+impl KeyPresence {
+    /// Whether some element of the blob carries `key`; `None` if it isn't a hot key
+    pub fn contains(&self, key: &str) -> Option<bool> {
+        let bit = self.keys.iter().position(|k| k == key)?;
+        Some(self.bits & (1 << bit) != 0)
+    }
+
+    /// The presence bits, if recorded for exactly the keys of `hot_keys`
+    pub(crate) fn bits_for(&self, hot_keys: &HotKeys) -> Option<u64> {
+        (self.keys == hot_keys.keys).then_some(self.bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::WayId;
+    use crate::blocks::primitives::prelude::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_presence() {
+        let hot_keys = HotKeys::new(&["highway", "building", "natural"]).unwrap();
+        let mut strings = StringTable::new();
+        let highway = strings.add_string("highway".to_string()) as u32;
+        let name = strings.add_string("name".to_string()) as u32;
+
+        let way = Way { id: WayId(1), keys: vec![name, highway], vals: vec![name, name], info: None, refs: vec![], lat: vec![], lon: vec![] };
+        let presence = hot_keys.presence(&strings, &[OsmElement::Way(way)]);
+
+        assert_eq!(presence.contains("highway"), Some(true));
+        assert_eq!(presence.contains("building"), Some(false));
+        assert_eq!(presence.contains("name"), None);
+        assert_eq!(hot_keys.presence(&strings, &[]).contains("highway"), Some(false));
+    }
+
+    #[test]
+    fn test_key_limit() {
+        let keys: Vec<String> = (0..=HotKeys::MAX_KEYS).map(|i| format!("key{i}")).collect();
+        assert!(HotKeys::new(&keys).is_err());
+        assert_eq!(HotKeys::new(&keys[1..]).unwrap().keys().len(), HotKeys::MAX_KEYS);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::blocks::osm_id::{NodeId, RelationId, WayId};
+
+/// Id of a node, way or relation, for id range filters and lookups
+///
+/// Ranges are kept per element type, so a range of way ids selects ways only
+/// and never nodes or relations that happen to share the numbers.
+pub trait ElementId: Copy + Ord {
+    /// The ranges of this id type in a filter
+    fn filter_ranges(ranges: &IdRanges) -> &[(Self, Self)];
+
+    /// Mutable access to the ranges of this id type in a filter
+    fn filter_ranges_mut(ranges: &mut IdRanges) -> &mut Vec<(Self, Self)>;
+
+    /// The range of this id type in a blob
+    fn blob_range(ranges: &BlobIdRanges) -> &Option<(Self, Self)>;
+
+    /// Mutable access to the range of this id type in a blob
+    fn blob_range_mut(ranges: &mut BlobIdRanges) -> &mut Option<(Self, Self)>;
+}
+
+macro_rules! element_id {
+    ($id:ty, $filter:ident, $blob:ident) => {
+        impl ElementId for $id {
+            fn filter_ranges(ranges: &IdRanges) -> &[(Self, Self)] {
+                &ranges.$filter
+            }
+
+            fn filter_ranges_mut(ranges: &mut IdRanges) -> &mut Vec<(Self, Self)> {
+                &mut ranges.$filter
+            }
+
+            fn blob_range(ranges: &BlobIdRanges) -> &Option<(Self, Self)> {
+                &ranges.$blob
+            }
+
+            fn blob_range_mut(ranges: &mut BlobIdRanges) -> &mut Option<(Self, Self)> {
+                &mut ranges.$blob
+            }
+        }
+    };
+}
+
+element_id!(NodeId, nodes, node);
+element_id!(WayId, ways, way);
+element_id!(RelationId, relations, relation);
+
+/// Inclusive id ranges an `ElementFilter` keeps, per element type
+///
+/// An element type without ranges isn't restricted by id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdRanges {
+    pub nodes: Vec<(NodeId, NodeId)>,
+    pub ways: Vec<(WayId, WayId)>,
+    pub relations: Vec<(RelationId, RelationId)>,
+}
+
+impl IdRanges {
+    /// Add an inclusive range for the id's element type
+    pub fn push<I: ElementId>(&mut self, min: I, max: I) {
+        I::filter_ranges_mut(self).push((min, max));
+    }
+
+    /// Whether no element type is restricted by id
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.ways.is_empty() && self.relations.is_empty()
+    }
+
+    /// Whether an element with this id passes the ranges of its type
+    pub fn contains<I: ElementId>(&self, id: I) -> bool {
+        let ranges = I::filter_ranges(self);
+        ranges.is_empty() || ranges.iter().any(|&(min, max)| (min..=max).contains(&id))
+    }
+
+    /// Whether the ranges of an id type leave anything of a blob whose ids of
+    /// that type span `blob`
+    pub(crate) fn overlaps<I: ElementId>(&self, blob: &BlobIdRanges) -> bool {
+        let ranges = I::filter_ranges(self);
+        I::blob_range(blob).is_some_and(|(low, high)| {
+            ranges.is_empty() || ranges.iter().any(|&(min, max)| low <= max && high >= min)
+        })
+    }
+}
+
+/// Lowest and highest id of each element type in a blob, `None` for types
+/// the blob doesn't hold (filled by `build_deep_index`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlobIdRanges {
+    pub node: Option<(NodeId, NodeId)>,
+    pub way: Option<(WayId, WayId)>,
+    pub relation: Option<(RelationId, RelationId)>,
+}
+
+impl BlobIdRanges {
+    /// Widen the range of the id's element type to include it
+    pub fn observe<I: ElementId>(&mut self, id: I) {
+        let range = I::blob_range_mut(self);
+        *range = Some(range.map_or((id, id), |(min, max)| (min.min(id), max.max(id))));
+    }
+
+    /// Whether the blob may hold ids of this type between `min` and `max`
+    pub fn overlaps<I: ElementId>(&self, min: I, max: I) -> bool {
+        I::blob_range(self).is_some_and(|(low, high)| low <= max && high >= min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ranges_are_per_element_type() {
+        let mut ranges = IdRanges::default();
+        assert!(ranges.is_empty());
+        ranges.push(WayId(10), WayId(20));
+        assert_eq!(ranges.ways, [(WayId(10), WayId(20))]);
+        assert!(ranges.contains(WayId(15)) && !ranges.contains(WayId(21)));
+        // Nodes and relations aren't restricted by a way range
+        assert!(ranges.contains(NodeId(21)) && ranges.contains(RelationId(1)));
+
+        let mut blob = BlobIdRanges::default();
+        for id in [40, 5, 12] {
+            blob.observe(NodeId(id));
+        }
+        assert_eq!(blob, BlobIdRanges { node: Some((NodeId(5), NodeId(40))), way: None, relation: None });
+        assert!(blob.overlaps(NodeId(40), NodeId(50)) && !blob.overlaps(WayId(1), WayId(100)));
+        assert!(ranges.overlaps::<NodeId>(&blob));
+        assert!(!ranges.overlaps::<WayId>(&blob));
+    }
+}
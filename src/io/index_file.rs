@@ -0,0 +1,306 @@
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::io::blob::{BlobError, BlobType, Result};
+use crate::io::hot_keys::HotKeys;
+use crate::io::id_ranges::BlobIdRanges;
+use crate::io::indexed_reader::{BlobIndex, ElementCounts};
+
+/// First bytes of an index sidecar file
+const MAGIC: &[u8; 8] = b"OPBF-IDX";
+
+/// Version of the sidecar layout, bumped on any change
+const VERSION: u32 = 3;
+
+/// Bytes hashed at each end of the file for `FileIdentity`
+pub(crate) const EDGE_BYTES: u64 = 64 * 1024;
+
+/// What a sidecar was built from: the file length and a digest of its first
+/// and last `EDGE_BYTES`
+///
+/// Cheap to compute on every open, and changed by appends, truncation and
+/// rewrites of the header or the last blobs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileIdentity {
+    pub len: u64,
+    pub edge_digest: [u8; 32],
+}
+
+impl FileIdentity {
+    /// Identity of a file from its length and its two edges
+    pub fn new(len: u64, head: &[u8], tail: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(len.to_le_bytes());
+        hasher.update(head);
+        hasher.update(tail);
+        Self { len, edge_digest: hasher.finalize().into() }
+    }
+}
+
+/// Contents of an index sidecar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IndexFile {
+    pub identity: FileIdentity,
+    /// Keys the blobs' key presence was recorded for
+    pub hot_keys: HotKeys,
+    pub blobs: Vec<BlobIndex>,
+}
+
+impl IndexFile {
+    /// Serialize to the compact little-endian sidecar layout
+    ///
+    /// Offsets, sizes, types, id ranges, element counts and the presence of
+    /// `hot_keys` are stored; bounding boxes are not.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64 + self.blobs.len() * 56);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.identity.len.to_le_bytes());
+        out.extend_from_slice(&self.identity.edge_digest);
+        out.extend_from_slice(&(self.hot_keys.keys().len() as u32).to_le_bytes());
+        for key in self.hot_keys.keys() {
+            out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            out.extend_from_slice(key.as_bytes());
+        }
+        out.extend_from_slice(&(self.blobs.len() as u64).to_le_bytes());
+        for blob in &self.blobs {
+            for value in [blob.offset, blob.header_size, blob.size] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            match &blob.blob_type {
+                BlobType::OSMHeader => out.push(0),
+                BlobType::OSMData => out.push(1),
+                // Stored by name, so older versions read it as an unknown type
+                BlobType::OSMChange | BlobType::ZstdDictionary | BlobType::Unknown(_) => {
+                    let name = blob.blob_type.as_str();
+                    out.push(2);
+                    out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+                    out.extend_from_slice(name.as_bytes());
+                }
+            }
+            // Known flag, then an optional range per element type
+            match blob.id_ranges {
+                Some(ranges) => {
+                    out.push(1);
+                    let node = ranges.node.map(|(min, max)| (min.0, max.0));
+                    let way = ranges.way.map(|(min, max)| (min.0, max.0));
+                    let relation = ranges.relation.map(|(min, max)| (min.0, max.0));
+                    for range in [node, way, relation] {
+                        match range {
+                            Some((min, max)) => {
+                                out.push(1);
+                                out.extend_from_slice(&min.to_le_bytes());
+                                out.extend_from_slice(&max.to_le_bytes());
+                            }
+                            None => out.push(0),
+                        }
+                    }
+                }
+                None => out.push(0),
+            }
+            let counts = &blob.element_counts;
+            for count in [counts.nodes, counts.ways, counts.relations, counts.changesets] {
+                out.extend_from_slice(&count.to_le_bytes());
+            }
+            // Presence recorded for other keys than `hot_keys` is dropped
+            match blob.key_presence.as_ref().and_then(|presence| presence.bits_for(&self.hot_keys)) {
+                Some(bits) => {
+                    out.push(1);
+                    out.extend_from_slice(&bits.to_le_bytes());
+                }
+                None => out.push(0),
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut input = Input::new("Index file", bytes);
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(input.error("not an index file"));
+        }
+        let version = input.u32()?;
+        if version != VERSION {
+            return Err(input.error(&format!("unsupported version {version}")));
+        }
+        let len = input.u64()?;
+        let edge_digest = input.take(32)?.try_into().expect("32 bytes");
+        let key_count = input.u32()? as usize;
+        if key_count > HotKeys::MAX_KEYS {
+            return Err(input.error(&format!("{key_count} hot keys (max: {})", HotKeys::MAX_KEYS)));
+        }
+        let mut keys = Vec::with_capacity(key_count);
+        for _ in 0..key_count {
+            let key_len = input.u32()? as usize;
+            keys.push(std::str::from_utf8(input.take(key_len)?).map_err(|_| input.error("hot key is not UTF-8"))?);
+        }
+        let hot_keys = HotKeys::new(&keys)?;
+
+        let count = input.u64()?;
+        // Every entry takes at least 43 bytes, so a bogus count fails here
+        // instead of allocating
+        if count > (bytes.len() / 43) as u64 {
+            return Err(input.error(&format!("{count} entries don't fit in {} bytes", bytes.len())));
+        }
+        let mut blobs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (offset, header_size, size) = (input.u64()?, input.u64()?, input.u64()?);
+            let blob_type = match input.u8()? {
+                0 => BlobType::OSMHeader,
+                1 => BlobType::OSMData,
+                2 => {
+                    let name_len = input.u32()? as usize;
+                    let name = std::str::from_utf8(input.take(name_len)?).map_err(|_| input.error("blob type is not UTF-8"))?;
+                    name.parse().expect("parsing blob types is infallible")
+                }
+                other => return Err(input.error(&format!("unknown blob type tag {other}"))),
+            };
+            let id_ranges = match input.u8()? {
+                0 => None,
+                _ => Some(BlobIdRanges {
+                    node: input.id_range()?.map(|(min, max)| (min.into(), max.into())),
+                    way: input.id_range()?.map(|(min, max)| (min.into(), max.into())),
+                    relation: input.id_range()?.map(|(min, max)| (min.into(), max.into())),
+                }),
+            };
+            let element_counts = ElementCounts {
+                nodes: input.u32()?,
+                ways: input.u32()?,
+                relations: input.u32()?,
+                changesets: input.u32()?,
+            };
+            let key_presence = match input.u8()? {
+                0 => None,
+                _ => Some(hot_keys.presence_from_bits(input.u64()?)),
+            };
+            blobs.push(BlobIndex { offset, header_size, size, blob_type, id_ranges, element_counts, bbox: None, key_presence });
+        }
+        if !input.is_at_end() {
+            return Err(input.error("trailing bytes"));
+        }
+        Ok(Self { identity: FileIdentity { len, edge_digest }, hot_keys, blobs })
+    }
+
+    /// Write the sidecar, replacing any previous one atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, self.to_bytes())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read a sidecar, `None` if there is none at `path`
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Cursor over the bytes of a sidecar or cache file, naming the file kind
+/// in its errors
+pub(crate) struct Input<'a> {
+    what: &'static str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    pub(crate) fn new(what: &'static str, bytes: &'a [u8]) -> Self {
+        Self { what, bytes, pos: 0 }
+    }
+
+    /// Whether every byte was consumed
+    pub(crate) fn is_at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| self.error("truncated"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes")))
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes")))
+    }
+
+    /// A range stored as a presence flag and, if present, its two ends
+    pub(crate) fn id_range(&mut self) -> Result<Option<(i64, i64)>> {
+        Ok(match self.u8()? {
+            0 => None,
+            _ => Some((self.i64()?, self.i64()?)),
+        })
+    }
+
+    pub(crate) fn error(&self, reason: &str) -> BlobError {
+        BlobError::InvalidFormat(format!("{} at byte {}: {reason}", self.what, self.pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::{NodeId, RelationId};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let blob = |offset, blob_type| BlobIndex {
+            offset,
+            header_size: 13,
+            size: 100,
+            blob_type,
+            id_ranges: None,
+            element_counts: ElementCounts::default(),
+            bbox: None,
+            key_presence: None,
+        };
+        let mut data = blob(117, BlobType::OSMData);
+        data.id_ranges = Some(BlobIdRanges {
+            node: Some((NodeId(-5), NodeId(1 << 40))),
+            way: None,
+            relation: Some((RelationId(7), RelationId(7))),
+        });
+        data.element_counts = ElementCounts { nodes: 1, ways: 2, relations: 3, changesets: 4 };
+        let hot_keys = HotKeys::new(&["highway", "building"]).unwrap();
+        data.key_presence = Some(hot_keys.presence_from_bits(0b10));
+        let index = IndexFile {
+            identity: FileIdentity::new(1000, b"head", b"tail"),
+            hot_keys: hot_keys.clone(),
+            blobs: vec![blob(0, BlobType::OSMHeader), data.clone(), blob(234, BlobType::Unknown("OSMExtra".to_string()))],
+        };
+        let bytes = index.to_bytes();
+        assert_eq!(IndexFile::from_bytes(&bytes).unwrap(), index);
+
+        // Presence of keys other than the saved ones isn't stored
+        let mut stale = index.clone();
+        stale.hot_keys = HotKeys::new(&["building"]).unwrap();
+        let loaded = IndexFile::from_bytes(&stale.to_bytes()).unwrap();
+        assert_eq!(loaded.blobs[1].key_presence, None);
+        assert_eq!(loaded.blobs[1].element_counts, data.element_counts);
+
+        assert!(IndexFile::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(IndexFile::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        let mut other_version = bytes.clone();
+        other_version[8] = 1;
+        assert!(IndexFile::from_bytes(&other_version).is_err());
+        assert_ne!(FileIdentity::new(1000, b"head", b"tail"), FileIdentity::new(1001, b"head", b"tail"));
+    }
+}
@@ -1,21 +1,57 @@
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
 use bytes::Bytes;
-use crate::io::blob::{Blob, BlobType, BlobError, Result};
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::osm_id::{NodeId, RelationId, WayId};
+use crate::blocks::string_table::StringTable;
+use crate::blocks::timestamp::TimestampMillis;
+use crate::io::codec::{BlockDecoder, PbfBlockCodec};
+use crate::io::blob::{checked_offset, checked_usize, Blob, BlobHeader, BlobType, BlobError, Result, MAX_BLOB_HEADER_SIZE, MAX_BLOB_MESSAGE_SIZE};
+use crate::io::buffer_pool::BufferPool;
+use crate::io::decode::{blob_payload, decode_blob, decode_blob_header, decode_elements, decode_header_features, summarize_data_blob};
+use crate::io::features::{FeaturePolicy, FileOrdering, ReplicationInfo};
+use crate::io::geometry::{NodeLocationStore, SparseLocations};
+use crate::io::hot_keys::{HotKeys, KeyPresence};
+use crate::io::id_ranges::{BlobIdRanges, ElementId, IdRanges};
+use crate::io::reader::{CorruptBlob, OsmElement};
+use crate::io::index_file::{FileIdentity, IndexFile, EDGE_BYTES};
+use crate::io::instrument;
+use crate::io::live_stats::LiveStats;
+use crate::io::metadata_filter::MetadataFilter;
+use crate::io::polygon::Polygon;
+use crate::io::logging::{log_index_file_ignored, log_resync, log_skipped, SkipLogLevel};
+use crate::io::retry::RetryPolicy;
+use crate::io::validate::GroupPolicy;
+use crate::io::zstd_dictionary::ZstdDictionaries;
+
+/// Bytes scanned per read while looking for the next frame after damage
+const RESYNC_WINDOW: usize = 64 * 1024;
+
+/// Serialized start of a BlobHeader: the `type` field with its length
+const BLOB_HEADER_MARKERS: [&[u8]; 2] = [b"\x0a\x07OSMData", b"\x0a\x09OSMHeader"];
 
 /// Index entry for a blob, containing metadata for fast access
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlobIndex {
     /// Byte offset in the file
     pub offset: u64,
-    /// Size of the blob in bytes
-    pub size: u32,
+    /// Size of the serialized BlobHeader
+    pub header_size: u64,
+    /// Size of the blob in bytes (excluding the length prefix and BlobHeader)
+    pub size: u64,
     /// Type of blob (OSMHeader, OSMData, etc.)
     pub blob_type: BlobType,
-    /// ID range for primitive blocks (min_id, max_id)
-    pub id_range: Option<(i64, i64)>,
+    /// Id range of each element type in the blob (filled by `build_deep_index`)
+    pub id_ranges: Option<BlobIdRanges>,
     /// Element counts by type (nodes, ways, relations)
     pub element_counts: ElementCounts,
+    /// Extent of the blob's elements, including the full extent of ways and
+    /// relations whose nodes live in other blobs (filled by `build_bbox_index`)
+    pub bbox: Option<BoundingBox>,
+    /// Which registered hot keys occur in the blob (filled by `build_bbox_index`)
+    pub key_presence: Option<KeyPresence>,
 }
 
 /// Counts of different OSM elements in a blob
@@ -27,6 +63,13 @@ pub struct ElementCounts {
     pub changesets: u32,
 }
 
+impl ElementCounts {
+    /// All zero, as before `build_deep_index`; the blob may hold anything
+    pub fn is_unknown(&self) -> bool {
+        self.nodes == 0 && self.ways == 0 && self.relations == 0 && self.changesets == 0
+    }
+}
+
 /// Filter criteria for selecting OSM elements
 #[derive(Debug, Clone)]
 pub struct ElementFilter {
@@ -38,12 +81,18 @@ pub struct ElementFilter {
     pub include_relations: bool,
     /// Include changesets
     pub include_changesets: bool,
-    /// Filter by specific ID ranges
-    pub id_ranges: Vec<(i64, i64)>,
+    /// Filter by id ranges, kept per element type
+    pub id_ranges: IdRanges,
     /// Filter by tags (key-value pairs)
     pub tag_filters: HashMap<String, Option<String>>, // None means any value
     /// Resolve dependencies (fetch referenced nodes for ways, etc.)
     pub resolve_dependencies: bool,
+    /// Keep only nodes inside this box; see `with_bbox`
+    pub bbox: Option<BoundingBox>,
+    /// Keep only nodes inside this polygon as well; see `with_polygon`
+    pub polygon: Option<Arc<Polygon>>,
+    /// Predicates on version, timestamp, changeset, user and visibility
+    pub metadata: MetadataFilter,
 }
 
 impl Default for ElementFilter {
@@ -53,9 +102,12 @@ impl Default for ElementFilter {
             include_ways: true,
             include_relations: true,
             include_changesets: false,
-            id_ranges: Vec::new(),
+            id_ranges: IdRanges::default(),
             tag_filters: HashMap::new(),
             resolve_dependencies: false,
+            bbox: None,
+            polygon: None,
+            metadata: MetadataFilter::default(),
         }
     }
 }
@@ -89,11 +141,38 @@ impl ElementFilter {
         }
     }
     
-    /// Add an ID range filter
-    pub fn with_id_range(mut self, min_id: i64, max_id: i64) -> Self {
-        self.id_ranges.push((min_id, max_id));
+    /// Create a filter for only relations
+    pub fn relations_only() -> Self {
+        Self {
+            include_nodes: false,
+            include_ways: false,
+            include_relations: true,
+            include_changesets: false,
+            ..Default::default()
+        }
+    }
+    
+    /// Add an id range filter for the element type of the ids
+    ///
+    /// A range of way ids keeps ways in it and leaves nodes and relations
+    /// alone; ranges of one type add up.
+    pub fn with_id_range<I: ElementId>(mut self, min_id: I, max_id: I) -> Self {
+        self.id_ranges.push(min_id, max_id);
         self
     }
+
+    /// Whether a blob may hold an included element inside the id ranges
+    ///
+    /// Blobs without id ranges (no deep index) may hold anything.
+    pub(crate) fn may_match_ids(&self, blob: &BlobIndex) -> bool {
+        let Some(ranges) = &blob.id_ranges else {
+            return true;
+        };
+        (self.include_nodes && self.id_ranges.overlaps::<NodeId>(ranges))
+            || (self.include_ways && self.id_ranges.overlaps::<WayId>(ranges))
+            || (self.include_relations && self.id_ranges.overlaps::<RelationId>(ranges))
+            || (self.include_changesets && blob.element_counts.changesets > 0)
+    }
     
     /// Add a tag filter (key must exist with any value)
     pub fn with_tag_key(mut self, key: String) -> Self {
@@ -106,6 +185,96 @@ impl ElementFilter {
         self.tag_filters.insert(key, Some(value));
         self
     }
+    
+    /// Keep only nodes inside a bounding box
+    ///
+    /// Node coordinates are checked while decoding, and blobs whose indexed
+    /// bounding box (see `build_bbox_index`) misses the box aren't read. With
+    /// `resolve_dependencies`, ways are kept only if they reference a node
+    /// inside the box, and relations only if they reference such a node or a
+    /// kept way; otherwise ways and relations aren't affected.
+    pub fn with_bbox(mut self, bbox: BoundingBox) -> Self {
+        self.bbox = Some(bbox);
+        self
+    }
+
+    /// Keep only nodes inside a polygon
+    ///
+    /// Sets the bounding box to the polygon's, so blobs are pruned and ways
+    /// and relations resolved as with `with_bbox`, and tests each node
+    /// inside the box against the polygon while decoding.
+    pub fn with_polygon(mut self, polygon: Polygon) -> Self {
+        self.bbox = Some(polygon.bbox());
+        self.polygon = Some(Arc::new(polygon));
+        self
+    }
+
+    /// Keep only elements with a version in `min..=max`
+    pub fn with_version_range(mut self, min: i32, max: i32) -> Self {
+        self.metadata.min_version = Some(min);
+        self.metadata.max_version = Some(max);
+        self
+    }
+
+    /// Keep only elements last modified strictly after `timestamp`
+    pub fn with_modified_after(mut self, timestamp: TimestampMillis) -> Self {
+        self.metadata.modified_after = Some(timestamp);
+        self
+    }
+
+    /// Keep only elements last modified strictly before `timestamp`
+    pub fn with_modified_before(mut self, timestamp: TimestampMillis) -> Self {
+        self.metadata.modified_before = Some(timestamp);
+        self
+    }
+
+    /// Add a range of changeset ids to keep elements from
+    pub fn with_changeset_range(mut self, min: i64, max: i64) -> Self {
+        self.metadata.changeset_ranges.push((min, max));
+        self
+    }
+
+    /// Add a user id to keep elements by
+    pub fn with_uid(mut self, uid: i32) -> Self {
+        self.metadata.uids.insert(uid);
+        self
+    }
+
+    /// Add a user name to keep elements by
+    pub fn with_user(mut self, user: String) -> Self {
+        self.metadata.users.insert(user);
+        self
+    }
+
+    /// Drop deleted elements (`visible == false`) of history files
+    pub fn with_visible_only(mut self) -> Self {
+        self.metadata.visible_only = true;
+        self
+    }
+
+    /// Whether ways and relations are kept by their members' node locations
+    pub(crate) fn bbox_dependencies(&self) -> bool {
+        self.bbox.is_some() && self.resolve_dependencies
+    }
+
+    /// Whether the element carries every filtered tag, resolved against `strings`
+    pub fn matches_tags(&self, element: &OsmElement, strings: &StringTable) -> bool {
+        if self.tag_filters.is_empty() {
+            return true;
+        }
+        let (keys, vals) = match element {
+            OsmElement::Node(node) => (&node.keys, &node.vals),
+            OsmElement::Way(way) => (&way.keys, &way.vals),
+            OsmElement::Relation(relation) => (&relation.keys, &relation.vals),
+            OsmElement::ChangeSet(changeset) => (&changeset.keys, &changeset.vals),
+        };
+        self.tag_filters.iter().all(|(key, value)| {
+            keys.iter().zip(vals).any(|(k, v)| {
+                strings.get_string(*k as usize) == Some(key.as_str())
+                    && value.as_ref().is_none_or(|value| strings.get_string(*v as usize) == Some(value.as_str()))
+            })
+        })
+    }
 }
 
 /// Performant structure for random-access and filtered streaming of OSM PBF data
@@ -118,37 +287,297 @@ pub struct IndexedReader<R: Read + Seek> {
     header_blob: Option<BlobIndex>,
     /// Quick lookup for blobs by offset
     offset_to_index: HashMap<u64, usize>,
+    /// Verbosity for reporting skipped data
+    skip_log_level: SkipLogLevel,
+    /// Retry policy for transient IO errors
+    retry_policy: RetryPolicy,
+    /// Number of retries performed so far
+    retries_performed: u64,
+    /// Scratch buffers for blob reads
+    buffer_pool: BufferPool,
+    /// Bytes of damaged regions skipped while building the index
+    bytes_skipped: u64,
+    /// Number of times indexing resynchronized after a damaged region
+    resyncs: u64,
+    /// Damaged regions indexing skipped, with the error found at their start
+    damaged_regions: Vec<CorruptBlob>,
+    /// Required features declared by the file header
+    required_features: Vec<String>,
+    /// Optional features declared by the file header
+    optional_features: Vec<String>,
+    /// Sort order declared by the header features
+    ordering: FileOrdering,
+    /// Replication state recorded in the file header
+    replication: ReplicationInfo,
+    /// Tag keys whose per-blob presence the deep index pass records
+    hot_keys: HotKeys,
+    /// zstd dictionaries of the file, loaded as their blobs are indexed
+    zstd_dictionaries: ZstdDictionaries,
+    /// Optional per-element bounding boxes of ways (filled by `build_bbox_index`)
+    way_bboxes: HashMap<WayId, BoundingBox>,
+    /// Optional per-element bounding boxes of relations (filled by `build_bbox_index`)
+    relation_bboxes: HashMap<RelationId, BoundingBox>,
+    /// Whether the index was loaded from a sidecar file
+    index_reused: bool,
+    /// Offset the index scan resumes at, while blobs are indexed on demand
+    scan_offset: Option<u64>,
+    /// File length seen when the index scan started
+    scan_len: u64,
+    /// Counters blob reads report their position in the file to
+    live_stats: LiveStats,
+    /// Largest blob read, stored or inflated (see `set_max_blob_size`)
+    max_blob_size: usize,
 }
 
 impl<R: Read + Seek> IndexedReader<R> {
     /// Create a new IndexedReader and build the index
     pub fn new(reader: R) -> Result<Self> {
-        let mut indexed_reader = Self {
+        Self::with_skip_log_level(reader, SkipLogLevel::default())
+    }
+    
+    /// Create a new IndexedReader that reports skipped data at the given level
+    pub fn with_skip_log_level(reader: R, skip_log_level: SkipLogLevel) -> Result<Self> {
+        Self::with_options(reader, skip_log_level, RetryPolicy::default(), FeaturePolicy::default(), false)
+    }
+    
+    /// Create a new IndexedReader that retries transient IO errors, including
+    /// while building the index
+    pub fn with_retry_policy(reader: R, retry_policy: RetryPolicy) -> Result<Self> {
+        Self::with_options(reader, SkipLogLevel::default(), retry_policy, FeaturePolicy::default(), false)
+    }
+    
+    /// Create a new IndexedReader that handles unsupported required features
+    /// in the file header according to `feature_policy`
+    pub fn with_feature_policy(reader: R, feature_policy: FeaturePolicy) -> Result<Self> {
+        Self::with_options(reader, SkipLogLevel::default(), RetryPolicy::default(), feature_policy, false)
+    }
+    
+    /// Create a new IndexedReader that indexes blobs on demand instead of
+    /// scanning the whole file first
+    ///
+    /// Only the first blob (the file header) is indexed on creation. Reads by
+    /// index extend the index up to the blob asked for, so `Reader::for_each`
+    /// emits elements from the first blobs right away; `blob_count`, `index`
+    /// and everything built on them cover only the blobs indexed so far until
+    /// `finish_index` is called or `is_index_complete` returns true.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::IndexedReader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = IndexedReader::lazy(File::open("planet.osm.pbf")?)?;
+    /// let first_data_blob = reader.read_blob_by_index(1)?;
+    /// assert!(!reader.is_index_complete());
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn lazy(reader: R) -> Result<Self> {
+        Self::with_options(reader, SkipLogLevel::default(), RetryPolicy::default(), FeaturePolicy::default(), true)
+    }
+    
+    /// Whether every blob of the file is indexed (always true unless created with `lazy`)
+    pub fn is_index_complete(&self) -> bool {
+        self.scan_offset.is_none()
+    }
+    
+    /// Index blobs up to and including `index`, returning whether that blob exists
+    pub fn ensure_indexed(&mut self, index: usize) -> Result<bool> {
+        while self.blob_index.len() <= index {
+            if !self.index_next_blob()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    
+    /// Index the rest of the file
+    pub fn finish_index(&mut self) -> Result<()> {
+        if self.is_index_complete() {
+            return Ok(());
+        }
+        let _span = instrument::build_index();
+        while self.index_next_blob()? {}
+        instrument::index_built(self.blob_index.len(), self.bytes_skipped);
+        Ok(())
+    }
+    
+    /// Open with the blob index saved at `index_path` by `save_index`, or
+    /// build the index if that file is missing, stale or unreadable
+    ///
+    /// The saved index is reused only if the file still has the length and
+    /// the first and last 64 KiB it had when the index was saved. Deep index
+    /// data (id ranges, element counts, hot key presence) is kept and the
+    /// saved hot keys are registered again; bounding boxes have to be rebuilt.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::IndexedReader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = IndexedReader::open_with_index(File::open("planet.osm.pbf")?, "planet.osm.pbf.idx")?;
+    /// if !reader.index_reused() {
+    ///     reader.save_index("planet.osm.pbf.idx")?;
+    /// }
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn open_with_index(reader: R, index_path: impl AsRef<Path>) -> Result<Self> {
+        let mut indexed_reader = Self::unindexed(reader, SkipLogLevel::default(), RetryPolicy::default());
+        let identity = indexed_reader.file_identity()?;
+        let saved = match IndexFile::load(index_path.as_ref()) {
+            Ok(Some(saved)) if saved.identity != identity => {
+                log_index_file_ignored(indexed_reader.skip_log_level, &"the file changed since the index was saved");
+                None
+            }
+            Ok(saved) => saved,
+            Err(e) => {
+                log_index_file_ignored(indexed_reader.skip_log_level, &e);
+                None
+            }
+        };
+        match saved {
+            Some(saved) => {
+                if !saved.hot_keys.is_empty() {
+                    indexed_reader.hot_keys = saved.hot_keys;
+                }
+                for entry in saved.blobs {
+                    if matches!(entry.blob_type, BlobType::OSMHeader) {
+                        indexed_reader.header_blob = Some(entry.clone());
+                    }
+                    indexed_reader.offset_to_index.insert(entry.offset, indexed_reader.blob_index.len());
+                    indexed_reader.blob_index.push(entry);
+                }
+                for index in 0..indexed_reader.blob_index.len() {
+                    if matches!(indexed_reader.blob_index[index].blob_type, BlobType::ZstdDictionary) {
+                        indexed_reader.load_zstd_dictionary(index);
+                    }
+                }
+                indexed_reader.index_reused = true;
+            }
+            None => indexed_reader.build_index()?,
+        }
+        indexed_reader.read_header(FeaturePolicy::default())?;
+        Ok(indexed_reader)
+    }
+    
+    /// Save the blob index next to the file, for `open_with_index`
+    pub fn save_index(&mut self, index_path: impl AsRef<Path>) -> Result<()> {
+        self.finish_index()?;
+        IndexFile {
+            identity: self.file_identity()?,
+            hot_keys: self.hot_keys.clone(),
+            blobs: self.blob_index.clone(),
+        }
+        .save(index_path.as_ref())
+    }
+    
+    /// Whether `open_with_index` reused a saved index instead of scanning the file
+    pub fn index_reused(&self) -> bool {
+        self.index_reused
+    }
+    
+    /// Length and edge digest identifying the file's current contents
+    fn file_identity(&mut self) -> Result<FileIdentity> {
+        let len = self.reader.seek(SeekFrom::End(0))?;
+        let edge = EDGE_BYTES.min(len);
+        let mut head = vec![0u8; edge as usize];
+        let mut tail = vec![0u8; edge as usize];
+        self.read_exact_at(0, &mut head)?;
+        self.read_exact_at(len - edge, &mut tail)?;
+        Ok(FileIdentity::new(len, &head, &tail))
+    }
+    
+    /// Create an IndexedReader with every constructor setting; with
+    /// `lazy_index`, index on demand as `lazy` does
+    pub(crate) fn with_options(reader: R, skip_log_level: SkipLogLevel, retry_policy: RetryPolicy, feature_policy: FeaturePolicy, lazy_index: bool) -> Result<Self> {
+        let mut indexed_reader = Self::unindexed(reader, skip_log_level, retry_policy);
+        if lazy_index {
+            indexed_reader.start_index()?;
+            indexed_reader.index_next_blob()?;
+        } else {
+            indexed_reader.build_index()?;
+        }
+        indexed_reader.read_header(feature_policy)?;
+        Ok(indexed_reader)
+    }
+    
+    fn unindexed(reader: R, skip_log_level: SkipLogLevel, retry_policy: RetryPolicy) -> Self {
+        Self {
             reader,
             blob_index: Vec::new(),
             header_blob: None,
             offset_to_index: HashMap::new(),
-        };
-        
-        indexed_reader.build_index()?;
-        Ok(indexed_reader)
+            skip_log_level,
+            retry_policy,
+            retries_performed: 0,
+            buffer_pool: BufferPool::default(),
+            bytes_skipped: 0,
+            resyncs: 0,
+            damaged_regions: Vec::new(),
+            required_features: Vec::new(),
+            optional_features: Vec::new(),
+            ordering: FileOrdering::default(),
+            replication: ReplicationInfo::default(),
+            hot_keys: HotKeys::default(),
+            zstd_dictionaries: ZstdDictionaries::default(),
+            way_bboxes: HashMap::new(),
+            relation_bboxes: HashMap::new(),
+            index_reused: false,
+            scan_offset: None,
+            scan_len: 0,
+            live_stats: LiveStats::default(),
+            max_blob_size: MAX_BLOB_MESSAGE_SIZE,
+        }
+    }
+    
+    /// Read the header features of an indexed file and check them against `feature_policy`
+    fn read_header(&mut self, feature_policy: FeaturePolicy) -> Result<()> {
+        (self.required_features, self.optional_features, self.replication) = self.read_header_features()?;
+        feature_policy.check(self.required_features.iter().map(String::as_str))?;
+        self.ordering = FileOrdering::from_features(
+            self.required_features.iter().chain(&self.optional_features).map(String::as_str),
+        );
+        Ok(())
     }
     
     /// Build the in-memory index by scanning all blobs
+    ///
+    /// A damaged frame doesn't end the scan: indexing resumes
+    /// at the next plausible BlobHeader, and the bytes in between are counted
+    /// in `bytes_skipped`.
     fn build_index(&mut self) -> Result<()> {
+        self.start_index()?;
+        self.finish_index()
+    }
+    
+    /// Check the start of the file and position the index scan there
+    fn start_index(&mut self) -> Result<()> {
+        self.scan_len = self.reader.seek(SeekFrom::End(0))?;
         self.reader.seek(SeekFrom::Start(0))?;
-        let mut current_offset = 0u64;
+        self.check_header_frame()?;
+        self.scan_offset = Some(0);
+        Ok(())
+    }
+    
+    /// Index the next blob of the scan, returning false once it reached the end
+    fn index_next_blob(&mut self) -> Result<bool> {
+        let Some(mut current_offset) = self.scan_offset else {
+            return Ok(false);
+        };
         
         loop {
             // Try to read the next blob
             match self.read_blob_header_at_offset(current_offset) {
-                Ok(Some((header, blob_size))) => {
+                Ok(Some((header, header_size))) => {
+                    let blob_size = header.datasize as u64;
                     let index_entry = BlobIndex {
                         offset: current_offset,
+                        header_size,
                         size: blob_size,
                         blob_type: header.blob_type,
-                        id_range: None, // Will be filled when we actually read the blob
+                        id_ranges: None, // Will be filled when we actually read the blob
                         element_counts: ElementCounts::default(),
+                        bbox: None,
+                        key_presence: None,
                     };
                     
                     // Store header blob separately
@@ -157,46 +586,227 @@ impl<R: Read + Seek> IndexedReader<R> {
                     }
                     
                     let index = self.blob_index.len();
+                    let is_dictionary = matches!(index_entry.blob_type, BlobType::ZstdDictionary);
                     self.offset_to_index.insert(current_offset, index);
                     self.blob_index.push(index_entry);
+                    if is_dictionary {
+                        self.load_zstd_dictionary(index);
+                    }
                     
                     // Move to next blob
-                    current_offset += 4 + blob_size as u64; // 4 bytes for size + blob data
+                    self.scan_offset = Some(checked_offset(current_offset, 4 + header_size + blob_size)?); // 4 bytes for size + header + blob data
+                    return Ok(true);
                 }
                 Ok(None) => break, // End of file
                 Err(e) => {
-                    // For robust error handling, log the error but continue if possible
-                    eprintln!("Warning: Error reading blob at offset {current_offset}: {e}");
-                    break;
+                    log_skipped(self.skip_log_level, Some(current_offset), Some(self.blob_index.len()), &e);
+                    self.damaged_regions.push(CorruptBlob { offset: current_offset, blob_index: None, kind: e.kind(), error: e.to_string() });
+                    let next = self.find_next_frame(current_offset, self.scan_len)?;
+                    let resumed_at = next.unwrap_or(self.scan_len).max(current_offset);
+                    log_resync(self.skip_log_level, current_offset, next);
+                    self.bytes_skipped += resumed_at - current_offset;
+                    match next {
+                        Some(next) => {
+                            self.resyncs += 1;
+                            current_offset = next;
+                        }
+                        None => break,
+                    }
                 }
             }
         }
         
-        Ok(())
+        self.scan_offset = None;
+        Ok(false)
     }
     
-    /// Read just the blob header at a specific offset (for indexing)
-    fn read_blob_header_at_offset(&mut self, offset: u64) -> Result<Option<(crate::io::blob::BlobHeader, u32)>> {
-        self.reader.seek(SeekFrom::Start(offset))?;
+    /// Scan forward from a damaged frame at `damaged` for the next plausible
+    /// frame: a BlobHeader of a known type whose blob fits in the file
+    fn find_next_frame(&mut self, damaged: u64, file_len: u64) -> Result<Option<u64>> {
+        let overlap = BLOB_HEADER_MARKERS.iter().map(|m| m.len()).max().unwrap_or(0);
+        // A frame starting after `damaged` has its marker after the 4 byte length prefix
+        let mut window_start = checked_offset(damaged, 5)?;
+        let mut window = vec![0u8; RESYNC_WINDOW + overlap];
+        
+        while window_start < file_len {
+            let len = (file_len - window_start).min(window.len() as u64) as usize;
+            self.read_exact_at(window_start, &mut window[..len])?;
+            
+            for pos in 0..len.min(RESYNC_WINDOW) {
+                if !BLOB_HEADER_MARKERS.iter().any(|marker| window[pos..len].starts_with(marker)) {
+                    continue;
+                }
+                let candidate = window_start + pos as u64 - 4;
+                if self.is_plausible_frame(candidate, file_len) {
+                    return Ok(Some(candidate));
+                }
+            }
+            window_start += RESYNC_WINDOW as u64;
+        }
         
+        Ok(None)
+    }
+    
+    fn is_plausible_frame(&mut self, offset: u64, file_len: u64) -> bool {
+        match self.read_blob_header_at_offset(offset) {
+            Ok(Some((header, header_size))) => {
+                let datasize = header.datasize as u64;
+                !matches!(header.blob_type, BlobType::Unknown(_))
+                    && datasize > 0
+                    && datasize <= MAX_BLOB_MESSAGE_SIZE as u64
+                    && offset + 4 + header_size + datasize <= file_len
+            }
+            _ => false,
+        }
+    }
+    
+    /// Read exactly `buf.len()` bytes at `offset`, retrying transient errors
+    fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        let reader = &mut self.reader;
+        self.retry_policy.run(&mut self.retries_performed, || {
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(buf)
+        })
+    }
+    
+    /// Check that the file starts with an OSMHeader blob (or is empty)
+    ///
+    /// A damaged first frame fails here; later damaged frames are skipped by
+    /// resynchronizing.
+    fn check_header_frame(&mut self) -> Result<()> {
+        match self.read_blob_header_at_offset(0)? {
+            None => Ok(()),
+            Some((header, _)) if matches!(header.blob_type, BlobType::OSMHeader) => Ok(()),
+            Some((header, _)) => Err(BlobError::InvalidFormat(format!(
+                "File starts with an {} blob instead of OSMHeader",
+                header.blob_type.as_str()
+            ))),
+        }
+    }
+    
+    /// Read just the blob header at a specific offset (for indexing), together
+    /// with the size of the serialized header
+    fn read_blob_header_at_offset(&mut self, offset: u64) -> Result<Option<(BlobHeader, u64)>> {
         // Read blob size (4 bytes, big-endian)
         let mut size_bytes = [0u8; 4];
-        match self.reader.read_exact(&mut size_bytes) {
+        match self.read_exact_at(offset, &mut size_bytes) {
             Ok(_) => {},
             Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
             Err(e) => return Err(BlobError::Io(e)),
         }
         
-        let blob_size = u32::from_be_bytes(size_bytes);
+        let header_size = u32::from_be_bytes(size_bytes) as usize;
+        if header_size > MAX_BLOB_HEADER_SIZE {
+            return Err(BlobError::HeaderTooLarge { size: header_size, max: MAX_BLOB_HEADER_SIZE });
+        }
+        let mut header_bytes = vec![0u8; header_size];
+        self.read_exact_at(checked_offset(offset, 4)?, &mut header_bytes)?;
         
-        // For now, we'll create a simplified header
-        // In a full implementation, this would parse the actual protobuf header
-        let header = crate::io::blob::BlobHeader::new(
-            BlobType::OSMData, // Default, would be parsed from actual data
-            blob_size
-        );
+        Ok(Some((decode_blob_header(&header_bytes)?, header_size as u64)))
+    }
+    
+    /// Load the zstd dictionary stored in an indexed blob
+    ///
+    /// A damaged dictionary is reported like a damaged frame; the blobs
+    /// compressed with it then fail to decompress on their own.
+    fn load_zstd_dictionary(&mut self, index: usize) {
+        let offset = self.blob_index[index].offset;
+        let loaded = self.read_blob_at_offset(offset).and_then(|blob| match blob {
+            Some(blob) => self.zstd_dictionaries.load(&blob),
+            None => Ok(()),
+        });
+        if let Err(e) = loaded {
+            log_skipped(self.skip_log_level, Some(offset), Some(index), &e);
+            self.damaged_regions.push(CorruptBlob { offset, blob_index: Some(index), kind: e.kind(), error: e.to_string() });
+        }
+    }
+    
+    /// Read the features and replication state from the header blob, if there is one
+    fn read_header_features(&mut self) -> Result<(Vec<String>, Vec<String>, ReplicationInfo)> {
+        let Some(offset) = self.header_blob.as_ref().map(|header| header.offset) else {
+            return Ok(Default::default());
+        };
+        let Some(blob) = self.read_blob_at_offset(offset)? else {
+            return Ok(Default::default());
+        };
+        match blob_payload(&blob) {
+            Ok(payload) => decode_header_features(&payload),
+            // LZMA and bzip2 headers can't be inspected until those are supported
+            Err(BlobError::Compression(_)) => Ok(Default::default()),
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// Required features declared by the file header (empty without one)
+    pub fn required_features(&self) -> &[String] {
+        &self.required_features
+    }
+    
+    /// Optional features declared by the file header (empty without one)
+    pub fn optional_features(&self) -> &[String] {
+        &self.optional_features
+    }
+    
+    /// Sort order declared by the file header
+    pub fn ordering(&self) -> &FileOrdering {
+        &self.ordering
+    }
+    
+    /// Replication state recorded in the file header (all `None` without one)
+    pub fn replication(&self) -> &ReplicationInfo {
+        &self.replication
+    }
+    
+    /// Read `len` bytes at `offset`, refusing lengths no valid blob can have
+    fn read_bytes_at(&mut self, offset: u64, len: u64) -> Result<Bytes> {
+        let len = checked_usize(len)?;
+        if len > MAX_BLOB_MESSAGE_SIZE {
+            return Err(BlobError::MessageTooLarge { size: len, max: MAX_BLOB_MESSAGE_SIZE });
+        }
         
-        Ok(Some((header, blob_size)))
+        let mut buf = vec![0u8; len];
+        self.read_exact_at(offset, &mut buf)?;
+        Ok(Bytes::from(buf))
+    }
+    
+    /// Get the verbosity used for reporting skipped data
+    pub fn skip_log_level(&self) -> SkipLogLevel {
+        self.skip_log_level
+    }
+    
+    /// Set the retry policy used for subsequent reads
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+    
+    /// Replace the pool providing scratch buffers for blob reads, e.g. to share
+    /// one pool between several readers
+    pub fn set_buffer_pool(&mut self, buffer_pool: BufferPool) {
+        self.buffer_pool = buffer_pool;
+    }
+    
+    /// Refuse blobs larger than `max_blob_size` bytes, stored or inflated,
+    /// with `BlobError::MessageTooLarge`
+    ///
+    /// Only lowers the limit: values above `MAX_BLOB_MESSAGE_SIZE`, the
+    /// format's own limit, are capped to it.
+    pub fn set_max_blob_size(&mut self, max_blob_size: usize) {
+        self.max_blob_size = max_blob_size.min(MAX_BLOB_MESSAGE_SIZE);
+    }
+    
+    /// Largest blob read, stored or inflated
+    pub fn max_blob_size(&self) -> usize {
+        self.max_blob_size
+    }
+    
+    /// Get the pool providing scratch buffers for blob reads
+    pub fn buffer_pool(&self) -> &BufferPool {
+        &self.buffer_pool
+    }
+    
+    /// Get the number of IO retries performed so far (including index build)
+    pub fn retries_performed(&self) -> u64 {
+        self.retries_performed
     }
     
     /// Get the header blob if it exists
@@ -204,6 +814,16 @@ impl<R: Read + Seek> IndexedReader<R> {
         self.header_blob.as_ref()
     }
     
+    /// Get the number of bytes of damaged regions skipped while indexing
+    pub fn bytes_skipped(&self) -> u64 {
+        self.bytes_skipped
+    }
+    
+    /// Damaged regions skipped while indexing, by where they start
+    pub fn damaged_regions(&self) -> &[CorruptBlob] {
+        &self.damaged_regions
+    }
+    
     /// Get the number of indexed blobs
     pub fn blob_count(&self) -> usize {
         self.blob_index.len()
@@ -214,41 +834,98 @@ impl<R: Read + Seek> IndexedReader<R> {
         self.blob_index.get(index)
     }
     
+    /// All blob index entries, in file order
+    pub fn index(&self) -> &[BlobIndex] {
+        &self.blob_index
+    }
+    
     /// Read a specific blob by its index
     pub fn read_blob_by_index(&mut self, index: usize) -> Result<Option<Blob>> {
+        self.ensure_indexed(index)?;
         let blob_index = self.blob_index.get(index).ok_or_else(|| {
             BlobError::InvalidFormat(format!("Blob index {index} out of range"))
         })?;
         
-        self.read_blob_at_offset(blob_index.offset)
+        let offset = blob_index.offset;
+        let _span = instrument::read_blob(index, offset);
+        self.live_stats.record_position(frame_end(blob_index), self.file_len());
+        self.read_blob_at_offset(offset)
     }
     
-    /// Read a blob at a specific file offset
-    pub fn read_blob_at_offset(&mut self, offset: u64) -> Result<Option<Blob>> {
-        self.reader.seek(SeekFrom::Start(offset))?;
+    /// Length of the file, as far as the index scan or a saved index knows
+    pub fn file_len(&self) -> u64 {
+        self.blob_index.last().map_or(self.scan_len, |last| self.scan_len.max(frame_end(last)))
+    }
+
+    /// Current length of the underlying stream, which a saved index doesn't record
+    pub(crate) fn stream_len(&mut self) -> Result<u64> {
+        Ok(self.reader.seek(SeekFrom::End(0))?)
+    }
+    
+    /// Counters blob reads by index report their position to, for progress
+    pub(crate) fn live_stats(&self) -> &LiveStats {
+        &self.live_stats
+    }
+    
+    /// Read the undecoded BlobHeader and Blob messages of a blob
+    ///
+    /// The bytes are exactly as stored in the file, without the length prefix,
+    /// and can be written elsewhere with `RawBlobWriter::write_raw` without
+    /// decompressing or decoding the block.
+    pub fn read_raw_blob(&mut self, index: usize) -> Result<(Bytes, Bytes)> {
+        self.ensure_indexed(index)?;
+        let blob_index = self.blob_index.get(index).cloned().ok_or_else(|| {
+            BlobError::InvalidFormat(format!("Blob index {index} out of range"))
+        })?;
         
-        // Read blob size
-        let mut size_bytes = [0u8; 4];
-        match self.reader.read_exact(&mut size_bytes) {
-            Ok(_) => {},
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-            Err(e) => return Err(BlobError::Io(e)),
+        let header_offset = checked_offset(blob_index.offset, 4)?;
+        let header = self.read_bytes_at(header_offset, blob_index.header_size)?;
+        let blob = self.read_bytes_at(checked_offset(header_offset, blob_index.header_size)?, blob_index.size)?;
+        Ok((header, blob))
+    }
+    
+    /// Read a complete frame exactly as stored in the file, length prefix included
+    ///
+    /// Concatenating the frames of the header blob and any data blobs yields a
+    /// valid file.
+    pub fn read_frame_bytes(&mut self, index: usize) -> Result<Bytes> {
+        self.ensure_indexed(index)?;
+        let blob_index = self.blob_index.get(index).ok_or_else(|| {
+            BlobError::InvalidFormat(format!("Blob index {index} out of range"))
+        })?;
+        if blob_index.header_size > MAX_BLOB_HEADER_SIZE as u64 {
+            return Err(BlobError::HeaderTooLarge { size: checked_usize(blob_index.header_size)?, max: MAX_BLOB_HEADER_SIZE });
+        }
+        if blob_index.size > MAX_BLOB_MESSAGE_SIZE as u64 {
+            return Err(BlobError::MessageTooLarge { size: checked_usize(blob_index.size)?, max: MAX_BLOB_MESSAGE_SIZE });
         }
         
-        let blob_size = u32::from_be_bytes(size_bytes);
-        
-        // Read blob data
-        let mut blob_data = vec![0u8; blob_size as usize];
-        self.reader.read_exact(&mut blob_data)?;
-        
-        // For now, create a simple raw blob
-        // In full implementation, this would parse the protobuf structure
-        let blob = Blob::new_raw(
-            BlobType::OSMData,
-            Bytes::from(blob_data),
-            offset
-        )?;
+        let offset = blob_index.offset;
+        let mut buf = vec![0u8; checked_usize(4 + blob_index.header_size + blob_index.size)?];
+        self.read_exact_at(offset, &mut buf)?;
+        Ok(Bytes::from(buf))
+    }
+    
+    /// Read a blob at a specific file offset
+    pub fn read_blob_at_offset(&mut self, offset: u64) -> Result<Option<Blob>> {
+        let Some((header, header_size)) = self.read_blob_header_at_offset(offset)? else {
+            return Ok(None);
+        };
+        let blob_offset = checked_offset(offset, 4 + header_size)?;
+        let datasize = header.datasize as usize;
+        if datasize > self.max_blob_size {
+            return Err(BlobError::MessageTooLarge { size: datasize, max: self.max_blob_size });
+        }
         
+        // The frame is only scratch space: decoding copies the payload out
+        let mut blob_data = self.buffer_pool.get(datasize);
+        self.read_exact_at(blob_offset, &mut blob_data)?;
+        let mut blob = decode_blob(header, &blob_data, offset)?;
+        self.zstd_dictionaries.attach(&mut blob);
+        let raw_size = blob.raw_size() as usize;
+        if raw_size > self.max_blob_size {
+            return Err(BlobError::MessageTooLarge { size: raw_size, max: self.max_blob_size });
+        }
         Ok(Some(blob))
     }
     
@@ -265,7 +942,8 @@ impl<R: Read + Seek> IndexedReader<R> {
             match blob_index.blob_type {
                 BlobType::OSMHeader => stats.header_blobs += 1,
                 BlobType::OSMData => stats.data_blobs += 1,
-                BlobType::Unknown(_) => stats.unknown_blobs += 1,
+                BlobType::OSMChange => stats.change_blobs += 1,
+                BlobType::ZstdDictionary | BlobType::Unknown(_) => stats.unknown_blobs += 1,
             }
             
             stats.total_nodes += blob_index.element_counts.nodes as u64;
@@ -275,29 +953,244 @@ impl<R: Read + Seek> IndexedReader<R> {
         }
         
         stats.total_blobs = self.blob_index.len() as u64;
+        stats.bytes_skipped = self.bytes_skipped;
+        stats.resyncs = self.resyncs;
         stats
     }
     
-    /// Find blobs that potentially contain elements in the given ID range
-    pub fn find_blobs_for_id_range(&self, min_id: i64, max_id: i64) -> Vec<usize> {
+    /// Register tag keys whose presence `build_deep_index` and
+    /// `build_bbox_index` record per blob
+    ///
+    /// Filters on a hot key then prune blobs where no element carries it, see
+    /// `PruneReason::TagKeys`. At most `HotKeys::MAX_KEYS` keys.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, IndexedReader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = IndexedReader::new(File::open("map.osm.pbf")?)?;
+    /// reader.set_hot_keys(&["highway", "building", "natural"])?;
+    /// reader.build_deep_index()?;
+    /// let plan = ElementFilter::all().with_tag_key("building".to_string()).explain(reader.index());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_hot_keys(&mut self, keys: &[&str]) -> Result<()> {
+        self.hot_keys = HotKeys::new(keys)?;
+        Ok(())
+    }
+    
+    /// Get the registered hot keys
+    pub fn hot_keys(&self) -> &HotKeys {
+        &self.hot_keys
+    }
+    
+    /// Record element counts, id ranges and hot key presence of every data blob
+    ///
+    /// Decodes each block's element ids, and tag keys if hot keys are
+    /// registered with `set_hot_keys`, but not tag values or coordinates.
+    /// Afterwards filters on element types, id ranges or hot keys skip blobs
+    /// without decoding them (see `ElementFilter::explain`), and `save_index`
+    /// keeps the result for later opens.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{IndexedReader, NodeId};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = IndexedReader::new(File::open("map.osm.pbf")?)?;
+    /// reader.build_deep_index()?;
+    /// println!("{:?}", reader.find_blobs_for_id_range(NodeId(1000), NodeId(2000)));
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn build_deep_index(&mut self) -> Result<()> {
+        self.build_deep_index_with(&PbfBlockCodec)
+    }
+
+    /// `build_deep_index` over blocks in the wire format of `decoder`
+    pub(crate) fn build_deep_index_with(&mut self, decoder: &dyn BlockDecoder) -> Result<()> {
+        self.finish_index()?;
+        for index in 0..self.blob_index.len() {
+            if !self.blob_index[index].blob_type.holds_elements() {
+                continue;
+            }
+            let Some(blob) = self.read_blob_by_index(index)? else {
+                continue;
+            };
+            summarize_data_blob(&blob, &mut self.blob_index[index], decoder, &self.hot_keys)?;
+        }
+        Ok(())
+    }
+    
+    /// Compute bounding boxes for every blob, way and relation (deep index pass)
+    ///
+    /// Runs a node location pass followed by a pass computing each way's extent
+    /// from its node locations and each relation's extent from its node and way
+    /// members. The blob bbox covers the full extent of its ways and relations, so
+    /// `find_blobs_for_bbox` keeps a blob whose way crosses the queried area even
+    /// when none of the way's nodes live in that blob. Nested relation members are
+    /// not followed.
+    ///
+    /// With `cache_elements`, per-element bboxes are retained for `way_bbox` and
+    /// `relation_bbox` lookups. Presence of the hot keys registered with
+    /// `set_hot_keys` is recorded for each data blob.
+    ///
+    /// Node locations are kept in a `SparseLocations`; use
+    /// `build_bbox_index_using` to pick another store for large files.
+    pub fn build_bbox_index(&mut self, cache_elements: bool) -> Result<()> {
+        self.build_bbox_index_using(cache_elements, &mut SparseLocations::new())
+    }
+
+    /// `build_bbox_index` with node locations kept in `locations`
+    ///
+    /// Only one blob's elements are held at a time; besides the location store,
+    /// memory goes to the way extents, which relations are resolved against.
+    /// Blobs holding relations are decoded a second time once every way is known.
+    pub fn build_bbox_index_using<S: NodeLocationStore>(&mut self, cache_elements: bool, locations: &mut S) -> Result<()> {
+        self.build_bbox_index_with(cache_elements, locations, &PbfBlockCodec)
+    }
+
+    /// `build_bbox_index_using` over blocks in the wire format of `decoder`
+    pub(crate) fn build_bbox_index_with<S: NodeLocationStore>(&mut self, cache_elements: bool, locations: &mut S, decoder: &dyn BlockDecoder) -> Result<()> {
+        self.finish_index()?;
+        // Pass 1: node locations
+        for index in 0..self.blob_index.len() {
+            for element in self.read_elements_for_index(index, decoder)? {
+                if let OsmElement::Node(node) = element {
+                    locations.set(node.id.0, node.location)?;
+                }
+            }
+        }
+        locations.finish()?;
+        
+        // Pass 2: way extents, and the extents of blobs without relations
+        let mut way_bboxes = HashMap::new();
+        let mut relation_blobs = Vec::new();
+        for index in 0..self.blob_index.len() {
+            let (strings, elements) = match self.read_blob_by_index(index)? {
+                Some(blob) => decode_elements(&blob, GroupPolicy::default(), decoder)?,
+                None => (StringTable::default(), Vec::new()),
+            };
+            if !self.hot_keys.is_empty() && self.blob_index[index].blob_type.holds_elements() {
+                self.blob_index[index].key_presence = Some(self.hot_keys.presence(&strings, &elements));
+            }
+            let mut blob_bbox = None;
+            let mut has_relations = false;
+            for element in &elements {
+                match element {
+                    OsmElement::Node(node) => BoundingBox::extend_option(&mut blob_bbox, node.location),
+                    OsmElement::Way(way) => {
+                        if let Some(bbox) = way_bbox_from_locations(&way.refs, locations) {
+                            BoundingBox::merge_option(&mut blob_bbox, &bbox);
+                            way_bboxes.insert(way.id, bbox);
+                        }
+                    }
+                    OsmElement::Relation(_) => has_relations = true,
+                    OsmElement::ChangeSet(_) => {}
+                }
+            }
+            if has_relations {
+                relation_blobs.push((index, blob_bbox));
+            } else {
+                self.blob_index[index].bbox = blob_bbox;
+            }
+        }
+        
+        // Pass 3: relation extents, now that every way is known
+        let mut relation_bboxes = HashMap::new();
+        for (index, mut blob_bbox) in relation_blobs {
+            for element in self.read_elements_for_index(index, decoder)? {
+                let OsmElement::Relation(relation) = element else {
+                    continue;
+                };
+                let mut relation_bbox = None;
+                for member in relation.members() {
+                    if let Some(location) = member.node_id().and_then(|id| locations.get(id.0)) {
+                        BoundingBox::extend_option(&mut relation_bbox, location);
+                    } else if let Some(bbox) = member.way_id().and_then(|id| way_bboxes.get(&id)) {
+                        BoundingBox::merge_option(&mut relation_bbox, bbox);
+                    }
+                }
+                if let Some(bbox) = relation_bbox {
+                    BoundingBox::merge_option(&mut blob_bbox, &bbox);
+                    if cache_elements {
+                        relation_bboxes.insert(relation.id, bbox);
+                    }
+                }
+            }
+            self.blob_index[index].bbox = blob_bbox;
+        }
+        
+        if cache_elements {
+            self.way_bboxes = way_bboxes;
+            self.relation_bboxes = relation_bboxes;
+        }
+        
+        Ok(())
+    }
+    
+    /// Decode the elements of the blob at the given index (empty for missing blobs)
+    fn read_elements_for_index(&mut self, index: usize, decoder: &dyn BlockDecoder) -> Result<Vec<OsmElement>> {
+        match self.read_blob_by_index(index)? {
+            Some(blob) => Ok(decode_elements(&blob, GroupPolicy::default(), decoder)?.1),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// Get the cached bounding box of a way (requires `build_bbox_index(true)`)
+    pub fn way_bbox(&self, way_id: WayId) -> Option<&BoundingBox> {
+        self.way_bboxes.get(&way_id)
+    }
+    
+    /// Get the cached bounding box of a relation (requires `build_bbox_index(true)`)
+    pub fn relation_bbox(&self, relation_id: RelationId) -> Option<&BoundingBox> {
+        self.relation_bboxes.get(&relation_id)
+    }
+    
+    /// Find blobs that potentially contain elements intersecting the given bbox
+    pub fn find_blobs_for_bbox(&self, bbox: &BoundingBox) -> Vec<usize> {
         self.blob_index
             .iter()
             .enumerate()
-            .filter_map(|(index, blob)| {
-                if let Some((blob_min, blob_max)) = blob.id_range {
-                    // Check if ranges overlap
-                    if blob_min <= max_id && blob_max >= min_id {
-                        Some(index)
-                    } else {
-                        None
-                    }
-                } else {
-                    // If we don't know the range, include it to be safe
-                    Some(index)
-                }
+            .filter_map(|(index, blob)| match &blob.bbox {
+                Some(blob_bbox) if !blob_bbox.intersects(bbox) => None,
+                // If we don't know the extent, include it to be safe
+                _ => Some(index),
             })
             .collect()
     }
+    
+    /// Find blobs that potentially contain elements in the given ID range
+    ///
+    /// Only ids of the range's element type count, so a node id range skips
+    /// blobs of ways with the same ids.
+    pub fn find_blobs_for_id_range<I: ElementId>(&self, min_id: I, max_id: I) -> Vec<usize> {
+        self.blob_index
+            .iter()
+            .enumerate()
+            // If we don't know the ranges, include the blob to be safe
+            .filter(|(_, blob)| blob.id_ranges.is_none_or(|ranges| ranges.overlaps(min_id, max_id)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Offset just past a blob's frame
+pub(crate) fn frame_end(blob_index: &BlobIndex) -> u64 {
+    blob_index.offset.saturating_add(4).saturating_add(blob_index.header_size).saturating_add(blob_index.size)
+}
+
+/// Compute a way's extent from its delta-encoded node refs
+fn way_bbox_from_locations(refs: &[i64], locations: &impl NodeLocationStore) -> Option<BoundingBox> {
+    let mut bbox = None;
+    let mut node_id = 0i64;
+    for delta in refs {
+        node_id += delta;
+        if let Some(location) = locations.get(node_id) {
+            BoundingBox::extend_option(&mut bbox, location);
+        }
+    }
+    bbox
 }
 
 /// Iterator for streaming filtered blobs
@@ -328,14 +1221,20 @@ impl<'a, R: Read + Seek> Iterator for FilteredBlobIterator<'a, R> {
             // Apply filter logic
             let should_include = match blob_index.blob_type {
                 BlobType::OSMHeader => true, // Always include headers
-                BlobType::OSMData => {
-                    // Check if this blob might contain elements we're interested in 
-                    (self.filter.include_nodes && blob_index.element_counts.nodes > 0) ||
-                        (self.filter.include_ways && blob_index.element_counts.ways > 0) ||
-                        (self.filter.include_relations && blob_index.element_counts.relations > 0) ||
-                        (self.filter.include_changesets && blob_index.element_counts.changesets > 0)
+                BlobType::OSMData | BlobType::OSMChange => {
+                    // Check if this blob might contain elements we're interested in;
+                    // without a deep index the counts are unknown
+                    let counts = &blob_index.element_counts;
+                    let wanted_type = counts.is_unknown()
+                        || (self.filter.include_nodes && counts.nodes > 0)
+                        || (self.filter.include_ways && counts.ways > 0)
+                        || (self.filter.include_relations && counts.relations > 0)
+                        || (self.filter.include_changesets && counts.changesets > 0);
+                    let wanted_ids = self.filter.id_ranges.is_empty() || self.filter.may_match_ids(blob_index);
+                    wanted_type && wanted_ids
                 }
-                BlobType::Unknown(_) => false, // Skip unknown types by default
+                // Dictionaries are loaded by the reader; unknown types are skipped
+                BlobType::ZstdDictionary | BlobType::Unknown(_) => false,
             };
             
             if should_include {
@@ -357,16 +1256,26 @@ pub struct IndexStatistics {
     pub total_blobs: u64,
     pub header_blobs: u64,
     pub data_blobs: u64,
+    /// `OSMChange` blobs, as in .osc.pbf files
+    pub change_blobs: u64,
+    /// Blobs of other types, zstd dictionaries included
     pub unknown_blobs: u64,
     pub total_nodes: u64,
     pub total_ways: u64,
     pub total_relations: u64,
     pub total_changesets: u64,
+    /// Bytes of damaged regions skipped while indexing
+    pub bytes_skipped: u64,
+    /// Times indexing resumed after a damaged region
+    pub resyncs: u64,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::io::geometry::DenseLocations;
+    use crate::io::plan::PruneReason;
     use std::io::Cursor;
     
     #[test]
@@ -403,6 +1312,411 @@ mod tests {
         assert!(reader.header_blob().is_none());
     }
     
+    /// Cursor wrapper that fails the first `failures` reads with a transient error
+    struct FlakyCursor {
+        inner: Cursor<Vec<u8>>,
+        failures: u32,
+    }
+    
+    impl Read for FlakyCursor {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+            }
+            self.inner.read(buf)
+        }
+    }
+    
+    impl Seek for FlakyCursor {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+    
+    #[test]
+    fn test_retry_policy_survives_transient_errors() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(1).grid_size(4).write_to(&mut data).unwrap();
+        let flaky = FlakyCursor { inner: Cursor::new(data), failures: 2 };
+        let policy = RetryPolicy::network_filesystem()
+            .with_backoff(std::time::Duration::ZERO, std::time::Duration::ZERO);
+        
+        let mut reader = IndexedReader::with_retry_policy(flaky, policy).unwrap();
+        assert_eq!(reader.blob_count() as u64, planet.blobs);
+        assert_eq!(reader.retries_performed(), 2);
+        assert!(reader.read_blob_by_index(1).unwrap().is_some());
+    }
+    
+    #[test]
+    fn test_way_bbox_from_locations() {
+        let mut locations = SparseLocations::new();
+        locations.set(10, LatLon::from_raw(100, 200)).unwrap();
+        locations.set(12, LatLon::from_raw(-50, 400)).unwrap();
+        
+        // Delta-encoded refs 10, 11 (unknown), 12
+        let bbox = way_bbox_from_locations(&[10, 1, 1], &locations).unwrap();
+        assert_eq!(bbox.min_lat.0, -50);
+        assert_eq!(bbox.max_lat.0, 100);
+        assert_eq!(bbox.min_lon.0, 200);
+        assert_eq!(bbox.max_lon.0, 400);
+        
+        assert!(way_bbox_from_locations(&[99], &locations).is_none());
+    }
+    
+    #[test]
+    fn test_find_blobs_for_bbox() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(8).block_size(10).write_to(&mut data).unwrap();
+        let mut reader = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        reader.build_bbox_index(true).unwrap();
+        let data_blobs: Vec<_> = (0..reader.blob_count()).filter(|&i| reader.blob_index[i].blob_type == BlobType::OSMData).collect();
+        assert!(data_blobs.iter().all(|&i| reader.blob_index[i].bbox.is_some()));
+        
+        // Way 1 runs along the first grid row, whose nodes fill the first node blob
+        let way = *reader.way_bbox(WayId(1)).unwrap();
+        let found = reader.find_blobs_for_bbox(&way);
+        assert!(found.contains(&1));
+        assert!(found.len() < data_blobs.len());
+        let far = BoundingBox::from_point(LatLon::try_from_degrees(-10.0, -10.0).unwrap());
+        // Only the header blob, which has no extent, is kept for a box outside the grid
+        assert_eq!(reader.find_blobs_for_bbox(&far), vec![0]);
+        assert!(reader.way_bbox(WayId(1_000)).is_none());
+        assert!(reader.relation_bbox(RelationId(1)).is_some());
+        
+        // The location store only changes where locations are kept
+        let mut dense = IndexedReader::new(Cursor::new(data)).unwrap();
+        dense.build_bbox_index_using(true, &mut DenseLocations::new()).unwrap();
+        let bboxes = |reader: &IndexedReader<_>| reader.blob_index.iter().map(|b| b.bbox).collect::<Vec<_>>();
+        assert_eq!(bboxes(&dense), bboxes(&reader));
+        assert_eq!(dense.way_bbox(WayId(1)), Some(&way));
+    }
+    
+    #[test]
+    fn test_offsets_beyond_4gib() {
+        use crate::io::wire::WireWriter;
+        use std::io::Write;
+        
+        let frame_header = |blob_type: BlobType, datasize: i64| {
+            let mut header = WireWriter::new();
+            header.string(1, blob_type.as_str());
+            header.int64(3, datasize);
+            let header = header.into_bytes();
+            let mut frame = (header.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(&header);
+            frame
+        };
+        
+        let raw_blob = |payload: &[u8]| {
+            let mut blob = WireWriter::new();
+            blob.bytes(1, payload);
+            blob.into_bytes()
+        };
+        
+        // Sparse file: an empty header blob, two data blobs of 2 GiB each,
+        // then a small blob past 4 GiB
+        let mut file = tempfile::tempfile().unwrap();
+        let header_blob = raw_blob(&[]);
+        file.write_all(&frame_header(BlobType::OSMHeader, header_blob.len() as i64)).unwrap();
+        file.write_all(&header_blob).unwrap();
+        let mut offset = file.stream_position().unwrap();
+        for _ in 0..2 {
+            let header = frame_header(BlobType::OSMData, i32::MAX as i64);
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(&header).unwrap();
+            offset += header.len() as u64 + i32::MAX as u64;
+        }
+        let blob = raw_blob(&[1, 2, 3]);
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&frame_header(BlobType::OSMData, blob.len() as i64)).unwrap();
+        file.write_all(&blob).unwrap();
+        
+        let mut reader = IndexedReader::new(file).unwrap();
+        assert_eq!(reader.blob_count(), 4);
+        assert_eq!(reader.get_blob_index(1).unwrap().size, i32::MAX as u64);
+        assert_eq!(reader.get_blob_index(3).unwrap().offset, offset);
+        assert!(offset > 1 << 32);
+        
+        let blob = reader.read_blob_by_index(3).unwrap().unwrap();
+        assert_eq!(blob.offset(), offset);
+        assert_eq!(blob.raw_size(), 3);
+        
+        // Oversized blobs are rejected before allocating their buffer
+        assert!(matches!(reader.read_blob_by_index(1), Err(BlobError::MessageTooLarge { .. })));
+    }
+    
+    #[test]
+    fn test_file_must_start_with_header() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(2).grid_size(4).write_to(&mut data).unwrap();
+        
+        // A damaged first frame is an error rather than skipped
+        let mut damaged = data.clone();
+        damaged[4..8].copy_from_slice(&[0xff; 4]);
+        assert!(IndexedReader::new(Cursor::new(damaged)).is_err());
+        
+        // Files must start with the header blob
+        let offsets = frame_offsets(&IndexedReader::new(Cursor::new(data.clone())).unwrap());
+        let without_header = data[offsets[1] as usize..].to_vec();
+        assert!(matches!(IndexedReader::new(Cursor::new(without_header)), Err(BlobError::InvalidFormat(_))));
+        
+        // Frames without a BlobHeader aren't read as raw payloads
+        let mut unframed = 3u32.to_be_bytes().to_vec();
+        unframed.extend_from_slice(&[1, 2, 3]);
+        assert!(IndexedReader::new(Cursor::new(unframed)).is_err());
+        
+        // The header frame on its own is a valid, empty file
+        let mut reader = IndexedReader::new(Cursor::new(data[..offsets[1] as usize].to_vec())).unwrap();
+        assert_eq!(reader.blob_count(), 1);
+        assert!(matches!(reader.read_blob_by_index(0).unwrap().unwrap().header.blob_type, BlobType::OSMHeader));
+    }
+    
+    #[test]
+    fn test_pbf_frames_indexed() {
+        let mut data = Vec::new();
+        let stats = crate::synthetic::PlanetBuilder::new(5)
+            .grid_size(20)
+            .block_size(100)
+            .write_to(&mut data)
+            .unwrap();
+        
+        let mut reader = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        assert_eq!(reader.blob_count() as u64, stats.blobs);
+        assert!(reader.header_blob().is_some());
+        assert!(matches!(reader.get_blob_index(1).unwrap().blob_type, BlobType::OSMData));
+        
+        let last = reader.get_blob_index(reader.blob_count() - 1).unwrap().clone();
+        assert_eq!(last.offset + 4 + last.header_size + last.size, data.len() as u64);
+        
+        let blob = reader.read_blob_by_index(1).unwrap().unwrap();
+        assert!(matches!(blob.header.blob_type, BlobType::OSMData));
+        assert_eq!(blob.offset(), reader.get_blob_index(1).unwrap().offset);
+        
+        // Later reads reuse the scratch buffer of the header read on open
+        reader.read_blob_by_index(2).unwrap().unwrap();
+        let stats = reader.buffer_pool().stats();
+        assert_eq!(stats.hits + stats.misses, 3);
+        assert_eq!(stats.pooled_buffers, 1);
+    }
+    
+    #[test]
+    fn test_required_features_policy() {
+        use crate::blocks::header_block::HeaderBlock;
+        use crate::io::writer::PbfWriter;
+        
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock {
+            required_features: vec!["OsmSchema-V0.6".into(), "Sort.Made-Up".into()],
+            ..Default::default()
+        }).unwrap();
+        let data = writer.into_inner();
+        
+        match IndexedReader::new(Cursor::new(data.clone())) {
+            Err(BlobError::UnsupportedFeature { features }) => assert_eq!(features, vec!["Sort.Made-Up".to_string()]),
+            other => panic!("expected UnsupportedFeature, got {:?}", other.map(|r| r.blob_count())),
+        }
+        
+        let reader = IndexedReader::with_feature_policy(Cursor::new(data), FeaturePolicy::Warn).unwrap();
+        assert_eq!(reader.required_features(), ["OsmSchema-V0.6", "Sort.Made-Up"]);
+    }
+    
+    #[test]
+    fn test_replication_header() {
+        use crate::blocks::header_block::{HeaderBlock, OsmosisReplicationTimestamp, OsmosisSequenceNumber};
+        use crate::io::writer::PbfWriter;
+        
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock {
+            osmosis_replication_timestamp: OsmosisReplicationTimestamp::new(1_709_528_767),
+            osmosis_replication_sequence_number: OsmosisSequenceNumber::new(6_149_021),
+            osmosis_replication_base_url: Some("https://planet.openstreetmap.org/replication/minute"),
+            ..Default::default()
+        }).unwrap();
+        let reader = IndexedReader::new(Cursor::new(writer.into_inner())).unwrap();
+        let replication = reader.replication();
+        assert_eq!(replication.sequence_number.map(|seq| seq.as_seq()), Some(6_149_021));
+        assert_eq!(replication.timestamp.map(|timestamp| timestamp.as_secs()), Some(1_709_528_767));
+        assert_eq!(replication.base_url.as_deref(), Some("https://planet.openstreetmap.org/replication/minute"));
+    }
+    
+    #[test]
+    fn test_saved_index_round_trip() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(6).block_size(20).write_to(&mut data).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("map.osm.pbf.idx");
+        
+        let mut reader = IndexedReader::open_with_index(Cursor::new(data.clone()), &path).unwrap();
+        assert!(!reader.index_reused());
+        // Deep index data survives the round trip
+        reader.blob_index[1].id_ranges = Some(BlobIdRanges { node: Some((NodeId(1), NodeId(20))), ..Default::default() });
+        reader.blob_index[1].element_counts.nodes = 20;
+        reader.save_index(&path).unwrap();
+        
+        let reused = IndexedReader::open_with_index(Cursor::new(data.clone()), &path).unwrap();
+        assert!(reused.index_reused());
+        assert_eq!(reused.index(), reader.index());
+        assert_eq!(reused.header_blob(), reader.header_blob());
+        assert_eq!(reused.get_blob_index(3), reader.get_blob_index(3));
+        
+        // A changed file or a damaged index file means scanning again
+        let mut appended = data.clone();
+        appended.extend_from_slice(&data[data.len() - 100..]);
+        assert!(!IndexedReader::open_with_index(Cursor::new(appended), &path).unwrap().index_reused());
+        let mut edited = data.clone();
+        *edited.last_mut().unwrap() ^= 1;
+        assert!(!IndexedReader::open_with_index(Cursor::new(edited), &path).unwrap().index_reused());
+        
+        std::fs::write(&path, b"OPBF-IDX\x01").unwrap();
+        let rebuilt = IndexedReader::open_with_index(Cursor::new(data), &path).unwrap();
+        assert!(!rebuilt.index_reused());
+        assert_eq!(rebuilt.blob_count(), reader.blob_count());
+    }
+    
+    #[test]
+    fn test_deep_index_records_counts_and_id_ranges() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(7).grid_size(8).block_size(20).relation_count(3).write_to(&mut data).unwrap();
+        let mut reader = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        let data_blobs = reader.statistics().data_blobs as usize;
+        let streamed = |reader: &mut IndexedReader<Cursor<Vec<u8>>>, filter: &ElementFilter| {
+            reader.stream_filtered(filter).filter(|blob| blob.as_ref().unwrap().blob_type() == &BlobType::OSMData).count()
+        };
+        // Unknown counts can't prune anything
+        assert_eq!(streamed(&mut reader, &ElementFilter::nodes_only()), data_blobs);
+        
+        reader.build_deep_index().unwrap();
+        let stats = reader.statistics();
+        assert_eq!((stats.total_nodes, stats.total_ways, stats.total_relations), (planet.nodes, planet.ways, planet.relations));
+        assert!(reader.index().iter().filter(|blob| blob.blob_type == BlobType::OSMData).all(|blob| blob.id_ranges.is_some()));
+        
+        let node_blobs = reader.index().iter().filter(|blob| blob.element_counts.nodes > 0).count();
+        assert!(node_blobs > 0 && node_blobs < data_blobs);
+        assert_eq!(streamed(&mut reader, &ElementFilter::nodes_only()), node_blobs);
+        
+        let (min, max) = reader.index()[1].id_ranges.unwrap().node.unwrap();
+        let in_range = reader.find_blobs_for_id_range(min, max);
+        assert!(in_range.contains(&1) && in_range.len() < reader.blob_count());
+        let filtered = ElementFilter::nodes_only().with_id_range(min, max);
+        assert_eq!(streamed(&mut reader, &filtered), 1);
+        
+        // Way ids with the same numbers select ways, never nodes
+        let (min, max) = (WayId(min.0), WayId(max.0));
+        assert!(!reader.find_blobs_for_id_range(min, max).contains(&1));
+        assert_eq!(streamed(&mut reader, &ElementFilter::nodes_only().with_id_range(min, max)), node_blobs);
+        // and only drop blobs of ways outside the range
+        let way_blobs = reader.index().iter().filter(|blob| blob.element_counts.ways > 0).count();
+        assert!(way_blobs > 0);
+        assert_eq!(streamed(&mut reader, &ElementFilter::all().with_id_range(WayId(1000), WayId(2000))), data_blobs - way_blobs);
+    }
+    
+    #[test]
+    fn test_deep_index_records_hot_keys() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(7).grid_size(12).block_size(30).relation_count(3).write_to(&mut data).unwrap();
+        let keys = ["amenity", "highway", "type", "building"];
+        let mut reader = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        reader.set_hot_keys(&keys).unwrap();
+        reader.build_deep_index().unwrap();
+        let presence = |reader: &IndexedReader<Cursor<Vec<u8>>>| -> Vec<_> {
+            reader.index().iter()
+                .map(|blob| blob.key_presence.as_ref().map(|presence| keys.map(|key| presence.contains(key).unwrap())))
+                .collect()
+        };
+        let deep = presence(&reader);
+        assert!(deep[1..].iter().all(Option::is_some));
+        assert!(deep.iter().flatten().any(|found| found[0]) && deep.iter().flatten().any(|found| !found[1]));
+        assert!(deep.iter().flatten().all(|found| !found[3]));
+        
+        // Same as recorded from the decoded elements
+        let mut decoded = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        decoded.set_hot_keys(&keys).unwrap();
+        decoded.build_bbox_index(false).unwrap();
+        assert_eq!(presence(&decoded), deep);
+        
+        let filter = ElementFilter::all().with_tag_key("highway".to_string());
+        let pruned = filter.explain(reader.index()).pruned_by(PruneReason::TagKeys);
+        assert!(pruned > 0);
+        
+        // Saved with the index, and the keys are registered again on open
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("map.osm.pbf.idx");
+        reader.save_index(&path).unwrap();
+        let reused = IndexedReader::open_with_index(Cursor::new(data), &path).unwrap();
+        assert!(reused.index_reused());
+        assert_eq!(reused.hot_keys(), reader.hot_keys());
+        assert_eq!(presence(&reused), deep);
+        assert_eq!(filter.explain(reused.index()).pruned_by(PruneReason::TagKeys), pruned);
+    }
+    
+    fn frame_offsets(reader: &IndexedReader<Cursor<Vec<u8>>>) -> Vec<u64> {
+        (0..reader.blob_count()).map(|i| reader.get_blob_index(i).unwrap().offset).collect()
+    }
+    
+    #[test]
+    fn test_resync_after_corrupted_length_prefix() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(20).block_size(100).write_to(&mut data).unwrap();
+        let intact = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        let offsets = frame_offsets(&intact);
+        assert!(offsets.len() > 4);
+        
+        let damaged_at = offsets[2] as usize;
+        data[damaged_at..damaged_at + 4].copy_from_slice(&[0xff; 4]);
+        let reader = IndexedReader::new(Cursor::new(data)).unwrap();
+        
+        let mut expected = offsets.clone();
+        expected.remove(2);
+        assert_eq!(frame_offsets(&reader), expected);
+        let stats = reader.statistics();
+        assert_eq!(stats.bytes_skipped, offsets[3] - offsets[2]);
+        assert_eq!(stats.resyncs, 1);
+    }
+    
+    #[test]
+    fn test_resync_skips_garbage_to_end() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(10).write_to(&mut data).unwrap();
+        let blobs = IndexedReader::new(Cursor::new(data.clone())).unwrap().blob_count();
+        
+        // A trailing frame whose header is garbage
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x10]);
+        data.extend(std::iter::repeat_n(0xab, 100));
+        let reader = IndexedReader::new(Cursor::new(data)).unwrap();
+        
+        assert_eq!(reader.blob_count(), blobs);
+        assert_eq!(reader.bytes_skipped(), 104);
+        assert_eq!(reader.statistics().resyncs, 0);
+    }
+    
+    #[test]
+    fn test_raw_blob_copy() {
+        use crate::io::writer::RawBlobWriter;
+        
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(9).grid_size(10).write_to(&mut data).unwrap();
+        let mut reader = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        
+        let mut writer = RawBlobWriter::new(Vec::new());
+        for index in 0..reader.blob_count() {
+            let (header, blob) = reader.read_raw_blob(index).unwrap();
+            writer.write_raw(&header, &blob).unwrap();
+        }
+        assert_eq!(writer.into_inner(), data);
+        
+        // The header frame and the last data frame still make a valid file
+        let last = reader.blob_count() - 1;
+        let mut writer = RawBlobWriter::new(Vec::new());
+        for index in [0, last] {
+            let (header, blob) = reader.read_raw_blob(index).unwrap();
+            writer.write_raw(&header, &blob).unwrap();
+        }
+        let mut subset = IndexedReader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(subset.blob_count(), 2);
+        assert_eq!(subset.read_raw_blob(1).unwrap(), reader.read_raw_blob(last).unwrap());
+        assert!(reader.read_raw_blob(last + 1).is_err());
+    }
+    
     #[test]
     fn test_element_counts() {
         let counts = ElementCounts {
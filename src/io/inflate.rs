@@ -0,0 +1,148 @@
+use std::io::Read;
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use crate::io::blob::{BlobError, Result};
+#[cfg(feature = "zstd")]
+use crate::io::zstd_dictionary::{frame_dictionary_id, ZstdDictionary};
+
+/// Inflate a zlib stream (RFC 1950) holding at most `max_size` bytes
+///
+/// The Adler-32 trailer is checked. Preset dictionaries aren't supported; PBF
+/// writers never use them.
+pub(crate) fn zlib_decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    read_limited(ZlibDecoder::new(data), max_size, "zlib")
+}
+
+/// Inflate a gzip stream (RFC 1952) holding at most `max_size` bytes in total
+///
+/// Concatenated members are inflated one after another, as `gzip -d` does,
+/// and each member's CRC-32 and length trailer is checked.
+pub(crate) fn gzip_decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    read_limited(MultiGzDecoder::new(data), max_size, "gzip")
+}
+
+/// Decode an LZ4 block, as written by libosmium, of exactly `raw_size` bytes
+#[cfg(feature = "lz4")]
+pub(crate) fn lz4_decompress(data: &[u8], raw_size: usize) -> Result<Vec<u8>> {
+    lz4_flex::block::decompress(data, raw_size).map_err(|e| BlobError::Compression(format!("Corrupt lz4 data: {e}")))
+}
+
+/// Decode a zstd frame holding at most `max_size` bytes
+///
+/// A frame compressed with a dictionary needs that `dictionary`.
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd_decompress(data: &[u8], max_size: usize, dictionary: Option<&ZstdDictionary>) -> Result<Vec<u8>> {
+    use ruzstd::decoding::{Dictionary, FrameDecoder, StreamingDecoder};
+
+    let mut frame_decoder = FrameDecoder::new();
+    if let Some(id) = frame_dictionary_id(data) {
+        let dictionary = dictionary.filter(|dictionary| dictionary.id() == id).ok_or_else(|| {
+            BlobError::Compression(format!("zstd data needs dictionary {id}, which the file doesn't hold"))
+        })?;
+        let dictionary = Dictionary::decode_dict(dictionary.as_bytes())
+            .map_err(|e| BlobError::Compression(format!("Corrupt zstd dictionary: {e}")))?;
+        frame_decoder.add_dict(dictionary)
+            .map_err(|e| BlobError::Compression(format!("Corrupt zstd dictionary: {e}")))?;
+    }
+    let decoder = StreamingDecoder::new_with_decoder(data, frame_decoder)
+        .map_err(|e| BlobError::Compression(format!("Corrupt zstd data: {e}")))?;
+    read_limited(decoder, max_size, "zstd")
+}
+
+/// Read a decoder to the end, stopping as soon as it yields more than `max_size` bytes
+fn read_limited(decoder: impl Read, max_size: usize, format: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    decoder
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| BlobError::Compression(format!("Corrupt {format} data: {e}")))?;
+    if out.len() > max_size {
+        return Err(BlobError::Compression(format!("Corrupt {format} data: inflates to more than {max_size} bytes")));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_block_types() {
+        // Stored block
+        let stored = hex("7801010c00f3ff73746f72656420626c6f636b1f8004bd");
+        assert_eq!(zlib_decompress(&stored, 100).unwrap(), b"stored block");
+
+        // Fixed Huffman codes with back references
+        let fixed = hex(
+            "78dacbc84ccf284facb42d4a2dce4c49cd2bc94cccb1ce4bcc4db5f54dcccc53082e294a4d2db1ce185534aa6854d1a8a2\
+             5145835511909f975952699b9c98960a00c9043cb2",
+        );
+        let mut expected = b"highway=residential;name=Main Street;".repeat(40);
+        expected.extend_from_slice(b"amenity=cafe");
+        assert_eq!(zlib_decompress(&fixed, 2000).unwrap(), expected);
+
+        // Dynamic Huffman codes
+        let dynamic = hex(
+            "78dad58dd915403014055bb91a70ec4b173e341004b13d428254efb5e17b66ced4a3c461543ba3d1746fe8e9c164d6fd04\
+             59a971315e847bd1d1e0a312ecad2f1a966e758de895958c9cdcb0a8c390e676383d0461142769961725eadf0f3e7be762fb",
+        );
+        let expected = b"The quick brown fox jumps over the lazy dog. Pack my box with five dozen liquor jugs! 0123456789 "
+            .repeat(3);
+        assert_eq!(zlib_decompress(&dynamic, 1000).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_corrupt_streams() {
+        let data = hex("789ccb2fce050002a30150");
+        assert_eq!(zlib_decompress(&data, 3).unwrap(), b"osm");
+
+        assert!(zlib_decompress(&data, 2).is_err());
+        assert!(zlib_decompress(&data[..data.len() - 1], 3).is_err());
+        assert!(zlib_decompress(&data[..5], 3).is_err());
+        assert!(zlib_decompress(b"\x78\x9d", 3).is_err());
+
+        let mut bad_checksum = data.clone();
+        *bad_checksum.last_mut().unwrap() ^= 1;
+        assert!(zlib_decompress(&bad_checksum, 3).is_err());
+    }
+
+    #[test]
+    fn test_gzip_members() {
+        // `gzip a.txt` of "osm", with the modification time zeroed
+        let member = hex("1f8b0808000000000003612e74787400cb2fce0500df62db8b03000000");
+        assert_eq!(gzip_decompress(&member, 3).unwrap(), b"osm");
+        assert_eq!(gzip_decompress(&member.repeat(2), 6).unwrap(), b"osmosm");
+        assert!(gzip_decompress(&member.repeat(2), 5).is_err());
+        assert!(gzip_decompress(&member[..member.len() - 1], 3).is_err());
+
+        let mut bad_crc = member.clone();
+        bad_crc[member.len() - 8] ^= 1;
+        assert!(gzip_decompress(&bad_crc, 3).is_err());
+        assert!(gzip_decompress(&hex("789ccb2fce050002a30150"), 3).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_frames() {
+        // `zstd -19` of "highway=residential;" ten times, with a checksum
+        let frame = hex("28b52ffd0468e50000a8686967687761793d7265736964656e7469616c3b68010061474d265554fe28");
+        assert_eq!(zstd_decompress(&frame, 200, None).unwrap(), b"highway=residential;".repeat(10));
+        assert!(zstd_decompress(&frame, 199, None).is_err());
+        assert!(zstd_decompress(&frame[..frame.len() - 2], 200, None).is_err());
+        assert!(zstd_decompress(&hex("789ccb2fce050002a30150"), 3, None).is_err());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_blocks() {
+        // A single sequence of three literals
+        let block = hex("306f736d");
+        assert_eq!(lz4_decompress(&block, 3).unwrap(), b"osm");
+        assert!(lz4_decompress(&block, 2).is_err());
+        assert!(lz4_decompress(&block[..3], 3).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+#[cfg(feature = "tracing")]
+use crate::io::logging::LOG_TARGET;
+
+/// A span entered until drop
+///
+/// Spans and counters are emitted with the `tracing` feature, under the
+/// `osm_pbf` target; without it the functions here do nothing.
+pub(crate) struct SpanGuard {
+    #[cfg(feature = "tracing")]
+    _entered: tracing::span::EnteredSpan,
+}
+
+/// Reading blob `blob_index`, framed at `offset`, from the underlying reader
+pub(crate) fn read_blob(blob_index: usize, offset: u64) -> SpanGuard {
+    #[cfg(feature = "tracing")]
+    {
+        SpanGuard { _entered: tracing::debug_span!(target: LOG_TARGET, "read_blob", blob_index, offset).entered() }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (blob_index, offset);
+        SpanGuard {}
+    }
+}
+
+/// Decompressing and decoding the blob framed at `offset`
+pub(crate) fn decode_blob(offset: u64, raw_size: u32) -> SpanGuard {
+    #[cfg(feature = "tracing")]
+    {
+        SpanGuard { _entered: tracing::debug_span!(target: LOG_TARGET, "decode_blob", offset, raw_size).entered() }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (offset, raw_size);
+        SpanGuard {}
+    }
+}
+
+/// Decoding an uncompressed block message of `len` bytes
+pub(crate) fn decode_block(len: usize) -> SpanGuard {
+    #[cfg(feature = "tracing")]
+    {
+        SpanGuard { _entered: tracing::trace_span!(target: LOG_TARGET, "decode_block", len).entered() }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = len;
+        SpanGuard {}
+    }
+}
+
+/// Scanning the file for blobs
+pub(crate) fn build_index() -> SpanGuard {
+    #[cfg(feature = "tracing")]
+    {
+        SpanGuard { _entered: tracing::info_span!(target: LOG_TARGET, "build_index").entered() }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        SpanGuard {}
+    }
+}
+
+/// Count bytes inflated from compressed blobs
+///
+/// Counters are events with `monotonic_counter.` fields, which metrics
+/// layers such as `tracing-opentelemetry`'s turn into counters.
+pub(crate) fn record_decompressed(bytes: u64) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(target: LOG_TARGET, { monotonic_counter.osm_pbf_bytes_decompressed = bytes }, "decompressed blob");
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = bytes;
+    }
+}
+
+/// Count elements a decoded block handed to the reader
+pub(crate) fn record_elements(count: u64) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(target: LOG_TARGET, { monotonic_counter.osm_pbf_elements_emitted = count }, "decoded block");
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = count;
+    }
+}
+
+/// Report the end of an index scan
+pub(crate) fn index_built(blobs: usize, bytes_skipped: u64) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(target: LOG_TARGET, blobs, bytes_skipped, "indexed {blobs} blobs");
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (blobs, bytes_skipped);
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::io::indexed_reader::{ElementFilter, IndexedReader};
+    use crate::io::reader::Reader;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Span names and counter totals seen
+    #[derive(Default)]
+    struct Seen {
+        spans: Vec<&'static str>,
+        counters: Vec<(&'static str, u64)>,
+    }
+
+    struct Recorder(Arc<Mutex<Seen>>);
+
+    impl Visit for &mut Seen {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name().starts_with("monotonic_counter.") {
+                match self.counters.iter_mut().find(|(name, _)| *name == field.name()) {
+                    Some((_, total)) => *total += value,
+                    None => self.counters.push((field.name(), value)),
+                }
+            }
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == "osm_pbf"
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut seen = self.0.lock().unwrap();
+            seen.spans.push(span.metadata().name());
+            Id::from_u64(seen.spans.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut &mut *self.0.lock().unwrap());
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_spans_and_counters() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(10).block_size(40).write_to(&mut data).unwrap();
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let elements = tracing::subscriber::with_default(Recorder(seen.clone()), || {
+            let mut reader = Reader::new(Cursor::new(data.clone())).unwrap();
+            reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap().elements_processed
+        });
+
+        let seen = seen.lock().unwrap();
+        let count = |name| seen.spans.iter().filter(|span| **span == name).count();
+        assert_eq!(count("build_index"), 1);
+        assert!(count("read_blob") > 1);
+        assert_eq!(count("decode_blob"), count("decode_block"));
+        assert!(count("decode_blob") > 1);
+        let counter = |name| seen.counters.iter().find(|(n, _)| *n == name).map(|(_, total)| *total);
+        assert_eq!(counter("monotonic_counter.osm_pbf_elements_emitted"), Some(elements));
+        // Every data blob is inflated once
+        let mut indexed = IndexedReader::new(Cursor::new(data)).unwrap();
+        let inflated: u64 = (1..indexed.blob_count()).map(|i| indexed.read_blob_by_index(i).unwrap().unwrap().raw_size() as u64).sum();
+        assert_eq!(counter("monotonic_counter.osm_pbf_bytes_decompressed"), Some(inflated));
+    }
+}
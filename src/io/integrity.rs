@@ -0,0 +1,250 @@
+use crate::blocks::primitives::block::PrimitiveBlock;
+use crate::blocks::primitives::member_type::MemberType;
+use crate::io::delta::check_block_deltas;
+use crate::io::ordering::OrderChecker;
+use crate::io::reader::Provenance;
+use crate::io::wire::WireReader;
+
+/// Largest latitude magnitude, in nanodegrees
+const MAX_LAT: i128 = 90_000_000_000;
+/// Largest longitude magnitude, in nanodegrees
+const MAX_LON: i128 = 180_000_000_000;
+
+/// What an `IntegrityIssue` found wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IssueKind {
+    /// Bytes between or after frames that aren't a readable blob, or a blob
+    /// that can't be read back
+    Framing,
+    /// Blob header or message above the size limits
+    Size,
+    /// Blob data that doesn't decompress to its declared size
+    Decompression,
+    /// String table entry that isn't valid UTF-8
+    InvalidUtf8,
+    /// Block message that doesn't decode, or columns of different lengths
+    Decode,
+    /// Delta-encoded column whose running sum overflows
+    DeltaOverflow,
+    /// Element out of order in a file declaring `Sort.Type_then_ID`
+    IdOrder,
+    /// Node location beyond ±90° latitude or ±180° longitude
+    CoordinateBounds,
+}
+
+/// One problem found by `Reader::verify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityIssue {
+    pub kind: IssueKind,
+    /// Byte offset of the frame (or unreadable bytes) the issue is in
+    pub offset: u64,
+    /// Index of the blob the issue is in, if it's in a blob
+    pub blob_index: Option<usize>,
+    pub message: String,
+}
+
+/// Result of `Reader::verify`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Length of the file in bytes
+    pub file_len: u64,
+    /// Blobs read back
+    pub blobs_checked: usize,
+    /// Elements whose ids and locations were checked
+    pub elements_checked: u64,
+    /// All issues found, including those not kept in `issues`
+    pub issue_count: u64,
+    /// The first issues, up to the limit asked for
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// Whether no issue was found
+    pub fn is_ok(&self) -> bool {
+        self.issue_count == 0
+    }
+
+    /// Kept issues of `kind`
+    pub fn issues_of(&self, kind: IssueKind) -> impl Iterator<Item = &IntegrityIssue> {
+        self.issues.iter().filter(move |issue| issue.kind == kind)
+    }
+}
+
+/// Collects the issues of a verification run, blob by blob
+pub(crate) struct IntegrityChecker {
+    max_issues: usize,
+    order: Option<OrderChecker>,
+    pub(crate) report: IntegrityReport,
+}
+
+impl IntegrityChecker {
+    /// Checker keeping `max_issues` issues; `order` checks ids across blocks
+    pub(crate) fn new(max_issues: usize, file_len: u64, order: Option<OrderChecker>) -> Self {
+        Self { max_issues, order, report: IntegrityReport { file_len, ..Default::default() } }
+    }
+
+    pub(crate) fn record(&mut self, kind: IssueKind, offset: u64, blob_index: Option<usize>, message: String) {
+        self.report.issue_count += 1;
+        if self.report.issues.len() < self.max_issues {
+            self.report.issues.push(IntegrityIssue { kind, offset, blob_index, message });
+        }
+    }
+
+    /// Check the string table of an uncompressed block message for invalid
+    /// UTF-8, returning whether it's clean
+    ///
+    /// Wire errors are left to the block decoder to report.
+    pub(crate) fn check_strings(&mut self, message: &[u8], blob_index: usize, offset: u64) -> bool {
+        let mut invalid = None;
+        let mut reader = WireReader::new(message);
+        while let Ok(Some((field, value))) = reader.next_field() {
+            let (1, Ok(table)) = (field, value.as_bytes()) else { continue };
+            let mut strings = WireReader::new(table);
+            let mut position = 0;
+            while let Ok(Some((field, value))) = strings.next_field() {
+                if field != 1 {
+                    continue;
+                }
+                if let Ok(bytes) = value.as_bytes()
+                    && let Err(e) = std::str::from_utf8(bytes)
+                {
+                    invalid.get_or_insert((position, e));
+                }
+                position += 1;
+            }
+        }
+        match invalid {
+            Some((position, e)) => {
+                self.record(IssueKind::InvalidUtf8, offset, Some(blob_index), format!("String table entry {position}: {e}"));
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Check the deltas, ids and node locations of a decoded block
+    pub(crate) fn check_block(&mut self, block: &PrimitiveBlock, blob_index: usize, offset: u64) {
+        if let Err(e) = check_block_deltas(block) {
+            self.record(IssueKind::DeltaOverflow, offset, Some(blob_index), e.to_string());
+            return;
+        }
+        let grid = BlockGrid { granularity: block.granularity as i128, lat_offset: block.lat_offset as i128, lon_offset: block.lon_offset as i128 };
+        let mut ordinal = 0;
+        for group in &block.primitivegroup {
+            for node in &group.nodes {
+                self.check_id(MemberType::Node, node.id.0, blob_index, offset, &mut ordinal);
+                self.check_location(&grid, MemberType::Node, node.id.0, (node.location.lat.0, node.location.lon.0), blob_index, offset);
+            }
+            if let Some(dense) = &group.dense {
+                if dense.lat.len() != dense.id.len() || dense.lon.len() != dense.id.len() {
+                    let message = format!("{} dense ids but {} latitudes and {} longitudes", dense.id.len(), dense.lat.len(), dense.lon.len());
+                    self.record(IssueKind::Decode, offset, Some(blob_index), message);
+                }
+                let (mut id, mut lat, mut lon) = (0i64, 0i64, 0i64);
+                for ((did, dlat), dlon) in dense.id.iter().zip(&dense.lat).zip(&dense.lon) {
+                    (id, lat, lon) = (id + did, lat + dlat, lon + dlon);
+                    self.check_id(MemberType::Node, id, blob_index, offset, &mut ordinal);
+                    self.check_location(&grid, MemberType::Node, id, (lat, lon), blob_index, offset);
+                }
+            }
+            for way in &group.ways {
+                self.check_id(MemberType::Way, way.id.0, blob_index, offset, &mut ordinal);
+                let (mut lat, mut lon) = (0i64, 0i64);
+                for (dlat, dlon) in way.lat.iter().zip(&way.lon) {
+                    (lat, lon) = (lat.saturating_add(*dlat), lon.saturating_add(*dlon));
+                    self.check_location(&grid, MemberType::Way, way.id.0, (lat, lon), blob_index, offset);
+                }
+            }
+            for relation in &group.relations {
+                self.check_id(MemberType::Relation, relation.id.0, blob_index, offset, &mut ordinal);
+            }
+            ordinal += group.changesets.len();
+        }
+    }
+
+    fn check_id(&mut self, kind: MemberType, id: i64, blob_index: usize, offset: u64, ordinal: &mut usize) {
+        self.report.elements_checked += 1;
+        let provenance = Provenance { blob_index, byte_offset: offset, element_ordinal: *ordinal };
+        *ordinal += 1;
+        if let Some(violation) = self.order.as_mut().and_then(|order| order.check_id(kind, id, provenance)) {
+            let message = format!("{kind:?} {id} after {:?} {}", violation.previous_kind, violation.previous_id);
+            self.record(IssueKind::IdOrder, offset, Some(blob_index), message);
+        }
+    }
+
+    /// Check a location in granularity units of an element, a node or a
+    /// node of a way with `LocationsOnWays`
+    fn check_location(&mut self, grid: &BlockGrid, kind: MemberType, id: i64, (lat, lon): (i64, i64), blob_index: usize, offset: u64) {
+        let lat = grid.lat_offset + grid.granularity * lat as i128;
+        let lon = grid.lon_offset + grid.granularity * lon as i128;
+        if lat.abs() > MAX_LAT || lon.abs() > MAX_LON {
+            let message = format!("{kind:?} {id} at {lat}, {lon} nanodegrees");
+            self.record(IssueKind::CoordinateBounds, offset, Some(blob_index), message);
+        }
+    }
+}
+
+/// Coordinate granularity and offsets of a block, widened so converting any
+/// stored coordinate can't overflow
+struct BlockGrid {
+    granularity: i128,
+    lat_offset: i128,
+    lon_offset: i128,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::header_block::HeaderBlock;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::NodeId;
+    use crate::blocks::primitives::prelude::*;
+    use crate::blocks::string_table::StringTable;
+    use crate::io::indexed_reader::IndexedReader;
+    use crate::io::reader::{OsmElement, Reader};
+    use crate::io::writer::{BlockBuffer, PbfWriter};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn node(id: i64, lat: i64) -> OsmElement {
+        OsmElement::Node(Node { id: NodeId(id), keys: vec![], vals: vec![], info: None, location: LatLon::from_raw(lat, 0) })
+    }
+
+    #[test]
+    fn test_verify_reports_issues_with_offsets() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(8).grid_size(10).block_size(30).relation_count(3).write_to(&mut data).unwrap();
+        let report = Reader::new(Cursor::new(data)).unwrap().verify(10).unwrap();
+        assert!(report.is_ok(), "{report:?}");
+        assert!(report.blobs_checked > 2);
+        assert!(report.elements_checked > 100);
+
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock {
+            required_features: vec!["OsmSchema-V0.6".into(), "DenseNodes".into()],
+            optional_features: vec!["Sort.Type_then_ID".into()],
+            ..Default::default()
+        }).unwrap();
+        for elements in [vec![node(1, 0), node(3, 0)], vec![node(2, 0), node(4, 95_000_000_000)]] {
+            let mut block = BlockBuffer::default();
+            for element in &elements {
+                block.push(element, &StringTable::default());
+            }
+            writer.write_primitive_block(&block.finish()).unwrap();
+        }
+        let mut data = writer.into_inner();
+        let clean_len = data.len() as u64;
+        data.extend_from_slice(&[0xff; 7]);
+
+        let second_blob = IndexedReader::new(Cursor::new(data.clone())).unwrap().index()[2].offset;
+        let report = Reader::new(Cursor::new(data)).unwrap().verify(10).unwrap();
+        let found: Vec<_> = report.issues.iter().map(|issue| (issue.kind, issue.offset, issue.blob_index)).collect();
+        assert_eq!(found, vec![
+            (IssueKind::IdOrder, second_blob, Some(2)),
+            (IssueKind::CoordinateBounds, second_blob, Some(2)),
+            (IssueKind::Framing, clean_len, None),
+        ]);
+        assert_eq!(report.elements_checked, 4);
+        assert_eq!(report.issues_of(IssueKind::IdOrder).count(), 1);
+    }
+}
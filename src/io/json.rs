@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+use serde::{Deserialize, Serialize};
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobError, Result};
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, ProcessingStats, Reader};
+
+/// Version of the JSON lines format, written to every line as `v`
+///
+/// Fields are only added or changed together with a new version, so a
+/// pipeline pinned to v1 keeps working across upgrades.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// JSON Schema (draft 2020-12) of one v1 line
+pub const JSON_SCHEMA_V1: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/lokrain/osm-pbf/schema/element-v1.json",
+  "title": "OSM element (osm-pbf JSON lines v1)",
+  "type": "object",
+  "required": ["v", "type", "id", "tags"],
+  "additionalProperties": false,
+  "properties": {
+    "v": { "const": 1 },
+    "type": { "enum": ["node", "way", "relation", "changeset"] },
+    "id": { "type": "integer" },
+    "lat": { "type": "number", "description": "Nodes only, degrees" },
+    "lon": { "type": "number", "description": "Nodes only, degrees" },
+    "tags": { "type": "object", "additionalProperties": { "type": "string" } },
+    "refs": { "type": "array", "items": { "type": "integer" }, "description": "Ways only, node ids in order" },
+    "members": {
+      "type": "array",
+      "description": "Relations only",
+      "items": {
+        "type": "object",
+        "required": ["type", "ref", "role"],
+        "additionalProperties": false,
+        "properties": {
+          "type": { "enum": ["node", "way", "relation"] },
+          "ref": { "type": "integer" },
+          "role": { "type": "string" }
+        }
+      }
+    },
+    "meta": {
+      "type": "object",
+      "description": "Present when the file carries metadata",
+      "required": ["version", "timestamp", "changeset", "uid", "user", "visible"],
+      "additionalProperties": false,
+      "properties": {
+        "version": { "type": "integer" },
+        "timestamp": { "type": "integer", "description": "As stored, in units of the block's date_granularity (seconds by default)" },
+        "changeset": { "type": "integer" },
+        "uid": { "type": "integer" },
+        "user": { "type": "string" },
+        "visible": { "type": "boolean" }
+      }
+    }
+  }
+}
+"##;
+
+/// Element type as written in JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonElementType {
+    Node,
+    Way,
+    Relation,
+    Changeset,
+}
+
+impl From<MemberType> for JsonElementType {
+    fn from(member_type: MemberType) -> Self {
+        match member_type {
+            MemberType::Node => JsonElementType::Node,
+            MemberType::Way => JsonElementType::Way,
+            MemberType::Relation => JsonElementType::Relation,
+        }
+    }
+}
+
+/// Element with tags, roles and user names resolved and deltas decoded,
+/// in the shape of `JSON_SCHEMA_V1`
+///
+/// Unknown fields are rejected when deserializing, so a line of another
+/// version fails loudly instead of losing data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolvedElement {
+    pub v: u32,
+    #[serde(rename = "type")]
+    pub kind: JsonElementType,
+    pub id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    pub tags: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refs: Option<Vec<i64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub members: Option<Vec<ResolvedMember>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ResolvedMeta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolvedMember {
+    #[serde(rename = "type")]
+    pub kind: JsonElementType,
+    #[serde(rename = "ref")]
+    pub id: i64,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolvedMeta {
+    pub version: i32,
+    pub timestamp: i64,
+    pub changeset: i64,
+    pub uid: i32,
+    pub user: String,
+    pub visible: bool,
+}
+
+impl ResolvedElement {
+    /// Resolve an element against the string table of its block
+    pub fn resolve(element: &OsmElement, strings: &StringTable) -> Self {
+        let string = |index: usize| strings.get_string_or_empty(index).to_string();
+        let (kind, id, keys, vals, info) = match element {
+            OsmElement::Node(n) => (JsonElementType::Node, n.id.0, &n.keys, &n.vals, &n.info),
+            OsmElement::Way(w) => (JsonElementType::Way, w.id.0, &w.keys, &w.vals, &w.info),
+            OsmElement::Relation(r) => (JsonElementType::Relation, r.id.0, &r.keys, &r.vals, &r.info),
+            OsmElement::ChangeSet(c) => (JsonElementType::Changeset, c.id.0, &c.keys, &c.vals, &c.info),
+        };
+
+        let mut resolved = Self {
+            v: JSON_SCHEMA_VERSION,
+            kind,
+            id,
+            lat: None,
+            lon: None,
+            tags: keys.iter().zip(vals).map(|(k, v)| (string(*k as usize), string(*v as usize))).collect(),
+            refs: None,
+            members: None,
+            meta: info.as_ref().map(|info| ResolvedMeta {
+                version: info.version,
+                timestamp: info.timestamp,
+                changeset: info.changeset,
+                uid: info.uid,
+                user: string(info.user_sid as usize),
+                visible: info.visible,
+            }),
+        };
+        match element {
+            OsmElement::Node(node) => {
+                let (lat, lon) = node.location.to_degrees();
+                resolved.lat = Some(lat);
+                resolved.lon = Some(lon);
+            }
+            OsmElement::Way(way) => resolved.refs = Some(way.node_ids().collect()),
+            OsmElement::Relation(relation) => {
+                resolved.members = Some(
+                    relation.members_with_roles(strings)
+                        .map(|(member, role)| ResolvedMember {
+                            kind: member.member_type.into(),
+                            id: member.id,
+                            role: role.to_string(),
+                        })
+                        .collect(),
+                );
+            }
+            OsmElement::ChangeSet(_) => {}
+        }
+        resolved
+    }
+}
+
+/// Write the elements matching `filter` as JSON lines, one v1 object per line
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{write_json_lines, ElementFilter, Reader};
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+/// let out = BufWriter::new(File::create("ways.jsonl")?);
+/// write_json_lines(&mut reader, &ElementFilter::ways_only(false), out)?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn write_json_lines<R: Read + Seek, W: Write>(
+    reader: &mut Reader<R>,
+    filter: &ElementFilter,
+    mut writer: W,
+) -> Result<ProcessingStats> {
+    let stats = reader.for_each_filtered_with_strings(filter, |element, strings| {
+        serde_json::to_writer(&mut writer, &ResolvedElement::resolve(&element, strings))
+            .map_err(|e| BlobError::Io(e.into()))?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    })?;
+    writer.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::{RelationId, WayId};
+    use pretty_assertions::assert_eq;
+
+    fn strings() -> StringTable {
+        let mut strings = StringTable::new();
+        for s in ["highway", "residential", "outer", "alice"] {
+            strings.add_string(s.to_string());
+        }
+        strings
+    }
+
+    fn line(element: &OsmElement) -> String {
+        serde_json::to_string(&ResolvedElement::resolve(element, &strings())).unwrap()
+    }
+
+    #[test]
+    fn test_output_shape_is_pinned() {
+        let mut node = Node::new(7, LatLon::from_raw(52_500_000_000, 13_250_000_000));
+        node.info = Some(Info { version: 2, timestamp: 1_600_000_000, changeset: 9, uid: 3, user_sid: 4, visible: true });
+        assert_eq!(
+            line(&OsmElement::Node(node)),
+            r#"{"v":1,"type":"node","id":7,"lat":52.5,"lon":13.25,"tags":{},"meta":{"version":2,"timestamp":1600000000,"changeset":9,"uid":3,"user":"alice","visible":true}}"#
+        );
+
+        let way = Way { id: WayId(8), keys: vec![1], vals: vec![2], info: None, refs: vec![10, 1, -2], lat: vec![], lon: vec![] };
+        assert_eq!(
+            line(&OsmElement::Way(way)),
+            r#"{"v":1,"type":"way","id":8,"tags":{"highway":"residential"},"refs":[10,11,9]}"#
+        );
+
+        let relation = Relation {
+            id: RelationId(9),
+            keys: vec![],
+            vals: vec![],
+            info: None,
+            roles_sid: vec![3, 0],
+            memids: vec![8, -1],
+            types: vec![MemberType::Way, MemberType::Node],
+        };
+        assert_eq!(
+            line(&OsmElement::Relation(relation)),
+            r#"{"v":1,"type":"relation","id":9,"tags":{},"members":[{"type":"way","ref":8,"role":"outer"},{"type":"node","ref":7,"role":""}]}"#
+        );
+    }
+
+    #[test]
+    fn test_schema_matches_output() {
+        let schema: serde_json::Value = serde_json::from_str(JSON_SCHEMA_V1).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        assert_eq!(properties["v"]["const"], JSON_SCHEMA_VERSION);
+
+        let node = Node { info: Some(Info::default()), ..Node::new(1, LatLon::default()) };
+        let way = Way { id: WayId(2), keys: vec![], vals: vec![], info: None, refs: vec![1], lat: vec![], lon: vec![] };
+        let mut keys = std::collections::BTreeSet::new();
+        for element in [OsmElement::Node(node), OsmElement::Way(way)] {
+            let value: serde_json::Value = serde_json::from_str(&line(&element)).unwrap();
+            keys.extend(value.as_object().unwrap().keys().cloned());
+            for required in schema["required"].as_array().unwrap() {
+                assert!(value.get(required.as_str().unwrap()).is_some());
+            }
+        }
+        assert!(keys.iter().all(|key| properties.contains_key(key)));
+
+        // Lines with fields from another version are rejected
+        assert!(serde_json::from_str::<ResolvedElement>(r#"{"v":2,"type":"node","id":1,"tags":{},"extra":0}"#).is_err());
+    }
+
+    #[test]
+    fn test_write_json_lines() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(4).grid_size(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(std::io::Cursor::new(data)).unwrap();
+        let mut out = Vec::new();
+        let stats = write_json_lines(&mut reader, &ElementFilter::ways_only(false), &mut out).unwrap();
+
+        let lines: Vec<ResolvedElement> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len() as u64, stats.ways_processed);
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|way| way.kind == JsonElementType::Way && way.tags.contains_key("highway")));
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Shared handle to the counters of a running `Reader`
+///
+/// Cheap to clone and safe to poll from any thread while processing is in
+/// progress, e.g. to feed a dashboard or progress bar. Counters reset at the
+/// start of every processing run.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::Reader;
+/// use std::fs::File;
+/// use std::time::Duration;
+///
+/// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+/// let live = reader.live_stats();
+///
+/// std::thread::spawn(move || loop {
+///     let snapshot = live.snapshot();
+///     println!("{:.0} elements/s, {:.1} MB/s", snapshot.elements_per_sec, snapshot.bytes_per_sec / 1e6);
+///     std::thread::sleep(Duration::from_secs(1));
+/// });
+///
+/// reader.for_each(|_| Ok(()))?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LiveStats {
+    inner: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    blobs_processed: AtomicU64,
+    elements_processed: AtomicU64,
+    bytes_processed: AtomicU64,
+    errors_encountered: AtomicU64,
+    file_position: AtomicU64,
+    file_len: AtomicU64,
+    started_at: Mutex<Option<Instant>>,
+    progress: Mutex<Option<ProgressHook>>,
+}
+
+/// Callback `LiveStats::set_progress_hook` installed
+struct ProgressHook {
+    interval: Duration,
+    last_report: Option<Instant>,
+    reported_end: bool,
+    callback: Box<dyn FnMut(&LiveSnapshot) + Send>,
+}
+
+impl std::fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressHook").field("interval", &self.interval).finish_non_exhaustive()
+    }
+}
+
+/// Point-in-time view of `LiveStats`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LiveSnapshot {
+    pub blobs_processed: u64,
+    pub elements_processed: u64,
+    /// Uncompressed blob bytes processed
+    pub bytes_processed: u64,
+    pub errors_encountered: u64,
+    /// End of the furthest blob read in the file, in file bytes
+    pub file_position: u64,
+    /// Length of the file, 0 if unknown
+    pub file_len: u64,
+    /// Time since the current run started
+    pub elapsed: Duration,
+    pub elements_per_sec: f64,
+    pub bytes_per_sec: f64,
+}
+
+impl LiveSnapshot {
+    /// Share of the file read so far, from 0 to 1, if its length is known
+    ///
+    /// Runs that skip blobs (filters pruning by the index, resumed runs) jump
+    /// ahead rather than read the whole file.
+    pub fn fraction_done(&self) -> Option<f64> {
+        (self.file_len > 0).then(|| (self.file_position as f64 / self.file_len as f64).min(1.0))
+    }
+
+    /// Time left at the rate so far, once some of the file has been read
+    pub fn eta(&self) -> Option<Duration> {
+        let done = self.fraction_done().filter(|done| *done > 0.0)?;
+        Some(self.elapsed.mul_f64((1.0 - done) / done))
+    }
+}
+
+impl LiveStats {
+    /// Create a handle with all counters at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a consistent-enough snapshot of the counters and derived rates
+    pub fn snapshot(&self) -> LiveSnapshot {
+        let counters = &self.inner;
+        let elapsed = counters
+            .started_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+
+        let elements_processed = counters.elements_processed.load(Ordering::Relaxed);
+        let bytes_processed = counters.bytes_processed.load(Ordering::Relaxed);
+        let secs = elapsed.as_secs_f64();
+        let rate = |count: u64| if secs > 0.0 { count as f64 / secs } else { 0.0 };
+
+        LiveSnapshot {
+            blobs_processed: counters.blobs_processed.load(Ordering::Relaxed),
+            elements_processed,
+            bytes_processed,
+            errors_encountered: counters.errors_encountered.load(Ordering::Relaxed),
+            file_position: counters.file_position.load(Ordering::Relaxed),
+            file_len: counters.file_len.load(Ordering::Relaxed),
+            elapsed,
+            elements_per_sec: rate(elements_processed),
+            bytes_per_sec: rate(bytes_processed),
+        }
+    }
+
+    /// Call `callback` with a snapshot as blobs are processed, at most once
+    /// per `interval` and once more when the end of the file is reached
+    ///
+    /// The callback runs on the thread that processed the blob, so it should
+    /// be quick, e.g. update a progress bar. Replaces any earlier hook.
+    pub fn set_progress_hook<F>(&self, interval: Duration, callback: F)
+    where
+        F: FnMut(&LiveSnapshot) + Send + 'static,
+    {
+        let hook = ProgressHook { interval, last_report: None, reported_end: false, callback: Box::new(callback) };
+        *self.inner.progress.lock().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+    }
+
+    /// Remove the hook set by `set_progress_hook`
+    pub fn clear_progress_hook(&self) {
+        *self.inner.progress.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Reset the counters and start the clock for a new run
+    pub(crate) fn begin(&self) {
+        let counters = &self.inner;
+        counters.blobs_processed.store(0, Ordering::Relaxed);
+        counters.elements_processed.store(0, Ordering::Relaxed);
+        counters.bytes_processed.store(0, Ordering::Relaxed);
+        counters.errors_encountered.store(0, Ordering::Relaxed);
+        counters.file_position.store(0, Ordering::Relaxed);
+        *counters.started_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+        if let Some(hook) = counters.progress.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            hook.last_report = None;
+            hook.reported_end = false;
+        }
+    }
+
+    pub(crate) fn record_blob(&self, bytes: u64) {
+        self.inner.blobs_processed.fetch_add(1, Ordering::Relaxed);
+        self.inner.bytes_processed.fetch_add(bytes, Ordering::Relaxed);
+        self.report_progress();
+    }
+
+    /// Record a blob read ending at `position` in a file of `file_len` bytes
+    pub(crate) fn record_position(&self, position: u64, file_len: u64) {
+        self.inner.file_position.fetch_max(position, Ordering::Relaxed);
+        self.inner.file_len.store(file_len, Ordering::Relaxed);
+    }
+
+    /// Call the progress hook if a report is due
+    ///
+    /// Threads finding the hook busy skip the report rather than wait.
+    fn report_progress(&self) {
+        let Ok(mut hook) = self.inner.progress.try_lock() else {
+            return;
+        };
+        let Some(hook) = hook.as_mut() else {
+            return;
+        };
+        let snapshot = self.snapshot();
+        let at_end = snapshot.file_len > 0 && snapshot.file_position >= snapshot.file_len;
+        let due = hook.last_report.is_none_or(|last| last.elapsed() >= hook.interval);
+        if due || (at_end && !hook.reported_end) {
+            hook.last_report = Some(Instant::now());
+            hook.reported_end |= at_end;
+            (hook.callback)(&snapshot);
+        }
+    }
+
+    pub(crate) fn record_element(&self) {
+        self.inner.elements_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_error(&self) {
+        self.inner.errors_encountered.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_reset() {
+        let stats = LiveStats::new();
+        assert_eq!(stats.snapshot(), LiveSnapshot::default());
+
+        stats.begin();
+        stats.record_blob(1_000);
+        stats.record_element();
+        stats.record_element();
+        stats.record_error();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.blobs_processed, 1);
+        assert_eq!(snapshot.elements_processed, 2);
+        assert_eq!(snapshot.bytes_processed, 1_000);
+        assert_eq!(snapshot.errors_encountered, 1);
+
+        stats.begin();
+        assert_eq!(stats.snapshot().elements_processed, 0);
+    }
+
+    #[test]
+    fn test_polled_from_other_thread() {
+        let stats = LiveStats::new();
+        stats.begin();
+
+        let writer = stats.clone();
+        std::thread::spawn(move || {
+            for _ in 0..1_000 {
+                writer.record_element();
+            }
+        })
+        .join()
+        .unwrap();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.elements_processed, 1_000);
+        assert!(snapshot.elements_per_sec > 0.0);
+    }
+
+    #[test]
+    fn test_progress_hook_reports_file_position() {
+        use crate::io::indexed_reader::ElementFilter;
+        use crate::io::reader::Reader;
+        use std::io::Cursor;
+
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(10).block_size(20).relation_count(2).write_to(&mut data).unwrap();
+        let file_len = data.len() as u64;
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        reader.set_progress_hook(Duration::ZERO, move |snapshot| sink.lock().unwrap().push(*snapshot));
+        let stats = reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap();
+        let all = std::mem::take(&mut *reports.lock().unwrap());
+        assert_eq!(all.len() as u64, stats.blobs_processed);
+        assert!(all.windows(2).all(|pair| pair[0].file_position <= pair[1].file_position));
+        let last = all.last().unwrap();
+        assert_eq!((last.file_position, last.file_len), (file_len, file_len));
+        assert_eq!((last.fraction_done(), last.eta()), (Some(1.0), Some(Duration::ZERO)));
+
+        // A long interval leaves the first report and the one at the end
+        let sink = reports.clone();
+        reader.set_progress_hook(Duration::from_secs(3600), move |snapshot| sink.lock().unwrap().push(*snapshot));
+        reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap();
+        reader.live_stats().clear_progress_hook();
+        reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap();
+        assert_eq!(reports.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,470 @@
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use crate::blocks::lat_lon::LatLon;
+use crate::io::blob::{BlobError, Result};
+use crate::io::geometry::NodeLocationStore;
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::mapped::MappedRegion;
+#[cfg(not(any(unix, windows)))]
+use crate::io::memory::MemoryMode;
+use crate::io::max_ids::MaxIds;
+use crate::io::reader::{OsmElement, Reader};
+
+/// Bytes per node slot: latitude and longitude as 32-bit values
+pub const SLOT_SIZE: u64 = 8;
+
+/// Largest run of consecutive slots buffered before a positioned write
+const MAX_RUN_BYTES: usize = 64 * 1024;
+
+/// Writes node locations into a sparse file indexed by node id
+///
+/// The location of node `id` is stored at byte offset `id * 8`, so memory and
+/// disk use follow the highest node id rather than the number of nodes; on
+/// file systems with sparse file support the unwritten ranges take no space.
+/// Coordinates are stored in units of 100 nanodegrees (1e-7 degrees, the
+/// precision of the default granularity), and runs of consecutive ids are
+/// written with a single positioned write. Only non-negative ids fit.
+#[derive(Debug)]
+pub struct SparseLocationWriter {
+    file: File,
+    run_start: u64,
+    run: Vec<u8>,
+    written: u64,
+}
+
+impl SparseLocationWriter {
+    /// Create (or truncate) the file, sized for ids up to `max_node_id`
+    ///
+    /// The file grows past that when higher ids are written.
+    pub fn create(path: impl AsRef<Path>, max_node_id: Option<i64>) -> Result<Self> {
+        let file = File::options().read(true).write(true).create(true).truncate(true).open(path)?;
+        if let Some(max_node_id) = max_node_id {
+            file.set_len(slot_offset(max_node_id)? + SLOT_SIZE)?;
+        }
+        Ok(Self { file, run_start: 0, run: Vec::new(), written: 0 })
+    }
+
+    /// Create the file sized from a file's highest node id
+    pub fn for_max_ids(path: impl AsRef<Path>, max_ids: &MaxIds) -> Result<Self> {
+        Self::create(path, max_ids.node)
+    }
+
+    /// Store the location of a node, replacing an earlier one
+    pub fn set(&mut self, id: i64, location: LatLon) -> Result<()> {
+        let offset = slot_offset(id)?;
+        let slot = encode(location)
+            .ok_or_else(|| BlobError::InvalidFormat(format!("Node {id} location {location:?} is out of range")))?;
+
+        let run_end = self.run_start + self.run.len() as u64;
+        if offset != run_end || self.run.len() >= MAX_RUN_BYTES {
+            self.flush_run()?;
+            self.run_start = offset;
+        }
+        self.run.extend_from_slice(&slot);
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Store the location if the element is a node
+    pub fn observe(&mut self, element: &OsmElement) -> Result<()> {
+        match element {
+            OsmElement::Node(node) => self.set(node.id.0, node.location),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of `set` calls so far, overwrites included
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Flush pending writes and map the file for reading
+    pub fn finish(mut self) -> Result<SparseLocationStore> {
+        self.flush_run()?;
+        self.file.sync_data()?;
+        SparseLocationStore::from_file(self.file)
+    }
+
+    fn flush_run(&mut self) -> Result<()> {
+        if !self.run.is_empty() {
+            write_all_at(&self.file, &self.run, self.run_start)?;
+            self.run.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Memory-mapped node locations written by `SparseLocationWriter`
+///
+/// Where files can't be mapped (with `pure-safe`, under miri, or on targets
+/// other than Unix and Windows) slots are read from the file with positioned
+/// reads instead of buffering the whole id space; on targets without
+/// positioned reads, files above `MemoryMode::LOW_MEMORY_BUFFER_LIMIT` fail
+/// to open with `BlobError::MemoryLimit`.
+pub struct SparseLocationStore {
+    slots_data: SlotData,
+    slots: u64,
+    disk_bytes: Option<u64>,
+}
+
+/// Where a `SparseLocationStore` reads its slots from
+enum SlotData {
+    Mapped(MappedRegion),
+    #[cfg(any(unix, windows))]
+    File(File),
+}
+
+impl SparseLocationStore {
+    /// Map an existing location file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_file(File::open(path)?)
+    }
+
+    /// Build a location file from every node of a PBF file
+    ///
+    /// The file is sized from `Reader::max_ids` up front, so the id space is
+    /// allocated once instead of growing with every blob.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, SparseLocationStore};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+    /// let store = SparseLocationStore::build("nodes.bin", &mut reader)?;
+    /// let stats = store.stats();
+    /// println!("{} of {} slots filled", stats.filled, stats.slots);
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn build<R: Read + Seek>(path: impl AsRef<Path>, reader: &mut Reader<R>) -> Result<Self> {
+        let mut writer = SparseLocationWriter::for_max_ids(path, &reader.max_ids()?)?;
+        reader.for_each_filtered(&ElementFilter::nodes_only(), |element| writer.observe(&element))?;
+        writer.finish()
+    }
+
+    fn from_file(file: File) -> Result<Self> {
+        let metadata = file.metadata()?;
+        let (len, disk_bytes) = (metadata.len(), disk_bytes(&metadata));
+        if !len.is_multiple_of(SLOT_SIZE) {
+            return Err(BlobError::InvalidFormat(format!("Location file size {len} is not a multiple of {SLOT_SIZE}")));
+        }
+        let slots_data = if MappedRegion::MAPS_FILES {
+            SlotData::Mapped(MappedRegion::new(file)?)
+        } else {
+            Self::unmapped(file)?
+        };
+        Ok(Self { slots_data, slots: len / SLOT_SIZE, disk_bytes })
+    }
+
+    #[cfg(any(unix, windows))]
+    fn unmapped(file: File) -> Result<SlotData> {
+        Ok(SlotData::File(file))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn unmapped(file: File) -> Result<SlotData> {
+        Ok(SlotData::Mapped(MappedRegion::with_memory_mode(file, MemoryMode::LowMemory)?))
+    }
+
+    /// Location of a node, `None` if it was never stored
+    pub fn get(&self, id: i64) -> Option<LatLon> {
+        let offset = slot_offset(id).ok()?;
+        if offset >= self.slots * SLOT_SIZE {
+            return None;
+        }
+        let mut slot = [0u8; SLOT_SIZE as usize];
+        self.read_at(offset, &mut slot).ok()?;
+        decode(slot)
+    }
+
+    /// Fill `buf` with the slot bytes starting at `offset`
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match &self.slots_data {
+            SlotData::Mapped(region) => {
+                let offset = usize::try_from(offset)
+                    .map_err(|_| BlobError::InvalidFormat(format!("Offset {offset} exceeds the address space")))?;
+                buf.copy_from_slice(region.get_slice(offset, buf.len())?);
+            }
+            #[cfg(any(unix, windows))]
+            SlotData::File(file) => read_exact_at(file, buf, offset)?,
+        }
+        Ok(())
+    }
+
+    /// Number of slots, i.e. one above the highest id the file covers
+    pub fn slots(&self) -> u64 {
+        self.slots
+    }
+
+    /// Fill ratio and size of the file
+    ///
+    /// Counting filled slots reads the whole file, so this touches every page.
+    pub fn stats(&self) -> LocationStoreStats {
+        let len = self.slots * SLOT_SIZE;
+        let mut chunk = vec![0u8; MAX_RUN_BYTES];
+        let mut filled = 0;
+        let mut offset = 0;
+        while offset < len {
+            let chunk = &mut chunk[..(len - offset).min(MAX_RUN_BYTES as u64) as usize];
+            if self.read_at(offset, chunk).is_err() {
+                break;
+            }
+            filled += chunk.chunks_exact(SLOT_SIZE as usize).filter(|slot| slot.iter().any(|b| *b != 0)).count() as u64;
+            offset += chunk.len() as u64;
+        }
+        LocationStoreStats {
+            slots: self.slots,
+            filled,
+            file_bytes: self.slots * SLOT_SIZE,
+            disk_bytes: self.disk_bytes,
+        }
+    }
+}
+
+impl std::fmt::Debug for SparseLocationStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SparseLocationStore").field("slots", &self.slots).finish_non_exhaustive()
+    }
+}
+
+/// Sparse location file as a `NodeLocationStore`
+///
+/// Locations go through a `SparseLocationWriter` until `finish` maps the
+/// file as a `SparseLocationStore`; lookups before that find nothing, and
+/// storing after it fails. Only non-negative ids fit.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{MappedLocations, NodeLocationStore, Reader};
+/// use std::fs::File;
+///
+/// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+/// let mut locations = MappedLocations::create("nodes.bin", reader.max_ids()?.node)?;
+/// locations.load(&mut reader)?;
+/// let stats = reader.ways_with_geometry_using(&locations, |way, coords| Ok(()))?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug)]
+pub enum MappedLocations {
+    Writing(SparseLocationWriter),
+    Mapped(SparseLocationStore),
+    /// A failed `finish`; the file is left as written
+    Failed,
+}
+
+impl MappedLocations {
+    /// Create (or truncate) the location file, sized for ids up to `max_node_id`
+    pub fn create(path: impl AsRef<Path>, max_node_id: Option<i64>) -> Result<Self> {
+        Ok(Self::Writing(SparseLocationWriter::create(path, max_node_id)?))
+    }
+
+    /// Map a location file written earlier
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::Mapped(SparseLocationStore::open(path)?))
+    }
+
+    /// The mapped store, once finished
+    pub fn store(&self) -> Option<&SparseLocationStore> {
+        match self {
+            Self::Mapped(store) => Some(store),
+            _ => None,
+        }
+    }
+}
+
+impl NodeLocationStore for MappedLocations {
+    fn set(&mut self, id: i64, location: LatLon) -> Result<()> {
+        match self {
+            Self::Writing(writer) => writer.set(id, location),
+            _ => Err(BlobError::InvalidFormat("Location file is already mapped".to_string())),
+        }
+    }
+
+    fn get(&self, id: i64) -> Option<LatLon> {
+        self.store()?.get(id)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !matches!(self, Self::Writing(_)) {
+            return Ok(());
+        }
+        if let Self::Writing(writer) = std::mem::replace(self, Self::Failed) {
+            *self = Self::Mapped(writer.finish()?);
+        }
+        Ok(())
+    }
+}
+
+/// Occupancy of a `SparseLocationStore`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocationStoreStats {
+    pub slots: u64,
+    /// Slots holding a location
+    pub filled: u64,
+    /// Apparent file size
+    pub file_bytes: u64,
+    /// Bytes allocated on disk, where the platform reports it
+    pub disk_bytes: Option<u64>,
+}
+
+impl LocationStoreStats {
+    /// Share of slots holding a location, between 0 and 1
+    pub fn fill_ratio(&self) -> f64 {
+        if self.slots == 0 {
+            0.0
+        } else {
+            self.filled as f64 / self.slots as f64
+        }
+    }
+}
+
+fn slot_offset(id: i64) -> Result<u64> {
+    u64::try_from(id)
+        .ok()
+        .and_then(|id| id.checked_mul(SLOT_SIZE))
+        .ok_or_else(|| BlobError::InvalidFormat(format!("Node id {id} can't be stored in a sparse location file")))
+}
+
+/// Encode a location so that an all-zero slot (a hole) means "no location"
+///
+/// Coordinates are biased by `i32::MIN`, which no valid coordinate reaches.
+fn encode(location: LatLon) -> Option<[u8; 8]> {
+    let scale = |nd: i64| i32::try_from(nd.checked_add(50)?.div_euclid(100)).ok().filter(|v| *v != i32::MIN);
+    let lat = scale(location.lat.raw())? as u32 ^ 0x8000_0000;
+    let lon = scale(location.lon.raw())? as u32 ^ 0x8000_0000;
+    let mut slot = [0u8; 8];
+    slot[..4].copy_from_slice(&lat.to_le_bytes());
+    slot[4..].copy_from_slice(&lon.to_le_bytes());
+    Some(slot)
+}
+
+fn decode(slot: [u8; 8]) -> Option<LatLon> {
+    if slot == [0; 8] {
+        return None;
+    }
+    let unscale = |bytes: [u8; 4]| (u32::from_le_bytes(bytes) ^ 0x8000_0000) as i32 as i64 * 100;
+    let lat = unscale(slot[..4].try_into().ok()?);
+    let lon = unscale(slot[4..].try_into().ok()?);
+    Some(LatLon::from_raw(lat, lon))
+}
+
+#[cfg(unix)]
+fn write_all_at(file: &File, buf: &[u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+}
+
+#[cfg(not(unix))]
+fn write_all_at(mut file: &File, buf: &[u8], offset: u64) -> std::io::Result<()> {
+    use std::io::{SeekFrom, Write};
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn disk_bytes(metadata: &std::fs::Metadata) -> Option<u64> {
+    Some(std::os::unix::fs::MetadataExt::blocks(metadata) * 512)
+}
+
+#[cfg(not(unix))]
+fn disk_bytes(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_round_trip_and_holes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nodes.bin");
+        let mut writer = SparseLocationWriter::create(&path, Some(999)).unwrap();
+        let berlin = LatLon::from_raw(52_520_008_300, 13_404_954_000);
+        writer.set(10, berlin).unwrap();
+        writer.set(11, LatLon::from_raw(-90_000_000_000, -180_000_000_000)).unwrap();
+        writer.set(5000, LatLon::default()).unwrap();
+        assert!(writer.set(-1, berlin).is_err());
+        assert!(writer.set(12, LatLon::from_raw(900_000_000_000, 0)).is_err());
+        assert!(writer.set(13, LatLon::from_raw(i64::MAX, 0)).is_err());
+        assert_eq!(writer.written(), 3);
+
+        let store = writer.finish().unwrap();
+        // Rounded to 1e-7 degrees
+        assert_eq!(store.get(10), Some(LatLon::from_raw(52_520_008_300, 13_404_954_000)));
+        assert_eq!(store.get(11), Some(LatLon::from_raw(-90_000_000_000, -180_000_000_000)));
+        assert_eq!(store.get(5000), Some(LatLon::default()));
+        assert_eq!((store.get(0), store.get(12), store.get(5001), store.get(-3)), (None, None, None, None));
+
+        let stats = SparseLocationStore::open(&path).unwrap().stats();
+        assert_eq!((stats.slots, stats.filled, stats.file_bytes), (5001, 3, 5001 * SLOT_SIZE));
+        assert!(stats.fill_ratio() > 0.0 && stats.fill_ratio() < 0.001);
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_positioned_reads_match_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nodes.bin");
+        let mut writer = SparseLocationWriter::create(&path, None).unwrap();
+        for id in (0..20_000).step_by(3) {
+            writer.set(id, LatLon::from_raw(id * 1_000, -id * 2_000)).unwrap();
+        }
+        let mapped = writer.finish().unwrap();
+        let file = File::open(&path).unwrap();
+        let unmapped = SparseLocationStore { slots_data: SlotData::File(file), slots: mapped.slots, disk_bytes: None };
+
+        for id in [-1, 0, 1, 3, 9_999, 19_998, 19_999, 20_000] {
+            assert_eq!(unmapped.get(id), mapped.get(id), "node {id}");
+        }
+        assert_eq!(unmapped.stats().filled, mapped.stats().filled);
+        assert_eq!(unmapped.stats().filled, 6_667);
+    }
+
+    #[test]
+    fn test_build_from_reader() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(5).grid_size(8).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(std::io::Cursor::new(data)).unwrap();
+        let mut nodes = Vec::new();
+        reader.for_each_filtered(&ElementFilter::nodes_only(), |element| {
+            if let OsmElement::Node(node) = element {
+                nodes.push(node);
+            }
+            Ok(())
+        }).unwrap();
+        assert!(!nodes.is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = SparseLocationStore::build(dir.path().join("nodes.bin"), &mut reader).unwrap();
+        for node in &nodes {
+            let location = store.get(node.id.0).unwrap();
+            assert!((location.lat.raw() - node.location.lat.raw()).abs() <= 50);
+            assert!((location.lon.raw() - node.location.lon.raw()).abs() <= 50);
+        }
+        assert_eq!(store.stats().filled, nodes.len() as u64);
+    }
+}
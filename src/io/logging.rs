@@ -0,0 +1,183 @@
+use std::fmt::Display;
+
+/// Verbosity used when a reader reports data it had to skip.
+///
+/// Events are emitted through the `log` crate when the `log` feature is enabled,
+/// so services control output via their normal logging configuration. Without the
+/// feature nothing is printed; skipped data is still listed in `ProcessingStats`
+/// and passed to `Reader::set_on_error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkipLogLevel {
+    /// Do not emit events for skipped data
+    Off,
+    /// Emit events at debug level
+    Debug,
+    /// Emit events at warn level
+    #[default]
+    Warn,
+}
+
+/// Log target used for all events emitted by this crate
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub const LOG_TARGET: &str = "osm_pbf";
+
+/// Report a skipped blob with structured fields (offset, blob index, reason)
+pub(crate) fn log_skipped(level: SkipLogLevel, offset: Option<u64>, blob_index: Option<usize>, reason: &dyn Display) {
+    #[cfg(feature = "log")]
+    {
+        let level = match level {
+            SkipLogLevel::Off => return,
+            SkipLogLevel::Debug => log::Level::Debug,
+            SkipLogLevel::Warn => log::Level::Warn,
+        };
+        log::log!(
+            target: LOG_TARGET,
+            level,
+            offset:? = offset, blob_index:? = blob_index, reason:% = reason;
+            "skipped blob (offset={offset:?}, blob_index={blob_index:?}): {reason}"
+        );
+    }
+
+    #[cfg(not(feature = "log"))]
+    {
+        let _ = (level, offset, blob_index, reason);
+    }
+}
+
+/// Report where indexing resumed after a damaged region (`None` if it couldn't)
+pub(crate) fn log_resync(level: SkipLogLevel, damaged: u64, resumed: Option<u64>) {
+    #[cfg(feature = "log")]
+    {
+        let level = match level {
+            SkipLogLevel::Off => return,
+            SkipLogLevel::Debug => log::Level::Debug,
+            SkipLogLevel::Warn => log::Level::Warn,
+        };
+        log::log!(
+            target: LOG_TARGET,
+            level,
+            offset = damaged, resumed:? = resumed;
+            "damaged data at offset {damaged}, resumed at {resumed:?}"
+        );
+    }
+
+    #[cfg(not(feature = "log"))]
+    {
+        let _ = (level, damaged, resumed);
+    }
+}
+
+/// Report a saved blob index that couldn't be used, so the file is scanned instead
+pub(crate) fn log_index_file_ignored(level: SkipLogLevel, reason: &dyn Display) {
+    #[cfg(feature = "log")]
+    {
+        let level = match level {
+            SkipLogLevel::Off => return,
+            SkipLogLevel::Debug => log::Level::Debug,
+            SkipLogLevel::Warn => log::Level::Warn,
+        };
+        log::log!(
+            target: LOG_TARGET,
+            level,
+            reason:% = reason;
+            "ignored saved blob index: {reason}"
+        );
+    }
+
+    #[cfg(not(feature = "log"))]
+    {
+        let _ = (level, reason);
+    }
+}
+
+/// Report primitive groups mixing element kinds that were decoded anyway
+pub(crate) fn log_mixed_groups(level: SkipLogLevel, offset: u64, groups: u64) {
+    #[cfg(feature = "log")]
+    {
+        let level = match level {
+            SkipLogLevel::Off => return,
+            SkipLogLevel::Debug => log::Level::Debug,
+            SkipLogLevel::Warn => log::Level::Warn,
+        };
+        log::log!(
+            target: LOG_TARGET,
+            level,
+            offset = offset, groups = groups;
+            "{groups} primitive groups mix element kinds in blob at offset {offset}"
+        );
+    }
+
+    #[cfg(not(feature = "log"))]
+    {
+        let _ = (level, offset, groups);
+    }
+}
+
+/// Report required features a file was opened with despite not being supported
+pub(crate) fn log_unsupported_features(features: &[String]) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        target: LOG_TARGET,
+        features:? = features;
+        "file requires unsupported features: {}", features.join(", ")
+    );
+
+    #[cfg(not(feature = "log"))]
+    {
+        let _ = features;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_default_level() {
+        assert_eq!(SkipLogLevel::default(), SkipLogLevel::Warn);
+    }
+
+    /// Records logged under `LOG_TARGET`, as level and message
+    #[cfg(feature = "log")]
+    struct CaptureLogger(std::sync::Mutex<Vec<(log::Level, String)>>);
+
+    #[cfg(feature = "log")]
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == LOG_TARGET
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_skipped_levels() {
+        static LOGGER: CaptureLogger = CaptureLogger(std::sync::Mutex::new(Vec::new()));
+        // Other tests log too, so only this test's reasons are looked at
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        for level in [SkipLogLevel::Off, SkipLogLevel::Debug, SkipLogLevel::Warn] {
+            log_skipped(level, Some(1024), Some(3), &format!("truncated blob at {level:?}"));
+        }
+        log_skipped(SkipLogLevel::Warn, None, None, &"truncated blob, unknown position");
+
+        let records: Vec<_> = LOGGER.0.lock().unwrap().iter().filter(|(_, message)| message.contains("truncated blob")).cloned().collect();
+        assert_eq!(
+            records,
+            [
+                (log::Level::Debug, "skipped blob (offset=Some(1024), blob_index=Some(3)): truncated blob at Debug".to_string()),
+                (log::Level::Warn, "skipped blob (offset=Some(1024), blob_index=Some(3)): truncated blob at Warn".to_string()),
+                (log::Level::Warn, "skipped blob (offset=None, blob_index=None): truncated blob, unknown position".to_string()),
+            ]
+        );
+    }
+}
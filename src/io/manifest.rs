@@ -0,0 +1,274 @@
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::str::FromStr;
+use sha2::{Digest, Sha256};
+use crate::io::blob::{BlobError, BlobType, Result};
+use crate::io::codec::{BlockDecoder, PbfBlockCodec};
+use crate::io::decode::decode_elements;
+use crate::io::indexed_reader::{ElementCounts, IndexedReader};
+use crate::io::reader::OsmElement;
+use crate::io::validate::GroupPolicy;
+
+/// First line of the text form of a manifest
+const MANIFEST_HEADER: &str = "# osm-pbf manifest v1";
+
+/// Digest and location of one blob frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Byte offset of the frame in the file
+    pub offset: u64,
+    /// Length of the frame, length prefix and BlobHeader included
+    pub length: u64,
+    pub blob_type: BlobType,
+    /// SHA-256 of the frame bytes as stored in the file
+    pub sha256: [u8; 32],
+    pub element_counts: ElementCounts,
+}
+
+impl ManifestEntry {
+    /// Byte range of the frame in the file
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.length
+    }
+}
+
+/// Per-blob SHA-256 digests of a file, for verifying mirrored or partial downloads
+///
+/// Digests cover the raw frames, so they identify the exact bytes published
+/// rather than the decoded content (see `Fingerprint` for that). The text form
+/// has one line per blob: offset, length, type, hex digest and the node, way,
+/// relation and changeset counts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Hash every blob frame of an indexed file
+    pub fn from_reader<R: Read + Seek>(reader: &mut IndexedReader<R>) -> Result<Self> {
+        Self::from_reader_with(reader, &PbfBlockCodec)
+    }
+
+    /// `from_reader` for files whose data blocks are in the wire format of
+    /// `decoder` (see `Reader::set_block_decoder`)
+    pub fn from_reader_with<R: Read + Seek>(reader: &mut IndexedReader<R>, decoder: &dyn BlockDecoder) -> Result<Self> {
+        let mut entries = Vec::with_capacity(reader.blob_count());
+        for index in 0..reader.blob_count() {
+            let frame = reader.read_frame_bytes(index)?;
+            let element_counts = match reader.read_blob_by_index(index)? {
+                Some(blob) => count_elements(&decode_elements(&blob, GroupPolicy::default(), decoder)?.1),
+                None => ElementCounts::default(),
+            };
+            let blob_index = &reader.index()[index];
+            entries.push(ManifestEntry {
+                offset: blob_index.offset,
+                length: frame.len() as u64,
+                blob_type: blob_index.blob_type.clone(),
+                sha256: Sha256::digest(&frame).into(),
+                element_counts,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Total number of bytes covered by the manifest
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|e| e.length).sum()
+    }
+
+    /// Check a (possibly incomplete) copy of the file against the manifest
+    ///
+    /// Frames are read at the offsets recorded in the manifest, so a damaged or
+    /// truncated blob doesn't affect the verification of the others.
+    pub fn verify<R: Read + Seek>(&self, reader: &mut R) -> Result<Verification> {
+        let mut verification = Verification::default();
+        let mut buf = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            buf.resize(usize::try_from(entry.length).map_err(|_| {
+                BlobError::InvalidFormat(format!("Manifest entry {index} is too large"))
+            })?, 0);
+            reader.seek(SeekFrom::Start(entry.offset))?;
+            match reader.read_exact(&mut buf) {
+                Ok(()) if <[u8; 32]>::from(Sha256::digest(&buf)) == entry.sha256 => verification.valid += 1,
+                Ok(()) => verification.corrupt.push(index),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => verification.missing.push(index),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(verification)
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{MANIFEST_HEADER}")?;
+        for entry in &self.entries {
+            let counts = &entry.element_counts;
+            write!(f, "{} {} {} ", entry.offset, entry.length, entry.blob_type.as_str())?;
+            for byte in entry.sha256 {
+                write!(f, "{byte:02x}")?;
+            }
+            writeln!(f, " {} {} {} {}", counts.nodes, counts.ways, counts.relations, counts.changesets)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = BlobError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some(MANIFEST_HEADER) {
+            return Err(BlobError::InvalidFormat("Missing manifest header".to_string()));
+        }
+
+        let mut entries = Vec::new();
+        for (number, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let invalid = || BlobError::InvalidFormat(format!("Invalid manifest line {}: {line:?}", number + 2));
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [offset, length, blob_type, sha256, nodes, ways, relations, changesets] = fields[..] else {
+                return Err(invalid());
+            };
+            entries.push(ManifestEntry {
+                offset: offset.parse().map_err(|_| invalid())?,
+                length: length.parse().map_err(|_| invalid())?,
+                blob_type: BlobType::from_str(blob_type).map_err(|_| invalid())?,
+                sha256: parse_hex_digest(sha256).ok_or_else(invalid)?,
+                element_counts: ElementCounts {
+                    nodes: nodes.parse().map_err(|_| invalid())?,
+                    ways: ways.parse().map_err(|_| invalid())?,
+                    relations: relations.parse().map_err(|_| invalid())?,
+                    changesets: changesets.parse().map_err(|_| invalid())?,
+                },
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// Result of `Manifest::verify`, with blobs identified by manifest entry index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// Number of blobs whose digest matches
+    pub valid: usize,
+    /// Blobs present but with a different digest
+    pub corrupt: Vec<usize>,
+    /// Blobs cut off by the end of the file
+    pub missing: Vec<usize>,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty()
+    }
+
+    /// Byte ranges to fetch again, adjacent bad blobs merged into one range
+    pub fn refetch_ranges(&self, manifest: &Manifest) -> Vec<Range<u64>> {
+        let mut bad: Vec<usize> = self.corrupt.iter().chain(&self.missing).copied().collect();
+        bad.sort_unstable();
+
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for range in bad.iter().filter_map(|&i| manifest.entries.get(i)).map(ManifestEntry::range) {
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+}
+
+fn count_elements(elements: &[OsmElement]) -> ElementCounts {
+    let mut counts = ElementCounts::default();
+    for element in elements {
+        match element {
+            OsmElement::Node(_) => counts.nodes += 1,
+            OsmElement::Way(_) => counts.ways += 1,
+            OsmElement::Relation(_) => counts.relations += 1,
+            OsmElement::ChangeSet(_) => counts.changesets += 1,
+        }
+    }
+    counts
+}
+
+fn parse_hex_digest(s: &str) -> Option<[u8; 32]> {
+    if s.len() != 64 || !s.is_ascii() {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn planet() -> Vec<u8> {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(4).write_to(&mut data).unwrap();
+        data
+    }
+
+    fn manifest_of(data: &[u8]) -> Manifest {
+        let mut reader = IndexedReader::new(Cursor::new(data.to_vec())).unwrap();
+        Manifest::from_reader(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let data = planet();
+        let manifest = manifest_of(&data);
+        assert!(manifest.entries.len() > 1);
+        assert_eq!(manifest.total_bytes(), data.len() as u64);
+
+        let text = manifest.to_string();
+        assert!(text.starts_with(MANIFEST_HEADER));
+        assert_eq!(text.parse::<Manifest>().unwrap(), manifest);
+        assert!("garbage".parse::<Manifest>().is_err());
+        assert!(format!("{MANIFEST_HEADER}\n0 10 OSMData abc 0 0 0 0\n").parse::<Manifest>().is_err());
+    }
+
+    #[test]
+    fn test_element_counts_from_decoded_blobs() {
+        let counts = |nodes, ways, relations| ElementCounts { nodes, ways, relations, changesets: 0 };
+        let manifest = manifest_of(&planet());
+
+        // A 4x4 grid fits one block per element type: 16 nodes, 8 ways and 4 relations
+        let element_counts: Vec<ElementCounts> = manifest.entries.iter().map(|e| e.element_counts.clone()).collect();
+        assert_eq!(element_counts, [counts(0, 0, 0), counts(16, 0, 0), counts(0, 8, 0), counts(0, 0, 4)]);
+        assert_eq!(manifest.entries[0].blob_type, BlobType::OSMHeader);
+        assert_eq!(manifest.to_string().parse::<Manifest>().unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_verify_detects_corrupt_and_missing_blobs() {
+        let data = planet();
+        let manifest = manifest_of(&data);
+        assert!(manifest.verify(&mut Cursor::new(&data)).unwrap().is_valid());
+
+        // Flip a byte in the second blob and cut the last one short
+        let mut damaged = data.clone();
+        damaged[manifest.entries[1].range().end as usize - 1] ^= 0xff;
+        let last = manifest.entries.len() - 1;
+        damaged.truncate(manifest.entries[last].offset as usize + 3);
+
+        let verification = manifest.verify(&mut Cursor::new(&damaged)).unwrap();
+        assert_eq!((verification.corrupt.clone(), verification.missing.clone()), (vec![1], vec![last]));
+        assert_eq!(verification.valid, manifest.entries.len() - 2);
+        assert_eq!(
+            verification.refetch_ranges(&manifest),
+            if last == 2 {
+                vec![manifest.entries[1].offset..manifest.entries[2].range().end]
+            } else {
+                vec![manifest.entries[1].range(), manifest.entries[last].range()]
+            }
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::fs::File;
+use bytes::Bytes;
+use crate::io::blob::{BlobError, Result};
+use crate::io::memory::MemoryMode;
+
+/// Read-only view of a whole file, memory-mapped when possible
+///
+/// All `unsafe` code in the readers lives here; the only other `unsafe` in the
+/// crate is the C ABI in `ffi.rs`. Inventory:
+///
+/// | Site | Invariant |
+/// |------|-----------|
+/// | `libc::mmap` in `Mapping::new` | `PROT_READ`/`MAP_PRIVATE` mapping of exactly `len` bytes of an open file; `MAP_FAILED` is checked |
+/// | `slice::from_raw_parts` in `Mapping::as_slice` | pointer is non-null and valid for `len` bytes until `Drop` runs; nothing writes through it |
+/// | `libc::munmap` in `Drop` | unmaps exactly the region returned by `mmap`, once |
+/// | `CreateFileMappingW` in `Mapping::new` (Windows) | `PAGE_READONLY` mapping object of an open file handle; a null result is checked |
+/// | `MapViewOfFile` in `Mapping::new` (Windows) | `FILE_MAP_READ` view of exactly `len` bytes; a null view is checked |
+/// | `CloseHandle` in `Mapping::new` (Windows) | closes the mapping object once; the view keeps the mapping alive |
+/// | `UnmapViewOfFile` in `Drop` (Windows) | unmaps exactly the view returned by `MapViewOfFile`, once |
+/// | `unsafe impl Send/Sync` | the mapping is immutable and owned by the region, so sharing `&[u8]` across threads is sound |
+///
+/// All offset arithmetic happens in safe code through checked slice access,
+/// and debug builds additionally assert the bounds of every access.
+///
+/// Building with `--cfg miri` or the `pure-safe` feature replaces the mapping
+/// with a file read into an owned buffer, so the readers contain no unsafe
+/// code at all and run under miri.
+pub(crate) struct MappedRegion {
+    inner: imp::Mapping,
+}
+
+impl MappedRegion {
+    /// Whether `new` maps files rather than reading them into memory
+    pub(crate) const MAPS_FILES: bool = imp::MEMORY_MAPPED;
+
+    /// Map (or, in the safe fallback, read) the whole file
+    pub(crate) fn new(file: File) -> Result<Self> {
+        Ok(Self { inner: imp::Mapping::new(file)? })
+    }
+
+    /// Like `new`, but fail with `BlobError::MemoryLimit` instead of reading a
+    /// file larger than `mode` allows into memory where it can't be mapped
+    pub(crate) fn with_memory_mode(file: File, mode: MemoryMode) -> Result<Self> {
+        if !imp::MEMORY_MAPPED {
+            mode.check_buffered(file.metadata()?.len())?;
+        }
+        Self::new(file)
+    }
+
+    /// Whether the data is backed by an actual memory mapping
+    pub(crate) fn is_memory_mapped(&self) -> bool {
+        imp::MEMORY_MAPPED
+    }
+
+    /// Get a slice of the data at the given offset and length
+    pub(crate) fn get_slice(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        let data = self.inner.as_slice();
+        let slice = offset
+            .checked_add(len)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| BlobError::InvalidFormat(
+                format!("Offset {} + length {} exceeds file size {}", offset, len, data.len())
+            ))?;
+
+        debug_assert!(offset + slice.len() <= data.len());
+        debug_assert_eq!(slice.len(), len);
+        Ok(slice)
+    }
+
+    /// Get an owned copy of the bytes at the given offset and length
+    pub(crate) fn get_bytes(&self, offset: usize, len: usize) -> Result<Bytes> {
+        Ok(Bytes::copy_from_slice(self.get_slice(offset, len)?))
+    }
+}
+
+#[cfg(all(unix, not(miri), not(feature = "pure-safe")))]
+mod imp {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr::NonNull;
+    use crate::io::blob::{BlobError, Result};
+
+    pub(super) const MEMORY_MAPPED: bool = true;
+
+    pub(super) struct Mapping {
+        /// Start of the mapping; `None` for empty files, which can't be mapped
+        data: Option<NonNull<u8>>,
+        len: usize,
+        /// Kept open for the lifetime of the mapping
+        _file: File,
+    }
+
+    // SAFETY: the mapping is read-only and exclusively owned by `Mapping`, which
+    // only hands out shared `&[u8]` borrows tied to its own lifetime.
+    unsafe impl Send for Mapping {}
+    unsafe impl Sync for Mapping {}
+
+    impl Mapping {
+        pub(super) fn new(file: File) -> Result<Self> {
+            let len = usize::try_from(file.metadata()?.len()).map_err(|_| {
+                BlobError::InvalidFormat("File too large to map on this platform".to_string())
+            })?;
+
+            if len == 0 {
+                return Ok(Self { data: None, len: 0, _file: file });
+            }
+
+            // SAFETY: requests a fresh read-only private mapping of `len` bytes of
+            // a valid open descriptor; the result is checked against MAP_FAILED.
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+
+            if ptr == libc::MAP_FAILED {
+                return Err(BlobError::Io(std::io::Error::last_os_error()));
+            }
+
+            Ok(Self {
+                data: NonNull::new(ptr as *mut u8),
+                len,
+                _file: file,
+            })
+        }
+
+        pub(super) fn as_slice(&self) -> &[u8] {
+            match self.data {
+                // SAFETY: `ptr` came from a successful mmap of `len` bytes that stays
+                // mapped until `drop`, and the borrow can't outlive `self`.
+                Some(ptr) => unsafe { std::slice::from_raw_parts(ptr.as_ptr(), self.len) },
+                None => {
+                    debug_assert_eq!(self.len, 0);
+                    &[]
+                }
+            }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            if let Some(ptr) = self.data.take() {
+                // SAFETY: unmaps exactly the region returned by mmap, once; no
+                // borrows of it can outlive `self`.
+                unsafe {
+                    libc::munmap(ptr.as_ptr() as *mut libc::c_void, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(windows, not(miri), not(feature = "pure-safe")))]
+mod imp {
+    use std::fs::File;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr::{self, NonNull};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::Memory::{
+        CreateFileMappingW, MapViewOfFile, UnmapViewOfFile, FILE_MAP_READ, MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READONLY,
+    };
+    use crate::io::blob::{BlobError, Result};
+
+    pub(super) const MEMORY_MAPPED: bool = true;
+
+    pub(super) struct Mapping {
+        /// Start of the view; `None` for empty files, which can't be mapped
+        data: Option<NonNull<u8>>,
+        len: usize,
+        /// Kept open for the lifetime of the view
+        _file: File,
+    }
+
+    // SAFETY: the view is read-only and exclusively owned by `Mapping`, which
+    // only hands out shared `&[u8]` borrows tied to its own lifetime.
+    unsafe impl Send for Mapping {}
+    unsafe impl Sync for Mapping {}
+
+    impl Mapping {
+        pub(super) fn new(file: File) -> Result<Self> {
+            let len = usize::try_from(file.metadata()?.len()).map_err(|_| {
+                BlobError::InvalidFormat("File too large to map on this platform".to_string())
+            })?;
+
+            if len == 0 {
+                return Ok(Self { data: None, len: 0, _file: file });
+            }
+
+            // SAFETY: creates a read-only mapping object of a valid open file
+            // handle, sized to the file; the result is checked for null.
+            let mapping = unsafe {
+                CreateFileMappingW(file.as_raw_handle() as HANDLE, ptr::null(), PAGE_READONLY, 0, 0, ptr::null())
+            };
+            if mapping.is_null() {
+                return Err(BlobError::Io(std::io::Error::last_os_error()));
+            }
+
+            // SAFETY: maps a read-only view of the first `len` bytes of the
+            // mapping object created above; the result is checked for null.
+            let view = unsafe { MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, len) };
+            let error = std::io::Error::last_os_error();
+
+            // SAFETY: closes the mapping object once; an open view keeps the
+            // mapping itself alive until it is unmapped.
+            unsafe {
+                CloseHandle(mapping);
+            }
+
+            let data = NonNull::new(view.Value as *mut u8).ok_or(BlobError::Io(error))?;
+            Ok(Self { data: Some(data), len, _file: file })
+        }
+
+        pub(super) fn as_slice(&self) -> &[u8] {
+            match self.data {
+                // SAFETY: `ptr` came from a successful MapViewOfFile of `len` bytes
+                // that stays mapped until `drop`, and the borrow can't outlive `self`.
+                Some(ptr) => unsafe { std::slice::from_raw_parts(ptr.as_ptr(), self.len) },
+                None => {
+                    debug_assert_eq!(self.len, 0);
+                    &[]
+                }
+            }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            if let Some(ptr) = self.data.take() {
+                // SAFETY: unmaps exactly the view returned by MapViewOfFile, once;
+                // no borrows of it can outlive `self`.
+                unsafe {
+                    UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS { Value: ptr.as_ptr().cast() });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(all(any(unix, windows), not(miri), not(feature = "pure-safe"))))]
+mod imp {
+    use std::fs::File;
+    use std::io::Read;
+    use crate::io::blob::Result;
+
+    pub(super) const MEMORY_MAPPED: bool = false;
+
+    pub(super) struct Mapping {
+        data: Vec<u8>,
+    }
+
+    impl Mapping {
+        pub(super) fn new(mut file: File) -> Result<Self> {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            Ok(Self { data })
+        }
+
+        pub(super) fn as_slice(&self) -> &[u8] {
+            &self.data
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn region_with(content: &[u8]) -> MappedRegion {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        file.flush().unwrap();
+        MappedRegion::new(file.reopen().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_get_slice_bounds() {
+        let region = region_with(b"0123456789");
+
+        assert_eq!(region.get_slice(0, 10).unwrap(), b"0123456789");
+        assert_eq!(region.get_slice(2, 3).unwrap(), b"234");
+        assert_eq!(region.get_slice(10, 0).unwrap(), b"");
+        assert!(region.get_slice(8, 3).is_err());
+        assert!(region.get_slice(usize::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let region = region_with(b"");
+
+        assert_eq!(region.get_slice(0, 0).unwrap(), b"");
+        assert!(region.get_slice(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_mapped_on_unix_and_windows() {
+        let region = region_with(b"0123456789");
+        assert_eq!(region.is_memory_mapped(), cfg!(all(any(unix, windows), not(miri), not(feature = "pure-safe"))));
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let region = std::sync::Arc::new(region_with(&[7u8; 4096]));
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let region = std::sync::Arc::clone(&region);
+                std::thread::spawn(move || region.get_bytes(i * 1024, 1024).unwrap())
+            })
+            .collect();
+
+        for handle in handles {
+            assert!(handle.join().unwrap().iter().all(|b| *b == 7));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::io::indexed_reader::BlobIndex;
+use crate::io::reader::OsmElement;
+
+/// Highest element ids in a file, from `Reader::max_ids`
+///
+/// `None` when the file holds no element of that type. Editors and importers
+/// allocate fresh ids above these.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaxIds {
+    pub node: Option<i64>,
+    pub way: Option<i64>,
+    pub relation: Option<i64>,
+}
+
+impl MaxIds {
+    /// Raise the maximum for the element's type if its id is higher
+    pub fn observe(&mut self, element: &OsmElement) {
+        match element {
+            OsmElement::Node(node) => raise(&mut self.node, node.id.0),
+            OsmElement::Way(way) => raise(&mut self.way, way.id.0),
+            OsmElement::Relation(relation) => raise(&mut self.relation, relation.id.0),
+            OsmElement::ChangeSet(_) => {}
+        }
+    }
+
+    /// Take what the index alone can answer and list the blobs left to decode
+    ///
+    /// A blob with known id ranges contributes the maximum of each element
+    /// type without decoding; blobs the deep index hasn't covered are returned.
+    pub(crate) fn from_index(index: &[BlobIndex]) -> (Self, Vec<usize>) {
+        let mut max_ids = Self::default();
+        let mut to_decode = Vec::new();
+
+        for (blob_index, blob) in index.iter().enumerate() {
+            if !blob.blob_type.holds_elements() {
+                continue;
+            }
+            let Some(ranges) = blob.id_ranges else {
+                to_decode.push(blob_index);
+                continue;
+            };
+            if let Some((_, max)) = ranges.node {
+                raise(&mut max_ids.node, max.0);
+            }
+            if let Some((_, max)) = ranges.way {
+                raise(&mut max_ids.way, max.0);
+            }
+            if let Some((_, max)) = ranges.relation {
+                raise(&mut max_ids.relation, max.0);
+            }
+        }
+        (max_ids, to_decode)
+    }
+}
+
+fn raise(slot: &mut Option<i64>, id: i64) {
+    *slot = Some(slot.map_or(id, |max| max.max(id)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::{NodeId, RelationId, WayId};
+    use crate::io::blob::BlobType;
+    use crate::io::id_ranges::BlobIdRanges;
+    use crate::io::indexed_reader::ElementCounts;
+    use pretty_assertions::assert_eq;
+
+    fn blob(id_ranges: Option<BlobIdRanges>) -> BlobIndex {
+        BlobIndex {
+            offset: 0,
+            header_size: 0,
+            size: 100,
+            blob_type: BlobType::OSMData,
+            id_ranges,
+            element_counts: ElementCounts::default(),
+            bbox: None,
+            key_presence: None,
+        }
+    }
+
+    #[test]
+    fn test_known_ranges_need_no_decoding() {
+        let mixed = BlobIdRanges {
+            node: Some((NodeId(101), NodeId(300))),
+            way: Some((WayId(1), WayId(40))),
+            relation: None,
+        };
+        let index = vec![
+            blob(Some(BlobIdRanges { node: Some((NodeId(1), NodeId(100))), ..Default::default() })),
+            blob(Some(mixed)),
+            blob(Some(BlobIdRanges { way: Some((WayId(41), WayId(50))), ..Default::default() })),
+            blob(Some(BlobIdRanges { relation: Some((RelationId(1), RelationId(5))), ..Default::default() })),
+            // Changesets only
+            blob(Some(BlobIdRanges::default())),
+        ];
+        let (max_ids, to_decode) = MaxIds::from_index(&index);
+
+        assert_eq!(max_ids, MaxIds { node: Some(300), way: Some(50), relation: Some(5) });
+        assert!(to_decode.is_empty());
+    }
+
+    #[test]
+    fn test_blobs_without_ranges_are_decoded() {
+        let index = vec![
+            blob(None),
+            blob(Some(BlobIdRanges { way: Some((WayId(1), WayId(90))), ..Default::default() })),
+            blob(None),
+        ];
+        let (max_ids, to_decode) = MaxIds::from_index(&index);
+
+        assert_eq!(max_ids, MaxIds { node: None, way: Some(90), relation: None });
+        assert_eq!(to_decode, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_observe() {
+        use crate::blocks::primitives::prelude::*;
+
+        let mut max_ids = MaxIds::default();
+        max_ids.observe(&OsmElement::Way(Way { id: WayId(7), keys: vec![], vals: vec![], info: None, refs: vec![], lat: vec![], lon: vec![] }));
+        max_ids.observe(&OsmElement::Way(Way { id: WayId(3), keys: vec![], vals: vec![], info: None, refs: vec![], lat: vec![], lon: vec![] }));
+
+        assert_eq!(max_ids, MaxIds { node: None, way: Some(7), relation: None });
+    }
+}
@@ -0,0 +1,188 @@
+use crate::io::blob::{BlobError, Result};
+use crate::io::profile::Profile;
+
+/// Whether the target has pointers narrower than 64 bits, where a planet
+/// file can be neither mapped nor buffered whole
+pub const CONSTRAINED_TARGET: bool = cfg!(not(target_pointer_width = "64"));
+
+/// Memory budget the readers pick their indexing, caches and fallbacks for
+///
+/// The default is `LowMemory` on 32-bit targets and with the `low-memory`
+/// feature, and `Standard` otherwise. Compared with `Standard`, `LowMemory`:
+///
+/// - indexes blobs while reading them (`IndexedReader::lazy`) instead of
+///   scanning the whole file before the first element;
+/// - keeps one idle blob buffer and decodes on one thread (`Profile::LowMemory`);
+/// - never reads a file of more than `LOW_MEMORY_BUFFER_LIMIT` bytes into
+///   memory where it can't be mapped (with `pure-safe`, under miri, or on
+///   targets other than Unix and Windows); `MmapBlobReader` fails with `BlobError::MemoryLimit`
+///   instead, so use `Reader` on those files.
+///
+/// Results are the same in both modes; only time-to-first-element,
+/// throughput and peak memory differ.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{MemoryMode, Reader};
+/// use std::fs::File;
+///
+/// let file = File::open("planet.osm.pbf")?;
+/// let mode = MemoryMode::detect(file.metadata()?.len());
+/// let mut reader = Reader::with_memory_mode(file, mode)?;
+/// reader.par_for_each(&mode.profile().parallel_config(), |_| Ok(()))?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryMode {
+    /// Eager index, default caches, whole-file fallbacks
+    Standard,
+    /// Streaming index, minimal caches, no whole-file buffering
+    LowMemory,
+}
+
+impl Default for MemoryMode {
+    fn default() -> Self {
+        if CONSTRAINED_TARGET || cfg!(feature = "low-memory") {
+            MemoryMode::LowMemory
+        } else {
+            MemoryMode::Standard
+        }
+    }
+}
+
+impl MemoryMode {
+    /// Largest file `LowMemory` reads whole into memory where it can't be mapped
+    pub const LOW_MEMORY_BUFFER_LIMIT: u64 = 64 << 20;
+
+    /// The default mode, or `LowMemory` for a file taking more than a quarter
+    /// of the address space
+    pub fn detect(file_len: u64) -> Self {
+        if file_len > usize::MAX as u64 / 4 {
+            MemoryMode::LowMemory
+        } else {
+            MemoryMode::default()
+        }
+    }
+
+    pub fn is_low_memory(self) -> bool {
+        self == MemoryMode::LowMemory
+    }
+
+    /// Tuning preset for threads, read-ahead and buffer pools
+    pub fn profile(self) -> Profile {
+        match self {
+            MemoryMode::Standard => Profile::Balanced,
+            MemoryMode::LowMemory => Profile::LowMemory,
+        }
+    }
+
+    /// Whether to index blobs while reading instead of up front
+    pub fn lazy_index(self) -> bool {
+        self.is_low_memory()
+    }
+
+    /// Largest file read whole into memory where it can't be mapped (`None`: any)
+    pub fn max_buffered_file(self) -> Option<u64> {
+        match self {
+            MemoryMode::Standard => None,
+            MemoryMode::LowMemory => Some(Self::LOW_MEMORY_BUFFER_LIMIT),
+        }
+    }
+
+    /// Fail with `BlobError::MemoryLimit` if a file of `len` bytes may not be buffered whole
+    pub(crate) fn check_buffered(self, len: u64) -> Result<()> {
+        match self.max_buffered_file() {
+            Some(limit) if len > limit => Err(BlobError::MemoryLimit { needed: len, limit }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::indexed_reader::ElementFilter;
+    use crate::io::reader::Reader;
+    use pretty_assertions::assert_eq;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::io::Cursor;
+
+    /// Passes allocations to the system allocator, tracking the live and
+    /// peak bytes of threads inside `capped`
+    struct TrackingAllocator;
+
+    thread_local! {
+        /// Live and peak bytes allocated by this thread while capped
+        static TRACKED: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+    }
+
+    // SAFETY: every call is forwarded unchanged to `System`; the bookkeeping
+    // only touches a const-initialized thread local, which doesn't allocate.
+    unsafe impl GlobalAlloc for TrackingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                let _ = TRACKED.try_with(|tracked| {
+                    if let Some((live, peak)) = tracked.get() {
+                        let live = live + layout.size();
+                        tracked.set(Some((live, peak.max(live))));
+                    }
+                });
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = TRACKED.try_with(|tracked| {
+                if let Some((live, peak)) = tracked.get() {
+                    tracked.set(Some((live.saturating_sub(layout.size()), peak)));
+                }
+            });
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+    /// Run `f`, failing if this thread's allocations peak above `cap` bytes
+    fn capped<T>(cap: usize, f: impl FnOnce() -> T) -> T {
+        TRACKED.with(|tracked| tracked.set(Some((0, 0))));
+        let result = f();
+        let (_, peak) = TRACKED.with(|tracked| tracked.replace(None)).unwrap();
+        assert!(peak <= cap, "peaked at {peak} bytes, cap is {cap}");
+        result
+    }
+
+    #[test]
+    fn test_low_memory_reader_under_cap() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(3).grid_size(800).block_size(100).relation_count(20)
+            .compression(crate::io::writer::BlobCompression::Raw).write_to(&mut data).unwrap();
+        let filter = ElementFilter::all();
+        let expected = Reader::new(Cursor::new(data.clone())).unwrap().count_filtered(&filter).unwrap();
+
+        // The largest decoded block plus the index, well under the size of the raw file
+        let cap = data.len() / 2;
+        let stats = capped(cap, || {
+            let mut reader = Reader::with_memory_mode(Cursor::new(&data[..]), MemoryMode::LowMemory).unwrap();
+            reader.count_filtered(&filter).unwrap()
+        });
+        assert_eq!(stats.elements_processed, expected.elements_processed);
+        assert_eq!(stats.nodes_processed, planet.nodes);
+    }
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(MemoryMode::default().is_low_memory(), CONSTRAINED_TARGET || cfg!(feature = "low-memory"));
+        assert_eq!(MemoryMode::detect(u64::MAX), MemoryMode::LowMemory);
+        assert_eq!(MemoryMode::LowMemory.profile(), Profile::LowMemory);
+        assert!(MemoryMode::Standard.check_buffered(u64::MAX).is_ok());
+        assert!(MemoryMode::LowMemory.check_buffered(MemoryMode::LOW_MEMORY_BUFFER_LIMIT).is_ok());
+        match MemoryMode::LowMemory.check_buffered(MemoryMode::LOW_MEMORY_BUFFER_LIMIT + 1) {
+            Err(BlobError::MemoryLimit { needed, limit }) => assert_eq!((needed, limit), (limit + 1, MemoryMode::LOW_MEMORY_BUFFER_LIMIT)),
+            other => panic!("expected MemoryLimit, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+use crate::blocks::primitives::info::Info;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::timestamp::TimestampMillis;
+
+/// Predicates on element metadata (`Info`), part of `ElementFilter`
+///
+/// All set predicates must hold. Elements without metadata fail every
+/// predicate except `visible_only`, and count as visible.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataFilter {
+    /// Lowest version kept
+    pub min_version: Option<i32>,
+    /// Highest version kept
+    pub max_version: Option<i32>,
+    /// Keep elements last modified strictly after this time
+    pub modified_after: Option<TimestampMillis>,
+    /// Keep elements last modified strictly before this time
+    pub modified_before: Option<TimestampMillis>,
+    /// Inclusive ranges of changeset ids; empty means any changeset
+    pub changeset_ranges: Vec<(i64, i64)>,
+    /// Keep elements by any of these user ids or `users`; both empty means any user
+    pub uids: HashSet<i32>,
+    /// User names, matched like `uids`
+    pub users: HashSet<String>,
+    /// Drop elements marked deleted (`visible == false`), as in history files
+    pub visible_only: bool,
+}
+
+impl MetadataFilter {
+    /// Whether no predicate is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether an element with `info` from a block with `date_granularity`
+    /// and `strings` passes
+    pub fn matches(&self, info: Option<&Info>, date_granularity: i32, strings: &StringTable) -> bool {
+        let Some(info) = info else {
+            return self.is_empty() || *self == Self { visible_only: true, ..Self::default() };
+        };
+        let timestamp = TimestampMillis::from_raw(info.timestamp, date_granularity);
+        (info.visible || !self.visible_only)
+            && self.min_version.is_none_or(|min| info.version >= min)
+            && self.max_version.is_none_or(|max| info.version <= max)
+            && self.modified_after.is_none_or(|after| timestamp > after)
+            && self.modified_before.is_none_or(|before| timestamp < before)
+            && (self.changeset_ranges.is_empty() || self.changeset_ranges.iter().any(|(min, max)| (*min..=*max).contains(&info.changeset)))
+            && ((self.uids.is_empty() && self.users.is_empty())
+                || self.uids.contains(&info.uid)
+                || strings.get_string(info.user_sid as usize).is_some_and(|user| self.users.contains(user)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_predicates() {
+        let mut strings = StringTable::new();
+        let alice = strings.add_string("alice".to_string()) as u32;
+        // Timestamps in seconds, as with a date granularity of 1000
+        let info = Info { version: 3, timestamp: 1_000, changeset: 42, uid: 7, user_sid: alice, visible: true };
+        let matches = |filter: MetadataFilter, info: Option<&Info>| filter.matches(info, 1000, &strings);
+
+        assert!(matches(MetadataFilter::default(), None));
+        assert!(matches(MetadataFilter { visible_only: true, ..Default::default() }, None));
+        assert!(!matches(MetadataFilter { min_version: Some(1), ..Default::default() }, None));
+
+        assert!(matches(MetadataFilter { min_version: Some(3), max_version: Some(3), ..Default::default() }, Some(&info)));
+        assert!(!matches(MetadataFilter { min_version: Some(4), ..Default::default() }, Some(&info)));
+        assert!(matches(MetadataFilter { modified_after: Some(TimestampMillis::from_secs(999)), ..Default::default() }, Some(&info)));
+        assert!(!matches(MetadataFilter { modified_after: Some(TimestampMillis::from_secs(1_000)), ..Default::default() }, Some(&info)));
+        assert!(!matches(MetadataFilter { modified_before: Some(TimestampMillis::from_secs(1_000)), ..Default::default() }, Some(&info)));
+        assert!(matches(MetadataFilter { changeset_ranges: vec![(1, 10), (40, 50)], ..Default::default() }, Some(&info)));
+        assert!(!matches(MetadataFilter { changeset_ranges: vec![(1, 10)], ..Default::default() }, Some(&info)));
+        assert!(matches(MetadataFilter { uids: HashSet::from([1]), users: HashSet::from(["alice".to_string()]), ..Default::default() }, Some(&info)));
+        assert!(!matches(MetadataFilter { uids: HashSet::from([1]), users: HashSet::from(["bob".to_string()]), ..Default::default() }, Some(&info)));
+
+        let deleted = Info { visible: false, ..info };
+        assert!(matches(MetadataFilter::default(), Some(&deleted)));
+        assert!(!matches(MetadataFilter { visible_only: true, ..Default::default() }, Some(&deleted)));
+    }
+}
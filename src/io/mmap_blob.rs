@@ -1,15 +1,11 @@
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use std::sync::Arc;
-use bytes::Bytes;
-use crate::io::blob::{Blob, BlobType, BlobHeader, BlobData, BlobError, Result};
+use crate::io::blob::{checked_offset, checked_usize, Blob, BlobType, BlobHeader, BlobError, Result};
+use crate::io::id_ranges::ElementId;
 use crate::io::indexed_reader::{BlobIndex, ElementFilter, ElementCounts, IndexStatistics};
-
-#[cfg(all(unix, feature = "mmap"))]
-use std::os::unix::fs::FileExt;
-#[cfg(all(unix, feature = "mmap"))]
-use std::os::unix::io::AsRawFd;
+use crate::io::mapped::MappedRegion;
+use crate::io::memory::MemoryMode;
 
 /// Memory-mapped OSM PBF file reader providing zero-copy blob access
 /// 
@@ -17,7 +13,7 @@ use std::os::unix::io::AsRawFd;
 /// Perfect for enterprise event sourcing, streaming analytics, and ETL pipelines.
 pub struct MmapBlobReader {
     /// Memory-mapped file data
-    mmap: Arc<MmapData>,
+    mmap: Arc<MappedRegion>,
     /// Cached blob index for fast random access
     blob_index: Vec<BlobIndex>,
     /// Header blob (if any)
@@ -26,126 +22,6 @@ pub struct MmapBlobReader {
     file_size: u64,
 }
 
-/// Wrapper around memory-mapped data with safety abstractions
-struct MmapData {
-    data: *const u8,
-    len: usize,
-    #[allow(dead_code)]
-    file: File, // Keep file alive for mmap validity
-}
-
-unsafe impl Send for MmapData {}
-unsafe impl Sync for MmapData {}
-
-impl MmapData {
-    /// Create new memory-mapped data from file
-    fn new(mut file: File) -> Result<Self> {
-        let metadata = file.metadata().map_err(BlobError::Io)?;
-        let len = metadata.len() as usize;
-        
-        if len == 0 {
-            return Ok(Self {
-                data: std::ptr::null(),
-                len: 0,
-                file,
-            });
-        }
-        
-        #[cfg(all(unix, feature = "mmap"))]
-        {
-            // Use mmap on Unix systems
-            let data = unsafe {
-                libc::mmap(
-                    std::ptr::null_mut(),
-                    len,
-                    libc::PROT_READ,
-                    libc::MAP_PRIVATE,
-                    file.as_raw_fd(),
-                    0,
-                )
-            };
-            
-            if data == libc::MAP_FAILED {
-                return Err(BlobError::Io(std::io::Error::last_os_error()));
-            }
-            
-            Ok(Self {
-                data: data as *const u8,
-                len,
-                file,
-            })
-        }
-        
-        #[cfg(not(all(unix, feature = "mmap")))]
-        {
-            // Fallback: read entire file into memory (less efficient but portable)
-            let mut buffer = Vec::with_capacity(len);
-            file.seek(SeekFrom::Start(0)).map_err(BlobError::Io)?;
-            file.read_to_end(&mut buffer).map_err(BlobError::Io)?;
-            
-            let boxed = buffer.into_boxed_slice();
-            let data = Box::into_raw(boxed) as *const u8;
-            
-            Ok(Self {
-                data,
-                len,
-                file,
-            })
-        }
-    }
-    
-    /// Get a slice of the mapped data at the given offset and length
-    /// 
-    /// # Safety
-    /// This function is safe because:
-    /// - We validate bounds before dereferencing
-    /// - The mmap is kept alive as long as MmapData exists
-    /// - We use read-only mapping
-    fn get_slice(&self, offset: usize, len: usize) -> Result<&[u8]> {
-        if offset.saturating_add(len) > self.len {
-            return Err(BlobError::InvalidFormat(
-                format!("Offset {} + length {} exceeds file size {}", offset, len, self.len)
-            ));
-        }
-        
-        if self.data.is_null() {
-            return Ok(&[]);
-        }
-        
-        unsafe {
-            Ok(std::slice::from_raw_parts(self.data.add(offset), len))
-        }
-    }
-    
-    /// Get bytes at offset without copying (zero-copy)
-    fn get_bytes(&self, offset: usize, len: usize) -> Result<Bytes> {
-        let slice = self.get_slice(offset, len)?;
-        // Create Bytes from slice - this will clone the data, but it's minimal overhead
-        // for the safety guarantees we get
-        Ok(Bytes::copy_from_slice(slice))
-    }
-}
-
-impl Drop for MmapData {
-    fn drop(&mut self) {
-        if !self.data.is_null() && self.len > 0 {
-            #[cfg(all(unix, feature = "mmap"))]
-            unsafe {
-                libc::munmap(self.data as *mut libc::c_void, self.len);
-            }
-            
-            #[cfg(not(all(unix, feature = "mmap")))]
-            unsafe {
-                // Free the manually allocated memory on non-Unix systems
-                let _ = Box::from_raw(std::slice::from_raw_parts_mut(
-                    self.data as *mut u8, 
-                    self.len
-                ));
-            }
-        }
-    }
-}
-
 impl MmapBlobReader {
     /// Create a new memory-mapped reader from a file path
     /// 
@@ -164,10 +40,16 @@ impl MmapBlobReader {
     
     /// Create a new memory-mapped reader from an open file
     pub fn from_file(file: File) -> Result<Self> {
+        Self::from_file_with_memory_mode(file, MemoryMode::default())
+    }
+    
+    /// Create a new memory-mapped reader, refusing to fall back to reading
+    /// the whole file into memory beyond what `mode` allows
+    pub fn from_file_with_memory_mode(file: File, mode: MemoryMode) -> Result<Self> {
         let metadata = file.metadata().map_err(BlobError::Io)?;
         let file_size = metadata.len();
         
-        let mmap = Arc::new(MmapData::new(file)?);
+        let mmap = Arc::new(MappedRegion::with_memory_mode(file, mode)?);
         let mut reader = Self {
             mmap,
             blob_index: Vec::new(),
@@ -188,10 +70,13 @@ impl MmapBlobReader {
                 Some((header, blob_size)) => {
                     let index_entry = BlobIndex {
                         offset: current_offset,
+                        header_size: 0,
                         size: blob_size,
                         blob_type: header.blob_type.clone(),
-                        id_range: None, // Will be filled when we parse the blob data
+                        id_ranges: None, // Will be filled when we parse the blob data
                         element_counts: ElementCounts::default(),
+                        bbox: None,
+                        key_presence: None,
                     };
                     
                     // Store header blob separately
@@ -202,7 +87,7 @@ impl MmapBlobReader {
                     self.blob_index.push(index_entry);
                     
                     // Move to next blob: 4 bytes for size + blob data size
-                    current_offset += 4 + blob_size as u64;
+                    current_offset = checked_offset(current_offset, 4 + blob_size)?;
                 }
                 None => break, // End of file
             }
@@ -212,27 +97,14 @@ impl MmapBlobReader {
     }
     
     /// Read blob header at specific offset (for indexing)
-    fn read_blob_header_at_offset(&self, offset: u64) -> Result<Option<(BlobHeader, u32)>> {
-        if offset + 4 > self.file_size {
+    fn read_blob_header_at_offset(&self, offset: u64) -> Result<Option<(BlobHeader, u64)>> {
+        let Some((_, blob_size)) = locate_blob(&self.mmap, self.file_size, offset)? else {
             return Ok(None); // End of file
-        }
-        
-        // Read blob size (4 bytes, big-endian)
-        let size_bytes = self.mmap.get_slice(offset as usize, 4)?;
-        let blob_size = u32::from_be_bytes([
-            size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]
-        ]);
-        
-        // Validate blob size
-        if offset + 4 + blob_size as u64 > self.file_size {
-            return Err(BlobError::InvalidFormat(
-                format!("Blob at offset {} extends beyond file end", offset)
-            ));
-        }
+        };
         
         // For now, create a simplified header
         // In full implementation, this would parse the actual protobuf header
-        let header = BlobHeader::new(BlobType::OSMData, blob_size);
+        let header = BlobHeader::new(BlobType::OSMData, blob_size as u32);
         
         Ok(Some((header, blob_size)))
     }
@@ -256,32 +128,7 @@ impl MmapBlobReader {
     /// 
     /// This is the core high-performance method - no data copying until absolutely necessary
     pub fn read_blob_at_offset(&self, offset: u64) -> Result<Option<Blob>> {
-        if offset + 4 > self.file_size {
-            return Ok(None);
-        }
-        
-        // Read blob size (zero-copy)
-        let size_bytes = self.mmap.get_slice(offset as usize, 4)?;
-        let blob_size = u32::from_be_bytes([
-            size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]
-        ]);
-        
-        // Validate size
-        if offset + 4 + blob_size as u64 > self.file_size {
-            return Err(BlobError::InvalidFormat(
-                format!("Blob at offset {} extends beyond file end", offset)
-            ));
-        }
-        
-        // Get blob data (zero-copy until Bytes creation)
-        let blob_data = self.mmap.get_bytes(
-            (offset + 4) as usize, 
-            blob_size as usize
-        )?;
-        
-        // Create blob with the data
-        let blob = Blob::new_raw(BlobType::OSMData, blob_data, offset)?;
-        Ok(Some(blob))
+        read_blob(&self.mmap, self.file_size, offset)
     }
     
     /// Read blob by index position
@@ -307,7 +154,8 @@ impl MmapBlobReader {
             match blob_index.blob_type {
                 BlobType::OSMHeader => stats.header_blobs += 1,
                 BlobType::OSMData => stats.data_blobs += 1,
-                BlobType::Unknown(_) => stats.unknown_blobs += 1,
+                BlobType::OSMChange => stats.change_blobs += 1,
+                BlobType::ZstdDictionary | BlobType::Unknown(_) => stats.unknown_blobs += 1,
             }
             
             stats.total_nodes += blob_index.element_counts.nodes as u64;
@@ -321,33 +169,22 @@ impl MmapBlobReader {
     }
     
     /// Find blobs that potentially contain elements in the given ID range
-    pub fn find_blobs_for_id_range(&self, min_id: i64, max_id: i64) -> Vec<usize> {
+    pub fn find_blobs_for_id_range<I: ElementId>(&self, min_id: I, max_id: I) -> Vec<usize> {
         self.blob_index
             .iter()
             .enumerate()
-            .filter_map(|(index, blob)| {
-                if let Some((blob_min, blob_max)) = blob.id_range {
-                    // Check if ranges overlap
-                    if blob_min <= max_id && blob_max >= min_id {
-                        Some(index)
-                    } else {
-                        None
-                    }
-                } else {
-                    // If we don't know the range, include it to be safe
-                    Some(index)
-                }
-            })
+            // If we don't know the ranges, include the blob to be safe
+            .filter(|(_, blob)| blob.id_ranges.is_none_or(|ranges| ranges.overlaps(min_id, max_id)))
+            .map(|(index, _)| index)
             .collect()
     }
     
     /// Get raw slice of file data at offset (advanced usage)
     /// 
-    /// # Safety
-    /// The returned slice is valid as long as the MmapBlobReader exists.
+    /// The returned slice borrows from the reader, so it can't outlive the mapping.
     /// This is a zero-copy operation for maximum performance.
-    pub fn get_raw_slice(&self, offset: usize, len: usize) -> Result<&[u8]> {
-        self.mmap.get_slice(offset, len)
+    pub fn get_raw_slice(&self, offset: u64, len: u64) -> Result<&[u8]> {
+        self.mmap.get_slice(checked_usize(offset)?, checked_usize(len)?)
     }
     
     /// Get file size
@@ -355,6 +192,15 @@ impl MmapBlobReader {
         self.file_size
     }
     
+    /// Check whether the file is actually memory-mapped
+    ///
+    /// Returns false when built with `--cfg miri` or the `pure-safe` feature,
+    /// or on targets other than Unix and Windows, where the file is read into
+    /// memory instead.
+    pub fn is_memory_mapped(&self) -> bool {
+        self.mmap.is_memory_mapped()
+    }
+    
     /// Check if this reader supports parallel access
     /// 
     /// Memory-mapped readers are inherently parallel-safe for reading
@@ -363,6 +209,42 @@ impl MmapBlobReader {
     }
 }
 
+/// Locate the payload of the length-prefixed blob at `offset`
+///
+/// Returns `None` past the end of the file, otherwise the payload offset and size.
+fn locate_blob(mmap: &MappedRegion, file_size: u64, offset: u64) -> Result<Option<(u64, u64)>> {
+    let data_offset = checked_offset(offset, 4)?;
+    if data_offset > file_size {
+        return Ok(None);
+    }
+    
+    // Read blob size (4 bytes, big-endian)
+    let size_bytes = mmap.get_slice(checked_usize(offset)?, 4)?;
+    let blob_size = u32::from_be_bytes([
+        size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]
+    ]) as u64;
+    
+    // Validate blob size
+    if checked_offset(data_offset, blob_size)? > file_size {
+        return Err(BlobError::InvalidFormat(
+            format!("Blob at offset {} extends beyond file end", offset)
+        ));
+    }
+    
+    Ok(Some((data_offset, blob_size)))
+}
+
+/// Read the blob at `offset`; shared by the sequential and parallel readers
+fn read_blob(mmap: &MappedRegion, file_size: u64, offset: u64) -> Result<Option<Blob>> {
+    let Some((data_offset, blob_size)) = locate_blob(mmap, file_size, offset)? else {
+        return Ok(None);
+    };
+    
+    let blob_data = mmap.get_bytes(checked_usize(data_offset)?, checked_usize(blob_size)?)?;
+    let blob = Blob::new_raw(BlobType::OSMData, blob_data, offset)?;
+    Ok(Some(blob))
+}
+
 /// Iterator for streaming filtered blobs from memory-mapped file
 pub struct MmapFilteredBlobIterator<'a> {
     reader: &'a MmapBlobReader,
@@ -391,7 +273,7 @@ impl<'a> Iterator for MmapFilteredBlobIterator<'a> {
             // Apply filter logic (same as IndexedReader)
             let should_include = match blob_index.blob_type {
                 BlobType::OSMHeader => true, // Always include headers
-                BlobType::OSMData => {
+                BlobType::OSMData | BlobType::OSMChange => {
                     // Check if this blob might contain elements we're interested in
                     let has_relevant_elements = 
                         (self.filter.include_nodes && blob_index.element_counts.nodes > 0) ||
@@ -401,7 +283,8 @@ impl<'a> Iterator for MmapFilteredBlobIterator<'a> {
                     
                     has_relevant_elements
                 }
-                BlobType::Unknown(_) => false, // Skip unknown types by default
+                // Dictionaries are loaded by the reader; unknown types are skipped
+                BlobType::ZstdDictionary | BlobType::Unknown(_) => false,
             };
             
             if should_include {
@@ -422,7 +305,7 @@ impl<'a> Iterator for MmapFilteredBlobIterator<'a> {
 /// Multiple threads can safely read different regions of the memory-mapped file
 #[derive(Clone)]
 pub struct ParallelMmapBlobReader {
-    mmap: Arc<MmapData>,
+    mmap: Arc<MappedRegion>,
     blob_index: Arc<Vec<BlobIndex>>,
     file_size: u64,
 }
@@ -449,24 +332,7 @@ impl ParallelMmapBlobReader {
     
     /// Read blob at offset (thread-safe)
     pub fn read_blob_at_offset(&self, offset: u64) -> Result<Option<Blob>> {
-        if offset + 4 > self.file_size {
-            return Ok(None);
-        }
-        
-        // Read blob size (zero-copy, thread-safe)
-        let size_bytes = self.mmap.get_slice(offset as usize, 4)?;
-        let blob_size = u32::from_be_bytes([
-            size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]
-        ]);
-        
-        // Get blob data (zero-copy, thread-safe)
-        let blob_data = self.mmap.get_bytes(
-            (offset + 4) as usize, 
-            blob_size as usize
-        )?;
-        
-        let blob = Blob::new_raw(BlobType::OSMData, blob_data, offset)?;
-        Ok(Some(blob))
+        read_blob(&self.mmap, self.file_size, offset)
     }
     
     /// Get blob count
@@ -515,6 +381,33 @@ mod tests {
         assert_eq!(blob.raw_size(), 100);
     }
     
+    #[test]
+    #[cfg(all(unix, not(feature = "pure-safe")))] // the safe fallback would read 4 GiB
+    fn test_offsets_beyond_4gib() {
+        use std::io::{Seek, SeekFrom};
+        
+        // Sparse file: one blob spanning the first 4 GiB, then a small blob past it
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        temp_file.as_file().set_len(4 + u32::MAX as u64).unwrap();
+        temp_file.seek(SeekFrom::End(0)).unwrap();
+        temp_file.write_all(&3u32.to_be_bytes()).unwrap();
+        temp_file.write_all(&[1, 2, 3]).unwrap();
+        temp_file.flush().unwrap();
+        
+        let reader = MmapBlobReader::from_file(temp_file.reopen().unwrap()).unwrap();
+        assert_eq!(reader.blob_count(), 2);
+        assert_eq!(reader.get_blob_index(1).unwrap().offset, (1 << 32) + 3);
+        
+        let blob = reader.read_blob_by_index(1).unwrap().unwrap();
+        assert_eq!(blob.raw_size(), 3);
+        assert_eq!(reader.get_raw_slice((1 << 32) + 7, 3).unwrap(), &[1, 2, 3]);
+        
+        let parallel_reader = ParallelMmapBlobReader::from_reader(&reader);
+        assert_eq!(parallel_reader.read_blob_by_index(1).unwrap().unwrap().raw_size(), 3);
+        assert!(parallel_reader.read_blob_at_offset((1 << 32) + 4).is_err());
+    }
+    
     #[test]
     fn test_parallel_reader() {
         let temp_file = NamedTempFile::new().unwrap();
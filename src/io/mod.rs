@@ -1,7 +1,64 @@
 pub mod blob;
+pub mod buffer_pool;
+pub mod capabilities;
+pub mod change;
+pub mod checkpoint;
+pub mod codec;
+pub(crate) mod decode;
+pub mod delta;
+pub mod dictionary;
+pub mod element_index;
+pub mod features;
+pub mod fingerprint;
+pub mod geometry;
+pub mod history;
+pub mod hot_keys;
+pub mod id_ranges;
+pub(crate) mod index_file;
 pub mod indexed_reader;
+pub(crate) mod inflate;
+pub(crate) mod instrument;
+pub mod integrity;
+pub mod live_stats;
+pub mod logging;
+pub mod manifest;
+pub mod max_ids;
+pub mod memory;
+pub mod metadata_filter;
+pub mod options;
+pub mod ordering;
+pub mod pagination;
+pub mod plan;
+pub mod polygon;
+pub mod privacy;
+pub mod profile;
+pub mod projection;
 pub mod reader;
+pub mod relations;
+pub mod retry;
+pub mod shards;
+pub mod spill;
+pub mod summary;
+pub(crate) mod stable;
+pub(crate) mod schedule;
+pub(crate) mod sequence;
+pub mod temp;
+pub mod transform;
+pub mod validate;
+pub mod wire;
+pub mod writer;
+pub mod zstd_dictionary;
 
+#[cfg(feature = "async")]
+pub mod async_reader;
+#[cfg(feature = "async")]
+pub mod byte_stream;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "mmap")]
+pub mod locations;
+#[cfg(feature = "mmap")]
+pub(crate) mod mapped;
 #[cfg(feature = "mmap")]
 pub mod mmap_blob;
 
@@ -0,0 +1,185 @@
+use crate::io::blob::MAX_BLOB_MESSAGE_SIZE;
+use crate::io::features::FeaturePolicy;
+use crate::io::logging::SkipLogLevel;
+use crate::io::memory::MemoryMode;
+use crate::io::profile::Profile;
+use crate::io::reader::ParallelConfig;
+use crate::io::retry::RetryPolicy;
+use crate::io::validate::{CorruptBlobPolicy, GroupPolicy};
+
+/// Every setting of a `Reader`, for `Reader::with_options`
+///
+/// Defaults match `Reader::new`. The `with_*` methods chain; `strict` and
+/// `lenient` set the validation policies together.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{MemoryMode, Reader, ReaderOptions, RetryPolicy};
+/// use std::fs::File;
+///
+/// let options = ReaderOptions::default()
+///     .with_memory_mode(MemoryMode::LowMemory)
+///     .with_retry_policy(RetryPolicy::network_filesystem())
+///     .with_max_blob_size(16 << 20)
+///     .with_decode_threads(4)
+///     .strict();
+/// let mut reader = Reader::with_options(File::open("map.osm.pbf")?, options)?;
+/// reader.par_for_each(&reader.parallel_config().clone(), |_| Ok(()))?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReaderOptions {
+    /// Index blobs while reading instead of before (see `Reader::lazy`)
+    pub lazy_index: bool,
+    /// Idle scratch buffers kept for blob reads
+    pub pooled_buffers: usize,
+    /// Largest blob read, stored or inflated; capped to `MAX_BLOB_MESSAGE_SIZE`
+    pub max_blob_size: usize,
+    /// Settings for the parallel methods, from `Reader::parallel_config`
+    pub parallel: ParallelConfig,
+    pub skip_log_level: SkipLogLevel,
+    pub retry_policy: RetryPolicy,
+    pub feature_policy: FeaturePolicy,
+    pub group_policy: GroupPolicy,
+    pub corrupt_blob_policy: CorruptBlobPolicy,
+    /// Decode without element metadata (see `Reader::set_skip_metadata`)
+    pub skip_metadata: bool,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        let mode = MemoryMode::default();
+        Self {
+            lazy_index: mode.lazy_index(),
+            pooled_buffers: mode.profile().pooled_buffers(),
+            max_blob_size: MAX_BLOB_MESSAGE_SIZE,
+            parallel: mode.profile().parallel_config(),
+            skip_log_level: SkipLogLevel::default(),
+            retry_policy: RetryPolicy::default(),
+            feature_policy: FeaturePolicy::default(),
+            group_policy: GroupPolicy::default(),
+            corrupt_blob_policy: CorruptBlobPolicy::default(),
+            skip_metadata: false,
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Index, buffering and parallelism for `mode`, as `Reader::with_memory_mode`
+    pub fn with_memory_mode(self, mode: MemoryMode) -> Self {
+        Self { lazy_index: mode.lazy_index(), ..self.with_profile(mode.profile()) }
+    }
+
+    /// Buffering and parallelism of a named profile
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.pooled_buffers = profile.pooled_buffers();
+        self.parallel = profile.parallel_config();
+        self
+    }
+
+    pub fn with_lazy_index(mut self, lazy_index: bool) -> Self {
+        self.lazy_index = lazy_index;
+        self
+    }
+
+    pub fn with_pooled_buffers(mut self, pooled_buffers: usize) -> Self {
+        self.pooled_buffers = pooled_buffers;
+        self
+    }
+
+    /// Refuse blobs larger than `max_blob_size` bytes, e.g. to bound memory
+    /// per decoding thread; can only lower the format's limit
+    pub fn with_max_blob_size(mut self, max_blob_size: usize) -> Self {
+        self.max_blob_size = max_blob_size.min(MAX_BLOB_MESSAGE_SIZE);
+        self
+    }
+
+    /// Threads decompressing and decoding blobs in the parallel methods
+    pub fn with_decode_threads(mut self, threads: usize) -> Self {
+        self.parallel.num_threads = Some(threads);
+        self
+    }
+
+    pub fn with_parallel_config(mut self, parallel: ParallelConfig) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn with_skip_log_level(mut self, skip_log_level: SkipLogLevel) -> Self {
+        self.skip_log_level = skip_log_level;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_feature_policy(mut self, feature_policy: FeaturePolicy) -> Self {
+        self.feature_policy = feature_policy;
+        self
+    }
+
+    pub fn with_group_policy(mut self, group_policy: GroupPolicy) -> Self {
+        self.group_policy = group_policy;
+        self
+    }
+
+    pub fn with_corrupt_blob_policy(mut self, corrupt_blob_policy: CorruptBlobPolicy) -> Self {
+        self.corrupt_blob_policy = corrupt_blob_policy;
+        self
+    }
+
+    pub fn with_skip_metadata(mut self, skip_metadata: bool) -> Self {
+        self.skip_metadata = skip_metadata;
+        self
+    }
+
+    /// Reject files requiring unsupported features and blocks mixing
+    /// element kinds in a group, and fail on blobs that don't decode
+    pub fn strict(self) -> Self {
+        self.with_feature_policy(FeaturePolicy::Reject)
+            .with_group_policy(GroupPolicy::Strict)
+            .with_corrupt_blob_policy(CorruptBlobPolicy::Fail)
+    }
+
+    /// Open files requiring unsupported features with a warning, decode
+    /// groups mixing element kinds, and skip blobs that don't decode
+    pub fn lenient(self) -> Self {
+        self.with_feature_policy(FeaturePolicy::Warn)
+            .with_group_policy(GroupPolicy::Lenient)
+            .with_corrupt_blob_policy(CorruptBlobPolicy::Skip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::reader::Reader;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_options_configure_reader() {
+        let defaults = ReaderOptions::default();
+        assert_eq!(defaults.lazy_index, MemoryMode::default().lazy_index());
+        assert_eq!(defaults.max_blob_size, MAX_BLOB_MESSAGE_SIZE);
+        assert_eq!((defaults.feature_policy, defaults.group_policy), (FeaturePolicy::Reject, GroupPolicy::Lenient));
+        assert_eq!(ReaderOptions::default().with_max_blob_size(usize::MAX).max_blob_size, MAX_BLOB_MESSAGE_SIZE);
+
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(2).grid_size(10).block_size(40).write_to(&mut data).unwrap();
+        let options = ReaderOptions::default().with_lazy_index(true).with_decode_threads(2).with_pooled_buffers(3).strict();
+        let mut reader = Reader::with_options(Cursor::new(data.clone()), options).unwrap();
+        assert_eq!(reader.group_policy(), GroupPolicy::Strict);
+        assert_eq!(reader.parallel_config().num_threads, Some(2));
+        assert!(reader.par_for_each(&reader.parallel_config().clone(), |_| Ok(())).unwrap().blobs_processed > 1);
+
+        // Blobs above the limit are skipped like unreadable ones; only the
+        // small header blob gets through
+        let mut reader = Reader::with_options(Cursor::new(data), ReaderOptions::default().with_max_blob_size(64)).unwrap();
+        let stats = reader.for_each(|_| Ok(())).unwrap();
+        assert!(stats.blobs_skipped > 0);
+        assert_eq!(stats.blobs_processed, 1);
+    }
+}
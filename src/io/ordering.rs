@@ -0,0 +1,120 @@
+use crate::blocks::primitives::member_type::MemberType;
+use crate::io::reader::{OsmElement, Provenance};
+
+/// An element out of `Sort.Type_then_ID` order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderViolation {
+    /// Where the element is stored
+    pub provenance: Provenance,
+    pub kind: MemberType,
+    pub id: i64,
+    /// The element before it in the file
+    pub previous_kind: MemberType,
+    pub previous_id: i64,
+}
+
+/// Result of `Reader::verify_ordering`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderingReport {
+    /// Whether the header declares `Sort.Type_then_ID`
+    pub declared: bool,
+    /// Nodes, ways and relations checked
+    pub elements_checked: u64,
+    /// All violations found, including those not kept in `violations`
+    pub violation_count: u64,
+    /// The first violations, up to the limit asked for
+    pub violations: Vec<OrderViolation>,
+}
+
+impl OrderingReport {
+    /// Whether the file is in type-then-id order
+    pub fn is_sorted(&self) -> bool {
+        self.violation_count == 0
+    }
+}
+
+/// Checks a stream of elements for `Sort.Type_then_ID` order: nodes, then
+/// ways, then relations, each by ascending id
+///
+/// Changesets are ignored. Repeated ids are violations unless
+/// `allow_repeats` is set, as for the versions of an element in history files.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OrderChecker {
+    allow_repeats: bool,
+    previous: Option<(MemberType, i64)>,
+}
+
+impl OrderChecker {
+    pub(crate) fn new(allow_repeats: bool) -> Self {
+        Self { allow_repeats, previous: None }
+    }
+
+    /// Record the next element, stored at `provenance`; a violation if it's
+    /// out of order with the one before
+    pub(crate) fn check(&mut self, element: &OsmElement, provenance: Provenance) -> Option<OrderViolation> {
+        let (kind, id) = match element {
+            OsmElement::Node(node) => (MemberType::Node, node.id.0),
+            OsmElement::Way(way) => (MemberType::Way, way.id.0),
+            OsmElement::Relation(relation) => (MemberType::Relation, relation.id.0),
+            OsmElement::ChangeSet(_) => return None,
+        };
+        self.check_id(kind, id, provenance)
+    }
+
+    /// `check` for an element known only by kind and id
+    pub(crate) fn check_id(&mut self, kind: MemberType, id: i64, provenance: Provenance) -> Option<OrderViolation> {
+        let (previous_kind, previous_id) = self.previous.replace((kind, id))?;
+        let in_order = (previous_kind as u8, previous_id) < (kind as u8, id)
+            || (self.allow_repeats && (previous_kind, previous_id) == (kind, id));
+        (!in_order).then_some(OrderViolation { provenance, kind, id, previous_kind, previous_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::header_block::HeaderBlock;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::{NodeId, WayId};
+    use crate::blocks::primitives::prelude::*;
+    use crate::blocks::string_table::StringTable;
+    use crate::io::reader::Reader;
+    use crate::io::writer::{BlockBuffer, PbfWriter};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn node(id: i64) -> OsmElement {
+        OsmElement::Node(Node { id: NodeId(id), keys: vec![], vals: vec![], info: None, location: LatLon::from_raw(0, 0) })
+    }
+
+    fn way(id: i64) -> OsmElement {
+        OsmElement::Way(Way { id: WayId(id), keys: vec![], vals: vec![], info: None, refs: vec![1], lat: vec![], lon: vec![] })
+    }
+
+    #[test]
+    fn test_verify_ordering() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(4).grid_size(10).block_size(30).relation_count(5).write_to(&mut data).unwrap();
+        let report = Reader::new(Cursor::new(data)).unwrap().verify_ordering(10).unwrap();
+        assert!(report.is_sorted(), "{report:?}");
+        assert!(report.elements_checked > 100);
+
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        for elements in [vec![node(1), node(3), node(2)], vec![way(5), way(5)], vec![node(9), way(6)]] {
+            let mut block = BlockBuffer::default();
+            for element in &elements {
+                block.push(element, &StringTable::default());
+            }
+            writer.write_primitive_block(&block.finish()).unwrap();
+        }
+        let report = Reader::new(Cursor::new(writer.into_inner())).unwrap().verify_ordering(2).unwrap();
+        assert!(!report.declared);
+        assert_eq!(report.elements_checked, 7);
+        assert_eq!(report.violation_count, 3);
+        let found: Vec<_> = report.violations.iter()
+            .map(|v| (v.provenance.blob_index, v.provenance.element_ordinal, v.kind, v.id, v.previous_kind, v.previous_id))
+            .collect();
+        assert_eq!(found, vec![(1, 2, MemberType::Node, 2, MemberType::Node, 3), (2, 1, MemberType::Way, 5, MemberType::Way, 5)]);
+    }
+}
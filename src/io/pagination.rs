@@ -0,0 +1,87 @@
+use crate::io::blob::{BlobError, Result};
+use crate::io::reader::OsmElement;
+
+/// Opaque continuation point for paginated reads
+///
+/// Points at an element position inside a blob (blob index + element offset
+/// within the blob's filtered elements). Stateless services hand it to clients
+/// as a token and resume from it on the next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct PageCursor {
+    blob_index: usize,
+    element_offset: usize,
+}
+
+impl PageCursor {
+    /// Prefix identifying the token format version
+    const TOKEN_PREFIX: &'static str = "v1.";
+
+    pub(crate) fn new(blob_index: usize, element_offset: usize) -> Self {
+        Self { blob_index, element_offset }
+    }
+
+    /// Blob the next page starts in
+    pub fn blob_index(&self) -> usize {
+        self.blob_index
+    }
+
+    /// Number of matching elements of that blob already returned
+    pub fn element_offset(&self) -> usize {
+        self.element_offset
+    }
+
+    /// Encode the cursor as an opaque, URL-safe token
+    pub fn to_token(&self) -> String {
+        format!("{}{:x}.{:x}", Self::TOKEN_PREFIX, self.blob_index, self.element_offset)
+    }
+
+    /// Decode a cursor from a token produced by `to_token`
+    pub fn from_token(token: &str) -> Result<Self> {
+        let invalid = || BlobError::InvalidFormat(format!("Invalid page token: {token:?}"));
+        let body = token.strip_prefix(Self::TOKEN_PREFIX).ok_or_else(invalid)?;
+        let (blob, offset) = body.split_once('.').ok_or_else(invalid)?;
+
+        Ok(Self {
+            blob_index: usize::from_str_radix(blob, 16).map_err(|_| invalid())?,
+            element_offset: usize::from_str_radix(offset, 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// One page of elements returned by `Reader::page`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Page {
+    /// Up to `limit` matching elements, in file order
+    pub elements: Vec<OsmElement>,
+    /// Where the next page starts; `None` once the file is exhausted
+    pub next: Option<PageCursor>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip() {
+        let cursor = PageCursor::new(1234, 56);
+        let token = cursor.to_token();
+        assert_eq!(token, "v1.4d2.38");
+        assert_eq!(PageCursor::from_token(&token).unwrap(), cursor);
+    }
+
+    #[test]
+    fn test_invalid_tokens() {
+        assert!(PageCursor::from_token("").is_err());
+        assert!(PageCursor::from_token("v1.zz.1").is_err());
+        assert!(PageCursor::from_token("v2.1.1").is_err());
+        assert!(PageCursor::from_token("v1.1").is_err());
+    }
+
+    #[test]
+    fn test_page_serializes_to_json() {
+        let page = Page { elements: Vec::new(), next: Some(PageCursor::new(2, 3)) };
+        let json = serde_json::to_string(&page).unwrap();
+        let back: Page = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.next, page.next);
+    }
+}
@@ -0,0 +1,362 @@
+use std::fmt;
+use crate::io::features::FileOrdering;
+use crate::io::indexed_reader::{BlobIndex, ElementFilter};
+
+/// Why a blob is skipped without being decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PruneReason {
+    /// Header or unknown blob, which holds no elements
+    BlobType,
+    /// The index says the blob holds none of the requested element types
+    ElementCounts,
+    /// The blob's id range doesn't overlap any requested id range
+    IdRange,
+    /// The blob's bounding box doesn't intersect the requested one
+    BoundingBox,
+    /// No element in the blob carries a requested hot key
+    TagKeys,
+}
+
+/// Decision for a single blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobPlan {
+    pub blob_index: usize,
+    pub offset: u64,
+    pub size: u64,
+    /// `None` when the blob must be decoded
+    pub pruned_by: Option<PruneReason>,
+}
+
+/// How a filter will execute against an index, from `ElementFilter::explain`
+///
+/// Printable with `{}` for a human-readable summary.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub blobs: Vec<BlobPlan>,
+    /// Filter parts that can only be applied after decoding
+    pub post_decode_filters: Vec<String>,
+    /// Pruning opportunities lost because the index lacks the metadata
+    pub missing_index_data: Vec<String>,
+    /// Blobs scanned for nodes and ways inside the box before `blobs`, when
+    /// the file order doesn't put nodes first (see `explain_with_ordering`)
+    pub member_blobs: Vec<usize>,
+}
+
+impl Plan {
+    /// Indices of the blobs that will be decoded, in file order
+    pub fn blobs_to_decode(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blobs.iter().filter(|b| b.pruned_by.is_none()).map(|b| b.blob_index)
+    }
+
+    /// Number of blobs skipped for the given reason
+    pub fn pruned_by(&self, reason: PruneReason) -> usize {
+        self.blobs.iter().filter(|b| b.pruned_by == Some(reason)).count()
+    }
+
+    /// Estimated bytes read and decoded by the query, member scan included
+    pub fn estimated_bytes(&self) -> u64 {
+        let members: u64 = self.member_blobs.iter().filter_map(|i| self.blobs.get(*i)).map(|b| b.size).sum();
+        members + self.blobs.iter().filter(|b| b.pruned_by.is_none()).map(|b| b.size).sum::<u64>()
+    }
+
+    /// Bytes in all indexed blobs
+    pub fn total_bytes(&self) -> u64 {
+        self.blobs.iter().map(|b| b.size).sum()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decoded = self.blobs_to_decode().count();
+        let total_bytes = self.total_bytes();
+        let percent = if total_bytes > 0 {
+            self.estimated_bytes() as f64 * 100.0 / total_bytes as f64
+        } else {
+            0.0
+        };
+
+        writeln!(f, "Decode {} of {} blobs ({} of {} bytes, {:.1}%)",
+            decoded, self.blobs.len(), self.estimated_bytes(), total_bytes, percent)?;
+        for (reason, label) in [
+            (PruneReason::BlobType, "blob type"),
+            (PruneReason::ElementCounts, "element counts"),
+            (PruneReason::IdRange, "id range"),
+            (PruneReason::BoundingBox, "bounding box"),
+            (PruneReason::TagKeys, "tag keys"),
+        ] {
+            writeln!(f, "  pruned by {label}: {}", self.pruned_by(reason))?;
+        }
+        if !self.member_blobs.is_empty() {
+            writeln!(f, "  scanned for bbox members first: {}", self.member_blobs.len())?;
+        }
+        for filter in &self.post_decode_filters {
+            writeln!(f, "  after decoding: {filter}")?;
+        }
+        for missing in &self.missing_index_data {
+            writeln!(f, "  not pruned: {missing}")?;
+        }
+        Ok(())
+    }
+}
+
+impl ElementFilter {
+    /// Describe how this filter will execute against a blob index
+    ///
+    /// Explains which blobs are skipped and why, and which parts of the filter
+    /// can't prune anything, e.g. because the index was built without element
+    /// counts or id ranges.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, IndexedReader, WayId};
+    /// use std::fs::File;
+    ///
+    /// let reader = IndexedReader::new(File::open("map.osm.pbf")?)?;
+    /// let filter = ElementFilter::ways_only(false).with_id_range(WayId(1000), WayId(2000));
+    /// println!("{}", filter.explain(reader.index()));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn explain(&self, index: &[BlobIndex]) -> Plan {
+        self.explain_with_ordering(index, &FileOrdering::default())
+    }
+
+    /// Describe how this filter will execute against the index of a file
+    /// declaring `ordering`
+    ///
+    /// Keeping ways and relations by the nodes they reference (see
+    /// `with_bbox`) needs every node inside the box before the first way. In
+    /// geographically sorted files nodes and ways are interleaved, so the
+    /// plan lists `member_blobs` to scan first: blobs holding nodes or ways
+    /// whose bounding box meets the query. The tiles of such files keep blob
+    /// boxes small, so with a bbox index that scan reads few blobs.
+    pub fn explain_with_ordering(&self, index: &[BlobIndex], ordering: &FileOrdering) -> Plan {
+        let mut plan = Plan::default();
+        let mut missing_counts = 0;
+        let mut missing_id_ranges = 0;
+        let mut missing_bboxes = 0;
+        let mut missing_key_presence = 0;
+
+        for (blob_index, blob) in index.iter().enumerate() {
+            let pruned_by = if !blob.blob_type.holds_elements() {
+                Some(PruneReason::BlobType)
+            } else if self.holds_bbox_members(blob) {
+                // Needed to decide which ways and relations reference the box
+                (!self.wanted_by_bbox(blob, &mut missing_bboxes)).then_some(PruneReason::BoundingBox)
+            } else if !self.wanted_by_counts(blob, &mut missing_counts) {
+                Some(PruneReason::ElementCounts)
+            } else if !self.wanted_by_id_range(blob, &mut missing_id_ranges) {
+                Some(PruneReason::IdRange)
+            } else if !self.wanted_by_bbox(blob, &mut missing_bboxes) {
+                Some(PruneReason::BoundingBox)
+            } else if !self.wanted_by_tag_keys(blob, &mut missing_key_presence) {
+                Some(PruneReason::TagKeys)
+            } else {
+                None
+            };
+
+            plan.blobs.push(BlobPlan {
+                blob_index,
+                offset: blob.offset,
+                size: blob.size,
+                pruned_by,
+            });
+        }
+
+        if ordering.interleaves_types() && self.bbox_dependencies() {
+            let mut missing = 0;
+            plan.member_blobs = index.iter().enumerate()
+                .filter(|(_, blob)| blob.blob_type.holds_elements())
+                .filter(|(_, blob)| {
+                    let counts = &blob.element_counts;
+                    counts.nodes > 0 || counts.ways > 0 || counts.is_unknown()
+                })
+                .filter(|(_, blob)| self.wanted_by_bbox(blob, &mut missing))
+                .map(|(blob_index, _)| blob_index)
+                .collect();
+        }
+        if missing_counts > 0 {
+            plan.missing_index_data.push(format!("{missing_counts} blobs have no element counts (deep index not built)"));
+        }
+        if missing_id_ranges > 0 {
+            plan.missing_index_data.push(format!("{missing_id_ranges} blobs have no id range"));
+        }
+        if missing_bboxes > 0 {
+            plan.missing_index_data.push(format!("{missing_bboxes} blobs have no bounding box (run build_bbox_index)"));
+        }
+        if missing_key_presence > 0 {
+            plan.missing_index_data.push(format!("{missing_key_presence} blobs have no tag key presence (register hot keys before build_deep_index)"));
+        }
+        if !self.tag_filters.is_empty() {
+            let mut keys: Vec<_> = self.tag_filters.keys().map(String::as_str).collect();
+            keys.sort_unstable();
+            plan.post_decode_filters.push(format!("tags {}", keys.join(", ")));
+        }
+        if !self.id_ranges.is_empty() {
+            plan.post_decode_filters.push("element ids".to_string());
+        }
+
+        plan
+    }
+
+    /// Whether the blob may hold nodes deciding `bbox` membership of ways and relations
+    fn holds_bbox_members(&self, blob: &BlobIndex) -> bool {
+        let counts = &blob.element_counts;
+        self.bbox_dependencies() && (counts.nodes > 0 || counts.is_unknown())
+    }
+
+    fn wanted_by_counts(&self, blob: &BlobIndex, missing: &mut usize) -> bool {
+        let counts = &blob.element_counts;
+        if counts.is_unknown() {
+            // Counts were never filled in; the blob may hold anything
+            *missing += 1;
+            return true;
+        }
+        (self.include_nodes && counts.nodes > 0)
+            || (self.include_ways && counts.ways > 0)
+            || (self.include_relations && counts.relations > 0)
+            || (self.include_changesets && counts.changesets > 0)
+    }
+
+    fn wanted_by_id_range(&self, blob: &BlobIndex, missing: &mut usize) -> bool {
+        if self.id_ranges.is_empty() {
+            return true;
+        }
+        if blob.id_ranges.is_none() {
+            *missing += 1;
+        }
+        self.may_match_ids(blob)
+    }
+
+    fn wanted_by_tag_keys(&self, blob: &BlobIndex, missing: &mut usize) -> bool {
+        if self.tag_filters.is_empty() {
+            return true;
+        }
+        match &blob.key_presence {
+            // Every requested key must be present; keys that aren't hot can't prune
+            Some(presence) => self.tag_filters.keys().all(|key| presence.contains(key) != Some(false)),
+            None => {
+                *missing += 1;
+                true
+            }
+        }
+    }
+
+    fn wanted_by_bbox(&self, blob: &BlobIndex, missing: &mut usize) -> bool {
+        let Some(query) = &self.bbox else {
+            return true;
+        };
+        match &blob.bbox {
+            Some(bbox) => bbox.intersects(query),
+            None => {
+                *missing += 1;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::bbox::BoundingBox;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::{NodeId, WayId};
+    use crate::io::blob::BlobType;
+    use crate::io::id_ranges::BlobIdRanges;
+    use crate::io::indexed_reader::ElementCounts;
+    use pretty_assertions::assert_eq;
+
+    /// A blob of nodes and ways, both spanning `id_range` if known
+    fn blob(offset: u64, blob_type: BlobType, counts: (u32, u32), id_range: Option<(i64, i64)>) -> BlobIndex {
+        BlobIndex {
+            offset,
+            header_size: 0,
+            size: 100,
+            blob_type,
+            id_ranges: id_range.map(|(min, max)| BlobIdRanges {
+                node: (counts.0 > 0).then_some((NodeId(min), NodeId(max))),
+                way: (counts.1 > 0).then_some((WayId(min), WayId(max))),
+                relation: None,
+            }),
+            element_counts: ElementCounts { nodes: counts.0, ways: counts.1, relations: 0, changesets: 0 },
+            bbox: None,
+            key_presence: None,
+        }
+    }
+
+    #[test]
+    fn test_prune_reasons() {
+        let index = vec![
+            blob(0, BlobType::OSMHeader, (0, 0), None),
+            blob(104, BlobType::OSMData, (10, 0), Some((1, 10))),
+            blob(208, BlobType::OSMData, (0, 5), Some((1, 5))),
+            blob(312, BlobType::OSMData, (0, 5), Some((100, 200))),
+            blob(416, BlobType::OSMData, (0, 0), None),
+        ];
+        let filter = ElementFilter::ways_only(false).with_id_range(WayId(1), WayId(50));
+        let plan = filter.explain(&index);
+
+        assert_eq!(plan.pruned_by(PruneReason::BlobType), 1);
+        assert_eq!(plan.pruned_by(PruneReason::ElementCounts), 1);
+        assert_eq!(plan.pruned_by(PruneReason::IdRange), 1);
+        assert_eq!(plan.blobs_to_decode().collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!(plan.estimated_bytes(), 200);
+        assert_eq!(plan.total_bytes(), 500);
+        assert_eq!(plan.missing_index_data.len(), 2);
+    }
+
+    #[test]
+    fn test_bbox_pruning() {
+        let mut inside = blob(0, BlobType::OSMData, (1, 0), None);
+        inside.bbox = Some(BoundingBox::from_point(LatLon::from_raw(10, 10)));
+        let mut outside = blob(104, BlobType::OSMData, (1, 0), None);
+        outside.bbox = Some(BoundingBox::from_point(LatLon::from_raw(500, 500)));
+
+        let mut query = BoundingBox::from_point(LatLon::from_raw(0, 0));
+        query.extend(LatLon::from_raw(20, 20));
+        let plan = ElementFilter::nodes_only().with_bbox(query).explain(&[inside, outside]);
+
+        assert_eq!(plan.blobs_to_decode().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(plan.pruned_by(PruneReason::BoundingBox), 1);
+    }
+
+    #[test]
+    fn test_tag_key_pruning() {
+        use crate::blocks::primitives::prelude::*;
+        use crate::blocks::string_table::StringTable;
+        use crate::io::hot_keys::HotKeys;
+        use crate::io::reader::OsmElement;
+
+        let hot_keys = HotKeys::new(&["highway", "building"]).unwrap();
+        let mut strings = StringTable::new();
+        let highway = strings.add_string("highway".to_string()) as u32;
+        let way = OsmElement::Way(Way { id: WayId(1), keys: vec![highway], vals: vec![highway], info: None, refs: vec![], lat: vec![], lon: vec![] });
+
+        let mut roads = blob(0, BlobType::OSMData, (0, 1), None);
+        roads.key_presence = Some(hot_keys.presence(&strings, std::slice::from_ref(&way)));
+        let mut empty = blob(104, BlobType::OSMData, (0, 1), None);
+        empty.key_presence = Some(hot_keys.presence(&strings, &[]));
+        let unknown = blob(208, BlobType::OSMData, (0, 1), None);
+        let index = [roads, empty, unknown];
+
+        let plan = ElementFilter::all().with_tag_key("highway".to_string()).explain(&index);
+        assert_eq!(plan.blobs_to_decode().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(plan.pruned_by(PruneReason::TagKeys), 1);
+
+        // Keys that aren't hot never prune
+        let plan = ElementFilter::all().with_tag_key("name".to_string()).explain(&index);
+        assert_eq!(plan.pruned_by(PruneReason::TagKeys), 0);
+    }
+
+    #[test]
+    fn test_display() {
+        let plan = ElementFilter::all()
+            .with_tag_key("highway".to_string())
+            .explain(&[blob(0, BlobType::OSMData, (0, 0), None)]);
+        let text = plan.to_string();
+
+        assert!(text.starts_with("Decode 1 of 1 blobs (100 of 100 bytes, 100.0%)"));
+        assert!(text.contains("after decoding: tags highway"));
+        assert!(text.contains("not pruned: 1 blobs have no element counts"));
+    }
+}
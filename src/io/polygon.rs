@@ -0,0 +1,237 @@
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::lat_lon::LatLon;
+use crate::formats::poly::PolyFile;
+use crate::io::blob::{BlobError, Result};
+
+/// Polygons with more edges than this get a latitude band index
+const INDEX_THRESHOLD: usize = 32;
+/// Average number of edges per latitude band of the index
+const EDGES_PER_BAND: usize = 4;
+
+/// Area bounded by rings, for point-in-polygon tests while decoding
+///
+/// Rings combine by the even-odd rule, as in osmium: a point is inside when
+/// an odd number of rings contain it, so holes cut out of the rings around
+/// them. Points outside the bounding box are rejected first; polygons with
+/// more than 32 edges also bucket their edges by latitude band, so a test
+/// only looks at the few edges crossing the point's latitude. Points on an
+/// edge may fall either way.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::formats::poly::PolyFile;
+/// use osm_pbf::{ElementFilter, Polygon};
+///
+/// let polygon = Polygon::from_poly(&PolyFile::read(std::fs::File::open("berlin.poly")?)?)?;
+/// let filter = ElementFilter::nodes_only().with_polygon(polygon);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polygon {
+    bbox: BoundingBox,
+    edges: Vec<Edge>,
+    bands: Option<EdgeBands>,
+}
+
+/// Ring segment in nanodegrees
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edge {
+    from: (i64, i64),
+    to: (i64, i64),
+}
+
+/// Edges overlapping each of equal-height latitude bands over the bbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EdgeBands {
+    min_lat: i64,
+    height: i64,
+    bands: Vec<Vec<u32>>,
+}
+
+impl Polygon {
+    /// Polygon from rings, closed or not; rings with fewer than three points
+    /// are dropped
+    pub fn new(rings: &[Vec<LatLon>]) -> Result<Self> {
+        let mut bbox = None;
+        let mut edges = Vec::new();
+        for ring in rings.iter().filter(|ring| ring.len() >= 3) {
+            for (from, to) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+                BoundingBox::extend_option(&mut bbox, *from);
+                if from != to {
+                    edges.push(Edge { from: (from.lat.0, from.lon.0), to: (to.lat.0, to.lon.0) });
+                }
+            }
+        }
+        let Some(bbox) = bbox else {
+            return Err(BlobError::InvalidFormat("Polygon without a ring of three points".to_string()));
+        };
+        let bands = (edges.len() > INDEX_THRESHOLD).then(|| EdgeBands::new(&edges, &bbox));
+        Ok(Self { bbox, edges, bands })
+    }
+
+    /// Polygon of the rings of a .poly file
+    pub fn from_poly(poly: &PolyFile) -> Result<Self> {
+        let rings: Vec<_> = poly.rings.iter().map(|ring| ring.points.clone()).collect();
+        Self::new(&rings)
+    }
+
+    /// Polygon of a GeoJSON `Polygon` or `MultiPolygon`, bare or as the
+    /// geometry of a `Feature` or of every feature of a `FeatureCollection`
+    #[cfg(feature = "json")]
+    pub fn from_geojson(geojson: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(geojson)
+            .map_err(|e| BlobError::InvalidFormat(format!("GeoJSON: {e}")))?;
+        let mut rings = Vec::new();
+        geojson_rings(&value, &mut rings)?;
+        Self::new(&rings)
+    }
+
+    /// Whether `point` lies inside the polygon
+    pub fn contains(&self, point: LatLon) -> bool {
+        if !self.bbox.contains(point) {
+            return false;
+        }
+        let (lat, lon) = (point.lat.0, point.lon.0);
+        let crossings = match &self.bands {
+            Some(bands) => bands.edges(lat).iter().filter(|&&edge| self.edges[edge as usize].crosses(lat, lon)).count(),
+            None => self.edges.iter().filter(|edge| edge.crosses(lat, lon)).count(),
+        };
+        crossings % 2 == 1
+    }
+
+    /// Extent of the rings
+    pub fn bbox(&self) -> BoundingBox {
+        self.bbox
+    }
+
+    /// Number of ring segments
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+}
+
+impl Edge {
+    /// Whether the edge crosses the parallel through the point, east of it
+    fn crosses(&self, lat: i64, lon: i64) -> bool {
+        let (y, x) = (lat as i128, lon as i128);
+        let (ay, ax, by, bx) = (self.from.0 as i128, self.from.1 as i128, self.to.0 as i128, self.to.1 as i128);
+        (ay > y) != (by > y) && ((x - ax) * (by - ay) < (bx - ax) * (y - ay)) == (by > ay)
+    }
+}
+
+impl EdgeBands {
+    fn new(edges: &[Edge], bbox: &BoundingBox) -> Self {
+        let count = (edges.len() / EDGES_PER_BAND).max(1);
+        let span = bbox.max_lat.0 as i128 - bbox.min_lat.0 as i128 + 1;
+        let height = (span / count as i128 + 1) as i64;
+        let mut index = Self { min_lat: bbox.min_lat.0, height, bands: vec![Vec::new(); count] };
+        for (i, edge) in edges.iter().enumerate() {
+            let (low, high) = (edge.from.0.min(edge.to.0), edge.from.0.max(edge.to.0));
+            for band in index.band(low)..=index.band(high) {
+                index.bands[band].push(i as u32);
+            }
+        }
+        index
+    }
+
+    fn band(&self, lat: i64) -> usize {
+        let band = (lat as i128 - self.min_lat as i128) / self.height as i128;
+        band.clamp(0, self.bands.len() as i128 - 1) as usize
+    }
+
+    /// Indices of the edges that may cross latitude `lat`
+    fn edges(&self, lat: i64) -> &[u32] {
+        &self.bands[self.band(lat)]
+    }
+}
+
+/// Collect the rings of a GeoJSON object
+#[cfg(feature = "json")]
+fn geojson_rings(value: &serde_json::Value, rings: &mut Vec<Vec<LatLon>>) -> Result<()> {
+    let error = |reason: &str| BlobError::InvalidFormat(format!("GeoJSON: {reason}"));
+    let coordinates = || value.get("coordinates").and_then(|c| c.as_array()).ok_or_else(|| error("missing coordinates"));
+    match value.get("type").and_then(|t| t.as_str()) {
+        Some("FeatureCollection") => {
+            let features = value.get("features").and_then(|f| f.as_array()).ok_or_else(|| error("missing features"))?;
+            for feature in features {
+                geojson_rings(feature, rings)?;
+            }
+        }
+        Some("Feature") => geojson_rings(value.get("geometry").ok_or_else(|| error("missing geometry"))?, rings)?,
+        Some("Polygon") => {
+            for ring in coordinates()? {
+                rings.push(geojson_ring(ring).ok_or_else(|| error("invalid ring"))?);
+            }
+        }
+        Some("MultiPolygon") => {
+            for polygon in coordinates()? {
+                for ring in polygon.as_array().ok_or_else(|| error("invalid polygon"))? {
+                    rings.push(geojson_ring(ring).ok_or_else(|| error("invalid ring"))?);
+                }
+            }
+        }
+        Some(other) => return Err(error(&format!("unsupported type {other}"))),
+        None => return Err(error("missing type")),
+    }
+    Ok(())
+}
+
+/// Points of a ring of `[lon, lat]` positions in degrees
+#[cfg(feature = "json")]
+fn geojson_ring(ring: &serde_json::Value) -> Option<Vec<LatLon>> {
+    ring.as_array()?.iter().map(|position| {
+        let (lon, lat) = (position.get(0)?.as_f64()?, position.get(1)?.as_f64()?);
+        LatLon::try_from_degrees(lat, lon).ok()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn point(lat: f64, lon: f64) -> LatLon {
+        LatLon::try_from_degrees(lat, lon).unwrap()
+    }
+
+    #[test]
+    fn test_contains_with_and_without_index() {
+        // A star of 40 points, large enough for the band index, with a square hole
+        let star: Vec<_> = (0..40).map(|i| {
+            let angle = i as f64 * std::f64::consts::PI / 20.0;
+            let radius = if i % 2 == 0 { 10.0 } else { 6.0 };
+            point(radius * angle.sin(), radius * angle.cos())
+        }).collect();
+        let hole = vec![point(-1.0, -1.0), point(-1.0, 1.0), point(1.0, 1.0), point(1.0, -1.0)];
+        let indexed = Polygon::new(&[star.clone(), hole.clone()]).unwrap();
+        assert_eq!(indexed.edge_count(), 44);
+        assert!(indexed.bands.is_some());
+
+        let points = [(0.0, 0.0), (3.0, 0.0), (0.0, -5.5), (9.5, 0.0), (6.5, 6.5), (12.0, 0.0), (0.0, 1.5), (-4.0, 4.0)];
+        let expected = [false, true, true, true, false, false, true, true];
+        for ((lat, lon), inside) in points.into_iter().zip(expected) {
+            let plain = Polygon { bands: None, ..indexed.clone() };
+            assert_eq!((indexed.contains(point(lat, lon)), plain.contains(point(lat, lon))), (inside, inside), "{lat}, {lon}");
+        }
+
+        let triangle = Polygon::new(&[vec![point(0.0, 0.0), point(0.0, 2.0), point(2.0, 0.0), point(0.0, 0.0)]]).unwrap();
+        assert!(triangle.bands.is_none());
+        assert!(triangle.contains(point(0.5, 0.5)) && !triangle.contains(point(1.5, 1.5)));
+        assert!(Polygon::new(&[vec![point(0.0, 0.0), point(1.0, 1.0)]]).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_geojson() {
+        let geojson = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {}, "geometry": {"type": "MultiPolygon", "coordinates": [
+                [[[0, 0], [4, 0], [4, 4], [0, 4], [0, 0]], [[1, 1], [2, 1], [2, 2], [1, 2], [1, 1]]]
+            ]}}
+        ]}"#;
+        let polygon = Polygon::from_geojson(geojson).unwrap();
+        assert_eq!(polygon.edge_count(), 8);
+        assert!(polygon.contains(point(3.0, 0.5)));
+        assert!(!polygon.contains(point(1.5, 1.5)));
+        assert!(Polygon::from_geojson(r#"{"type": "Point", "coordinates": [0, 0]}"#).is_err());
+    }
+}
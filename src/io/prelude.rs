@@ -1,9 +1,60 @@
-pub use crate::io::blob::{Blob, BlobHeader, BlobData, BlobType, BlobError, Result};
+pub use crate::io::blob::{Blob, BlobHeader, BlobData, BlobType, BlobError, BlobErrorKind, Result};
+pub use crate::io::buffer_pool::{BufferPool, BufferPoolStats, PooledBuffer};
+pub use crate::io::capabilities::{capabilities, Capabilities};
+pub use crate::io::change::{ChangeAction, ChangeElement};
+pub use crate::io::checkpoint::{Checkpoint, TimedRun};
+pub use crate::io::codec::{BlockDecoder, BlockEncoder, PbfBlockCodec, SkipMetadataCodec};
+pub use crate::io::delta::{delta_decode, delta_decoded, delta_encode, DeltaDecoded, DeltaEncoder, DeltaValue};
+pub use crate::io::dictionary::StringDictionary;
+pub use crate::io::element_index::ElementIndex;
+pub use crate::io::features::{unsupported_features, FeaturePolicy, FileOrdering, ReplicationInfo, HISTORICAL_INFORMATION, LOCATIONS_ON_WAYS, SUPPORTED_FEATURES};
+pub use crate::io::fingerprint::{Fingerprint, FingerprintBuilder};
+pub use crate::io::geometry::{DenseLocations, GeometryStats, NodeLocationCache, NodeLocationStore, SparseLocations};
+pub use crate::io::history::{ElementHistory, HistoryItem, HistoryIter};
+pub use crate::io::hot_keys::{HotKeys, KeyPresence};
+pub use crate::io::id_ranges::{BlobIdRanges, ElementId, IdRanges};
 pub use crate::io::indexed_reader::{
     IndexedReader, BlobIndex, ElementFilter, ElementCounts, IndexStatistics,
     FilteredBlobIterator
 };
-pub use crate::io::reader::{ParallelConfig, ProcessingStats};
+pub use crate::io::integrity::{IntegrityIssue, IntegrityReport, IssueKind};
+pub use crate::io::live_stats::{LiveSnapshot, LiveStats};
+pub use crate::io::logging::SkipLogLevel;
+pub use crate::io::manifest::{Manifest, ManifestEntry, Verification};
+pub use crate::io::max_ids::MaxIds;
+pub use crate::io::memory::{MemoryMode, CONSTRAINED_TARGET};
+pub use crate::io::metadata_filter::MetadataFilter;
+pub use crate::io::options::ReaderOptions;
+pub use crate::io::ordering::{OrderViolation, OrderingReport};
+pub use crate::io::pagination::{Page, PageCursor};
+pub use crate::io::plan::{BlobPlan, Plan, PruneReason};
+pub use crate::io::polygon::Polygon;
+pub use crate::io::privacy::{pseudonymize, PseudonymMap};
+pub use crate::io::profile::Profile;
+pub use crate::io::projection::{project_tags, TagProjection};
+pub use crate::io::reader::{CorruptBlob, ElementBatch, ElementIter, ParallelConfig, ProcessingStats, Provenance, StreamConfig};
+pub use crate::io::relations::{CyclePolicy, QualityReport, RelationGraph, RelationWalk};
+pub use crate::io::retry::RetryPolicy;
+pub use crate::io::shards::{Shard, ShardStorage};
+pub use crate::io::spill::{SpillConfig, SpillableElements, DEFAULT_MEMORY_BUDGET};
+pub use crate::io::summary::{CompressionStats, FileSummary};
+pub use crate::io::temp::{TempDir, TempDirPolicy, DEFAULT_GC_AGE};
+pub use crate::io::transform::{map_blocks, map_blocks_with_codecs, rewrite_tags, TagRewrite, TransformStats};
+pub use crate::io::validate::{CorruptBlobPolicy, GroupPolicy, StringPolicy, MAX_STRING_CHARS};
+pub use crate::io::wire::{decode_varint, encode_varint, zigzag_decode, zigzag_encode};
+pub use crate::io::writer::{BlobCompression, PbfWriter, RawBlobWriter, SizeEstimator, WriterStats};
+pub use crate::io::zstd_dictionary::ZstdDictionary;
 
+#[cfg(feature = "async")]
+pub use crate::io::async_reader::{AsyncElementStream, AsyncReader};
+#[cfg(feature = "async")]
+pub use crate::io::byte_stream::BlobByteStream;
+#[cfg(feature = "json")]
+pub use crate::io::json::{
+    write_json_lines, JsonElementType, ResolvedElement, ResolvedMember, ResolvedMeta,
+    JSON_SCHEMA_V1, JSON_SCHEMA_VERSION
+};
+#[cfg(feature = "mmap")]
+pub use crate::io::locations::{LocationStoreStats, MappedLocations, SparseLocationStore, SparseLocationWriter};
 #[cfg(feature = "mmap")]
 pub use crate::io::mmap_blob::{MmapBlobReader, MmapFilteredBlobIterator, ParallelMmapBlobReader};
\ No newline at end of file
@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Read, Write};
+use crate::blocks::primitives::prelude::*;
+use crate::io::blob::{BlobError, Result};
+use crate::io::transform::{map_blocks, TransformStats};
+
+/// First line of the text form of a pseudonym map
+const MAP_HEADER: &str = "# osm-pbf pseudonyms v1";
+
+/// Reversible mapping of user ids and changeset ids to pseudonyms
+///
+/// Pseudonyms are assigned from 1 upwards in order of first appearance, so
+/// the same input always gets the same pseudonyms. Keep the map separate from
+/// the shared output: anyone holding it can undo the pseudonymization. The
+/// map is written as plain text; to store it encrypted, pass an encrypting
+/// writer to `write_to`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PseudonymMap {
+    uids: HashMap<i32, i32>,
+    changesets: HashMap<i64, i64>,
+}
+
+impl PseudonymMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pseudonym of a user id, assigning a new one on first use
+    ///
+    /// uid 0 (anonymous or missing) stays 0.
+    pub fn uid(&mut self, uid: i32) -> i32 {
+        if uid == 0 {
+            return 0;
+        }
+        let next = self.uids.len() as i32 + 1;
+        *self.uids.entry(uid).or_insert(next)
+    }
+
+    /// Pseudonym of a changeset id, assigning a new one on first use
+    ///
+    /// Changeset 0 (missing) stays 0.
+    pub fn changeset(&mut self, changeset: i64) -> i64 {
+        if changeset == 0 {
+            return 0;
+        }
+        let next = self.changesets.len() as i64 + 1;
+        *self.changesets.entry(changeset).or_insert(next)
+    }
+
+    /// Original user id of a pseudonym
+    pub fn original_uid(&self, pseudonym: i32) -> Option<i32> {
+        self.uids.iter().find(|(_, p)| **p == pseudonym).map(|(uid, _)| *uid)
+    }
+
+    /// Original changeset id of a pseudonym
+    pub fn original_changeset(&self, pseudonym: i64) -> Option<i64> {
+        self.changesets.iter().find(|(_, p)| **p == pseudonym).map(|(changeset, _)| *changeset)
+    }
+
+    /// Number of user ids mapped so far
+    pub fn uid_count(&self) -> usize {
+        self.uids.len()
+    }
+
+    /// Number of changeset ids mapped so far
+    pub fn changeset_count(&self) -> usize {
+        self.changesets.len()
+    }
+
+    /// Replace user ids and changeset ids in a block with pseudonyms and
+    /// blank the user names
+    ///
+    /// User names are set to the empty string in the string table unless a
+    /// tag or role uses the same string.
+    pub fn pseudonymize_block(&mut self, block: &mut PrimitiveBlock) {
+        let mut user_strings = HashSet::new();
+        let mut other_strings = HashSet::new();
+
+        for group in &mut block.primitivegroup {
+            let infos = group.nodes.iter_mut().map(|n| (&mut n.info, &n.keys, &n.vals))
+                .chain(group.ways.iter_mut().map(|w| (&mut w.info, &w.keys, &w.vals)))
+                .chain(group.relations.iter_mut().map(|r| (&mut r.info, &r.keys, &r.vals)))
+                .chain(group.changesets.iter_mut().map(|c| (&mut c.info, &c.keys, &c.vals)));
+            for (info, keys, vals) in infos {
+                other_strings.extend(keys.iter().chain(vals).map(|s| *s as usize));
+                if let Some(info) = info {
+                    user_strings.insert(info.user_sid as usize);
+                    info.uid = self.uid(info.uid);
+                    info.changeset = self.changeset(info.changeset);
+                    info.user_sid = 0;
+                }
+            }
+            for relation in &group.relations {
+                other_strings.extend(relation.roles_sid.iter().map(|s| *s as usize));
+            }
+
+            if let Some(dense) = &mut group.dense {
+                other_strings.extend(dense.keys_vals.iter().map(|s| *s as usize));
+                if let Some(info) = &mut dense.denseinfo {
+                    self.pseudonymize_dense(info, &mut user_strings);
+                }
+            }
+        }
+
+        for index in user_strings.difference(&other_strings) {
+            if let Some(s) = block.stringtable.s.get_mut(*index).filter(|_| *index != 0) {
+                s.clear();
+            }
+        }
+    }
+
+    fn pseudonymize_dense(&mut self, info: &mut DenseInfo, user_strings: &mut HashSet<usize>) {
+        let (mut uid, mut mapped_uid) = (0i32, 0i32);
+        for delta in &mut info.uid {
+            uid = uid.wrapping_add(*delta);
+            let pseudonym = self.uid(uid);
+            *delta = pseudonym.wrapping_sub(mapped_uid);
+            mapped_uid = pseudonym;
+        }
+
+        let (mut changeset, mut mapped_changeset) = (0i64, 0i64);
+        for delta in &mut info.changeset {
+            changeset = changeset.wrapping_add(*delta);
+            let pseudonym = self.changeset(changeset);
+            *delta = pseudonym.wrapping_sub(mapped_changeset);
+            mapped_changeset = pseudonym;
+        }
+
+        let mut user_sid = 0i32;
+        for delta in &mut info.user_sid {
+            user_sid = user_sid.wrapping_add(*delta);
+            user_strings.insert(user_sid as usize);
+            *delta = 0;
+        }
+    }
+
+    /// Write the map as text, one `uid` or `changeset` line per mapped id
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "{MAP_HEADER}")?;
+        let mut uids: Vec<_> = self.uids.iter().collect();
+        uids.sort_unstable_by_key(|(_, pseudonym)| **pseudonym);
+        for (uid, pseudonym) in uids {
+            writeln!(writer, "uid {uid} {pseudonym}")?;
+        }
+        let mut changesets: Vec<_> = self.changesets.iter().collect();
+        changesets.sort_unstable_by_key(|(_, pseudonym)| **pseudonym);
+        for (changeset, pseudonym) in changesets {
+            writeln!(writer, "changeset {changeset} {pseudonym}")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read a map written by `write_to`, e.g. to continue with the same pseudonyms
+    pub fn read_from<R: BufRead>(reader: R) -> Result<Self> {
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref().map(str::trim) != Some(MAP_HEADER) {
+            return Err(BlobError::InvalidFormat("Missing pseudonym map header".to_string()));
+        }
+
+        let mut map = Self::new();
+        for (number, line) in lines.enumerate() {
+            let line = line?;
+            let invalid = || BlobError::InvalidFormat(format!("Invalid pseudonym map line {}: {line:?}", number + 2));
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => {}
+                ["uid", original, pseudonym] => {
+                    map.uids.insert(original.parse().map_err(|_| invalid())?, pseudonym.parse().map_err(|_| invalid())?);
+                }
+                ["changeset", original, pseudonym] => {
+                    map.changesets.insert(original.parse().map_err(|_| invalid())?, pseudonym.parse().map_err(|_| invalid())?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Copy a PBF stream with user ids and changeset ids replaced by pseudonyms
+/// from `map` and user names blanked
+///
+/// Write `map` to a separate file afterwards to keep the result reversible.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{pseudonymize, PseudonymMap};
+/// use std::fs::File;
+///
+/// let mut map = PseudonymMap::new();
+/// pseudonymize(File::open("in.osm.pbf")?, File::create("shared.osm.pbf")?, &mut map)?;
+/// map.write_to(File::create("pseudonyms.txt")?)?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn pseudonymize<R: Read, W: Write>(reader: R, writer: W, map: &mut PseudonymMap) -> Result<TransformStats> {
+    map_blocks(reader, writer, |mut block| {
+        map.pseudonymize_block(&mut block);
+        block
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::WayId;
+    use pretty_assertions::assert_eq;
+
+    fn block() -> PrimitiveBlock {
+        let mut block = PrimitiveBlock::default();
+        let alice = block.stringtable.add_string("alice".to_string()) as u32;
+        let bob = block.stringtable.add_string("bob".to_string()) as u32;
+        let name = block.stringtable.add_string("name".to_string()) as u32;
+        block.primitivegroup.push(PrimitiveGroup {
+            dense: Some(DenseNodes {
+                id: vec![1, 1, 1],
+                denseinfo: Some(DenseInfo {
+                    version: vec![1, 1, 1],
+                    timestamp: vec![0, 0, 0],
+                    changeset: vec![500, 0, 100],
+                    uid: vec![42, 0, -35],
+                    user_sid: vec![alice as i32, 0, 1],
+                    visible: vec![],
+                }),
+                lat: vec![0, 0, 0],
+                lon: vec![0, 0, 0],
+                keys_vals: vec![0, 0, 0],
+            }),
+            ..Default::default()
+        });
+        block.primitivegroup.push(PrimitiveGroup {
+            ways: vec![Way {
+                id: WayId(1),
+                keys: vec![name],
+                vals: vec![bob],
+                info: Some(Info { uid: 7, changeset: 700, user_sid: bob, ..Default::default() }),
+                refs: vec![],
+                lat: vec![],
+                lon: vec![],
+            }],
+            ..Default::default()
+        });
+        block
+    }
+
+    #[test]
+    fn test_pseudonymize_block() {
+        let mut map = PseudonymMap::new();
+        let mut block = block();
+        map.pseudonymize_block(&mut block);
+
+        let info = block.primitivegroup[0].dense.as_ref().unwrap().denseinfo.as_ref().unwrap();
+        assert_eq!(info.uid, vec![1, 0, 1]);
+        assert_eq!(info.changeset, vec![1, 0, 1]);
+        assert_eq!(info.user_sid, vec![0, 0, 0]);
+        let way_info = block.primitivegroup[1].ways[0].info.as_ref().unwrap();
+        assert_eq!((way_info.uid, way_info.changeset, way_info.user_sid), (2, 3, 0));
+
+        // "bob" is also a tag value, so only "alice" is blanked
+        assert_eq!(block.stringtable.s[1..], ["", "bob", "name"]);
+        assert_eq!((map.original_uid(2), map.original_changeset(3)), (Some(7), Some(700)));
+        assert_eq!(map.original_uid(3), None);
+    }
+
+    #[test]
+    fn test_map_round_trip() {
+        let mut map = PseudonymMap::new();
+        map.pseudonymize_block(&mut block());
+        assert_eq!((map.uid_count(), map.changeset_count()), (2, 3));
+
+        let mut text = Vec::new();
+        map.write_to(&mut text).unwrap();
+        assert!(text.starts_with(MAP_HEADER.as_bytes()));
+        assert_eq!(PseudonymMap::read_from(text.as_slice()).unwrap(), map);
+        assert!(PseudonymMap::read_from(&b"uid 1 2\n"[..]).is_err());
+
+        // A restored map keeps assigning the same pseudonyms
+        let mut restored = PseudonymMap::read_from(text.as_slice()).unwrap();
+        assert_eq!((restored.uid(7), restored.uid(8)), (2, 3));
+    }
+
+    #[test]
+    fn test_pseudonymize_stream() {
+        let mut input = Vec::new();
+        crate::synthetic::PlanetBuilder::new(2).grid_size(6).write_to(&mut input).unwrap();
+        let mut output = Vec::new();
+        let stats = pseudonymize(input.as_slice(), &mut output, &mut PseudonymMap::new()).unwrap();
+        assert!(stats.blocks_transformed > 0);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::io::buffer_pool::BufferPool;
+use crate::io::reader::{ParallelConfig, StreamConfig};
+
+/// Named presets for the reader's tuning knobs
+///
+/// Each profile sets thread count, read-ahead (blobs read per parallel chunk),
+/// stream channel capacity and buffer pool size consistently, so one line
+/// replaces tuning each of them.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{Profile, Reader};
+/// use std::fs::File;
+///
+/// let profile = Profile::LowMemory;
+/// let mut reader = Reader::with_profile(File::open("map.osm.pbf")?, profile)?;
+/// reader.par_for_each(&profile.parallel_config(), |_| Ok(()))?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Single decoding thread, minimal read-ahead and buffering
+    LowMemory,
+    /// The library defaults
+    #[default]
+    Balanced,
+    /// All cores, deep read-ahead sized by decode cost, and generous buffering
+    MaxThroughput,
+    /// First results as early as possible, delivered in file order
+    Interactive,
+}
+
+impl Profile {
+    /// Parallel decoding settings for `par_for_each` and friends
+    pub fn parallel_config(self) -> ParallelConfig {
+        match self {
+            Profile::LowMemory => ParallelConfig { num_threads: Some(1), chunk_size: 4, preserve_order: false, adaptive: false },
+            Profile::Balanced => ParallelConfig::default(),
+            Profile::MaxThroughput => ParallelConfig { num_threads: None, chunk_size: 256, preserve_order: false, adaptive: true },
+            Profile::Interactive => ParallelConfig { num_threads: None, chunk_size: 8, preserve_order: true, adaptive: false },
+        }
+    }
+
+    /// Settings for `Reader::spawn_stream`
+    pub fn stream_config(self) -> StreamConfig {
+        let channel_capacity = match self {
+            Profile::LowMemory => 2,
+            Profile::Balanced => StreamConfig::default().channel_capacity,
+            Profile::MaxThroughput => 64,
+            Profile::Interactive => 4,
+        };
+        StreamConfig { channel_capacity, parallel: self.parallel_config() }
+    }
+
+    /// Number of idle scratch buffers kept for blob reads
+    pub fn pooled_buffers(self) -> usize {
+        match self {
+            Profile::LowMemory => 1,
+            Profile::Balanced | Profile::Interactive => BufferPool::DEFAULT_MAX_BUFFERS,
+            Profile::MaxThroughput => std::thread::available_parallelism().map_or(8, |n| n.get() * 2),
+        }
+    }
+
+    /// A fresh buffer pool sized for this profile
+    pub fn buffer_pool(self) -> BufferPool {
+        BufferPool::new(self.pooled_buffers())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_balanced_matches_defaults() {
+        let config = Profile::Balanced.parallel_config();
+        let defaults = ParallelConfig::default();
+
+        assert_eq!((config.num_threads, config.chunk_size, config.preserve_order),
+            (defaults.num_threads, defaults.chunk_size, defaults.preserve_order));
+        assert_eq!(Profile::Balanced.stream_config().channel_capacity, StreamConfig::default().channel_capacity);
+        assert_eq!(Profile::default(), Profile::Balanced);
+    }
+
+    #[test]
+    fn test_profiles_are_ordered_by_resource_use() {
+        let low = Profile::LowMemory;
+        let max = Profile::MaxThroughput;
+
+        assert_eq!(low.parallel_config().num_threads, Some(1));
+        assert!(low.parallel_config().chunk_size < max.parallel_config().chunk_size);
+        assert!(low.stream_config().channel_capacity < max.stream_config().channel_capacity);
+        assert!(low.pooled_buffers() < max.pooled_buffers());
+        assert!(Profile::Interactive.stream_config().parallel.preserve_order);
+    }
+}
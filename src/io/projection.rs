@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::Result;
+use crate::io::delta::{delta_decoded, DeltaEncoder};
+use crate::io::transform::{map_blocks, TransformStats};
+
+/// Allow-list of tag keys per element type, for `project_tags`
+///
+/// Tags whose key is not listed for the element's type are dropped; an
+/// element type without any listed keys loses all its tags. Elements
+/// themselves, their metadata and relation roles are kept.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::TagProjection;
+///
+/// // Routing-only extract
+/// let projection = TagProjection::new()
+///     .with_way_keys(["highway", "oneway", "maxspeed", "access"])
+///     .with_relation_keys(["type", "restriction"]);
+/// assert!(projection.keeps_way_key("oneway"));
+/// assert!(!projection.keeps_node_key("amenity"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagProjection {
+    node_keys: HashSet<String>,
+    way_keys: HashSet<String>,
+    relation_keys: HashSet<String>,
+    changeset_keys: HashSet<String>,
+}
+
+impl TagProjection {
+    /// Projection keeping no tags at all
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep these keys on every element type
+    pub fn with_keys<I: IntoIterator<Item = S>, S: Into<String>>(self, keys: I) -> Self {
+        let keys: Vec<String> = keys.into_iter().map(Into::into).collect();
+        self.with_node_keys(keys.clone())
+            .with_way_keys(keys.clone())
+            .with_relation_keys(keys.clone())
+            .with_changeset_keys(keys)
+    }
+
+    pub fn with_node_keys<I: IntoIterator<Item = S>, S: Into<String>>(mut self, keys: I) -> Self {
+        self.node_keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_way_keys<I: IntoIterator<Item = S>, S: Into<String>>(mut self, keys: I) -> Self {
+        self.way_keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_relation_keys<I: IntoIterator<Item = S>, S: Into<String>>(mut self, keys: I) -> Self {
+        self.relation_keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_changeset_keys<I: IntoIterator<Item = S>, S: Into<String>>(mut self, keys: I) -> Self {
+        self.changeset_keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn keeps_node_key(&self, key: &str) -> bool {
+        self.node_keys.contains(key)
+    }
+
+    pub fn keeps_way_key(&self, key: &str) -> bool {
+        self.way_keys.contains(key)
+    }
+
+    pub fn keeps_relation_key(&self, key: &str) -> bool {
+        self.relation_keys.contains(key)
+    }
+
+    pub fn keeps_changeset_key(&self, key: &str) -> bool {
+        self.changeset_keys.contains(key)
+    }
+
+    /// Drop the tags not on the allow-list and rebuild the string table
+    ///
+    /// The new table holds only the strings still referenced, in order of
+    /// first use, with the empty string at index 0.
+    pub fn project_block(&self, block: &mut PrimitiveBlock) {
+        let allowed = |keys: &HashSet<String>| -> Vec<bool> {
+            block.stringtable.s.iter().enumerate().map(|(index, s)| index != 0 && keys.contains(s)).collect()
+        };
+        let (node_keys, way_keys) = (allowed(&self.node_keys), allowed(&self.way_keys));
+        let (relation_keys, changeset_keys) = (allowed(&self.relation_keys), allowed(&self.changeset_keys));
+
+        let mut strings = Reinterner::new(&block.stringtable);
+        for group in &mut block.primitivegroup {
+            for node in &mut group.nodes {
+                strings.retain_tags(&mut node.keys, &mut node.vals, &node_keys);
+                strings.remap_info(&mut node.info);
+            }
+            for way in &mut group.ways {
+                strings.retain_tags(&mut way.keys, &mut way.vals, &way_keys);
+                strings.remap_info(&mut way.info);
+            }
+            for relation in &mut group.relations {
+                strings.retain_tags(&mut relation.keys, &mut relation.vals, &relation_keys);
+                strings.remap_info(&mut relation.info);
+                for role in &mut relation.roles_sid {
+                    *role = strings.index(*role as u32) as i32;
+                }
+            }
+            for changeset in &mut group.changesets {
+                strings.retain_tags(&mut changeset.keys, &mut changeset.vals, &changeset_keys);
+                strings.remap_info(&mut changeset.info);
+            }
+            if let Some(dense) = &mut group.dense {
+                strings.retain_dense_tags(&mut dense.keys_vals, &node_keys);
+                strings.remap_dense_info(&mut dense.denseinfo);
+            }
+        }
+        block.stringtable = StringTable { s: strings.finish() };
+    }
+}
+
+/// Builds a block's reduced string table while indices are rewritten
+pub(crate) struct Reinterner<'a> {
+    old: &'a [String],
+    /// New index of each old entry, `u32::MAX` until first use
+    new_index: Vec<u32>,
+    /// Old index of each old string, built on the first `intern`
+    old_lookup: Option<HashMap<&'a str, u32>>,
+    /// Index of each string not in the old table
+    added: HashMap<String, u32>,
+    strings: Vec<String>,
+}
+
+impl<'a> Reinterner<'a> {
+    pub(crate) fn new(table: &'a StringTable) -> Self {
+        let mut new_index = vec![u32::MAX; table.s.len()];
+        if let Some(first) = new_index.first_mut() {
+            *first = 0;
+        }
+        Self { old: &table.s, new_index, old_lookup: None, added: HashMap::new(), strings: vec![String::new()] }
+    }
+
+    /// String at an old index; indices outside the table read as empty
+    pub(crate) fn old(&self, old: u32) -> &'a str {
+        self.old.get(old as usize).map_or("", String::as_str)
+    }
+
+    /// Index of a string that may not be in the old table
+    pub(crate) fn intern(&mut self, s: &str) -> u32 {
+        let old = self.old;
+        let old_lookup = self.old_lookup.get_or_insert_with(|| {
+            old.iter().enumerate().rev().map(|(index, s)| (s.as_str(), index as u32)).collect()
+        });
+        if let Some(&index) = old_lookup.get(s) {
+            return self.index(index);
+        }
+        if let Some(index) = self.added.get(s) {
+            return *index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(s.to_string());
+        self.added.insert(s.to_string(), index);
+        index
+    }
+
+    /// New index of an old one; indices outside the table become 0
+    pub(crate) fn index(&mut self, old: u32) -> u32 {
+        let Some(slot) = self.new_index.get_mut(old as usize) else {
+            return 0;
+        };
+        if *slot == u32::MAX {
+            *slot = self.strings.len() as u32;
+            self.strings.push(self.old[old as usize].clone());
+        }
+        *slot
+    }
+
+    fn retain_tags(&mut self, keys: &mut Vec<u32>, vals: &mut Vec<u32>, allowed: &[bool]) {
+        let (mut kept_keys, mut kept_vals) = (Vec::new(), Vec::new());
+        for (key, val) in keys.iter().zip(vals.iter()) {
+            if allowed.get(*key as usize).copied().unwrap_or(false) {
+                kept_keys.push(self.index(*key));
+                kept_vals.push(self.index(*val));
+            }
+        }
+        (*keys, *vals) = (kept_keys, kept_vals);
+    }
+
+    /// Dense tags are `key, val` pairs with each node's list ended by a 0
+    fn retain_dense_tags(&mut self, keys_vals: &mut Vec<i32>, allowed: &[bool]) {
+        if keys_vals.is_empty() {
+            return;
+        }
+        let mut kept = Vec::with_capacity(keys_vals.len());
+        let mut cursor = 0;
+        while let Some(&key) = keys_vals.get(cursor) {
+            if key == 0 {
+                kept.push(0);
+                cursor += 1;
+                continue;
+            }
+            let val = keys_vals.get(cursor + 1).copied().unwrap_or(0);
+            if allowed.get(key as usize).copied().unwrap_or(false) {
+                kept.push(self.index(key as u32) as i32);
+                kept.push(self.index(val as u32) as i32);
+            }
+            cursor += 2;
+        }
+        *keys_vals = kept;
+    }
+
+    pub(crate) fn remap_info(&mut self, info: &mut Option<Info>) {
+        if let Some(info) = info {
+            info.user_sid = self.index(info.user_sid);
+        }
+    }
+
+    /// Dense user names are delta-encoded string indices
+    pub(crate) fn remap_dense_info(&mut self, info: &mut Option<DenseInfo>) {
+        if let Some(info) = info {
+            let user_sids: Vec<i32> = delta_decoded(&info.user_sid).collect();
+            let mut encoder = DeltaEncoder::new();
+            for (delta, user_sid) in info.user_sid.iter_mut().zip(user_sids) {
+                *delta = encoder.encode(self.index(user_sid as u32) as i32);
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<String> {
+        self.strings
+    }
+}
+
+/// Copy a PBF stream keeping only the tags allowed by `projection`
+///
+/// Each block's string table is rebuilt from the strings still in use, so
+/// dropped keys and values take no space in the output.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{project_tags, TagProjection};
+/// use std::fs::File;
+///
+/// let projection = TagProjection::new().with_way_keys(["highway", "oneway"]);
+/// let stats = project_tags(File::open("in.osm.pbf")?, File::create("routing.osm.pbf")?, &projection)?;
+/// println!("{} -> {} bytes", stats.bytes_read, stats.bytes_written);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn project_tags<R: Read, W: Write>(reader: R, writer: W, projection: &TagProjection) -> Result<TransformStats> {
+    map_blocks(reader, writer, |mut block| {
+        projection.project_block(&mut block);
+        block
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::{RelationId, WayId};
+    use crate::synthetic::PlanetBuilder;
+    use pretty_assertions::assert_eq;
+
+    fn table(strings: &[&str]) -> StringTable {
+        StringTable { s: strings.iter().map(|s| s.to_string()).collect() }
+    }
+
+    #[test]
+    fn test_project_block_reinterns_strings() {
+        let info = |user_sid| Some(Info { user_sid, ..Default::default() });
+        let mut group = PrimitiveGroup::default();
+        group.ways.push(Way { id: WayId(1), keys: vec![1, 3], vals: vec![2, 4], info: info(5), refs: vec![1, 1], lat: vec![], lon: vec![] });
+        group.relations.push(Relation {
+            id: RelationId(2),
+            keys: vec![3],
+            vals: vec![4],
+            info: None,
+            roles_sid: vec![6, 0],
+            memids: vec![1, 1],
+            types: vec![MemberType::Way, MemberType::Way],
+        });
+        let mut dense_group = PrimitiveGroup::default();
+        let mut dense = DenseNodes { id: vec![10, 1, 1], lat: vec![0; 3], lon: vec![0; 3], ..Default::default() };
+        dense.keys_vals = vec![1, 2, 7, 8, 0, 0, 7, 8, 0];
+        dense.denseinfo = Some(DenseInfo { user_sid: vec![5, 0, -5], ..Default::default() });
+        dense_group.dense = Some(dense);
+
+        let mut block = PrimitiveBlock {
+            stringtable: table(&["", "highway", "primary", "name", "Main Street", "alice", "stop", "amenity", "cafe"]),
+            primitivegroup: vec![group, dense_group],
+            ..Default::default()
+        };
+        TagProjection::new().with_keys(["highway"]).with_relation_keys(["name"]).project_block(&mut block);
+
+        assert_eq!(block.stringtable, table(&["", "highway", "primary", "alice", "name", "Main Street", "stop"]));
+        let way = &block.primitivegroup[0].ways[0];
+        assert_eq!((way.keys.clone(), way.vals.clone(), way.info.as_ref().unwrap().user_sid), (vec![1], vec![2], 3));
+        let relation = &block.primitivegroup[0].relations[0];
+        assert_eq!((relation.keys.clone(), relation.vals.clone(), relation.roles_sid.clone()), (vec![4], vec![5], vec![6, 0]));
+        let dense = block.primitivegroup[1].dense.as_ref().unwrap();
+        assert_eq!(dense.keys_vals, vec![1, 2, 0, 0, 0]);
+        assert_eq!(dense.denseinfo.as_ref().unwrap().user_sid, vec![3, 0, -3]);
+    }
+
+    #[test]
+    fn test_project_tags_shrinks_file() {
+        let mut input = Vec::new();
+        let planet = PlanetBuilder::new(3).grid_size(30).block_size(200).relation_count(4).write_to(&mut input).unwrap();
+
+        let projection = TagProjection::new().with_way_keys(["highway"]).with_relation_keys(["type", "route"]);
+        let mut output = Vec::new();
+        let stats = project_tags(input.as_slice(), &mut output, &projection).unwrap();
+        assert_eq!(stats.blocks_transformed + stats.blobs_copied, planet.blobs);
+        assert!(output.len() < input.len(), "{} >= {}", output.len(), input.len());
+
+        let (mut ways, mut relations) = (0, 0);
+        map_blocks(output.as_slice(), std::io::sink(), |block| {
+            let strings = &block.stringtable.s;
+            assert!(!strings.iter().any(|s| s.starts_with("Street ") || s == "amenity"));
+            for group in &block.primitivegroup {
+                if let Some(dense) = &group.dense {
+                    assert!(dense.keys_vals.iter().all(|index| *index == 0));
+                }
+                for way in &group.ways {
+                    assert_eq!(way.keys.iter().map(|k| strings[*k as usize].as_str()).collect::<Vec<_>>(), ["highway"]);
+                    ways += 1;
+                }
+                for relation in &group.relations {
+                    assert_eq!(strings[relation.roles_sid[0] as usize], "platform");
+                    assert_eq!(relation.keys.len(), 2);
+                    relations += 1;
+                }
+            }
+            block
+        })
+        .unwrap();
+        assert_eq!((ways, relations), (planet.ways, planet.relations));
+    }
+}
@@ -1,16 +1,68 @@
+use std::collections::HashSet;
+use std::fs::File;
 use std::io::{Read, Seek};
-use crate::io::blob::{Blob, BlobError, Result};
-use crate::io::indexed_reader::{IndexedReader, ElementFilter};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crossbeam_channel::Receiver;
+use rayon::iter::Either;
+use rayon::prelude::*;
+use crate::io::blob::{Blob, BlobError, BlobErrorKind, Result};
+use crate::io::buffer_pool::BufferPool;
+use crate::io::codec::{BlockDecoder, PbfBlockCodec, SkipMetadataCodec};
+use crate::io::checkpoint::{Checkpoint, TimedRun};
+use crate::io::indexed_reader::{frame_end, IndexedReader, ElementFilter};
+use crate::io::logging::{log_mixed_groups, log_skipped, SkipLogLevel};
+use crate::io::retry::RetryPolicy;
+use crate::io::shards::{Shard, ShardSet, ShardStorage};
+use crate::io::spill::{SpillConfig, SpillableElements};
+use crate::io::summary::{FileSummary, SummaryBuilder};
+use crate::io::stable;
+use crate::io::transform::{RewritingDecoder, TagRewrite};
 use crate::blocks::primitives::prelude::*;
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::string_table::StringTable;
+use crate::blocks::tags::Tags;
+use crate::io::dictionary::{StringDictionary, StringDictionaryBuilder};
+use crate::io::decode::{blob_payload, count_matching_elements, decode_elements, decode_matching_elements, DecodePredicate, MatchCounts, MatchingElements};
+use crate::io::change::ChangeElement;
+use crate::io::features::{FeaturePolicy, FileOrdering, ReplicationInfo, HISTORICAL_INFORMATION, LOCATIONS_ON_WAYS};
+use crate::io::history::{self, ElementHistory};
+use crate::io::fingerprint::{Fingerprint, FingerprintBuilder};
+use crate::io::geometry::{GeometryStats, NodeLocationCache, NodeLocationStore, SparseLocations};
+use crate::io::live_stats::{LiveSnapshot, LiveStats};
+use crate::io::max_ids::MaxIds;
+use crate::io::memory::MemoryMode;
+use crate::io::options::ReaderOptions;
+use crate::io::integrity::{IntegrityChecker, IntegrityReport, IssueKind};
+use crate::io::ordering::{OrderChecker, OrderingReport};
+use crate::io::pagination::{Page, PageCursor};
+use crate::io::plan::{Plan, PruneReason};
+use crate::io::profile::Profile;
+use crate::io::relations::{QualityReport, RelationGraph};
+use crate::io::schedule::{AdaptiveScheduler, BlobKind, CostHint, ScheduledBlob};
+use crate::io::sequence::SequenceMerger;
+use crate::io::validate::{CorruptBlobPolicy, GroupPolicy};
+
+/// Callback of `Reader::set_on_error`
+type OnError = Box<dyn FnMut(&CorruptBlob) + Send>;
 
 /// High-level, zero-boilerplate entry point for extracting OSM elements from PBF files
 /// Optimized for streaming, parallelism, and business-grade throughput
 pub struct Reader<R: Read + Seek> {
     indexed_reader: IndexedReader<R>,
+    live_stats: LiveStats,
+    group_policy: GroupPolicy,
+    corrupt_blob_policy: CorruptBlobPolicy,
+    on_error: Option<OnError>,
+    block_decoder: Arc<dyn BlockDecoder>,
+    parallel_config: ParallelConfig,
 }
 
 /// Represents any OSM element that can be extracted from a PBF file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum OsmElement {
     Node(Node),
     Way(Way),
@@ -18,15 +70,55 @@ pub enum OsmElement {
     ChangeSet(ChangeSet),
 }
 
+impl OsmElement {
+    /// Tags of the element, resolved against the string table of its block
+    pub fn tags<'a>(&'a self, strings: &'a StringTable) -> Tags<'a> {
+        match self {
+            OsmElement::Node(node) => node.tags(strings),
+            OsmElement::Way(way) => way.tags(strings),
+            OsmElement::Relation(relation) => relation.tags(strings),
+            OsmElement::ChangeSet(changeset) => changeset.tags(strings),
+        }
+    }
+}
+
+/// Where a decoded element is stored in the file
+///
+/// Enough to find the element again for a bug report or a targeted re-read
+/// without scanning the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Provenance {
+    /// Index of the blob in the reader's blob index
+    pub blob_index: usize,
+    /// Byte offset of the blob's header in the file
+    pub byte_offset: u64,
+    /// Position of the element among all elements of the blob's block, counting
+    /// filtered-out elements too
+    pub element_ordinal: usize,
+}
+
 /// Configuration for parallel processing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParallelConfig {
     /// Number of threads to use (None = use all available cores)
     pub num_threads: Option<usize>,
     /// Chunk size for parallel processing
     pub chunk_size: usize,
     /// Whether to preserve order of elements
+    ///
+    /// When true, `par_for_each` emits elements in file order by merging decoded
+    /// blobs back by sequence number; when false, each blob's elements are
+    /// emitted as soon as it is decoded (still in order within the blob).
     pub preserve_order: bool,
+    /// Size chunks by measured decode cost instead of a fixed blob count
+    ///
+    /// Decode times are tracked per kind of blob (dense nodes, ways,
+    /// relations), and each chunk is filled until its estimated decode time
+    /// keeps every thread busy, up to `chunk_size` blobs. The costliest blobs
+    /// of a chunk are decoded first and each blob is a separate task for work
+    /// stealing, which shortens the wait on the slowest blob of a chunk in
+    /// files mixing cheap and costly blobs.
+    pub adaptive: bool,
 }
 
 impl Default for ParallelConfig {
@@ -35,10 +127,39 @@ impl Default for ParallelConfig {
             num_threads: None,
             chunk_size: 100,
             preserve_order: false,
+            adaptive: false,
+        }
+    }
+}
+
+/// Configuration for `Reader::spawn_stream`
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// Maximum number of batches waiting in the channel before decoding pauses
+    pub channel_capacity: usize,
+    /// Decoding threads, chunking and ordering
+    pub parallel: ParallelConfig,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            channel_capacity: 16,
+            parallel: ParallelConfig::default(),
         }
     }
 }
 
+/// Elements of one decoded blob, as delivered by `Reader::spawn_stream`
+#[derive(Debug, Clone)]
+pub struct ElementBatch {
+    /// Position of the blob in the file
+    pub blob_index: usize,
+    /// String table the elements' tag and role indices refer to
+    pub strings: StringTable,
+    pub elements: Vec<OsmElement>,
+}
+
 /// Statistics from processing operations
 #[derive(Debug, Clone, Default)]
 pub struct ProcessingStats {
@@ -49,6 +170,57 @@ pub struct ProcessingStats {
     pub relations_processed: u64,
     pub changesets_processed: u64,
     pub errors_encountered: u64,
+    /// Blobs that could not be read and were skipped
+    pub blobs_skipped: u64,
+    /// Transient IO errors that were retried
+    pub retries_performed: u64,
+    /// Data blobs a filter skipped by index pruning, without reading them
+    pub blobs_pruned: u64,
+    /// Elements a filter rejected inside the decoder, before materializing them
+    pub elements_skipped_early: u64,
+    /// Elements a filter rejected after materializing them (tag keys and values)
+    pub elements_skipped_late: u64,
+    /// Primitive groups holding more than one element kind (see `GroupPolicy`)
+    pub mixed_groups: u64,
+    /// Blobs skipped as unreadable or, under `CorruptBlobPolicy::Skip`,
+    /// undecodable, and damaged regions indexing resynchronized past
+    pub corrupt_blobs: Vec<CorruptBlob>,
+}
+
+/// A blob or damaged region skipped while reading, in `ProcessingStats::corrupt_blobs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptBlob {
+    /// Byte offset of the blob's frame, or of the start of the damaged region
+    pub offset: u64,
+    /// Index of the blob, `None` for regions that didn't index as a blob
+    pub blob_index: Option<usize>,
+    pub kind: BlobErrorKind,
+    pub error: String,
+}
+
+impl Reader<File> {
+    /// Open a file another process may still be writing, once it is complete
+    ///
+    /// Polls every `poll_interval` until the file exists, its size is the same
+    /// on two polls in a row and it ends right after a complete blob, then
+    /// indexes it like `new`. Fails with an `Io` error of kind `TimedOut` if
+    /// that doesn't happen within `timeout`.
+    ///
+    /// A producer that writes to a temp file and renames it into place makes
+    /// this unnecessary; use it where the producer can't be changed.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::time::Duration;
+    ///
+    /// let mut reader = Reader::open_when_stable("incoming/extract.osm.pbf", Duration::from_secs(1), Duration::from_secs(600))?;
+    /// reader.for_each(|_| Ok(()))?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn open_when_stable(path: impl AsRef<Path>, poll_interval: Duration, timeout: Duration) -> Result<Self> {
+        Self::new(stable::open_when_stable(path.as_ref(), poll_interval, timeout)?)
+    }
 }
 
 impl<R: Read + Seek> Reader<R> {
@@ -71,13 +243,293 @@ impl<R: Read + Seek> Reader<R> {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn new(reader: R) -> Result<Self> {
-        let indexed_reader = IndexedReader::new(reader)?;
-        Ok(Self { indexed_reader })
+        if MemoryMode::default().is_low_memory() {
+            return Self::with_memory_mode(reader, MemoryMode::LowMemory);
+        }
+        Self::with_skip_log_level(reader, SkipLogLevel::default())
+    }
+
+    /// Create a new Reader that reports skipped data at the given level
+    ///
+    /// Events go through the `log` crate when the `log` feature is enabled;
+    /// skipped blobs are always counted in `ProcessingStats::blobs_skipped`.
+    pub fn with_skip_log_level(reader: R, skip_log_level: SkipLogLevel) -> Result<Self> {
+        let indexed_reader = IndexedReader::with_skip_log_level(reader, skip_log_level)?;
+        Ok(Self::from_indexed_reader(indexed_reader))
+    }
+
+    /// Create a new Reader that retries transient IO errors (e.g. on NFS or
+    /// FUSE mounts) according to the given policy
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, RetryPolicy};
+    /// use std::fs::File;
+    /// 
+    /// let file = File::open("/mnt/s3/planet.osm.pbf")?;
+    /// let mut reader = Reader::with_retry_policy(file, RetryPolicy::network_filesystem())?;
+    /// let stats = reader.for_each(|_| Ok(()))?;
+    /// println!("Survived {} transient errors", stats.retries_performed);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_retry_policy(reader: R, retry_policy: RetryPolicy) -> Result<Self> {
+        let indexed_reader = IndexedReader::with_retry_policy(reader, retry_policy)?;
+        Ok(Self::from_indexed_reader(indexed_reader))
+    }
+
+    /// Create a new Reader that handles unsupported required features in the
+    /// file header according to `feature_policy` (rejected by default)
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{BlobError, FeaturePolicy, Reader};
+    /// use std::fs::File;
+    /// 
+    /// match Reader::new(File::open("map.osm.pbf")?) {
+    ///     Err(BlobError::UnsupportedFeature { features }) => {
+    ///         eprintln!("needs {}; opening anyway", features.join(", "));
+    ///         Reader::with_feature_policy(File::open("map.osm.pbf")?, FeaturePolicy::Warn)?;
+    ///     }
+    ///     other => { other?; }
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_feature_policy(reader: R, feature_policy: FeaturePolicy) -> Result<Self> {
+        let indexed_reader = IndexedReader::with_feature_policy(reader, feature_policy)?;
+        Ok(Self::from_indexed_reader(indexed_reader))
+    }
+
+    /// Create a new Reader with the buffering of a named profile
+    ///
+    /// Pass `parallel_config()` or `profile.stream_config()` to the parallel
+    /// methods to apply the rest of the profile.
+    pub fn with_profile(reader: R, profile: Profile) -> Result<Self> {
+        let mut indexed_reader = IndexedReader::new(reader)?;
+        indexed_reader.set_buffer_pool(profile.buffer_pool());
+        Ok(Self { parallel_config: profile.parallel_config(), ..Self::from_indexed_reader(indexed_reader) })
+    }
+
+    /// Create a new Reader that indexes the file while reading it instead of
+    /// before
+    ///
+    /// `new` scans every blob header before returning, which takes a while on
+    /// huge files on slow storage. A lazy reader returns after the file header;
+    /// `for_each` then discovers blobs one at a time and emits the elements of
+    /// each as soon as it is read, so the first elements arrive right away.
+    /// Methods needing the whole index (filtered reads, parallel decoding,
+    /// pagination, ...) complete it first; `explain` and `statistics` only
+    /// cover the blobs indexed so far. See `IndexedReader::lazy`.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::lazy(File::open("planet.osm.pbf")?)?;
+    /// reader.for_each(|element| {
+    ///     println!("{element:?}");
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn lazy(reader: R) -> Result<Self> {
+        let indexed_reader = IndexedReader::lazy(reader)?;
+        Ok(Self::from_indexed_reader(indexed_reader))
+    }
+
+    /// Create a new Reader whose indexing and buffering fit `mode`
+    ///
+    /// `new` uses `MemoryMode::default()`, i.e. `LowMemory` on 32-bit targets
+    /// and with the `low-memory` feature. See `MemoryMode` for the differences.
+    pub fn with_memory_mode(reader: R, mode: MemoryMode) -> Result<Self> {
+        let mut indexed_reader = if mode.lazy_index() {
+            IndexedReader::lazy(reader)?
+        } else {
+            IndexedReader::new(reader)?
+        };
+        indexed_reader.set_buffer_pool(mode.profile().buffer_pool());
+        Ok(Self { parallel_config: mode.profile().parallel_config(), ..Self::from_indexed_reader(indexed_reader) })
+    }
+
+    /// Create a new Reader with all settings in one place
+    ///
+    /// See `ReaderOptions`; `ReaderOptions::default()` gives the reader `new`
+    /// would.
+    pub fn with_options(reader: R, options: ReaderOptions) -> Result<Self> {
+        let mut indexed_reader = IndexedReader::with_options(
+            reader,
+            options.skip_log_level,
+            options.retry_policy,
+            options.feature_policy,
+            options.lazy_index,
+        )?;
+        indexed_reader.set_buffer_pool(BufferPool::new(options.pooled_buffers));
+        indexed_reader.set_max_blob_size(options.max_blob_size);
+        let mut reader = Self::from_indexed_reader(indexed_reader);
+        reader.group_policy = options.group_policy;
+        reader.corrupt_blob_policy = options.corrupt_blob_policy;
+        reader.parallel_config = options.parallel;
+        reader.set_skip_metadata(options.skip_metadata);
+        Ok(reader)
+    }
+
+    fn from_indexed_reader(indexed_reader: IndexedReader<R>) -> Self {
+        Self {
+            live_stats: indexed_reader.live_stats().clone(),
+            indexed_reader,
+            group_policy: GroupPolicy::default(),
+            corrupt_blob_policy: CorruptBlobPolicy::default(),
+            on_error: None,
+            block_decoder: Arc::new(PbfBlockCodec),
+            parallel_config: ParallelConfig::default(),
+        }
+    }
+
+    /// Parallel settings the reader was created with (see
+    /// `ReaderOptions::parallel`), for `par_for_each` and friends
+    pub fn parallel_config(&self) -> &ParallelConfig {
+        &self.parallel_config
+    }
+
+    /// Handle to live counters updated while this reader processes data
+    ///
+    /// Unlike the `ProcessingStats` returned after a run, the handle can be
+    /// polled from another thread during `for_each` and friends.
+    pub fn live_stats(&self) -> LiveStats {
+        self.live_stats.clone()
+    }
+
+    /// Report progress to `callback` while processing, at most once per
+    /// `interval` (see `LiveStats::set_progress_hook`)
+    ///
+    /// Snapshots carry the blobs, elements and bytes processed and, through
+    /// `LiveSnapshot::fraction_done` and `eta`, how far into the file the run
+    /// is.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    ///
+    /// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+    /// reader.set_progress_hook(Duration::from_millis(500), |snapshot| {
+    ///     let done = snapshot.fraction_done().unwrap_or(0.0) * 100.0;
+    ///     eprint!("\r{done:.1}% {} elements, {:?} left", snapshot.elements_processed, snapshot.eta());
+    /// });
+    /// reader.for_each(|_| Ok(()))?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn set_progress_hook<F>(&mut self, interval: Duration, callback: F)
+    where
+        F: FnMut(&LiveSnapshot) + Send + 'static,
+    {
+        self.live_stats.set_progress_hook(interval, callback);
+    }
+
+    /// Set how filtered reads handle primitive groups mixing element kinds
+    /// (decoded with a warning by default)
+    pub fn set_group_policy(&mut self, group_policy: GroupPolicy) {
+        self.group_policy = group_policy;
+    }
+
+    pub fn group_policy(&self) -> GroupPolicy {
+        self.group_policy
+    }
+
+    /// Set whether `for_each`, `for_each_filtered` and the methods built on
+    /// them, and the element iterators, skip blobs that don't decode (they
+    /// fail the run by default)
+    ///
+    /// The parallel methods still fail on them.
+    pub fn set_corrupt_blob_policy(&mut self, corrupt_blob_policy: CorruptBlobPolicy) {
+        self.corrupt_blob_policy = corrupt_blob_policy;
+    }
+
+    pub fn corrupt_blob_policy(&self) -> CorruptBlobPolicy {
+        self.corrupt_blob_policy
+    }
+
+    /// Call `on_error` for every blob and damaged region a run skips, as
+    /// it's added to `ProcessingStats::corrupt_blobs`
+    ///
+    /// For services that route diagnostics to their own channels instead of
+    /// the `log` crate (see `SkipLogLevel`); damaged regions indexing skipped
+    /// are passed again at the start of each run.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{CorruptBlobPolicy, Reader, SkipLogLevel};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::with_skip_log_level(File::open("map.osm.pbf")?, SkipLogLevel::Off)?;
+    /// reader.set_corrupt_blob_policy(CorruptBlobPolicy::Skip);
+    /// reader.set_on_error(|corrupt| {
+    ///     // e.g. metrics.increment(format!("{:?}", corrupt.kind))
+    ///     let _ = (corrupt.offset, corrupt.blob_index, corrupt.kind);
+    /// });
+    /// reader.for_each(|_| Ok(()))?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn set_on_error<F>(&mut self, on_error: F)
+    where
+        F: FnMut(&CorruptBlob) + Send + 'static,
+    {
+        self.on_error = Some(Box::new(on_error));
+    }
+
+    pub fn clear_on_error(&mut self) {
+        self.on_error = None;
+    }
+
+    /// Decode data blocks with `decoder` instead of as PBF PrimitiveBlock messages
+    ///
+    /// For files whose OSMData blobs hold blocks in another wire format; see
+    /// `BlockDecoder`.
+    pub fn set_block_decoder(&mut self, decoder: impl BlockDecoder + 'static) {
+        self.block_decoder = Arc::new(decoder);
+    }
+
+    /// Skip parsing element metadata (`Info`/`DenseInfo`) while decoding
+    ///
+    /// Faster when only ids, tags, locations and members are needed: elements
+    /// come out with `info: None`, so metadata predicates of an
+    /// `ElementFilter` no longer match them. Replaces a decoder set with
+    /// `set_block_decoder`; `false` goes back to plain PBF decoding.
+    pub fn set_skip_metadata(&mut self, skip_metadata: bool) {
+        if skip_metadata {
+            self.set_block_decoder(SkipMetadataCodec);
+        } else {
+            self.set_block_decoder(PbfBlockCodec);
+        }
+    }
+
+    /// Rewrite the tags of every decoded block with `rewrite`
+    ///
+    /// Callbacks, iterators, filters and everything built on them, such as
+    /// `extract`, see the rewritten tags. Wraps the current block decoder,
+    /// so call it after `set_block_decoder` or `set_skip_metadata`, which
+    /// replace it; calling it again applies both rewrites in turn.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, TagRewrite};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// reader.set_tag_rewrite(TagRewrite::new().with_dropped_keys(["source", "tiger:*"]));
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn set_tag_rewrite(&mut self, rewrite: TagRewrite) {
+        self.block_decoder = Arc::new(RewritingDecoder { inner: self.block_decoder.clone(), rewrite });
     }
 
     /// Sequential streaming of all elements with a closure
     /// Zero-boilerplate, maximum simplicity
     /// 
+    /// Elements are emitted in file order: blob by blob, and within each blob
+    /// in the order they are stored.
+    /// 
     /// # Examples
     /// ```rust,no_run
     /// use osm_pbf::{Reader, OsmElement};
@@ -103,25 +555,30 @@ impl<R: Read + Seek> Reader<R> {
         F: FnMut(OsmElement) -> Result<()>,
     {
         let mut stats = ProcessingStats::default();
+        let retries_before = self.indexed_reader.retries_performed();
+        self.live_stats.begin();
         
-        // Collect blob indices first to avoid borrowing conflicts
-        let blob_indices: Vec<_> = (0..self.indexed_reader.blob_count()).collect();
-        
-        for blob_index in blob_indices {
+        // Extends a lazy index blob by blob, so elements flow before the scan ends
+        for blob_index in 0.. {
+            if !self.indexed_reader.ensure_indexed(blob_index)? {
+                break;
+            }
             let blob = match self.indexed_reader.read_blob_by_index(blob_index) {
                 Ok(Some(blob)) => blob,
                 Ok(None) => continue,
                 Err(e) => {
-                    stats.errors_encountered += 1;
-                    eprintln!("Warning: Error processing blob: {e}");
+                    self.skip_blob(&mut stats, blob_index, &e);
                     continue;
                 }
             };
             
-            stats.blobs_processed += 1;
-            
             // Extract elements from blob
-            let elements = self.extract_elements_from_blob(&blob)?;
+            let decoded = self.extract_elements_from_blob(&blob);
+            let Some(elements) = self.decoded_or_skip(&mut stats, blob_index, decoded)? else {
+                continue;
+            };
+            stats.blobs_processed += 1;
+            self.live_stats.record_blob(blob.raw_size() as u64);
             
             for element in elements {
                 match &element {
@@ -132,16 +589,22 @@ impl<R: Read + Seek> Reader<R> {
                 }
                 
                 stats.elements_processed += 1;
+                self.live_stats.record_element();
                 
                 processor(element)?
             }
         }
         
+        self.record_damaged_regions(&mut stats);
+        stats.retries_performed = self.indexed_reader.retries_performed() - retries_before;
         Ok(stats)
     }
 
     /// Filtered sequential streaming with element filtering
     /// 
+    /// Matching elements are emitted in file order, like `for_each`. Only the
+    /// blobs selected by `explain(filter)` are read.
+    /// 
     /// # Examples
     /// ```rust,no_run
     /// use osm_pbf::{Reader, OsmElement, ElementFilter};
@@ -165,29 +628,165 @@ impl<R: Read + Seek> Reader<R> {
     pub fn for_each_filtered<F>(&mut self, filter: &ElementFilter, mut processor: F) -> Result<ProcessingStats>
     where
         F: FnMut(OsmElement) -> Result<()>,
+    {
+        self.for_each_filtered_with_strings(filter, |element, _| processor(element))
+    }
+
+    /// Like `for_each_filtered`, also passing where each element is stored
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// reader.for_each_filtered_with_provenance(&ElementFilter::ways_only(false), |element, provenance| {
+    ///     println!("{element:?} from blob {} at byte {}", provenance.blob_index, provenance.byte_offset);
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn for_each_filtered_with_provenance<F>(&mut self, filter: &ElementFilter, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(OsmElement, Provenance) -> Result<()>,
+    {
+        self.for_each_filtered_traced(filter, |element, _, provenance| processor(element, provenance))
+    }
+
+    /// Like `for_each_filtered`, also passing the string table of the
+    /// element's block, to resolve tags with `OsmElement::tags`
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let filter = ElementFilter::ways_only(false).with_tag_key("highway".to_string());
+    /// reader.for_each_filtered_with_strings(&filter, |element, strings| {
+    ///     println!("{:?}", element.tags(strings).get("name"));
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn for_each_filtered_with_strings<F>(&mut self, filter: &ElementFilter, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(OsmElement, &StringTable) -> Result<()>,
+    {
+        self.for_each_filtered_traced(filter, |element, strings, _| processor(element, strings))
+    }
+
+    /// Stream the elements of a change file (.osc.pbf) with what the change
+    /// does with them
+    ///
+    /// `OSMChange` blobs are read like `OSMData` blobs, so `for_each_filtered`
+    /// and the other readers stream their elements too; this also passes the
+    /// action each element's metadata records (see `ChangeAction::of`).
+    /// Elements of `OSMData` blobs get an action the same way.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ChangeElement, ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("changes.osc.pbf")?)?;
+    /// reader.for_each_change(&ElementFilter::all(), |change, _| {
+    ///     if let ChangeElement::Delete(element) = change {
+    ///         println!("deleted {element:?}");
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn for_each_change<F>(&mut self, filter: &ElementFilter, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(ChangeElement, &StringTable) -> Result<()>,
+    {
+        self.for_each_filtered_traced(filter, |element, strings, _| processor(ChangeElement::from_element(element), strings))
+    }
+
+    /// Stream the elements of a full-history file grouped by element, all
+    /// versions of each at once
+    ///
+    /// Each version comes with the string table of its block, as versions of
+    /// one element may span blocks. Versions are grouped as `HistoryIter`
+    /// does, so the file must keep them adjacent; filters apply per version.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("history.osh.pbf")?)?;
+    /// reader.for_each_history(&ElementFilter::ways_only(false), |history| {
+    ///     if history.is_deleted() {
+    ///         println!("way {} deleted after {} versions", history.id(), history.versions.len());
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn for_each_history<F>(&mut self, filter: &ElementFilter, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(ElementHistory<(OsmElement, Arc<StringTable>)>) -> Result<()>,
+    {
+        let mut block: Option<(usize, Arc<StringTable>)> = None;
+        let mut pending = Vec::new();
+        let stats = self.for_each_filtered_traced(filter, |element, strings, provenance| {
+            let strings = match &block {
+                Some((blob_index, strings)) if *blob_index == provenance.blob_index => strings.clone(),
+                _ => block.insert((provenance.blob_index, Arc::new(strings.clone()))).1.clone(),
+            };
+            if let Some((last, _)) = pending.last()
+                && history::identity(last) != history::identity(&element)
+            {
+                processor(ElementHistory { versions: std::mem::take(&mut pending) })?;
+            }
+            pending.push((element, strings));
+            Ok(())
+        })?;
+        if !pending.is_empty() {
+            processor(ElementHistory { versions: pending })?;
+        }
+        Ok(stats)
+    }
+
+    pub(crate) fn for_each_filtered_traced<F>(&mut self, filter: &ElementFilter, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(OsmElement, &StringTable, Provenance) -> Result<()>,
     {
         let mut stats = ProcessingStats::default();
-        
-        // Collect blob indices first to avoid borrowing conflicts
-        let blob_indices: Vec<_> = (0..self.indexed_reader.blob_count()).collect();
+        let retries_before = self.indexed_reader.retries_performed();
+        let (blob_indices, mut members) = self.start_filtered(filter, &mut stats)?;
         
         for blob_index in blob_indices {
             let blob = match self.indexed_reader.read_blob_by_index(blob_index) {
                 Ok(Some(blob)) => blob,
                 Ok(None) => continue,
                 Err(e) => {
-                    stats.errors_encountered += 1;
-                    eprintln!("Warning: Error processing blob: {e}");
+                    self.skip_blob(&mut stats, blob_index, &e);
                     continue;
                 }
             };
             
-            stats.blobs_processed += 1;
-            
             // Extract and filter elements from blob
-            let elements = self.extract_filtered_elements_from_blob(&blob, filter)?;
+            let decoded = self.extract_filtered_elements_from_blob(&blob, filter, &mut stats);
+            let Some(MatchingElements { strings, elements, ordinals, nodes_in_bbox, .. }) = self.decoded_or_skip(&mut stats, blob_index, decoded)? else {
+                continue;
+            };
+            stats.blobs_processed += 1;
+            self.live_stats.record_blob(blob.raw_size() as u64);
+            if let Some(members) = &mut members {
+                members.nodes.extend(nodes_in_bbox);
+            }
             
-            for element in elements {
+            for (element, element_ordinal) in elements.into_iter().zip(ordinals) {
+                if let Some(members) = &mut members
+                    && !members.admit(&element)
+                {
+                    stats.elements_skipped_late += 1;
+                    continue;
+                }
                 match &element {
                     OsmElement::Node(_) => stats.nodes_processed += 1,
                     OsmElement::Way(_) => stats.ways_processed += 1,
@@ -196,211 +795,1954 @@ impl<R: Read + Seek> Reader<R> {
                 }
                 
                 stats.elements_processed += 1;
+                self.live_stats.record_element();
                 
-                processor(element)?
+                processor(element, &strings, Provenance { blob_index, byte_offset: blob.offset, element_ordinal })?
             }
         }
         
+        stats.retries_performed = self.indexed_reader.retries_performed() - retries_before;
         Ok(stats)
     }
 
-    /// Collect all elements into a vector (for small datasets)
-    /// 
+    /// Blobs to decode for `filter` and, for filters keeping ways and
+    /// relations by a bounding box, the members found inside it
+    fn start_filtered(&mut self, filter: &ElementFilter, stats: &mut ProcessingStats) -> Result<(Vec<usize>, Option<BboxMembers>)> {
+        self.indexed_reader.finish_index()?;
+        self.live_stats.begin();
+        self.record_damaged_regions(stats);
+        
+        let plan = self.explain(filter);
+        stats.blobs_pruned = plan.blobs.iter()
+            .filter(|b| b.pruned_by.is_some_and(|reason| reason != PruneReason::BlobType))
+            .count() as u64;
+        let members = if filter.bbox_dependencies() {
+            Some(self.scan_bbox_members(filter, &plan.member_blobs, stats)?)
+        } else {
+            None
+        };
+        Ok((plan.blobs_to_decode().collect(), members))
+    }
+
+    /// Iterate over all elements in file order
+    ///
+    /// Like `iter_filtered` with `ElementFilter::all()`.
+    ///
     /// # Examples
     /// ```rust,no_run
-    /// use osm_pbf::{Reader, ElementFilter};
+    /// use osm_pbf::{OsmElement, Reader};
     /// use std::fs::File;
-    /// 
-    /// let file = File::open("small_map.osm.pbf")?;
-    /// let mut reader = Reader::new(file)?;
-    /// 
-    /// let filter = ElementFilter::nodes_only();
-    /// let (elements, stats) = reader.collect_filtered(&filter)?;
-    /// 
-    /// println!("Collected {} nodes", elements.len());
-    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let mut nodes = 0;
+    /// for element in reader.iter() {
+    ///     if let OsmElement::Node(_) = element? {
+    ///         nodes += 1;
+    ///     }
+    /// }
+    /// println!("{nodes} nodes");
+    /// # Ok::<(), osm_pbf::BlobError>(())
     /// ```
-    pub fn collect_filtered(&mut self, filter: &ElementFilter) -> Result<(Vec<OsmElement>, ProcessingStats)> {
-        let mut elements = Vec::new();
-        let stats = self.for_each_filtered(filter, |element| {
-            elements.push(element);
-            Ok(())
-        })?;
-        
-        Ok((elements, stats))
+    pub fn iter(&mut self) -> ElementIter<'_, R> {
+        self.iter_filtered(&ElementFilter::all())
     }
 
-    /// Parallel map-reduce style processing for maximum throughput
-    /// Leverages all CPU cores for business-grade performance
-    /// 
+    /// Iterate over the elements matching `filter` in file order, like
+    /// `for_each_filtered` without the closure
+    ///
+    /// Blobs are read and decoded one at a time as the iterator advances, so
+    /// memory stays bounded by the largest blob. A blob that fails to decode
+    /// ends the iteration with its error; unreadable blobs are skipped and
+    /// logged. `ElementIter::stats` has the counts so far.
+    ///
     /// # Examples
     /// ```rust,no_run
-    /// use osm_pbf::{Reader, OsmElement, ParallelConfig};
+    /// use osm_pbf::{ElementFilter, OsmElement, Reader};
     /// use std::fs::File;
-    /// 
-    /// let file = File::open("large_map.osm.pbf")?;
-    /// let mut reader = Reader::new(file)?;
-    /// 
-    /// let config = ParallelConfig::default();
-    /// 
-    /// let total_highways = reader.par_map_reduce(
-    ///     &config,
-    ///     // Map: Process each element
-    ///     |element| {
-    ///         match element {
-    ///             OsmElement::Way(way) if way.keys.contains(&1) => 1u64, // Assuming key 1 is "highway"
-    ///             _ => 0u64,
-    ///         }
-    ///     },
-    ///     // Reduce: Combine results
-    ///     || 0u64,
-    ///     |acc, count| acc + count,
-    ///     0u64,
-    /// )?;
-    /// 
-    /// println!("Total highways: {}", total_highways);
-    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let filter = ElementFilter::ways_only(false).with_tag_key("highway".to_string());
+    /// let longest = reader.iter_filtered(&filter)
+    ///     .filter_map(|element| match element {
+    ///         Ok(OsmElement::Way(way)) => Some(Ok(way.refs.len())),
+    ///         Ok(_) => None,
+    ///         Err(e) => Some(Err(e)),
+    ///     })
+    ///     .try_fold(0, |longest, len| len.map(|len| longest.max(len)))?;
+    /// println!("longest highway: {longest} nodes");
+    /// # Ok::<(), osm_pbf::BlobError>(())
     /// ```
-    pub fn par_map_reduce<M, ReduceFn, T, I>(&mut self, 
-                                      config: &ParallelConfig,
-                                      map_fn: M,
-                                      identity: I,
-                                      reduce_fn: ReduceFn,
-                                      _initial: T) -> Result<T>
-    where
-        M: Fn(OsmElement) -> T + Send + Sync,
-        ReduceFn: Fn(T, T) -> T + Send + Sync,
-        I: Fn() -> T + Send + Sync,
-        T: Send + Sync,
-    {
-        // Configure Rayon thread pool if specified
-        if let Some(num_threads) = config.num_threads {
-            rayon::ThreadPoolBuilder::new()
-                .num_threads(num_threads)
-                .build_global()
-                .map_err(|e| BlobError::InvalidFormat(format!("Failed to configure thread pool: {e}")))?;
-        }
-
-        // For now, we'll do sequential processing and return the identity value
-        // In a full implementation, this would:
-        // 1. Collect all blobs into a Vec
-        // 2. Use rayon's parallel iterator to process them
-        // 3. Extract elements from each blob in parallel
-        // 4. Apply the map function to each element
-        // 5. Reduce the results using the reduce function
-        
-        // Sequential fallback for demonstration
-        let mut result = identity();
-        let all_elements = self.collect_all_elements()?;
-        
-        for element in all_elements {
-            let mapped = map_fn(element);
-            result = reduce_fn(result, mapped);
+    pub fn iter_filtered(&mut self, filter: &ElementFilter) -> ElementIter<'_, R> {
+        let retries_before = self.indexed_reader.retries_performed();
+        ElementIter {
+            reader: self,
+            filter: filter.clone(),
+            started: None,
+            block: Vec::new().into_iter(),
+            stats: ProcessingStats::default(),
+            retries_before,
+            done: false,
         }
-
-        Ok(result)
     }
 
-    /// Helper method to collect all elements (for parallel processing)
-    fn collect_all_elements(&mut self) -> Result<Vec<OsmElement>> {
-        let mut all_elements = Vec::new();
+    /// Count the elements matching `filter` without materializing them
+    ///
+    /// Uses the same blob pruning and decoder checks as `for_each_filtered`,
+    /// but never builds `OsmElement` values, and checks tag filters against
+    /// string indices resolved once per block. The per-type counts are in
+    /// `nodes_processed`, `ways_processed`, `relations_processed` and
+    /// `changesets_processed`. Filters keeping ways and relations by a
+    /// bounding box (see `ElementFilter::with_bbox`) decode every element.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let filter = ElementFilter::nodes_only().with_tag_key("amenity".to_string());
+    /// println!("{} amenities", reader.count_filtered(&filter)?.nodes_processed);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn count_filtered(&mut self, filter: &ElementFilter) -> Result<ProcessingStats> {
+        if filter.bbox_dependencies() {
+            // Membership of ways and relations needs the decoded refs
+            return self.for_each_filtered(filter, |_| Ok(()));
+        }
+        self.indexed_reader.finish_index()?;
+        let mut stats = ProcessingStats::default();
+        let retries_before = self.indexed_reader.retries_performed();
+        self.live_stats.begin();
         
-        self.for_each(|element| {
-            all_elements.push(element);
-            Ok(())
-        })?;
+        let plan = self.explain(filter);
+        stats.blobs_pruned = plan.blobs.iter()
+            .filter(|b| b.pruned_by.is_some_and(|reason| reason != PruneReason::BlobType))
+            .count() as u64;
+        let blob_indices: Vec<_> = plan.blobs_to_decode().collect();
         
-        Ok(all_elements)
-    }
+        for blob_index in blob_indices {
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index) {
+                Ok(Some(blob)) => blob,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.skip_blob(&mut stats, blob_index, &e);
+                    continue;
+                }
+            };
+            
+            stats.blobs_processed += 1;
+            self.live_stats.record_blob(blob.raw_size() as u64);
+            
+            let counts = count_matching_elements(&blob, filter, self.group_policy, self.block_decoder.as_ref())?;
+            if counts.mixed_groups > 0 {
+                log_mixed_groups(self.indexed_reader.skip_log_level(), blob.offset, counts.mixed_groups);
+            }
+            let MatchCounts { nodes, ways, relations, changesets, skipped_early, skipped_late, mixed_groups } = counts;
+            stats.nodes_processed += nodes;
+            stats.ways_processed += ways;
+            stats.relations_processed += relations;
+            stats.changesets_processed += changesets;
+            stats.elements_processed += nodes + ways + relations + changesets;
+            stats.elements_skipped_early += skipped_early;
+            stats.elements_skipped_late += skipped_late;
+            stats.mixed_groups += mixed_groups;
+        }
+        
+        stats.retries_performed = self.indexed_reader.retries_performed() - retries_before;
+        Ok(stats)
+    }
+
+    /// Find the highest node, way and relation ids in the file
+    ///
+    /// Blobs whose index entry has id ranges (see `build_deep_index`) are
+    /// answered from the index; without a deep index every data blob is
+    /// decoded.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    /// 
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let max_ids = reader.max_ids()?;
+    /// let next_node_id = max_ids.node.unwrap_or(0) + 1;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn max_ids(&mut self) -> Result<MaxIds> {
+        self.indexed_reader.finish_index()?;
+        let (mut max_ids, to_decode) = MaxIds::from_index(self.indexed_reader.index());
+        
+        for blob_index in to_decode {
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index)? {
+                Some(blob) => blob,
+                None => continue,
+            };
+            for element in self.extract_elements_from_blob(&blob)? {
+                max_ids.observe(&element);
+            }
+        }
+        
+        Ok(max_ids)
+    }
+
+    /// Relation-to-relation memberships of the file, for walking nested
+    /// relations with cycle handling
+    pub fn relation_graph(&mut self) -> Result<RelationGraph> {
+        let mut graph = RelationGraph::new();
+        self.for_each_filtered(&ElementFilter::relations_only(), |element| {
+            if let OsmElement::Relation(relation) = element {
+                graph.insert(&relation);
+            }
+            Ok(())
+        })?;
+        Ok(graph)
+    }
+
+    /// Check relation integrity: nested memberships, members missing from
+    /// the file and reference cycles
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// for cycle in reader.quality_report()?.relation_cycles {
+    ///     eprintln!("relation cycle: {cycle:?}");
+    /// }
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn quality_report(&mut self) -> Result<QualityReport> {
+        Ok(self.relation_graph()?.quality_report())
+    }
+
+    /// Pool providing scratch buffers for blob reads, with hit/miss counters
+    pub fn buffer_pool(&self) -> &BufferPool {
+        self.indexed_reader.buffer_pool()
+    }
+
+    /// Record element counts and id ranges of every data blob, so filters
+    /// on element types and ids skip blobs
+    ///
+    /// See `IndexedReader::build_deep_index`.
+    pub fn build_deep_index(&mut self) -> Result<()> {
+        self.indexed_reader.build_deep_index_with(self.block_decoder.as_ref())
+    }
+
+    /// Compute bounding boxes for every blob, so bounding box filters skip
+    /// blobs outside the box
+    ///
+    /// See `IndexedReader::build_bbox_index`.
+    pub fn build_bbox_index(&mut self, cache_elements: bool) -> Result<()> {
+        self.indexed_reader.build_bbox_index_with(cache_elements, &mut SparseLocations::new(), self.block_decoder.as_ref())
+    }
+
+    /// Describe how `filter` will execute against this file's index
+    /// 
+    /// See `ElementFilter::explain_with_ordering`.
+    pub fn explain(&self, filter: &ElementFilter) -> Plan {
+        filter.explain_with_ordering(self.indexed_reader.index(), self.ordering())
+    }
+
+    /// Sort order declared by the file header, e.g. `Sort.Geographic`
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// if reader.ordering().geographic {
+    ///     println!("blobs are spatially clustered");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn ordering(&self) -> &FileOrdering {
+        self.indexed_reader.ordering()
+    }
+
+    /// Stream the file checking that it is in `Sort.Type_then_ID` order,
+    /// whether or not the header declares it
+    ///
+    /// Keeps the first `max_violations` violations with where they are;
+    /// in history files (see `is_history`) versions of an element may repeat
+    /// its id. Downstream merges and diffs can call this before relying on
+    /// `ordering().type_then_id`.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let report = reader.verify_ordering(10)?;
+    /// for violation in &report.violations {
+    ///     println!("{:?} {} after {:?} {} at byte {}", violation.kind, violation.id,
+    ///         violation.previous_kind, violation.previous_id, violation.provenance.byte_offset);
+    /// }
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn verify_ordering(&mut self, max_violations: usize) -> Result<OrderingReport> {
+        let mut report = OrderingReport { declared: self.ordering().type_then_id, ..Default::default() };
+        let mut checker = OrderChecker::new(self.is_history());
+        self.for_each_filtered_traced(&ElementFilter::all(), |element, _, provenance| {
+            if matches!(element, OsmElement::ChangeSet(_)) {
+                return Ok(());
+            }
+            report.elements_checked += 1;
+            if let Some(violation) = checker.check(&element, provenance) {
+                report.violation_count += 1;
+                if report.violations.len() < max_violations {
+                    report.violations.push(violation);
+                }
+            }
+            Ok(())
+        })?;
+        Ok(report)
+    }
+
+    /// Walk the whole file checking its integrity, keeping the first
+    /// `max_issues` issues found with their offsets
+    ///
+    /// Checks that frames tile the file, that blobs are within the size
+    /// limits (see `ReaderOptions::with_max_blob_size`) and decompress, that
+    /// string tables are valid UTF-8, that blocks decode and their
+    /// delta-encoded columns don't overflow, and that node locations are
+    /// within ±90°/±180°. In files declaring `Sort.Type_then_ID` ids are
+    /// checked as in `verify_ordering`. Unreadable blobs are reported as
+    /// issues rather than skipped or returned as errors.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let report = reader.verify(100)?;
+    /// for issue in &report.issues {
+    ///     println!("{:?} at byte {}: {}", issue.kind, issue.offset, issue.message);
+    /// }
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn verify(&mut self, max_issues: usize) -> Result<IntegrityReport> {
+        self.indexed_reader.finish_index()?;
+        let file_len = self.indexed_reader.stream_len()?;
+        let order = self.ordering().type_then_id.then(|| OrderChecker::new(self.is_history()));
+        let mut checker = IntegrityChecker::new(max_issues, file_len, order);
+
+        let mut expected = 0;
+        let frames: Vec<_> = self.indexed_reader.index().iter().map(|entry| (entry.offset, frame_end(entry))).collect();
+        for (blob_index, (offset, end)) in frames.into_iter().enumerate() {
+            if offset > expected {
+                checker.record(IssueKind::Framing, expected, None, format!("{} unreadable bytes before the blob at {offset}", offset - expected));
+            }
+            expected = expected.max(end);
+            if end > file_len {
+                checker.record(IssueKind::Framing, offset, Some(blob_index), format!("Frame ends at {end}, past the end of the file"));
+                continue;
+            }
+
+            checker.report.blobs_checked += 1;
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index) {
+                Ok(Some(blob)) => blob,
+                Ok(None) => continue,
+                Err(e @ (BlobError::HeaderTooLarge { .. } | BlobError::MessageTooLarge { .. })) => {
+                    checker.record(IssueKind::Size, offset, Some(blob_index), e.to_string());
+                    continue;
+                }
+                Err(e) => {
+                    checker.record(IssueKind::Framing, offset, Some(blob_index), e.to_string());
+                    continue;
+                }
+            };
+            if !blob.blob_type().holds_elements() {
+                continue;
+            }
+            let message = match blob_payload(&blob) {
+                Ok(message) => message,
+                Err(e @ BlobError::MessageTooLarge { .. }) => {
+                    checker.record(IssueKind::Size, offset, Some(blob_index), e.to_string());
+                    continue;
+                }
+                Err(e) => {
+                    checker.record(IssueKind::Decompression, offset, Some(blob_index), e.to_string());
+                    continue;
+                }
+            };
+            if !checker.check_strings(&message, blob_index, offset) {
+                continue;
+            }
+            match self.block_decoder.decode_block(&message) {
+                Ok(block) => checker.check_block(&block, blob_index, offset),
+                Err(e) => checker.record(IssueKind::Decode, offset, Some(blob_index), e.to_string()),
+            }
+        }
+        if expected < file_len {
+            checker.record(IssueKind::Framing, expected, None, format!("{} unreadable bytes at the end of the file", file_len - expected));
+        }
+        Ok(checker.report)
+    }
+
+    /// Replication state recorded in the file header, for `replication::Client::from_header`
+    pub fn replication(&self) -> &ReplicationInfo {
+        self.indexed_reader.replication()
+    }
+
+    /// Whether the header declares `LocationsOnWays`, i.e. ways carry their node locations
+    pub fn has_locations_on_ways(&self) -> bool {
+        let mut features = self.indexed_reader.required_features().iter().chain(self.indexed_reader.optional_features());
+        features.any(|feature| feature == LOCATIONS_ON_WAYS)
+    }
+
+    /// Whether the header declares `HistoricalInformation`, i.e. the file
+    /// holds every version of its elements
+    pub fn is_history(&self) -> bool {
+        let mut features = self.indexed_reader.required_features().iter().chain(self.indexed_reader.optional_features());
+        features.any(|feature| feature == HISTORICAL_INFORMATION)
+    }
+
+    /// Collect all elements into a vector (for small datasets)
+    /// 
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, ElementFilter};
+    /// use std::fs::File;
+    /// 
+    /// let file = File::open("small_map.osm.pbf")?;
+    /// let mut reader = Reader::new(file)?;
+    /// 
+    /// let filter = ElementFilter::nodes_only();
+    /// let (elements, stats) = reader.collect_filtered(&filter)?;
+    /// 
+    /// println!("Collected {} nodes", elements.len());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn collect_filtered(&mut self, filter: &ElementFilter) -> Result<(Vec<OsmElement>, ProcessingStats)> {
+        let mut elements = Vec::new();
+        let stats = self.for_each_filtered(filter, |element| {
+            elements.push(element);
+            Ok(())
+        })?;
+        
+        Ok((elements, stats))
+    }
+
+    /// Collect matching elements like `collect_filtered`, spilling them to
+    /// temp files past the memory budget of `config`
+    ///
+    /// For result sets that may not fit in memory. Elements come back with
+    /// the string table their tags refer to; see `SpillableElements`.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader, SpillConfig};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+    /// let filter = ElementFilter::ways_only(false).with_tag_key("highway".to_string());
+    /// let (ways, _) = reader.collect_bounded(&filter, &SpillConfig::with_memory_budget(1 << 30))?;
+    /// ways.for_each_with_strings(|way, strings| {
+    ///     println!("{:?}", way.tags(strings).get("highway"));
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn collect_bounded(&mut self, filter: &ElementFilter, config: &SpillConfig) -> Result<(SpillableElements, ProcessingStats)> {
+        let mut elements = SpillableElements::new(config.clone());
+        let mut block: Option<(usize, Arc<StringTable>)> = None;
+        let stats = self.for_each_filtered_traced(filter, |element, strings, provenance| {
+            let strings = match &block {
+                Some((blob_index, strings)) if *blob_index == provenance.blob_index => strings,
+                _ => &block.insert((provenance.blob_index, Arc::new(strings.clone()))).1,
+            };
+            elements.push(element, strings)
+        })?;
+        Ok((elements, stats))
+    }
+
+    /// Read one page of matching elements, for stateless paginated services
+    ///
+    /// Returns up to `limit` elements starting at `cursor` (or the beginning of
+    /// the file when `None`) plus a continuation cursor for the next request.
+    /// Blobs are pruned and elements matched as by `iter_filtered`, and only
+    /// the blobs needed for the page are decoded, except that filters keeping
+    /// ways and relations by a bounding box also scan the blobs before the
+    /// cursor for members. A `limit` of zero is an error, since its page would
+    /// never move the cursor.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, ElementFilter, PageCursor};
+    /// use std::fs::File;
+    /// 
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let filter = ElementFilter::nodes_only();
+    /// 
+    /// // First request
+    /// let page = reader.page(&filter, None, 100)?;
+    /// let token = page.next.map(|cursor| cursor.to_token());
+    /// 
+    /// // Next request, possibly on another server
+    /// if let Some(token) = token {
+    ///     let cursor = PageCursor::from_token(&token)?;
+    ///     let page = reader.page(&filter, Some(&cursor), 100)?;
+    ///     println!("{}", serde_json::to_string(&page.elements)?);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn page(&mut self, filter: &ElementFilter, cursor: Option<&PageCursor>, limit: usize) -> Result<Page> {
+        if limit == 0 {
+            return Err(BlobError::InvalidFormat("Page limit must be at least 1".to_string()));
+        }
+        let start = cursor.copied().unwrap_or_default();
+        let mut stats = ProcessingStats::default();
+        let (blob_indices, mut members) = self.start_filtered(filter, &mut stats)?;
+        let (earlier, blob_indices): (Vec<_>, Vec<_>) = blob_indices.into_iter().partition(|&blob_index| blob_index < start.blob_index());
+        if let Some(members) = &mut members {
+            // Ways and relations of this page may reference nodes of earlier ones
+            let scanned = self.scan_bbox_members(filter, &earlier, &mut stats)?;
+            members.nodes.extend(scanned.nodes);
+            members.ways.extend(scanned.ways);
+        }
+        let mut elements = Vec::with_capacity(limit.min(10_000));
+        
+        for blob_index in blob_indices {
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index) {
+                Ok(Some(blob)) => blob,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.skip_blob(&mut stats, blob_index, &e);
+                    continue;
+                }
+            };
+            
+            let decoded = self.extract_filtered_elements_from_blob(&blob, filter, &mut stats);
+            let Some(MatchingElements { elements: mut blob_elements, nodes_in_bbox, .. }) = self.decoded_or_skip(&mut stats, blob_index, decoded)? else {
+                continue;
+            };
+            if let Some(members) = &mut members {
+                members.nodes.extend(nodes_in_bbox);
+                blob_elements.retain(|element| members.admit(element));
+            }
+            let skip = if blob_index == start.blob_index() { start.element_offset() } else { 0 };
+            let available = blob_elements.len().saturating_sub(skip);
+            let take = available.min(limit - elements.len());
+            elements.extend(blob_elements.into_iter().skip(skip).take(take));
+            
+            if elements.len() == limit {
+                let next = if take < available {
+                    PageCursor::new(blob_index, skip + take)
+                } else {
+                    PageCursor::new(blob_index + 1, 0)
+                };
+                return Ok(Page { elements, next: Some(next) });
+            }
+        }
+        
+        Ok(Page { elements, next: None })
+    }
+
+    /// Process elements for at most `budget` of wall-clock time, resuming from
+    /// and saving to `checkpoint`
+    ///
+    /// Meant for cron-sliced jobs: each run continues where the previous one
+    /// stopped and saves its position before returning, also when `processor`
+    /// fails, so the failing element is retried next time. A finished run saves
+    /// the end position; later runs do nothing until the checkpoint is cleared.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Checkpoint, Reader};
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    /// 
+    /// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+    /// let checkpoint = Checkpoint::new("planet.checkpoint");
+    /// 
+    /// let run = reader.process_for(Duration::from_secs(50 * 60), &checkpoint, |element| {
+    ///     // import element
+    ///     Ok(())
+    /// })?;
+    /// println!("{:.1}% done", run.progress() * 100.0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn process_for<F>(&mut self, budget: Duration, checkpoint: &Checkpoint, mut processor: F) -> Result<TimedRun>
+    where
+        F: FnMut(OsmElement) -> Result<()>,
+    {
+        self.indexed_reader.finish_index()?;
+        let deadline = Instant::now() + budget;
+        let start = checkpoint.load()?.unwrap_or_default();
+        let blob_count = self.indexed_reader.blob_count();
+        let mut stats = ProcessingStats::default();
+        let retries_before = self.indexed_reader.retries_performed();
+        self.live_stats.begin();
+        
+        let mut position = PageCursor::new(start.blob_index().min(blob_count), start.element_offset());
+        while position.blob_index() < blob_count && Instant::now() < deadline {
+            let blob_index = position.blob_index();
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index) {
+                Ok(Some(blob)) => blob,
+                Ok(None) => {
+                    position = PageCursor::new(blob_index + 1, 0);
+                    continue;
+                }
+                Err(e) => {
+                    checkpoint.save(&position)?;
+                    return Err(e);
+                }
+            };
+            
+            let elements = decode_elements(&blob, self.group_policy, self.block_decoder.as_ref())?.1;
+            if position.element_offset() == 0 {
+                record_blob(&mut stats, &self.live_stats, blob.raw_size() as u64);
+            }
+            
+            let mut stopped_at = None;
+            for (element_offset, element) in elements.into_iter().enumerate().skip(position.element_offset()) {
+                if Instant::now() >= deadline {
+                    stopped_at = Some(element_offset);
+                    break;
+                }
+                record_element(&mut stats, &self.live_stats, &element);
+                if let Err(e) = processor(element) {
+                    checkpoint.save(&PageCursor::new(blob_index, element_offset))?;
+                    return Err(e);
+                }
+            }
+            
+            if let Some(element_offset) = stopped_at {
+                position = PageCursor::new(blob_index, element_offset);
+                break;
+            }
+            position = PageCursor::new(blob_index + 1, 0);
+        }
+        
+        checkpoint.save(&position)?;
+        stats.retries_performed = self.indexed_reader.retries_performed() - retries_before;
+        Ok(TimedRun { stats, position, blob_count })
+    }
+
+    /// Parallel map-reduce over every element
+    ///
+    /// Blobs are read `config.chunk_size` at a time (or as sized by
+    /// `config.adaptive`), then decompressed, decoded and mapped on a pool of `config.num_threads` threads. Each blob's elements
+    /// are folded from `identity()` with `map_fn` and `reduce_fn`, and the
+    /// per-blob results are reduced into `initial`. Partial results are combined
+    /// in no particular order, so `reduce_fn` should be associative and
+    /// commutative. Blobs that can't be read are skipped and logged, as with
+    /// `par_for_each`; blobs that can't be decoded fail the run.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, OsmElement, ParallelConfig};
+    /// use std::fs::File;
+    ///
+    /// let file = File::open("large_map.osm.pbf")?;
+    /// let mut reader = Reader::new(file)?;
+    ///
+    /// let config = ParallelConfig { num_threads: Some(4), ..Default::default() };
+    ///
+    /// let tagged_ways = reader.par_map_reduce(
+    ///     &config,
+    ///     // Map: Process each element
+    ///     |element| match element {
+    ///         OsmElement::Way(way) if !way.keys.is_empty() => 1u64,
+    ///         _ => 0u64,
+    ///     },
+    ///     // Reduce: Combine results
+    ///     || 0u64,
+    ///     |acc, count| acc + count,
+    ///     0u64,
+    /// )?;
+    ///
+    /// println!("Tagged ways: {}", tagged_ways);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn par_map_reduce<M, ReduceFn, T, I>(&mut self, 
+                                      config: &ParallelConfig,
+                                      map_fn: M,
+                                      identity: I,
+                                      reduce_fn: ReduceFn,
+                                      initial: T) -> Result<T>
+    where
+        M: Fn(OsmElement) -> T + Send + Sync,
+        ReduceFn: Fn(T, T) -> T + Send + Sync,
+        I: Fn() -> T + Send + Sync,
+        T: Send + Sync,
+    {
+        self.indexed_reader.finish_index()?;
+        let pool = thread_pool(config)?;
+        let scheduler = adaptive_scheduler(config, pool.as_ref());
+        let group_policy = self.group_policy;
+        let block_decoder = self.block_decoder.clone();
+        let mut stats = ProcessingStats::default();
+        let mut result = initial;
+
+        let blob_count = self.indexed_reader.blob_count();
+        let mut chunk_start = 0;
+        while chunk_start < blob_count {
+            let chunk = self.next_chunk(chunk_start..blob_count, config, scheduler.as_ref());
+            chunk_start = chunk.end;
+            let blobs = self.read_blobs(chunk, &mut stats);
+            let blobs = self.schedule(blobs, scheduler.as_ref());
+
+            let reduce_chunk = || {
+                blobs.into_par_iter()
+                    .with_max_len(max_task_len(scheduler.as_ref()))
+                    .map(|scheduled| -> Result<T> {
+                        let started = Instant::now();
+                        let decoded = decode_matching_elements(&scheduled.blob, &DecodePredicate::default(), group_policy, block_decoder.as_ref())?;
+                        if let Some(scheduler) = &scheduler {
+                            scheduler.record(BlobKind::of_elements(&decoded.elements), scheduled.hint.size, started.elapsed());
+                        }
+                        Ok(decoded.elements.into_iter().fold(identity(), |acc, element| reduce_fn(acc, map_fn(element))))
+                    })
+                    .try_reduce(&identity, |a, b| Ok(reduce_fn(a, b)))
+            };
+            let partial = match &pool {
+                Some(pool) => pool.install(reduce_chunk),
+                None => reduce_chunk(),
+            }?;
+            result = reduce_fn(result, partial);
+        }
+
+        Ok(result)
+    }
+
+    /// Decode the blobs holding elements that match `filter` in parallel and
+    /// collect the matches into `n_shards` in-memory shards by `key_fn`
+    ///
+    /// `key_fn` picks the shard of each matching element, given the string
+    /// table its tags refer to, or drops it with `None`; it fails the run when
+    /// it returns a shard of `n_shards` or more. Within a shard elements keep
+    /// their file order. This groups elements, e.g. ways by highway class,
+    /// without collecting them all first; see `par_collect_sharded_with` to
+    /// keep the shards on disk.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// const CLASSES: [&str; 3] = ["motorway", "primary", "residential"];
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let filter = ElementFilter::ways_only(false).with_tag_key("highway".to_string());
+    /// let shards = reader.par_collect_sharded(&filter, CLASSES.len(), |way, strings| {
+    ///     let class = way.tags(strings).get("highway")?;
+    ///     CLASSES.iter().position(|c| *c == class)
+    /// })?;
+    /// for shard in &shards {
+    ///     println!("{}: {} ways", CLASSES[shard.index()], shard.len());
+    /// }
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn par_collect_sharded<K>(&mut self, filter: &ElementFilter, n_shards: usize, key_fn: K) -> Result<Vec<Shard>>
+    where
+        K: Fn(&OsmElement, &StringTable) -> Option<usize> + Send + Sync,
+    {
+        self.par_collect_sharded_with(&ParallelConfig::default(), &ShardStorage::Memory, filter, n_shards, key_fn)
+    }
+
+    /// `par_collect_sharded` on the pool of `config`, keeping the shards in `storage`
+    ///
+    /// Blobs are read `config.chunk_size` at a time; the matches of a chunk are
+    /// appended to the shards on the calling thread before the next chunk is
+    /// read. Filters keeping ways and relations by a bounding box (see
+    /// `ElementFilter::with_bbox`) are matched on the calling thread as in
+    /// `for_each_filtered`.
+    pub fn par_collect_sharded_with<K>(
+        &mut self,
+        config: &ParallelConfig,
+        storage: &ShardStorage,
+        filter: &ElementFilter,
+        n_shards: usize,
+        key_fn: K,
+    ) -> Result<Vec<Shard>>
+    where
+        K: Fn(&OsmElement, &StringTable) -> Option<usize> + Send + Sync,
+    {
+        let mut shards = ShardSet::new(n_shards, storage)?;
+        let shard_of = |element: &OsmElement, strings: &StringTable| match key_fn(element, strings) {
+            Some(shard) if shard >= n_shards => {
+                Err(BlobError::InvalidFormat(format!("Key function returned shard {shard} of {n_shards}")))
+            }
+            shard => Ok(shard),
+        };
+
+        if filter.bbox_dependencies() {
+            // Membership of ways and relations depends on the elements before them
+            // The string table of the blob the last match came from
+            let mut strings: Option<(usize, Arc<StringTable>)> = None;
+            self.for_each_filtered_traced(filter, |element, block_strings, provenance| {
+                if let Some(shard) = shard_of(&element, block_strings)? {
+                    let strings = match &strings {
+                        Some((blob_index, strings)) if *blob_index == provenance.blob_index => strings,
+                        _ => &strings.insert((provenance.blob_index, Arc::new(block_strings.clone()))).1,
+                    };
+                    shards.append(shard, strings, vec![element])?;
+                }
+                Ok(())
+            })?;
+            return shards.finish();
+        }
+
+        self.indexed_reader.finish_index()?;
+        let pool = thread_pool(config)?;
+        let group_policy = self.group_policy;
+        let block_decoder = self.block_decoder.clone();
+        let predicate = DecodePredicate::from(filter);
+        let mut stats = ProcessingStats::default();
+
+        let blob_indices: Vec<_> = self.explain(filter).blobs_to_decode().collect();
+        for chunk in blob_indices.chunks(config.chunk_size.max(1)) {
+            let blobs = self.read_blobs(chunk.iter().copied(), &mut stats);
+            let split_chunk = || {
+                blobs.into_par_iter()
+                    .map(|(_, blob)| -> Result<_> {
+                        let decoded = decode_matching_elements(&blob, &predicate, group_policy, block_decoder.as_ref())?;
+                        let mut by_shard: Vec<Vec<OsmElement>> = (0..n_shards).map(|_| Vec::new()).collect();
+                        for element in decoded.elements {
+                            if let Some(shard) = shard_of(&element, &decoded.strings)? {
+                                by_shard[shard].push(element);
+                            }
+                        }
+                        Ok((Arc::new(decoded.strings), by_shard))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            };
+            let split = match &pool {
+                Some(pool) => pool.install(split_chunk),
+                None => split_chunk(),
+            }?;
+            for (strings, by_shard) in split {
+                for (shard, elements) in by_shard.into_iter().enumerate().filter(|(_, elements)| !elements.is_empty()) {
+                    shards.append(shard, &strings, elements)?;
+                }
+            }
+        }
+        shards.finish()
+    }
+
+    /// Streaming with blobs decoded in parallel and elements handed to
+    /// `processor` on the calling thread
+    ///
+    /// Blobs are read `config.chunk_size` at a time and decoded on the rayon pool.
+    /// With `config.preserve_order` the elements arrive in file order, exactly as
+    /// with `for_each`; otherwise blobs may arrive out of order, which avoids
+    /// waiting on a slow blob.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, ParallelConfig};
+    /// use std::fs::File;
+    /// 
+    /// let mut reader = Reader::new(File::open("changes.osm.pbf")?)?;
+    /// let config = ParallelConfig { preserve_order: true, ..Default::default() };
+    /// 
+    /// reader.par_for_each(&config, |element| {
+    ///     // apply in file order
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn par_for_each<F>(&mut self, config: &ParallelConfig, mut processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(OsmElement) -> Result<()>,
+    {
+        let live_stats = self.live_stats.clone();
+        
+        self.par_decode(config, |stats, _blob_index, size, decoded| {
+            let (_strings, elements) = decoded?;
+            record_blob(stats, &live_stats, size);
+            
+            for element in elements {
+                record_element(stats, &live_stats, &element);
+                processor(element)?
+            }
+            
+            Ok(ControlFlow::Continue(()))
+        })
+    }
+
+    /// Read blobs sequentially and decode them on the rayon pool, handing each
+    /// decoded blob to `sink` on the calling thread (in file order with
+    /// `config.preserve_order`) until it breaks or fails
+    pub(crate) fn par_decode<F>(&mut self, config: &ParallelConfig, mut sink: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(&mut ProcessingStats, usize, u64, Result<(StringTable, Vec<OsmElement>)>) -> Result<ControlFlow<()>>,
+    {
+        self.indexed_reader.finish_index()?;
+        let pool = thread_pool(config)?;
+        let scheduler = adaptive_scheduler(config, pool.as_ref());
+        let group_policy = self.group_policy;
+        let block_decoder = self.block_decoder.clone();
+        
+        let mut stats = ProcessingStats::default();
+        let retries_before = self.indexed_reader.retries_performed();
+        self.live_stats.begin();
+        
+        let blob_count = self.indexed_reader.blob_count();
+        let mut chunk_start = 0;
+        while chunk_start < blob_count {
+            let chunk = self.next_chunk(chunk_start..blob_count, config, scheduler.as_ref());
+            chunk_start = chunk.end;
+            
+            let blobs = self.read_blobs(chunk, &mut stats);
+            let blobs = self.schedule(blobs, scheduler.as_ref());
+            
+            let (tx, rx) = mpsc::channel();
+            let mut merger = SequenceMerger::new();
+            
+            let flow = std::thread::scope(|scope| -> Result<ControlFlow<()>> {
+                scope.spawn(|| {
+                    let scheduler = scheduler.as_ref();
+                    let block_decoder = block_decoder.as_ref();
+                    let decode = move || {
+                        blobs.into_par_iter().with_max_len(max_task_len(scheduler)).for_each_with(tx, |tx, scheduled| {
+                            let size = scheduled.blob.raw_size() as u64;
+                            let started = Instant::now();
+                            let decoded = decode_elements(&scheduled.blob, group_policy, block_decoder);
+                            if let (Some(scheduler), Ok((_, elements))) = (scheduler, &decoded) {
+                                scheduler.record(BlobKind::of_elements(elements), scheduled.hint.size, started.elapsed());
+                            }
+                            // The receiver only hangs up when processing already stopped
+                            let _ = tx.send((scheduled.seq, scheduled.blob_index, size, decoded));
+                        });
+                    };
+                    match &pool {
+                        Some(pool) => pool.install(decode),
+                        None => decode(),
+                    }
+                });
+                
+                for (seq, blob_index, size, decoded) in rx {
+                    if config.preserve_order {
+                        merger.push(seq, (blob_index, size, decoded));
+                    } else if sink(&mut stats, blob_index, size, decoded)?.is_break() {
+                        return Ok(ControlFlow::Break(()));
+                    }
+                    
+                    while let Some((blob_index, size, decoded)) = merger.pop_ready() {
+                        if sink(&mut stats, blob_index, size, decoded)?.is_break() {
+                            return Ok(ControlFlow::Break(()));
+                        }
+                    }
+                }
+                
+                debug_assert_eq!(merger.buffered(), 0);
+                Ok(ControlFlow::Continue(()))
+            })?;
+            
+            if flow.is_break() {
+                break;
+            }
+        }
+        
+        stats.retries_performed = self.indexed_reader.retries_performed() - retries_before;
+        Ok(stats)
+    }
+
+    /// Blobs of the next parallel chunk within `remaining`
+    fn next_chunk(&self, remaining: std::ops::Range<usize>, config: &ParallelConfig, scheduler: Option<&AdaptiveScheduler>) -> std::ops::Range<usize> {
+        match scheduler {
+            Some(scheduler) => scheduler.next_chunk(remaining, |blob_index| self.cost_hint(blob_index)),
+            None => remaining.start..(remaining.start + config.chunk_size.max(1)).min(remaining.end),
+        }
+    }
+
+    /// Size and kind of a blob as far as the index knows
+    fn cost_hint(&self, blob_index: usize) -> CostHint {
+        self.indexed_reader.get_blob_index(blob_index)
+            .map(|index| CostHint { size: index.size, kind: BlobKind::from_counts(&index.element_counts) })
+            .unwrap_or_default()
+    }
+
+    /// Number read blobs in file order and, when adaptive, put the costliest first
+    fn schedule(&self, blobs: Vec<(usize, Blob)>, scheduler: Option<&AdaptiveScheduler>) -> Vec<ScheduledBlob> {
+        let mut blobs: Vec<_> = blobs.into_iter().enumerate()
+            .map(|(seq, (blob_index, blob))| ScheduledBlob { seq, blob_index, hint: self.cost_hint(blob_index), blob })
+            .collect();
+        if let Some(scheduler) = scheduler {
+            scheduler.order(&mut blobs);
+        }
+        blobs
+    }
+
+    /// Count, log and record a blob skipped because of `error`, and pass it
+    /// to the `on_error` callback
+    fn skip_blob(&mut self, stats: &mut ProcessingStats, blob_index: usize, error: &BlobError) {
+        stats.errors_encountered += 1;
+        stats.blobs_skipped += 1;
+        self.live_stats.record_error();
+        let offset = self.indexed_reader.get_blob_index(blob_index).map(|b| b.offset);
+        log_skipped(self.indexed_reader.skip_log_level(), offset, Some(blob_index), error);
+        let corrupt = CorruptBlob { offset: offset.unwrap_or_default(), blob_index: Some(blob_index), kind: error.kind(), error: error.to_string() };
+        if let Some(on_error) = &mut self.on_error {
+            on_error(&corrupt);
+        }
+        stats.corrupt_blobs.push(corrupt);
+    }
+
+    /// `decoded`, or `None` after skipping the blob if it failed under
+    /// `CorruptBlobPolicy::Skip`
+    fn decoded_or_skip<T>(&mut self, stats: &mut ProcessingStats, blob_index: usize, decoded: Result<T>) -> Result<Option<T>> {
+        match decoded {
+            Ok(decoded) => Ok(Some(decoded)),
+            Err(e) if self.corrupt_blob_policy == CorruptBlobPolicy::Skip => {
+                self.skip_blob(stats, blob_index, &e);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Record the damaged regions indexing skipped in `stats`, and pass them
+    /// to the `on_error` callback
+    fn record_damaged_regions(&mut self, stats: &mut ProcessingStats) {
+        let damaged = self.indexed_reader.damaged_regions();
+        if let Some(on_error) = &mut self.on_error {
+            for corrupt in damaged {
+                on_error(corrupt);
+            }
+        }
+        stats.corrupt_blobs.extend_from_slice(damaged);
+    }
+
+    /// Read a range of blobs for parallel decoding, skipping and logging
+    /// unreadable ones
+    ///
+    /// IO stays sequential; only decoding is parallel.
+    fn read_blobs(&mut self, blob_indices: impl ExactSizeIterator<Item = usize>, stats: &mut ProcessingStats) -> Vec<(usize, Blob)> {
+        let mut blobs = Vec::with_capacity(blob_indices.len());
+        for blob_index in blob_indices {
+            match self.indexed_reader.read_blob_by_index(blob_index) {
+                Ok(Some(blob)) => blobs.push((blob_index, blob)),
+                Ok(None) => continue,
+                Err(e) => self.skip_blob(stats, blob_index, &e),
+            }
+        }
+        blobs
+    }
+
+    /// Compute a stable fingerprint of the file's logical content
+    ///
+    /// The fingerprint covers element type, id, version, tags, coordinates and
+    /// references, and is independent of compression, blob packing, string table
+    /// layout and element order. Use it to compare a mirror or a rewritten file
+    /// against the original.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    /// 
+    /// let mut original = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let mut mirror = Reader::new(File::open("mirror/map.osm.pbf")?)?;
+    /// assert_eq!(original.fingerprint()?, mirror.fingerprint()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn fingerprint(&mut self) -> Result<Fingerprint> {
+        self.indexed_reader.finish_index()?;
+        let mut builder = FingerprintBuilder::new();
+        
+        for blob_index in 0..self.indexed_reader.blob_count() {
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index)? {
+                Some(blob) => blob,
+                None => continue,
+            };
+            
+            let (strings, elements) = self.extract_elements_with_strings(&blob)?;
+            for element in &elements {
+                builder.add(element, &strings);
+            }
+        }
+        
+        Ok(builder.finish())
+    }
+
+    /// Summarize the whole file in one pass: element counts, tag key
+    /// frequencies, node extent, id and timestamp ranges, users, compression
+    /// and block sizes
+    ///
+    /// Every data blob is decoded. Timestamps and users come from element
+    /// metadata, so they're missing with `set_skip_metadata`.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let summary = reader.summarize()?;
+    /// println!("{} nodes, {} ways, {} relations", summary.nodes, summary.ways, summary.relations);
+    /// for (key, count) in summary.top_keys(10) {
+    ///     println!("{count:>10} {key}");
+    /// }
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn summarize(&mut self) -> Result<FileSummary> {
+        self.indexed_reader.finish_index()?;
+        let mut builder = SummaryBuilder::default();
+
+        for blob_index in 0..self.indexed_reader.blob_count() {
+            let blob = match self.indexed_reader.read_blob_by_index(blob_index)? {
+                Some(blob) if blob.blob_type().holds_elements() => blob,
+                _ => continue,
+            };
+            let block = self.block_decoder.decode_block(&blob_payload(&blob)?)?;
+            builder.add(&blob, &block);
+        }
+
+        Ok(builder.finish())
+    }
+
+    /// Merge the string tables of all data blobs into one dictionary
+    ///
+    /// Strings are ordered by how often elements use them, and every blob
+    /// gets a table from its local string indices to the global ones; see
+    /// `StringDictionary`. Every data blob is decoded.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let dictionary = reader.export_string_dictionary()?;
+    /// for (string, count) in dictionary.strings.iter().zip(&dictionary.counts).skip(1).take(10) {
+    ///     println!("{count:>10} {string}");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn export_string_dictionary(&mut self) -> Result<StringDictionary> {
+        self.indexed_reader.finish_index()?;
+        let mut builder = StringDictionaryBuilder::new();
+        for blob_index in 0..self.indexed_reader.blob_count() {
+            if !self.indexed_reader.get_blob_index(blob_index).is_some_and(|blob| blob.blob_type.holds_elements()) {
+                continue;
+            }
+            let Some(blob) = self.indexed_reader.read_blob_by_index(blob_index)? else {
+                continue;
+            };
+            let decoded = decode_matching_elements(&blob, &DecodePredicate::default(), self.group_policy, self.block_decoder.as_ref())?;
+            builder.add_block(blob_index, &decoded.strings, &decoded.elements);
+        }
+        Ok(builder.finish())
+    }
 
     /// Get file statistics
     pub fn statistics(&self) -> crate::io::indexed_reader::IndexStatistics {
         self.indexed_reader.statistics()
     }
 
-    /// Extract elements from a blob (placeholder implementation)
-    fn extract_elements_from_blob(&self, _blob: &Blob) -> Result<Vec<OsmElement>> {
-        // In a full implementation, this would:
-        // 1. Decompress the blob if needed
-        // 2. Parse the protobuf PrimitiveBlock
-        // 3. Extract nodes, ways, relations from PrimitiveGroups
-        // 4. Handle DenseNodes efficiently
-        // 5. Resolve string table references
-        
-        // For now, return empty vec as placeholder
-        Ok(Vec::new())
+    /// Extract elements from a blob
+    fn extract_elements_from_blob(&self, blob: &Blob) -> Result<Vec<OsmElement>> {
+        let (_strings, elements) = self.extract_elements_with_strings(blob)?;
+        Ok(elements)
+    }
+
+    /// Extract elements from a blob together with the string table their
+    /// tag and role indices refer to
+    fn extract_elements_with_strings(&self, blob: &Blob) -> Result<(StringTable, Vec<OsmElement>)> {
+        decode_elements(blob, self.group_policy, self.block_decoder.as_ref())
+    }
+
+    /// Collect the nodes inside the box and the ways referencing them from
+    /// `blob_indices`, for files where ways may precede their nodes
+    fn scan_bbox_members(&mut self, filter: &ElementFilter, blob_indices: &[usize], stats: &mut ProcessingStats) -> Result<BboxMembers> {
+        let predicate = DecodePredicate {
+            include_nodes: false,
+            include_relations: false,
+            include_changesets: false,
+            bbox: filter.bbox,
+            polygon: filter.polygon.clone(),
+            record_bbox_nodes: true,
+            ..DecodePredicate::default()
+        };
+        let mut members = BboxMembers::default();
+        let mut ways = Vec::new();
+        for &blob_index in blob_indices {
+            let Some(blob) = self.indexed_reader.read_blob_by_index(blob_index)? else {
+                continue;
+            };
+            stats.blobs_processed += 1;
+            let decoded = decode_matching_elements(&blob, &predicate, self.group_policy, self.block_decoder.as_ref())?;
+            members.nodes.extend(decoded.nodes_in_bbox);
+            ways.extend(decoded.elements);
+        }
+        for way in &ways {
+            members.admit(way);
+        }
+        Ok(members)
+    }
+
+    /// Extract filtered elements from a blob
+    ///
+    /// Element kinds, id ranges, the bounding box and tag presence are checked
+    /// inside the decoder, so excluded nodes are never materialized; tag keys
+    /// and values are checked on the decoded elements. Both kinds of rejection
+    /// are counted in `stats`.
+    fn extract_filtered_elements_from_blob(&self, blob: &Blob, filter: &ElementFilter, stats: &mut ProcessingStats) -> Result<MatchingElements> {
+        let decoded = decode_matching_elements(blob, &DecodePredicate::from(filter), self.group_policy, self.block_decoder.as_ref())?;
+        if decoded.mixed_groups > 0 {
+            log_mixed_groups(self.indexed_reader.skip_log_level(), blob.offset, decoded.mixed_groups);
+            stats.mixed_groups += decoded.mixed_groups;
+        }
+        stats.elements_skipped_early += decoded.skipped;
+        stats.elements_skipped_late += decoded.skipped_by_tags;
+        Ok(decoded)
+    }
+}
+
+/// Convenience functions for common use cases
+impl<R: Read + Seek> Reader<R> {
+    /// Count elements of each type
+    pub fn count_elements(&mut self) -> Result<(u64, u64, u64, u64)> {
+        let mut nodes = 0u64;
+        let mut ways = 0u64;
+        let mut relations = 0u64;
+        let mut changesets = 0u64;
+
+        self.for_each(|element| {
+            match element {
+                OsmElement::Node(_) => nodes += 1,
+                OsmElement::Way(_) => ways += 1,
+                OsmElement::Relation(_) => relations += 1,
+                OsmElement::ChangeSet(_) => changesets += 1,
+            }
+            Ok(())
+        })?;
+
+        Ok((nodes, ways, relations, changesets))
+    }
+
+    /// Extract all nodes (streaming, memory efficient)
+    pub fn nodes<F>(&mut self, processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(Node) -> Result<()>,
+    {
+        let filter = ElementFilter::nodes_only();
+        let mut node_processor = processor;
+        
+        self.for_each_filtered(&filter, |element| {
+            if let OsmElement::Node(node) = element {
+                node_processor(node)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Extract all ways with optional dependency resolution
+    pub fn ways<F>(&mut self, resolve_dependencies: bool, processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(Way) -> Result<()>,
+    {
+        let filter = ElementFilter::ways_only(resolve_dependencies);
+        let mut way_processor = processor;
+        
+        self.for_each_filtered(&filter, |element| {
+            if let OsmElement::Way(way) = element {
+                way_processor(way)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Extract all ways along with the locations of their nodes
+    ///
+    /// Files declaring `LocationsOnWays` carry the locations on each way and
+    /// are read in one pass. Otherwise a first pass reads every node into a
+    /// `DenseLocations` cache; use `ways_with_geometry_using` to bring your own
+    /// `NodeLocationStore`, e.g. `MappedLocations` for the planet. Ways with a node missing
+    /// from the file are skipped and counted in `GeometryStats::ways_incomplete`.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::Reader;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let stats = reader.ways_with_geometry(|way, coords| {
+    ///     println!("way {} starts at {:?}", way.id, coords.first().map(|c| c.to_degrees()));
+    ///     Ok(())
+    /// })?;
+    /// println!("{} ways resolved, {} incomplete", stats.ways_resolved, stats.ways_incomplete);
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn ways_with_geometry<F>(&mut self, processor: F) -> Result<GeometryStats>
+    where
+        F: FnMut(Way, &[LatLon]) -> Result<()>,
+    {
+        if self.has_locations_on_ways() {
+            return self.resolve_way_geometries(&ElementFilter::ways_only(false), None::<&NodeLocationCache>, processor);
+        }
+        let cache = NodeLocationCache::build_dense(self)?;
+        self.ways_with_geometry_using(&cache, processor)
+    }
+
+    /// Extract all ways along with node locations looked up in `store`
+    ///
+    /// Locations stored on the ways themselves take precedence over the store.
+    pub fn ways_with_geometry_using<S, F>(&mut self, store: &S, processor: F) -> Result<GeometryStats>
+    where
+        S: NodeLocationStore + ?Sized,
+        F: FnMut(Way, &[LatLon]) -> Result<()>,
+    {
+        self.resolve_way_geometries(&ElementFilter::ways_only(false), Some(store), processor)
+    }
+
+    /// Extract the ways matching `filter` with complete geometry, reading
+    /// only the node locations they need
+    ///
+    /// Pass 1 collects the node ids of the matching ways, pass 2 stores the
+    /// locations of just those nodes in a `SparseLocations`, and pass 3 hands
+    /// each matching way to `processor` with the locations of its nodes, in
+    /// order. Memory follows the selected ways rather than the file, so this
+    /// suits a few ways out of a large file; for most of them,
+    /// `ways_with_geometry` is cheaper. Only the way criteria of `filter`
+    /// apply and nothing else is emitted; a `bbox` selects the ways with a
+    /// node inside, whose geometry then extends past it. Files with
+    /// `LocationsOnWays` take a single pass. Ways with a node missing from
+    /// the file are skipped and counted in `GeometryStats::ways_incomplete`.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, Reader};
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("planet.osm.pbf")?)?;
+    /// let filter = ElementFilter::ways_only(false).with_tag("railway".to_string(), "rail".to_string());
+    /// let stats = reader.ways_complete(&filter, |way, geometry| {
+    ///     println!("way {} has {} points", way.id, geometry.len());
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn ways_complete<F>(&mut self, filter: &ElementFilter, processor: F) -> Result<GeometryStats>
+    where
+        F: FnMut(Way, &[LatLon]) -> Result<()>,
+    {
+        let ways = ElementFilter {
+            include_nodes: false,
+            include_ways: true,
+            include_relations: false,
+            include_changesets: false,
+            resolve_dependencies: false,
+            ..filter.clone()
+        };
+        if self.has_locations_on_ways() {
+            return self.resolve_way_geometries(&ways, None::<&NodeLocationCache>, processor);
+        }
+
+        let mut needed = HashSet::new();
+        self.for_each_filtered(&ways, |element| {
+            if let OsmElement::Way(way) = element {
+                needed.extend(way.node_ids());
+            }
+            Ok(())
+        })?;
+        let mut locations = SparseLocations::new();
+        if !needed.is_empty() {
+            self.for_each_filtered(&ElementFilter::nodes_only(), |element| match element {
+                OsmElement::Node(node) if needed.contains(&node.id.0) => locations.set(node.id.0, node.location),
+                _ => Ok(()),
+            })?;
+        }
+        self.resolve_way_geometries(&ways, Some(&locations), processor)
+    }
+
+    fn resolve_way_geometries<S, F>(&mut self, filter: &ElementFilter, store: Option<&S>, mut processor: F) -> Result<GeometryStats>
+    where
+        S: NodeLocationStore + ?Sized,
+        F: FnMut(Way, &[LatLon]) -> Result<()>,
+    {
+        let mut stats = GeometryStats::default();
+        self.for_each_filtered(filter, |element| {
+            let OsmElement::Way(way) = element else {
+                return Ok(());
+            };
+            let coords = match way.locations() {
+                Some(coords) => {
+                    stats.from_locations_on_ways += 1;
+                    coords
+                }
+                None => match store.and_then(|store| store.way_locations(&way)) {
+                    Some(coords) => coords,
+                    None => {
+                        stats.ways_incomplete += 1;
+                        return Ok(());
+                    }
+                },
+            };
+            stats.ways_resolved += 1;
+            processor(way, &coords)
+        })?;
+        Ok(stats)
+    }
+}
+
+impl<R: Read + Seek + Send + 'static> Reader<R> {
+    /// Decode on background threads and deliver one batch per blob over a
+    /// bounded channel
+    ///
+    /// The reader moves to a background thread that decodes like `par_for_each`
+    /// and blocks once `config.channel_capacity` batches are waiting, so a slow
+    /// consumer (an async task, an actor, another pipeline stage) naturally
+    /// throttles decoding. Dropping the receiver stops the stream early.
+    ///
+    /// The handle yields the processing statistics, or the first decode error.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{Reader, StreamConfig};
+    /// use std::fs::File;
+    /// 
+    /// let reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let (handle, batches) = reader.spawn_stream(StreamConfig::default());
+    /// 
+    /// for batch in batches {
+    ///     println!("blob {}: {} elements", batch.blob_index, batch.elements.len());
+    /// }
+    /// let stats = handle.join().expect("stream thread panicked")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn spawn_stream(mut self, config: StreamConfig) -> (JoinHandle<Result<ProcessingStats>>, Receiver<ElementBatch>) {
+        let (tx, rx) = crossbeam_channel::bounded(config.channel_capacity.max(1));
+        
+        let handle = std::thread::spawn(move || {
+            let live_stats = self.live_stats.clone();
+            
+            self.par_decode(&config.parallel, |stats, blob_index, size, decoded| {
+                let (strings, elements) = decoded?;
+                record_blob(stats, &live_stats, size);
+                for element in &elements {
+                    record_element(stats, &live_stats, element);
+                }
+                
+                match tx.send(ElementBatch { blob_index, strings, elements }) {
+                    Ok(()) => Ok(ControlFlow::Continue(())),
+                    // The consumer hung up; nobody wants the rest
+                    Err(_) => Ok(ControlFlow::Break(())),
+                }
+            })
+        });
+        
+        (handle, rx)
+    }
+}
+
+impl<R: Read + Seek + Send> Reader<R> {
+    /// Parallel iterator over the elements matching `filter`, for rayon
+    /// pipelines
+    ///
+    /// Blobs are read sequentially and decoded on the rayon pool, one blob
+    /// per task, so elements come in no particular order. Blob errors are
+    /// handled as by `iter_filtered`, except that decode errors are yielded
+    /// without stopping the other blobs. Fails up front if the file can't be
+    /// indexed. Filters keeping ways and relations by a bounding box (see
+    /// `ElementFilter::with_bbox`) are matched by `iter_filtered` on the
+    /// calling thread, and only the work after `par_iter` runs in parallel.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{ElementFilter, OsmElement, Reader};
+    /// use rayon::prelude::*;
+    /// use std::fs::File;
+    ///
+    /// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+    /// let tagged = reader.par_iter(&ElementFilter::nodes_only())?
+    ///     .filter(|element| matches!(element, Ok(OsmElement::Node(node)) if !node.keys.is_empty()))
+    ///     .count();
+    /// println!("{tagged} tagged nodes");
+    /// # Ok::<(), osm_pbf::BlobError>(())
+    /// ```
+    pub fn par_iter(&mut self, filter: &ElementFilter) -> Result<impl ParallelIterator<Item = Result<OsmElement>> + '_> {
+        if filter.bbox_dependencies() {
+            // Membership of ways and relations depends on the elements before them
+            self.indexed_reader.finish_index()?;
+            return Ok(Either::Left(self.iter_filtered(filter).par_bridge()));
+        }
+        let mut stats = ProcessingStats::default();
+        let (blob_indices, _) = self.start_filtered(filter, &mut stats)?;
+        let predicate = DecodePredicate::from(filter);
+        let (group_policy, block_decoder) = (self.group_policy, self.block_decoder.clone());
+        let skip_log_level = self.indexed_reader.skip_log_level();
+        let live_stats = self.live_stats.clone();
+        
+        let indexed_reader = &mut self.indexed_reader;
+        let read_stats = live_stats.clone();
+        let blobs = blob_indices.into_iter().filter_map(move |blob_index| match indexed_reader.read_blob_by_index(blob_index) {
+            Ok(blob) => blob,
+            Err(e) => {
+                read_stats.record_error();
+                let offset = indexed_reader.get_blob_index(blob_index).map(|b| b.offset);
+                log_skipped(skip_log_level, offset, Some(blob_index), &e);
+                None
+            }
+        });
+        
+        Ok(Either::Right(blobs.par_bridge().flat_map_iter(move |blob| {
+            live_stats.record_blob(blob.raw_size() as u64);
+            let decoded = match decode_matching_elements(&blob, &predicate, group_policy, block_decoder.as_ref()) {
+                Ok(decoded) => decoded,
+                Err(e) => return vec![Err(e)],
+            };
+            if decoded.mixed_groups > 0 {
+                log_mixed_groups(skip_log_level, blob.offset, decoded.mixed_groups);
+            }
+            decoded.elements.into_iter()
+                .inspect(|_| live_stats.record_element())
+                .map(Ok)
+                .collect()
+        })))
+    }
+}
+
+/// Elements of a file in file order, from `Reader::iter` and
+/// `Reader::iter_filtered`
+pub struct ElementIter<'a, R: Read + Seek> {
+    reader: &'a mut Reader<R>,
+    filter: ElementFilter,
+    /// Blobs left to decode and bounding box members, once started
+    started: Option<(std::vec::IntoIter<usize>, Option<BboxMembers>)>,
+    block: std::vec::IntoIter<OsmElement>,
+    stats: ProcessingStats,
+    retries_before: u64,
+    done: bool,
+}
+
+impl<R: Read + Seek> ElementIter<'_, R> {
+    /// Counts of the elements and blobs processed so far
+    pub fn stats(&self) -> &ProcessingStats {
+        &self.stats
+    }
+}
+
+impl<R: Read + Seek> Iterator for ElementIter<'_, R> {
+    type Item = Result<OsmElement>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let (blob_indices, members) = match &mut self.started {
+                Some(started) => started,
+                None => match self.reader.start_filtered(&self.filter, &mut self.stats) {
+                    Ok((blob_indices, members)) => self.started.insert((blob_indices.into_iter(), members)),
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+            };
+            
+            for element in self.block.by_ref() {
+                if let Some(members) = members
+                    && !members.admit(&element)
+                {
+                    self.stats.elements_skipped_late += 1;
+                    continue;
+                }
+                record_element(&mut self.stats, &self.reader.live_stats, &element);
+                return Some(Ok(element));
+            }
+            
+            let Some(blob_index) = blob_indices.next() else {
+                self.done = true;
+                self.stats.retries_performed = self.reader.indexed_reader.retries_performed() - self.retries_before;
+                break;
+            };
+            let blob = match self.reader.indexed_reader.read_blob_by_index(blob_index) {
+                Ok(Some(blob)) => blob,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.reader.skip_blob(&mut self.stats, blob_index, &e);
+                    continue;
+                }
+            };
+            let decoded = self.reader.extract_filtered_elements_from_blob(&blob, &self.filter, &mut self.stats);
+            match self.reader.decoded_or_skip(&mut self.stats, blob_index, decoded) {
+                Ok(Some(decoded)) => {
+                    record_blob(&mut self.stats, &self.reader.live_stats, blob.raw_size() as u64);
+                    if let Some(members) = members {
+                        members.nodes.extend(decoded.nodes_in_bbox);
+                    }
+                    self.block = decoded.elements.into_iter();
+                }
+                Ok(None) => continue,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Nodes inside a filter's box, and the ways kept because they reference one
+#[derive(Debug, Default)]
+struct BboxMembers {
+    nodes: HashSet<i64>,
+    ways: HashSet<i64>,
+}
+
+impl BboxMembers {
+    /// Whether a way or relation references the box; other elements always pass
+    fn admit(&mut self, element: &OsmElement) -> bool {
+        match element {
+            OsmElement::Way(way) => {
+                let inside = way.node_ids().any(|node_id| self.nodes.contains(&node_id));
+                if inside {
+                    self.ways.insert(way.id.0);
+                }
+                inside
+            }
+            OsmElement::Relation(relation) => {
+                relation.members().any(|member| match member.member_type {
+                    MemberType::Node => self.nodes.contains(&member.id),
+                    MemberType::Way => self.ways.contains(&member.id),
+                    MemberType::Relation => false,
+                })
+            }
+            _ => true,
+        }
     }
+}
 
-    /// Extract filtered elements from a blob
-    fn extract_filtered_elements_from_blob(&self, blob: &Blob, _filter: &ElementFilter) -> Result<Vec<OsmElement>> {
-        // In full implementation, this would apply filters during extraction
-        // for better performance than post-filtering
-        self.extract_elements_from_blob(blob)
+/// Dedicated pool for `config.num_threads`, or `None` to use the global pool
+fn thread_pool(config: &ParallelConfig) -> Result<Option<rayon::ThreadPool>> {
+    config.num_threads
+        .map(|num_threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .map_err(|e| BlobError::InvalidFormat(format!("Failed to configure thread pool: {e}")))
+        })
+        .transpose()
+}
+
+/// Decode cost tracker for `config.adaptive`, sized for the pool's threads
+fn adaptive_scheduler(config: &ParallelConfig, pool: Option<&rayon::ThreadPool>) -> Option<AdaptiveScheduler> {
+    let threads = pool.map_or_else(rayon::current_num_threads, |pool| pool.current_num_threads());
+    config.adaptive.then(|| AdaptiveScheduler::new(threads, config.chunk_size))
+}
+
+/// Longest run of blobs one rayon task takes; one blob per task when adaptive,
+/// so idle threads can steal every remaining blob
+fn max_task_len(scheduler: Option<&AdaptiveScheduler>) -> usize {
+    scheduler.map_or(usize::MAX, |_| 1)
+}
+
+/// Count a processed blob
+fn record_blob(stats: &mut ProcessingStats, live_stats: &LiveStats, size: u64) {
+    stats.blobs_processed += 1;
+    live_stats.record_blob(size);
+}
+
+/// Count a processed element by type
+fn record_element(stats: &mut ProcessingStats, live_stats: &LiveStats, element: &OsmElement) {
+    match element {
+        OsmElement::Node(_) => stats.nodes_processed += 1,
+        OsmElement::Way(_) => stats.ways_processed += 1,
+        OsmElement::Relation(_) => stats.relations_processed += 1,
+        OsmElement::ChangeSet(_) => stats.changesets_processed += 1,
     }
+    
+    stats.elements_processed += 1;
+    live_stats.record_element();
 }
 
-/// Convenience functions for common use cases
-impl<R: Read + Seek> Reader<R> {
-    /// Count elements of each type
-    pub fn count_elements(&mut self) -> Result<(u64, u64, u64, u64)> {
-        let mut nodes = 0u64;
-        let mut ways = 0u64;
-        let mut relations = 0u64;
-        let mut changesets = 0u64;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::io::options::ReaderOptions;
+    use crate::blocks::bbox::BoundingBox;
+    use crate::blocks::osm_id::{NodeId, WayId};
+    use std::io::Cursor;
+    use std::sync::Mutex;
 
-        self.for_each(|element| {
-            match element {
-                OsmElement::Node(_) => nodes += 1,
-                OsmElement::Way(_) => ways += 1,
-                OsmElement::Relation(_) => relations += 1,
-                OsmElement::ChangeSet(_) => changesets += 1,
+    #[test]
+    fn test_process_for_resumes_from_checkpoint() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(4).grid_size(10).block_size(20).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut expected = Vec::new();
+        reader.for_each(|element| {
+            expected.push(element_key(&element));
+            Ok(())
+        }).unwrap();
+        
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path().join("run.checkpoint"));
+        let run = reader.process_for(Duration::ZERO, &checkpoint, |_| Ok(())).unwrap();
+        assert!(!run.finished());
+        assert_eq!(run.progress(), 0.0);
+        assert_eq!(checkpoint.load().unwrap(), Some(PageCursor::new(0, 0)));
+        
+        // Fail on node 45, the fifth element of the third node blob
+        let mut seen = Vec::new();
+        let err = reader.process_for(Duration::from_secs(60), &checkpoint, |element| {
+            if element_key(&element) == (0, 45) {
+                return Err(BlobError::InvalidFormat("import failed".to_string()));
             }
+            seen.push(element_key(&element));
             Ok(())
-        })?;
-
-        Ok((nodes, ways, relations, changesets))
+        }).unwrap_err();
+        assert!(matches!(err, BlobError::InvalidFormat(_)));
+        assert_eq!(seen, expected[..44]);
+        assert_eq!(checkpoint.load().unwrap(), Some(PageCursor::new(3, 4)));
+        
+        // The failed element is processed again, and nothing before it
+        let run = reader.process_for(Duration::from_secs(60), &checkpoint, |element| {
+            seen.push(element_key(&element));
+            Ok(())
+        }).unwrap();
+        assert!(run.finished());
+        assert_eq!(run.stats.elements_processed as usize, expected.len() - 44);
+        assert_eq!(seen, expected);
+        
+        // A finished checkpoint makes later runs no-ops
+        let run = reader.process_for(Duration::from_secs(60), &checkpoint, |_| panic!("no elements left")).unwrap();
+        assert_eq!(run.stats.blobs_processed, 0);
+        assert_eq!(run.progress(), 1.0);
     }
-
-    /// Extract all nodes (streaming, memory efficient)
-    pub fn nodes<F>(&mut self, processor: F) -> Result<ProcessingStats>
-    where
-        F: FnMut(Node) -> Result<()>,
-    {
-        let filter = ElementFilter::nodes_only();
-        let mut node_processor = processor;
+    
+    #[test]
+    fn test_filter_pushdown_stats() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(5).grid_size(8).block_size(16).relation_count(3).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
         
-        self.for_each_filtered(&filter, |element| {
+        let filter = ElementFilter::all().with_tag_key("highway".to_string());
+        let stats = reader.for_each_filtered(&filter, |_| Ok(())).unwrap();
+        assert_eq!(stats.ways_processed, planet.ways);
+        assert_eq!(stats.elements_processed, planet.ways);
+        assert_eq!(stats.blobs_pruned, 0);
+        
+        // Node and relation blocks have no "highway" string, so none of their
+        // elements get materialized
+        assert_eq!(stats.elements_skipped_early, planet.nodes + planet.relations);
+        assert_eq!(stats.elements_skipped_late, 0);
+        assert_eq!(
+            stats.elements_processed + stats.elements_skipped_early + stats.elements_skipped_late,
+            planet.nodes + planet.ways + planet.relations
+        );
+    }
+    
+    #[test]
+    fn test_count_filtered_matches_for_each_filtered() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(9).grid_size(12).relation_count(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        
+        let filters = [
+            ElementFilter::all(),
+            ElementFilter::nodes_only().with_tag_key("amenity".to_string()),
+            ElementFilter::ways_only(false).with_tag_key("highway".to_string()).with_tag_key("name".to_string()),
+            ElementFilter::all().with_tag("type".to_string(), "route".to_string()),
+            ElementFilter::all().with_tag_key("no-such-key".to_string()),
+            ElementFilter::nodes_only().with_id_range(NodeId(10), NodeId(40)),
+            ElementFilter::all().with_id_range(WayId(3), WayId(7)),
+        ];
+        for filter in &filters {
+            let expected = reader.for_each_filtered(filter, |_| Ok(())).unwrap();
+            let counted = reader.count_filtered(filter).unwrap();
+            assert_eq!(
+                (counted.nodes_processed, counted.ways_processed, counted.relations_processed, counted.elements_processed),
+                (expected.nodes_processed, expected.ways_processed, expected.relations_processed, expected.elements_processed),
+                "{filter:?}"
+            );
+            assert_eq!(
+                (counted.elements_skipped_early, counted.elements_skipped_late),
+                (expected.elements_skipped_early, expected.elements_skipped_late),
+                "{filter:?}"
+            );
+        }
+        let amenities = reader.count_filtered(&filters[1]).unwrap();
+        assert!(amenities.nodes_processed > 0 && amenities.elements_skipped_late == 0);
+        // A way id range leaves nodes and relations alone
+        let ways = reader.count_filtered(&filters[6]).unwrap();
+        assert_eq!((ways.nodes_processed, ways.ways_processed, ways.relations_processed), (planet.nodes, 5, planet.relations));
+    }
+    
+    #[test]
+    fn test_par_map_reduce_matches_sequential_counts() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(12).block_size(50).relation_count(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let expected = reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap();
+        assert!(expected.blobs_processed > 2);
+        
+        for (num_threads, chunk_size, adaptive) in [(None, 100, false), (Some(1), 1, false), (Some(3), 2, false), (Some(3), 100, true)] {
+            let config = ParallelConfig { num_threads, chunk_size, preserve_order: false, adaptive };
+            let counts = reader.par_map_reduce(
+                &config,
+                |element| match element {
+                    OsmElement::Node(_) => (1, 0, 0),
+                    OsmElement::Way(_) => (0, 1, 0),
+                    _ => (0, 0, 1),
+                },
+                || (0u64, 0u64, 0u64),
+                |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2),
+                (0, 0, 0),
+            ).unwrap();
+            assert_eq!(counts, (expected.nodes_processed, expected.ways_processed, expected.relations_processed));
+        }
+    }
+    
+    #[test]
+    fn test_provenance_locates_elements() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(6).grid_size(8).block_size(40).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        
+        let filter = ElementFilter::ways_only(false).with_tag_key("highway".to_string());
+        let mut traced = Vec::new();
+        reader.for_each_filtered_with_provenance(&filter, |element, provenance| {
+            traced.push((provenance, format!("{element:?}")));
+            Ok(())
+        }).unwrap();
+        assert!(!traced.is_empty());
+        
+        // Re-decoding the blob at the recorded offset yields the same element
+        for (provenance, element) in traced {
+            assert_eq!(reader.indexed_reader.get_blob_index(provenance.blob_index).unwrap().offset, provenance.byte_offset);
+            let blob = reader.indexed_reader.read_blob_at_offset(provenance.byte_offset).unwrap().unwrap();
+            let all = decode_matching_elements(&blob, &DecodePredicate::default(), GroupPolicy::Lenient, &PbfBlockCodec).unwrap();
+            assert_eq!(format!("{:?}", all.elements[provenance.element_ordinal]), element);
+        }
+    }
+    
+    #[test]
+    fn test_bbox_keeps_ways_by_node_membership() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(11).grid_size(10).block_size(25).relation_count(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        
+        let mut nodes = Vec::new();
+        reader.for_each_filtered(&ElementFilter::nodes_only(), |element| {
             if let OsmElement::Node(node) = element {
-                node_processor(node)
-            } else {
-                Ok(())
+                nodes.push(node);
             }
-        })
-    }
-
-    /// Extract all ways with optional dependency resolution
-    pub fn ways<F>(&mut self, resolve_dependencies: bool, processor: F) -> Result<ProcessingStats>
-    where
-        F: FnMut(Way) -> Result<()>,
-    {
-        let filter = ElementFilter::ways_only(resolve_dependencies);
-        let mut way_processor = processor;
+            Ok(())
+        }).unwrap();
+        let mut bbox = BoundingBox::from_point(nodes[0].location);
+        for node in &nodes[..30] {
+            bbox.extend(node.location);
+        }
+        let inside: HashSet<i64> = nodes.iter().filter(|n| bbox.contains(n.location)).map(|n| n.id.0).collect();
+        assert!(!inside.is_empty() && inside.len() < nodes.len());
         
-        self.for_each_filtered(&filter, |element| {
+        let mut expected_ways = Vec::new();
+        reader.for_each_filtered(&ElementFilter::ways_only(false), |element| {
             if let OsmElement::Way(way) = element {
-                way_processor(way)
-            } else {
-                Ok(())
+                let refs = crate::io::delta::delta_decode(&way.refs).unwrap();
+                if refs.iter().any(|id| inside.contains(id)) {
+                    expected_ways.push(way.id);
+                }
             }
-        })
+            Ok(())
+        }).unwrap();
+        assert!(!expected_ways.is_empty());
+        
+        // Without dependency resolution the box only applies to nodes
+        let stats = reader.count_filtered(&ElementFilter::all().with_bbox(bbox)).unwrap();
+        assert_eq!(stats.nodes_processed, inside.len() as u64);
+        assert!(stats.ways_processed > expected_ways.len() as u64);
+        
+        let mut filter = ElementFilter::all().with_bbox(bbox);
+        filter.resolve_dependencies = true;
+        let (mut kept_nodes, mut kept_ways, mut kept_relations) = (0, Vec::new(), 0);
+        let stats = reader.for_each_filtered(&filter, |element| {
+            match element {
+                OsmElement::Node(node) => kept_nodes += usize::from(inside.contains(&node.id.0)),
+                OsmElement::Way(way) => kept_ways.push(way.id),
+                _ => kept_relations += 1,
+            }
+            Ok(())
+        }).unwrap();
+        assert_eq!((kept_nodes, kept_ways), (inside.len(), expected_ways));
+        assert_eq!(stats.nodes_processed, inside.len() as u64);
+        assert!(kept_relations <= 4);
+        assert_eq!(reader.count_filtered(&filter).unwrap().elements_processed, stats.elements_processed);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
 
+    #[test]
+    fn test_geographic_ordering_scans_members_first() {
+        use crate::blocks::header_block::HeaderBlock;
+        use crate::io::writer::{BlockBuffer, PbfWriter};
+        
+        let strings = StringTable::new();
+        let way = |id, refs: Vec<i64>| OsmElement::Way(Way { id: WayId(id), keys: vec![], vals: vec![], info: None, refs, lat: vec![], lon: vec![] });
+        let node = |id, lat| OsmElement::Node(Node::new(id, LatLon::from_raw(lat, 0)));
+        // A tile's ways come before the nodes of the next tile they reach into
+        let blocks = [vec![node(1, 50_000_000_000), way(10, vec![1, 1]), way(11, vec![3])], vec![node(2, 10_000_000_000), node(3, 60_000_000_000)]];
+        let write = |optional_features: Vec<&'static str>| {
+            let mut writer = PbfWriter::new(Vec::new());
+            writer.write_header(&HeaderBlock {
+                optional_features: optional_features.into_iter().map(Into::into).collect(),
+                ..Default::default()
+            }).unwrap();
+            for elements in &blocks {
+                let mut block = BlockBuffer::default();
+                for element in elements {
+                    block.push(element, &strings);
+                }
+                writer.write_primitive_block(&block.finish()).unwrap();
+            }
+            writer.into_inner()
+        };
+        let bbox = BoundingBox::from_point(LatLon::from_raw(10_000_000_000, 0));
+        let mut filter = ElementFilter::ways_only(false).with_bbox(bbox);
+        filter.resolve_dependencies = true;
+        let kept_ways = |data| {
+            let mut reader = Reader::new(Cursor::new(data)).unwrap();
+            let mut ways = Vec::new();
+            reader.for_each_filtered(&filter, |element| {
+                if let OsmElement::Way(way) = element {
+                    ways.push(way.id);
+                }
+                Ok(())
+            }).unwrap();
+            (reader.ordering().clone(), reader.explain(&filter).member_blobs, ways)
+        };
+        
+        // Way 10 reaches node 2 only in the next blob
+        let (ordering, member_blobs, ways) = kept_ways(write(vec![]));
+        assert_eq!((ordering, member_blobs, ways), (FileOrdering::default(), vec![], vec![]));
+        
+        let (ordering, member_blobs, ways) = kept_ways(write(vec!["Sort.Geographic"]));
+        assert!(ordering.geographic);
+        assert_eq!((member_blobs, ways), (vec![1, 2], vec![WayId(10)]));
+    }
+    
+    #[test]
+    fn test_mixed_group_policy() {
+        // Fixture: one group holding both a node and a way
+        let mut block = PrimitiveBlock::default();
+        block.primitivegroup.push(PrimitiveGroup {
+            nodes: vec![Node::new(1, LatLon::default())],
+            ways: vec![Way { id: WayId(2), keys: vec![], vals: vec![], info: None, refs: vec![1], lat: vec![], lon: vec![] }],
+            ..Default::default()
+        });
+        let mut writer = crate::io::writer::PbfWriter::new(Vec::new());
+        writer.write_header(&crate::blocks::header_block::HeaderBlock::default()).unwrap();
+        writer.write_primitive_block(&block).unwrap();
+        let data = writer.into_inner();
+        
+        let mut reader = Reader::new(Cursor::new(data.clone())).unwrap();
+        assert_eq!(reader.group_policy(), GroupPolicy::Lenient);
+        let stats = reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap();
+        assert_eq!((stats.nodes_processed, stats.ways_processed, stats.mixed_groups), (1, 1, 1));
+        
+        reader.set_group_policy(GroupPolicy::Strict);
+        let err = reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap_err();
+        assert!(matches!(err, BlobError::MixedGroup { group: 0, .. }));
+    }
+    
     #[test]
     fn test_reader_creation() {
         let empty_data = Vec::new();
@@ -409,6 +2751,242 @@ mod tests {
         assert!(reader.is_ok());
     }
 
+    #[test]
+    fn test_reader_with_skip_log_level() {
+        let cursor = Cursor::new(Vec::new());
+        let mut reader = Reader::with_skip_log_level(cursor, SkipLogLevel::Off).unwrap();
+        let stats = reader.for_each(|_| Ok(())).unwrap();
+        assert_eq!(stats.blobs_skipped, 0);
+    }
+
+    /// A synthetic planet with small blocks, so it spans several blobs
+    fn planet(relations: usize) -> (Vec<u8>, crate::synthetic::PlanetStats) {
+        let mut data = Vec::new();
+        let stats = crate::synthetic::PlanetBuilder::new(3).grid_size(8).block_size(10).relation_count(relations).write_to(&mut data).unwrap();
+        (data, stats)
+    }
+
+    #[test]
+    fn test_for_each_decodes_every_element() {
+        let (data, planet) = planet(4);
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut ids = Vec::new();
+        let stats = reader.for_each(|element| {
+            ids.push(element_key(&element));
+            Ok(())
+        }).unwrap();
+        assert_eq!(stats.elements_processed, planet.nodes + planet.ways + planet.relations);
+        assert_eq!((stats.nodes_processed, stats.ways_processed, stats.relations_processed), (planet.nodes, planet.ways, planet.relations));
+
+        let mut filtered = Vec::new();
+        reader.for_each_filtered(&ElementFilter::all(), |element| {
+            filtered.push(element_key(&element));
+            Ok(())
+        }).unwrap();
+        assert_eq!(ids, filtered);
+    }
+
+    /// Kind and id of an element, for comparing what different paths return
+    fn element_key(element: &OsmElement) -> (u8, i64) {
+        match element {
+            OsmElement::Node(node) => (0, node.id.0),
+            OsmElement::Way(way) => (1, way.id.0),
+            OsmElement::Relation(relation) => (2, relation.id.0),
+            OsmElement::ChangeSet(changeset) => (3, changeset.id.0),
+        }
+    }
+
+    #[test]
+    fn test_live_stats_handle_tracks_runs() {
+        let (data, _) = planet(0);
+        let size = data.len() as u64;
+        let mut indexed = IndexedReader::new(Cursor::new(data.clone())).unwrap();
+        let blobs = indexed.blob_count() as u64;
+        let raw_bytes: u64 = (0..indexed.blob_count()).map(|i| indexed.read_blob_by_index(i).unwrap().unwrap().raw_size() as u64).sum();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let live = reader.live_stats();
+        
+        reader.for_each(|_| Ok(())).unwrap();
+        let snapshot = live.snapshot();
+        assert_eq!(snapshot.blobs_processed, blobs);
+        // Blobs are zlib-compressed, so more bytes are processed than read
+        assert_eq!(snapshot.bytes_processed, raw_bytes);
+        assert!(raw_bytes > size);
+        assert!(snapshot.elapsed > std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_par_for_each_matches_sequential_stats() {
+        let (data, _) = planet(4);
+        let mut sequential = Vec::new();
+        let stats = Reader::new(Cursor::new(data.clone())).unwrap().for_each(|element| {
+            sequential.push(element_key(&element));
+            Ok(())
+        }).unwrap();
+        
+        for (preserve_order, adaptive) in [(true, false), (false, false), (true, true)] {
+            let mut reader = Reader::new(Cursor::new(data.clone())).unwrap();
+            let config = ParallelConfig { num_threads: Some(2), chunk_size: 3, preserve_order, adaptive };
+            let mut parallel = Vec::new();
+            let par_stats = reader.par_for_each(&config, |element| {
+                parallel.push(element_key(&element));
+                Ok(())
+            }).unwrap();
+            
+            assert_eq!((par_stats.blobs_processed, par_stats.elements_processed), (stats.blobs_processed, stats.elements_processed));
+            let mut expected = sequential.clone();
+            if !preserve_order {
+                parallel.sort_unstable();
+                expected.sort_unstable();
+            }
+            assert_eq!(parallel, expected, "preserve_order: {preserve_order}, adaptive: {adaptive}");
+        }
+    }
+
+    #[test]
+    fn test_spawn_stream_delivers_every_blob() {
+        let (data, planet) = planet(4);
+        let blobs = IndexedReader::new(Cursor::new(data.clone())).unwrap().blob_count();
+        let reader = Reader::new(Cursor::new(data)).unwrap();
+        let config = StreamConfig {
+            channel_capacity: 1,
+            parallel: ParallelConfig { num_threads: Some(2), chunk_size: 4, preserve_order: true, adaptive: false },
+        };
+        let (handle, batches) = reader.spawn_stream(config);
+        
+        let (mut indices, mut streamed, mut highways) = (Vec::new(), Vec::new(), 0);
+        for batch in batches {
+            indices.push(batch.blob_index);
+            // Tags resolve against the batch's own string table
+            highways += batch.elements.iter().filter(|element| element.tags(&batch.strings).get("highway").is_some()).count() as u64;
+            streamed.extend(batch.elements.iter().map(element_key));
+        }
+        assert_eq!(indices, (0..blobs).collect::<Vec<_>>());
+        let stats = handle.join().unwrap().unwrap();
+        assert_eq!(stats.blobs_processed as usize, blobs);
+        assert_eq!(streamed.len() as u64, planet.nodes + planet.ways + planet.relations);
+        assert_eq!(highways, planet.ways);
+        assert_eq!(&streamed[..2], [(0, 1), (0, 2)]);
+        assert_eq!(streamed.last(), Some(&(2, planet.relations as i64)));
+    }
+
+    #[test]
+    fn test_spawn_stream_stops_when_receiver_dropped() {
+        let (data, _) = planet(4);
+        let blobs = IndexedReader::new(Cursor::new(data.clone())).unwrap().blob_count() as u64;
+        let reader = Reader::new(Cursor::new(data)).unwrap();
+        let config = StreamConfig { channel_capacity: 1, ..Default::default() };
+        let (handle, batches) = reader.spawn_stream(config);
+        
+        assert_eq!(batches.recv().unwrap().blob_index, 0);
+        let first_nodes = batches.recv().unwrap();
+        assert_eq!(first_nodes.elements.first().map(element_key), Some((0, 1)));
+        drop(batches);
+        assert!(handle.join().unwrap().unwrap().blobs_processed < blobs);
+    }
+
+    #[test]
+    fn test_fingerprint_empty() {
+        let mut reader = Reader::new(Cursor::new(Vec::new())).unwrap();
+        let fingerprint = reader.fingerprint().unwrap();
+        assert_eq!(fingerprint.elements, 0);
+    }
+
+    #[test]
+    fn test_fingerprint_survives_repacking() {
+        use crate::blocks::header_block::HeaderBlock;
+        use crate::io::writer::{BlockBuffer, PbfWriter};
+        
+        let (data, planet) = planet(4);
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let fingerprint = reader.fingerprint().unwrap();
+        assert_eq!(fingerprint.elements, planet.nodes + planet.ways + planet.relations);
+        
+        // Smaller blocks, each with its own string table in another order
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        let mut block = BlockBuffer::default();
+        reader.for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| {
+            block.push(&element, strings);
+            if block.len() == 7 {
+                writer.write_primitive_block(&std::mem::take(&mut block).finish())?;
+            }
+            Ok(())
+        }).unwrap();
+        writer.write_primitive_block(&block.finish()).unwrap();
+        
+        let repacked = Reader::new(Cursor::new(writer.into_inner())).unwrap().fingerprint().unwrap();
+        assert_eq!(repacked, fingerprint);
+    }
+
+    #[test]
+    fn test_max_ids() {
+        let (data, planet) = planet(4);
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        // The grid has ids 1..=n² for nodes, 1..=2n for ways and 1..=count for relations
+        let expected = MaxIds { node: Some(planet.nodes as i64), way: Some(planet.ways as i64), relation: Some(planet.relations as i64) };
+        assert_eq!(reader.max_ids().unwrap(), expected);
+        // With id ranges and counts in the index, single-type blobs aren't decoded
+        reader.build_deep_index().unwrap();
+        assert_eq!(reader.max_ids().unwrap(), expected);
+        
+        let mut reader = Reader::new(Cursor::new(Vec::new())).unwrap();
+        assert_eq!(reader.max_ids().unwrap(), MaxIds::default());
+    }
+
+    #[test]
+    fn test_page_empty_file() {
+        let mut reader = Reader::new(Cursor::new(Vec::new())).unwrap();
+        let page = reader.page(&ElementFilter::all(), None, 10).unwrap();
+        assert!(page.elements.is_empty());
+        assert!(page.next.is_none());
+    }
+
+    #[test]
+    fn test_page_zero_limit() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(3).grid_size(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+
+        let cursor = PageCursor::new(1, 2);
+        assert!(matches!(reader.page(&ElementFilter::all(), None, 0), Err(BlobError::InvalidFormat(_))));
+        assert!(matches!(reader.page(&ElementFilter::all(), Some(&cursor), 0), Err(BlobError::InvalidFormat(_))));
+        let page = reader.page(&ElementFilter::all(), Some(&cursor), 1).unwrap();
+        assert_eq!((page.elements.len(), page.next), (1, Some(PageCursor::new(1, 3))));
+    }
+
+    #[test]
+    fn test_pages_match_iter_filtered() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(17).grid_size(20).block_size(30).relation_count(8).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let (nodes, _) = reader.collect_filtered(&ElementFilter::nodes_only()).unwrap();
+        let mut bbox = None;
+        for element in &nodes[200..260] {
+            if let OsmElement::Node(node) = element {
+                bbox.get_or_insert_with(|| BoundingBox::from_point(node.location)).extend(node.location);
+            }
+        }
+        let mut with_members = ElementFilter::all().with_bbox(bbox.unwrap());
+        with_members.resolve_dependencies = true;
+        let by_id = ElementFilter::all().with_id_range(WayId(5), WayId(20));
+
+        for filter in [with_members, by_id] {
+            let expected: Vec<_> = reader.iter_filtered(&filter).map(|element| crate::io::history::identity(&element.unwrap())).collect();
+            assert!(expected.iter().any(|(kind, _)| *kind == 1));
+            let (mut ids, mut cursor) = (Vec::new(), None);
+            loop {
+                let page = reader.page(&filter, cursor.as_ref(), 7).unwrap();
+                ids.extend(page.elements.iter().map(crate::io::history::identity));
+                match page.next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(ids, expected);
+        }
+    }
+
     #[test]
     fn test_parallel_config() {
         let config = ParallelConfig::default();
@@ -422,20 +3000,223 @@ mod tests {
         let stats = ProcessingStats::default();
         assert_eq!(stats.blobs_processed, 0);
         assert_eq!(stats.elements_processed, 0);
+        assert_eq!(stats.blobs_skipped, 0);
     }
 
     #[test]
     fn test_osm_element_types() {
         let node = Node {
-            id: 1,
+            id: NodeId(1),
             keys: vec![],
             vals: vec![],
             info: None,
-            lat: 0,
-            lon: 0,
+            location: LatLon::default(),
         };
         
         let element = OsmElement::Node(node);
         assert!(matches!(element, OsmElement::Node(_)));
     }
+
+    #[test]
+    fn test_ways_with_geometry_from_node_cache() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(9).grid_size(12).block_size(50).relation_count(2).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut points = 0;
+        let stats = reader.ways_with_geometry(|way, coords| {
+            assert_eq!(coords.len(), way.refs.len());
+            points += coords.len();
+            Ok(())
+        }).unwrap();
+        assert_eq!(stats, GeometryStats { ways_resolved: planet.ways, from_locations_on_ways: 0, ways_incomplete: 0 });
+        assert!(points > 0);
+    }
+
+    #[test]
+    fn test_ways_complete() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(9).grid_size(16).block_size(50).relation_count(2).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut all = std::collections::HashMap::new();
+        reader.ways_with_geometry(|way, coords| {
+            all.insert(way.id, coords.to_vec());
+            Ok(())
+        }).unwrap();
+
+        let mut primary = HashSet::new();
+        reader.for_each_filtered_with_strings(&ElementFilter::ways_only(false), |element, strings| {
+            if let OsmElement::Way(way) = element
+                && way.tags(strings).get("highway") == Some("primary")
+            {
+                primary.insert(way.id);
+            }
+            Ok(())
+        }).unwrap();
+        assert!(!primary.is_empty() && primary.len() < all.len());
+
+        let filter = ElementFilter::all().with_tag("highway".to_string(), "primary".to_string());
+        let mut seen = HashSet::new();
+        let stats = reader.ways_complete(&filter, |way, coords| {
+            assert_eq!(coords, all[&way.id].as_slice());
+            seen.insert(way.id);
+            Ok(())
+        }).unwrap();
+        assert_eq!(seen, primary);
+        assert_eq!(stats, GeometryStats { ways_resolved: primary.len() as u64, from_locations_on_ways: 0, ways_incomplete: 0 });
+    }
+
+    #[test]
+    fn test_ways_with_geometry_from_locations_on_ways() {
+        use crate::blocks::header_block::HeaderBlock;
+        use crate::io::writer::{BlockBuffer, PbfWriter};
+
+        // Nodes 1..=3 are not in the file, only on the way
+        let strings = StringTable::new();
+        let located = Way { id: WayId(7), keys: vec![], vals: vec![], info: None, refs: vec![1, 1, 1], lat: vec![10_000_000_000, 100, -200], lon: vec![-5_000_000_000, 0, 300] };
+        let bare = Way { id: WayId(8), keys: vec![], vals: vec![], info: None, refs: vec![1], lat: vec![], lon: vec![] };
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock { optional_features: vec![LOCATIONS_ON_WAYS.into()], ..Default::default() }).unwrap();
+        let mut block = BlockBuffer::default();
+        block.push(&OsmElement::Way(located), &strings);
+        block.push(&OsmElement::Way(bare), &strings);
+        writer.write_primitive_block(&block.finish()).unwrap();
+
+        let mut reader = Reader::new(Cursor::new(writer.into_inner())).unwrap();
+        let mut resolved = Vec::new();
+        let stats = reader.ways_with_geometry(|way, coords| {
+            assert_eq!(way.locations().as_deref(), Some(coords));
+            resolved.push((way.id, coords.to_vec()));
+            Ok(())
+        }).unwrap();
+        let expected = [LatLon::from_raw(10_000_000_000, -5_000_000_000), LatLon::from_raw(10_000_000_100, -5_000_000_000), LatLon::from_raw(9_999_999_900, -4_999_999_700)];
+        assert_eq!(resolved, vec![(WayId(7), expected.to_vec())]);
+        assert_eq!(stats, GeometryStats { ways_resolved: 1, from_locations_on_ways: 1, ways_incomplete: 1 });
+    }
+
+    #[test]
+    fn test_lazy_index_extends_while_reading() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(5).grid_size(10).block_size(30).relation_count(2).write_to(&mut data).unwrap();
+        let mut eager = Reader::new(Cursor::new(data.clone())).unwrap();
+        let expected = eager.for_each(|_| Ok(())).unwrap();
+
+        let mut lazy = Reader::lazy(Cursor::new(data.clone())).unwrap();
+        assert_eq!(lazy.indexed_reader.blob_count(), 1);
+        assert!(lazy.indexed_reader.read_blob_by_index(2).unwrap().is_some());
+        assert_eq!(lazy.indexed_reader.blob_count(), 3);
+        assert!(!lazy.indexed_reader.is_index_complete());
+        let stats = lazy.for_each(|_| Ok(())).unwrap();
+        assert_eq!(stats.blobs_processed, expected.blobs_processed);
+        assert!(lazy.indexed_reader.is_index_complete());
+        assert_eq!(lazy.indexed_reader.index(), eager.indexed_reader.index());
+
+        let mut lazy = Reader::lazy(Cursor::new(data)).unwrap();
+        let filter = ElementFilter::ways_only(false);
+        assert_eq!(lazy.count_filtered(&filter).unwrap().ways_processed, eager.count_filtered(&filter).unwrap().ways_processed);
+    }
+
+    #[test]
+    fn test_iter_and_par_iter_match_for_each_filtered() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(7).grid_size(10).block_size(25).relation_count(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let (nodes, _) = reader.collect_filtered(&ElementFilter::nodes_only()).unwrap();
+        let locations: Vec<_> = nodes.iter().take(30).filter_map(|element| match element {
+            OsmElement::Node(node) => Some(node.location),
+            _ => None,
+        }).collect();
+        let mut bbox = BoundingBox::from_point(locations[0]);
+        for location in &locations {
+            bbox.extend(*location);
+        }
+        let mut with_members = ElementFilter::all().with_bbox(bbox);
+        with_members.resolve_dependencies = true;
+
+        for filter in [ElementFilter::all(), ElementFilter::ways_only(false), with_members] {
+            let mut expected = Vec::new();
+            let stats = reader.for_each_filtered(&filter, |element| {
+                expected.push(crate::io::history::identity(&element));
+                Ok(())
+            }).unwrap();
+            assert!(!expected.is_empty());
+
+            let mut iter = reader.iter_filtered(&filter);
+            let ids: Vec<_> = iter.by_ref().map(|element| crate::io::history::identity(&element.unwrap())).collect();
+            assert_eq!(ids, expected);
+            assert_eq!(iter.stats().elements_processed, stats.elements_processed);
+            assert_eq!(iter.stats().blobs_processed, stats.blobs_processed);
+
+            let mut ids: Vec<_> = reader.par_iter(&filter).unwrap().map(|element| crate::io::history::identity(&element.unwrap())).collect();
+            ids.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(ids, expected);
+        }
+        assert_eq!(reader.iter().count() as u64, reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).unwrap().elements_processed);
+    }
+
+    #[test]
+    fn test_par_iter_bbox_matches_iter_filtered() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(13).grid_size(30).block_size(20).relation_count(12).write_to(&mut data).unwrap();
+        assert!(planet.blobs > 40);
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut bbox = None;
+        for element in reader.iter_filtered(&ElementFilter::nodes_only()) {
+            if let OsmElement::Node(node) = element.unwrap() {
+                if node.id.0 % 30 < 10 && node.id.0 > 780 {
+                    bbox.get_or_insert_with(|| BoundingBox::from_point(node.location)).extend(node.location);
+                }
+            }
+        }
+        let mut filter = ElementFilter::all().with_bbox(bbox.unwrap());
+        filter.resolve_dependencies = true;
+
+        let mut expected: Vec<_> = reader.iter_filtered(&filter).map(|element| crate::io::history::identity(&element.unwrap())).collect();
+        expected.sort_unstable();
+        assert!(expected.iter().any(|(kind, _)| *kind == 1) && expected.iter().any(|(kind, _)| *kind == 2));
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for _ in 0..8 {
+            let elements = reader.par_iter(&filter).unwrap();
+            let mut ids: Vec<_> = pool.install(|| elements.map(|element| crate::io::history::identity(&element.unwrap())).collect());
+            ids.sort_unstable();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[test]
+    fn test_corrupt_blobs_skipped_and_recorded() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(9).grid_size(10).block_size(25).write_to(&mut data).unwrap();
+        let (total, _) = Reader::new(Cursor::new(data.clone())).unwrap().collect_filtered(&ElementFilter::all()).unwrap();
+        let index = IndexedReader::new(Cursor::new(data.clone())).unwrap().index().to_vec();
+        let damaged = &index[2];
+        // Garble the end of the zlib stream, leaving the Blob message readable
+        let end = (damaged.offset + 4 + damaged.header_size + damaged.size) as usize;
+        data[end - damaged.size as usize / 2..end].fill(0xff);
+        let clean_len = data.len() as u64;
+        data.extend_from_slice(&[0xff; 9]);
+
+        let mut reader = Reader::new(Cursor::new(data.clone())).unwrap();
+        assert!(reader.for_each_filtered(&ElementFilter::all(), |_| Ok(())).is_err());
+
+        let options = ReaderOptions::default().lenient().with_skip_log_level(SkipLogLevel::Off);
+        let mut reader = Reader::with_options(Cursor::new(data), options).unwrap();
+        assert_eq!(reader.corrupt_blob_policy(), CorruptBlobPolicy::Skip);
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        reader.set_on_error(move |corrupt| sink.lock().unwrap().push(corrupt.clone()));
+        let (elements, stats) = reader.collect_filtered(&ElementFilter::all()).unwrap();
+        let found: Vec<_> = stats.corrupt_blobs.iter().map(|corrupt| (corrupt.offset, corrupt.blob_index, corrupt.kind)).collect();
+        assert_eq!(found, vec![(clean_len, None, BlobErrorKind::HeaderTooLarge), (damaged.offset, Some(2), BlobErrorKind::Compression)]);
+        assert_eq!(*reported.lock().unwrap(), stats.corrupt_blobs);
+        reader.clear_on_error();
+        assert_eq!(stats.blobs_skipped, 1);
+        // All data blobs but the damaged one; the header blob isn't decoded
+        assert_eq!(stats.blobs_processed, index.len() as u64 - 2);
+        assert!(!elements.is_empty() && elements.len() < total.len());
+
+        let mut iter = reader.iter();
+        assert_eq!(iter.by_ref().map(|element| element.unwrap()).count(), elements.len());
+        assert_eq!(iter.stats().corrupt_blobs.len(), 2);
+    }
 }
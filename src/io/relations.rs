@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use crate::blocks::primitives::relation::Relation;
+use crate::io::blob::{BlobError, Result};
+
+/// What to do when a relation's members lead back to a relation on the current path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CyclePolicy {
+    /// Skip the member closing the cycle
+    #[default]
+    BreakCycle,
+    /// Fail with `BlobError::RelationCycle`
+    Error,
+    /// Skip the member like `BreakCycle` and record the cycle in `RelationWalk::cycles`
+    Report,
+}
+
+/// Relation members of each relation, for following nested relations
+///
+/// Traversals keep their own stack instead of recursing, so deep nesting
+/// doesn't overflow the thread stack, and check every member against the
+/// current path, so cycles such as a route master listing itself end
+/// according to a `CyclePolicy` instead of looping.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::{CyclePolicy, RelationGraph};
+///
+/// let mut graph = RelationGraph::new();
+/// graph.add(1, [2]);
+/// graph.add(2, [3]);
+/// graph.add(3, [1]);
+///
+/// let walk = graph.walk(1, CyclePolicy::Report)?;
+/// assert_eq!(walk.order, [1, 2, 3]);
+/// assert_eq!(walk.cycles, [vec![1, 2, 3]]);
+/// assert!(graph.walk(1, CyclePolicy::Error).is_err());
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RelationGraph {
+    children: HashMap<i64, Vec<i64>>,
+}
+
+/// Relations reached by a traversal of a `RelationGraph`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelationWalk {
+    /// Relations in visiting order, each once
+    pub order: Vec<i64>,
+    /// Cycles met under `CyclePolicy::Report`, each as the path from the
+    /// re-entered relation to the one listing it as a member
+    pub cycles: Vec<Vec<i64>>,
+    /// Member relations not in the graph, e.g. outside an extract
+    pub missing: Vec<i64>,
+}
+
+/// Relation integrity of a file, from `Reader::quality_report`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QualityReport {
+    pub relations: u64,
+    /// Memberships of a relation in another relation
+    pub nested_memberships: u64,
+    /// Memberships referring to relations not in the file
+    pub missing_relation_members: u64,
+    /// Reference cycles, as in `RelationWalk::cycles`
+    pub relation_cycles: Vec<Vec<i64>>,
+}
+
+impl RelationGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a relation given its member relation ids
+    pub fn add(&mut self, id: i64, member_relations: impl IntoIterator<Item = i64>) {
+        self.children.entry(id).or_default().extend(member_relations);
+    }
+
+    /// Add a decoded relation, following its delta-encoded member ids
+    pub fn insert(&mut self, relation: &Relation) {
+        let members = relation.members().filter_map(|member| member.relation_id());
+        self.add(relation.id.0, members.map(|id| id.0).collect::<Vec<_>>());
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn contains(&self, id: i64) -> bool {
+        self.children.contains_key(&id)
+    }
+
+    /// Member relations of a relation, in stored order
+    pub fn members(&self, id: i64) -> &[i64] {
+        self.children.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Relations reachable from `root`, parents before their members
+    pub fn walk(&self, root: i64, policy: CyclePolicy) -> Result<RelationWalk> {
+        let mut walk = RelationWalk::default();
+        self.traverse(root, policy, &mut HashSet::new(), &mut walk, Order::Pre)?;
+        Ok(walk)
+    }
+
+    /// Relations reachable from `root` in dependency order: members before
+    /// the relations listing them, `root` last
+    pub fn dependencies(&self, root: i64, policy: CyclePolicy) -> Result<RelationWalk> {
+        let mut walk = RelationWalk::default();
+        self.traverse(root, policy, &mut HashSet::new(), &mut walk, Order::Post)?;
+        Ok(walk)
+    }
+
+    /// Every cycle in the graph, each found once
+    pub fn find_cycles(&self) -> Vec<Vec<i64>> {
+        let mut roots: Vec<i64> = self.children.keys().copied().collect();
+        roots.sort_unstable();
+        let mut seen = HashSet::new();
+        let mut walk = RelationWalk::default();
+        for root in roots {
+            if !seen.contains(&root) {
+                // Report never fails
+                let _ = self.traverse(root, CyclePolicy::Report, &mut seen, &mut walk, Order::Pre);
+            }
+        }
+        walk.cycles
+    }
+
+    /// Membership counts and cycles of the whole graph
+    pub fn quality_report(&self) -> QualityReport {
+        let members = self.children.values().flatten();
+        QualityReport {
+            relations: self.children.len() as u64,
+            nested_memberships: self.children.values().map(|m| m.len() as u64).sum(),
+            missing_relation_members: members.filter(|id| !self.contains(**id)).count() as u64,
+            relation_cycles: self.find_cycles(),
+        }
+    }
+
+    /// Depth-first traversal from `root`, skipping relations in `seen`
+    fn traverse(&self, root: i64, policy: CyclePolicy, seen: &mut HashSet<i64>, walk: &mut RelationWalk, order: Order) -> Result<()> {
+        if !seen.insert(root) {
+            return Ok(());
+        }
+        if order == Order::Pre {
+            walk.order.push(root);
+        }
+        // Current path, with the next member to look at for each relation on it
+        let mut path: Vec<(i64, usize)> = vec![(root, 0)];
+        let mut on_path: HashMap<i64, usize> = HashMap::from([(root, 0)]);
+
+        while let Some((id, next)) = path.last_mut() {
+            let id = *id;
+            let Some(&member) = self.members(id).get(*next) else {
+                path.pop();
+                on_path.remove(&id);
+                if order == Order::Post {
+                    walk.order.push(id);
+                }
+                continue;
+            };
+            *next += 1;
+
+            if let Some(&depth) = on_path.get(&member) {
+                let cycle: Vec<i64> = path[depth..].iter().map(|(id, _)| *id).collect();
+                match policy {
+                    CyclePolicy::BreakCycle => {}
+                    CyclePolicy::Error => return Err(BlobError::RelationCycle { cycle }),
+                    CyclePolicy::Report => walk.cycles.push(cycle),
+                }
+                continue;
+            }
+            if !self.contains(member) {
+                if seen.insert(member) {
+                    walk.missing.push(member);
+                }
+                continue;
+            }
+            if seen.insert(member) {
+                if order == Order::Pre {
+                    walk.order.push(member);
+                }
+                on_path.insert(member, path.len());
+                path.push((member, 0));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Order {
+    Pre,
+    Post,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::RelationId;
+    use crate::blocks::primitives::member_type::MemberType;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_cycle_policies() {
+        let mut graph = RelationGraph::new();
+        graph.add(1, [2, 3]);
+        graph.add(2, [4, 1]);
+        graph.add(3, [4, 9]);
+        graph.add(4, [3]);
+
+        let walk = graph.walk(1, CyclePolicy::BreakCycle).unwrap();
+        assert_eq!(walk, RelationWalk { order: vec![1, 2, 4, 3], cycles: vec![], missing: vec![9] });
+        let walk = graph.dependencies(1, CyclePolicy::Report).unwrap();
+        assert_eq!(walk.order, vec![3, 4, 2, 1]);
+        assert_eq!(walk.cycles, vec![vec![4, 3], vec![1, 2]]);
+        match graph.dependencies(1, CyclePolicy::Error) {
+            Err(BlobError::RelationCycle { cycle }) => assert_eq!(cycle, vec![4, 3]),
+            other => panic!("expected RelationCycle, got {other:?}"),
+        }
+        assert_eq!(graph.find_cycles(), vec![vec![4, 3], vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_deep_nesting_and_report() {
+        // A chain deep enough to overflow a recursive walk, closed into a ring
+        let mut graph = RelationGraph::new();
+        for id in 0..200_000 {
+            graph.insert(&Relation {
+                id: RelationId(id),
+                keys: vec![],
+                vals: vec![],
+                info: None,
+                roles_sid: vec![0],
+                memids: vec![id + 1],
+                types: vec![MemberType::Relation],
+            });
+        }
+        assert_eq!(graph.walk(0, CyclePolicy::Error).unwrap().missing, vec![200_000]);
+        graph.add(200_000, [0]);
+        assert_eq!(graph.dependencies(0, CyclePolicy::BreakCycle).unwrap().order.len(), 200_001);
+
+        let report = graph.quality_report();
+        assert_eq!((report.relations, report.nested_memberships, report.missing_relation_members), (200_001, 200_001, 0));
+        assert_eq!(report.relation_cycles.len(), 1);
+        assert_eq!(report.relation_cycles[0].len(), 200_001);
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::ErrorKind;
+use std::time::Duration;
+
+/// Retry policy for transient IO errors at the blob source layer
+///
+/// Network filesystems (NFS, s3fs and other FUSE mounts) occasionally fail reads
+/// that succeed when repeated. The policy retries only errors classified as
+/// retryable by [`is_retryable`], sleeping with exponential backoff in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts per read, including the first one (1 = no retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each failed attempt
+    pub backoff_multiplier: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry; every error is returned immediately
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            backoff_multiplier: 1,
+        }
+    }
+
+    /// Sensible defaults for network filesystems (5 attempts, 50ms doubling up to 2s)
+    pub fn network_filesystem() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            backoff_multiplier: 2,
+        }
+    }
+
+    /// Set the total number of attempts
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the initial and maximum backoff delays
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Delay to wait before the given retry (1-based)
+    pub fn backoff_for(&self, retry: u32) -> Duration {
+        let factor = self.backoff_multiplier.max(1).saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// Run an IO operation, retrying transient failures
+    ///
+    /// `retries` is incremented once per retry performed, so callers can surface
+    /// the number of retries in their statistics.
+    pub fn run<T, F>(&self, retries: &mut u64, mut op: F) -> std::io::Result<T>
+    where
+        F: FnMut() -> std::io::Result<T>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts && is_retryable(&e) => {
+                    std::thread::sleep(self.backoff_for(attempt));
+                    *retries += 1;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Classify an IO error as transient (worth retrying) or fatal
+///
+/// Data errors such as `UnexpectedEof` or `InvalidData` are fatal: repeating the
+/// read returns the same bytes.
+pub fn is_retryable(error: &std::io::Error) -> bool {
+    match error.kind() {
+        ErrorKind::Interrupted
+        | ErrorKind::WouldBlock
+        | ErrorKind::TimedOut
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::NotConnected
+        | ErrorKind::BrokenPipe
+        | ErrorKind::ResourceBusy
+        | ErrorKind::StaleNetworkFileHandle => true,
+        // FUSE filesystems commonly report backend hiccups as a bare EIO
+        _ => is_raw_eio(error),
+    }
+}
+
+#[cfg(unix)]
+fn is_raw_eio(error: &std::io::Error) -> bool {
+    error.raw_os_error() == Some(5)
+}
+
+#[cfg(not(unix))]
+fn is_raw_eio(_error: &std::io::Error) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Error;
+
+    #[test]
+    fn test_classification() {
+        assert!(is_retryable(&Error::from(ErrorKind::TimedOut)));
+        assert!(is_retryable(&Error::from(ErrorKind::Interrupted)));
+        assert!(!is_retryable(&Error::from(ErrorKind::UnexpectedEof)));
+        assert!(!is_retryable(&Error::from(ErrorKind::NotFound)));
+        #[cfg(unix)]
+        assert!(is_retryable(&Error::from_raw_os_error(5)));
+    }
+
+    #[test]
+    fn test_backoff_growth() {
+        let policy = RetryPolicy::network_filesystem();
+        assert_eq!(policy.backoff_for(1), Duration::from_millis(50));
+        assert_eq!(policy.backoff_for(2), Duration::from_millis(100));
+        assert_eq!(policy.backoff_for(10), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_run_retries_transient_errors() {
+        let policy = RetryPolicy::network_filesystem().with_backoff(Duration::ZERO, Duration::ZERO);
+        let mut retries = 0;
+        let mut calls = 0;
+        let result = policy.run(&mut retries, || {
+            calls += 1;
+            if calls < 3 { Err(Error::from(ErrorKind::TimedOut)) } else { Ok(calls) }
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(retries, 2);
+    }
+
+    #[test]
+    fn test_run_gives_up() {
+        let policy = RetryPolicy::none();
+        let mut retries = 0;
+        let result: std::io::Result<()> = policy.run(&mut retries, || Err(Error::from(ErrorKind::TimedOut)));
+        assert!(result.is_err());
+        assert_eq!(retries, 0);
+
+        let policy = RetryPolicy::network_filesystem().with_backoff(Duration::ZERO, Duration::ZERO);
+        let result: std::io::Result<()> = policy.run(&mut retries, || Err(Error::from(ErrorKind::InvalidData)));
+        assert!(result.is_err());
+        assert_eq!(retries, 0);
+    }
+}
@@ -0,0 +1,182 @@
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::io::blob::Blob;
+use crate::io::indexed_reader::ElementCounts;
+use crate::io::reader::OsmElement;
+
+/// Decode time a chunk should give each thread
+const TARGET_TIME_PER_THREAD: Duration = Duration::from_millis(20);
+
+/// Weight of a new measurement in the moving average of decode rates
+const SMOOTHING: f64 = 0.3;
+
+/// Dominant element kind of a blob, which decides most of its decode cost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BlobKind {
+    Nodes,
+    Ways,
+    Relations,
+    Other,
+}
+
+impl BlobKind {
+    /// Kind from index counts, `None` before `build_deep_index`
+    pub fn from_counts(counts: &ElementCounts) -> Option<Self> {
+        if counts.is_unknown() {
+            return None;
+        }
+        let kinds = [(counts.nodes, Self::Nodes), (counts.ways, Self::Ways), (counts.relations, Self::Relations), (counts.changesets, Self::Other)];
+        kinds.into_iter().max_by_key(|(count, _)| *count).map(|(_, kind)| kind)
+    }
+
+    /// Kind of a decoded blob
+    pub fn of_elements(elements: &[OsmElement]) -> Self {
+        let mut counts = ElementCounts::default();
+        for element in elements {
+            match element {
+                OsmElement::Node(_) => counts.nodes += 1,
+                OsmElement::Way(_) => counts.ways += 1,
+                OsmElement::Relation(_) => counts.relations += 1,
+                OsmElement::ChangeSet(_) => counts.changesets += 1,
+            }
+        }
+        Self::from_counts(&counts).unwrap_or(Self::Other)
+    }
+}
+
+/// What the index tells about a blob's decode cost before reading it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CostHint {
+    /// Size of the blob in the file
+    pub size: u64,
+    pub kind: Option<BlobKind>,
+}
+
+/// A read blob waiting for a decoding thread
+#[derive(Debug)]
+pub(crate) struct ScheduledBlob {
+    /// Position in file order within the chunk
+    pub seq: usize,
+    pub blob_index: usize,
+    pub hint: CostHint,
+    pub blob: Blob,
+}
+
+#[derive(Debug)]
+struct Rates {
+    /// Smoothed decode nanoseconds per byte of blob, by `BlobKind`
+    per_kind: [Option<f64>; 4],
+    /// Kind of the last decoded blob, the guess for blobs the index knows nothing about
+    last_kind: BlobKind,
+}
+
+/// Chunk planner for `ParallelConfig::adaptive`
+///
+/// Learns how long each kind of blob takes to decode per byte and sizes the
+/// next chunk so its estimated decode time keeps every thread busy for about
+/// `TARGET_TIME_PER_THREAD`: many cheap node blobs per chunk, few costly
+/// relation blobs. Within a chunk, the costliest blobs are handed out first,
+/// so a chunk doesn't wait on a big blob started last.
+#[derive(Debug)]
+pub(crate) struct AdaptiveScheduler {
+    threads: usize,
+    max_chunk: usize,
+    rates: Mutex<Rates>,
+}
+
+impl AdaptiveScheduler {
+    pub fn new(threads: usize, max_chunk: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            max_chunk: max_chunk.max(1),
+            rates: Mutex::new(Rates { per_kind: [None; 4], last_kind: BlobKind::Other }),
+        }
+    }
+
+    /// Estimated decode nanoseconds, `None` before the first measurement
+    ///
+    /// Kinds not measured yet are assumed as costly as the costliest one seen.
+    fn estimate(rates: &Rates, hint: CostHint) -> Option<f64> {
+        let kind = hint.kind.unwrap_or(rates.last_kind);
+        let rate = rates.per_kind[kind as usize].or_else(|| rates.per_kind.iter().flatten().copied().reduce(f64::max))?;
+        Some(rate * hint.size as f64)
+    }
+
+    /// Blobs of the next chunk, starting at `remaining.start`
+    ///
+    /// Until a decode has been timed, a chunk holds one blob per thread. A
+    /// chunk never holds more than `max_chunk` blobs, nor fewer than one per
+    /// thread while blobs remain.
+    pub fn next_chunk(&self, remaining: Range<usize>, hint: impl Fn(usize) -> CostHint) -> Range<usize> {
+        let start = remaining.start;
+        let limit = self.max_chunk.min(remaining.len());
+        let rates = self.rates.lock().expect("scheduler lock poisoned");
+        if rates.per_kind.iter().all(Option::is_none) {
+            return start..start + self.threads.min(limit);
+        }
+
+        let budget = TARGET_TIME_PER_THREAD.as_nanos() as f64 * self.threads as f64;
+        let mut total = 0.0;
+        let mut end = start;
+        while end < start + limit && (total < budget || end - start < self.threads) {
+            total += Self::estimate(&rates, hint(end)).unwrap_or(0.0);
+            end += 1;
+        }
+        start..end
+    }
+
+    /// Put the costliest blobs first
+    pub fn order(&self, blobs: &mut [ScheduledBlob]) {
+        let rates = self.rates.lock().expect("scheduler lock poisoned");
+        blobs.sort_by(|a, b| {
+            let cost = |blob: &ScheduledBlob| Self::estimate(&rates, blob.hint).unwrap_or(blob.hint.size as f64);
+            cost(b).total_cmp(&cost(a))
+        });
+    }
+
+    /// Account a decoded blob
+    pub fn record(&self, kind: BlobKind, size: u64, elapsed: Duration) {
+        if size == 0 {
+            return;
+        }
+        let rate = elapsed.as_nanos() as f64 / size as f64;
+        let mut rates = self.rates.lock().expect("scheduler lock poisoned");
+        let slot = &mut rates.per_kind[kind as usize];
+        *slot = Some(slot.map_or(rate, |old| old + SMOOTHING * (rate - old)));
+        rates.last_kind = kind;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_chunks_follow_decode_cost() {
+        let scheduler = AdaptiveScheduler::new(2, 1000);
+        let hint = |kind| move |_| CostHint { size: 1000, kind: Some(kind) };
+        assert_eq!(scheduler.next_chunk(0..50, hint(BlobKind::Nodes)), 0..2);
+        assert_eq!(scheduler.next_chunk(49..50, hint(BlobKind::Nodes)), 49..50);
+
+        // 1µs per node blob, 10ms per relation blob
+        scheduler.record(BlobKind::Nodes, 1000, Duration::from_micros(1));
+        scheduler.record(BlobKind::Relations, 1000, Duration::from_millis(10));
+        let nodes = scheduler.next_chunk(0..1000, hint(BlobKind::Nodes));
+        let relations = scheduler.next_chunk(0..1000, hint(BlobKind::Relations));
+        assert!(nodes.len() > 10 * relations.len(), "{nodes:?} vs {relations:?}");
+        assert_eq!(relations, 0..4);
+        // Unmeasured kinds are assumed as costly as relations
+        assert_eq!(scheduler.next_chunk(0..1000, hint(BlobKind::Ways)), 0..4);
+        assert_eq!(AdaptiveScheduler::new(2, 3).next_chunk(0..10, hint(BlobKind::Nodes)), 0..2);
+    }
+
+    #[test]
+    fn test_kind_from_counts() {
+        assert_eq!(BlobKind::from_counts(&ElementCounts::default()), None);
+        let counts = ElementCounts { nodes: 2, ways: 0, relations: 5, changesets: 0 };
+        assert_eq!(BlobKind::from_counts(&counts), Some(BlobKind::Relations));
+        assert_eq!(BlobKind::of_elements(&[]), BlobKind::Other);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+/// Restores sequence order of results produced out of order by parallel workers
+///
+/// Items are pushed with their sequence number (0, 1, 2, ...) in any order and
+/// popped strictly in sequence, buffering the ones that arrive early.
+#[derive(Debug)]
+pub(crate) struct SequenceMerger<T> {
+    next: usize,
+    pending: BTreeMap<usize, T>,
+}
+
+impl<T> SequenceMerger<T> {
+    pub(crate) fn new() -> Self {
+        Self { next: 0, pending: BTreeMap::new() }
+    }
+
+    /// Add the result for sequence number `seq`
+    pub(crate) fn push(&mut self, seq: usize, item: T) {
+        debug_assert!(seq >= self.next && !self.pending.contains_key(&seq), "duplicate sequence number {seq}");
+        self.pending.insert(seq, item);
+    }
+
+    /// Take the next in-sequence result, if it has arrived
+    pub(crate) fn pop_ready(&mut self) -> Option<T> {
+        let item = self.pending.remove(&self.next)?;
+        self.next += 1;
+        Some(item)
+    }
+
+    /// Number of results buffered while waiting for an earlier one
+    pub(crate) fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorders_results() {
+        let mut merger = SequenceMerger::new();
+        let mut out = Vec::new();
+
+        for seq in [2, 0, 3, 1, 4] {
+            merger.push(seq, seq * 10);
+            while let Some(item) = merger.pop_ready() {
+                out.push(item);
+            }
+        }
+
+        assert_eq!(out, vec![0, 10, 20, 30, 40]);
+        assert_eq!(merger.buffered(), 0);
+    }
+
+    #[test]
+    fn test_waits_for_gap() {
+        let mut merger = SequenceMerger::new();
+        merger.push(1, "b");
+        assert_eq!(merger.pop_ready(), None);
+        assert_eq!(merger.buffered(), 1);
+
+        merger.push(0, "a");
+        assert_eq!(merger.pop_ready(), Some("a"));
+        assert_eq!(merger.pop_ready(), Some("b"));
+    }
+}
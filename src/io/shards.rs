@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobError, Result};
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, Reader};
+use crate::io::temp::{TempDir, TempDirPolicy};
+use crate::io::writer::{BlockBuffer, PbfWriter};
+
+/// Elements per block of an on-disk shard
+const SHARD_BLOCK_SIZE: usize = 8000;
+
+/// Where `Reader::par_collect_sharded_with` keeps the elements of each shard
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ShardStorage {
+    /// Decoded elements in memory, with the string tables of their blocks
+    #[default]
+    Memory,
+    /// One PBF file per shard in a temp directory created by the policy,
+    /// removed when the last shard of the collection is dropped
+    Disk(TempDirPolicy),
+}
+
+/// Elements collected into one shard by `Reader::par_collect_sharded`
+///
+/// Elements keep the order they have in the input file and the form they
+/// have in `Reader::for_each_filtered`: node locations in nanodegrees, way
+/// refs and relation member ids delta-encoded.
+#[derive(Debug)]
+pub struct Shard {
+    index: usize,
+    len: u64,
+    data: ShardData,
+}
+
+#[derive(Debug)]
+enum ShardData {
+    Memory(Vec<(Arc<StringTable>, Vec<OsmElement>)>),
+    Disk { path: PathBuf, _dir: Arc<TempDir> },
+}
+
+impl Shard {
+    /// Position of the shard, the value the key function returned for its elements
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// File holding an on-disk shard, valid while the shard lives
+    pub fn path(&self) -> Option<&std::path::Path> {
+        match &self.data {
+            ShardData::Memory(_) => None,
+            ShardData::Disk { path, .. } => Some(path),
+        }
+    }
+
+    /// Visit the shard's elements with the string table their tags refer to
+    pub fn for_each_with_strings<F>(&self, mut processor: F) -> Result<()>
+    where
+        F: FnMut(OsmElement, &StringTable) -> Result<()>,
+    {
+        match &self.data {
+            ShardData::Memory(batches) => {
+                for (strings, elements) in batches {
+                    for element in elements {
+                        processor(element.clone(), strings)?;
+                    }
+                }
+                Ok(())
+            }
+            ShardData::Disk { path, .. } => {
+                let mut reader = Reader::new(File::open(path)?)?;
+                reader.for_each_filtered_with_strings(&ElementFilter::all(), processor)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Shards being filled by `Reader::par_collect_sharded_with`
+pub(crate) struct ShardSet {
+    shards: Vec<ShardBuilder>,
+    dir: Option<Arc<TempDir>>,
+}
+
+enum ShardBuilder {
+    Memory { len: u64, batches: Vec<(Arc<StringTable>, Vec<OsmElement>)> },
+    Disk { len: u64, path: PathBuf, writer: Box<PbfWriter<BufWriter<File>>>, block: BlockBuffer },
+}
+
+impl ShardSet {
+    pub(crate) fn new(n_shards: usize, storage: &ShardStorage) -> Result<Self> {
+        if n_shards == 0 {
+            return Err(BlobError::InvalidFormat("Unsupported shard count 0".to_string()));
+        }
+        match storage {
+            ShardStorage::Memory => Ok(Self {
+                shards: (0..n_shards).map(|_| ShardBuilder::Memory { len: 0, batches: Vec::new() }).collect(),
+                dir: None,
+            }),
+            ShardStorage::Disk(policy) => {
+                let dir = policy.create()?;
+                let mut shards = Vec::with_capacity(n_shards);
+                for index in 0..n_shards {
+                    let name = format!("shard-{index}.osm.pbf");
+                    let mut writer = PbfWriter::new(BufWriter::new(dir.create_file(&name)?));
+                    writer.write_header(&HeaderBlock::default())?;
+                    shards.push(ShardBuilder::Disk { len: 0, path: dir.path().join(name), writer: Box::new(writer), block: BlockBuffer::default() });
+                }
+                Ok(Self { shards, dir: Some(Arc::new(dir)) })
+            }
+        }
+    }
+
+    /// Append elements of one block to a shard
+    pub(crate) fn append(&mut self, shard: usize, strings: &Arc<StringTable>, elements: Vec<OsmElement>) -> Result<()> {
+        match &mut self.shards[shard] {
+            ShardBuilder::Memory { len, batches } => {
+                *len += elements.len() as u64;
+                batches.push((strings.clone(), elements));
+            }
+            ShardBuilder::Disk { len, writer, block, .. } => {
+                *len += elements.len() as u64;
+                for element in &elements {
+                    block.push(element, strings);
+                    if block.len() >= SHARD_BLOCK_SIZE {
+                        writer.write_primitive_block(&std::mem::take(block).finish())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<Vec<Shard>> {
+        let mut shards = Vec::with_capacity(self.shards.len());
+        for (index, builder) in self.shards.into_iter().enumerate() {
+            let (len, data) = match builder {
+                ShardBuilder::Memory { len, batches } => (len, ShardData::Memory(batches)),
+                ShardBuilder::Disk { len, path, mut writer, block } => {
+                    if block.len() > 0 {
+                        writer.write_primitive_block(&block.finish())?;
+                    }
+                    writer.flush()?;
+                    let dir = self.dir.clone().expect("on-disk shards have a directory");
+                    (len, ShardData::Disk { path, _dir: dir })
+                }
+            };
+            shards.push(Shard { index, len, data });
+        }
+        Ok(shards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::bbox::BoundingBox;
+    use crate::io::reader::ParallelConfig;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn id(element: &OsmElement) -> i64 {
+        match element {
+            OsmElement::Node(node) => node.id.0,
+            OsmElement::Way(way) => way.id.0,
+            OsmElement::Relation(relation) => relation.id.0,
+            OsmElement::ChangeSet(changeset) => changeset.id.0,
+        }
+    }
+
+    #[test]
+    fn test_shards_match_sequential_grouping() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(5).grid_size(20).block_size(50).relation_count(4).write_to(&mut data).unwrap();
+        let filter = ElementFilter::all();
+        let key = |element: &OsmElement, _: &StringTable| (!matches!(element, OsmElement::Relation(_))).then(|| id(element).rem_euclid(3) as usize);
+
+        let mut expected = vec![Vec::new(); 3];
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        reader.for_each_filtered_with_strings(&filter, |element, strings| {
+            if let Some(shard) = key(&element, strings) {
+                expected[shard].push(id(&element));
+            }
+            Ok(())
+        }).unwrap();
+
+        let root = tempfile::tempdir().unwrap();
+        let config = ParallelConfig { num_threads: Some(2), chunk_size: 3, ..Default::default() };
+        for storage in [ShardStorage::Memory, ShardStorage::Disk(TempDirPolicy::with_location(root.path()))] {
+            let shards = reader.par_collect_sharded_with(&config, &storage, &filter, 3, key).unwrap();
+            for shard in &shards {
+                let mut ids = Vec::new();
+                shard.for_each_with_strings(|element, _| {
+                    ids.push(id(&element));
+                    Ok(())
+                }).unwrap();
+                assert_eq!(ids, expected[shard.index()]);
+                assert_eq!(shard.len(), ids.len() as u64);
+                assert_eq!(shard.path().is_some(), storage != ShardStorage::Memory);
+            }
+        }
+        assert!(reader.par_collect_sharded(&filter, 2, |_, _| Some(2)).is_err());
+        assert!(reader.par_collect_sharded(&filter, 0, key).is_err());
+    }
+
+    #[test]
+    fn test_bbox_shards_keep_each_blob_strings() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(9).grid_size(20).block_size(50).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut bbox = None;
+        reader.for_each_filtered(&ElementFilter::nodes_only(), |element| {
+            if let OsmElement::Node(node) = element {
+                if node.id.0 <= 120 {
+                    bbox.get_or_insert_with(|| BoundingBox::from_point(node.location)).extend(node.location);
+                }
+            }
+            Ok(())
+        }).unwrap();
+        let mut filter = ElementFilter::all().with_bbox(bbox.unwrap());
+        filter.resolve_dependencies = true;
+        let key = |element: &OsmElement, _: &StringTable| matches!(element, OsmElement::Way(_)).then(|| id(element).rem_euclid(2) as usize);
+        let tagged = |element: &OsmElement, strings: &StringTable| match element {
+            OsmElement::Way(way) => (way.id.0, way.tags(strings).iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>()),
+            _ => unreachable!(),
+        };
+
+        let mut expected = vec![Vec::new(); 2];
+        reader.for_each_filtered_with_strings(&filter, |element, strings| {
+            if let Some(shard) = key(&element, strings) {
+                expected[shard].push(tagged(&element, strings));
+            }
+            Ok(())
+        }).unwrap();
+        assert!(expected.iter().all(|ways| !ways.is_empty()));
+
+        for shard in &reader.par_collect_sharded(&filter, 2, key).unwrap() {
+            let mut ways = Vec::new();
+            shard.for_each_with_strings(|element, strings| {
+                ways.push(tagged(&element, strings));
+                Ok(())
+            }).unwrap();
+            assert_eq!(ways, expected[shard.index()]);
+        }
+    }
+}
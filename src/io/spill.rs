@@ -0,0 +1,211 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::Arc;
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::primitives::member_type::MemberType;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::Result;
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, Reader};
+use crate::io::temp::{TempDir, TempDirPolicy};
+use crate::io::writer::{BlockBuffer, PbfWriter};
+
+/// Default memory budget of `SpillConfig` (256 MiB)
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 << 20;
+
+/// Elements per block of a spilled chunk
+const SPILL_BLOCK_SIZE: usize = 8000;
+
+/// How much of a `SpillableElements` stays in memory, and where the rest goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillConfig {
+    /// Estimated bytes of elements and string tables kept in memory before
+    /// they're written to a chunk on disk
+    pub memory_budget: usize,
+    /// Where the chunk files are kept; created on the first spill
+    pub temp_dir: TempDirPolicy,
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        Self { memory_budget: DEFAULT_MEMORY_BUDGET, temp_dir: TempDirPolicy::default() }
+    }
+}
+
+impl SpillConfig {
+    /// Config keeping up to `memory_budget` bytes in memory
+    pub fn with_memory_budget(memory_budget: usize) -> Self {
+        Self { memory_budget, ..Default::default() }
+    }
+}
+
+/// Elements collected by `Reader::collect_bounded`, in memory up to a budget
+/// and in PBF chunk files beyond it
+///
+/// Elements keep their order and the form they have in
+/// `Reader::for_each_filtered`. Tags refer to the string table handed out
+/// with each element, which for spilled elements is that of their chunk
+/// block, not of the input file. The chunk files are removed on drop.
+#[derive(Debug)]
+pub struct SpillableElements {
+    config: SpillConfig,
+    len: u64,
+    memory: Vec<(Arc<StringTable>, Vec<OsmElement>)>,
+    memory_bytes: usize,
+    chunks: Vec<std::path::PathBuf>,
+    dir: Option<TempDir>,
+}
+
+impl SpillableElements {
+    pub fn new(config: SpillConfig) -> Self {
+        Self { config, len: 0, memory: Vec::new(), memory_bytes: 0, chunks: Vec::new(), dir: None }
+    }
+
+    /// Append an element whose tags refer to `strings`, spilling what's in
+    /// memory once it exceeds the budget
+    ///
+    /// Consecutive elements sharing a string table store it once.
+    pub fn push(&mut self, element: OsmElement, strings: &Arc<StringTable>) -> Result<()> {
+        self.memory_bytes += estimated_size(&element);
+        match self.memory.last_mut() {
+            Some((last, elements)) if Arc::ptr_eq(last, strings) => elements.push(element),
+            _ => {
+                self.memory_bytes += strings.s.iter().map(|s| size_of::<String>() + s.len()).sum::<usize>();
+                self.memory.push((strings.clone(), vec![element]));
+            }
+        }
+        self.len += 1;
+        if self.memory_bytes > self.config.memory_budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Write the elements in memory to a new chunk file
+    fn spill(&mut self) -> Result<()> {
+        let dir = match &mut self.dir {
+            Some(dir) => dir,
+            None => self.dir.insert(self.config.temp_dir.create()?),
+        };
+        let name = format!("chunk-{}.osm.pbf", self.chunks.len());
+        let mut writer = PbfWriter::new(BufWriter::new(dir.create_file(&name)?));
+        writer.write_header(&HeaderBlock::default())?;
+        let mut block = BlockBuffer::default();
+        for (strings, elements) in self.memory.drain(..) {
+            for element in &elements {
+                block.push(element, &strings);
+                if block.len() >= SPILL_BLOCK_SIZE {
+                    writer.write_primitive_block(&std::mem::take(&mut block).finish())?;
+                }
+            }
+        }
+        if block.len() > 0 {
+            writer.write_primitive_block(&block.finish())?;
+        }
+        writer.flush()?;
+        self.chunks.push(dir.path().join(name));
+        self.memory_bytes = 0;
+        Ok(())
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Chunk files written so far
+    pub fn spilled_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Estimated bytes of the elements and string tables still in memory
+    pub fn memory_bytes(&self) -> usize {
+        self.memory_bytes
+    }
+
+    /// Visit the elements in order with the string table their tags refer to
+    pub fn for_each_with_strings<F>(&self, mut processor: F) -> Result<()>
+    where
+        F: FnMut(OsmElement, &StringTable) -> Result<()>,
+    {
+        for path in &self.chunks {
+            let mut reader = Reader::new(File::open(path)?)?;
+            reader.for_each_filtered_with_strings(&ElementFilter::all(), &mut processor)?;
+        }
+        for (strings, elements) in &self.memory {
+            for element in elements {
+                processor(element.clone(), strings)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rough heap and inline size of a decoded element
+fn estimated_size(element: &OsmElement) -> usize {
+    let heap = match element {
+        OsmElement::Node(node) => (node.keys.capacity() + node.vals.capacity()) * size_of::<u32>(),
+        OsmElement::Way(way) => {
+            (way.keys.capacity() + way.vals.capacity()) * size_of::<u32>()
+                + (way.refs.capacity() + way.lat.capacity() + way.lon.capacity()) * size_of::<i64>()
+        }
+        OsmElement::Relation(relation) => {
+            (relation.keys.capacity() + relation.vals.capacity()) * size_of::<u32>()
+                + relation.roles_sid.capacity() * size_of::<i32>()
+                + relation.memids.capacity() * size_of::<i64>()
+                + relation.types.capacity() * size_of::<MemberType>()
+        }
+        OsmElement::ChangeSet(_) => 0,
+    };
+    size_of::<OsmElement>() + heap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn summary(element: &OsmElement, strings: &StringTable) -> (i64, Vec<(String, String)>) {
+        let id = match element {
+            OsmElement::Node(node) => node.id.0,
+            OsmElement::Way(way) => way.id.0,
+            OsmElement::Relation(relation) => relation.id.0,
+            OsmElement::ChangeSet(changeset) => changeset.id.0,
+        };
+        (id, element.tags(strings).into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn test_collect_bounded_spills_past_budget() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(6).grid_size(20).block_size(50).relation_count(4).write_to(&mut data).unwrap();
+        let mut reader = Reader::new(Cursor::new(data)).unwrap();
+        let filter = ElementFilter::all();
+        let mut expected = Vec::new();
+        reader.for_each_filtered_with_strings(&filter, |element, strings| {
+            expected.push(summary(&element, strings));
+            Ok(())
+        }).unwrap();
+
+        let root = tempfile::tempdir().unwrap();
+        let small = SpillConfig { memory_budget: 16 << 10, temp_dir: TempDirPolicy::with_location(root.path()) };
+        for (config, spills) in [(SpillConfig::default(), false), (small, true)] {
+            let (elements, stats) = reader.collect_bounded(&filter, &config).unwrap();
+            assert_eq!(elements.len(), expected.len() as u64);
+            assert_eq!(elements.spilled_chunks() > 1, spills);
+            assert!(elements.memory_bytes() <= config.memory_budget);
+            assert!(stats.blobs_processed > 1);
+            let mut found = Vec::new();
+            elements.for_each_with_strings(|element, strings| {
+                found.push(summary(&element, strings));
+                Ok(())
+            }).unwrap();
+            assert_eq!(found, expected);
+        }
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 0);
+    }
+}
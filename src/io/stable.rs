@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use crate::io::blob::{BlobError, MAX_BLOB_HEADER_SIZE, Result};
+use crate::io::decode::decode_blob_header;
+
+/// Wait until the file at `path` exists, has the same non-zero size on two
+/// polls in a row and ends right after a complete blob, then open it
+///
+/// Fails with an `Io` error of kind `TimedOut` if that doesn't happen within
+/// `timeout`.
+pub(crate) fn open_when_stable(path: &Path, poll_interval: Duration, timeout: Duration) -> Result<File> {
+    let deadline = Instant::now() + timeout;
+    let mut last_len = None;
+    loop {
+        let len = match std::fs::metadata(path) {
+            Ok(metadata) => Some(metadata.len()),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(len) = len.filter(|len| *len > 0 && last_len == Some(*len)) {
+            let mut file = File::open(path)?;
+            if ends_after_complete_blob(&mut file, len)? {
+                file.seek(SeekFrom::Start(0))?;
+                return Ok(file);
+            }
+        }
+        last_len = len;
+
+        let now = Instant::now();
+        if now >= deadline {
+            let message = format!("{} did not stabilize within {timeout:?}", path.display());
+            return Err(BlobError::Io(std::io::Error::new(ErrorKind::TimedOut, message)));
+        }
+        std::thread::sleep(poll_interval.min(deadline - now));
+    }
+}
+
+/// Whether the first `len` bytes of the file are a sequence of whole blobs
+///
+/// Files that don't start with a blob header count as complete once their
+/// size is stable, so opening them fails instead of waiting.
+fn ends_after_complete_blob(file: &mut File, len: u64) -> Result<bool> {
+    let mut offset = 0u64;
+    while offset < len {
+        let Some(header_start) = offset.checked_add(4).filter(|end| *end <= len) else {
+            return Ok(false);
+        };
+        let mut size_bytes = [0u8; 4];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut size_bytes)?;
+        let size = u32::from_be_bytes(size_bytes) as u64;
+        if size as usize > MAX_BLOB_HEADER_SIZE {
+            return Ok(offset == 0);
+        }
+        if header_start + size > len {
+            return Ok(false);
+        }
+        let mut header_bytes = vec![0u8; size as usize];
+        file.read_exact(&mut header_bytes)?;
+        let header = match decode_blob_header(&header_bytes) {
+            Ok(header) => header,
+            Err(_) if offset == 0 => return Ok(true),
+            Err(_) => return Ok(false),
+        };
+        offset = header_start + size + header.datasize as u64;
+    }
+    Ok(offset == len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_waits_for_writer() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(2).grid_size(20).block_size(50).write_to(&mut data).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("growing.osm.pbf");
+
+        // Half the file, then the rest a little later
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&data[..data.len() / 2]).unwrap();
+        let poll = Duration::from_millis(10);
+        assert!(matches!(
+            open_when_stable(&path, poll, Duration::from_millis(50)),
+            Err(BlobError::Io(e)) if e.kind() == ErrorKind::TimedOut
+        ));
+
+        let rest = data[data.len() / 2..].to_vec();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            file.write_all(&rest).unwrap();
+        });
+        let mut stable = open_when_stable(&path, poll, Duration::from_secs(10)).unwrap();
+        writer.join().unwrap();
+        let mut read = Vec::new();
+        stable.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+}
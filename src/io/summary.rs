@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::blocks::bbox::BoundingBox;
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::primitives::block::PrimitiveBlock;
+use crate::blocks::primitives::info::Info;
+use crate::blocks::timestamp::TimestampMillis;
+use crate::io::blob::{Blob, BlobData};
+
+/// Data blobs stored with one compression, in `FileSummary::compression`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    pub blobs: u64,
+    /// Bytes of blob data as stored in the file
+    pub stored_bytes: u64,
+    /// Bytes of blob data once decompressed
+    pub raw_bytes: u64,
+}
+
+/// Overview of a file from `Reader::summarize`, like `osmium fileinfo -e`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileSummary {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    pub changesets: u64,
+    /// Elements carrying each tag key
+    pub key_counts: HashMap<String, u64>,
+    /// Extent of all node locations
+    pub bbox: Option<BoundingBox>,
+    /// Lowest and highest node id
+    pub node_ids: Option<(i64, i64)>,
+    /// Lowest and highest way id
+    pub way_ids: Option<(i64, i64)>,
+    /// Lowest and highest relation id
+    pub relation_ids: Option<(i64, i64)>,
+    /// Earliest and latest timestamp of elements with metadata
+    pub timestamps: Option<(TimestampMillis, TimestampMillis)>,
+    /// Distinct user ids of elements with metadata
+    pub users: u64,
+    /// Data blobs per compression, named as in `Capabilities::compression`
+    pub compression: BTreeMap<&'static str, CompressionStats>,
+    /// Data blobs decoded
+    pub data_blobs: u64,
+}
+
+impl FileSummary {
+    /// Nodes, ways, relations and changesets
+    pub fn elements(&self) -> u64 {
+        self.nodes + self.ways + self.relations + self.changesets
+    }
+
+    /// The `n` most frequent tag keys, most frequent first, ties by key
+    pub fn top_keys(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut keys: Vec<_> = self.key_counts.iter().map(|(key, count)| (key.as_str(), *count)).collect();
+        keys.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        keys.truncate(n);
+        keys
+    }
+
+    /// Mean decompressed size of a data blob in bytes
+    pub fn average_block_bytes(&self) -> Option<f64> {
+        let raw_bytes: u64 = self.compression.values().map(|stats| stats.raw_bytes).sum();
+        (self.data_blobs > 0).then(|| raw_bytes as f64 / self.data_blobs as f64)
+    }
+
+    /// Mean number of elements in a data blob
+    pub fn average_block_elements(&self) -> Option<f64> {
+        (self.data_blobs > 0).then(|| self.elements() as f64 / self.data_blobs as f64)
+    }
+}
+
+/// Builds a `FileSummary` block by block
+#[derive(Debug, Default)]
+pub(crate) struct SummaryBuilder {
+    summary: FileSummary,
+    users: HashSet<i32>,
+}
+
+impl SummaryBuilder {
+    /// Add a data blob and the block decoded from it
+    pub(crate) fn add(&mut self, blob: &Blob, block: &PrimitiveBlock) {
+        let (name, stored_bytes) = match &blob.data {
+            BlobData::Raw(data) => ("raw", data.len()),
+            BlobData::ZlibData { compressed, .. } => ("zlib", compressed.len()),
+            BlobData::LzmaData { compressed, .. } => ("lzma", compressed.len()),
+            BlobData::Bzip2Data { compressed, .. } => ("bzip2", compressed.len()),
+            BlobData::Lz4Data { compressed, .. } => ("lz4", compressed.len()),
+            BlobData::ZstdData { compressed, .. } => ("zstd", compressed.len()),
+        };
+        let compression = self.summary.compression.entry(name).or_default();
+        compression.blobs += 1;
+        compression.stored_bytes += stored_bytes as u64;
+        compression.raw_bytes += blob.data.raw_size() as u64;
+        self.summary.data_blobs += 1;
+
+        // Keys are counted per string table index, then by string once per block
+        let mut keys = vec![0u64; block.stringtable.s.len()];
+        let mut count_keys = |indices: &mut dyn Iterator<Item = u32>| {
+            for key in indices {
+                if let Some(count) = keys.get_mut(key as usize) {
+                    *count += 1;
+                }
+            }
+        };
+        for group in &block.primitivegroup {
+            for node in &group.nodes {
+                self.add_node(node.id.0, block.node_location(node), node.info.as_ref(), block);
+                count_keys(&mut node.keys.iter().copied());
+            }
+            if let Some(dense) = &group.dense {
+                for node in dense.iter(block) {
+                    self.add_node(node.id.0, node.location, node.info.as_ref(), block);
+                    count_keys(&mut node.tags().map(|(key, _)| key));
+                }
+            }
+            for way in &group.ways {
+                self.summary.ways += 1;
+                widen(&mut self.summary.way_ids, way.id.0);
+                self.add_info(way.info.as_ref(), block);
+                count_keys(&mut way.keys.iter().copied());
+            }
+            for relation in &group.relations {
+                self.summary.relations += 1;
+                widen(&mut self.summary.relation_ids, relation.id.0);
+                self.add_info(relation.info.as_ref(), block);
+                count_keys(&mut relation.keys.iter().copied());
+            }
+            for changeset in &group.changesets {
+                self.summary.changesets += 1;
+                count_keys(&mut changeset.keys.iter().copied());
+            }
+        }
+        for (key, count) in block.stringtable.s.iter().zip(keys) {
+            if count > 0 {
+                *self.summary.key_counts.entry(key.clone()).or_default() += count;
+            }
+        }
+    }
+
+    fn add_node(&mut self, id: i64, location: LatLon, info: Option<&Info>, block: &PrimitiveBlock) {
+        self.summary.nodes += 1;
+        widen(&mut self.summary.node_ids, id);
+        BoundingBox::extend_option(&mut self.summary.bbox, location);
+        self.add_info(info, block);
+    }
+
+    /// Unset (zero) timestamps and user ids aren't counted
+    fn add_info(&mut self, info: Option<&Info>, block: &PrimitiveBlock) {
+        let Some(info) = info else { return };
+        if info.timestamp != 0 {
+            let timestamp = block.info_timestamp(info);
+            self.summary.timestamps = Some(match self.summary.timestamps {
+                Some((first, last)) => (first.min(timestamp), last.max(timestamp)),
+                None => (timestamp, timestamp),
+            });
+        }
+        if info.uid != 0 {
+            self.users.insert(info.uid);
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> FileSummary {
+        self.summary.users = self.users.len() as u64;
+        self.summary
+    }
+}
+
+fn widen(range: &mut Option<(i64, i64)>, id: i64) {
+    *range = Some(match *range {
+        Some((min, max)) => (min.min(id), max.max(id)),
+        None => (id, id),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::header_block::HeaderBlock;
+    use crate::blocks::primitives::prelude::*;
+    use crate::blocks::string_table::StringTable;
+    use crate::io::reader::{OsmElement, Reader};
+    use crate::io::writer::{BlockBuffer, PbfWriter};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use super::*;
+    use crate::blocks::osm_id::WayId;
+
+    fn info(timestamp: i64, uid: i32) -> Option<Info> {
+        Some(Info { version: 1, timestamp, changeset: 1, uid, ..Default::default() })
+    }
+
+    #[test]
+    fn test_summarize() {
+        let strings = StringTable { s: vec![String::new(), "highway".into(), "name".into(), "x".into()] };
+        let node = |id, lat, lon, tagged: bool, info| {
+            let mut node = Node::new(id, LatLon::from_raw(lat, lon));
+            if tagged {
+                node.add_tag(2, 3);
+            }
+            node.info = info;
+            OsmElement::Node(node)
+        };
+        let way = Way { id: WayId(7), keys: vec![1, 2], vals: vec![3, 3], info: info(2_000, 4), refs: vec![1, 1], lat: vec![], lon: vec![] };
+        let blocks = [
+            vec![node(1, 10_000_000, 20_000_000, true, info(1_000, 4)), node(2, -5_000_000, 30_000_000, false, info(3_000, 9))],
+            vec![node(-3, 0, 0, true, None), OsmElement::Way(way)],
+        ];
+
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        for elements in &blocks {
+            let mut block = BlockBuffer::default();
+            for element in elements {
+                block.push(element, &strings);
+            }
+            writer.write_primitive_block(&block.finish()).unwrap();
+        }
+        let summary = Reader::new(Cursor::new(writer.into_inner())).unwrap().summarize().unwrap();
+
+        assert_eq!((summary.nodes, summary.ways, summary.relations, summary.elements()), (3, 1, 0, 4));
+        assert_eq!(summary.top_keys(5), vec![("name", 3), ("highway", 1)]);
+        assert_eq!(summary.top_keys(1), vec![("name", 3)]);
+        let mut bbox = BoundingBox::from_point(LatLon::from_raw(10_000_000, 20_000_000));
+        bbox.extend(LatLon::from_raw(-5_000_000, 30_000_000));
+        bbox.extend(LatLon::from_raw(0, 0));
+        assert_eq!(summary.bbox, Some(bbox));
+        assert_eq!((summary.node_ids, summary.way_ids, summary.relation_ids), (Some((-3, 2)), Some((7, 7)), None));
+        assert_eq!(summary.timestamps, Some((TimestampMillis::from_secs(1_000), TimestampMillis::from_secs(3_000))));
+        assert_eq!(summary.users, 2);
+        assert_eq!(summary.data_blobs, 2);
+        let zlib = summary.compression["zlib"];
+        assert_eq!((summary.compression.len(), zlib.blobs), (1, 2));
+        assert!(zlib.stored_bytes > 0 && zlib.raw_bytes > 0);
+        assert_eq!(summary.average_block_elements(), Some(2.0));
+        assert_eq!(summary.average_block_bytes(), Some(zlib.raw_bytes as f64 / 2.0));
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::io::blob::Result;
+
+/// Default age after which leftover temp directories are removed (one day)
+pub const DEFAULT_GC_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// Where and how long-running jobs (external sorts, spilling writers) keep temp data
+///
+/// Directories are named `<prefix><pid>-<unique>` under `location`. Before a
+/// new directory is created, directories with the same prefix that weren't
+/// modified for `gc_age` are removed, so data left behind by crashed jobs
+/// doesn't accumulate. Jobs that may leave their temp files untouched for
+/// longer than that should raise the age.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempDirPolicy {
+    pub location: PathBuf,
+    pub prefix: String,
+    /// Remove the directory and its contents when the `TempDir` is dropped
+    pub cleanup_on_drop: bool,
+    /// Age of leftovers to remove; `None` disables the collection
+    pub gc_age: Option<Duration>,
+}
+
+impl Default for TempDirPolicy {
+    fn default() -> Self {
+        Self {
+            location: std::env::temp_dir(),
+            prefix: "osm-pbf-".to_string(),
+            cleanup_on_drop: true,
+            gc_age: Some(DEFAULT_GC_AGE),
+        }
+    }
+}
+
+impl TempDirPolicy {
+    /// Policy with temp directories under `location`
+    pub fn with_location(location: impl Into<PathBuf>) -> Self {
+        Self { location: location.into(), ..Default::default() }
+    }
+
+    /// Create a fresh temp directory, collecting old leftovers first
+    pub fn create(&self) -> Result<TempDir> {
+        std::fs::create_dir_all(&self.location)?;
+        self.collect_garbage()?;
+        loop {
+            let unique = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+                ^ NEXT_DIR.fetch_add(1, Ordering::Relaxed);
+            let path = self.location.join(format!("{}{}-{unique:x}", self.prefix, std::process::id()));
+            match std::fs::create_dir(&path) {
+                Ok(()) => return Ok(TempDir { path, cleanup: self.cleanup_on_drop }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Remove directories with this policy's prefix older than `gc_age`
+    ///
+    /// Directories of the current process are kept. Returns the number of
+    /// directories removed; entries that vanish or can't be removed meanwhile
+    /// (e.g. owned by another user) are skipped.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        let Some(gc_age) = self.gc_age else {
+            return Ok(0);
+        };
+        let own_prefix = format!("{}{}-", self.prefix, std::process::id());
+        let entries = match std::fs::read_dir(&self.location) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else { continue };
+            if !name.starts_with(&self.prefix) || name.starts_with(&own_prefix) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else { continue };
+            let age = metadata.modified().ok().and_then(|modified| now.duration_since(modified).ok());
+            if metadata.is_dir() && age.is_some_and(|age| age >= gc_age) && std::fs::remove_dir_all(entry.path()).is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Temp directory created by `TempDirPolicy::create`
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+    cleanup: bool,
+}
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create (or truncate) a file in the directory, open for reading and writing
+    pub fn create_file(&self, name: &str) -> Result<File> {
+        Ok(File::options().read(true).write(true).create(true).truncate(true).open(self.path.join(name))?)
+    }
+
+    /// Keep the directory after drop, e.g. to inspect a failed job
+    pub fn keep(mut self) -> PathBuf {
+        self.cleanup = false;
+        self.path.clone()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if self.cleanup {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    #[test]
+    fn test_cleanup_on_drop_and_keep() {
+        let root = tempfile::tempdir().unwrap();
+        let policy = TempDirPolicy::with_location(root.path());
+
+        let dir = policy.create().unwrap();
+        let path = dir.path().to_path_buf();
+        dir.create_file("chunk-0").unwrap().write_all(b"spill").unwrap();
+        assert!(path.file_name().unwrap().to_str().unwrap().starts_with("osm-pbf-"));
+        drop(dir);
+        assert!(!path.exists());
+
+        let kept = policy.create().unwrap().keep();
+        assert!(kept.exists());
+        let other = policy.create().unwrap();
+        assert!(other.path() != kept);
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let root = tempfile::tempdir().unwrap();
+        let leftover = root.path().join("osm-pbf-999999999-1");
+        let unrelated = root.path().join("other-1");
+        std::fs::create_dir(&leftover).unwrap();
+        std::fs::create_dir(&unrelated).unwrap();
+
+        let mut policy = TempDirPolicy::with_location(root.path());
+        assert_eq!(policy.collect_garbage().unwrap(), 0);
+        policy.gc_age = None;
+        assert_eq!(policy.collect_garbage().unwrap(), 0);
+
+        // Leftovers of this process are kept even when old enough
+        policy.gc_age = Some(Duration::ZERO);
+        let own = policy.create().unwrap();
+        assert!(!leftover.exists());
+        assert!(unrelated.exists());
+        assert_eq!(policy.collect_garbage().unwrap(), 0);
+        assert!(own.path().exists());
+    }
+}
@@ -0,0 +1,478 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use crate::blocks::primitives::block::PrimitiveBlock;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobType, Result};
+use crate::io::codec::{BlockDecoder, BlockEncoder, PbfBlockCodec};
+use crate::io::decode::{blob_payload, read_frame};
+use crate::io::projection::Reinterner;
+use crate::io::writer::PbfWriter;
+use crate::io::zstd_dictionary::ZstdDictionaries;
+
+/// Counters reported by `map_blocks`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransformStats {
+    /// OSMData and OSMChange blobs decoded, passed through the hook and re-encoded
+    pub blocks_transformed: u64,
+    /// Header and unknown blobs copied unchanged
+    pub blobs_copied: u64,
+    /// Bytes read from the input
+    pub bytes_read: u64,
+    /// Bytes written to the output
+    pub bytes_written: u64,
+}
+
+/// Rewrite a PBF stream block by block
+///
+/// Every OSMData and OSMChange blob of `reader` is decoded into a
+/// `PrimitiveBlock`, handed to `f` and re-encoded into `writer` as a blob of
+/// the same type; other blobs are copied with their payload unchanged. Blobs
+/// are written zlib-compressed, as by `PbfWriter`, so zstd dictionaries of the
+/// input are used for reading and left out of the output. Elements are never
+/// materialized, so this is the cheapest way to rewrite string tables,
+/// granularity or metadata in bulk. Blocks are wire-faithful: coordinates are
+/// in granularity units and delta-encoded fields stay delta-encoded.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::map_blocks;
+/// use std::fs::File;
+///
+/// let input = File::open("in.osm.pbf")?;
+/// let output = File::create("out.osm.pbf")?;
+///
+/// // Drop per-node metadata from all dense node groups
+/// map_blocks(input, output, |mut block| {
+///     for group in &mut block.primitivegroup {
+///         if let Some(dense) = &mut group.dense {
+///             dense.denseinfo = None;
+///         }
+///     }
+///     block
+/// })?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn map_blocks<R, W, F>(reader: R, writer: W, f: F) -> Result<TransformStats>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(PrimitiveBlock) -> PrimitiveBlock,
+{
+    map_blocks_with_codecs(reader, writer, &PbfBlockCodec, PbfBlockCodec, f)
+}
+
+/// `map_blocks` reading blocks with `decoder` and writing them with `encoder`
+///
+/// With different codecs this converts a file between block wire formats,
+/// e.g. from PBF to a `BlockEncoder` used for transport and back.
+pub fn map_blocks_with_codecs<R, W, F>(
+    mut reader: R,
+    writer: W,
+    decoder: &dyn BlockDecoder,
+    encoder: impl BlockEncoder + 'static,
+    mut f: F,
+) -> Result<TransformStats>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(PrimitiveBlock) -> PrimitiveBlock,
+{
+    let mut out = PbfWriter::new(writer).with_block_encoder(encoder);
+    let mut stats = TransformStats::default();
+    let mut dictionaries = ZstdDictionaries::default();
+
+    while let Some((mut blob, frame_len)) = read_frame(&mut reader, stats.bytes_read)? {
+        stats.bytes_read += frame_len;
+        dictionaries.observe(&mut blob)?;
+        if matches!(blob.blob_type(), BlobType::ZstdDictionary) {
+            continue;
+        }
+        let payload = blob_payload(&blob)?;
+
+        if blob.blob_type().holds_elements() {
+            let block = f(decoder.decode_block(&payload)?);
+            match blob.blob_type() {
+                BlobType::OSMChange => out.write_change_block(&block)?,
+                _ => out.write_primitive_block(&block)?,
+            }
+            stats.blocks_transformed += 1;
+        } else {
+            out.write_blob(blob.blob_type(), &payload)?;
+            stats.blobs_copied += 1;
+        }
+    }
+
+    out.flush()?;
+    stats.bytes_written = out.bytes_written();
+    Ok(stats)
+}
+
+/// Maps a tag value to its replacement, `None` keeping it
+type ValueMap = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Tag rewrite rules applied to decoded blocks
+///
+/// Rules run in the order they were added, each seeing the tag as left by
+/// the previous ones: a dropped tag is gone for the rules after it, and a
+/// renamed key is matched by its new name. Key patterns are exact keys or
+/// globs with one `*`, e.g. `tiger:*` or `*:source`. An element whose tags
+/// end up with the same key twice keeps the first. Relation roles and user
+/// names are not tags and stay as they are.
+///
+/// Set on a reader with `Reader::set_tag_rewrite`, or copy a file with
+/// `rewrite_tags`.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::TagRewrite;
+///
+/// let rewrite = TagRewrite::new()
+///     .with_dropped_keys(["source", "tiger:*"])
+///     .with_renamed_key("postal_code", "addr:postcode")
+///     .with_value_map("oneway", |value| matches!(value, "true" | "1").then(|| "yes".to_string()));
+///
+/// assert_eq!(rewrite.rewrite("tiger:county", "Ada, ID"), None);
+/// assert_eq!(rewrite.rewrite("postal_code", "83702").unwrap().0, "addr:postcode");
+/// assert_eq!(rewrite.rewrite("oneway", "true").unwrap().1, "yes");
+/// ```
+#[derive(Clone, Default)]
+pub struct TagRewrite {
+    rules: Vec<TagRule>,
+}
+
+#[derive(Clone)]
+enum TagRule {
+    Drop(String),
+    Rename { from: String, to: String },
+    MapValues(String, ValueMap),
+}
+
+impl TagRewrite {
+    /// Rewrite without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop tags whose key matches one of `patterns`
+    pub fn with_dropped_keys<I: IntoIterator<Item = S>, S: Into<String>>(mut self, patterns: I) -> Self {
+        self.rules.extend(patterns.into_iter().map(|pattern| TagRule::Drop(pattern.into())));
+        self
+    }
+
+    /// Rename the key `from` to `to`
+    pub fn with_renamed_key(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.rules.push(TagRule::Rename { from: from.into(), to: to.into() });
+        self
+    }
+
+    /// Replace the values of keys matching `pattern` by what `map` returns;
+    /// `None` keeps the value
+    pub fn with_value_map<F>(mut self, pattern: impl Into<String>, map: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.rules.push(TagRule::MapValues(pattern.into(), Arc::new(map)));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Tag after all rules, `None` once dropped
+    pub fn rewrite<'a>(&'a self, key: &'a str, value: &'a str) -> Option<(Cow<'a, str>, Cow<'a, str>)> {
+        let (mut key, mut value) = (Cow::Borrowed(key), Cow::Borrowed(value));
+        for rule in &self.rules {
+            match rule {
+                TagRule::Drop(pattern) if key_matches(pattern, &key) => return None,
+                TagRule::Rename { from, to } if *key == **from => key = Cow::Borrowed(to.as_str()),
+                TagRule::MapValues(pattern, map) if key_matches(pattern, &key) => {
+                    if let Some(mapped) = map(&value) {
+                        value = Cow::Owned(mapped);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some((key, value))
+    }
+
+    /// Rewrite the tags of every element and rebuild the string table
+    ///
+    /// Each distinct key/value pair of the block goes through the rules
+    /// once. The new table holds only the strings still referenced, as with
+    /// `TagProjection::project_block`.
+    pub fn rewrite_block(&self, block: &mut PrimitiveBlock) {
+        let mut tags = BlockTags { rewrite: self, strings: Reinterner::new(&block.stringtable), pairs: HashMap::new() };
+        for group in &mut block.primitivegroup {
+            for node in &mut group.nodes {
+                tags.rewrite_tags(&mut node.keys, &mut node.vals);
+                tags.strings.remap_info(&mut node.info);
+            }
+            for way in &mut group.ways {
+                tags.rewrite_tags(&mut way.keys, &mut way.vals);
+                tags.strings.remap_info(&mut way.info);
+            }
+            for relation in &mut group.relations {
+                tags.rewrite_tags(&mut relation.keys, &mut relation.vals);
+                tags.strings.remap_info(&mut relation.info);
+                for role in &mut relation.roles_sid {
+                    *role = tags.strings.index(*role as u32) as i32;
+                }
+            }
+            for changeset in &mut group.changesets {
+                tags.rewrite_tags(&mut changeset.keys, &mut changeset.vals);
+                tags.strings.remap_info(&mut changeset.info);
+            }
+            if let Some(dense) = &mut group.dense {
+                tags.rewrite_dense_tags(&mut dense.keys_vals);
+                tags.strings.remap_dense_info(&mut dense.denseinfo);
+            }
+        }
+        block.stringtable = StringTable { s: tags.strings.finish() };
+    }
+}
+
+impl fmt::Debug for TagRewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.rules).finish()
+    }
+}
+
+impl fmt::Debug for TagRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagRule::Drop(pattern) => write!(f, "drop {pattern}"),
+            TagRule::Rename { from, to } => write!(f, "rename {from} -> {to}"),
+            TagRule::MapValues(pattern, _) => write!(f, "map values of {pattern}"),
+        }
+    }
+}
+
+/// Whether `key` is `pattern`, or matches it around its first `*`
+fn key_matches(pattern: &str, key: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => key.len() >= prefix.len() + suffix.len() && key.starts_with(prefix) && key.ends_with(suffix),
+        None => pattern == key,
+    }
+}
+
+/// Rewrites the tags of one block, caching the outcome per index pair
+struct BlockTags<'a> {
+    rewrite: &'a TagRewrite,
+    strings: Reinterner<'a>,
+    pairs: HashMap<(u32, u32), Option<(u32, u32)>>,
+}
+
+impl BlockTags<'_> {
+    /// New indices of a tag, `None` once dropped
+    fn pair(&mut self, key: u32, val: u32) -> Option<(u32, u32)> {
+        if let Some(pair) = self.pairs.get(&(key, val)) {
+            return *pair;
+        }
+        let (old_key, old_val) = (self.strings.old(key), self.strings.old(val));
+        let pair = self.rewrite.rewrite(old_key, old_val).map(|(new_key, new_val)| {
+            let key = if new_key == old_key { self.strings.index(key) } else { self.strings.intern(&new_key) };
+            let val = if new_val == old_val { self.strings.index(val) } else { self.strings.intern(&new_val) };
+            (key, val)
+        });
+        self.pairs.insert((key, val), pair);
+        pair
+    }
+
+    fn rewrite_tags(&mut self, keys: &mut Vec<u32>, vals: &mut Vec<u32>) {
+        let (mut kept_keys, mut kept_vals) = (Vec::with_capacity(keys.len()), Vec::with_capacity(vals.len()));
+        for (key, val) in keys.iter().zip(vals.iter()) {
+            if let Some((key, val)) = self.pair(*key, *val)
+                && !kept_keys.contains(&key)
+            {
+                kept_keys.push(key);
+                kept_vals.push(val);
+            }
+        }
+        (*keys, *vals) = (kept_keys, kept_vals);
+    }
+
+    /// Dense tags are `key, val` pairs with each node's list ended by a 0
+    fn rewrite_dense_tags(&mut self, keys_vals: &mut Vec<i32>) {
+        let mut kept = Vec::with_capacity(keys_vals.len());
+        let (mut cursor, mut node_start) = (0, 0);
+        while let Some(&key) = keys_vals.get(cursor) {
+            if key == 0 {
+                kept.push(0);
+                node_start = kept.len();
+                cursor += 1;
+                continue;
+            }
+            let val = keys_vals.get(cursor + 1).copied().unwrap_or(0);
+            if let Some((key, val)) = self.pair(key as u32, val as u32)
+                && !kept[node_start..].chunks_exact(2).any(|pair| pair[0] == key as i32)
+            {
+                kept.push(key as i32);
+                kept.push(val as i32);
+            }
+            cursor += 2;
+        }
+        *keys_vals = kept;
+    }
+}
+
+/// Decodes blocks with another decoder, then rewrites their tags
+pub(crate) struct RewritingDecoder {
+    pub(crate) inner: Arc<dyn BlockDecoder>,
+    pub(crate) rewrite: TagRewrite,
+}
+
+impl BlockDecoder for RewritingDecoder {
+    fn decode_block(&self, message: &[u8]) -> Result<PrimitiveBlock> {
+        let mut block = self.inner.decode_block(message)?;
+        self.rewrite.rewrite_block(&mut block);
+        Ok(block)
+    }
+}
+
+/// Copy a PBF stream with its tags rewritten by `rewrite`
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{rewrite_tags, TagRewrite};
+/// use std::fs::File;
+///
+/// let rewrite = TagRewrite::new().with_dropped_keys(["source", "tiger:*"]);
+/// rewrite_tags(File::open("in.osm.pbf")?, File::create("clean.osm.pbf")?, &rewrite)?;
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn rewrite_tags<R: Read, W: Write>(reader: R, writer: W, rewrite: &TagRewrite) -> Result<TransformStats> {
+    map_blocks(reader, writer, |mut block| {
+        rewrite.rewrite_block(&mut block);
+        block
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::WayId;
+    use crate::synthetic::PlanetBuilder;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_identity_transform_is_lossless() {
+        let mut input = Vec::new();
+        let planet = PlanetBuilder::new(11).grid_size(40).block_size(500).write_to(&mut input).unwrap();
+
+        let mut output = Vec::new();
+        let stats = map_blocks(input.as_slice(), &mut output, |block| block).unwrap();
+
+        assert_eq!(output, input);
+        assert_eq!(stats.blocks_transformed + stats.blobs_copied, planet.blobs);
+        assert_eq!(stats.blobs_copied, 1);
+        assert_eq!(stats.bytes_read, input.len() as u64);
+        assert_eq!(stats.bytes_written, output.len() as u64);
+    }
+
+    #[test]
+    fn test_rewrites_blocks() {
+        let mut input = Vec::new();
+        PlanetBuilder::new(5).grid_size(10).write_to(&mut input).unwrap();
+
+        let mut output = Vec::new();
+        map_blocks(input.as_slice(), &mut output, |mut block| {
+            block.date_granularity = 60_000;
+            block
+        })
+        .unwrap();
+
+        let mut seen = 0;
+        map_blocks(output.as_slice(), std::io::sink(), |block| {
+            assert_eq!(block.date_granularity, 60_000);
+            seen += 1;
+            block
+        })
+        .unwrap();
+        assert!(seen > 0);
+    }
+
+    #[test]
+    fn test_rewrite_block() {
+        use crate::blocks::primitives::prelude::*;
+        let table = |strings: &[&str]| StringTable { s: strings.iter().map(|s| s.to_string()).collect() };
+        let rewrite = TagRewrite::new()
+            .with_dropped_keys(["source", "tiger:*"])
+            .with_renamed_key("highway_type", "highway")
+            .with_value_map("oneway", |value| matches!(value, "true" | "1").then(|| "yes".to_string()));
+
+        let mut group = PrimitiveGroup::default();
+        group.ways.push(Way {
+            id: WayId(1),
+            keys: vec![1, 3, 5, 7],
+            vals: vec![2, 4, 6, 8],
+            info: Some(Info { user_sid: 9, ..Default::default() }),
+            refs: vec![1, 1],
+            lat: vec![],
+            lon: vec![],
+        });
+        // The renamed key collides with a highway tag already there
+        group.ways.push(Way { id: WayId(2), keys: vec![10, 3], vals: vec![2, 2], info: None, refs: vec![1], lat: vec![], lon: vec![] });
+        let mut dense_group = PrimitiveGroup::default();
+        let mut dense = DenseNodes { id: vec![10, 1], lat: vec![0; 2], lon: vec![0; 2], ..Default::default() };
+        dense.keys_vals = vec![1, 2, 5, 6, 0, 5, 8, 0];
+        dense_group.dense = Some(dense);
+
+        let mut block = PrimitiveBlock {
+            stringtable: table(&["", "source", "bing", "highway_type", "primary", "tiger:cfcc", "A41", "oneway", "true", "alice", "highway"]),
+            primitivegroup: vec![group, dense_group],
+            ..Default::default()
+        };
+        rewrite.rewrite_block(&mut block);
+
+        assert_eq!(block.stringtable, table(&["", "highway", "primary", "oneway", "yes", "alice", "bing"]));
+        let ways = &block.primitivegroup[0].ways;
+        assert_eq!((ways[0].keys.clone(), ways[0].vals.clone()), (vec![1, 3], vec![2, 4]));
+        assert_eq!(ways[0].info.as_ref().unwrap().user_sid, 5);
+        assert_eq!((ways[1].keys.clone(), ways[1].vals.clone()), (vec![1], vec![6]));
+        assert_eq!(block.primitivegroup[1].dense.as_ref().unwrap().keys_vals, vec![0, 0]);
+    }
+
+    #[test]
+    fn test_rewrite_tags_and_reader() {
+        use crate::io::indexed_reader::ElementFilter;
+        use crate::io::reader::{OsmElement, Reader};
+        let mut input = Vec::new();
+        let planet = PlanetBuilder::new(2).grid_size(20).block_size(100).relation_count(2).write_to(&mut input).unwrap();
+        let rewrite = TagRewrite::new().with_dropped_keys(["name", "ref"]).with_renamed_key("highway", "road");
+
+        let keys = |reader: &mut Reader<std::io::Cursor<Vec<u8>>>| {
+            let mut keys = std::collections::BTreeSet::new();
+            reader.for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| {
+                let tags: Vec<(u32, u32)> = match element {
+                    OsmElement::Way(way) => way.keys.iter().copied().zip(way.vals.iter().copied()).collect(),
+                    OsmElement::Relation(relation) => relation.keys.iter().copied().zip(relation.vals.iter().copied()).collect(),
+                    _ => Vec::new(),
+                };
+                keys.extend(tags.into_iter().map(|(key, _)| strings.s[key as usize].clone()));
+                Ok(())
+            })
+            .unwrap();
+            keys.into_iter().collect::<Vec<_>>()
+        };
+        let mut reader = Reader::new(std::io::Cursor::new(input.clone())).unwrap();
+        reader.set_tag_rewrite(rewrite.clone());
+        assert_eq!(keys(&mut reader), ["road", "route", "type"]);
+
+        let mut output = Vec::new();
+        let stats = rewrite_tags(input.as_slice(), &mut output, &rewrite).unwrap();
+        assert_eq!(stats.blocks_transformed + stats.blobs_copied, planet.blobs);
+        assert!(output.len() < input.len());
+        assert_eq!(keys(&mut Reader::new(std::io::Cursor::new(output)).unwrap()), ["road", "route", "type"]);
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        let garbage = [0u8, 0, 0, 3, 0xff, 0xff, 0xff];
+        assert!(map_blocks(&garbage[..], std::io::sink(), |block| block).is_err());
+    }
+}
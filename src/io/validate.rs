@@ -0,0 +1,295 @@
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobError, Result};
+
+/// Longest tag key, tag value or role OSM accepts, in characters
+pub const MAX_STRING_CHARS: usize = 255;
+
+/// How `PbfWriter` handles tag keys, values and roles longer than `MAX_STRING_CHARS`
+///
+/// String tables hold Rust strings, so they're always valid UTF-8; string
+/// indices that point outside the table are rejected under every policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringPolicy {
+    /// Fail with `BlobError::InvalidString` naming the element and key
+    #[default]
+    Reject,
+    /// Cut every over-long string in the block's string table to
+    /// `MAX_STRING_CHARS` characters
+    Truncate,
+    /// Drop tags with an over-long key or value and clear over-long roles
+    Skip,
+}
+
+/// How readers handle primitive groups holding more than one element kind
+///
+/// The spec requires each group to hold a single kind (nodes, dense nodes,
+/// ways, relations or changesets), but some writers produce mixed groups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupPolicy {
+    /// Fail with `BlobError::MixedGroup` naming the group index
+    Strict,
+    /// Decode every non-empty collection of the group, count it in
+    /// `ProcessingStats::mixed_groups` and log a warning
+    #[default]
+    Lenient,
+}
+
+/// How the sequential readers handle blobs that are read but don't decode,
+/// e.g. truncated or failing decompression
+///
+/// Blobs that can't be read at all are always skipped. Skipped blobs are
+/// listed in `ProcessingStats::corrupt_blobs` either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CorruptBlobPolicy {
+    /// Fail the run with the decoding error
+    #[default]
+    Fail,
+    /// Skip the blob, record it in `ProcessingStats::corrupt_blobs` and go on
+    /// with the next one
+    Skip,
+}
+
+/// Apply `policy` to the strings referenced by the block's elements
+///
+/// Returns the cleaned block, or `None` if it needs no changes.
+pub(crate) fn apply_string_policy(block: &PrimitiveBlock, policy: StringPolicy) -> Result<Option<PrimitiveBlock>> {
+    let strings = &block.stringtable;
+    let mut violation = None;
+    for_each_string(block, |element, key, index| {
+        if violation.is_some() {
+            return Ok(());
+        }
+        let s = resolve(strings, element, key, index)?;
+        if s.chars().count() > MAX_STRING_CHARS {
+            violation = Some((element, key.to_string(), s.chars().count()));
+        }
+        Ok(())
+    })?;
+
+    let Some((element, key, chars)) = violation else {
+        return Ok(None);
+    };
+    match policy {
+        StringPolicy::Reject => Err(BlobError::InvalidString {
+            element: element.to_string(),
+            key,
+            reason: format!("{chars} characters (max: {MAX_STRING_CHARS})"),
+        }),
+        StringPolicy::Truncate => {
+            let mut block = block.clone();
+            for s in block.stringtable.s.iter_mut() {
+                if let Some((cut, _)) = s.char_indices().nth(MAX_STRING_CHARS) {
+                    s.truncate(cut);
+                }
+            }
+            Ok(Some(block))
+        }
+        StringPolicy::Skip => Ok(Some(skip_long_strings(block))),
+    }
+}
+
+/// Element and key descriptions used in errors, e.g. `("way 42", "name")`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ElementRef {
+    kind: &'static str,
+    id: i64,
+}
+
+impl std::fmt::Display for ElementRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind, self.id)
+    }
+}
+
+fn resolve<'a>(strings: &'a StringTable, element: ElementRef, key: &str, index: usize) -> Result<&'a str> {
+    strings.get_string(index).ok_or_else(|| BlobError::InvalidString {
+        element: element.to_string(),
+        key: key.to_string(),
+        reason: format!("string index {index} out of range (table has {})", strings.len()),
+    })
+}
+
+/// Visit every tag key, tag value and role index with its element and key name
+fn for_each_string<F>(block: &PrimitiveBlock, mut f: F) -> Result<()>
+where
+    F: FnMut(ElementRef, &str, usize) -> Result<()>,
+{
+    let strings = &block.stringtable;
+    let tags = |element: ElementRef, keys: &[u32], vals: &[u32], f: &mut F| -> Result<()> {
+        for (key, val) in keys.iter().zip(vals) {
+            f(element, "<key>", *key as usize)?;
+            let name = resolve(strings, element, "<key>", *key as usize)?;
+            f(element, name, *val as usize)?;
+        }
+        Ok(())
+    };
+
+    for group in &block.primitivegroup {
+        for node in &group.nodes {
+            tags(ElementRef { kind: "node", id: node.id.0 }, &node.keys, &node.vals, &mut f)?;
+        }
+        if let Some(dense) = &group.dense {
+            let mut id = 0i64;
+            let mut pairs = dense.keys_vals.iter();
+            for delta in &dense.id {
+                id = id.wrapping_add(*delta);
+                let element = ElementRef { kind: "node", id };
+                while let Some(&key) = pairs.next() {
+                    if key == 0 {
+                        break;
+                    }
+                    let val = pairs.next().copied().unwrap_or(0);
+                    tags(element, &[key as u32], &[val as u32], &mut f)?;
+                }
+            }
+        }
+        for way in &group.ways {
+            tags(ElementRef { kind: "way", id: way.id.0 }, &way.keys, &way.vals, &mut f)?;
+        }
+        for relation in &group.relations {
+            let element = ElementRef { kind: "relation", id: relation.id.0 };
+            tags(element, &relation.keys, &relation.vals, &mut f)?;
+            for (i, role) in relation.roles_sid.iter().enumerate() {
+                f(element, &format!("<role of member {i}>"), *role as usize)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn skip_long_strings(block: &PrimitiveBlock) -> PrimitiveBlock {
+    let mut block = block.clone();
+    let too_long: Vec<bool> = block.stringtable.s.iter().map(|s| s.chars().count() > MAX_STRING_CHARS).collect();
+    let long = |index: u32| too_long.get(index as usize).copied().unwrap_or(false);
+
+    let retain_tags = |keys: &mut Vec<u32>, vals: &mut Vec<u32>| {
+        let (kept_keys, kept_vals) = keys.iter().zip(vals.iter())
+            .filter(|(k, v)| !long(**k) && !long(**v))
+            .map(|(k, v)| (*k, *v))
+            .unzip();
+        *keys = kept_keys;
+        *vals = kept_vals;
+    };
+
+    for group in &mut block.primitivegroup {
+        for node in &mut group.nodes {
+            retain_tags(&mut node.keys, &mut node.vals);
+        }
+        if let Some(dense) = &mut group.dense {
+            let mut kept = Vec::with_capacity(dense.keys_vals.len());
+            let mut pairs = dense.keys_vals.iter();
+            while let Some(&key) = pairs.next() {
+                if key == 0 {
+                    kept.push(0);
+                    continue;
+                }
+                let val = pairs.next().copied().unwrap_or(0);
+                if !long(key as u32) && !long(val as u32) {
+                    kept.extend([key, val]);
+                }
+            }
+            dense.keys_vals = kept;
+        }
+        for way in &mut group.ways {
+            retain_tags(&mut way.keys, &mut way.vals);
+        }
+        for relation in &mut group.relations {
+            retain_tags(&mut relation.keys, &mut relation.vals);
+            for role in &mut relation.roles_sid {
+                if long(*role as u32) {
+                    *role = 0;
+                }
+            }
+        }
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::{RelationId, WayId};
+    use pretty_assertions::assert_eq;
+
+    fn block_with_way(key: &str, value: &str) -> PrimitiveBlock {
+        let mut block = PrimitiveBlock::default();
+        let name = block.stringtable.add_string("name".to_string()) as u32;
+        let short = block.stringtable.add_string("Main Street".to_string()) as u32;
+        let k = block.stringtable.add_string(key.to_string()) as u32;
+        let v = block.stringtable.add_string(value.to_string()) as u32;
+        block.primitivegroup.push(PrimitiveGroup {
+            ways: vec![Way { id: WayId(42), keys: vec![name, k], vals: vec![short, v], info: None, refs: vec![], lat: vec![], lon: vec![] }],
+            ..Default::default()
+        });
+        block
+    }
+
+    #[test]
+    fn test_valid_block_needs_no_changes() {
+        let block = block_with_way("highway", &"é".repeat(MAX_STRING_CHARS));
+        for policy in [StringPolicy::Reject, StringPolicy::Truncate, StringPolicy::Skip] {
+            assert_eq!(apply_string_policy(&block, policy).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_reject_names_element_and_key() {
+        let block = block_with_way("description", &"x".repeat(300));
+        match apply_string_policy(&block, StringPolicy::Reject) {
+            Err(BlobError::InvalidString { element, key, reason }) => {
+                assert_eq!((element.as_str(), key.as_str()), ("way 42", "description"));
+                assert!(reason.starts_with("300 characters"));
+            }
+            other => panic!("expected InvalidString, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_truncate_and_skip() {
+        let block = block_with_way("description", &"ü".repeat(300));
+
+        let truncated = apply_string_policy(&block, StringPolicy::Truncate).unwrap().unwrap();
+        assert_eq!(truncated.stringtable.s[4].chars().count(), MAX_STRING_CHARS);
+
+        let skipped = apply_string_policy(&block, StringPolicy::Skip).unwrap().unwrap();
+        let way = &skipped.primitivegroup[0].ways[0];
+        assert_eq!((way.keys.clone(), way.vals.clone()), (vec![1], vec![2]));
+    }
+
+    #[test]
+    fn test_out_of_range_index_always_rejected() {
+        let mut block = block_with_way("highway", "primary");
+        block.primitivegroup[0].ways[0].vals[1] = 99;
+        assert!(matches!(apply_string_policy(&block, StringPolicy::Skip), Err(BlobError::InvalidString { .. })));
+    }
+
+    #[test]
+    fn test_dense_and_roles() {
+        let mut block = PrimitiveBlock::default();
+        let long = block.stringtable.add_string("r".repeat(256)) as i32;
+        let key = block.stringtable.add_string("amenity".to_string()) as i32;
+        block.primitivegroup.push(PrimitiveGroup {
+            dense: Some(DenseNodes { id: vec![5, 2], keys_vals: vec![0, key, long, 0], ..Default::default() }),
+            relations: vec![Relation {
+                id: RelationId(3),
+                keys: vec![],
+                vals: vec![],
+                info: None,
+                roles_sid: vec![long],
+                memids: vec![5],
+                types: vec![MemberType::Node],
+            }],
+            ..Default::default()
+        });
+
+        match apply_string_policy(&block, StringPolicy::Reject) {
+            Err(BlobError::InvalidString { element, key, .. }) => assert_eq!((element.as_str(), key.as_str()), ("node 7", "amenity")),
+            other => panic!("expected InvalidString, got {other:?}"),
+        }
+
+        let skipped = apply_string_policy(&block, StringPolicy::Skip).unwrap().unwrap();
+        assert_eq!(skipped.primitivegroup[0].dense.as_ref().unwrap().keys_vals, vec![0, 0]);
+        assert_eq!(skipped.primitivegroup[0].relations[0].roles_sid, vec![0]);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::io::blob::{BlobError, Result};
+
+/// Protobuf wire type for varint-encoded scalars
+pub const WIRE_VARINT: u32 = 0;
+/// Protobuf wire type for 64-bit fixed-width scalars
+pub const WIRE_FIXED64: u32 = 1;
+/// Protobuf wire type for length-delimited fields (bytes, strings, messages, packed)
+pub const WIRE_LEN: u32 = 2;
+/// Protobuf wire type for 32-bit fixed-width scalars
+pub const WIRE_FIXED32: u32 = 5;
+
+/// Zigzag-encode a signed 64-bit integer (sint64)
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Zigzag-decode a signed 64-bit integer (sint64)
+pub fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Append a value as a varint: 7 bits per byte, low bits first
+pub fn encode_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Read a varint from the start of `buf`, returning it and the bytes it took
+pub fn decode_varint(buf: &[u8]) -> Result<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, shift) in (0..64).step_by(7).enumerate() {
+        let byte = *buf.get(index).ok_or_else(|| BlobError::InvalidFormat("Truncated varint".to_string()))?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+    Err(BlobError::InvalidFormat("Varint longer than 10 bytes".to_string()))
+}
+
+/// Append-only buffer for encoding a protobuf message
+///
+/// Supports the subset of the protobuf wire format used by the OSM PBF schema:
+/// varints, zigzag-encoded signed integers, length-delimited fields and packed
+/// repeated scalars.
+#[derive(Debug, Clone, Default)]
+pub struct WireWriter {
+    buf: Vec<u8>,
+}
+
+impl WireWriter {
+    /// Create an empty message buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if nothing has been encoded yet
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Finish and return the encoded bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Write a raw varint
+    pub fn write_varint(&mut self, value: u64) {
+        encode_varint(value, &mut self.buf);
+    }
+
+    fn write_key(&mut self, field: u32, wire_type: u32) {
+        self.write_varint(((field << 3) | wire_type) as u64);
+    }
+
+    /// Write an unsigned varint field (uint32/uint64)
+    pub fn uint64(&mut self, field: u32, value: u64) {
+        self.write_key(field, WIRE_VARINT);
+        self.write_varint(value);
+    }
+
+    /// Write a signed varint field (int32/int64, two's complement)
+    pub fn int64(&mut self, field: u32, value: i64) {
+        self.uint64(field, value as u64);
+    }
+
+    /// Write a zigzag-encoded field (sint32/sint64)
+    pub fn sint64(&mut self, field: u32, value: i64) {
+        self.uint64(field, zigzag_encode(value));
+    }
+
+    /// Write a bool field
+    pub fn bool(&mut self, field: u32, value: bool) {
+        self.uint64(field, value as u64);
+    }
+
+    /// Write a bytes field
+    pub fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.write_key(field, WIRE_LEN);
+        self.write_varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    /// Write a string field
+    pub fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    /// Write an embedded message field
+    pub fn message(&mut self, field: u32, message: &WireWriter) {
+        self.bytes(field, &message.buf);
+    }
+
+    /// Write a packed repeated unsigned varint field (skipped when empty)
+    pub fn packed_uint64<I: IntoIterator<Item = u64>>(&mut self, field: u32, values: I) {
+        let mut packed = WireWriter::new();
+        for value in values {
+            packed.write_varint(value);
+        }
+        if !packed.is_empty() {
+            self.message(field, &packed);
+        }
+    }
+
+    /// Write a packed repeated int32/int64 field (skipped when empty)
+    pub fn packed_int64<I: IntoIterator<Item = i64>>(&mut self, field: u32, values: I) {
+        self.packed_uint64(field, values.into_iter().map(|v| v as u64));
+    }
+
+    /// Write a packed repeated sint32/sint64 field (skipped when empty)
+    pub fn packed_sint64<I: IntoIterator<Item = i64>>(&mut self, field: u32, values: I) {
+        self.packed_uint64(field, values.into_iter().map(zigzag_encode));
+    }
+}
+
+/// Value of a single decoded field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireValue<'a> {
+    /// Varint scalar (not yet zigzag-decoded)
+    Varint(u64),
+    /// Length-delimited payload: bytes, string, embedded message or packed values
+    Len(&'a [u8]),
+    /// Fixed-width scalar (fixed32/fixed64/float/double), kept as raw bits
+    Fixed(u64),
+}
+
+impl<'a> WireValue<'a> {
+    /// Varint scalar as u64
+    pub fn as_u64(&self) -> Result<u64> {
+        match self {
+            WireValue::Varint(v) => Ok(*v),
+            other => Err(unexpected("varint", other)),
+        }
+    }
+
+    /// Varint scalar as two's complement int64/int32
+    pub fn as_i64(&self) -> Result<i64> {
+        self.as_u64().map(|v| v as i64)
+    }
+
+    /// Zigzag-encoded sint64/sint32
+    pub fn as_sint64(&self) -> Result<i64> {
+        self.as_u64().map(zigzag_decode)
+    }
+
+    /// Length-delimited payload
+    pub fn as_bytes(&self) -> Result<&'a [u8]> {
+        match self {
+            WireValue::Len(bytes) => Ok(bytes),
+            other => Err(unexpected("length-delimited", other)),
+        }
+    }
+
+    /// Length-delimited payload as UTF-8
+    pub fn as_str(&self) -> Result<&'a str> {
+        std::str::from_utf8(self.as_bytes()?)
+            .map_err(|e| BlobError::InvalidFormat(format!("Invalid UTF-8 string: {e}")))
+    }
+
+    /// Repeated varint field, accepting both packed and unpacked encodings
+    pub fn read_varints(&self, out: &mut Vec<u64>) -> Result<()> {
+        match self {
+            WireValue::Varint(v) => out.push(*v),
+            WireValue::Len(bytes) => {
+                let mut reader = WireReader::new(bytes);
+                while !reader.is_empty() {
+                    out.push(reader.read_varint()?);
+                }
+            }
+            other => return Err(unexpected("varint or packed", other)),
+        }
+        Ok(())
+    }
+
+    /// Repeated int32/int64 field
+    pub fn read_i64s(&self, out: &mut Vec<i64>) -> Result<()> {
+        self.read_mapped(out, |v| v as i64)
+    }
+
+    /// Repeated sint32/sint64 field
+    pub fn read_sint64s(&self, out: &mut Vec<i64>) -> Result<()> {
+        self.read_mapped(out, zigzag_decode)
+    }
+
+    fn read_mapped<T>(&self, out: &mut Vec<T>, map: impl Fn(u64) -> T) -> Result<()> {
+        let mut raw = Vec::new();
+        self.read_varints(&mut raw)?;
+        out.extend(raw.into_iter().map(map));
+        Ok(())
+    }
+}
+
+fn unexpected(expected: &str, got: &WireValue) -> BlobError {
+    BlobError::InvalidFormat(format!("Expected {expected} field, found {got:?}"))
+}
+
+/// Cursor over an encoded protobuf message
+#[derive(Debug, Clone)]
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    /// Start reading `buf` from the beginning
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Returns true once the whole buffer has been consumed
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    /// Read a raw varint
+    pub fn read_varint(&mut self) -> Result<u64> {
+        let (value, len) = decode_varint(self.buf.get(self.pos..).unwrap_or_default())?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| BlobError::InvalidFormat("Truncated field".to_string()))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Read the next field, or `None` at the end of the message
+    pub fn next_field(&mut self) -> Result<Option<(u32, WireValue<'a>)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.read_varint()?;
+        let field = (key >> 3) as u32;
+        let value = match (key & 0x7) as u32 {
+            WIRE_VARINT => WireValue::Varint(self.read_varint()?),
+            WIRE_FIXED64 => WireValue::Fixed(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            WIRE_LEN => {
+                let len = self.read_varint()? as usize;
+                WireValue::Len(self.take(len)?)
+            }
+            WIRE_FIXED32 => WireValue::Fixed(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as u64),
+            other => {
+                return Err(BlobError::InvalidFormat(format!("Unsupported wire type {other} for field {field}")));
+            }
+        };
+        Ok(Some((field, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_zigzag() {
+        for value in [0, 1, -1, 2, -2, i64::MAX, i64::MIN, 123_456_789, -987_654_321] {
+            assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+        }
+        assert_eq!(zigzag_encode(0), 0);
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+        assert_eq!(zigzag_encode(i64::MIN), u64::MAX);
+    }
+
+    #[test]
+    fn test_varint_encoding() {
+        let mut w = WireWriter::new();
+        w.write_varint(300);
+        assert_eq!(w.into_bytes(), vec![0xac, 0x02]);
+
+        let mut w = WireWriter::new();
+        w.write_varint(u64::MAX);
+        assert_eq!(w.into_bytes().len(), 10);
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        // Both sides of every 7-bit boundary, then zigzag-encoded signed values
+        let mut values = vec![0, u64::MAX];
+        for bits in (7..64).step_by(7) {
+            values.extend([(1u64 << bits) - 1, 1u64 << bits]);
+        }
+        values.extend([0i64, -1, 1, -64, 64, i32::MIN as i64, i64::MIN, i64::MAX].map(zigzag_encode));
+        for value in values {
+            let mut buf = Vec::new();
+            encode_varint(value, &mut buf);
+            let expected_len = (64 - value.leading_zeros() as usize).div_ceil(7).max(1);
+            assert_eq!(buf.len(), expected_len, "{value}");
+            buf.push(0xff);
+            assert_eq!(decode_varint(&buf).unwrap(), (value, expected_len));
+            assert!(decode_varint(&buf[..expected_len - 1]).is_err());
+        }
+        assert!(decode_varint(&[0x80; 11]).is_err());
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut w = WireWriter::new();
+        w.uint64(1, 150);
+        w.string(2, "hi");
+        w.sint64(3, -1);
+        assert_eq!(w.into_bytes(), vec![0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i', 0x18, 0x01]);
+    }
+
+    #[test]
+    fn test_packed_skips_empty() {
+        let mut w = WireWriter::new();
+        w.packed_sint64(1, Vec::new());
+        assert!(w.is_empty());
+
+        w.packed_sint64(1, vec![1, -1]);
+        assert_eq!(w.into_bytes(), vec![0x0a, 0x02, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_reader_round_trip() {
+        let mut w = WireWriter::new();
+        w.uint64(1, 150);
+        w.string(2, "hi");
+        w.packed_sint64(3, vec![-5, 7]);
+        w.sint64(3, 9); // unpacked occurrence of a repeated field
+        let bytes = w.into_bytes();
+
+        let mut reader = WireReader::new(&bytes);
+        let mut values = Vec::new();
+        while let Some((field, value)) = reader.next_field().unwrap() {
+            match field {
+                1 => assert_eq!(value.as_u64().unwrap(), 150),
+                2 => assert_eq!(value.as_str().unwrap(), "hi"),
+                3 => value.read_sint64s(&mut values).unwrap(),
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(values, vec![-5, 7, 9]);
+    }
+
+    #[test]
+    fn test_reader_rejects_truncated_input() {
+        let mut reader = WireReader::new(&[0x0a, 0x05, b'a']);
+        assert!(reader.next_field().is_err());
+
+        let mut reader = WireReader::new(&[0x08, 0x80]);
+        assert!(reader.next_field().is_err());
+    }
+}
@@ -0,0 +1,1046 @@
+use std::io::{Sink, Write};
+use std::sync::Arc;
+use flate2::write::ZlibEncoder;
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::lat_lon::LatLon;
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::{StringTable, StringTableBuilder};
+use crate::io::blob::{BlobError, BlobType, Result, MAX_BLOB_HEADER_SIZE, MAX_BLOB_MESSAGE_SIZE};
+use crate::io::codec::{BlockEncoder, PbfBlockCodec};
+use crate::io::decode::decode_blob_header;
+use crate::io::reader::OsmElement;
+use crate::io::delta::check_block_deltas;
+use crate::io::validate::{apply_string_policy, StringPolicy};
+use crate::io::wire::WireWriter;
+#[cfg(feature = "zstd-write")]
+use crate::io::zstd_dictionary::ZstdDictionary;
+
+/// Sequential writer producing OSM PBF files
+///
+/// Frames each block as `[u32 BE header length][BlobHeader][Blob]`, as defined by
+/// the PBF spec. Blobs are zlib-compressed unless another `BlobCompression` is
+/// set with `with_compression`.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{HeaderBlock, PbfWriter, PrimitiveBlock};
+/// use std::fs::File;
+///
+/// let mut writer = PbfWriter::new(File::create("out.osm.pbf")?);
+/// writer.write_header(&HeaderBlock::default())?;
+/// writer.write_primitive_block(&PrimitiveBlock::default())?;
+/// writer.flush()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct PbfWriter<W: Write> {
+    writer: RawBlobWriter<W>,
+    compression: BlobCompression,
+    string_policy: StringPolicy,
+    date_granularity: Option<i32>,
+    block_encoder: Arc<dyn BlockEncoder>,
+    stats: WriterStats,
+    nonstandard: bool,
+    #[cfg(feature = "zstd-write")]
+    zstd_dictionary: Option<ZstdDictionary>,
+    #[cfg(feature = "zstd-write")]
+    zstd_dictionary_written: bool,
+}
+
+/// How a `PbfWriter` stores blob payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobCompression {
+    /// Uncompressed, for fast local intermediates
+    Raw,
+    /// zlib at the given level, 0 to 9, as written by most PBF tools
+    Zlib(u32),
+    /// zstd at the given level, 1 to 22 (written with the `zstd-write` feature)
+    #[cfg(feature = "zstd-write")]
+    Zstd(i32),
+}
+
+impl Default for BlobCompression {
+    fn default() -> Self {
+        BlobCompression::Zlib(6)
+    }
+}
+
+/// What a `PbfWriter` has written so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriterStats {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    pub changesets: u64,
+    /// Blobs written, the OSMHeader blob included
+    pub blobs: u64,
+    /// Encoded block messages, before compression
+    pub uncompressed_bytes: u64,
+    /// Blob payloads as stored, i.e. after compression
+    pub compressed_bytes: u64,
+    /// Bytes written, i.e. the size of the file if it ended now
+    pub file_bytes: u64,
+}
+
+impl WriterStats {
+    /// Elements of all types
+    pub fn elements(&self) -> u64 {
+        self.nodes + self.ways + self.relations + self.changesets
+    }
+
+    /// Stored size relative to the encoded size, below 1.0 when compression
+    /// pays off and 1.0 for raw blobs
+    pub fn compression_ratio(&self) -> f64 {
+        if self.uncompressed_bytes == 0 {
+            1.0
+        } else {
+            self.compressed_bytes as f64 / self.uncompressed_bytes as f64
+        }
+    }
+
+    fn count_block(&mut self, block: &PrimitiveBlock) {
+        for group in &block.primitivegroup {
+            self.nodes += (group.nodes.len() + group.dense.as_ref().map_or(0, |dense| dense.id.len())) as u64;
+            self.ways += group.ways.len() as u64;
+            self.relations += group.relations.len() as u64;
+            self.changesets += group.changesets.len() as u64;
+        }
+    }
+}
+
+impl<W: Write> PbfWriter<W> {
+    /// Create a writer over any `Write` sink
+    pub fn new(writer: W) -> Self {
+        Self::with_string_policy(writer, StringPolicy::default())
+    }
+
+    /// Create a writer handling over-long tag keys, values and roles according
+    /// to `string_policy` (rejected by default)
+    pub fn with_string_policy(writer: W, string_policy: StringPolicy) -> Self {
+        Self {
+            writer: RawBlobWriter::new(writer),
+            compression: BlobCompression::default(),
+            string_policy,
+            date_granularity: None,
+            block_encoder: Arc::new(PbfBlockCodec),
+            stats: WriterStats::default(),
+            nonstandard: false,
+            #[cfg(feature = "zstd-write")]
+            zstd_dictionary: None,
+            #[cfg(feature = "zstd-write")]
+            zstd_dictionary_written: false,
+        }
+    }
+
+    /// Store blob payloads with `compression` instead of zlib at level 6
+    pub fn with_compression(mut self, compression: BlobCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Store timestamps with the given date granularity in milliseconds
+    ///
+    /// Blocks with a different granularity have their timestamps rounded to
+    /// the nearest multiple of it before writing. By default each block keeps
+    /// its own granularity.
+    pub fn with_date_granularity(mut self, date_granularity: i32) -> Self {
+        self.date_granularity = Some(date_granularity);
+        self
+    }
+
+    /// Encode data blocks with `encoder` instead of as PBF PrimitiveBlock messages
+    ///
+    /// Framing and the header blob stay PBF; read such files back with
+    /// `Reader::set_block_decoder`.
+    pub fn with_block_encoder(mut self, encoder: impl BlockEncoder + 'static) -> Self {
+        self.block_encoder = Arc::new(encoder);
+        self
+    }
+
+    /// Allow output that mainstream PBF tools can't read, such as blobs
+    /// compressed with a zstd dictionary (see `with_zstd_dictionary`)
+    pub fn with_nonstandard(mut self, nonstandard: bool) -> Self {
+        self.nonstandard = nonstandard;
+        self
+    }
+
+    /// Compress data blobs with a zstd dictionary shared across the file
+    /// (nonstandard)
+    ///
+    /// Applies with `BlobCompression::Zstd`. The dictionary is written in a
+    /// `BlobType::ZstdDictionary` blob before the first data blob, and readers
+    /// of this crate load it from there. Files using it can't be read by
+    /// mainstream tools, so writing data blobs fails unless
+    /// `with_nonstandard(true)` is set as well.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use osm_pbf::{BlobCompression, HeaderBlock, PbfWriter, PrimitiveBlock, ZstdDictionary};
+    /// use std::fs::File;
+    ///
+    /// let dictionary = ZstdDictionary::new(std::fs::read("blocks.dict")?)?;
+    /// let mut writer = PbfWriter::new(File::create("out.osm.pbf")?)
+    ///     .with_compression(BlobCompression::Zstd(19))
+    ///     .with_zstd_dictionary(dictionary)
+    ///     .with_nonstandard(true);
+    /// writer.write_header(&HeaderBlock::default())?;
+    /// writer.write_primitive_block(&PrimitiveBlock::default())?;
+    /// writer.flush()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "zstd-write")]
+    pub fn with_zstd_dictionary(mut self, dictionary: ZstdDictionary) -> Self {
+        self.zstd_dictionary = Some(dictionary);
+        self
+    }
+
+    /// Write the OSMHeader blob; must be the first blob of the file
+    pub fn write_header(&mut self, header: &HeaderBlock) -> Result<()> {
+        self.write_blob(&BlobType::OSMHeader, &encode_header_block(header))
+    }
+
+    /// Write a PrimitiveBlock as an OSMData blob
+    ///
+    /// Fails with `BlobError::DeltaOverflow` if a delta-encoded column (dense
+    /// ids and coordinates, way refs, relation memids) would overflow i64 when
+    /// decoded, rather than writing a block that reads back corrupted. Tag keys,
+    /// values and roles over `MAX_STRING_CHARS` characters are handled by the
+    /// writer's `StringPolicy`.
+    pub fn write_primitive_block(&mut self, block: &PrimitiveBlock) -> Result<()> {
+        self.write_block(&BlobType::OSMData, block)
+    }
+
+    /// Write a PrimitiveBlock as an OSMChange blob, for .osc.pbf files
+    ///
+    /// Each element's action is recorded in its metadata (see
+    /// `ChangeAction::of`): deleted elements need an `Info` with `visible`
+    /// false, created ones version 1. Checks are as for `write_primitive_block`.
+    pub fn write_change_block(&mut self, block: &PrimitiveBlock) -> Result<()> {
+        self.write_block(&BlobType::OSMChange, block)
+    }
+
+    fn write_block(&mut self, blob_type: &BlobType, block: &PrimitiveBlock) -> Result<()> {
+        check_block_deltas(block)?;
+        let requantized = match self.date_granularity {
+            Some(date_granularity) if date_granularity != block.date_granularity => {
+                let mut block = block.clone();
+                block.requantize_dates(date_granularity).map_err(|e| BlobError::InvalidFormat(e.to_string()))?;
+                Some(block)
+            }
+            _ => None,
+        };
+        let block = requantized.as_ref().unwrap_or(block);
+        let cleaned = apply_string_policy(block, self.string_policy)?;
+        let block = cleaned.as_ref().unwrap_or(block);
+        self.write_blob(blob_type, &self.block_encoder.encode_block(block)?)?;
+        self.stats.count_block(block);
+        Ok(())
+    }
+
+    /// Frame and write an already encoded block message, compressed with the
+    /// writer's `BlobCompression`
+    pub fn write_blob(&mut self, blob_type: &BlobType, message: &[u8]) -> Result<()> {
+        if message.len() > MAX_BLOB_MESSAGE_SIZE {
+            return Err(BlobError::MessageTooLarge {
+                size: message.len(),
+                max: MAX_BLOB_MESSAGE_SIZE,
+            });
+        }
+
+        let mut blob = WireWriter::new();
+        let stored = match self.compression {
+            BlobCompression::Raw => {
+                blob.bytes(1, message); // raw
+                message.len()
+            }
+            BlobCompression::Zlib(level) => {
+                let level = flate2::Compression::new(level.min(9));
+                let mut encoder = ZlibEncoder::new(Vec::with_capacity(message.len() / 2), level);
+                encoder.write_all(message)?;
+                let compressed = encoder.finish()?;
+                blob.int64(2, message.len() as i64); // raw_size
+                blob.bytes(3, &compressed); // zlib_data
+                compressed.len()
+            }
+            #[cfg(feature = "zstd-write")]
+            BlobCompression::Zstd(level) => {
+                let compressed = self.zstd_compress(blob_type, message, level)?;
+                blob.int64(2, message.len() as i64); // raw_size
+                blob.bytes(7, &compressed); // zstd_data
+                compressed.len()
+            }
+        };
+
+        self.write_frame(blob_type, &blob.into_bytes())?;
+        self.stats.uncompressed_bytes += message.len() as u64;
+        self.stats.compressed_bytes += stored as u64;
+        Ok(())
+    }
+
+    /// Compress a message with zstd, with the writer's dictionary for data
+    /// blobs, writing the dictionary blob ahead of the first one
+    #[cfg(feature = "zstd-write")]
+    fn zstd_compress(&mut self, blob_type: &BlobType, message: &[u8], level: i32) -> Result<Vec<u8>> {
+        let compressed = match self.zstd_dictionary.clone() {
+            Some(dictionary) if blob_type.holds_elements() => {
+                if !self.nonstandard {
+                    return Err(BlobError::InvalidFormat(
+                        "zstd dictionaries are nonstandard; allow them with `with_nonstandard(true)`".to_string()
+                    ));
+                }
+                if !self.zstd_dictionary_written {
+                    let mut blob = WireWriter::new();
+                    blob.bytes(1, dictionary.as_bytes()); // raw
+                    self.write_frame(&BlobType::ZstdDictionary, &blob.into_bytes())?;
+                    self.zstd_dictionary_written = true;
+                }
+                zstd::bulk::Compressor::with_dictionary(level, dictionary.as_bytes())
+                    .and_then(|mut compressor| compressor.compress(message))
+            }
+            _ => zstd::bulk::compress(message, level),
+        };
+        compressed.map_err(|e| BlobError::Compression(format!("zstd compression failed: {e}")))
+    }
+
+    /// Write a Blob message in a frame with its BlobHeader
+    fn write_frame(&mut self, blob_type: &BlobType, blob: &[u8]) -> Result<()> {
+        let mut header = WireWriter::new();
+        header.string(1, blob_type.as_str());
+        header.int64(3, blob.len() as i64); // datasize
+        self.writer.write_raw(&header.into_bytes(), blob)
+    }
+
+    /// Elements, blobs and bytes written so far
+    pub fn stats(&self) -> WriterStats {
+        WriterStats {
+            blobs: self.writer.blobs_written(),
+            file_bytes: self.writer.bytes_written(),
+            ..self.stats
+        }
+    }
+
+    /// Number of blobs written so far
+    pub fn blobs_written(&self) -> u64 {
+        self.writer.blobs_written()
+    }
+
+    /// Number of bytes written so far
+    pub fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written()
+    }
+
+    /// Flush the underlying sink
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the underlying sink
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+/// Frame-level writer for already encoded BlobHeader and Blob messages
+///
+/// Only adds the length prefix, so blobs read with `IndexedReader::read_raw_blob`
+/// can be copied between files (to concatenate, split or filter them) without
+/// being decompressed or decoded.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{IndexedReader, RawBlobWriter};
+/// use std::fs::File;
+///
+/// let mut reader = IndexedReader::new(File::open("in.osm.pbf")?)?;
+/// let mut writer = RawBlobWriter::new(File::create("out.osm.pbf")?);
+/// for index in 0..reader.blob_count() {
+///     let (header, blob) = reader.read_raw_blob(index)?;
+///     writer.write_raw(&header, &blob)?;
+/// }
+/// writer.flush()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct RawBlobWriter<W: Write> {
+    writer: W,
+    blobs_written: u64,
+    bytes_written: u64,
+}
+
+impl<W: Write> RawBlobWriter<W> {
+    /// Create a writer over any `Write` sink
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            blobs_written: 0,
+            bytes_written: 0,
+        }
+    }
+
+    /// Write one frame from a serialized BlobHeader and Blob
+    ///
+    /// The header is decoded to check that its `datasize` matches the blob, so
+    /// a mismatched pair can't corrupt the framing of the output.
+    pub fn write_raw(&mut self, header: &[u8], blob: &[u8]) -> Result<()> {
+        if header.len() > MAX_BLOB_HEADER_SIZE {
+            return Err(BlobError::HeaderTooLarge {
+                size: header.len(),
+                max: MAX_BLOB_HEADER_SIZE,
+            });
+        }
+
+        let datasize = decode_blob_header(header)?.datasize as usize;
+        if datasize != blob.len() {
+            return Err(BlobError::InvalidFormat(format!(
+                "BlobHeader datasize {} doesn't match blob length {}",
+                datasize,
+                blob.len()
+            )));
+        }
+
+        self.writer.write_all(&(header.len() as u32).to_be_bytes())?;
+        self.writer.write_all(header)?;
+        self.writer.write_all(blob)?;
+
+        self.blobs_written += 1;
+        self.bytes_written += 4 + header.len() as u64 + blob.len() as u64;
+        Ok(())
+    }
+
+    /// Number of blobs written so far
+    pub fn blobs_written(&self) -> u64 {
+        self.blobs_written
+    }
+
+    /// Number of bytes written so far
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Flush the underlying sink
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Return the underlying sink
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Dry run of `PbfWriter` predicting the output size of an element stream
+///
+/// Elements are packed into blocks, encoded and compressed exactly as they
+/// would be written, but nothing is stored, so the estimate is exact and
+/// cheap enough to plan storage for generated extracts.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{ElementFilter, HeaderBlock, Reader, SizeEstimator};
+/// use std::fs::File;
+///
+/// let mut estimator = SizeEstimator::new();
+/// estimator.write_header(&HeaderBlock::default())?;
+/// let mut reader = Reader::new(File::open("map.osm.pbf")?)?;
+/// let (_handle, batches) = reader.spawn_stream(Default::default());
+/// for batch in batches {
+///     for element in &batch.elements {
+///         estimator.write(element, &batch.strings)?;
+///     }
+/// }
+/// println!("about {} bytes", estimator.finish()?.file_bytes);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub struct SizeEstimator {
+    writer: PbfWriter<Sink>,
+    block: BlockBuffer,
+    block_size: usize,
+}
+
+impl Default for SizeEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SizeEstimator {
+    /// Default maximum number of elements per block
+    pub const DEFAULT_BLOCK_SIZE: usize = 8000;
+
+    pub fn new() -> Self {
+        Self { writer: PbfWriter::new(std::io::sink()), block: BlockBuffer::default(), block_size: Self::DEFAULT_BLOCK_SIZE }
+    }
+
+    /// Set the maximum number of elements per block
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Estimate an output written with `compression` instead of zlib
+    pub fn with_compression(mut self, compression: BlobCompression) -> Self {
+        self.writer = self.writer.with_compression(compression);
+        self
+    }
+
+    /// Count the OSMHeader blob
+    pub fn write_header(&mut self, header: &HeaderBlock) -> Result<()> {
+        self.writer.write_header(header)
+    }
+
+    /// Add one element; its tag, role and user indices refer to `strings`
+    pub fn write(&mut self, element: &OsmElement, strings: &StringTable) -> Result<()> {
+        self.block.push(element, strings);
+        if self.block.len() >= self.block_size {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    /// Add an already built block, flushing buffered elements first
+    pub fn write_primitive_block(&mut self, block: &PrimitiveBlock) -> Result<()> {
+        self.flush_block()?;
+        self.writer.write_primitive_block(block)
+    }
+
+    /// Estimate so far, buffered elements not included
+    pub fn stats(&self) -> WriterStats {
+        self.writer.stats()
+    }
+
+    /// Flush buffered elements and return the final estimate
+    pub fn finish(mut self) -> Result<WriterStats> {
+        self.flush_block()?;
+        Ok(self.writer.stats())
+    }
+
+    fn flush_block(&mut self) -> Result<()> {
+        if self.block.len() > 0 {
+            let block = std::mem::take(&mut self.block).finish();
+            self.writer.write_primitive_block(&block)?;
+        }
+        Ok(())
+    }
+}
+
+/// Elements awaiting a block, with their own string table
+#[derive(Default)]
+pub(crate) struct BlockBuffer {
+    strings: StringTableBuilder,
+    nodes: Vec<Node>,
+    ways: Vec<Way>,
+    relations: Vec<Relation>,
+    changesets: Vec<ChangeSet>,
+}
+
+impl BlockBuffer {
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len() + self.ways.len() + self.relations.len() + self.changesets.len()
+    }
+
+    pub(crate) fn push(&mut self, element: &OsmElement, strings: &StringTable) {
+        match element {
+            OsmElement::Node(node) => {
+                let mut node = node.clone();
+                self.reintern_tags(&mut node.keys, &mut node.vals, strings);
+                self.reintern_info(&mut node.info, strings);
+                self.nodes.push(node);
+            }
+            OsmElement::Way(way) => {
+                let mut way = way.clone();
+                self.reintern_tags(&mut way.keys, &mut way.vals, strings);
+                self.reintern_info(&mut way.info, strings);
+                self.ways.push(way);
+            }
+            OsmElement::Relation(relation) => {
+                let mut relation = relation.clone();
+                self.reintern_tags(&mut relation.keys, &mut relation.vals, strings);
+                self.reintern_info(&mut relation.info, strings);
+                for role in &mut relation.roles_sid {
+                    *role = self.string(strings.get_string_or_empty(*role as usize)) as i32;
+                }
+                self.relations.push(relation);
+            }
+            OsmElement::ChangeSet(changeset) => {
+                let mut changeset = changeset.clone();
+                self.reintern_tags(&mut changeset.keys, &mut changeset.vals, strings);
+                self.reintern_info(&mut changeset.info, strings);
+                self.changesets.push(changeset);
+            }
+        }
+    }
+
+    fn reintern_tags(&mut self, keys: &mut [u32], vals: &mut [u32], strings: &StringTable) {
+        for index in keys.iter_mut().chain(vals.iter_mut()) {
+            *index = self.string(strings.get_string_or_empty(*index as usize));
+        }
+    }
+
+    fn reintern_info(&mut self, info: &mut Option<Info>, strings: &StringTable) {
+        if let Some(info) = info {
+            info.user_sid = self.string(strings.get_string_or_empty(info.user_sid as usize));
+        }
+    }
+
+    fn string(&mut self, s: &str) -> u32 {
+        self.strings.get_or_insert(s)
+    }
+
+    /// One group per element type, in the order nodes, ways, relations, changesets
+    ///
+    /// Node locations, on nodes and on ways, are taken as nanodegrees, as
+    /// decoded, and stored with the default granularity unless that would
+    /// lose precision. The most used strings come first in the string table.
+    pub(crate) fn finish(mut self) -> PrimitiveBlock {
+        let (stringtable, remap) = std::mem::take(&mut self.strings).build_by_frequency();
+        let remap_tags = |keys: &mut [u32], vals: &mut [u32]| {
+            keys.iter_mut().chain(vals.iter_mut()).for_each(|index| *index = remap[*index as usize]);
+        };
+        let remap_info = |info: &mut Option<Info>| {
+            if let Some(info) = info {
+                info.user_sid = remap[info.user_sid as usize];
+            }
+        };
+        for node in &mut self.nodes {
+            remap_tags(&mut node.keys, &mut node.vals);
+            remap_info(&mut node.info);
+        }
+        for way in &mut self.ways {
+            remap_tags(&mut way.keys, &mut way.vals);
+            remap_info(&mut way.info);
+        }
+        for relation in &mut self.relations {
+            remap_tags(&mut relation.keys, &mut relation.vals);
+            remap_info(&mut relation.info);
+            relation.roles_sid.iter_mut().for_each(|role| *role = remap[*role as usize] as i32);
+        }
+        for changeset in &mut self.changesets {
+            remap_tags(&mut changeset.keys, &mut changeset.vals);
+            remap_info(&mut changeset.info);
+        }
+
+        let granularity = PrimitiveBlock::DEFAULT_GRANULARITY as i64;
+        let on_grid = |value: &i64| value % granularity == 0;
+        let granularity = if self.nodes.iter().all(|n| on_grid(&n.location.lat.0) && on_grid(&n.location.lon.0))
+            && self.ways.iter().all(|w| w.lat.iter().chain(&w.lon).all(on_grid))
+        {
+            granularity
+        } else {
+            1
+        };
+        for node in &mut self.nodes {
+            node.location = LatLon::from_raw(node.location.lat.0 / granularity, node.location.lon.0 / granularity);
+        }
+        for way in &mut self.ways {
+            way.lat.iter_mut().chain(way.lon.iter_mut()).for_each(|delta| *delta /= granularity);
+        }
+
+        let mut groups = Vec::new();
+        if !self.nodes.is_empty() {
+            groups.push(PrimitiveGroup { nodes: self.nodes, ..Default::default() });
+        }
+        if !self.ways.is_empty() {
+            groups.push(PrimitiveGroup { ways: self.ways, ..Default::default() });
+        }
+        if !self.relations.is_empty() {
+            groups.push(PrimitiveGroup { relations: self.relations, ..Default::default() });
+        }
+        if !self.changesets.is_empty() {
+            groups.push(PrimitiveGroup { changesets: self.changesets, ..Default::default() });
+        }
+        PrimitiveBlock { stringtable, primitivegroup: groups, granularity: granularity as i32, ..Default::default() }
+    }
+}
+
+/// Encode a HeaderBlock message
+pub fn encode_header_block(header: &HeaderBlock) -> Vec<u8> {
+    let mut w = WireWriter::new();
+    for feature in &header.required_features {
+        w.string(4, feature);
+    }
+    for feature in &header.optional_features {
+        w.string(5, feature);
+    }
+    if !header.writing_program.is_empty() {
+        w.string(16, header.writing_program);
+    }
+    if !header.source.is_empty() {
+        w.string(17, header.source);
+    }
+    if let Some(timestamp) = header.osmosis_replication_timestamp {
+        w.int64(32, timestamp.as_secs());
+    }
+    if let Some(sequence) = header.osmosis_replication_sequence_number {
+        w.int64(33, sequence.as_seq());
+    }
+    if let Some(url) = header.osmosis_replication_base_url {
+        w.string(34, url);
+    }
+    w.into_bytes()
+}
+
+/// Encode a PrimitiveBlock message (values are written as stored: raw
+/// granularity units, delta-encoded where the format is delta-encoded)
+pub fn encode_primitive_block(block: &PrimitiveBlock) -> Vec<u8> {
+    let mut w = WireWriter::new();
+    w.message(1, &encode_string_table(&block.stringtable));
+    for group in &block.primitivegroup {
+        w.message(2, &encode_group(group));
+    }
+    if block.granularity != PrimitiveBlock::DEFAULT_GRANULARITY {
+        w.int64(17, block.granularity as i64);
+    }
+    if block.date_granularity != PrimitiveBlock::DEFAULT_DATE_GRANULARITY {
+        w.int64(18, block.date_granularity as i64);
+    }
+    if block.lat_offset != 0 {
+        w.int64(19, block.lat_offset);
+    }
+    if block.lon_offset != 0 {
+        w.int64(20, block.lon_offset);
+    }
+    w.into_bytes()
+}
+
+fn encode_string_table(table: &StringTable) -> WireWriter {
+    let mut w = WireWriter::new();
+    for s in &table.s {
+        w.string(1, s);
+    }
+    w
+}
+
+fn encode_group(group: &PrimitiveGroup) -> WireWriter {
+    let mut w = WireWriter::new();
+    for node in &group.nodes {
+        w.message(1, &encode_node(node));
+    }
+    if let Some(dense) = &group.dense {
+        w.message(2, &encode_dense(dense));
+    }
+    for way in &group.ways {
+        w.message(3, &encode_way(way));
+    }
+    for relation in &group.relations {
+        w.message(4, &encode_relation(relation));
+    }
+    for changeset in &group.changesets {
+        let mut c = WireWriter::new();
+        c.int64(1, changeset.id.0);
+        w.message(5, &c);
+    }
+    w
+}
+
+fn encode_info(info: &Info) -> WireWriter {
+    let mut w = WireWriter::new();
+    w.int64(1, info.version as i64);
+    w.int64(2, info.timestamp);
+    w.int64(3, info.changeset);
+    w.int64(4, info.uid as i64);
+    w.uint64(5, info.user_sid as u64);
+    if !info.visible {
+        w.bool(6, false);
+    }
+    w
+}
+
+fn encode_tags(w: &mut WireWriter, keys: &[u32], vals: &[u32]) {
+    w.packed_uint64(2, keys.iter().map(|k| *k as u64));
+    w.packed_uint64(3, vals.iter().map(|v| *v as u64));
+}
+
+fn encode_node(node: &Node) -> WireWriter {
+    let mut w = WireWriter::new();
+    w.sint64(1, node.id.0);
+    encode_tags(&mut w, &node.keys, &node.vals);
+    if let Some(info) = &node.info {
+        w.message(4, &encode_info(info));
+    }
+    w.sint64(8, node.location.lat.0);
+    w.sint64(9, node.location.lon.0);
+    w
+}
+
+fn encode_dense(dense: &DenseNodes) -> WireWriter {
+    let mut w = WireWriter::new();
+    w.packed_sint64(1, dense.id.iter().copied());
+    if let Some(info) = &dense.denseinfo {
+        let mut i = WireWriter::new();
+        i.packed_int64(1, info.version.iter().map(|v| *v as i64));
+        i.packed_sint64(2, info.timestamp.iter().copied());
+        i.packed_sint64(3, info.changeset.iter().copied());
+        i.packed_sint64(4, info.uid.iter().map(|v| *v as i64));
+        i.packed_sint64(5, info.user_sid.iter().map(|v| *v as i64));
+        i.packed_uint64(6, info.visible.iter().map(|v| *v as u64));
+        w.message(5, &i);
+    }
+    w.packed_sint64(8, dense.lat.iter().copied());
+    w.packed_sint64(9, dense.lon.iter().copied());
+    w.packed_int64(10, dense.keys_vals.iter().map(|v| *v as i64));
+    w
+}
+
+fn encode_way(way: &Way) -> WireWriter {
+    let mut w = WireWriter::new();
+    w.int64(1, way.id.0);
+    encode_tags(&mut w, &way.keys, &way.vals);
+    if let Some(info) = &way.info {
+        w.message(4, &encode_info(info));
+    }
+    w.packed_sint64(8, way.refs.iter().copied());
+    w.packed_sint64(9, way.lat.iter().copied());
+    w.packed_sint64(10, way.lon.iter().copied());
+    w
+}
+
+fn encode_relation(relation: &Relation) -> WireWriter {
+    let mut w = WireWriter::new();
+    w.int64(1, relation.id.0);
+    encode_tags(&mut w, &relation.keys, &relation.vals);
+    if let Some(info) = &relation.info {
+        w.message(4, &encode_info(info));
+    }
+    w.packed_int64(8, relation.roles_sid.iter().map(|v| *v as i64));
+    w.packed_sint64(9, relation.memids.iter().copied());
+    w.packed_int64(10, relation.types.iter().map(|t| *t as i64));
+    w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::{RelationId, WayId};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_frame_layout() {
+        let mut writer = PbfWriter::new(Vec::new());
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        assert_eq!(writer.blobs_written(), 1);
+
+        let bytes = writer.into_inner();
+        let header_len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let header = &bytes[4..4 + header_len];
+
+        // type = "OSMHeader", followed by datasize covering the rest of the frame
+        assert_eq!(header[0], 0x0a);
+        assert_eq!(&header[2..11], b"OSMHeader");
+        assert_eq!(header[11], 0x18);
+        assert_eq!(header[12] as usize, bytes.len() - 4 - header_len);
+    }
+
+    #[test]
+    fn test_bytes_written_matches_output() {
+        let mut writer = PbfWriter::new(Vec::new());
+        let mut block = PrimitiveBlock::default();
+        block.stringtable.add_string("highway".to_string());
+        block.primitivegroup.push(PrimitiveGroup {
+            ways: vec![Way { id: WayId(1), keys: vec![1], vals: vec![1], info: None, refs: vec![1, 1, 1], lat: vec![], lon: vec![] }],
+            ..Default::default()
+        });
+        writer.write_primitive_block(&block).unwrap();
+
+        let written = writer.bytes_written();
+        assert_eq!(writer.into_inner().len() as u64, written);
+    }
+
+    #[test]
+    fn test_blob_compression() {
+        let mut block = PrimitiveBlock::default();
+        block.stringtable.add_string("highway".to_string());
+        block.primitivegroup.push(PrimitiveGroup {
+            ways: (1..=50).map(|id| Way { id: WayId(id), keys: vec![1], vals: vec![1], info: None, refs: vec![1; 20], lat: vec![], lon: vec![] }).collect(),
+            ..Default::default()
+        });
+        let written = |compression| {
+            let mut writer = PbfWriter::new(Vec::new()).with_compression(compression);
+            writer.write_header(&HeaderBlock::default()).unwrap();
+            writer.write_primitive_block(&block).unwrap();
+            let mut reader = crate::io::indexed_reader::IndexedReader::new(std::io::Cursor::new(writer.into_inner())).unwrap();
+            reader.read_blob_by_index(1).unwrap().unwrap()
+        };
+
+        let (zlib, raw) = (written(BlobCompression::default()), written(BlobCompression::Raw));
+        assert!(zlib.is_compressed() && !raw.is_compressed());
+        assert!(zlib.compressed_size() < raw.compressed_size());
+        assert_eq!(zlib.decompress().unwrap(), raw.decompress().unwrap());
+        assert_eq!(zlib.raw_size(), raw.raw_size());
+        assert_eq!(written(BlobCompression::Zlib(0)).decompress().unwrap(), raw.decompress().unwrap());
+    }
+
+    #[test]
+    fn test_write_raw_rejects_datasize_mismatch() {
+        let mut header = WireWriter::new();
+        header.string(1, "OSMData");
+        header.int64(3, 4);
+        let header = header.into_bytes();
+
+        let mut writer = RawBlobWriter::new(Vec::new());
+        assert!(matches!(writer.write_raw(&header, &[0; 3]), Err(BlobError::InvalidFormat(_))));
+        assert!(writer.write_raw(&[0xff], &[]).is_err());
+        assert_eq!(writer.blobs_written(), 0);
+
+        writer.write_raw(&header, &[0; 4]).unwrap();
+        assert_eq!(writer.into_inner().len(), 4 + header.len() + 4);
+    }
+
+    #[test]
+    fn test_overflowing_memids_rejected() {
+        let mut block = PrimitiveBlock::default();
+        block.primitivegroup.push(PrimitiveGroup {
+            relations: vec![Relation {
+                id: RelationId(1),
+                keys: vec![],
+                vals: vec![],
+                info: None,
+                roles_sid: vec![0, 0],
+                memids: vec![i64::MAX, 1],
+                types: vec![MemberType::Node, MemberType::Node],
+            }],
+            ..Default::default()
+        });
+
+        let mut writer = PbfWriter::new(Vec::new());
+        assert!(matches!(writer.write_primitive_block(&block), Err(BlobError::DeltaOverflow { index: 1 })));
+        assert_eq!(writer.blobs_written(), 0);
+
+        block.primitivegroup[0].relations[0].memids = vec![i64::MAX, -i64::MAX];
+        writer.write_primitive_block(&block).unwrap();
+    }
+
+    #[test]
+    fn test_string_policy() {
+        let mut block = PrimitiveBlock::default();
+        let key = block.stringtable.add_string("note".to_string()) as u32;
+        let value = block.stringtable.add_string("n".repeat(400)) as u32;
+        block.primitivegroup.push(PrimitiveGroup {
+            ways: vec![Way { id: WayId(9), keys: vec![key], vals: vec![value], info: None, refs: vec![], lat: vec![], lon: vec![] }],
+            ..Default::default()
+        });
+
+        let mut writer = PbfWriter::new(Vec::new());
+        let err = writer.write_primitive_block(&block).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid string in way 9, key \"note\": 400 characters (max: 255)");
+        assert_eq!(writer.blobs_written(), 0);
+
+        let mut writer = PbfWriter::with_string_policy(Vec::new(), StringPolicy::Truncate);
+        writer.write_primitive_block(&block).unwrap();
+        assert!(writer.bytes_written() < 400);
+    }
+
+    #[test]
+    fn test_writer_stats() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(3).grid_size(6).block_size(20).relation_count(2).write_to(&mut data).unwrap();
+        let mut reader = crate::io::indexed_reader::IndexedReader::new(std::io::Cursor::new(data.clone())).unwrap();
+
+        let mut writer = PbfWriter::new(Vec::new()).with_compression(BlobCompression::Raw);
+        writer.write_header(&HeaderBlock::default()).unwrap();
+        let header_bytes = writer.stats().file_bytes;
+        for index in 1..reader.blob_count() {
+            let blob = reader.read_blob_by_index(index).unwrap().unwrap();
+            let block = crate::io::decode::decode_primitive_block(&blob.decompress().unwrap()).unwrap();
+            writer.write_primitive_block(&block).unwrap();
+        }
+
+        let stats = writer.stats();
+        assert_eq!((stats.nodes, stats.ways, stats.relations, stats.changesets), (planet.nodes, planet.ways, planet.relations, 0));
+        assert_eq!(stats.blobs, planet.blobs);
+        assert_eq!(stats.compression_ratio(), 1.0);
+        assert!(stats.uncompressed_bytes > 0 && stats.uncompressed_bytes < stats.file_bytes - header_bytes);
+        assert_eq!(writer.into_inner().len() as u64, stats.file_bytes);
+
+        // zlib output: the ratio comes from the payloads as stored
+        let mut writer = PbfWriter::new(Vec::new());
+        for index in 0..reader.blob_count() {
+            let blob = reader.read_blob_by_index(index).unwrap().unwrap();
+            writer.write_blob(blob.blob_type(), &blob.decompress().unwrap()).unwrap();
+        }
+        let stats = writer.stats();
+        let mut written = crate::io::indexed_reader::IndexedReader::new(std::io::Cursor::new(writer.into_inner())).unwrap();
+        let (mut stored, mut raw) = (0, 0);
+        for index in 0..written.blob_count() {
+            let blob = written.read_blob_by_index(index).unwrap().unwrap();
+            let crate::io::blob::BlobData::ZlibData { compressed, raw_size } = &blob.data else {
+                panic!("blob {index} isn't zlib-compressed");
+            };
+            stored += compressed.len() as u64;
+            raw += *raw_size as u64;
+        }
+        assert_eq!((stats.compressed_bytes, stats.uncompressed_bytes), (stored, raw));
+        assert_eq!(stats.compression_ratio(), stored as f64 / raw as f64);
+        assert!(stats.compression_ratio() < 1.0, "ratio {}", stats.compression_ratio());
+    }
+
+    #[cfg(feature = "zstd-write")]
+    #[test]
+    fn test_zstd_dictionary_round_trip() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(5).grid_size(16).block_size(8).relation_count(2).write_to(&mut data).unwrap();
+        let mut reader = crate::io::indexed_reader::IndexedReader::new(std::io::Cursor::new(data)).unwrap();
+        let blobs: Vec<_> = (0..reader.blob_count())
+            .map(|index| {
+                let blob = reader.read_blob_by_index(index).unwrap().unwrap();
+                (blob.blob_type().clone(), blob.decompress().unwrap())
+            })
+            .collect();
+        let samples: Vec<_> = blobs.iter().skip(1).map(|(_, message)| message.clone()).collect();
+        let dictionary = ZstdDictionary::train(&samples, 1024).unwrap();
+
+        // Element blobs with a dictionary need the nonstandard opt-in
+        let mut writer = PbfWriter::new(Vec::new()).with_compression(BlobCompression::Zstd(3)).with_zstd_dictionary(dictionary.clone());
+        writer.write_blob(&blobs[0].0, &blobs[0].1).unwrap();
+        assert!(matches!(writer.write_blob(&blobs[1].0, &blobs[1].1), Err(BlobError::InvalidFormat(_))));
+
+        let mut writer = writer.with_nonstandard(true);
+        for (blob_type, message) in &blobs[1..] {
+            writer.write_blob(blob_type, message).unwrap();
+        }
+        let written = writer.into_inner();
+
+        let mut reader = crate::io::indexed_reader::IndexedReader::new(std::io::Cursor::new(written.clone())).unwrap();
+        // The header, then the dictionary ahead of the first data blob
+        assert_eq!(reader.blob_count(), blobs.len() + 1);
+        assert_eq!(reader.statistics().unknown_blobs, 1);
+        let stored = reader.read_blob_by_index(1).unwrap().unwrap();
+        assert_eq!((stored.blob_type(), &stored.decompress().unwrap()[..]), (&BlobType::ZstdDictionary, dictionary.as_bytes()));
+        for (index, (_, message)) in blobs.iter().enumerate().skip(1) {
+            assert_eq!(reader.read_blob_by_index(index + 1).unwrap().unwrap().decompress().unwrap(), message);
+        }
+
+        let mut reader = crate::io::reader::Reader::new(std::io::Cursor::new(written)).unwrap();
+        let stats = reader.for_each(|_| Ok(())).unwrap();
+        assert_eq!((stats.nodes_processed, stats.ways_processed, stats.relations_processed), (planet.nodes, planet.ways, planet.relations));
+        assert_eq!(stats.blobs_skipped, 0);
+    }
+
+    #[test]
+    fn test_size_estimator_matches_written_output() {
+        let mut data = Vec::new();
+        crate::synthetic::PlanetBuilder::new(4).grid_size(6).relation_count(2).write_to(&mut data).unwrap();
+        let mut reader = crate::io::reader::Reader::new(std::io::Cursor::new(data)).unwrap();
+
+        let header = HeaderBlock::default();
+        let mut estimator = SizeEstimator::new().with_block_size(15);
+        estimator.write_header(&header).unwrap();
+        let mut sharded = crate::partition::ShardedWriter::new(
+            vec![Vec::new()], &header, crate::partition::SpanPolicy::Duplicate, |_: &OsmElement| Some(0),
+        ).unwrap().with_block_size(15);
+        reader.for_each_filtered_with_strings(&crate::io::indexed_reader::ElementFilter::all(), |element, strings| {
+            estimator.write(&element, strings)?;
+            sharded.write(&element, strings)
+        }).unwrap();
+
+        let written = sharded.finish().unwrap().remove(0);
+        let estimate = estimator.finish().unwrap();
+        assert_eq!(estimate.file_bytes, written.len() as u64);
+        assert!(estimate.blobs > 2 && estimate.elements() > 0);
+    }
+
+    #[test]
+    fn test_primitive_block_defaults_omitted() {
+        let encoded = encode_primitive_block(&PrimitiveBlock::default());
+        // Only the string table with its single empty string: field 1 { field 1 "" }
+        assert_eq!(encoded, vec![0x0a, 0x02, 0x0a, 0x00]);
+    }
+}
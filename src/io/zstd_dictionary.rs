@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use bytes::Bytes;
+use crate::io::blob::{Blob, BlobData, BlobError, BlobType, Result};
+
+/// First bytes of a zstd dictionary with entropy tables
+const DICTIONARY_MAGIC: [u8; 4] = [0x37, 0xa4, 0x30, 0xec];
+
+/// First bytes of a zstd frame
+const FRAME_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A zstd dictionary shared by the zstd blobs of a file (nonstandard)
+///
+/// Stored in a `BlobType::ZstdDictionary` blob ahead of the data blobs using
+/// it. Readers load it on their own; writers only use one with
+/// `PbfWriter::with_nonstandard`, since mainstream tools can't read such
+/// files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZstdDictionary {
+    id: u32,
+    bytes: Bytes,
+}
+
+impl ZstdDictionary {
+    /// Wrap a zstd dictionary, e.g. one made by `zstd --train`
+    ///
+    /// Only dictionaries with entropy tables and a non-zero id are accepted;
+    /// raw content dictionaries carry no id for frames to refer to. With the
+    /// `zstd` feature the tables are checked as well.
+    pub fn new(bytes: impl Into<Bytes>) -> Result<Self> {
+        let bytes = bytes.into();
+        if bytes.len() < 8 || bytes[..4] != DICTIONARY_MAGIC {
+            return Err(BlobError::Compression("Not a zstd dictionary".to_string()));
+        }
+        let id = u32::from_le_bytes(bytes[4..8].try_into().expect("4 bytes"));
+        if id == 0 {
+            return Err(BlobError::Compression("zstd dictionary without an id".to_string()));
+        }
+        #[cfg(feature = "zstd")]
+        {
+            // `decode_dict` panics on some truncated dictionaries instead of failing
+            let decoded = std::panic::catch_unwind(|| ruzstd::decoding::Dictionary::decode_dict(&bytes).is_ok());
+            if !matches!(decoded, Ok(true)) {
+                return Err(BlobError::Compression(format!("Corrupt zstd dictionary {id}")));
+            }
+        }
+        Ok(Self { id, bytes })
+    }
+
+    /// Train a dictionary of at most `max_size` bytes on sample block messages
+    #[cfg(feature = "zstd-write")]
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Result<Self> {
+        let bytes = zstd::dict::from_samples(samples, max_size)
+            .map_err(|e| BlobError::Compression(format!("Can't train zstd dictionary: {e}")))?;
+        Self::new(bytes)
+    }
+
+    /// Id that zstd frames compressed with this dictionary carry
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The serialized dictionary, as stored in its blob
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Id of the dictionary a zstd frame was compressed with, if any
+pub(crate) fn frame_dictionary_id(frame: &[u8]) -> Option<u32> {
+    if frame.get(..4)? != FRAME_MAGIC {
+        return None;
+    }
+    let descriptor = *frame.get(4)?;
+    let single_segment = descriptor & 0x20 != 0;
+    let start = if single_segment { 5 } else { 6 };
+    let len = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+    let mut id = [0u8; 4];
+    id[..len].copy_from_slice(frame.get(start..start + len)?);
+    Some(u32::from_le_bytes(id)).filter(|&id| id != 0)
+}
+
+/// Dictionaries loaded from a file's `ZstdDictionary` blobs, by id
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ZstdDictionaries {
+    by_id: HashMap<u32, ZstdDictionary>,
+}
+
+impl ZstdDictionaries {
+    /// Load the dictionary stored in a `ZstdDictionary` blob
+    pub fn load(&mut self, blob: &Blob) -> Result<()> {
+        let dictionary = ZstdDictionary::new(blob.decompress()?)?;
+        self.by_id.insert(dictionary.id, dictionary);
+        Ok(())
+    }
+
+    /// Hand a zstd blob the dictionary its frame names, if that is loaded
+    ///
+    /// Blobs whose dictionary isn't loaded are left alone and fail to
+    /// decompress, naming the missing id.
+    pub fn attach(&self, blob: &mut Blob) {
+        if let BlobData::ZstdData { compressed, dictionary, .. } = &mut blob.data
+            && let Some(id) = frame_dictionary_id(compressed)
+        {
+            *dictionary = self.by_id.get(&id).cloned();
+        }
+    }
+
+    /// Load dictionary blobs and attach dictionaries to zstd blobs, for
+    /// readers seeing blobs in file order
+    pub fn observe(&mut self, blob: &mut Blob) -> Result<()> {
+        match blob.blob_type() {
+            BlobType::ZstdDictionary => self.load(blob),
+            _ => {
+                self.attach(blob);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Dictionary trained with `zstd` on "highway=residential;name=Street {i};" samples
+    const DICTIONARY: &str = "37a430ec0e587a5b271030bb910ef01769c35fa40d7f9136fc45da55555555555555555585e4092184902d1d0000a008030000000000e801000000244080000102040404040404040404040404040404040404040404040404b0432111111111912449d201b47b18866118c618638c31c618638c3166666666b66d0100000004000000080000006d653d537472656574203234393b686967687761793d7265736964656e7469616c3b6e616d653d537472656574203235303b6d653d537472656574203231393b686967687761793d7265736964656e7469616c3b6e616d653d537472656574203232303b6d653d537472656574203132393b68696768776179";
+
+    /// "highway=residential;name=Street 7;" compressed with `DICTIONARY`
+    const FRAME: &str = "28b52ffd230e587a5b224d00001868373b0100bc5142";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_dictionary_ids() {
+        let dictionary = ZstdDictionary::new(hex(DICTIONARY)).unwrap();
+        assert_eq!(dictionary.id(), 0x5b7a_580e);
+        assert_eq!(dictionary.as_bytes(), hex(DICTIONARY));
+
+        let mut without_id = hex(DICTIONARY);
+        without_id[4..8].fill(0);
+        assert!(ZstdDictionary::new(without_id).is_err());
+        assert!(ZstdDictionary::new(hex(FRAME)).is_err());
+        // Broken tables are only noticed by a build that can use them
+        assert_eq!(ZstdDictionary::new(hex(&DICTIONARY[..80])).is_err(), cfg!(feature = "zstd"));
+
+        assert_eq!(frame_dictionary_id(&hex(FRAME)), Some(0x5b7a_580e));
+        // A single segment frame without a dictionary, then a frame with a
+        // window descriptor and a 1 byte id
+        assert_eq!(frame_dictionary_id(b"\x28\xb5\x2f\xfd\x20\x03osm"), None);
+        assert_eq!(frame_dictionary_id(b"\x28\xb5\x2f\xfd\x01\x58\x07\x03osm"), Some(7));
+        assert_eq!(frame_dictionary_id(&hex(FRAME)[..6]), None);
+        assert_eq!(frame_dictionary_id(b"\x78\x9c"), None);
+    }
+
+    #[test]
+    fn test_dictionary_blobs_are_loaded_for_later_blobs() {
+        let stored = Blob::new_raw(BlobType::ZstdDictionary, Bytes::from(hex(DICTIONARY)), 0).unwrap();
+        let message = b"highway=residential;name=Street 7;";
+        let zstd = Blob {
+            data: BlobData::ZstdData { compressed: Bytes::from(hex(FRAME)), raw_size: message.len() as u32, dictionary: None },
+            ..Blob::new_raw(BlobType::OSMData, Bytes::new(), 300).unwrap()
+        };
+
+        // Without its dictionary the frame can't be read
+        let mut dictionaries = ZstdDictionaries::default();
+        let mut blob = zstd.clone();
+        dictionaries.observe(&mut blob).unwrap();
+        assert_eq!(blob, zstd);
+        assert!(blob.decompress().is_err());
+
+        dictionaries.observe(&mut stored.clone()).unwrap();
+        let mut blob = zstd.clone();
+        dictionaries.observe(&mut blob).unwrap();
+        assert!(matches!(&blob.data, BlobData::ZstdData { dictionary: Some(d), .. } if d.id() == 0x5b7a_580e));
+        assert_eq!(blob.decompress().ok(), cfg!(feature = "zstd").then(|| Bytes::from_static(message)));
+
+        let broken = Blob::new_raw(BlobType::ZstdDictionary, Bytes::from_static(b"osm"), 0).unwrap();
+        assert!(dictionaries.observe(&mut broken.clone()).is_err());
+    }
+}
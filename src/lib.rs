@@ -1,5 +1,21 @@
 mod blocks;
 mod io;
+
+pub mod analysis;
+pub mod export;
+pub mod extract;
+pub mod formats;
+pub mod geometry_cache;
+pub mod partition;
+pub mod replication;
+pub mod stitch;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(any(test, feature = "synthetic"))]
+pub mod synthetic;
+
 pub mod prelude;
 
 pub use prelude::*;
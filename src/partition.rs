@@ -0,0 +1,339 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+use crate::analysis::GridCell;
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::{BlobError, Result};
+use crate::io::reader::OsmElement;
+use crate::io::writer::{BlockBuffer, PbfWriter};
+
+/// Shard of an element by a stable hash of its id
+///
+/// The same id always lands in the same shard, across runs and platforms.
+/// `shards` must be non-zero.
+pub fn by_id(element: &OsmElement, shards: usize) -> usize {
+    (mix(element_id(element) as u64) % shards as u64) as usize
+}
+
+/// Web mercator cell of a node at `zoom`; `None` for elements without a location
+pub fn by_tile(element: &OsmElement, zoom: u8) -> Option<GridCell> {
+    match element {
+        OsmElement::Node(node) => Some(GridCell::containing(node.location, zoom)),
+        _ => None,
+    }
+}
+
+fn element_id(element: &OsmElement) -> i64 {
+    match element {
+        OsmElement::Node(node) => node.id.0,
+        OsmElement::Way(way) => way.id.0,
+        OsmElement::Relation(relation) => relation.id.0,
+        OsmElement::ChangeSet(changeset) => changeset.id.0,
+    }
+}
+
+/// SplitMix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Where `ShardedWriter` puts ways and relations whose members live in other shards
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpanPolicy {
+    /// Write the element to its primary shard only
+    #[default]
+    Primary,
+    /// Also write it to every shard holding one of its member nodes or ways,
+    /// so each shard is referentially complete for the ways it contains
+    Duplicate,
+}
+
+/// Writer routing elements to one of several PBF outputs
+///
+/// The partitioner picks the primary shard of each element. When it returns
+/// `None`, as `by_tile` does for ways and relations, the element goes to the
+/// shard of its first member that was already written. Elements are buffered
+/// per shard and written in blocks of at most `block_size` elements.
+///
+/// To place ways and relations the writer remembers the shard of every node and
+/// way written, about 12 bytes per element.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::{HeaderBlock, Reader};
+/// use osm_pbf::partition::{by_id, ShardedWriter, SpanPolicy};
+/// use std::fs::File;
+///
+/// let outputs = (0..4).map(|i| File::create(format!("shard-{i}.osm.pbf"))).collect::<Result<Vec<_>, _>>()?;
+/// let mut sharded = ShardedWriter::new(outputs, &HeaderBlock::default(), SpanPolicy::Duplicate, |e| Some(by_id(e, 4)))?;
+///
+/// let (_handle, batches) = Reader::new(File::open("map.osm.pbf")?)?.spawn_stream(Default::default());
+/// for batch in batches {
+///     for element in &batch.elements {
+///         sharded.write(element, &batch.strings)?;
+///     }
+/// }
+/// sharded.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ShardedWriter<W: Write, P> {
+    shards: Vec<Shard<W>>,
+    partitioner: P,
+    policy: SpanPolicy,
+    block_size: usize,
+    node_shards: HashMap<i64, u32>,
+    way_shards: HashMap<i64, u32>,
+}
+
+impl<W: Write, P: FnMut(&OsmElement) -> Option<usize>> ShardedWriter<W, P> {
+    /// Default maximum number of elements per written block
+    pub const DEFAULT_BLOCK_SIZE: usize = 8000;
+
+    /// Create a writer over one output per shard, writing `header` to each
+    pub fn new(outputs: Vec<W>, header: &HeaderBlock, policy: SpanPolicy, partitioner: P) -> Result<Self> {
+        if outputs.is_empty() || outputs.len() > u32::MAX as usize {
+            return Err(BlobError::InvalidFormat(format!("Unsupported shard count {}", outputs.len())));
+        }
+
+        let mut shards = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            let mut writer = PbfWriter::new(output);
+            writer.write_header(header)?;
+            shards.push(Shard { writer, block: BlockBuffer::default(), elements_written: 0 });
+        }
+
+        Ok(Self {
+            shards,
+            partitioner,
+            policy,
+            block_size: Self::DEFAULT_BLOCK_SIZE,
+            node_shards: HashMap::new(),
+            way_shards: HashMap::new(),
+        })
+    }
+
+    /// Set the maximum number of elements per written block
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Route one element; its tag, role and user indices refer to `strings`
+    pub fn write(&mut self, element: &OsmElement, strings: &StringTable) -> Result<()> {
+        let primary = match (self.partitioner)(element) {
+            Some(shard) if shard < self.shards.len() => Some(shard),
+            Some(shard) => {
+                return Err(BlobError::InvalidFormat(format!(
+                    "Partitioner returned shard {shard} of {}", self.shards.len()
+                )));
+            }
+            None => None,
+        };
+        let members = self.member_shards(element);
+        let primary = primary.or_else(|| members.first().copied()).unwrap_or(0);
+
+        match element {
+            OsmElement::Node(node) => {
+                self.node_shards.insert(node.id.0, primary as u32);
+            }
+            OsmElement::Way(way) => {
+                self.way_shards.insert(way.id.0, primary as u32);
+            }
+            _ => {}
+        }
+
+        let mut targets = BTreeSet::from([primary]);
+        if self.policy == SpanPolicy::Duplicate {
+            targets.extend(members);
+        }
+        for shard in targets {
+            self.push(shard, element, strings)?;
+        }
+        Ok(())
+    }
+
+    /// Elements written to each shard so far, buffered ones included
+    pub fn elements_written(&self) -> Vec<u64> {
+        self.shards.iter().map(|shard| shard.elements_written).collect()
+    }
+
+    /// Flush all buffered elements and return the outputs
+    pub fn finish(mut self) -> Result<Vec<W>> {
+        let mut outputs = Vec::with_capacity(self.shards.len());
+        for shard in &mut self.shards {
+            shard.flush_block()?;
+            shard.writer.flush()?;
+        }
+        for shard in self.shards {
+            outputs.push(shard.writer.into_inner());
+        }
+        Ok(outputs)
+    }
+
+    /// Shards of the already written members, in member order without repeats
+    fn member_shards(&self, element: &OsmElement) -> Vec<usize> {
+        let mut shards = Vec::new();
+        let mut add = |shard: Option<&u32>| {
+            if let Some(&shard) = shard
+                && !shards.contains(&(shard as usize))
+            {
+                shards.push(shard as usize);
+            }
+        };
+
+        match element {
+            OsmElement::Way(way) => {
+                for node_id in way.node_ids() {
+                    add(self.node_shards.get(&node_id));
+                }
+            }
+            OsmElement::Relation(relation) => {
+                for member in relation.members() {
+                    match member.member_type {
+                        MemberType::Node => add(self.node_shards.get(&member.id)),
+                        MemberType::Way => add(self.way_shards.get(&member.id)),
+                        MemberType::Relation => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        shards
+    }
+
+    fn push(&mut self, shard: usize, element: &OsmElement, strings: &StringTable) -> Result<()> {
+        let shard = &mut self.shards[shard];
+        shard.block.push(element, strings);
+        shard.elements_written += 1;
+        if shard.block.len() >= self.block_size {
+            shard.flush_block()?;
+        }
+        Ok(())
+    }
+}
+
+struct Shard<W: Write> {
+    writer: PbfWriter<W>,
+    block: BlockBuffer,
+    elements_written: u64,
+}
+
+impl<W: Write> Shard<W> {
+    fn flush_block(&mut self) -> Result<()> {
+        if self.block.len() > 0 {
+            let block = std::mem::take(&mut self.block).finish();
+            self.writer.write_primitive_block(&block)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::lat_lon::LatLon;
+    use crate::blocks::osm_id::WayId;
+    use crate::io::decode::{blob_payload, decode_primitive_block, read_frame};
+    use pretty_assertions::assert_eq;
+
+    fn node(id: i64) -> OsmElement {
+        OsmElement::Node(Node::new(id, LatLon::from_raw(0, 0)))
+    }
+
+    fn way(id: i64, refs: &[i64], strings: &mut StringTable) -> OsmElement {
+        let highway = strings.add_string("highway".to_string()) as u32;
+        let road = strings.add_string("residential".to_string()) as u32;
+        OsmElement::Way(Way {
+            id: WayId(id),
+            keys: vec![highway],
+            vals: vec![road],
+            info: None,
+            refs: crate::io::delta::delta_encode(refs).unwrap(),
+            lat: vec![],
+            lon: vec![],
+        })
+    }
+
+    fn blocks(data: &[u8]) -> Vec<PrimitiveBlock> {
+        let mut reader = data;
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        while let Some((blob, len)) = read_frame(&mut reader, offset).unwrap() {
+            offset += len;
+            if matches!(blob.blob_type(), crate::io::blob::BlobType::OSMData) {
+                blocks.push(decode_primitive_block(&blob_payload(&blob).unwrap()).unwrap());
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_by_id_is_stable_and_spread() {
+        let mut counts = [0; 4];
+        for id in 0..1000 {
+            let shard = by_id(&node(id), 4);
+            assert_eq!(shard, by_id(&node(id), 4));
+            counts[shard] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 150), "{counts:?}");
+    }
+
+    #[test]
+    fn test_by_tile() {
+        let element = OsmElement::Node(Node::new(1, LatLon::try_from_degrees(-10.0, -10.0).unwrap()));
+        assert_eq!(by_tile(&element, 1), Some(GridCell { x: 0, y: 1 }));
+        assert_eq!(by_tile(&OsmElement::Way(Way { id: WayId(1), keys: vec![], vals: vec![], info: None, refs: vec![], lat: vec![], lon: vec![] }), 1), None);
+    }
+
+    #[test]
+    fn test_span_policies() {
+        let mut strings = StringTable::new();
+        let elements = [node(1), node(2), way(10, &[1, 2], &mut strings)];
+        // Even node ids to shard 0, odd ones to shard 1; ways follow their nodes
+        let partitioner = |element: &OsmElement| match element {
+            OsmElement::Node(node) => Some((node.id.0 % 2) as usize),
+            _ => None,
+        };
+
+        let run = |policy| {
+            let mut sharded = ShardedWriter::new(vec![Vec::new(), Vec::new()], &HeaderBlock::default(), policy, partitioner).unwrap();
+            for element in &elements {
+                sharded.write(element, &strings).unwrap();
+            }
+            let written = sharded.elements_written();
+            (written, sharded.finish().unwrap())
+        };
+
+        let (written, _) = run(SpanPolicy::Primary);
+        assert_eq!(written, vec![1, 2]);
+
+        let (written, outputs) = run(SpanPolicy::Duplicate);
+        assert_eq!(written, vec![2, 2]);
+        let shard0 = blocks(&outputs[0]);
+        let way = &shard0[0].primitivegroup[1].ways[0];
+        assert_eq!(way.id, 10);
+        assert_eq!(shard0[0].stringtable.get_string_or_empty(way.keys[0] as usize), "highway");
+    }
+
+    #[test]
+    fn test_blocks_are_split() {
+        let mut sharded = ShardedWriter::new(vec![Vec::new()], &HeaderBlock::default(), SpanPolicy::Primary, |_: &OsmElement| Some(0))
+            .unwrap()
+            .with_block_size(2);
+        for id in 1..=5 {
+            sharded.write(&node(id), &StringTable::new()).unwrap();
+        }
+        let outputs = sharded.finish().unwrap();
+
+        let sizes: Vec<usize> = blocks(&outputs[0]).iter().map(|b| b.primitivegroup[0].nodes.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert!(ShardedWriter::new(vec![Vec::new()], &HeaderBlock::default(), SpanPolicy::Primary, |_: &OsmElement| Some(1))
+            .unwrap()
+            .write(&node(1), &StringTable::new())
+            .is_err());
+    }
+}
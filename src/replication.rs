@@ -0,0 +1,342 @@
+use url::Url;
+
+use crate::blocks::timestamp::TimestampMillis;
+use crate::io::blob::{BlobError, Result};
+use crate::io::features::ReplicationInfo;
+use crate::io::inflate::gzip_decompress;
+
+#[cfg(feature = "xml")]
+use crate::blocks::string_table::StringTable;
+#[cfg(feature = "xml")]
+use crate::io::change::ChangeAction;
+#[cfg(feature = "xml")]
+use crate::io::reader::{OsmElement, ProcessingStats};
+
+/// Largest decompressed diff `Client::next_diff` accepts
+pub const MAX_DIFF_SIZE: usize = 1 << 30;
+
+/// A replication sequence and the time its data runs up to, as in a state.txt file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReplicationState {
+    pub sequence_number: u64,
+    pub timestamp: TimestampMillis,
+}
+
+impl ReplicationState {
+    /// Parse a state.txt file, a Java properties file with `sequenceNumber`
+    /// and `timestamp` keys (the latter with escaped colons)
+    pub fn parse(text: &str) -> Result<Self> {
+        let (mut sequence_number, mut timestamp) = (None, None);
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().replace('\\', "");
+            match key.trim() {
+                "sequenceNumber" => sequence_number = value.parse().ok(),
+                "timestamp" => timestamp = TimestampMillis::parse_iso8601(&value),
+                _ => {}
+            }
+        }
+        match (sequence_number, timestamp) {
+            (Some(sequence_number), Some(timestamp)) => Ok(Self { sequence_number, timestamp }),
+            _ => Err(BlobError::Replication("state file without a valid sequenceNumber and timestamp".to_string())),
+        }
+    }
+}
+
+/// Path of a sequence's files below the base URL, without extension, e.g.
+/// `006/149/021` for 6149021
+pub fn sequence_path(sequence_number: u64) -> String {
+    format!("{:03}/{:03}/{:03}", sequence_number / 1_000_000, sequence_number / 1000 % 1000, sequence_number % 1000)
+}
+
+/// Fetches files from a replication server
+///
+/// Implemented for closures, e.g. to serve a mirror from disk or to fake a
+/// server in tests, and by `HttpTransport` with the `http` feature.
+pub trait Transport {
+    /// The body of `url`, or `None` if the server doesn't have it (yet)
+    fn get(&self, url: &Url) -> Result<Option<Vec<u8>>>;
+}
+
+impl<F: Fn(&Url) -> Result<Option<Vec<u8>>>> Transport for F {
+    fn get(&self, url: &Url) -> Result<Option<Vec<u8>>> {
+        self(url)
+    }
+}
+
+/// Blocking HTTP(S) downloads; 404 responses are reported as missing files
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "http")]
+impl Default for HttpTransport {
+    fn default() -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .user_agent(concat!("osm-pbf/", env!("CARGO_PKG_VERSION")))
+            .timeout(std::time::Duration::from_secs(60))
+            .build();
+        Self { agent }
+    }
+}
+
+#[cfg(feature = "http")]
+impl HttpTransport {
+    /// Use a configured agent, e.g. one with a proxy
+    pub fn with_agent(agent: ureq::Agent) -> Self {
+        Self { agent }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Transport for HttpTransport {
+    fn get(&self, url: &Url) -> Result<Option<Vec<u8>>> {
+        use std::io::Read;
+
+        match self.agent.get(url.as_str()).call() {
+            Ok(response) => {
+                let mut body = Vec::new();
+                response.into_reader().read_to_end(&mut body)?;
+                Ok(Some(body))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(BlobError::Replication(format!("{url}: {e}"))),
+        }
+    }
+}
+
+/// Keeps a local dataset current with the diffs of a replication server
+///
+/// The server publishes, below its base URL, a `state.txt` with its latest
+/// sequence number and, for each sequence, an OsmChange diff
+/// (`AAA/BBB/CCC.osc.gz`) and its own `AAA/BBB/CCC.state.txt`. The client
+/// starts after the last sequence applied to the local data, usually the one
+/// recorded in a PBF header (`from_header`), and fetches one diff after the
+/// other with `next_diff` until it has caught up.
+///
+/// # Examples
+/// ```rust,no_run
+/// # #[cfg(all(feature = "http", feature = "xml"))]
+/// # fn main() -> Result<(), osm_pbf::BlobError> {
+/// use osm_pbf::replication::Client;
+/// use osm_pbf::Reader;
+/// use std::fs::File;
+///
+/// let reader = Reader::new(File::open("planet.osm.pbf")?)?;
+/// let mut client = Client::from_header(reader.replication())?;
+/// while let Some(diff) = client.next_diff()? {
+///     diff.for_each_change(|action, element, _| {
+///         println!("{action:?} {element:?}");
+///         Ok(())
+///     })?;
+/// }
+/// # Ok(())
+/// # }
+/// # #[cfg(not(all(feature = "http", feature = "xml")))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client<T: Transport> {
+    base_url: Url,
+    transport: T,
+    /// Last sequence applied
+    sequence_number: u64,
+    /// Time the data runs up to, if known
+    timestamp: Option<TimestampMillis>,
+}
+
+#[cfg(feature = "http")]
+impl Client<HttpTransport> {
+    /// Start after `sequence_number` on the server at `base_url`, over HTTP(S)
+    pub fn new(base_url: &str, sequence_number: u64) -> Result<Self> {
+        Self::with_transport(base_url, sequence_number, HttpTransport::default())
+    }
+
+    /// Start after the sequence recorded in a file header, over HTTP(S)
+    pub fn from_header(replication: &ReplicationInfo) -> Result<Self> {
+        Self::from_header_with_transport(replication, HttpTransport::default())
+    }
+}
+
+impl<T: Transport> Client<T> {
+    pub fn with_transport(base_url: &str, sequence_number: u64, transport: T) -> Result<Self> {
+        // Relative paths resolve below the base only with a trailing slash
+        let base_url = match base_url.ends_with('/') {
+            true => Url::parse(base_url),
+            false => Url::parse(&format!("{base_url}/")),
+        }
+        .map_err(|e| BlobError::Replication(format!("invalid base URL {base_url:?}: {e}")))?;
+        Ok(Self { base_url, transport, sequence_number, timestamp: None })
+    }
+
+    /// Start after the sequence recorded in a file header; fails if the
+    /// header has no base URL or sequence number
+    pub fn from_header_with_transport(replication: &ReplicationInfo, transport: T) -> Result<Self> {
+        let base_url = replication.base_url.as_deref()
+            .ok_or_else(|| BlobError::Replication("header has no replication base URL".to_string()))?;
+        let sequence_number = replication.sequence_number
+            .ok_or_else(|| BlobError::Replication("header has no replication sequence number".to_string()))?;
+        let mut client = Self::with_transport(base_url, sequence_number.as_seq() as u64, transport)?;
+        client.timestamp = replication.timestamp.map(|timestamp| TimestampMillis::from_secs(timestamp.as_secs()));
+        Ok(client)
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Last sequence applied, i.e. the one `next_diff` fetches is one higher
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+
+    /// Time the applied data runs up to (`None` until known from the header or a diff)
+    pub fn timestamp(&self) -> Option<TimestampMillis> {
+        self.timestamp
+    }
+
+    /// URL of a sequence's state file, or of the server's latest state for `None`
+    pub fn state_url(&self, sequence_number: Option<u64>) -> Url {
+        match sequence_number {
+            Some(sequence_number) => self.url(&format!("{}.state.txt", sequence_path(sequence_number))),
+            None => self.url("state.txt"),
+        }
+    }
+
+    /// URL of a sequence's gzipped OsmChange file
+    pub fn diff_url(&self, sequence_number: u64) -> Url {
+        self.url(&format!("{}.osc.gz", sequence_path(sequence_number)))
+    }
+
+    fn url(&self, path: &str) -> Url {
+        self.base_url.join(path).expect("sequence paths are valid relative URLs")
+    }
+
+    /// The server's latest state
+    pub fn remote_state(&self) -> Result<ReplicationState> {
+        let url = self.state_url(None);
+        let text = self.transport.get(&url)?.ok_or_else(|| BlobError::Replication(format!("{url} not found")))?;
+        ReplicationState::parse(&String::from_utf8_lossy(&text))
+    }
+
+    /// Sequences published after the last one applied
+    pub fn pending(&self) -> Result<u64> {
+        Ok(self.remote_state()?.sequence_number.saturating_sub(self.sequence_number))
+    }
+
+    /// Download the diff following the last one applied and advance past it,
+    /// or return `None` if the server hasn't published it yet
+    ///
+    /// The client advances when the diff is returned, so a caller that fails
+    /// to apply it should start a new client at `diff.state.sequence_number - 1`.
+    pub fn next_diff(&mut self) -> Result<Option<Diff>> {
+        let sequence_number = self.sequence_number + 1;
+        let Some(state) = self.transport.get(&self.state_url(Some(sequence_number)))? else {
+            return Ok(None);
+        };
+        let state = ReplicationState::parse(&String::from_utf8_lossy(&state))?;
+        if state.sequence_number != sequence_number {
+            return Err(BlobError::Replication(format!(
+                "state file of sequence {sequence_number} is for sequence {}", state.sequence_number,
+            )));
+        }
+        // The state file is written after the diff, so the diff is there
+        let url = self.diff_url(sequence_number);
+        let compressed = self.transport.get(&url)?.ok_or_else(|| BlobError::Replication(format!("{url} not found")))?;
+        let osc = gzip_decompress(&compressed, MAX_DIFF_SIZE)?;
+
+        self.sequence_number = sequence_number;
+        self.timestamp = Some(state.timestamp);
+        Ok(Some(Diff { state, osc }))
+    }
+}
+
+/// One sequence's changes, as fetched by `Client::next_diff`
+#[derive(Debug, Clone)]
+pub struct Diff {
+    pub state: ReplicationState,
+    osc: Vec<u8>,
+}
+
+impl Diff {
+    /// The decompressed OsmChange XML
+    pub fn osc(&self) -> &[u8] {
+        &self.osc
+    }
+
+    /// Stream the changed elements, decoded as by `XmlReader::for_each_change`
+    #[cfg(feature = "xml")]
+    pub fn for_each_change<F>(&self, processor: F) -> Result<ProcessingStats>
+    where
+        F: FnMut(ChangeAction, OsmElement, &StringTable) -> Result<()>,
+    {
+        crate::formats::xml::XmlReader::new(&self.osc[..]).for_each_change(processor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn state(sequence_number: u64, time: &str) -> Vec<u8> {
+        format!("#Mon Mar 04 05:06:07 UTC 2024\nsequenceNumber={sequence_number}\ntimestamp={}\n", time.replace(':', "\\:")).into_bytes()
+    }
+
+    #[test]
+    fn test_paths_and_state() {
+        assert_eq!(sequence_path(6_149_021), "006/149/021");
+        assert_eq!(sequence_path(7), "000/000/007");
+        let parsed = ReplicationState::parse(&String::from_utf8(state(42, "2024-03-04T05:06:07Z")).unwrap()).unwrap();
+        assert_eq!(parsed, ReplicationState { sequence_number: 42, timestamp: TimestampMillis::from_secs(1_709_528_767) });
+        assert!(ReplicationState::parse("sequenceNumber=1\n").is_err());
+    }
+
+    #[test]
+    fn test_client_follows_sequences() {
+        let osc = br#"<osmChange version="0.6"><modify><node id="5" lat="1" lon="2" version="3"/></modify></osmChange>"#;
+        let files: HashMap<String, Vec<u8>> = [
+            ("state.txt".to_string(), state(1001, "2024-03-04T05:07:00Z")),
+            ("000/001/001.state.txt".to_string(), state(1001, "2024-03-04T05:07:00Z")),
+            ("000/001/001.osc.gz".to_string(), gzip(osc)),
+        ].into_iter().collect();
+        let transport = |url: &Url| Ok(url.path().strip_prefix("/replication/minute/").and_then(|path| files.get(path)).cloned());
+
+        let header = ReplicationInfo {
+            timestamp: crate::blocks::header_block::OsmosisReplicationTimestamp::new(1_709_528_760),
+            sequence_number: crate::blocks::header_block::OsmosisSequenceNumber::new(1000),
+            base_url: Some("https://planet.example.org/replication/minute".to_string()),
+        };
+        let mut client = Client::from_header_with_transport(&header, transport).unwrap();
+        assert_eq!(client.diff_url(1001).as_str(), "https://planet.example.org/replication/minute/000/001/001.osc.gz");
+        assert_eq!(client.pending().unwrap(), 1);
+
+        let diff = client.next_diff().unwrap().unwrap();
+        assert_eq!(diff.osc(), osc);
+        #[cfg(feature = "xml")]
+        diff.for_each_change(|action, element, _| {
+            assert_eq!(action, ChangeAction::Modify);
+            assert!(matches!(element, OsmElement::Node(node) if node.id == 5));
+            Ok(())
+        }).unwrap();
+        assert_eq!((client.sequence_number(), client.timestamp()), (1001, Some(TimestampMillis::from_secs(1_709_528_820))));
+        assert!(client.next_diff().unwrap().is_none());
+        assert_eq!(client.pending().unwrap(), 0);
+        assert!(Client::from_header_with_transport(&ReplicationInfo::default(), transport).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTable;
+use crate::io::blob::Result;
+use crate::io::indexed_reader::ElementFilter;
+use crate::io::reader::{OsmElement, Reader};
+use crate::io::writer::{BlockBuffer, PbfWriter};
+
+/// Maximum number of elements per block written by `merge_tiles`
+pub const BLOCK_SIZE: usize = 8000;
+
+/// What `merge_tiles` read and wrote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StitchStats {
+    pub inputs: u64,
+    /// Elements read from all inputs, copies included
+    pub elements_read: u64,
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    /// Copies of an element already read from another tile, dropped
+    pub duplicates: u64,
+    /// Ways whose node lists differed between tiles and were joined
+    pub stitched_ways: u64,
+    /// Relations whose member lists differed between tiles and were joined
+    pub stitched_relations: u64,
+}
+
+/// Merge per-tile extracts back into one seamless file, the inverse of
+/// `partition::ShardedWriter`
+///
+/// Elements appearing in several tiles are written once. Copies of a way or
+/// relation that differ, e.g. because a splitter clipped them at the tile
+/// edge, are joined: node lists overlapping at their ends are chained, and
+/// members missing from the first copy are appended. When copies carry
+/// different versions the newest one wins instead. The output is sorted by
+/// type and id, in blocks of `BLOCK_SIZE` elements.
+///
+/// All elements are held in memory until written, so this is meant for
+/// extracts rather than planet-sized inputs.
+///
+/// # Examples
+/// ```rust,no_run
+/// use osm_pbf::stitch::merge_tiles;
+/// use osm_pbf::{HeaderBlock, Reader};
+/// use std::fs::File;
+///
+/// let tiles = (0..4)
+///     .map(|i| Reader::new(File::open(format!("shard-{i}.osm.pbf"))?))
+///     .collect::<Result<Vec<_>, osm_pbf::BlobError>>()?;
+/// let stats = merge_tiles(tiles, &HeaderBlock::default(), File::create("merged.osm.pbf")?)?;
+/// println!("{} duplicates dropped, {} ways stitched", stats.duplicates, stats.stitched_ways);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+pub fn merge_tiles<R: Read + Seek, W: Write>(inputs: Vec<Reader<R>>, header: &HeaderBlock, output: W) -> Result<StitchStats> {
+    let mut stats = StitchStats::default();
+    let mut merged = Merged::default();
+
+    for mut input in inputs {
+        stats.inputs += 1;
+        // Elements of a block share its string table
+        let mut shared: Option<Arc<StringTable>> = None;
+        input.for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| {
+            let strings = match &shared {
+                Some(shared) if shared.s == strings.s => shared.clone(),
+                _ => shared.insert(Arc::new(strings.clone())).clone(),
+            };
+            stats.elements_read += 1;
+            merged.add(element, strings, &mut stats);
+            Ok(())
+        })?;
+    }
+
+    let mut writer = PbfWriter::new(output);
+    writer.write_header(header)?;
+    let mut block = BlockBuffer::default();
+    let entries = merged.nodes.into_values().chain(merged.ways.into_values()).chain(merged.relations.into_values());
+    for Entry { element, strings } in entries {
+        match &element {
+            OsmElement::Node(_) => stats.nodes += 1,
+            OsmElement::Way(_) => stats.ways += 1,
+            _ => stats.relations += 1,
+        }
+        block.push(&element, &strings);
+        if block.len() >= BLOCK_SIZE {
+            writer.write_primitive_block(&std::mem::take(&mut block).finish())?;
+        }
+    }
+    if block.len() > 0 {
+        writer.write_primitive_block(&block.finish())?;
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+struct Entry {
+    element: OsmElement,
+    strings: Arc<StringTable>,
+}
+
+#[derive(Default)]
+struct Merged {
+    nodes: BTreeMap<i64, Entry>,
+    ways: BTreeMap<i64, Entry>,
+    relations: BTreeMap<i64, Entry>,
+}
+
+impl Merged {
+    fn add(&mut self, element: OsmElement, strings: Arc<StringTable>, stats: &mut StitchStats) {
+        let (map, id) = match &element {
+            OsmElement::Node(node) => (&mut self.nodes, node.id.0),
+            OsmElement::Way(way) => (&mut self.ways, way.id.0),
+            OsmElement::Relation(relation) => (&mut self.relations, relation.id.0),
+            OsmElement::ChangeSet(_) => return,
+        };
+        let Some(existing) = map.get_mut(&id) else {
+            map.insert(id, Entry { element, strings });
+            return;
+        };
+
+        stats.duplicates += 1;
+        let (old, new) = (version(&existing.element), version(&element));
+        if old != new {
+            if new > old {
+                *existing = Entry { element, strings };
+            }
+            return;
+        }
+        match (&mut existing.element, &element) {
+            (OsmElement::Way(way), OsmElement::Way(other)) => {
+                let mut refs = undelta(&way.refs);
+                if join(&mut refs, &undelta(&other.refs)) {
+                    way.refs = delta(&refs);
+                    // Locations on ways no longer line up with the joined refs
+                    way.lat.clear();
+                    way.lon.clear();
+                    stats.stitched_ways += 1;
+                }
+            }
+            (OsmElement::Relation(relation), OsmElement::Relation(other)) => {
+                let mut members = resolve_members(relation, &existing.strings);
+                if join(&mut members, &resolve_members(other, &strings)) {
+                    let table = Arc::make_mut(&mut existing.strings);
+                    relation.types = members.iter().map(|(member_type, _, _)| *member_type).collect();
+                    relation.memids = delta(&members.iter().map(|(_, id, _)| *id).collect::<Vec<_>>());
+                    relation.roles_sid = members.iter().map(|(_, _, role)| intern(table, role) as i32).collect();
+                    stats.stitched_relations += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn version(element: &OsmElement) -> Option<i32> {
+    let info = match element {
+        OsmElement::Node(node) => &node.info,
+        OsmElement::Way(way) => &way.info,
+        OsmElement::Relation(relation) => &relation.info,
+        OsmElement::ChangeSet(changeset) => &changeset.info,
+    };
+    info.as_ref().map(|info| info.version)
+}
+
+fn resolve_members(relation: &Relation, strings: &StringTable) -> Vec<(MemberType, i64, String)> {
+    relation.members_with_roles(strings)
+        .map(|(member, role)| (member.member_type, member.id, role.to_string()))
+        .collect()
+}
+
+fn intern(strings: &mut StringTable, s: &str) -> usize {
+    strings.s.iter().position(|existing| existing == s).unwrap_or_else(|| strings.add_string(s.to_string()))
+}
+
+/// Join `other` into `sequence`; returns whether `sequence` changed
+///
+/// A copy contained in the other is dropped, sequences whose ends overlap are
+/// chained on the longest overlap, and otherwise the missing items of `other`
+/// are appended.
+fn join<T: PartialEq + Clone>(sequence: &mut Vec<T>, other: &[T]) -> bool {
+    if other.is_empty() || contains(sequence, other) {
+        return false;
+    }
+    if contains(other, sequence) {
+        *sequence = other.to_vec();
+        return true;
+    }
+
+    let after = overlap(sequence, other);
+    let before = overlap(other, sequence);
+    if after >= before && after > 0 {
+        sequence.extend_from_slice(&other[after..]);
+    } else if before > 0 {
+        let mut joined = other.to_vec();
+        joined.extend_from_slice(&sequence[before..]);
+        *sequence = joined;
+    } else {
+        let missing: Vec<T> = other.iter().filter(|item| !sequence.contains(item)).cloned().collect();
+        sequence.extend(missing);
+    }
+    true
+}
+
+/// Whether `part` appears as a contiguous run in `whole`
+fn contains<T: PartialEq>(whole: &[T], part: &[T]) -> bool {
+    part.is_empty() || whole.windows(part.len()).any(|window| window == part)
+}
+
+/// Length of the longest suffix of `first` that is a prefix of `second`
+fn overlap<T: PartialEq>(first: &[T], second: &[T]) -> usize {
+    (1..=first.len().min(second.len())).rev().find(|len| first[first.len() - len..] == second[..*len]).unwrap_or(0)
+}
+
+fn undelta(deltas: &[i64]) -> Vec<i64> {
+    let mut value = 0i64;
+    deltas.iter().map(|d| {
+        value = value.wrapping_add(*d);
+        value
+    }).collect()
+}
+
+fn delta(values: &[i64]) -> Vec<i64> {
+    let mut previous = 0i64;
+    values.iter().map(|v| {
+        let d = v.wrapping_sub(previous);
+        previous = *v;
+        d
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::osm_id::WayId;
+    use crate::partition::{by_id, ShardedWriter, SpanPolicy};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_join() {
+        let joined = |a: &[i64], b: &[i64]| {
+            let mut a = a.to_vec();
+            let changed = join(&mut a, b);
+            (a, changed)
+        };
+        assert_eq!(joined(&[1, 2, 3], &[3, 4, 5]), (vec![1, 2, 3, 4, 5], true));
+        assert_eq!(joined(&[3, 4, 5], &[1, 2, 3]), (vec![1, 2, 3, 4, 5], true));
+        assert_eq!(joined(&[1, 2, 3, 4], &[2, 3]), (vec![1, 2, 3, 4], false));
+        assert_eq!(joined(&[2, 3], &[1, 2, 3, 4]), (vec![1, 2, 3, 4], true));
+        assert_eq!(joined(&[1, 2], &[7, 8]), (vec![1, 2, 7, 8], true));
+    }
+
+    /// Elements with string indices resolved, as block packing changes them
+    fn elements(data: Vec<u8>) -> Vec<String> {
+        let mut out = Vec::new();
+        Reader::new(Cursor::new(data)).unwrap()
+            .for_each_filtered_with_strings(&ElementFilter::all(), |mut element, strings| {
+                let (keys, vals) = match &mut element {
+                    OsmElement::Node(node) => (&mut node.keys, &mut node.vals),
+                    OsmElement::Way(way) => (&mut way.keys, &mut way.vals),
+                    OsmElement::Relation(relation) => (&mut relation.keys, &mut relation.vals),
+                    OsmElement::ChangeSet(changeset) => (&mut changeset.keys, &mut changeset.vals),
+                };
+                let tags: Vec<_> = keys.drain(..).zip(vals.drain(..))
+                    .map(|(k, v)| (strings.get_string_or_empty(k as usize), strings.get_string_or_empty(v as usize)))
+                    .collect();
+                let roles: Vec<_> = match &mut element {
+                    OsmElement::Relation(relation) => relation.roles_sid.drain(..).map(|r| strings.get_string_or_empty(r as usize)).collect(),
+                    _ => Vec::new(),
+                };
+                out.push(format!("{element:?} {tags:?} {roles:?}"));
+                Ok(())
+            })
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_merge_tiles_inverts_partitioning() {
+        let mut data = Vec::new();
+        let planet = crate::synthetic::PlanetBuilder::new(12).grid_size(8).relation_count(3).write_to(&mut data).unwrap();
+
+        let header = HeaderBlock::default();
+        let mut sharded = ShardedWriter::new(vec![Vec::new(); 3], &header, SpanPolicy::Duplicate, |e| Some(by_id(e, 3))).unwrap();
+        Reader::new(Cursor::new(data.clone())).unwrap()
+            .for_each_filtered_with_strings(&ElementFilter::all(), |element, strings| sharded.write(&element, strings))
+            .unwrap();
+        let tiles = sharded.finish().unwrap();
+        let copies: usize = tiles.iter().map(|tile| elements(tile.clone()).len()).sum();
+
+        let readers = tiles.into_iter().map(|tile| Reader::new(Cursor::new(tile)).unwrap()).collect();
+        let mut merged = Vec::new();
+        let stats = merge_tiles(readers, &header, &mut merged).unwrap();
+        assert_eq!((stats.nodes, stats.ways, stats.relations), (planet.nodes, planet.ways, planet.relations));
+        assert_eq!(stats.elements_read as usize, copies);
+        assert!(stats.duplicates > 0);
+        assert_eq!((stats.stitched_ways, stats.stitched_relations), (0, 0));
+
+        // Same elements as the source, which is sorted the same way
+        assert_eq!(elements(merged), elements(data));
+    }
+
+    #[test]
+    fn test_clipped_ways_are_stitched() {
+        let tile = |refs: Vec<i64>| {
+            let mut block = PrimitiveBlock::default();
+            block.primitivegroup.push(PrimitiveGroup {
+                ways: vec![Way { id: WayId(5), keys: vec![], vals: vec![], info: None, refs: delta(&refs), lat: vec![], lon: vec![] }],
+                ..Default::default()
+            });
+            let mut writer = PbfWriter::new(Vec::new());
+            writer.write_header(&HeaderBlock::default()).unwrap();
+            writer.write_primitive_block(&block).unwrap();
+            Reader::new(Cursor::new(writer.into_inner())).unwrap()
+        };
+
+        let mut merged = Vec::new();
+        let stats = merge_tiles(vec![tile(vec![3, 4, 5]), tile(vec![1, 2, 3])], &HeaderBlock::default(), &mut merged).unwrap();
+        assert_eq!((stats.ways, stats.duplicates, stats.stitched_ways), (1, 1, 1));
+        let mut reader = Reader::new(Cursor::new(merged)).unwrap();
+        reader.for_each_filtered(&ElementFilter::ways_only(false), |element| {
+            let OsmElement::Way(way) = element else { panic!("expected a way") };
+            assert_eq!(undelta(&way.refs), vec![1, 2, 3, 4, 5]);
+            Ok(())
+        }).unwrap();
+    }
+}
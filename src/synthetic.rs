@@ -0,0 +1,319 @@
+use std::io::Write;
+use crate::blocks::header_block::HeaderBlock;
+use crate::blocks::osm_id::{RelationId, WayId};
+use crate::blocks::primitives::prelude::*;
+use crate::blocks::string_table::StringTableBuilder;
+use crate::io::blob::Result;
+use crate::io::delta::delta_encode;
+use crate::io::writer::{BlobCompression, PbfWriter};
+
+/// Highway classes assigned to generated roads
+const HIGHWAY_CLASSES: &[&str] = &["residential", "tertiary", "secondary", "primary", "service"];
+
+/// Amenities scattered over generated nodes
+const AMENITIES: &[&str] = &["cafe", "school", "pharmacy", "bench", "parking"];
+
+/// Deterministic generator of synthetic but valid PBF data
+///
+/// Lays out a `grid_size` x `grid_size` grid of nodes, one road per grid row
+/// and column, and a few bus route relations over those roads. The same seed
+/// and parameters always produce byte-identical output.
+///
+/// # Examples
+/// ```rust
+/// use osm_pbf::synthetic::PlanetBuilder;
+///
+/// let mut data = Vec::new();
+/// let stats = PlanetBuilder::new(42).grid_size(100).write_to(&mut data)?;
+/// assert_eq!(stats.nodes, 10_000);
+/// assert_eq!(stats.ways, 200);
+/// # Ok::<(), osm_pbf::BlobError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PlanetBuilder {
+    seed: u64,
+    grid_size: u32,
+    block_size: usize,
+    relation_count: usize,
+    compression: BlobCompression,
+}
+
+/// Summary of what `PlanetBuilder::write_to` produced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlanetStats {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    pub blobs: u64,
+    pub bytes: u64,
+}
+
+impl PlanetBuilder {
+    /// Grid spacing in granularity units (100 nanodegrees), about 110 m
+    const SPACING: i64 = 10_000;
+    /// Maximum random displacement of a grid node, in granularity units
+    const JITTER: i64 = 2_000;
+    /// Grid origin (lat, lon) in granularity units
+    const ORIGIN: (i64, i64) = (475_000_000, 85_000_000);
+
+    /// Create a builder with the given seed and default size (100 x 100 grid)
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            grid_size: 100,
+            block_size: 8_000,
+            relation_count: 4,
+            compression: BlobCompression::default(),
+        }
+    }
+
+    /// Set the number of nodes per grid side; the file holds `n * n` nodes and `2 * n` ways
+    pub fn grid_size(mut self, n: u32) -> Self {
+        self.grid_size = n.max(2);
+        self
+    }
+
+    /// Set the maximum number of elements per PrimitiveBlock
+    pub fn block_size(mut self, n: usize) -> Self {
+        self.block_size = n.max(1);
+        self
+    }
+
+    /// Set the number of route relations
+    pub fn relation_count(mut self, n: usize) -> Self {
+        self.relation_count = n;
+        self
+    }
+
+    /// Set how blobs are stored, zlib by default as with `PbfWriter`
+    pub fn compression(mut self, compression: BlobCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Generate the file into `writer`
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<PlanetStats> {
+        let mut out = PbfWriter::new(writer).with_compression(self.compression);
+        let mut stats = PlanetStats::default();
+        let mut rng = SplitMix64::new(self.seed);
+
+        out.write_header(&HeaderBlock {
+            required_features: vec!["OsmSchema-V0.6".into(), "DenseNodes".into()],
+            writing_program: "osm-pbf synthetic",
+            ..Default::default()
+        })?;
+
+        let n = self.grid_size as i64;
+        let node_count = (n * n) as usize;
+
+        for start in (0..node_count).step_by(self.block_size) {
+            let end = (start + self.block_size).min(node_count);
+            let mut block = BlockBuilder::new();
+            let mut dense = DenseNodes::default();
+            let (mut last_id, mut last_lat, mut last_lon) = (0, 0, 0);
+
+            for index in start..end {
+                let (row, col) = (index as i64 / n, index as i64 % n);
+                let id = index as i64 + 1;
+                let lat = Self::ORIGIN.0 + row * Self::SPACING + rng.jitter(Self::JITTER);
+                let lon = Self::ORIGIN.1 + col * Self::SPACING + rng.jitter(Self::JITTER);
+
+                dense.id.push(id - last_id);
+                dense.lat.push(lat - last_lat);
+                dense.lon.push(lon - last_lon);
+                (last_id, last_lat, last_lon) = (id, lat, lon);
+
+                if rng.below(16) == 0 {
+                    let amenity = AMENITIES[rng.below(AMENITIES.len() as u64) as usize];
+                    dense.keys_vals.push(block.string("amenity") as i32);
+                    dense.keys_vals.push(block.string(amenity) as i32);
+                }
+                dense.keys_vals.push(0);
+            }
+
+            stats.nodes += (end - start) as u64;
+            block.group.dense = Some(dense);
+            out.write_primitive_block(&block.finish())?;
+        }
+
+        // Ways 1..=n are the rows, n+1..=2n the columns
+        let way_refs = |way: i64| -> Vec<i64> {
+            if way <= n {
+                (0..n).map(|col| (way - 1) * n + col + 1).collect()
+            } else {
+                (0..n).map(|row| row * n + (way - n - 1) + 1).collect()
+            }
+        };
+
+        for start in (1..=2 * n).step_by(self.block_size) {
+            let end = (start + self.block_size as i64).min(2 * n + 1);
+            let mut block = BlockBuilder::new();
+
+            for id in start..end {
+                let highway = HIGHWAY_CLASSES[rng.below(HIGHWAY_CLASSES.len() as u64) as usize];
+                let keys = vec![block.string("highway"), block.string("name")];
+                let vals = vec![block.string(highway), block.string(&format!("Street {id}"))];
+                block.group.ways.push(Way {
+                    id: WayId(id),
+                    keys,
+                    vals,
+                    info: None,
+                    refs: delta_encode(&way_refs(id))?,
+                    lat: vec![],
+                    lon: vec![],
+                });
+            }
+
+            stats.ways += (end - start) as u64;
+            out.write_primitive_block(&block.finish())?;
+        }
+
+        if self.relation_count > 0 {
+            let mut block = BlockBuilder::new();
+
+            for index in 0..self.relation_count {
+                let row_way = 1 + rng.below(n as u64) as i64;
+                let col_way = n + 1 + rng.below(n as u64) as i64;
+                let stop = way_refs(row_way)[rng.below(n as u64) as usize];
+
+                let keys = vec![block.string("type"), block.string("route"), block.string("ref")];
+                let vals = vec![block.string("route"), block.string("bus"), block.string(&(index + 1).to_string())];
+                let (empty, platform) = (0, block.string("platform") as i32);
+
+                block.group.relations.push(Relation {
+                    id: RelationId(index as i64 + 1),
+                    keys,
+                    vals,
+                    info: None,
+                    roles_sid: vec![platform, empty, empty],
+                    memids: delta_encode(&[stop, row_way, col_way])?,
+                    types: vec![MemberType::Node, MemberType::Way, MemberType::Way],
+                });
+            }
+
+            stats.relations += self.relation_count as u64;
+            out.write_primitive_block(&block.finish())?;
+        }
+
+        out.flush()?;
+        stats.blobs = out.blobs_written();
+        stats.bytes = out.bytes_written();
+        Ok(stats)
+    }
+}
+
+/// Single-group PrimitiveBlock under construction, with string interning
+struct BlockBuilder {
+    strings: StringTableBuilder,
+    group: PrimitiveGroup,
+}
+
+impl BlockBuilder {
+    fn new() -> Self {
+        Self {
+            strings: StringTableBuilder::new(),
+            group: PrimitiveGroup::default(),
+        }
+    }
+
+    fn string(&mut self, s: &str) -> u32 {
+        self.strings.get_or_insert(s)
+    }
+
+    fn finish(self) -> PrimitiveBlock {
+        PrimitiveBlock {
+            stringtable: self.strings.build(),
+            primitivegroup: vec![self.group],
+            ..Default::default()
+        }
+    }
+}
+
+/// SplitMix64 PRNG: tiny, fast and stable across platforms and releases
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    fn jitter(&mut self, max: i64) -> i64 {
+        self.below(2 * max as u64 + 1) as i64 - max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_deterministic_output() {
+        let a = PlanetBuilder::new(7).grid_size(20).write_to(Vec::new()).unwrap();
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        PlanetBuilder::new(7).grid_size(20).write_to(&mut first).unwrap();
+        PlanetBuilder::new(7).grid_size(20).write_to(&mut second).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(a.bytes, first.len() as u64);
+
+        let mut other = Vec::new();
+        PlanetBuilder::new(8).grid_size(20).write_to(&mut other).unwrap();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_stats_and_blob_count() {
+        let stats = PlanetBuilder::new(1)
+            .grid_size(50)
+            .block_size(1_000)
+            .relation_count(3)
+            .write_to(Vec::new())
+            .unwrap();
+
+        assert_eq!(stats.nodes, 2_500);
+        assert_eq!(stats.ways, 100);
+        assert_eq!(stats.relations, 3);
+        // header + 3 node blocks + 1 way block + 1 relation block
+        assert_eq!(stats.blobs, 6);
+    }
+
+    #[test]
+    fn test_frames_are_well_formed() {
+        let mut data = Vec::new();
+        let stats = PlanetBuilder::new(3).grid_size(30).block_size(200).write_to(&mut data).unwrap();
+
+        let mut offset = 0;
+        let mut frames = 0;
+        while offset < data.len() {
+            let header_len = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let header = &data[offset + 4..offset + 4 + header_len];
+
+            // BlobHeader is `type` (field 1) followed by `datasize` (field 3, varint)
+            let datasize_at = 2 + header[1] as usize;
+            assert_eq!(header[datasize_at], 0x18);
+            let mut datasize = 0usize;
+            for (shift, byte) in header[datasize_at + 1..].iter().enumerate() {
+                datasize |= ((byte & 0x7f) as usize) << (7 * shift);
+            }
+
+            offset += 4 + header_len + datasize;
+            frames += 1;
+        }
+
+        assert_eq!(offset, data.len());
+        assert_eq!(frames, stats.blobs);
+    }
+}